@@ -6,22 +6,49 @@
 //! # Usage
 //!
 //! Call the `init` function with a reference to the boot services table.
-//! Failure to do so before calling a memory allocating function will panic.
+//! Until then, allocations fail, and the allocation error handler is called.
 //!
-//! Call the `exit_boot_services` function before exiting UEFI boot services.
-//! Failure to do so will turn subsequent allocation into undefined behaviour.
+//! Call the `exit_boot_services` function when exiting UEFI boot services,
+//! which `SystemTable::exit_boot_services` does when it succeeds. Failure to
+//! do so will turn subsequent allocation into undefined behaviour.
+//! Afterwards, allocations fail too. `is_initialized` and `is_armed` tell
+//! whether the allocator can be used.
+//!
+//! The memory is allocated as `MemoryType::LOADER_DATA`, unless another type
+//! is given to `init_with_memory_type`. `with_memory_type` changes the type
+//! of the allocations of a closure, for example to allocate the structures an
+//! OS kernel must find in the memory map with a custom type.
+//!
+//! Small allocations are made from the pool, and only those aligned to more
+//! than 8 bytes, which is all the pool guarantees, use a few more bytes to
+//! be aligned. Allocations larger than two pages, or aligned to pages, are
+//! made with whole pages. These blocks are resized in place when
+//! possible, and zeroed allocations are cleared with `BootServices::set_mem`.
+//!
+//! With the `alloc-stats` feature, the allocator counts the allocations and
+//! their sizes, which `stats` returns, and can log the large allocations.
 
 use core::alloc::{GlobalAlloc, Layout};
-use core::ptr::{self, NonNull};
+use core::ptr;
+#[cfg(feature = "alloc-stats")]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
 
+use crate::data_types::{PhysicalAddress, PAGE_SIZE};
 use crate::prelude::*;
-use crate::table::boot::{BootServices, MemoryType};
+use crate::table::boot::{AllocateType, BootServices, MemoryType};
 
-/// Reference to the boot services table, used to call the pool memory allocation functions.
+/// Reference to the boot services table, used to call the memory allocation functions.
 ///
-/// The inner pointer is only safe to dereference if UEFI boot services have not been
-/// exited by the host application yet.
-static mut BOOT_SERVICES: Option<NonNull<BootServices>> = None;
+/// The pointer is null before `init`, and after `exit_boot_services`.
+static BOOT_SERVICES: AtomicPtr<BootServices> = AtomicPtr::new(ptr::null_mut());
+
+/// Set once `exit_boot_services` is called, after which the allocator stays
+/// disarmed.
+static EXITED: AtomicBool = AtomicBool::new(false);
+
+/// Memory type of the allocations.
+static MEMORY_TYPE: AtomicU32 = AtomicU32::new(MemoryType::LOADER_DATA.0);
 
 /// Initializes the allocator.
 ///
@@ -30,73 +57,370 @@ static mut BOOT_SERVICES: Option<NonNull<BootServices>> = None;
 /// This function is unsafe because you _must_ make sure that exit_boot_services
 /// will be called when UEFI boot services will be exited.
 pub unsafe fn init(boot_services: &BootServices) {
-    BOOT_SERVICES = NonNull::new(boot_services as *const _ as *mut _);
+    init_with_memory_type(boot_services, MemoryType::LOADER_DATA);
+}
+
+/// Initializes the allocator, allocating memory of type `mem_ty`.
+///
+/// # Safety
+///
+/// As with `init`, you must make sure that exit_boot_services will be called
+/// when UEFI boot services will be exited.
+pub unsafe fn init_with_memory_type(boot_services: &BootServices, mem_ty: MemoryType) {
+    MEMORY_TYPE.store(mem_ty.0, Ordering::SeqCst);
+    if !EXITED.load(Ordering::SeqCst) {
+        BOOT_SERVICES.store(boot_services as *const _ as *mut _, Ordering::SeqCst);
+    }
 }
 
-/// Access the boot services
-fn boot_services() -> NonNull<BootServices> {
-    unsafe { BOOT_SERVICES.expect("Boot services are unavailable or have been exited") }
+/// Returns whether `init` was called, even if boot services were exited
+/// since then.
+pub fn is_initialized() -> bool {
+    is_armed() || EXITED.load(Ordering::SeqCst)
+}
+
+/// Returns whether the allocator can allocate memory, which is the case
+/// after `init` and until `exit_boot_services`.
+pub fn is_armed() -> bool {
+    !BOOT_SERVICES.load(Ordering::SeqCst).is_null()
+}
+
+/// Returns the memory type of the allocations.
+pub fn memory_type() -> MemoryType {
+    MemoryType(MEMORY_TYPE.load(Ordering::SeqCst))
+}
+
+/// Calls `f`, allocating the memory it allocates with type `mem_ty`, and
+/// restores the previous type.
+///
+/// The memory can be freed at any time, after `f` returns too. As the type
+/// is global, the allocations of event notification functions interrupting
+/// `f` have this type too.
+pub fn with_memory_type<R>(mem_ty: MemoryType, f: impl FnOnce() -> R) -> R {
+    let previous = MEMORY_TYPE.swap(mem_ty.0, Ordering::SeqCst);
+    let result = f();
+    MEMORY_TYPE.store(previous, Ordering::SeqCst);
+    result
+}
+
+/// Access the boot services, if the allocator is armed
+fn boot_services() -> Option<&'static BootServices> {
+    unsafe { BOOT_SERVICES.load(Ordering::SeqCst).as_ref() }
 }
 
 /// Notify the allocator library that boot services are not safe to call anymore
 ///
-/// You must arrange for this function to be called on exit from UEFI boot services
+/// You must arrange for this function to be called on exit from UEFI boot
+/// services. `SystemTable::exit_boot_services` calls it when it succeeds.
+///
+/// The allocator is disarmed for good: allocations fail, which calls the
+/// allocation error handler, and deallocations do nothing, as the memory
+/// now belongs to the OS.
 pub fn exit_boot_services() {
-    unsafe {
-        BOOT_SERVICES = None;
+    EXITED.store(true, Ordering::SeqCst);
+    BOOT_SERVICES.store(ptr::null_mut(), Ordering::SeqCst);
+}
+
+/// Allocations larger than this are made with `allocate_pages`, as large
+/// allocations fragment the pool.
+const MAX_POOL_SIZE: usize = 2 * PAGE_SIZE as usize;
+
+/// Alignment of the memory returned by `allocate_pool`.
+const POOL_ALIGN: usize = 8;
+
+/// How the memory of an allocation is obtained.
+///
+/// It only depends on the layout, which is passed to `dealloc` too, so the
+/// memory is always freed by the function matching the one allocating it.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Block {
+    /// Directly from the pool, without any overhead.
+    Pool,
+    /// From the pool, with room to align the block, and the address of the
+    /// pool allocation stored right before the block.
+    AlignedPool,
+    /// With whole pages.
+    Pages,
+}
+
+impl Block {
+    fn of(layout: &Layout) -> Self {
+        if layout.align() <= POOL_ALIGN && layout.size() <= MAX_POOL_SIZE {
+            Block::Pool
+        } else if layout.align() < PAGE_SIZE as usize
+            && layout.size() + layout.align() <= MAX_POOL_SIZE
+        {
+            Block::AlignedPool
+        } else {
+            Block::Pages
+        }
     }
 }
 
-/// Allocator which uses the UEFI pool allocation functions.
+/// Number of pages holding `size` bytes, which is at least 1.
+fn page_count(size: usize) -> usize {
+    let page_size = PAGE_SIZE as usize;
+    size.saturating_sub(1) / page_size + 1
+}
+
+/// Allocator which uses the UEFI pool allocation functions for small
+/// allocations, and the page allocation functions for large or page-aligned
+/// allocations.
 ///
 /// Only valid for as long as the UEFI boot services are available.
 pub struct Allocator;
 
-#[allow(clippy::cast_ptr_alignment)]
 unsafe impl GlobalAlloc for Allocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let mem_ty = MemoryType::LOADER_DATA;
-        let size = layout.size();
-        let align = layout.align();
-
-        if align > 8 {
-            // allocate more space for alignment
-            let ptr = if let Ok(ptr) = boot_services()
-                .as_ref()
-                .allocate_pool(mem_ty, size + align)
+        let ptr = allocate(&layout);
+        #[cfg(feature = "alloc-stats")]
+        {
+            if !ptr.is_null() {
+                record_alloc(&layout, ptr);
+            }
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        #[cfg(feature = "alloc-stats")]
+        record_dealloc(&layout);
+        free(ptr, &layout);
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.alloc(layout);
+        // Neither the pool nor the pages are cleared by the firmware, but its
+        // `set_mem` is faster than a loop writing bytes.
+        if let (false, Some(bt)) = (ptr.is_null(), boot_services()) {
+            bt.set_mem(ptr, layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        if resize_in_place(ptr, &layout, &new_layout) {
+            #[cfg(feature = "alloc-stats")]
+            record_resize(&layout, new_size);
+            return ptr;
+        }
+
+        let new_ptr = self.alloc(new_layout);
+        if let (false, Some(bt)) = (new_ptr.is_null(), boot_services()) {
+            bt.memmove(new_ptr, ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+/// Resizes a block allocated with pages without moving it, if the new size
+/// is also allocated with pages, and returns whether it succeeded.
+///
+/// Blocks shrink by freeing their last pages, and grow by allocating the
+/// pages which follow them, if they are free. These pages have the current
+/// memory type, which may differ from the type of the block.
+unsafe fn resize_in_place(ptr: *mut u8, layout: &Layout, new_layout: &Layout) -> bool {
+    let bt = match boot_services() {
+        Some(bt) => bt,
+        None => return false,
+    };
+    if Block::of(layout) != Block::Pages || Block::of(new_layout) != Block::Pages {
+        return false;
+    }
+
+    let pages = page_count(layout.size());
+    let new_pages = page_count(new_layout.size());
+    let page_address =
+        |index: usize| PhysicalAddress::new((ptr as usize + index * PAGE_SIZE as usize) as u64);
+    if new_pages <= pages {
+        if new_pages < pages {
+            // Failing to free the last pages only leaks them.
+            let _ = bt.free_pages(page_address(new_pages), pages - new_pages);
+        }
+        true
+    } else {
+        bt.allocate_pages(
+            AllocateType::Address(page_address(pages)),
+            memory_type(),
+            new_pages - pages,
+        )
+        .warning_as_error()
+        .is_ok()
+    }
+}
+
+/// Allocates memory for `layout` from the pool or with pages.
+unsafe fn allocate(layout: &Layout) -> *mut u8 {
+    let bt = match boot_services() {
+        Some(bt) => bt,
+        None => return ptr::null_mut(),
+    };
+    let mem_ty = memory_type();
+
+    match Block::of(layout) {
+        Block::Pool => {
+            return bt
+                .allocate_pool(mem_ty, layout.size())
+                .warning_as_error()
+                .unwrap_or(ptr::null_mut());
+        }
+        Block::AlignedPool => {
+            let start = match bt
+                .allocate_pool(mem_ty, layout.size() + layout.align())
                 .warning_as_error()
             {
-                ptr
-            } else {
-                return ptr::null_mut();
+                Ok(start) => start,
+                Err(_) => return ptr::null_mut(),
             };
-            // calculate align offset
-            let mut offset = ptr.align_offset(align);
-            if offset == 0 {
-                offset = align;
-            }
-            let return_ptr = ptr.add(offset);
-            // store allocated pointer before the struct
-            (return_ptr as *mut *mut u8).sub(1).write(ptr);
-            return_ptr
-        } else {
-            boot_services()
-                .as_ref()
-                .allocate_pool(mem_ty, size)
-                .warning_as_error()
-                .unwrap_or(ptr::null_mut())
+            // The pool is aligned to 8 bytes, so there is always room for
+            // the address of the allocation before the aligned block.
+            let offset = match start.align_offset(layout.align()) {
+                0 => layout.align(),
+                offset => offset,
+            };
+            let aligned = start.add(offset);
+            aligned.cast::<*mut u8>().sub(1).write(start);
+            return aligned;
         }
+        Block::Pages => {}
     }
 
-    unsafe fn dealloc(&self, mut ptr: *mut u8, layout: Layout) {
-        if layout.align() > 8 {
-            ptr = (ptr as *const *mut u8).sub(1).read();
-        }
-        boot_services()
-            .as_ref()
-            .free_pool(ptr)
-            .warning_as_error()
-            .unwrap();
+    // Pages are aligned to the page size. Larger alignments are obtained
+    // by allocating more pages, and freeing those around the aligned
+    // block, so that `free` only needs the layout.
+    let pages = page_count(layout.size());
+    let extra = page_count(layout.align()) - 1;
+    let start = match bt
+        .allocate_pages(AllocateType::AnyPages, mem_ty, pages + extra)
+        .warning_as_error()
+    {
+        Ok(start) => start.as_u64() as usize,
+        Err(_) => return ptr::null_mut(),
+    };
+    let aligned = (start + layout.align() - 1) & !(layout.align() - 1);
+    let before = (aligned - start) / PAGE_SIZE as usize;
+    let after = extra - before;
+    // Failing to free the extra pages only leaks them.
+    if before > 0 {
+        let _ = bt.free_pages(PhysicalAddress::new(start as u64), before);
+    }
+    if after > 0 {
+        let end = aligned + pages * PAGE_SIZE as usize;
+        let _ = bt.free_pages(PhysicalAddress::new(end as u64), after);
+    }
+    aligned as *mut u8
+}
+
+/// Frees memory allocated by `allocate` for `layout`.
+unsafe fn free(ptr: *mut u8, layout: &Layout) {
+    let bt = match boot_services() {
+        Some(bt) => bt,
+        None => return,
+    };
+    match Block::of(layout) {
+        Block::Pool => bt.free_pool(ptr),
+        Block::AlignedPool => bt.free_pool(ptr.cast::<*mut u8>().sub(1).read()),
+        Block::Pages => bt.free_pages(PhysicalAddress::new(ptr as u64), page_count(layout.size())),
+    }
+    .warning_as_error()
+    .unwrap();
+}
+
+/// Statistics about the allocations of the global allocator
+///
+/// The sizes are the ones requested by the layouts, whether the memory comes
+/// from the pool or from pages.
+#[cfg(feature = "alloc-stats")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AllocStats {
+    /// Number of allocations which weren't freed yet.
+    pub live_allocations: usize,
+    /// Number of bytes of the allocations which weren't freed yet.
+    pub live_bytes: usize,
+    /// Highest value of `live_bytes`, since the allocator was initialized or
+    /// `reset_peak` was called.
+    pub peak_bytes: usize,
+    /// Number of successful allocations since the allocator was initialized.
+    pub total_allocations: usize,
+}
+
+#[cfg(feature = "alloc-stats")]
+static LIVE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "alloc-stats")]
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "alloc-stats")]
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "alloc-stats")]
+static TOTAL_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocations of at least this size are logged.
+#[cfg(feature = "alloc-stats")]
+static LOG_THRESHOLD: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Set while an allocation is logged, so that the allocations of the logger
+/// are not logged in turn.
+#[cfg(feature = "alloc-stats")]
+static LOGGING: AtomicBool = AtomicBool::new(false);
+
+/// Returns the statistics of the allocator.
+///
+/// The counters are read one after the other, so they may be slightly
+/// inconsistent if an allocation happens in the meantime, for example in an
+/// event notification function.
+#[cfg(feature = "alloc-stats")]
+pub fn stats() -> AllocStats {
+    AllocStats {
+        live_allocations: LIVE_ALLOCATIONS.load(Ordering::SeqCst),
+        live_bytes: LIVE_BYTES.load(Ordering::SeqCst),
+        peak_bytes: PEAK_BYTES.load(Ordering::SeqCst),
+        total_allocations: TOTAL_ALLOCATIONS.load(Ordering::SeqCst),
+    }
+}
+
+/// Resets the peak number of bytes to the current number of live bytes.
+#[cfg(feature = "alloc-stats")]
+pub fn reset_peak() {
+    PEAK_BYTES.store(LIVE_BYTES.load(Ordering::SeqCst), Ordering::SeqCst);
+}
+
+/// Logs every allocation of at least `threshold` bytes, at the debug level,
+/// or stops logging them if `threshold` is `None`.
+#[cfg(feature = "alloc-stats")]
+pub fn log_allocations_above(threshold: Option<usize>) {
+    LOG_THRESHOLD.store(threshold.unwrap_or(usize::MAX), Ordering::SeqCst);
+}
+
+#[cfg(feature = "alloc-stats")]
+fn record_alloc(layout: &Layout, ptr: *mut u8) {
+    LIVE_ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    TOTAL_ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    let live_bytes = LIVE_BYTES.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+    PEAK_BYTES.fetch_max(live_bytes, Ordering::SeqCst);
+
+    if layout.size() >= LOG_THRESHOLD.load(Ordering::SeqCst)
+        && !LOGGING.swap(true, Ordering::SeqCst)
+    {
+        log::debug!("Allocated {:?} at {:p}", layout, ptr);
+        LOGGING.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+fn record_dealloc(layout: &Layout) {
+    LIVE_ALLOCATIONS.fetch_sub(1, Ordering::SeqCst);
+    LIVE_BYTES.fetch_sub(layout.size(), Ordering::SeqCst);
+}
+
+#[cfg(feature = "alloc-stats")]
+fn record_resize(layout: &Layout, new_size: usize) {
+    if new_size >= layout.size() {
+        let growth = new_size - layout.size();
+        let live_bytes = LIVE_BYTES.fetch_add(growth, Ordering::SeqCst) + growth;
+        PEAK_BYTES.fetch_max(live_bytes, Ordering::SeqCst);
+    } else {
+        LIVE_BYTES.fetch_sub(layout.size() - new_size, Ordering::SeqCst);
     }
 }
 
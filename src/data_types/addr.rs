@@ -0,0 +1,214 @@
+//! Memory address types
+//!
+//! UEFI deals with both physical and virtual addresses, which are all 64-bit
+//! integers. These newtypes prevent mixing them up, and provide the page
+//! arithmetic which is commonly needed when handling memory maps.
+
+use core::fmt;
+
+/// Size of a page, as used by the UEFI memory services.
+pub const PAGE_SIZE: u64 = 4096;
+
+macro_rules! address_type {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        #[repr(transparent)]
+        pub struct $name(u64);
+
+        impl $name {
+            /// Creates an address from its integer value.
+            pub const fn new(addr: u64) -> Self {
+                $name(addr)
+            }
+
+            /// Returns the integer value of this address.
+            pub const fn as_u64(self) -> u64 {
+                self.0
+            }
+
+            /// Returns this address as a raw pointer.
+            pub fn as_ptr<T>(self) -> *const T {
+                self.0 as usize as *const T
+            }
+
+            /// Returns this address as a raw mutable pointer.
+            pub fn as_mut_ptr<T>(self) -> *mut T {
+                self.0 as usize as *mut T
+            }
+
+            /// Adds a number of bytes to this address,
+            /// returning `None` on overflow.
+            pub fn checked_add(self, bytes: u64) -> Option<Self> {
+                self.0.checked_add(bytes).map($name)
+            }
+
+            /// Subtracts a number of bytes from this address,
+            /// returning `None` on underflow.
+            pub fn checked_sub(self, bytes: u64) -> Option<Self> {
+                self.0.checked_sub(bytes).map($name)
+            }
+
+            /// Adds a number of pages to this address,
+            /// returning `None` on overflow.
+            pub fn checked_add_pages(self, pages: u64) -> Option<Self> {
+                pages
+                    .checked_mul(PAGE_SIZE)
+                    .and_then(|bytes| self.checked_add(bytes))
+            }
+
+            /// Checks if this address is aligned to a page boundary.
+            pub fn is_page_aligned(self) -> bool {
+                self.0 % PAGE_SIZE == 0
+            }
+
+            /// Rounds this address down to the previous page boundary.
+            pub fn page_align_down(self) -> Self {
+                $name(self.0 & !(PAGE_SIZE - 1))
+            }
+
+            /// Rounds this address up to the next page boundary,
+            /// returning `None` on overflow.
+            pub fn page_align_up(self) -> Option<Self> {
+                self.0
+                    .checked_add(PAGE_SIZE - 1)
+                    .map(|addr| $name(addr & !(PAGE_SIZE - 1)))
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, concat!(stringify!($name), "({:#x})"), self.0)
+            }
+        }
+
+        impl fmt::LowerHex for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::LowerHex::fmt(&self.0, f)
+            }
+        }
+    };
+}
+
+address_type! {
+    /// A physical memory address.
+    ///
+    /// While boot services are active, memory is identity-mapped, so physical
+    /// addresses can be converted to pointers with `as_ptr` / `as_mut_ptr`.
+    ///
+    /// ```
+    /// use uefi::data_types::PhysicalAddress;
+    ///
+    /// let addr = PhysicalAddress::new(0x1234);
+    /// assert!(!addr.is_page_aligned());
+    /// assert_eq!(addr.page_align_down(), PhysicalAddress::new(0x1000));
+    /// assert_eq!(addr.page_align_up(), Some(PhysicalAddress::new(0x2000)));
+    /// assert_eq!(addr.checked_add_pages(2), Some(PhysicalAddress::new(0x3234)));
+    ///
+    /// let top = PhysicalAddress::new(u64::MAX - 0xfff);
+    /// assert!(top.is_page_aligned());
+    /// assert_eq!(top.page_align_up(), Some(top));
+    /// assert_eq!(top.checked_add(0x1000), None);
+    /// assert_eq!(top.checked_add_pages(1), None);
+    /// assert_eq!(PhysicalAddress::new(u64::MAX).page_align_up(), None);
+    /// assert_eq!(PhysicalAddress::new(0).checked_add_pages(u64::MAX), None);
+    /// assert_eq!(PhysicalAddress::new(0).checked_sub(1), None);
+    /// ```
+    PhysicalAddress
+}
+
+address_type! {
+    /// A virtual memory address.
+    ///
+    /// These are mostly used to describe the virtual address map which is
+    /// set up by the OS when calling `RuntimeServices::set_virtual_address_map`.
+    VirtualAddress
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::format;
+
+    #[test]
+    fn checked_add_overflow() {
+        let addr = PhysicalAddress::new(u64::MAX - 1);
+        assert_eq!(addr.checked_add(1), Some(PhysicalAddress::new(u64::MAX)));
+        assert_eq!(addr.checked_add(2), None);
+        assert_eq!(addr.checked_add(u64::MAX), None);
+        assert_eq!(
+            PhysicalAddress::new(0).checked_add(u64::MAX),
+            Some(PhysicalAddress::new(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn checked_sub_underflow() {
+        let addr = VirtualAddress::new(0x1000);
+        assert_eq!(addr.checked_sub(0x1000), Some(VirtualAddress::new(0)));
+        assert_eq!(addr.checked_sub(0x1001), None);
+        assert_eq!(
+            VirtualAddress::new(0).checked_sub(0),
+            Some(VirtualAddress::new(0))
+        );
+    }
+
+    #[test]
+    fn checked_add_pages_overflow() {
+        let last_page = PhysicalAddress::new(u64::MAX - PAGE_SIZE + 1);
+        assert_eq!(
+            PhysicalAddress::new(0).checked_add_pages(u64::MAX / PAGE_SIZE),
+            Some(last_page)
+        );
+        assert_eq!(last_page.checked_add_pages(0), Some(last_page));
+        assert_eq!(last_page.checked_add_pages(1), None);
+        // The page count overflows before the addition does.
+        assert_eq!(
+            PhysicalAddress::new(0).checked_add_pages(u64::MAX / PAGE_SIZE + 1),
+            None
+        );
+        assert_eq!(
+            PhysicalAddress::new(PAGE_SIZE).checked_add_pages(u64::MAX / PAGE_SIZE),
+            None
+        );
+    }
+
+    #[test]
+    fn page_alignment() {
+        for &addr in &[0, PAGE_SIZE, 0x1234_5000, u64::MAX - PAGE_SIZE + 1] {
+            let addr = PhysicalAddress::new(addr);
+            assert!(addr.is_page_aligned());
+            assert_eq!(addr.page_align_down(), addr);
+            assert_eq!(addr.page_align_up(), Some(addr));
+        }
+        for &addr in &[1, PAGE_SIZE - 1, PAGE_SIZE + 1, 0x1234_5678] {
+            let addr = PhysicalAddress::new(addr);
+            assert!(!addr.is_page_aligned());
+            let down = addr.page_align_down();
+            let up = addr.page_align_up().unwrap();
+            assert!(down.is_page_aligned() && up.is_page_aligned());
+            assert!(down < addr && addr < up);
+            assert_eq!(up.as_u64() - down.as_u64(), PAGE_SIZE);
+        }
+    }
+
+    #[test]
+    fn page_align_up_overflow() {
+        let top = u64::MAX - PAGE_SIZE + 1;
+        for &addr in &[top + 1, u64::MAX - 1, u64::MAX] {
+            let addr = VirtualAddress::new(addr);
+            assert_eq!(addr.page_align_up(), None);
+            assert_eq!(addr.page_align_down(), VirtualAddress::new(top));
+        }
+    }
+
+    #[test]
+    fn fmt() {
+        let addr = PhysicalAddress::new(0xfee0_0000);
+        assert_eq!(format!("{:?}", addr), "PhysicalAddress(0xfee00000)");
+        assert_eq!(format!("{:x}", addr), "fee00000");
+        assert_eq!(format!("{:#x}", VirtualAddress::new(0x10)), "0x10");
+    }
+}
@@ -11,10 +11,33 @@ use core::fmt;
 pub struct CharConversionError;
 
 /// A Latin-1 character
+///
+/// Every Latin-1 character maps to the Unicode code point of the same value,
+/// including the upper half of the range (`0x80..=0xFF`).
+///
+/// ```
+/// use core::convert::TryFrom;
+/// use uefi::Char8;
+///
+/// assert_eq!(Char8::from(0xe9), 'é');
+/// assert_eq!(char::from(Char8::from(0xff)), 'ÿ');
+/// assert_eq!(Char8::try_from('\u{80}').map(u8::from).unwrap(), 0x80);
+/// assert!(Char8::try_from('\u{100}').is_err());
+/// ```
 #[derive(Clone, Copy, Default, Eq, PartialEq, PartialOrd, Ord)]
 #[repr(transparent)]
 pub struct Char8(u8);
 
+impl Char8 {
+    /// The null character
+    pub const NUL: Char8 = Char8(0);
+
+    /// Checks if this character is within the ASCII range
+    pub fn is_ascii(self) -> bool {
+        self.0.is_ascii()
+    }
+}
+
 impl TryFrom<char> for Char8 {
     type Error = CharConversionError;
 
@@ -46,6 +69,12 @@ impl From<Char8> for u8 {
     }
 }
 
+impl PartialEq<char> for Char8 {
+    fn eq(&self, other: &char) -> bool {
+        u32::from(self.0) == u32::from(*other)
+    }
+}
+
 impl fmt::Debug for Char8 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         <char as fmt::Debug>::fmt(&From::from(self.0), f)
@@ -59,13 +88,72 @@ impl fmt::Display for Char8 {
 }
 
 /// Latin-1 version of the NUL character
-pub const NUL_8: Char8 = Char8(0);
+pub const NUL_8: Char8 = Char8::NUL;
 
 /// An UCS-2 code point
+///
+/// UCS-2 can represent every character of the Basic Multilingual Plane, except
+/// for the surrogate code points (`0xD800..=0xDFFF`), which only make sense in
+/// UTF-16 and are therefore rejected.
+///
+/// ```
+/// use core::convert::TryFrom;
+/// use uefi::Char16;
+///
+/// assert_eq!(Char16::try_from('A').unwrap(), 'A');
+/// assert_eq!(Char16::try_from(0xd7ff).unwrap(), '\u{d7ff}');
+/// assert!(Char16::try_from(0xd800).is_err());
+/// assert!(Char16::try_from(0xdfff).is_err());
+/// assert_eq!(Char16::try_from(0xe000).unwrap(), '\u{e000}');
+/// assert_eq!(Char16::try_from('\u{ffff}').unwrap(), '\u{ffff}');
+/// assert!(Char16::try_from('\u{10000}').is_err());
+/// assert!(Char16::try_from('a').unwrap() < Char16::try_from('b').unwrap());
+/// ```
 #[derive(Clone, Copy, Default, Eq, PartialEq, PartialOrd, Ord)]
 #[repr(transparent)]
 pub struct Char16(u16);
 
+impl Char16 {
+    /// The null character
+    pub const NUL: Char16 = Char16(0);
+
+    /// The carriage return character (`'\r'`)
+    pub const CARRIAGE_RETURN: Char16 = Char16(0x0d);
+
+    /// The line feed character (`'\n'`)
+    pub const LINE_FEED: Char16 = Char16(0x0a);
+
+    /// Creates an UCS-2 character from a Unicode code point without checks
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that `value` is a valid UCS-2 code point,
+    /// i.e. that it is not a surrogate.
+    pub const unsafe fn from_u16_unchecked(value: u16) -> Self {
+        Char16(value)
+    }
+
+    /// Checks if this character is within the ASCII range
+    pub fn is_ascii(self) -> bool {
+        self.0 < 0x80
+    }
+
+    /// Checks if this character is an ASCII alphanumeric character
+    pub fn is_ascii_alphanumeric(self) -> bool {
+        self.is_ascii() && (self.0 as u8).is_ascii_alphanumeric()
+    }
+
+    /// Checks if this character is an ASCII whitespace character
+    pub fn is_ascii_whitespace(self) -> bool {
+        self.is_ascii() && (self.0 as u8).is_ascii_whitespace()
+    }
+
+    /// Checks if this character is an ASCII control character
+    pub fn is_ascii_control(self) -> bool {
+        self.is_ascii() && (self.0 as u8).is_ascii_control()
+    }
+}
+
 impl TryFrom<char> for Char16 {
     type Error = CharConversionError;
 
@@ -105,6 +193,12 @@ impl From<Char16> for u16 {
     }
 }
 
+impl PartialEq<char> for Char16 {
+    fn eq(&self, other: &char) -> bool {
+        u32::from(self.0) == u32::from(*other)
+    }
+}
+
 impl fmt::Debug for Char16 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Ok(c) = u32::from(self.0).try_into() {
@@ -126,4 +220,84 @@ impl fmt::Display for Char16 {
 }
 
 /// UCS-2 version of the NUL character
-pub const NUL_16: Char16 = Char16(0);
+pub const NUL_16: Char16 = Char16::NUL;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn char16_rejects_surrogates() {
+        for code in 0xd800..=0xdfff {
+            assert!(Char16::try_from(code).is_err(), "{:#x}", code);
+        }
+        assert_eq!(Char16::try_from(0xd7ff).map(u16::from).unwrap(), 0xd7ff);
+        assert_eq!(Char16::try_from(0xe000).map(u16::from).unwrap(), 0xe000);
+    }
+
+    #[test]
+    fn char16_bmp_boundary() {
+        assert_eq!(Char16::try_from('\u{ffff}').map(u16::from).unwrap(), 0xffff);
+        assert_eq!(
+            Char16::try_from(0xffff).map(char::from).unwrap(),
+            '\u{ffff}'
+        );
+        assert!(Char16::try_from('\u{10000}').is_err());
+        assert!(Char16::try_from(char::MAX).is_err());
+    }
+
+    #[test]
+    fn char16_round_trip() {
+        for &c in &['\0', 'A', 'é', '€', '中', '\u{fffd}'] {
+            let char16 = Char16::try_from(c).unwrap();
+            assert_eq!(char::from(char16), c);
+            assert_eq!(Char16::try_from(u16::from(char16)).unwrap(), char16);
+        }
+    }
+
+    #[test]
+    fn char16_eq_char() {
+        assert_eq!(Char16::try_from('a').unwrap(), 'a');
+        assert_eq!(Char16::try_from(0xe9).unwrap(), 'é');
+        assert_ne!(Char16::try_from('a').unwrap(), 'A');
+        assert_eq!(Char16::NUL, '\0');
+        assert_eq!(Char16::CARRIAGE_RETURN, '\r');
+        assert_eq!(Char16::LINE_FEED, '\n');
+        assert_eq!(NUL_16, Char16::NUL);
+    }
+
+    #[test]
+    fn char16_ord() {
+        let a = Char16::try_from('a').unwrap();
+        let b = Char16::try_from('b').unwrap();
+        let e_acute = Char16::try_from('é').unwrap();
+        assert!(a < b && b < e_acute);
+        assert_eq!(a.cmp(&a), core::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn char16_predicates() {
+        let c = |c| Char16::try_from(c).unwrap();
+        assert!(c('A').is_ascii() && c('\u{7f}').is_ascii());
+        assert!(!c('\u{80}').is_ascii() && !c('é').is_ascii());
+        assert!(c('z').is_ascii_alphanumeric() && c('7').is_ascii_alphanumeric());
+        assert!(!c('-').is_ascii_alphanumeric() && !c('é').is_ascii_alphanumeric());
+        assert!(c(' ').is_ascii_whitespace() && c('\t').is_ascii_whitespace());
+        assert!(!c('\u{a0}').is_ascii_whitespace());
+        assert!(c('\0').is_ascii_control() && c('\u{1b}').is_ascii_control());
+        assert!(!c('a').is_ascii_control() && !c('\u{85}').is_ascii_control());
+    }
+
+    #[test]
+    fn char16_fmt() {
+        let e_acute = Char16::try_from('é').unwrap();
+        assert_eq!(e_acute.to_string(), "é");
+        assert_eq!(std::format!("{:?}", e_acute), "'é'");
+        let surrogate = unsafe { Char16::from_u16_unchecked(0xd800) };
+        assert_eq!(surrogate.to_string(), "\u{fffd}");
+        assert_eq!(std::format!("{:?}", surrogate), "Char16(55296)");
+    }
+}
@@ -62,20 +62,26 @@ macro_rules! newtype_enum {
                 $(#[$variant_attrs])*
                 pub const $variant: $type = $type($value);
             )*
+
+            /// Name of the variant, or `None` if the value is unknown
+            fn variant_name(self) -> Option<&'static str> {
+                match self {
+                    $(
+                        $type::$variant => Some(stringify!($variant)),
+                    )*
+                    _ => None,
+                }
+            }
         }
 
         impl core::fmt::Debug for $type {
             fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-                match *self {
+                match self.variant_name() {
                     // Display variants by their name, like Rust enums do
-                    $(
-                        $type::$variant => write!(f, stringify!($variant)),
-                    )*
+                    Some(name) => f.write_str(name),
 
                     // Display unknown variants in tuple struct format
-                    $type(unknown) => {
-                        write!(f, "{}({})", stringify!($type), unknown)
-                    }
+                    None => write!(f, "{}({})", stringify!($type), self.0),
                 }
             }
         }
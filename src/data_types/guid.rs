@@ -1,4 +1,4 @@
-use core::fmt;
+use core::{fmt, str::FromStr};
 
 /// A globally unique identifier
 ///
@@ -6,9 +6,10 @@ use core::fmt;
 /// mostly like variant 2 UUIDs as specified by RFC 4122, but differ from them
 /// in that the first 3 fields are little endian instead of big endian.
 ///
-/// The `Display` formatter prints GUIDs in the canonical format defined by
-/// RFC 4122, which is also used by UEFI.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// The `Display` and `Debug` formatters print GUIDs in the canonical format
+/// defined by RFC 4122, which is also used by UEFI. The same format is
+/// accepted by `Guid::parse` and `Guid::from_str`.
+#[derive(Copy, Clone, Eq, PartialEq)]
 #[repr(C)]
 pub struct Guid {
     /// The low field of the timestamp.
@@ -55,6 +56,137 @@ impl Guid {
             ],
         }
     }
+
+    /// Creates a GUID from its in-memory representation, in which the first
+    /// three fields are little endian.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Guid {
+            a: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            b: u16::from_le_bytes([bytes[4], bytes[5]]),
+            c: u16::from_le_bytes([bytes[6], bytes[7]]),
+            d: [
+                bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14],
+                bytes[15],
+            ],
+        }
+    }
+
+    /// Returns the in-memory representation of this GUID, in which the first
+    /// three fields are little endian.
+    pub const fn to_bytes(self) -> [u8; 16] {
+        let a = self.a.to_le_bytes();
+        let b = self.b.to_le_bytes();
+        let c = self.c.to_le_bytes();
+        let d = self.d;
+        [
+            a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5],
+            d[6], d[7],
+        ]
+    }
+
+    /// Parses a GUID from its canonical textual representation.
+    ///
+    /// The expected format is `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`, where
+    /// each `x` is a hexadecimal digit. Both lowercase and uppercase digits are
+    /// accepted, and the whole string may optionally be enclosed in braces.
+    ///
+    /// ```
+    /// use uefi::Guid;
+    ///
+    /// let text = "8868e871-e4f1-11d3-bc22-0080c73c8881";
+    /// let guid = Guid::parse(text).unwrap();
+    /// assert_eq!(
+    ///     guid,
+    ///     Guid::from_values(0x8868e871, 0xe4f1, 0x11d3, 0xbc22, [0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81])
+    /// );
+    /// assert_eq!(Guid::parse("{8868E871-E4F1-11D3-BC22-0080C73C8881}"), Ok(guid));
+    /// assert!(Guid::parse("8868e871-e4f1-11d3-bc22-0080c73c888").is_err());
+    /// ```
+    pub const fn parse(s: &str) -> Result<Self, GuidParseError> {
+        let bytes = s.as_bytes();
+
+        let (start, len) = match bytes.len() {
+            36 => (0, 36),
+            38 if bytes[0] == b'{' && bytes[37] == b'}' => (1, 36),
+            _ => return Err(GuidParseError::InvalidLength),
+        };
+
+        // Check that the hyphens are at the right places
+        let mut i = 0;
+        while i < len {
+            let is_hyphen = bytes[start + i] == b'-';
+            let must_be_hyphen = matches!(i, 8 | 13 | 18 | 23);
+            if is_hyphen != must_be_hyphen {
+                return Err(if must_be_hyphen {
+                    GuidParseError::InvalidSeparator(start + i)
+                } else {
+                    GuidParseError::InvalidDigit(start + i)
+                });
+            }
+            i += 1;
+        }
+
+        macro_rules! field {
+            ($offset:expr, $digits:expr) => {
+                match parse_hex(bytes, start + $offset, $digits) {
+                    Ok(value) => value,
+                    Err(err) => return Err(err),
+                }
+            };
+        }
+
+        let time_low = field!(0, 8) as u32;
+        let time_mid = field!(9, 4) as u16;
+        let time_high_and_version = field!(14, 4) as u16;
+        let clock_seq_and_variant = field!(19, 4) as u16;
+        let node = field!(24, 12);
+
+        Ok(Guid::from_values(
+            time_low,
+            time_mid,
+            time_high_and_version,
+            clock_seq_and_variant,
+            [
+                (node >> 40) as u8,
+                (node >> 32) as u8,
+                (node >> 24) as u8,
+                (node >> 16) as u8,
+                (node >> 8) as u8,
+                node as u8,
+            ],
+        ))
+    }
+}
+
+/// Parses `digits` hexadecimal digits of `bytes`, starting at `offset`.
+const fn parse_hex(bytes: &[u8], offset: usize, digits: usize) -> Result<u64, GuidParseError> {
+    let mut value = 0;
+    let mut i = offset;
+    while i < offset + digits {
+        let digit = match bytes[i] {
+            b'0'..=b'9' => bytes[i] - b'0',
+            b'a'..=b'f' => bytes[i] - b'a' + 10,
+            b'A'..=b'F' => bytes[i] - b'A' + 10,
+            _ => return Err(GuidParseError::InvalidDigit(i)),
+        };
+        value = (value << 4) | digit as u64;
+        i += 1;
+    }
+    Ok(value)
+}
+
+impl FromStr for Guid {
+    type Err = GuidParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Guid::parse(s)
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, fmt)
+    }
 }
 
 impl fmt::Display for Guid {
@@ -81,6 +213,86 @@ impl fmt::Display for Guid {
     }
 }
 
+/// Creates a `Guid` from its canonical textual representation at compile time.
+///
+/// The GUID string is parsed during constant evaluation, using the same rules
+/// as `Guid::parse`, so malformed GUIDs are reported as build errors. The
+/// resulting expression can be used in `const` contexts.
+///
+/// Reporting errors relies on panicking in constants, so crates using this
+/// macro must enable the `const_panic` feature.
+///
+/// ```
+/// #![feature(const_panic)]
+/// use uefi::{guid, Guid};
+///
+/// const ACPI2_GUID: Guid = guid!("8868e871-e4f1-11d3-bc22-0080c73c8881");
+/// assert_eq!(ACPI2_GUID.to_string(), "8868e871-e4f1-11d3-bc22-0080c73c8881");
+/// ```
+///
+/// Invalid GUIDs do not compile, whether a digit is missing, a hyphen is
+/// misplaced or a character is not a hexadecimal digit:
+///
+/// ```compile_fail
+/// #![feature(const_panic)]
+/// use uefi::{guid, Guid};
+///
+/// const ACPI2_GUID: Guid = guid!("8868e871-e4f1-11d3-bc22-0080c73c888");
+/// assert_eq!(ACPI2_GUID.to_string(), "8868e871-e4f1-11d3-bc22-0080c73c8881");
+/// ```
+///
+/// ```compile_fail
+/// #![feature(const_panic)]
+/// use uefi::{guid, Guid};
+///
+/// const ACPI2_GUID: Guid = guid!("8868e871-e4f1-11d3-bc220-080c73c8881");
+/// assert_eq!(ACPI2_GUID.to_string(), "8868e871-e4f1-11d3-bc22-0080c73c8881");
+/// ```
+///
+/// ```compile_fail
+/// #![feature(const_panic)]
+/// use uefi::{guid, Guid};
+///
+/// const ACPI2_GUID: Guid = guid!("8868e871-e4f1-11d3-bc22-0080c73c888g");
+/// assert_eq!(ACPI2_GUID.to_string(), "8868e871-e4f1-11d3-bc22-0080c73c8881");
+/// ```
+#[macro_export]
+macro_rules! guid {
+    ($s:literal) => {{
+        const GUID: $crate::Guid = match $crate::Guid::parse($s) {
+            Ok(guid) => guid,
+            Err(_) => panic!(concat!("invalid GUID: ", $s)),
+        };
+        GUID
+    }};
+}
+
+/// Errors which can occur when parsing a GUID from its textual representation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GuidParseError {
+    /// The string does not have the length of a canonical GUID,
+    /// with or without braces.
+    InvalidLength,
+    /// A separator was expected at this byte offset, but was not found.
+    InvalidSeparator(usize),
+    /// The character at this byte offset is not a hexadecimal digit.
+    InvalidDigit(usize),
+}
+
+impl fmt::Display for GuidParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuidParseError::InvalidLength => write!(f, "invalid GUID length"),
+            GuidParseError::InvalidSeparator(offset) => {
+                write!(f, "expected a '-' at offset {} of GUID", offset)
+            }
+            GuidParseError::InvalidDigit(offset) => {
+                write!(f, "invalid hexadecimal digit at offset {} of GUID", offset)
+            }
+        }
+    }
+}
+
 /// Several entities in the UEFI specification can be referred to by their GUID,
 /// this trait is a building block to interface them in uefi-rs.
 ///
@@ -97,8 +309,43 @@ impl fmt::Display for Guid {
 /// textual format as an argument, and is used in the following way:
 ///
 /// ```
+/// use uefi::{unsafe_guid, Guid, Identify};
+///
+/// struct Empty;
+///
 /// #[unsafe_guid("12345678-9abc-def0-1234-56789abcdef0")]
-/// type Emptiness = ();
+/// type Emptiness = Empty;
+///
+/// assert_eq!(
+///     <Emptiness as Identify>::GUID,
+///     Guid::from_values(0x12345678, 0x9abc, 0xdef0, 0x1234, [0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0])
+/// );
+/// ```
+///
+/// The GUID is validated at compile time, so malformed strings are rejected:
+///
+/// ```compile_fail
+/// # use uefi::unsafe_guid;
+/// #[unsafe_guid("12345678-9abc-def0-1234-56789abcdef")]
+/// struct TooShort;
+/// ```
+///
+/// ```compile_fail
+/// # use uefi::unsafe_guid;
+/// #[unsafe_guid("12345678-9abc-def0-1234-56789abcdefg")]
+/// struct NotHex;
+/// ```
+///
+/// ```compile_fail
+/// # use uefi::unsafe_guid;
+/// #[unsafe_guid("12345678-9abc-def0-123456789abcdef0")]
+/// struct MissingDash;
+/// ```
+///
+/// ```compile_fail
+/// # use uefi::unsafe_guid;
+/// #[unsafe_guid("+2345678-9abc-def0-1234-56789abcdef0")]
+/// struct Signed;
 /// ```
 pub unsafe trait Identify {
     /// Unique protocol identifier.
@@ -106,3 +353,174 @@ pub unsafe trait Identify {
 }
 
 pub use uefi_macros::unsafe_guid;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{console, media};
+    use crate::table::cfg;
+
+    extern crate std;
+    use std::string::ToString;
+
+    /// Checks that the textual form of `guid` parses back into it.
+    fn round_trip(guid: Guid) {
+        let text = guid.to_string();
+        assert_eq!(Guid::parse(&text), Ok(guid), "{}", text);
+        let braced = std::format!("{{{}}}", text.to_uppercase());
+        assert_eq!(Guid::parse(&braced), Ok(guid), "{}", braced);
+    }
+
+    macro_rules! round_trip_identify {
+        ($($ty:ty),* $(,)?) => {
+            $(round_trip(<$ty as Identify>::GUID);)*
+        };
+    }
+
+    #[test]
+    fn identify_round_trip() {
+        round_trip_identify!(
+            console::gop::GraphicsOutput,
+            console::pointer::Pointer,
+            console::serial::Serial,
+            console::text::Input,
+            console::text::Output,
+            crate::proto::debug::DebugSupport,
+            crate::proto::device_path::DevicePath,
+            crate::proto::loaded_image::LoadedImage,
+            media::block::BlockIO,
+            media::file::FileInfo,
+            media::file::FileSystemInfo,
+            media::file::FileSystemVolumeLabel,
+            media::fs::SimpleFileSystem,
+            media::partition::PartitionInfo,
+            crate::proto::pi::mp::MpServices,
+            crate::proto::shim::ShimLock,
+        );
+    }
+
+    #[test]
+    fn constants_round_trip() {
+        for &guid in &[
+            cfg::ACPI_GUID,
+            cfg::ACPI2_GUID,
+            cfg::SMBIOS_GUID,
+            cfg::SMBIOS3_GUID,
+            cfg::PROPERTIES_TABLE_GUID,
+            cfg::MEMORY_ATTRIBUTES_GUID,
+            cfg::HAND_OFF_BLOCK_LIST_GUID,
+            cfg::MEMORY_TYPE_INFORMATION_GUID,
+            cfg::MEMORY_STATUS_CODE_RECORD_GUID,
+            cfg::DXE_SERVICES_GUID,
+            cfg::LZMA_COMPRESS_GUID,
+            cfg::TIANO_COMPRESS_GUID,
+            cfg::DEBUG_IMAGE_INFO_GUID,
+            crate::table::runtime::GLOBAL_VARIABLE,
+        ] {
+            round_trip(guid);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_bad_hyphens() {
+        use GuidParseError::*;
+
+        // Hyphen moved one digit to the right.
+        assert_eq!(
+            Guid::parse("8868e871-e4f1-11d3-bc220-080c73c8881"),
+            Err(InvalidSeparator(23))
+        );
+        // Missing hyphen, with the length kept by an extra digit.
+        assert_eq!(
+            Guid::parse("8868e871ae4f1-11d3-bc22-0080c73c8881"),
+            Err(InvalidSeparator(8))
+        );
+        // Hyphens everywhere.
+        assert_eq!(
+            Guid::parse("------------------------------------"),
+            Err(InvalidDigit(0))
+        );
+        // Missing hyphen, which also makes the string too short.
+        assert_eq!(
+            Guid::parse("8868e871-e4f111d3-bc22-0080c73c8881"),
+            Err(InvalidLength)
+        );
+        // Inside braces, offsets include the opening brace.
+        assert_eq!(
+            Guid::parse("{8868e871-e4f1-11d3_bc22-0080c73c8881}"),
+            Err(InvalidSeparator(19))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_bad_digits() {
+        use GuidParseError::*;
+
+        assert_eq!(
+            Guid::parse("8868e871-e4f1-11d3-bc22-0080c73c888g"),
+            Err(InvalidDigit(35))
+        );
+        assert_eq!(
+            Guid::parse("x868e871-e4f1-11d3-bc22-0080c73c8881"),
+            Err(InvalidDigit(0))
+        );
+        assert_eq!(
+            Guid::parse("+868e871-e4f1-11d3-bc22-0080c73c8881"),
+            Err(InvalidDigit(0))
+        );
+        assert_eq!(
+            Guid::parse("8868e871-e4f1-11d3-bc22-0080c73c888 "),
+            Err(InvalidDigit(35))
+        );
+        // Multi-byte characters are rejected without splitting them.
+        assert_eq!(
+            Guid::parse("8868e871-e4f1-11d3-bc22-0080c73c88é"),
+            Err(InvalidDigit(34))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_bad_braces() {
+        use GuidParseError::*;
+
+        assert_eq!(
+            Guid::parse("{8868e871-e4f1-11d3-bc22-0080c73c8881"),
+            Err(InvalidLength)
+        );
+        assert_eq!(
+            Guid::parse("8868e871-e4f1-11d3-bc22-0080c73c8881}"),
+            Err(InvalidLength)
+        );
+        assert_eq!(
+            Guid::parse("{8868e871-e4f1-11d3-bc22-0080c73c8881{"),
+            Err(InvalidLength)
+        );
+        assert_eq!(
+            Guid::parse("}8868e871-e4f1-11d3-bc22-0080c73c8881{"),
+            Err(InvalidLength)
+        );
+        assert_eq!(
+            Guid::parse("{{8868e871-e4f1-11d3-bc22-0080c73c8881}}"),
+            Err(InvalidLength)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_bad_lengths() {
+        use GuidParseError::*;
+
+        let text = "8868e871-e4f1-11d3-bc22-0080c73c8881";
+        for len in 0..text.len() {
+            assert_eq!(Guid::parse(&text[..len]), Err(InvalidLength));
+        }
+        assert_eq!(
+            Guid::parse("8868e871-e4f1-11d3-bc22-0080c73c88810"),
+            Err(InvalidLength)
+        );
+        assert_eq!(
+            Guid::parse("08868e871-e4f1-11d3-bc22-0080c73c8881"),
+            Err(InvalidLength)
+        );
+        assert_eq!(Guid::parse("{}"), Err(InvalidLength));
+    }
+}
@@ -2,23 +2,80 @@
 //!
 //! This module defines the basic data types that are used throughout uefi-rs
 
-use core::{ffi::c_void, mem::MaybeUninit};
+use core::{ffi::c_void, fmt, mem, ptr::NonNull};
 
 /// Opaque handle to an UEFI entity (protocol, image...)
-#[derive(Clone, Copy)]
+///
+/// Handles are never null, so `Option<Handle>` has the same layout as a
+/// nullable `EFI_HANDLE`, and can be used in FFI signatures where the
+/// firmware may pass or return a null handle.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
 #[repr(transparent)]
-pub struct Handle(*mut c_void);
+pub struct Handle(NonNull<c_void>);
 
 impl Handle {
-    pub(crate) unsafe fn uninitialized() -> Self {
-        MaybeUninit::zeroed().assume_init()
+    /// Creates a new `Handle` from a raw pointer, or `None` if it is null.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the pointer is a valid UEFI handle.
+    pub unsafe fn from_ptr(ptr: *mut c_void) -> Option<Self> {
+        NonNull::new(ptr).map(Handle)
+    }
+
+    /// Returns the raw pointer of this handle.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0.as_ptr()
+    }
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.0).finish()
     }
 }
 
+// `Option<Handle>` must be usable in place of a nullable `EFI_HANDLE`.
+const _: () = assert!(mem::size_of::<Option<Handle>>() == mem::size_of::<*mut c_void>());
+
 /// Handle to an event structure
-#[derive(Clone, Copy)]
+///
+/// Events created with `BootServices::create_event` are owned by the caller,
+/// and should be closed exactly once with `BootServices::close_event`, which
+/// consumes them. This type is therefore neither `Copy` nor `Clone`.
+///
+/// Events which are owned by a protocol, such as `Input::wait_for_key_event`,
+/// are only handed out by reference.
 #[repr(transparent)]
-pub struct Event(*mut c_void);
+pub struct Event(NonNull<c_void>);
+
+impl Event {
+    /// Clone this `Event`
+    ///
+    /// # Safety
+    ///
+    /// When an event is closed by calling `BootServices::close_event`, that
+    /// event and ALL references to it are invalidated and the underlying
+    /// memory is freed by firmware. The caller must ensure that no clones of
+    /// closed `Event`s are used after the original has been closed.
+    pub unsafe fn unsafe_clone(&self) -> Self {
+        Event(self.0)
+    }
+
+    /// Returns the raw pointer of this event.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0.as_ptr()
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Event").field(&self.0).finish()
+    }
+}
+
+// `Option<Event>` must be usable in place of a nullable `EFI_EVENT`.
+const _: () = assert!(mem::size_of::<Option<Event>>() == mem::size_of::<*mut c_void>());
 
 /// Trait for querying the alignment of a struct
 ///
@@ -39,9 +96,18 @@ pub trait Align {
     }
 }
 
+#[macro_use]
 mod guid;
-pub use self::guid::Guid;
 pub use self::guid::{unsafe_guid, Identify};
+pub use self::guid::{Guid, GuidParseError};
+
+mod addr;
+pub use self::addr::{PhysicalAddress, VirtualAddress, PAGE_SIZE};
+
+mod net;
+pub use self::net::{
+    AddressParseError, IpAddress, IpVersion, Ipv4Address, Ipv6Address, MacAddress,
+};
 
 pub mod chars;
 pub use self::chars::{Char16, Char8};
@@ -49,5 +115,12 @@ pub use self::chars::{Char16, Char8};
 #[macro_use]
 mod enums;
 
+pub mod ucs2;
+
 mod strs;
-pub use self::strs::{CStr16, CStr8};
+pub use self::strs::{CStr16, CStr8, FromSliceWithNulError};
+
+#[cfg(feature = "exts")]
+mod owned_strs;
+#[cfg(feature = "exts")]
+pub use self::owned_strs::{CString16, FromStrError};
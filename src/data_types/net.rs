@@ -0,0 +1,701 @@
+//! Network address types
+//!
+//! These are shared by all of the networking protocols (SNP, PXE, TCP, ...).
+
+use core::fmt;
+use core::str::FromStr;
+
+/// Error returned when parsing a network address from a string fails.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AddressParseError;
+
+impl fmt::Display for AddressParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid network address syntax")
+    }
+}
+
+/// Parses a hexadecimal number made of 1 to `max_digits` digits.
+fn parse_hex(s: &str, max_digits: usize) -> Result<u16, AddressParseError> {
+    // `from_str_radix` accepts a leading sign, which is not valid here.
+    if s.is_empty() || s.len() > max_digits || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(AddressParseError);
+    }
+    u16::from_str_radix(s, 16).map_err(|_| AddressParseError)
+}
+
+/// An IPv4 internet protocol address.
+///
+/// ```
+/// use uefi::data_types::Ipv4Address;
+///
+/// let addr: Ipv4Address = "192.168.0.1".parse().unwrap();
+/// assert_eq!(addr, Ipv4Address([192, 168, 0, 1]));
+/// assert_eq!(format!("{}", addr), "192.168.0.1");
+///
+/// assert!("192.168.0".parse::<Ipv4Address>().is_err());
+/// assert!("192.168.0.256".parse::<Ipv4Address>().is_err());
+/// assert!("192.168..1".parse::<Ipv4Address>().is_err());
+/// assert!("1.2.3.4.5".parse::<Ipv4Address>().is_err());
+/// ```
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[repr(transparent)]
+pub struct Ipv4Address(pub [u8; 4]);
+
+impl Ipv4Address {
+    /// The unspecified address, `0.0.0.0`.
+    pub const UNSPECIFIED: Self = Ipv4Address([0; 4]);
+
+    /// Returns the four bytes of the address, in network order.
+    pub const fn octets(self) -> [u8; 4] {
+        self.0
+    }
+}
+
+impl From<[u8; 4]> for Ipv4Address {
+    fn from(octets: [u8; 4]) -> Self {
+        Ipv4Address(octets)
+    }
+}
+
+impl FromStr for Ipv4Address {
+    type Err = AddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut octets = [0; 4];
+        let mut parts = s.split('.');
+        for octet in octets.iter_mut() {
+            let part = parts.next().ok_or(AddressParseError)?;
+            // Reject signs, and leading zeros since they are ambiguous
+            // (some parsers treat them as octal).
+            let valid = !part.is_empty()
+                && part.len() <= 3
+                && part.bytes().all(|b| b.is_ascii_digit())
+                && !(part.len() > 1 && part.starts_with('0'));
+            if !valid {
+                return Err(AddressParseError);
+            }
+            *octet = part.parse().map_err(|_| AddressParseError)?;
+        }
+        if parts.next().is_some() {
+            return Err(AddressParseError);
+        }
+        Ok(Ipv4Address(octets))
+    }
+}
+
+impl fmt::Display for Ipv4Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d] = self.0;
+        write!(f, "{}.{}.{}.{}", a, b, c, d)
+    }
+}
+
+impl fmt::Debug for Ipv4Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// An IPv6 internet protocol address.
+///
+/// Addresses are displayed in the canonical form of RFC 5952: lowercase,
+/// with the longest run of zero groups compressed as `::`.
+///
+/// ```
+/// use uefi::data_types::Ipv6Address;
+///
+/// let addr: Ipv6Address = "2001:DB8::1".parse().unwrap();
+/// assert_eq!(format!("{}", addr), "2001:db8::1");
+///
+/// let addr: Ipv6Address = "2001:db8:0:0:1:0:0:1".parse().unwrap();
+/// assert_eq!(format!("{}", addr), "2001:db8::1:0:0:1");
+///
+/// assert_eq!(format!("{}", Ipv6Address::UNSPECIFIED), "::");
+/// assert_eq!(format!("{}", "::1".parse::<Ipv6Address>().unwrap()), "::1");
+/// assert_eq!(format!("{}", "1::".parse::<Ipv6Address>().unwrap()), "1::");
+/// assert_eq!(
+///     format!("{}", "1:0:2:3:4:5:6:7".parse::<Ipv6Address>().unwrap()),
+///     "1:0:2:3:4:5:6:7"
+/// );
+///
+/// assert!("1::2::3".parse::<Ipv6Address>().is_err());
+/// assert!("1:2:3:4:5:6:7".parse::<Ipv6Address>().is_err());
+/// assert!("1:2:3:4:5:6:7:8:9".parse::<Ipv6Address>().is_err());
+/// assert!("1:2:3:4:5:6:7::8".parse::<Ipv6Address>().is_err());
+/// assert!("12345::".parse::<Ipv6Address>().is_err());
+/// assert!(":1::".parse::<Ipv6Address>().is_err());
+/// assert!("+1::".parse::<Ipv6Address>().is_err());
+/// ```
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[repr(transparent)]
+pub struct Ipv6Address(pub [u8; 16]);
+
+impl Ipv6Address {
+    /// The unspecified address, `::`.
+    pub const UNSPECIFIED: Self = Ipv6Address([0; 16]);
+
+    /// Returns the sixteen bytes of the address, in network order.
+    pub const fn octets(self) -> [u8; 16] {
+        self.0
+    }
+
+    /// Returns the eight 16-bit groups of the address.
+    pub fn segments(self) -> [u16; 8] {
+        let mut segments = [0; 8];
+        for (segment, bytes) in segments.iter_mut().zip(self.0.chunks_exact(2)) {
+            *segment = u16::from_be_bytes([bytes[0], bytes[1]]);
+        }
+        segments
+    }
+
+    /// Builds an address from its eight 16-bit groups.
+    pub fn from_segments(segments: [u16; 8]) -> Self {
+        let mut octets = [0; 16];
+        for (bytes, segment) in octets.chunks_exact_mut(2).zip(segments.iter()) {
+            bytes.copy_from_slice(&segment.to_be_bytes());
+        }
+        Ipv6Address(octets)
+    }
+}
+
+impl From<[u8; 16]> for Ipv6Address {
+    fn from(octets: [u8; 16]) -> Self {
+        Ipv6Address(octets)
+    }
+}
+
+/// Parses a list of colon-separated groups into `out`,
+/// returning the number of groups that were read.
+fn parse_ipv6_groups(s: &str, out: &mut [u16]) -> Result<usize, AddressParseError> {
+    if s.is_empty() {
+        return Ok(0);
+    }
+    let mut count = 0;
+    for group in s.split(':') {
+        let slot = out.get_mut(count).ok_or(AddressParseError)?;
+        *slot = parse_hex(group, 4)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+impl FromStr for Ipv6Address {
+    type Err = AddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = [0; 8];
+        match s.find("::") {
+            None => {
+                if parse_ipv6_groups(s, &mut segments)? != 8 {
+                    return Err(AddressParseError);
+                }
+            }
+            Some(idx) => {
+                let (head, tail) = (&s[..idx], &s[idx + 2..]);
+                let head_len = parse_ipv6_groups(head, &mut segments)?;
+                let mut tail_segments = [0; 8];
+                let tail_len = parse_ipv6_groups(tail, &mut tail_segments)?;
+                // `::` must stand for at least one group of zeros.
+                if head_len + tail_len > 7 {
+                    return Err(AddressParseError);
+                }
+                segments[8 - tail_len..].copy_from_slice(&tail_segments[..tail_len]);
+            }
+        }
+        Ok(Ipv6Address::from_segments(segments))
+    }
+}
+
+impl fmt::Display for Ipv6Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let segments = self.segments();
+
+        // Find the longest run of zero groups (the first one on ties).
+        let (mut best_start, mut best_len) = (0, 0);
+        let mut run_start = 0;
+        for (i, &segment) in segments.iter().enumerate() {
+            if segment != 0 {
+                run_start = i + 1;
+            } else if i + 1 - run_start > best_len {
+                best_start = run_start;
+                best_len = i + 1 - run_start;
+            }
+        }
+
+        let write_groups = |f: &mut fmt::Formatter, groups: &[u16]| {
+            for (i, group) in groups.iter().enumerate() {
+                if i != 0 {
+                    f.write_str(":")?;
+                }
+                write!(f, "{:x}", group)?;
+            }
+            Ok(())
+        };
+
+        // A single zero group is not worth compressing.
+        if best_len < 2 {
+            write_groups(f, &segments)
+        } else {
+            write_groups(f, &segments[..best_start])?;
+            f.write_str("::")?;
+            write_groups(f, &segments[best_start + best_len..])
+        }
+    }
+}
+
+impl fmt::Debug for Ipv6Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Version of the internet protocol.
+///
+/// This is required to interpret an `IpAddress`, which does not store
+/// the version of the address it contains.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IpVersion {
+    /// IPv4
+    V4,
+    /// IPv6
+    V6,
+}
+
+/// An IPv4 or IPv6 internet protocol address.
+///
+/// This corresponds to the `EFI_IP_ADDRESS` union of the spec, which has no
+/// discriminant: the version has to be known from the context, and must be
+/// passed explicitly to the accessors.
+///
+/// ```
+/// use uefi::data_types::{IpAddress, IpVersion, Ipv4Address};
+///
+/// let addr = IpAddress::from([10, 0, 2, 15]);
+/// assert_eq!(addr.as_ipv4(), Ipv4Address([10, 0, 2, 15]));
+/// assert_eq!(format!("{}", addr.display(IpVersion::V4)), "10.0.2.15");
+/// assert_eq!(format!("{}", addr.display(IpVersion::V6)), "a00:20f::");
+///
+/// let addr: IpAddress = "fe80::1".parse().unwrap();
+/// assert_eq!(format!("{}", addr.display(IpVersion::V6)), "fe80::1");
+/// ```
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[repr(C, align(4))]
+pub struct IpAddress([u8; 16]);
+
+impl IpAddress {
+    /// Creates an address holding an IPv4 address.
+    pub fn new_v4(addr: Ipv4Address) -> Self {
+        let mut octets = [0; 16];
+        octets[..4].copy_from_slice(&addr.0);
+        IpAddress(octets)
+    }
+
+    /// Creates an address holding an IPv6 address.
+    pub fn new_v6(addr: Ipv6Address) -> Self {
+        IpAddress(addr.0)
+    }
+
+    /// Interprets this address as an IPv4 address.
+    pub fn as_ipv4(&self) -> Ipv4Address {
+        let mut octets = [0; 4];
+        octets.copy_from_slice(&self.0[..4]);
+        Ipv4Address(octets)
+    }
+
+    /// Interprets this address as an IPv6 address.
+    pub fn as_ipv6(&self) -> Ipv6Address {
+        Ipv6Address(self.0)
+    }
+
+    /// Returns the raw bytes of the address.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Returns an object which displays this address as the given version.
+    pub fn display(&self, version: IpVersion) -> impl fmt::Display {
+        IpAddressDisplay {
+            addr: *self,
+            version,
+        }
+    }
+}
+
+struct IpAddressDisplay {
+    addr: IpAddress,
+    version: IpVersion,
+}
+
+impl fmt::Display for IpAddressDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.version {
+            IpVersion::V4 => fmt::Display::fmt(&self.addr.as_ipv4(), f),
+            IpVersion::V6 => fmt::Display::fmt(&self.addr.as_ipv6(), f),
+        }
+    }
+}
+
+impl fmt::Debug for IpAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("IpAddress").field(&self.0).finish()
+    }
+}
+
+impl From<Ipv4Address> for IpAddress {
+    fn from(addr: Ipv4Address) -> Self {
+        IpAddress::new_v4(addr)
+    }
+}
+
+impl From<Ipv6Address> for IpAddress {
+    fn from(addr: Ipv6Address) -> Self {
+        IpAddress::new_v6(addr)
+    }
+}
+
+impl From<[u8; 4]> for IpAddress {
+    fn from(octets: [u8; 4]) -> Self {
+        IpAddress::new_v4(Ipv4Address(octets))
+    }
+}
+
+impl From<[u8; 16]> for IpAddress {
+    fn from(octets: [u8; 16]) -> Self {
+        IpAddress(octets)
+    }
+}
+
+impl FromStr for IpAddress {
+    type Err = AddressParseError;
+
+    /// Parses either a dotted-quad IPv4 address or a colon-hex IPv6 address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(':') {
+            s.parse::<Ipv6Address>().map(IpAddress::new_v6)
+        } else {
+            s.parse::<Ipv4Address>().map(IpAddress::new_v4)
+        }
+    }
+}
+
+/// A hardware (MAC) address.
+///
+/// This corresponds to `EFI_MAC_ADDRESS`, which is padded to 32 bytes.
+/// The number of significant bytes depends on the network interface
+/// (it is 6 for Ethernet), and is usually reported by the protocol.
+///
+/// ```
+/// use uefi::data_types::MacAddress;
+///
+/// let addr: MacAddress = "52:54:00:12:34:56".parse().unwrap();
+/// assert_eq!(addr, MacAddress::from([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]));
+/// assert_eq!(format!("{}", addr.display(6)), "52:54:00:12:34:56");
+/// assert_eq!(format!("{}", addr.display(2)), "52:54");
+///
+/// // Oversized lengths are clamped to the size of the buffer.
+/// assert_eq!(format!("{}", addr.display(100)).len(), 32 * 3 - 1);
+///
+/// assert!("52:54:00:12:34:5".parse::<MacAddress>().is_ok());
+/// assert!("52:54:00:12:34:567".parse::<MacAddress>().is_err());
+/// assert!("52:54::12:34:56".parse::<MacAddress>().is_err());
+/// assert!("".parse::<MacAddress>().is_err());
+/// ```
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+#[repr(transparent)]
+pub struct MacAddress(pub [u8; 32]);
+
+impl MacAddress {
+    /// Returns the raw bytes of the address, including padding.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Returns an object which displays the first `len` bytes of this address
+    /// as colon-separated hexadecimal numbers.
+    pub fn display(&self, len: usize) -> impl fmt::Display + '_ {
+        MacAddressDisplay {
+            bytes: &self.0[..len.min(self.0.len())],
+        }
+    }
+}
+
+struct MacAddressDisplay<'a> {
+    bytes: &'a [u8],
+}
+
+impl fmt::Display for MacAddressDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i != 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Without the length, trailing zeroes are assumed to be padding.
+        let len = self.0.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        write!(f, "MacAddress({})", self.display(len.max(6)))
+    }
+}
+
+impl From<[u8; 6]> for MacAddress {
+    fn from(octets: [u8; 6]) -> Self {
+        let mut bytes = [0; 32];
+        bytes[..6].copy_from_slice(&octets);
+        MacAddress(bytes)
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = AddressParseError;
+
+    /// Parses up to 32 colon or dash separated hexadecimal bytes.
+    /// The remaining bytes are set to zero.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 32];
+        for (i, part) in s.split(&[':', '-'][..]).enumerate() {
+            let byte = bytes.get_mut(i).ok_or(AddressParseError)?;
+            *byte = parse_hex(part, 2)? as u8;
+        }
+        Ok(MacAddress(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem;
+
+    extern crate std;
+    use std::format;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    #[test]
+    fn layout() {
+        // These types are embedded in protocol structures.
+        assert_eq!(mem::size_of::<Ipv4Address>(), 4);
+        assert_eq!(mem::size_of::<Ipv6Address>(), 16);
+        assert_eq!(mem::size_of::<IpAddress>(), 16);
+        assert_eq!(mem::align_of::<IpAddress>(), 4);
+        assert_eq!(mem::size_of::<MacAddress>(), 32);
+    }
+
+    #[test]
+    fn ipv4_round_trip() {
+        for &text in &["0.0.0.0", "10.0.2.15", "127.0.0.1", "255.255.255.255"] {
+            let addr: Ipv4Address = text.parse().unwrap();
+            assert_eq!(addr.to_string(), text);
+            assert_eq!(format!("{:?}", addr), text);
+        }
+        assert_eq!(Ipv4Address::from([1, 2, 3, 4]).octets(), [1, 2, 3, 4]);
+        assert_eq!("0.0.0.0".parse(), Ok(Ipv4Address::UNSPECIFIED));
+    }
+
+    #[test]
+    fn ipv4_rejects_bad_syntax() {
+        let bad = [
+            "",
+            "1",
+            "1.2.3",
+            "1.2.3.4.",
+            ".1.2.3",
+            "1.2.3.4.5",
+            "256.0.0.0",
+            "1.2.3.-4",
+            "1.2.+3.4",
+            "01.2.3.4",
+            "1.2.3.0x4",
+            "1.2.3.4 ",
+            "1..3.4",
+            "1.2.3.1000",
+        ];
+        for &text in &bad {
+            assert_eq!(
+                text.parse::<Ipv4Address>(),
+                Err(AddressParseError),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn ipv6_round_trip() {
+        let canonical = [
+            "::",
+            "::1",
+            "1::",
+            "fe80::1",
+            "2001:db8::1:0:0:1",
+            "2001:db8:0:1:1:1:1:1",
+            "1:0:2:3:4:5:6:7",
+            "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+            // The first of two equally long runs is compressed.
+            "1::2:0:0:3",
+            // The longest run wins over an earlier one.
+            "1:0:0:2::3",
+        ];
+        for &text in &canonical {
+            let addr: Ipv6Address = text.parse().unwrap();
+            assert_eq!(addr.to_string(), text);
+            assert_eq!(format!("{:?}", addr), text);
+            assert_eq!(Ipv6Address::from_segments(addr.segments()), addr);
+        }
+    }
+
+    #[test]
+    fn ipv6_canonicalizes() {
+        let cases = [
+            ("0:0:0:0:0:0:0:0", "::"),
+            ("0:0:0:0:0:0:0:1", "::1"),
+            ("FE80:0000:0000:0000:0000:0000:0000:0001", "fe80::1"),
+            ("1:0:0:2:0:0:0:3", "1:0:0:2::3"),
+            ("1::0:2", "1::2"),
+        ];
+        for &(text, canonical) in &cases {
+            let addr: Ipv6Address = text.parse().unwrap();
+            assert_eq!(addr.to_string(), canonical);
+        }
+    }
+
+    #[test]
+    fn ipv6_segments() {
+        let addr: Ipv6Address = "2001:db8::ff00:42:8329".parse().unwrap();
+        assert_eq!(
+            addr.segments(),
+            [0x2001, 0xdb8, 0, 0, 0, 0xff00, 0x42, 0x8329]
+        );
+        assert_eq!(
+            addr.octets(),
+            [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0xff, 0, 0, 0x42, 0x83, 0x29]
+        );
+        assert_eq!(Ipv6Address::from(addr.octets()), addr);
+    }
+
+    #[test]
+    fn ipv6_rejects_bad_syntax() {
+        let bad = [
+            "",
+            ":",
+            ":::",
+            "1",
+            "1:2:3:4:5:6:7",
+            "1:2:3:4:5:6:7:8:9",
+            "1::2::3",
+            "1:2:3:4:5:6:7::8",
+            "::1:2:3:4:5:6:7:8",
+            "12345::",
+            ":1::",
+            "1::1:",
+            "1:::2",
+            "+1::",
+            "g::",
+            "1.2.3.4",
+            " ::1",
+        ];
+        for &text in &bad {
+            assert_eq!(
+                text.parse::<Ipv6Address>(),
+                Err(AddressParseError),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn ip_address_versions() {
+        let v4 = Ipv4Address([192, 168, 1, 2]);
+        let addr = IpAddress::from(v4);
+        assert_eq!(addr, IpAddress::new_v4(v4));
+        assert_eq!(addr, IpAddress::from([192, 168, 1, 2]));
+        assert_eq!(addr.as_ipv4(), v4);
+        assert_eq!(addr.as_bytes()[..4], [192, 168, 1, 2]);
+        assert!(addr.as_bytes()[4..].iter().all(|&b| b == 0));
+        assert_eq!(addr.display(IpVersion::V4).to_string(), "192.168.1.2");
+        assert_eq!(addr.display(IpVersion::V6).to_string(), "c0a8:102::");
+
+        let v6: Ipv6Address = "fe80::1".parse().unwrap();
+        let addr = IpAddress::from(v6);
+        assert_eq!(addr, IpAddress::new_v6(v6));
+        assert_eq!(addr, IpAddress::from(v6.octets()));
+        assert_eq!(addr.as_ipv6(), v6);
+        assert_eq!(addr.as_ipv4(), Ipv4Address([0xfe, 0x80, 0, 0]));
+        assert_eq!(addr.display(IpVersion::V6).to_string(), "fe80::1");
+    }
+
+    #[test]
+    fn ip_address_parse() {
+        assert_eq!("10.0.2.2".parse(), Ok(IpAddress::from([10, 0, 2, 2])));
+        assert_eq!(
+            "::ffff".parse(),
+            Ok(IpAddress::from([
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff
+            ]))
+        );
+        assert_eq!("10.0.2".parse::<IpAddress>(), Err(AddressParseError));
+        assert_eq!("10:0:2".parse::<IpAddress>(), Err(AddressParseError));
+        assert_eq!("".parse::<IpAddress>(), Err(AddressParseError));
+    }
+
+    #[test]
+    fn mac_address_parse() {
+        let addr = MacAddress::from([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+        assert_eq!("52:54:00:12:34:56".parse(), Ok(addr));
+        assert_eq!("52-54-00-12-34-56".parse(), Ok(addr));
+        assert_eq!("52:54:0:12:34:56".parse(), Ok(addr));
+        assert!(addr.as_bytes()[6..].iter().all(|&b| b == 0));
+
+        let long = (0..32)
+            .map(|i| format!("{:02x}", i))
+            .collect::<Vec<_>>()
+            .join(":");
+        let parsed: MacAddress = long.parse().unwrap();
+        assert!(parsed
+            .as_bytes()
+            .iter()
+            .enumerate()
+            .all(|(i, &b)| usize::from(b) == i));
+        assert_eq!(parsed.display(32).to_string(), long);
+
+        let too_long = format!("{}:20", long);
+        assert_eq!(too_long.parse::<MacAddress>(), Err(AddressParseError));
+        for &text in &[
+            "", ":", "52::54", "52:54:", "525:4", "5g:54", "+5:54", "52:54 ",
+        ] {
+            assert_eq!(
+                text.parse::<MacAddress>(),
+                Err(AddressParseError),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn mac_address_fmt() {
+        let addr = MacAddress::from([0x52, 0x54, 0x00, 0xab, 0xcd, 0x00]);
+        assert_eq!(addr.display(6).to_string(), "52:54:00:ab:cd:00");
+        assert_eq!(addr.display(0).to_string(), "");
+        assert_eq!(addr.display(8).to_string(), "52:54:00:ab:cd:00:00:00");
+        assert_eq!(format!("{:?}", addr), "MacAddress(52:54:00:ab:cd:00)");
+
+        let mut bytes = [0; 32];
+        bytes[7] = 1;
+        assert_eq!(
+            format!("{:?}", MacAddress(bytes)),
+            "MacAddress(00:00:00:00:00:00:00:01)"
+        );
+        assert_eq!(
+            format!("{:?}", MacAddress::default()),
+            "MacAddress(00:00:00:00:00:00)"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use super::chars::{Char16, NUL_16};
+use super::strs::CStr16;
+use crate::alloc_api::{string::String, vec, vec::Vec};
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops;
+
+/// Errors which can occur when converting a Rust string to a `CString16`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FromStrError {
+    /// The character is outside of the Basic Multilingual Plane,
+    /// and cannot be represented in UCS-2
+    InvalidChar(char),
+
+    /// The string contains a null character
+    InteriorNul,
+}
+
+/// An owned UCS-2 null-terminated string
+///
+/// This is the owned counterpart of `CStr16`, and like `std::ffi::CString`,
+/// it guarantees that the string is null-terminated and contains no interior
+/// null characters.
+///
+/// ```
+/// use core::convert::TryFrom;
+/// use uefi::CString16;
+/// use uefi::data_types::FromStrError;
+///
+/// let string = CString16::try_from("Grüße, Jürgen ❤").unwrap();
+/// assert_eq!(String::from(string), "Grüße, Jürgen ❤");
+///
+/// assert_eq!(CString16::try_from("a\0b"), Err(FromStrError::InteriorNul));
+/// assert_eq!(CString16::try_from("🦀"), Err(FromStrError::InvalidChar('🦀')));
+/// ```
+#[derive(Clone, Eq, PartialEq)]
+pub struct CString16(Vec<Char16>);
+
+impl CString16 {
+    /// Creates a new, empty C string
+    pub fn new() -> Self {
+        CString16(vec![NUL_16])
+    }
+
+    /// Appends a character to the end of this C string
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ch` is the null character.
+    pub fn push(&mut self, ch: Char16) {
+        assert!(
+            ch != NUL_16,
+            "Cannot push a null character into a CString16"
+        );
+        let nul = self.0.len() - 1;
+        self.0.insert(nul, ch);
+    }
+}
+
+impl Default for CString16 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TryFrom<&str> for CString16 {
+    type Error = FromStrError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        let mut output = Vec::with_capacity(input.len() + 1);
+        for ch in input.chars() {
+            if ch == '\0' {
+                return Err(FromStrError::InteriorNul);
+            }
+            let ch = Char16::try_from(ch).map_err(|_| FromStrError::InvalidChar(ch))?;
+            output.push(ch);
+        }
+        output.push(NUL_16);
+        Ok(CString16(output))
+    }
+}
+
+/// Builds a C string from UCS-2 characters
+///
+/// # Panics
+///
+/// Panics if one of the characters is the null character.
+impl FromIterator<Char16> for CString16 {
+    fn from_iter<I: IntoIterator<Item = Char16>>(iter: I) -> Self {
+        let mut string = CString16::new();
+        for ch in iter {
+            string.push(ch);
+        }
+        string
+    }
+}
+
+impl ops::Deref for CString16 {
+    type Target = CStr16;
+
+    fn deref(&self) -> &CStr16 {
+        let codes = unsafe { &*(self.0.as_slice() as *const [Char16] as *const [u16]) };
+        unsafe { CStr16::from_u16_with_nul_unchecked(codes) }
+    }
+}
+
+impl AsRef<CStr16> for CString16 {
+    fn as_ref(&self) -> &CStr16 {
+        self
+    }
+}
+
+impl From<&CStr16> for CString16 {
+    fn from(string: &CStr16) -> Self {
+        CString16(string.iter().copied().chain(Some(NUL_16)).collect())
+    }
+}
+
+/// Converts a C string to a Rust string
+///
+/// Invalid UCS-2 code points, which may only be present in strings built with
+/// the unchecked `CStr16` constructors, are replaced with the Unicode
+/// replacement character.
+impl From<&CStr16> for String {
+    fn from(string: &CStr16) -> Self {
+        string
+            .to_u16_slice()
+            .iter()
+            .map(|&code| {
+                core::char::from_u32(u32::from(code)).unwrap_or(core::char::REPLACEMENT_CHARACTER)
+            })
+            .collect()
+    }
+}
+
+impl From<CString16> for String {
+    fn from(string: CString16) -> Self {
+        String::from(&*string)
+    }
+}
+
+impl fmt::Debug for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CString16({:?})", &self.0[..self.0.len() - 1])
+    }
+}
+
+impl fmt::Display for CString16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl PartialEq<&str> for CString16 {
+    fn eq(&self, other: &&str) -> bool {
+        **self == **other
+    }
+}
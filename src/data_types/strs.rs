@@ -6,6 +6,7 @@ use core::result::Result;
 use core::slice;
 
 /// Errors which can occur during checked [uN] -> CStrN conversions
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum FromSliceWithNulError {
     /// An invalid character was encountered before the end of the slice
     InvalidChar(usize),
@@ -81,6 +82,54 @@ impl CStr8 {
     }
 }
 
+impl fmt::Debug for CStr8 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CStr8({:?})", &self.0)
+    }
+}
+
+/// Latin-1 characters are displayed as the Unicode characters of the same
+/// code point.
+///
+/// ```
+/// use uefi::CStr8;
+///
+/// let string = CStr8::from_bytes_with_nul(b"caf\xe9 \xbd\0").unwrap();
+/// assert_eq!(string.to_string(), "café ½");
+/// assert!(*string == "café ½");
+/// ```
+impl fmt::Display for CStr8 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0[..self.0.len() - 1].iter() {
+            <Char8 as fmt::Display>::fmt(c, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for CStr8 {
+    fn eq(&self, other: &CStr8) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for CStr8 {}
+
+impl PartialEq<str> for CStr8 {
+    fn eq(&self, other: &str) -> bool {
+        self.to_bytes()
+            .iter()
+            .map(|&c| u32::from(c))
+            .eq(other.chars().map(u32::from))
+    }
+}
+
+impl PartialEq<&str> for CStr8 {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
 /// An UCS-2 null-terminated string
 ///
 /// This type is largely inspired by `std::ffi::CStr`, see the documentation of
@@ -154,6 +203,17 @@ impl CStr16 {
         unsafe { &*(&self.0 as *const [Char16] as *const [u16]) }
     }
 
+    /// Returns the number of characters in this C string,
+    /// not including the trailing null character
+    pub fn num_chars(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    /// Returns true if this C string only contains the trailing null character
+    pub fn is_empty(&self) -> bool {
+        self.num_chars() == 0
+    }
+
     /// Returns an iterator over this C string
     pub fn iter(&self) -> CStr16Iter {
         CStr16Iter {
@@ -183,12 +243,51 @@ impl<'a> Iterator for CStr16Iter<'a> {
     }
 }
 
+impl PartialEq for CStr16 {
+    fn eq(&self, other: &CStr16) -> bool {
+        self.to_u16_slice() == other.to_u16_slice()
+    }
+}
+
+impl Eq for CStr16 {}
+
+/// Compares a C string with a Rust string, character by character
+///
+/// ```
+/// use uefi::CStr16;
+///
+/// let name = CStr16::from_u16_with_nul(&[0x43, 0x3a, 0xe9, 0]).unwrap();
+/// assert!(*name == "C:é");
+/// assert!(*name != "C:e");
+/// assert!(*name != "C:");
+///
+/// let empty = CStr16::from_u16_with_nul(&[0]).unwrap();
+/// assert!(*empty == "");
+/// assert!(CStr16::from_u16_with_nul(&[0x43, 0, 0x3a, 0]).is_err());
+/// ```
+impl PartialEq<str> for CStr16 {
+    fn eq(&self, other: &str) -> bool {
+        self.iter()
+            .map(|&c| u32::from(u16::from(c)))
+            .eq(other.chars().map(u32::from))
+    }
+}
+
+impl PartialEq<&str> for CStr16 {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
 impl fmt::Debug for CStr16 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "CStr16({:?})", &self.0)
     }
 }
 
+/// Unpaired surrogates and other invalid UCS-2 code points, which may only be
+/// present in strings built with the unchecked constructors, are displayed as
+/// the Unicode replacement character.
 impl fmt::Display for CStr16 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for c in self.iter() {
@@ -197,3 +296,88 @@ impl fmt::Display for CStr16 {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn cstr16_empty() {
+        let empty = CStr16::from_u16_with_nul(&[0]).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.num_chars(), 0);
+        assert_eq!(empty.to_u16_slice(), &[]);
+        assert_eq!(empty.to_u16_slice_with_nul(), &[0]);
+        assert_eq!(empty.iter().count(), 0);
+        assert_eq!(empty.to_string(), "");
+
+        assert_eq!(
+            CStr16::from_u16_with_nul(&[]),
+            Err(FromSliceWithNulError::NotNulTerminated)
+        );
+    }
+
+    #[test]
+    fn cstr16_rejects_interior_nul() {
+        assert_eq!(
+            CStr16::from_u16_with_nul(&[0, 0]),
+            Err(FromSliceWithNulError::InteriorNul(0))
+        );
+        assert_eq!(
+            CStr16::from_u16_with_nul(&[0x41, 0, 0x42, 0]),
+            Err(FromSliceWithNulError::InteriorNul(1))
+        );
+        assert_eq!(
+            CStr16::from_u16_with_nul(&[0x41, 0x42]),
+            Err(FromSliceWithNulError::NotNulTerminated)
+        );
+    }
+
+    #[test]
+    fn cstr16_rejects_surrogates() {
+        assert_eq!(
+            CStr16::from_u16_with_nul(&[0x41, 0xd800, 0]),
+            Err(FromSliceWithNulError::InvalidChar(1))
+        );
+        assert_eq!(
+            CStr16::from_u16_with_nul(&[0xdfff, 0]),
+            Err(FromSliceWithNulError::InvalidChar(0))
+        );
+    }
+
+    #[test]
+    fn cstr16_non_ascii() {
+        let codes = [0x48, 0xe9, 0x20ac, 0x4e2d, 0xfffd, 0];
+        let s = CStr16::from_u16_with_nul(&codes).unwrap();
+        assert_eq!(s.num_chars(), 5);
+        assert_eq!(s.to_u16_slice(), &codes[..5]);
+        assert_eq!(s.to_u16_slice_with_nul(), &codes);
+        assert!(s
+            .iter()
+            .map(|&c| u16::from(c))
+            .eq(codes[..5].iter().copied()));
+        assert_eq!(s.to_string(), "Hé€中\u{fffd}");
+        assert!(*s == "Hé€中\u{fffd}");
+        assert!(*s != "Hé€中");
+        assert!(*s != "Hé€中\u{fffd}!");
+    }
+
+    #[test]
+    fn cstr16_display_replaces_surrogates() {
+        let codes = [0x41, 0xd83d, 0x42, 0];
+        let s = unsafe { CStr16::from_u16_with_nul_unchecked(&codes) };
+        assert_eq!(s.to_string(), "A\u{fffd}B");
+    }
+
+    #[test]
+    fn cstr16_eq() {
+        let a = CStr16::from_u16_with_nul(&[0x61, 0x62, 0]).unwrap();
+        let b = CStr16::from_u16_with_nul(&[0x61, 0x62, 0]).unwrap();
+        let c = CStr16::from_u16_with_nul(&[0x61, 0]).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
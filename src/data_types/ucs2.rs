@@ -0,0 +1,347 @@
+//! UCS-2 string conversions
+//!
+//! UEFI strings are encoded in UCS-2, which is the subset of UTF-16 that does
+//! not use surrogate pairs. This module converts them to and from the UTF-8
+//! strings used by Rust.
+
+use super::chars::Char16;
+use crate::result::Error;
+use crate::{Completion, Result, Status};
+use core::convert::TryFrom;
+
+#[cfg(feature = "exts")]
+use crate::alloc_api::string::String;
+
+/// Errors which can occur while encoding a string to UCS-2
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EncodeError<E> {
+    /// The input contains a character outside of the Basic Multilingual Plane,
+    /// which cannot be represented in UCS-2.
+    InvalidChar(char),
+    /// The output sink reported an error.
+    Sink(E),
+}
+
+/// Encodes a string to UCS-2, passing each encoded character to `output`
+///
+/// Characters outside of the Basic Multilingual Plane cannot be represented in
+/// UCS-2, and stop the encoding with `EncodeError::InvalidChar`. Errors
+/// reported by `output` also stop the encoding, and are propagated as
+/// `EncodeError::Sink`. In both cases, the characters preceding the error have
+/// already been passed to `output`.
+///
+/// On success, returns the number of UCS-2 characters that were emitted.
+///
+/// ```
+/// use uefi::data_types::ucs2::{self, EncodeError};
+///
+/// let mut buffer = Vec::new();
+/// let count = ucs2::encode_with("Größe", |ch| {
+///     buffer.push(u16::from(ch));
+///     Ok::<_, ()>(())
+/// });
+/// assert_eq!(count, Ok(5));
+/// assert_eq!(buffer, "Größe".encode_utf16().collect::<Vec<_>>());
+///
+/// assert_eq!(
+///     ucs2::encode_with("a🦀", |_| Ok::<_, ()>(())),
+///     Err(EncodeError::InvalidChar('🦀'))
+/// );
+///
+/// let mut remaining = 2;
+/// let failing_sink = |_| {
+///     if remaining == 0 {
+///         return Err("sink is full");
+///     }
+///     remaining -= 1;
+///     Ok(())
+/// };
+/// assert_eq!(
+///     ucs2::encode_with("abc", failing_sink),
+///     Err(EncodeError::Sink("sink is full"))
+/// );
+/// ```
+pub fn encode_with<E>(
+    input: &str,
+    mut output: impl FnMut(Char16) -> core::result::Result<(), E>,
+) -> core::result::Result<usize, EncodeError<E>> {
+    let mut count = 0;
+    for ch in input.chars() {
+        let ch = Char16::try_from(ch).map_err(|_| EncodeError::InvalidChar(ch))?;
+        output(ch).map_err(EncodeError::Sink)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Encodes a string to UCS-2 in a caller-provided buffer, adding a trailing null
+///
+/// On success, returns the number of UCS-2 characters written to `buffer`,
+/// including the trailing null character.
+///
+/// # Errors
+///
+/// * `Status::BUFFER_TOO_SMALL`: the buffer is too small, the required number
+///   of UCS-2 characters (including the null character) is returned.
+/// * `Status::INVALID_PARAMETER`: the string contains a null character.
+/// * `Status::UNSUPPORTED`: the string contains a character outside of the
+///   Basic Multilingual Plane, which cannot be represented in UCS-2.
+///
+/// ```
+/// use uefi::data_types::ucs2;
+/// use uefi::{ResultExt, Status};
+///
+/// let mut buffer = [0; 4];
+/// assert_eq!(ucs2::encode_str("abc", &mut buffer).unwrap_success(), 4);
+/// assert_eq!(buffer, [0x61, 0x62, 0x63, 0]);
+///
+/// let err = ucs2::encode_str("abcd", &mut buffer).unwrap_err();
+/// assert_eq!(err.status(), Status::BUFFER_TOO_SMALL);
+/// assert_eq!(*err.data(), Some(5));
+///
+/// assert_eq!(ucs2::encode_str("", &mut buffer).unwrap_success(), 1);
+/// assert_eq!(buffer[0], 0);
+/// assert_eq!(ucs2::encode_str("", &mut []).unwrap_err().data(), &Some(1));
+///
+/// assert_eq!(ucs2::encode_str("a\0", &mut buffer).status(), Status::INVALID_PARAMETER);
+/// assert_eq!(ucs2::encode_str("🦀", &mut buffer).status(), Status::UNSUPPORTED);
+/// ```
+pub fn encode_str(input: &str, buffer: &mut [u16]) -> Result<usize, Option<usize>> {
+    let mut written = 0;
+    let res = encode_with(input, |ch| {
+        if ch == Char16::NUL {
+            return Err(Status::INVALID_PARAMETER);
+        }
+        // Keep room for the trailing null character
+        if written + 1 < buffer.len() {
+            buffer[written] = ch.into();
+        }
+        written += 1;
+        Ok(())
+    });
+
+    match res {
+        Ok(len) if len < buffer.len() => {
+            buffer[len] = 0;
+            Ok(Completion::from(len + 1))
+        }
+        Ok(len) => Err(Error::new(Status::BUFFER_TOO_SMALL, Some(len + 1))),
+        Err(EncodeError::InvalidChar(_)) => Err(Error::new(Status::UNSUPPORTED, None)),
+        Err(EncodeError::Sink(status)) => Err(Error::new(status, None)),
+    }
+}
+
+/// Iterates over the characters of an UCS-2 string
+///
+/// Decoding stops at the first null character, if any. Surrogates, which
+/// are not valid in UCS-2, are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+fn decode_chars(input: &[u16]) -> impl Iterator<Item = char> + '_ {
+    input.iter().take_while(|&&code| code != 0).map(|&code| {
+        core::char::from_u32(u32::from(code)).unwrap_or(core::char::REPLACEMENT_CHARACTER)
+    })
+}
+
+/// Decodes an UCS-2 string into an UTF-8 buffer
+///
+/// Decoding stops at the first null character, which is not written to the
+/// output. It is therefore possible to directly pass the contents of a
+/// null-terminated string. Unpaired surrogates are not valid UCS-2 and are
+/// replaced with `U+FFFD REPLACEMENT CHARACTER`, so that the output is always
+/// valid UTF-8.
+///
+/// On success, returns the number of bytes which were written to `output`.
+/// If the output buffer is too small, `Status::BUFFER_TOO_SMALL` is returned
+/// along with the required buffer size, and the contents of the buffer are
+/// unspecified.
+///
+/// ```
+/// use uefi::data_types::ucs2;
+/// use uefi::ResultExt;
+///
+/// let input: Vec<u16> = "Ünïcödé ✓\0ignored".encode_utf16().collect();
+///
+/// let mut buffer = [0; 32];
+/// let len = ucs2::decode(&input, &mut buffer).unwrap_success();
+/// assert_eq!(&buffer[..len], "Ünïcödé ✓".as_bytes());
+///
+/// let mut small_buffer = [0; 4];
+/// let err = ucs2::decode(&input, &mut small_buffer).unwrap_err();
+/// assert_eq!(*err.data(), len);
+///
+/// let len = ucs2::decode(&[0x41, 0xd800, 0x42], &mut buffer).unwrap_success();
+/// assert_eq!(&buffer[..len], "A\u{fffd}B".as_bytes());
+/// ```
+pub fn decode(input: &[u16], output: &mut [u8]) -> Result<usize, usize> {
+    let mut written = 0;
+    let mut required = 0;
+
+    for ch in decode_chars(input) {
+        let len = ch.len_utf8();
+        if required == written && written + len <= output.len() {
+            ch.encode_utf8(&mut output[written..]);
+            written += len;
+        }
+        required += len;
+    }
+
+    if required == written {
+        Ok(Completion::from(written))
+    } else {
+        Err(Error::new(Status::BUFFER_TOO_SMALL, required))
+    }
+}
+
+/// Decodes an UCS-2 string into a newly allocated `String`
+///
+/// This follows the same rules as `decode`, i.e. decoding stops at the first
+/// null character and surrogates are replaced with `U+FFFD`.
+#[cfg(feature = "exts")]
+pub fn decode_to_string(input: &[u16]) -> String {
+    decode_chars(input).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResultExt;
+
+    extern crate std;
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// Strings covering the whole Basic Multilingual Plane, except for the
+    /// surrogates and the null character.
+    fn bmp_strings() -> impl Iterator<Item = String> {
+        (1..0x1_0000u32)
+            .filter_map(core::char::from_u32)
+            .collect::<Vec<_>>()
+            .chunks(97)
+            .map(|chunk| chunk.iter().collect())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn encode_str_exact_fit() {
+        let mut buffer = [0xffff; 4];
+        assert_eq!(encode_str("aé€", &mut buffer).unwrap_success(), 4);
+        assert_eq!(buffer, [0x61, 0xe9, 0x20ac, 0]);
+    }
+
+    #[test]
+    fn encode_str_one_short() {
+        let mut buffer = [0xffff; 3];
+        let err = encode_str("aé€", &mut buffer).unwrap_err();
+        assert_eq!(err.status(), Status::BUFFER_TOO_SMALL);
+        assert_eq!(*err.data(), Some(4));
+
+        // The required size does not depend on how short the buffer is.
+        for size in 0..3 {
+            let err = encode_str("aé€", &mut buffer[..size]).unwrap_err();
+            assert_eq!(*err.data(), Some(4));
+        }
+    }
+
+    #[test]
+    fn encode_str_empty() {
+        let mut buffer = [0xffff; 2];
+        assert_eq!(encode_str("", &mut buffer).unwrap_success(), 1);
+        assert_eq!(buffer, [0, 0xffff]);
+        assert_eq!(encode_str("", &mut buffer[..1]).unwrap_success(), 1);
+
+        let err = encode_str("", &mut []).unwrap_err();
+        assert_eq!(err.status(), Status::BUFFER_TOO_SMALL);
+        assert_eq!(*err.data(), Some(1));
+    }
+
+    #[test]
+    fn encode_str_rejects_nul() {
+        let mut buffer = [0; 8];
+        for &input in &["\0", "a\0", "a\0b"] {
+            let err = encode_str(input, &mut buffer).unwrap_err();
+            assert_eq!(err.status(), Status::INVALID_PARAMETER);
+            assert_eq!(*err.data(), None);
+        }
+    }
+
+    #[test]
+    fn encode_str_rejects_non_bmp() {
+        let mut buffer = [0; 8];
+        for &input in &["\u{10000}", "a🦀", "a\u{10ffff}b"] {
+            let err = encode_str(input, &mut buffer).unwrap_err();
+            assert_eq!(err.status(), Status::UNSUPPORTED);
+            assert_eq!(*err.data(), None);
+        }
+        // Even when the buffer is too small, the invalid character wins.
+        let err = encode_str("abc🦀", &mut buffer[..2]).unwrap_err();
+        assert_eq!(err.status(), Status::UNSUPPORTED);
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        for input in bmp_strings() {
+            let mut ucs2 = [0; 98];
+            let len = encode_str(&input, &mut ucs2).unwrap_success();
+            assert_eq!(len, input.chars().count() + 1);
+
+            let mut utf8 = [0; 97 * 3];
+            let len = decode(&ucs2, &mut utf8).unwrap_success();
+            assert_eq!(&utf8[..len], input.as_bytes());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "exts")]
+    fn decode_to_string_round_trip() {
+        for input in bmp_strings() {
+            let ucs2: Vec<u16> = input.encode_utf16().chain(Some(0)).collect();
+            assert_eq!(decode_to_string(&ucs2), input);
+        }
+    }
+
+    #[test]
+    fn decode_reports_required_size() {
+        for input in bmp_strings().take(64) {
+            let ucs2: Vec<u16> = input.encode_utf16().collect();
+            let mut utf8 = [0; 97 * 3];
+            for size in 0..input.len() {
+                let err = decode(&ucs2, &mut utf8[..size]).unwrap_err();
+                assert_eq!(err.status(), Status::BUFFER_TOO_SMALL);
+                assert_eq!(*err.data(), input.len());
+            }
+            let len = decode(&ucs2, &mut utf8[..input.len()]).unwrap_success();
+            assert_eq!(len, input.len());
+        }
+    }
+
+    #[test]
+    fn decode_stops_at_nul() {
+        let mut utf8 = [0; 8];
+        assert_eq!(decode(&[], &mut utf8).unwrap_success(), 0);
+        assert_eq!(decode(&[0], &mut []).unwrap_success(), 0);
+        assert_eq!(decode(&[0x41, 0, 0x42], &mut utf8).unwrap_success(), 1);
+        assert_eq!(&utf8[..1], b"A");
+        assert_eq!(decode(&[0x41, 0x42], &mut utf8).unwrap_success(), 2);
+        assert_eq!(&utf8[..2], b"AB");
+    }
+
+    #[test]
+    fn decode_replaces_surrogates() {
+        let mut utf8 = [0; 8];
+        for &surrogate in &[0xd800, 0xdbff, 0xdc00, 0xdfff] {
+            let len = decode(&[0x61, surrogate, 0x62], &mut utf8).unwrap_success();
+            assert_eq!(&utf8[..len], "a\u{fffd}b".as_bytes());
+        }
+        // Even a valid UTF-16 surrogate pair is two invalid UCS-2 characters.
+        let len = decode(&[0xd83e, 0xdd80], &mut utf8).unwrap_success();
+        assert_eq!(&utf8[..len], "\u{fffd}\u{fffd}".as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "exts")]
+    fn decode_to_string_stops_at_nul() {
+        assert_eq!(decode_to_string(&[]), "");
+        assert_eq!(decode_to_string(&[0x41, 0x42, 0, 0x43]), "AB");
+        assert_eq!(decode_to_string(&[0x41, 0xd800]), "A\u{fffd}");
+    }
+}
@@ -25,8 +25,7 @@
 
 #![cfg_attr(feature = "exts", feature(allocator_api, alloc_layout_extra))]
 #![feature(auto_traits)]
-#![feature(control_flow_enum)]
-#![feature(try_trait_v2)]
+#![cfg_attr(feature = "unstable", feature(control_flow_enum, try_trait_v2))]
 #![feature(abi_efiapi)]
 #![feature(negative_impls)]
 #![feature(const_panic)]
@@ -39,13 +38,20 @@
 #[cfg(feature = "exts")]
 extern crate alloc as alloc_api;
 
+// Allows the procedural macros to refer to this crate as `::uefi`,
+// both from within the crate and from its users.
+extern crate self as uefi;
+
 #[macro_use]
 pub mod data_types;
 pub use self::data_types::{unsafe_guid, Identify};
 pub use self::data_types::{CStr16, CStr8, Char16, Char8, Event, Guid, Handle};
 
+#[cfg(feature = "exts")]
+pub use self::data_types::CString16;
+
 mod result;
-pub use self::result::{Completion, Result, ResultExt, Status};
+pub use self::result::{Completion, Error, Result, ResultExt, Status};
 
 pub mod table;
 
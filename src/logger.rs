@@ -4,6 +4,15 @@
 //! The main export of this module is the `Logger` structure,
 //! which implements the `log` crate's trait `Log`.
 //!
+//! The messages can also be written to a debug port or to a serial device,
+//! so that they can still be read when the console is not visible, for example
+//! after switching the display to a graphics mode. Each `Sink` of a `Logger`
+//! has its own level filter, so that the console can be kept quiet while the
+//! serial device receives all messages. `SerialLogger` writes to a serial
+//! device on its own. Once the boot services are exited, the messages can still
+//! be kept in a `LogBuffer` in memory. The messages can be prefixed with the
+//! time elapsed since the logger was set up, to be compared with other logs.
+//!
 //! # Implementation details
 //!
 //! The implementation is not the most efficient, since there is no buffering done,
@@ -12,22 +21,62 @@
 //! The last part also means that some Unicode characters might not be
 //! supported by the UEFI console. Don't expect emoji output support.
 
-use crate::proto::console::text::Output;
+use crate::proto::console::serial::Serial;
+use crate::proto::console::text::{Color, Output};
+use crate::proto::debug::DebugPort;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::proto::timestamp::TscTimer;
+use crate::proto::timestamp::{Timestamp, TimestampProperties};
+use crate::table::boot::{BootServices, Tpl};
 
+use core::cell::{Cell, UnsafeCell};
 use core::fmt::{self, Write};
-use core::ptr::NonNull;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicUsize, Ordering};
+use core::time::Duration;
+
+/// Maximum number of sinks of a `Logger`.
+pub const MAX_SINKS: usize = 4;
 
-/// Logging implementation which writes to a UEFI output stream.
+/// Logging implementation which writes to a UEFI output stream, and to other
+/// sinks.
+///
+/// The messages are written to every sink whose level filter lets them
+/// through, in the order in which the sinks were added. As the logger may be
+/// set up before memory allocation is available, it holds at most `MAX_SINKS`
+/// sinks.
+///
+/// The sinks are written to one message at a time. When the logger knows the
+/// boot services, it raises the task priority level to `Tpl::NOTIFY` while
+/// writing, so that the messages of event notification functions are written
+/// after the current message instead of in its middle. Messages logged while
+/// the logger is in use anyway, for example from a panic in a sink, are
+/// dropped. As with the protocols it uses, the logger must not be used above
+/// `Tpl::NOTIFY`, nor from other processors than the boot processor.
 ///
 /// If this logger is used as a global logger, you must disable it using the
 /// `disable` method before exiting UEFI boot services in order to prevent
-/// undefined behaviour from inadvertent logging.
+/// undefined behaviour from inadvertent logging. Memory sinks keep receiving
+/// the messages once the logger is disabled.
 pub struct Logger {
-    writer: Option<NonNull<Output<'static>>>,
+    state: UnsafeCell<LoggerState>,
+    /// Whether the state is in use.
+    busy: AtomicBool,
+    enabled: AtomicBool,
+    boot_services: AtomicPtr<BootServices>,
+    /// Most verbose level of the sinks, as a `log::LevelFilter`.
+    max_level: AtomicUsize,
+}
+
+/// The state of a `Logger`, only accessed in `Logger::with_state`.
+struct LoggerState {
+    sinks: [Option<Sink>; MAX_SINKS],
+    timestamps: Option<Timestamps>,
 }
 
 impl Logger {
-    /// Creates a new logger.
+    /// Creates a new logger, which writes all messages to `output`, colored
+    /// by level.
     ///
     /// You must arrange for the `disable` method to be called or for this logger
     /// to be otherwise discarded before boot services are exited.
@@ -35,51 +84,253 @@ impl Logger {
     /// # Safety
     ///
     /// Undefined behaviour may occur if this logger is still active after the
-    /// application has exited the boot services stage.
-    pub unsafe fn new(output: &mut Output) -> Self {
+    /// application has exited the boot services stage. `output` must be valid
+    /// while the logger is active, and must not be used through references
+    /// while messages are logged, for example by deriving it from the
+    /// reference of another user of the output.
+    pub unsafe fn new(boot_services: &BootServices, output: *mut Output) -> Self {
+        Self::with_colors(boot_services, output, true)
+    }
+
+    /// Creates a new logger, which writes all messages to `output`, colored
+    /// by level if `colors` is true.
+    ///
+    /// Disabling the colors saves changing the attribute of the output twice
+    /// per message, which is wasted work when the output is captured to a
+    /// file.
+    ///
+    /// # Safety
+    ///
+    /// The requirements of `new` apply.
+    pub unsafe fn with_colors(
+        boot_services: &BootServices,
+        output: *mut Output,
+        colors: bool,
+    ) -> Self {
+        let logger = Self::empty();
+        logger.set_boot_services(boot_services);
+        logger.push(Sink::console(output).colors(colors));
+        logger
+    }
+
+    /// Creates a logger without sinks, which are added with `add_sink`.
+    ///
+    /// Until `set_boot_services` is called, the logger doesn't raise the task
+    /// priority level, and drops the messages of event notification functions
+    /// interrupting one of its messages.
+    pub fn empty() -> Self {
         Logger {
-            writer: NonNull::new(output as *const _ as *mut _),
+            state: UnsafeCell::new(LoggerState {
+                sinks: Default::default(),
+                timestamps: None,
+            }),
+            busy: AtomicBool::new(false),
+            enabled: AtomicBool::new(true),
+            boot_services: AtomicPtr::new(ptr::null_mut()),
+            max_level: AtomicUsize::new(log::LevelFilter::Off as usize),
+        }
+    }
+
+    /// Sets the boot services used to raise the task priority level while
+    /// writing a message.
+    ///
+    /// # Safety
+    ///
+    /// As with `new`, undefined behaviour may occur if this logger is still
+    /// active after the application has exited the boot services stage.
+    pub unsafe fn set_boot_services(&self, boot_services: &BootServices) {
+        let boot_services = boot_services as *const BootServices as *mut BootServices;
+        self.boot_services.store(boot_services, Ordering::SeqCst);
+    }
+
+    /// Adds a sink to the logger.
+    ///
+    /// The sink is given back if the logger already has `MAX_SINKS` sinks,
+    /// or if it is in use.
+    pub fn add_sink(&self, sink: Sink) -> core::result::Result<(), Sink> {
+        let mut sink = Some(sink);
+        self.with_state(|state| {
+            if let Some(slot) = state.sinks.iter_mut().find(|slot| slot.is_none()) {
+                *slot = sink.take();
+            }
+        });
+        match sink {
+            Some(sink) => Err(sink),
+            None => Ok(()),
         }
     }
 
+    /// Adds a sink to the logger, panicking if it is full.
+    fn push(&self, sink: Sink) {
+        if self.add_sink(sink).is_err() {
+            panic!("The logger can't have more than {} sinks", MAX_SINKS);
+        }
+    }
+
+    /// Also write the messages to a debug port.
+    ///
+    /// Errors of the debug port, such as timeouts when no debugger is
+    /// listening, are ignored, and the rest of the message is dropped.
+    ///
+    /// # Safety
+    ///
+    /// As with `new`, undefined behaviour may occur if this logger is still
+    /// active after the application has exited the boot services stage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the logger already has `MAX_SINKS` sinks.
+    pub unsafe fn set_debug_port(&self, debug_port: &mut DebugPort) {
+        self.push(Sink::debug_port(debug_port));
+    }
+
+    /// Also write the messages to a serial device, with a `SerialLogger`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the logger already has `MAX_SINKS` sinks.
+    pub fn set_serial(&self, serial: SerialLogger) {
+        self.push(Sink::from(serial));
+    }
+
+    /// Prefixes the messages with the time elapsed since this call, and with
+    /// a sequence number, as in `[   1.234 007 INFO ]`.
+    ///
+    /// The time is measured with the Timestamp protocol if it is installed,
+    /// and otherwise with the time stamp counter on x86, which is calibrated
+    /// for 10 milliseconds. If neither is available, the time is always 0,
+    /// but the sequence numbers still order the messages. The Timestamp
+    /// protocol is not used while the logger is disabled, so the messages
+    /// only have a sequence number then.
+    ///
+    /// # Safety
+    ///
+    /// As with `new`, undefined behaviour may occur if this logger is still
+    /// active after the application has exited the boot services stage.
+    pub unsafe fn enable_timestamps(&self, boot_services: &BootServices) {
+        let timestamps = Timestamps::new(boot_services);
+        self.with_state(|state| state.timestamps = Some(timestamps))
+            .expect("The logger is in use");
+    }
+
+    /// Sets the level of all the sinks.
+    pub fn set_level(&self, level: log::LevelFilter) {
+        self.with_state(|state| {
+            for sink in state.sinks.iter_mut().flatten() {
+                sink.level = level;
+            }
+        })
+        .expect("The logger is in use");
+    }
+
+    /// Returns the most verbose level of the sinks, which can be given to
+    /// `log::set_max_level`.
+    pub fn max_level(&self) -> log::LevelFilter {
+        LEVEL_FILTERS[self.max_level.load(Ordering::SeqCst)]
+    }
+
     /// Disable the logger
-    pub fn disable(&mut self) {
-        self.writer = None;
+    ///
+    /// The messages are only written to the memory sinks, which don't use the
+    /// boot services, until the logger is enabled again. This can be called
+    /// through the reference given to the `log` crate, from the notification
+    /// of the exit from boot services.
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::SeqCst);
+    }
+
+    /// Enables the logger again after `disable`.
+    ///
+    /// # Safety
+    ///
+    /// As with `new`, undefined behaviour may occur if the boot services were
+    /// exited.
+    pub unsafe fn enable(&self) {
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+
+    /// Runs `f` with exclusive access to the state of the logger, at
+    /// `Tpl::NOTIFY` if the boot services are known, and updates the cached
+    /// level of the sinks.
+    ///
+    /// `None` is returned if the state is already in use, which happens when
+    /// `f` is interrupted by something which logs.
+    fn with_state<R>(&self, f: impl FnOnce(&mut LoggerState) -> R) -> Option<R> {
+        let boot_services = self.boot_services.load(Ordering::SeqCst);
+        let _tpl = if !boot_services.is_null() && self.enabled.load(Ordering::SeqCst) {
+            Some(unsafe { (*boot_services).raise_tpl(Tpl::NOTIFY) })
+        } else {
+            None
+        };
+        if self.busy.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: the `busy` flag makes this the only reference to the state.
+        let state = unsafe { &mut *self.state.get() };
+        let result = f(state);
+        let max_level = state
+            .sinks
+            .iter()
+            .flatten()
+            .map(|sink| sink.level)
+            .max()
+            .unwrap_or(log::LevelFilter::Off);
+        self.max_level.store(max_level as usize, Ordering::SeqCst);
+        self.busy.store(false, Ordering::Release);
+        Some(result)
     }
 }
 
-impl<'boot> log::Log for Logger {
-    fn enabled(&self, _metadata: &log::Metadata) -> bool {
-        self.writer.is_some()
+/// The level filters, indexed by their value.
+const LEVEL_FILTERS: [log::LevelFilter; 6] = [
+    log::LevelFilter::Off,
+    log::LevelFilter::Error,
+    log::LevelFilter::Warn,
+    log::LevelFilter::Info,
+    log::LevelFilter::Debug,
+    log::LevelFilter::Trace,
+];
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.max_level()
     }
 
     fn log(&self, record: &log::Record) {
-        if let Some(mut ptr) = self.writer {
-            let writer = unsafe { ptr.as_mut() };
-            let result = DecoratedLog::write(
-                writer,
-                record.level(),
-                record.args(),
-                record.file().unwrap_or("<unknown file>"),
-                record.line().unwrap_or(0),
-            );
-
-            // Some UEFI implementations, such as the one used by VirtualBox,
-            // may intermittently drop out some text from SimpleTextOutput and
-            // report an EFI_DEVICE_ERROR. This will be reported here as an
-            // `fmt::Error`, and given how the `log` crate is designed, our main
-            // choices when that happens are to ignore the error or panic.
-            //
-            // Ignoring errors is bad, especially when they represent loss of
-            // precious early-boot system diagnosis data, so we panic by
-            // default. But if you experience this problem and want your UEFI
-            // application to keep running when it happens, you can enable the
-            // `ignore-logger-error` cargo feature. If you do so, logging errors
-            // will be ignored by `uefi-rs` instead.
-            //
-            if !cfg!(feature = "ignore-logger-errors") {
-                result.unwrap()
+        let enabled = self.enabled.load(Ordering::SeqCst);
+        let result = self.with_state(|state| {
+            // Errors of the console are only reported once the message was
+            // written to the other sinks.
+            let stamp = state
+                .timestamps
+                .as_ref()
+                .map(|timestamps| timestamps.next(enabled));
+            let mut result = Ok(());
+            for sink in state.sinks.iter().flatten() {
+                if (enabled || !sink.uses_boot_services()) && record.level() <= sink.level {
+                    result = result.and(sink.write(record, stamp));
+                }
             }
+            result
+        });
+        // If the logger is in use, the message is dropped.
+        let result = result.unwrap_or(Ok(()));
+
+        // Some UEFI implementations, such as the one used by VirtualBox,
+        // may intermittently drop out some text from SimpleTextOutput and
+        // report an EFI_DEVICE_ERROR. This will be reported here as an
+        // `fmt::Error`, and given how the `log` crate is designed, our main
+        // choices when that happens are to ignore the error or panic.
+        //
+        // Ignoring errors is bad, especially when they represent loss of
+        // precious early-boot system diagnosis data, so we panic by
+        // default. But if you experience this problem and want your UEFI
+        // application to keep running when it happens, you can enable the
+        // `ignore-logger-error` cargo feature. If you do so, logging errors
+        // will be ignored by `uefi-rs` instead.
+        //
+        if !cfg!(feature = "ignore-logger-errors") {
+            result.unwrap()
         }
     }
 
@@ -88,10 +339,442 @@ impl<'boot> log::Log for Logger {
     }
 }
 
-// The logger is not thread-safe, but the UEFI boot environment only uses one processor.
+// SAFETY: the state of the logger, including the pointers to the protocols of
+// the sinks, is only accessed with the `busy` flag set, which is an atomic
+// flag. If the logger is used while it is in use, for example by an event
+// notification function interrupting a message, or by another processor, the
+// message is dropped instead of accessing the state.
 unsafe impl Sync for Logger {}
 unsafe impl Send for Logger {}
 
+/// A destination of the messages of a `Logger`, with its own level filter
+///
+/// Sinks let all messages through by default, and `level` restricts them to
+/// the messages of a level and the more important ones.
+pub struct Sink {
+    kind: SinkKind,
+    level: log::LevelFilter,
+}
+
+enum SinkKind {
+    Console {
+        output: NonNull<Output<'static>>,
+        colors: bool,
+    },
+    Serial(SerialLogger),
+    DebugPort(NonNull<DebugPort>),
+    Memory(&'static LogBuffer),
+}
+
+impl Sink {
+    /// Creates a sink writing to a UEFI text output protocol, such as the
+    /// console.
+    ///
+    /// The messages are colored by level: errors in red, warnings in yellow,
+    /// and debug and trace messages in dark grey, after which the previous
+    /// colors of the output are restored. If the output fails to change
+    /// colors, the messages are written uncolored. Other errors of the output
+    /// make the logger panic, unless the `ignore-logger-errors` feature is
+    /// enabled.
+    ///
+    /// # Safety
+    ///
+    /// Undefined behaviour may occur if the logger is still active after the
+    /// application has exited the boot services stage. As with `Logger::new`,
+    /// `output` must be valid while the logger is active, and must not be
+    /// derived from the references of other users of the output.
+    pub unsafe fn console(output: *mut Output) -> Self {
+        let output = NonNull::new(output.cast()).expect("The output must not be null");
+        Self::with_kind(SinkKind::Console {
+            output,
+            colors: true,
+        })
+    }
+
+    /// Creates a sink writing to the serial device, with a `SerialLogger`.
+    ///
+    /// # Safety
+    ///
+    /// As with `console`, undefined behaviour may occur if the logger is
+    /// still active after the application has exited the boot services stage.
+    pub unsafe fn serial(boot_services: &BootServices) -> Self {
+        Self::from(SerialLogger::new(boot_services))
+    }
+
+    /// Creates a sink writing to a debug port, whose errors are ignored.
+    ///
+    /// # Safety
+    ///
+    /// As with `console`, undefined behaviour may occur if the logger is
+    /// still active after the application has exited the boot services stage.
+    pub unsafe fn debug_port(debug_port: &mut DebugPort) -> Self {
+        Self::with_kind(SinkKind::DebugPort(NonNull::from(debug_port)))
+    }
+
+    /// Creates a sink writing to a ring buffer in memory.
+    ///
+    /// Unlike the other sinks, memory sinks still receive the messages when
+    /// the logger is disabled, so that the messages logged after exiting the
+    /// boot services can be read later.
+    pub fn memory(buffer: &'static LogBuffer) -> Self {
+        Self::with_kind(SinkKind::Memory(buffer))
+    }
+
+    fn with_kind(kind: SinkKind) -> Self {
+        Sink {
+            kind,
+            level: log::LevelFilter::Trace,
+        }
+    }
+
+    /// Sets the most verbose level of the messages written to this sink.
+    pub fn level(mut self, level: log::LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets whether the messages written to a console sink are colored by
+    /// level. This has no effect on the other sinks.
+    pub fn colors(mut self, enabled: bool) -> Self {
+        if let SinkKind::Console { ref mut colors, .. } = self.kind {
+            *colors = enabled;
+        }
+        self
+    }
+
+    /// Returns whether the sink uses the boot services, and must not be
+    /// written to when the logger is disabled.
+    fn uses_boot_services(&self) -> bool {
+        !matches!(self.kind, SinkKind::Memory(_))
+    }
+
+    /// Writes a record, returning the errors which must be reported.
+    fn write(&self, record: &log::Record, stamp: Option<Stamp>) -> fmt::Result {
+        match self.kind {
+            SinkKind::Console { output, colors } => {
+                let output = unsafe { &mut *output.as_ptr() };
+                let color = if colors {
+                    level_color(record.level())
+                } else {
+                    None
+                };
+                let (foreground, background) = output.color();
+                // Errors of SetAttribute are ignored, printing the message
+                // uncolored.
+                let colored = match color {
+                    Some(color) => output.set_color(color, background).is_ok(),
+                    None => false,
+                };
+                let result = write_record(output, record, stamp);
+                if colored {
+                    let _ = output.set_color(foreground, background);
+                }
+                result
+            }
+            SinkKind::Serial(ref serial) => {
+                serial.write(record, stamp);
+                Ok(())
+            }
+            SinkKind::DebugPort(ptr) => {
+                let _ = write_record(unsafe { &mut *ptr.as_ptr() }, record, stamp);
+                Ok(())
+            }
+            SinkKind::Memory(buffer) => {
+                let _ = write_record(&mut LogBufferWriter(buffer), record, stamp);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<SerialLogger> for Sink {
+    fn from(serial: SerialLogger) -> Self {
+        Self::with_kind(SinkKind::Serial(serial))
+    }
+}
+
+/// Ring buffer in memory, keeping the last bytes of the messages written to a
+/// memory sink
+///
+/// ```
+/// use log::Log;
+/// use uefi::logger::{LogBuffer, Logger, Sink};
+///
+/// let buffer = Box::leak(Box::new(LogBuffer::new(Box::leak(Box::new([0; 16])))));
+/// let logger = Logger::empty();
+/// logger.add_sink(Sink::memory(buffer)).ok().unwrap();
+///
+/// // Memory sinks are still written to once the logger is disabled.
+/// logger.disable();
+/// logger.log(
+///     &log::Record::builder()
+///         .level(log::Level::Info)
+///         .args(format_args!("late message"))
+///         .build(),
+/// );
+/// assert!(buffer.written() > 16);
+/// let mut contents = [0; 32];
+/// let len = buffer.copy_to(&mut contents);
+/// assert_eq!(&contents[..len], b"0: late message\n");
+/// ```
+pub struct LogBuffer {
+    data: NonNull<u8>,
+    capacity: usize,
+    written: Cell<usize>,
+}
+
+impl LogBuffer {
+    /// Creates a ring buffer storing the messages in `buffer`.
+    pub fn new(buffer: &'static mut [u8]) -> Self {
+        LogBuffer {
+            capacity: buffer.len(),
+            data: NonNull::from(buffer).cast(),
+            written: Cell::new(0),
+        }
+    }
+
+    /// Returns the number of bytes written to the buffer since it was created,
+    /// including the ones which were overwritten since.
+    pub fn written(&self) -> usize {
+        self.written.get()
+    }
+
+    /// Copies the last bytes written to the buffer to `out`, from the oldest
+    /// to the newest, and returns the number of bytes copied.
+    pub fn copy_to(&self, out: &mut [u8]) -> usize {
+        let written = self.written.get();
+        let len = written.min(self.capacity).min(out.len());
+        let start = written - len;
+        for (i, byte) in out[..len].iter_mut().enumerate() {
+            *byte = unsafe { self.data.as_ptr().add((start + i) % self.capacity).read() };
+        }
+        len
+    }
+
+    /// Writes bytes to the buffer, overwriting the oldest ones when full.
+    fn push(&self, bytes: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        for &byte in bytes {
+            let position = self.written.get();
+            unsafe { self.data.as_ptr().add(position % self.capacity).write(byte) };
+            self.written.set(position + 1);
+        }
+    }
+}
+
+// SAFETY: the buffer is written by the memory sinks of `Logger`, which only
+// write one message at a time. `copy_to` reads it byte by byte, and must not
+// be interrupted by messages, which the boot environment doesn't do outside
+// of event notification functions.
+unsafe impl Sync for LogBuffer {}
+unsafe impl Send for LogBuffer {}
+
+/// Writer to a `LogBuffer`.
+struct LogBufferWriter<'a>(&'a LogBuffer);
+
+impl<'a> fmt::Write for LogBufferWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Returns the color of the messages of a level, or `None` for the current
+/// color of the console.
+fn level_color(level: log::Level) -> Option<Color> {
+    match level {
+        log::Level::Error => Some(Color::LightRed),
+        log::Level::Warn => Some(Color::Yellow),
+        log::Level::Info => None,
+        log::Level::Debug | log::Level::Trace => Some(Color::DarkGray),
+    }
+}
+
+/// Writes a record, with its level and location, to `writer`.
+fn write_record<W: fmt::Write>(
+    writer: &mut W,
+    record: &log::Record,
+    stamp: Option<Stamp>,
+) -> fmt::Result {
+    DecoratedLog::write(
+        writer,
+        record.level(),
+        record.args(),
+        record.file().unwrap_or("<unknown file>"),
+        record.line().unwrap_or(0),
+        stamp,
+    )
+}
+
+/// Time and sequence number of a message.
+#[derive(Clone, Copy)]
+struct Stamp {
+    /// Time since the timestamps were enabled, if the clock could be read.
+    time: Option<Duration>,
+    sequence: u32,
+}
+
+/// Clock and message counter of a `Logger` with timestamps.
+struct Timestamps {
+    clock: Option<Clock>,
+    start: u64,
+    sequence: AtomicU32,
+}
+
+enum Clock {
+    Timestamp(NonNull<Timestamp>, TimestampProperties),
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Tsc(TscTimer),
+}
+
+impl Timestamps {
+    unsafe fn new(boot_services: &BootServices) -> Self {
+        let timestamp = boot_services
+            .locate_protocol::<Timestamp>()
+            .ok()
+            .map(|timestamp| timestamp.log().get())
+            .and_then(NonNull::new)
+            .and_then(|timestamp| {
+                let properties = timestamp.as_ref().get_properties().ok()?.log();
+                Some(Clock::Timestamp(timestamp, properties))
+            });
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        let timestamp = timestamp.or_else(|| Some(Clock::Tsc(TscTimer::calibrate(boot_services))));
+        let mut timestamps = Timestamps {
+            clock: timestamp,
+            start: 0,
+            sequence: AtomicU32::new(0),
+        };
+        timestamps.start = timestamps.ticks();
+        timestamps
+    }
+
+    /// Returns the current value of the clock.
+    fn ticks(&self) -> u64 {
+        match self.clock {
+            Some(Clock::Timestamp(timestamp, _)) => unsafe { timestamp.as_ref() }.get_timestamp(),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Some(Clock::Tsc(ref tsc)) => tsc.get_timestamp(),
+            None => 0,
+        }
+    }
+
+    /// Returns the stamp of the next message.
+    ///
+    /// The Timestamp protocol is only read if `boot_services` is true, as the
+    /// protocol is gone once the boot services are exited.
+    fn next(&self, boot_services: bool) -> Stamp {
+        let time = match self.clock {
+            Some(Clock::Timestamp(_, properties)) if boot_services => {
+                Some(properties.elapsed(self.start, self.ticks()))
+            }
+            Some(Clock::Timestamp(..)) => None,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Some(Clock::Tsc(ref tsc)) => {
+                Some(tsc.get_properties().elapsed(self.start, self.ticks()))
+            }
+            None => Some(Duration::from_secs(0)),
+        };
+        Stamp {
+            time,
+            sequence: self.sequence.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+}
+
+/// Logging implementation which writes to a serial device.
+///
+/// The serial device is located with the boot services when the first message
+/// is logged, and messages are dropped if there is none. Errors of the device,
+/// such as timeouts when nothing reads the other end of the line, drop the rest
+/// of the message instead of panicking.
+///
+/// Like `Logger`, this logger must be disabled using the `disable` method
+/// before exiting UEFI boot services.
+pub struct SerialLogger {
+    boot_services: Option<NonNull<BootServices>>,
+    serial: Cell<SerialState>,
+}
+
+/// Whether the serial device of a `SerialLogger` was located.
+#[derive(Clone, Copy)]
+enum SerialState {
+    Unknown,
+    Missing,
+    Found(NonNull<Serial<'static>>),
+}
+
+impl SerialLogger {
+    /// Creates a new serial logger, which locates the serial device using
+    /// `boot_services`.
+    ///
+    /// # Safety
+    ///
+    /// Undefined behaviour may occur if this logger is still active after the
+    /// application has exited the boot services stage.
+    pub unsafe fn new(boot_services: &BootServices) -> Self {
+        SerialLogger {
+            boot_services: NonNull::new(boot_services as *const _ as *mut _),
+            serial: Cell::new(SerialState::Unknown),
+        }
+    }
+
+    /// Disable the logger
+    pub fn disable(&mut self) {
+        self.boot_services = None;
+        self.serial.set(SerialState::Missing);
+    }
+
+    /// Writes a record, ignoring the errors of the device.
+    fn write(&self, record: &log::Record, stamp: Option<Stamp>) {
+        if let Some(mut ptr) = self.serial() {
+            let _ = write_record(unsafe { ptr.as_mut() }, record, stamp);
+        }
+    }
+
+    /// Returns the serial device, locating it if needed.
+    fn serial(&self) -> Option<NonNull<Serial<'static>>> {
+        match self.serial.get() {
+            SerialState::Found(serial) => Some(serial),
+            SerialState::Missing => None,
+            SerialState::Unknown => {
+                let boot_services = unsafe { self.boot_services?.as_ref() };
+                let state = match boot_services.locate_protocol::<Serial>() {
+                    Ok(serial) => match NonNull::new(serial.log().get()) {
+                        Some(serial) => SerialState::Found(serial.cast()),
+                        None => SerialState::Missing,
+                    },
+                    Err(_) => SerialState::Missing,
+                };
+                self.serial.set(state);
+                self.serial()
+            }
+        }
+    }
+}
+
+impl log::Log for SerialLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        self.boot_services.is_some()
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.write(record, None);
+    }
+
+    fn flush(&self) {
+        // This simple logger does not buffer output.
+    }
+}
+
+// SAFETY: inside a `Logger`, the serial logger is only used one message at a
+// time. Used on its own, it relies on the boot services only running on one
+// processor, and must not be used from event notification functions.
+unsafe impl Sync for SerialLogger {}
+unsafe impl Send for SerialLogger {}
+
 /// Writer wrapper which prints a log level in front of every line of text
 ///
 /// This is less easy than it sounds because...
@@ -109,6 +792,7 @@ struct DecoratedLog<'writer, 'a, W: fmt::Write> {
     at_line_start: bool,
     file: &'a str,
     line: u32,
+    stamp: Option<Stamp>,
 }
 
 impl<'writer, 'a, W: fmt::Write> DecoratedLog<'writer, 'a, W> {
@@ -119,6 +803,7 @@ impl<'writer, 'a, W: fmt::Write> DecoratedLog<'writer, 'a, W> {
         args: &fmt::Arguments,
         file: &'a str,
         line: u32,
+        stamp: Option<Stamp>,
     ) -> fmt::Result {
         let mut decorated_writer = Self {
             writer,
@@ -126,6 +811,7 @@ impl<'writer, 'a, W: fmt::Write> DecoratedLog<'writer, 'a, W> {
             at_line_start: true,
             file,
             line,
+            stamp,
         };
         writeln!(decorated_writer, "{}", *args)
     }
@@ -141,11 +827,30 @@ impl<'writer, 'a, W: fmt::Write> fmt::Write for DecoratedLog<'writer, 'a, W> {
         // beginning of a line of output.
         let first = lines.next().unwrap_or("");
         if self.at_line_start {
-            write!(
-                self.writer,
-                "[{:>5}]: {:>12}@{:03}: ",
-                self.log_level, self.file, self.line
-            )?;
+            match self.stamp {
+                Some(Stamp {
+                    time: Some(time),
+                    sequence,
+                }) => write!(
+                    self.writer,
+                    "[{:>4}.{:03} {:03} {:<5}]: ",
+                    time.as_secs(),
+                    time.subsec_millis(),
+                    sequence,
+                    self.log_level
+                )?,
+                // The time is left blank, keeping the columns aligned.
+                Some(Stamp {
+                    time: None,
+                    sequence,
+                }) => write!(
+                    self.writer,
+                    "[{:>8} {:03} {:<5}]: ",
+                    "", sequence, self.log_level
+                )?,
+                None => write!(self.writer, "[{:>5}]: ", self.log_level)?,
+            }
+            write!(self.writer, "{:>12}@{:03}: ", self.file, self.line)?;
             self.at_line_start = false;
         }
         write!(self.writer, "{}", first)?;
@@ -10,4 +10,78 @@ pub use crate::table::runtime::RuntimeServices;
 pub use crate::table::{Boot, SystemTable};
 
 // Import the macro for creating the custom entry point.
+/// Attribute exporting the entry point of a UEFI application
+///
+/// ```no_run
+/// #![feature(abi_efiapi)]
+/// use uefi::prelude::*;
+///
+/// #[entry]
+/// fn app_main(_image: Handle, _st: SystemTable<Boot>) -> Status {
+///     Status::SUCCESS
+/// }
+/// ```
+///
+/// With `#[entry(init)]`, `uefi_services::init` is called before the body of
+/// the function.
+///
+/// Functions with another signature are rejected:
+///
+/// ```compile_fail
+/// #![feature(abi_efiapi)]
+/// use uefi::prelude::*;
+///
+/// #[entry]
+/// fn app_main(_image: Handle) -> Status {
+///     Status::SUCCESS
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #![feature(abi_efiapi)]
+/// use uefi::prelude::*;
+///
+/// #[entry]
+/// fn app_main(_image: Handle, _st: &SystemTable<Boot>) -> Status {
+///     Status::SUCCESS
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #![feature(abi_efiapi)]
+/// use uefi::prelude::*;
+///
+/// #[entry]
+/// fn app_main(_image: Handle, _st: SystemTable<Boot>) {}
+/// ```
+///
+/// ```compile_fail
+/// #![feature(abi_efiapi)]
+/// use uefi::prelude::*;
+///
+/// #[entry]
+/// unsafe fn app_main(_image: Handle, _st: SystemTable<Boot>) -> Status {
+///     Status::SUCCESS
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #![feature(abi_efiapi)]
+/// use uefi::prelude::*;
+///
+/// #[entry]
+/// fn app_main<T>(_image: Handle, _st: SystemTable<Boot>) -> Status {
+///     Status::SUCCESS
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #![feature(abi_efiapi)]
+/// use uefi::prelude::*;
+///
+/// #[entry(services)]
+/// fn app_main(_image: Handle, _st: SystemTable<Boot>) -> Status {
+///     Status::SUCCESS
+/// }
+/// ```
 pub use uefi_macros::entry;
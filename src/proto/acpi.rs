@@ -0,0 +1,96 @@
+//! ACPI Table protocol.
+//!
+//! This protocol installs ACPI tables, such as an SSDT or a BGRT, and
+//! updates the RSDT, XSDT and checksums of the firmware accordingly. It
+//! should be used instead of patching the XSDT in place.
+
+use crate::proto::Protocol;
+use crate::{Guid, Identify, Result, Status};
+use core::convert::TryInto;
+
+/// Size of the header shared by the ACPI tables.
+pub const TABLE_HEADER_SIZE: usize = 36;
+
+/// Offset of the checksum in the header of ACPI tables.
+const CHECKSUM_OFFSET: usize = 9;
+
+/// Key identifying a table installed by `AcpiTable::install_acpi_table`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct TableKey(usize);
+
+/// Sets the checksum field of an ACPI table, so that the sum of its bytes
+/// is zero.
+///
+/// The table must contain at least its header.
+///
+/// ```
+/// use uefi::proto::acpi::{set_checksum, TABLE_HEADER_SIZE};
+///
+/// let mut table = [0; TABLE_HEADER_SIZE];
+/// table[..4].copy_from_slice(b"SSDT");
+/// table[4] = TABLE_HEADER_SIZE as u8;
+/// set_checksum(&mut table);
+/// assert_eq!(table.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)), 0);
+/// ```
+pub fn set_checksum(table: &mut [u8]) {
+    table[CHECKSUM_OFFSET] = 0;
+    let sum = table.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    table[CHECKSUM_OFFSET] = sum.wrapping_neg();
+}
+
+/// The ACPI Table protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct AcpiTable {
+    install_acpi_table: unsafe extern "efiapi" fn(
+        this: &AcpiTable,
+        acpi_table_buffer: *const u8,
+        acpi_table_buffer_size: usize,
+        table_key: &mut TableKey,
+    ) -> Status,
+    uninstall_acpi_table: extern "efiapi" fn(this: &AcpiTable, table_key: TableKey) -> Status,
+}
+
+unsafe impl Identify for AcpiTable {
+    const GUID: Guid = guid!("ffe06bdd-6107-46a6-7bb2-5a9c7ec5275c");
+}
+
+impl AcpiTable {
+    /// Installs a copy of an ACPI table, and returns the key needed to
+    /// uninstall it.
+    ///
+    /// The firmware recomputes the checksum of the table, but the table is
+    /// checked before submitting it, to catch mistakes early:
+    /// `BAD_BUFFER_SIZE` is returned if the length in its header is not the
+    /// length of `table`, and `CRC_ERROR` if its checksum is wrong. The
+    /// checksum can be set with `set_checksum`. The FACS has no checksum,
+    /// and only its length is checked. `ACCESS_DENIED` is returned if a
+    /// table which can only be installed once, such as the FADT, is already
+    /// installed.
+    pub fn install_acpi_table(&self, table: &[u8]) -> Result<TableKey> {
+        if table.len() < TABLE_HEADER_SIZE {
+            return Err(Status::BAD_BUFFER_SIZE.into());
+        }
+        let length = u32::from_le_bytes(table[4..8].try_into().unwrap());
+        if length as usize != table.len() {
+            return Err(Status::BAD_BUFFER_SIZE.into());
+        }
+        let sum = table.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        if !table.starts_with(b"FACS") && sum != 0 {
+            return Err(Status::CRC_ERROR.into());
+        }
+
+        let mut key = TableKey(0);
+        unsafe { (self.install_acpi_table)(self, table.as_ptr(), table.len(), &mut key) }
+            .into_with_val(|| key)
+    }
+
+    /// Uninstalls a table installed by `install_acpi_table`.
+    ///
+    /// `NOT_FOUND` is returned if the key does not identify an installed
+    /// table.
+    pub fn uninstall_acpi_table(&self, key: TableKey) -> Result {
+        (self.uninstall_acpi_table)(self, key).into()
+    }
+}
@@ -0,0 +1,264 @@
+//! Adapter Information protocol.
+//!
+//! This protocol is installed by the drivers of network interface cards and
+//! host bus adapters, to report information such as the state of the media
+//! or the boot capabilities of the adapter. Each kind of information is a
+//! block identified by a GUID. The standard ones can be viewed through the
+//! types implementing `InformationType`, and the others as raw bytes.
+
+use crate::data_types::MacAddress;
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{Completion, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+use core::{fmt, mem, ptr, slice};
+
+/// A standard kind of information, which can be viewed as this type.
+///
+/// # Safety
+///
+/// The type must have the layout of the information block identified by
+/// `GUID`.
+pub unsafe trait InformationType: Sized {
+    /// GUID identifying this kind of information.
+    const GUID: Guid;
+}
+
+/// State of the media attached to a network adapter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct MediaState {
+    /// `SUCCESS` if the media is attached, `NO_MEDIA` if it is not, and
+    /// `NOT_READY` if it is attached but the adapter is not ready yet, for
+    /// example because it is still negotiating the link.
+    pub media_state: Status,
+}
+
+unsafe impl InformationType for MediaState {
+    const GUID: Guid = guid!("d7c74207-a831-4a26-b1f5-d193065ce8b6");
+}
+
+/// Network boot capabilities of an adapter, and the boot methods which are
+/// enabled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct NetworkBoot {
+    /// The adapter can boot from iSCSI over IPv4.
+    pub iscsi_ipv4_boot_capability: bool,
+    /// The adapter can boot from iSCSI over IPv6.
+    pub iscsi_ipv6_boot_capability: bool,
+    /// The adapter can boot from FCoE.
+    pub fcoe_boot_capability: bool,
+    /// The adapter offloads TCP, iSCSI or FCoE.
+    pub offload_capability: bool,
+    /// The adapter supports iSCSI multipath I/O.
+    pub iscsi_mpio_capability: bool,
+    /// Booting from iSCSI over IPv4 is enabled.
+    pub iscsi_ipv4_boot: bool,
+    /// Booting from iSCSI over IPv6 is enabled.
+    pub iscsi_ipv6_boot: bool,
+    /// Booting from FCoE is enabled.
+    pub fcoe_boot: bool,
+}
+
+unsafe impl InformationType for NetworkBoot {
+    const GUID: Guid = guid!("1fbd2960-4130-41e5-94ac-d2cf037fb37c");
+}
+
+/// MAC address used by an adapter for storage area network traffic, such as
+/// iSCSI or FCoE, which may differ from the one of the network interface.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct SanMacAddress {
+    /// The SAN MAC address.
+    pub san_mac_address: MacAddress,
+}
+
+unsafe impl InformationType for SanMacAddress {
+    const GUID: Guid = guid!("114da5ef-2cf1-4e12-9bbb-c470b55205d9");
+}
+
+/// The kinds of information supported by an adapter, which are freed when
+/// dropped.
+pub struct SupportedTypes<'a> {
+    buffer: *mut Guid,
+    count: usize,
+    bt: &'a BootServices,
+}
+
+impl SupportedTypes<'_> {
+    /// GUIDs of the supported kinds of information.
+    pub fn as_slice(&self) -> &[Guid] {
+        if self.buffer.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.buffer, self.count) }
+        }
+    }
+
+    /// Whether information of type `T` is supported.
+    pub fn contains<T: InformationType>(&self) -> bool {
+        self.as_slice().contains(&T::GUID)
+    }
+}
+
+impl fmt::Debug for SupportedTypes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl Drop for SupportedTypes<'_> {
+    fn drop(&mut self) {
+        if !self.buffer.is_null() {
+            // Ignore the result, we can't do anything about an error here.
+            let _ = self.bt.free_pool(self.buffer.cast());
+        }
+    }
+}
+
+/// An information block returned by `AdapterInformation::get_information`,
+/// which is freed when dropped.
+pub struct InformationBlock<'a> {
+    info_type: Guid,
+    data: *mut u8,
+    size: usize,
+    bt: &'a BootServices,
+}
+
+impl InformationBlock<'_> {
+    /// GUID identifying the kind of information.
+    pub fn info_type(&self) -> Guid {
+        self.info_type
+    }
+
+    /// Raw contents of the block.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.data.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.data, self.size) }
+        }
+    }
+
+    /// Views the block as information of type `T`.
+    ///
+    /// Returns `None` if the block is of another kind, or is too small.
+    pub fn view<T: InformationType>(&self) -> Option<&T> {
+        if self.info_type != T::GUID
+            || self.size < mem::size_of::<T>()
+            || self.data.align_offset(mem::align_of::<T>()) != 0
+        {
+            return None;
+        }
+        Some(unsafe { &*self.data.cast::<T>() })
+    }
+}
+
+impl fmt::Debug for InformationBlock<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InformationBlock")
+            .field("info_type", &self.info_type)
+            .field("data", &self.as_bytes())
+            .finish()
+    }
+}
+
+impl Drop for InformationBlock<'_> {
+    fn drop(&mut self) {
+        if !self.data.is_null() {
+            // Ignore the result, we can't do anything about an error here.
+            let _ = self.bt.free_pool(self.data);
+        }
+    }
+}
+
+/// The Adapter Information protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct AdapterInformation {
+    get_information: unsafe extern "efiapi" fn(
+        this: &AdapterInformation,
+        information_type: &Guid,
+        information_block: &mut *mut c_void,
+        information_block_size: &mut usize,
+    ) -> Status,
+    set_information: unsafe extern "efiapi" fn(
+        this: &mut AdapterInformation,
+        information_type: &Guid,
+        information_block: *const c_void,
+        information_block_size: usize,
+    ) -> Status,
+    get_supported_types: unsafe extern "efiapi" fn(
+        this: &AdapterInformation,
+        info_types_buffer: &mut *mut Guid,
+        info_types_buffer_count: &mut usize,
+    ) -> Status,
+}
+
+unsafe impl Identify for AdapterInformation {
+    const GUID: Guid = guid!("e5dd1403-d622-c24e-8488-c71b17f5e802");
+}
+
+impl AdapterInformation {
+    /// Returns the kinds of information supported by the adapter.
+    pub fn get_supported_types<'bt>(&self, bt: &'bt BootServices) -> Result<SupportedTypes<'bt>> {
+        let mut buffer = ptr::null_mut();
+        let mut count = 0;
+        unsafe { (self.get_supported_types)(self, &mut buffer, &mut count) }
+            .into_with_val(|| SupportedTypes { buffer, count, bt })
+    }
+
+    /// Returns the information block identified by `info_type`.
+    ///
+    /// `UNSUPPORTED` is returned if the adapter does not support this kind
+    /// of information.
+    pub fn get_information<'bt>(
+        &self,
+        bt: &'bt BootServices,
+        info_type: &Guid,
+    ) -> Result<InformationBlock<'bt>> {
+        let mut data = ptr::null_mut();
+        let mut size = 0;
+        unsafe { (self.get_information)(self, info_type, &mut data, &mut size) }.into_with_val(
+            || InformationBlock {
+                info_type: *info_type,
+                data: data.cast(),
+                size,
+                bt,
+            },
+        )
+    }
+
+    /// Returns the information of type `T`.
+    ///
+    /// `UNSUPPORTED` is returned if the adapter does not support this kind
+    /// of information, and `DEVICE_ERROR` if the block it returned is too
+    /// small.
+    pub fn get<T: InformationType + Copy>(&self, bt: &BootServices) -> Result<T> {
+        let (status, block) = self.get_information(bt, &T::GUID)?.split();
+        let info = block.view::<T>().ok_or(Status::DEVICE_ERROR)?;
+        Ok(Completion::new(status, *info))
+    }
+
+    /// Sets the information block identified by `info_type`.
+    ///
+    /// `UNSUPPORTED` is returned if the adapter does not support this kind
+    /// of information, and `WRITE_PROTECTED` if it cannot be changed.
+    pub fn set_information(&mut self, info_type: &Guid, data: &[u8]) -> Result {
+        unsafe { (self.set_information)(self, info_type, data.as_ptr().cast(), data.len()) }.into()
+    }
+
+    /// Sets the information of type `T`.
+    pub fn set<T: InformationType>(&mut self, info: &T) -> Result {
+        unsafe {
+            (self.set_information)(
+                self,
+                &T::GUID,
+                (info as *const T).cast(),
+                mem::size_of::<T>(),
+            )
+        }
+        .into()
+    }
+}
@@ -54,8 +54,9 @@
 //! You will have to implement your own double buffering if you want to
 //! avoid tearing with animations.
 
+use crate::data_types::PhysicalAddress;
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Completion, Result, Status};
+use crate::{Completion, Guid, Identify, Result, Status};
 use core::marker::PhantomData;
 use core::mem;
 use core::ptr;
@@ -65,7 +66,6 @@ use core::ptr;
 /// The GOP can be used to set the properties of the frame buffer,
 /// and also allows the app to access the in-memory buffer.
 #[repr(C)]
-#[unsafe_guid("9042a9de-23dc-4a38-96fb-7aded080516a")]
 #[derive(Protocol)]
 pub struct GraphicsOutput<'boot> {
     query_mode: extern "efiapi" fn(
@@ -92,6 +92,10 @@ pub struct GraphicsOutput<'boot> {
     mode: &'boot ModeData<'boot>,
 }
 
+unsafe impl<'boot> Identify for GraphicsOutput<'boot> {
+    const GUID: Guid = guid!("9042a9de-23dc-4a38-96fb-7aded080516a");
+}
+
 impl<'boot> GraphicsOutput<'boot> {
     /// Returns information for an available graphics mode that the graphics
     /// device and the set of active video output devices supports.
@@ -305,7 +309,7 @@ impl<'boot> GraphicsOutput<'boot> {
             self.mode.info.format != PixelFormat::BltOnly,
             "Cannot access the framebuffer in a Blt-only mode"
         );
-        let base = self.mode.fb_address as *mut u8;
+        let base = self.mode.fb_address.as_mut_ptr();
         let size = self.mode.fb_size;
 
         FrameBuffer {
@@ -327,7 +331,7 @@ struct ModeData<'info> {
     // Size of the above structure.
     info_sz: usize,
     // Physical address of the frame buffer.
-    fb_address: u64,
+    fb_address: PhysicalAddress,
     // Size in bytes. Equal to (pixel size) * height * stride.
     fb_size: usize,
 }
@@ -582,6 +586,14 @@ impl<'gop> FrameBuffer<'gop> {
         self.base
     }
 
+    /// Query the physical address of the framebuffer
+    ///
+    /// This is useful for OS loaders which need to hand the framebuffer over
+    /// to the kernel after exiting boot services.
+    pub fn physical_address(&self) -> PhysicalAddress {
+        PhysicalAddress::new(self.base as u64)
+    }
+
     /// Query the framebuffer size in bytes
     pub fn size(&self) -> usize {
         self.size
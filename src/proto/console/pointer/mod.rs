@@ -1,12 +1,11 @@
 //! Pointer device access.
 
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Event, Result, Status};
+use crate::{Event, Guid, Identify, Result, Status};
 use core::mem::MaybeUninit;
 
 /// Provides information about a pointer device.
 #[repr(C)]
-#[unsafe_guid("31878c87-0b75-11d5-9a4f-0090273fc14d")]
 #[derive(Protocol)]
 pub struct Pointer<'boot> {
     reset: extern "efiapi" fn(this: &mut Pointer, ext_verif: bool) -> Status,
@@ -15,6 +14,10 @@ pub struct Pointer<'boot> {
     mode: &'boot PointerMode,
 }
 
+unsafe impl<'boot> Identify for Pointer<'boot> {
+    const GUID: Guid = guid!("31878c87-0b75-11d5-9a4f-0090273fc14d");
+}
+
 impl<'boot> Pointer<'boot> {
     /// Resets the pointer device hardware.
     ///
@@ -47,8 +50,8 @@ impl<'boot> Pointer<'boot> {
 
     /// Event to be used with `BootServices::wait_for_event()` in order to wait
     /// for input from the pointer device
-    pub fn wait_for_input_event(&self) -> Event {
-        self.wait_for_input
+    pub fn wait_for_input_event(&self) -> &Event {
+        &self.wait_for_input
     }
 
     /// Returns a reference to the pointer device information.
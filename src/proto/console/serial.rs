@@ -1,8 +1,9 @@
 //! Abstraction over byte stream devices, also known as serial I/O devices.
 
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Result, Status};
+use crate::{Guid, Identify, Result, ResultExt, Status};
 use bitflags::bitflags;
+use core::fmt;
 
 /// Provides access to a serial I/O device.
 ///
@@ -12,7 +13,6 @@ use bitflags::bitflags;
 /// Since UEFI drivers are implemented through polling, if you fail to regularly
 /// check for input/output, some data might be lost.
 #[repr(C)]
-#[unsafe_guid("bb25cf6f-f1d4-11d2-9a0c-0090273fc1fd")]
 #[derive(Protocol)]
 pub struct Serial<'boot> {
     // Revision of this protocol, only 1.0 is currently defined.
@@ -35,6 +35,10 @@ pub struct Serial<'boot> {
     io_mode: &'boot IoMode,
 }
 
+unsafe impl<'boot> Identify for Serial<'boot> {
+    const GUID: Guid = guid!("bb25cf6f-f1d4-11d2-9a0c-0090273fc1fd");
+}
+
 impl<'boot> Serial<'boot> {
     /// Reset the device.
     pub fn reset(&mut self) -> Result {
@@ -113,6 +117,29 @@ impl<'boot> Serial<'boot> {
     }
 }
 
+/// Writes text to the device, converting Rust line feeds to the line endings
+/// expected by terminals.
+///
+/// Errors of the device, such as timeouts when the device is not ready, are
+/// reported as `fmt::Error`, and the rest of the text is not written.
+impl<'boot> fmt::Write for Serial<'boot> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.write(b"\r\n")
+                    .warning_as_error()
+                    .map_err(|_| fmt::Error)?;
+            }
+            if !line.is_empty() {
+                self.write(line.as_bytes())
+                    .warning_as_error()
+                    .map_err(|_| fmt::Error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Structure representing the device's current parameters.
 ///
 /// The default values for all UART-like devices is:
@@ -1,10 +1,9 @@
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Char16, Event, Result, Status};
+use crate::{Char16, Event, Guid, Identify, Result, Status};
 use core::mem::MaybeUninit;
 
 /// Interface for text-based input devices.
 #[repr(C)]
-#[unsafe_guid("387477c1-69c7-11d2-8e39-00a0c969723b")]
 #[derive(Protocol)]
 pub struct Input {
     reset: extern "efiapi" fn(this: &mut Input, extended: bool) -> Status,
@@ -12,6 +11,10 @@ pub struct Input {
     wait_for_key: Event,
 }
 
+unsafe impl Identify for Input {
+    const GUID: Guid = guid!("387477c1-69c7-11d2-8e39-00a0c969723b");
+}
+
 impl Input {
     /// Resets the input device hardware.
     ///
@@ -44,8 +47,8 @@ impl Input {
 
     /// Event to be used with `BootServices::wait_for_event()` in order to wait
     /// for a key to be available
-    pub fn wait_for_key_event(&self) -> Event {
-        self.wait_for_key
+    pub fn wait_for_key_event(&self) -> &Event {
+        &self.wait_for_key
     }
 }
 
@@ -1,6 +1,7 @@
+use crate::data_types::ucs2;
 use crate::prelude::*;
 use crate::proto::Protocol;
-use crate::{unsafe_guid, CStr16, Char16, Completion, Result, Status};
+use crate::{CStr16, Char16, Completion, Guid, Identify, Result, Status};
 use core::fmt;
 
 /// Interface for text-based output devices.
@@ -8,7 +9,6 @@ use core::fmt;
 /// It implements the fmt::Write trait, so you can use it to print text with
 /// standard Rust constructs like the `write!()` and `writeln!()` macros.
 #[repr(C)]
-#[unsafe_guid("387477c2-69c7-11d2-8e39-00a0c969723b")]
 #[derive(Protocol)]
 pub struct Output<'boot> {
     reset: extern "efiapi" fn(this: &Output, extended: bool) -> Status,
@@ -28,6 +28,10 @@ pub struct Output<'boot> {
     data: &'boot OutputData,
 }
 
+unsafe impl<'boot> Identify for Output<'boot> {
+    const GUID: Guid = guid!("387477c2-69c7-11d2-8e39-00a0c969723b");
+}
+
 impl<'boot> Output<'boot> {
     /// Resets and clears the text output device hardware.
     pub fn reset(&mut self, extended: bool) -> Result {
@@ -129,6 +133,15 @@ impl<'boot> Output<'boot> {
         (self.set_cursor_position)(self, column, row).into()
     }
 
+    /// Returns the text and background colors of the console.
+    pub fn color(&self) -> (Color, Color) {
+        let attribute = self.data.attribute as usize;
+        (
+            Color::ALL[attribute & 0xF],
+            Color::ALL[(attribute >> 4) & 0x7],
+        )
+    }
+
     /// Sets the text and background colors for the console.
     ///
     /// Note that for the foreground color you can choose any color.
@@ -168,14 +181,12 @@ impl<'boot> fmt::Write for Output<'boot> {
 
         // This closure converts a character to UCS-2 and adds it to the buffer,
         // flushing it as necessary.
-        let mut add_char = |ch| {
-            // UEFI only supports UCS-2 characters, not UTF-16,
-            // so there are no multibyte characters.
-            buf[i] = ch;
+        let mut add_char = |ch: Char16| {
+            buf[i] = ch.into();
             i += 1;
 
             if i == BUF_SIZE {
-                flush_buffer(&mut buf, &mut i).map_err(|_| ucs2::Error::BufferOverflow)
+                flush_buffer(&mut buf, &mut i)
             } else {
                 Ok(())
             }
@@ -183,13 +194,15 @@ impl<'boot> fmt::Write for Output<'boot> {
 
         // This one converts Rust line feeds to UEFI line feeds beforehand
         let add_ch = |ch| {
-            if ch == '\n' as u16 {
-                add_char('\r' as u16)?;
+            if ch == Char16::LINE_FEED {
+                add_char(Char16::CARRIAGE_RETURN)?;
             }
             add_char(ch)
         };
 
-        // Translate and write the input string, flushing the buffer when needed
+        // Translate and write the input string, flushing the buffer when needed.
+        // Characters which cannot be represented in UCS-2 are reported as errors,
+        // as well as failures to write the text to the output device.
         ucs2::encode_with(s, add_ch).map_err(|_| fmt::Error)?;
 
         // Flush the remainder of the buffer
@@ -274,7 +287,7 @@ struct OutputData {
 /// All colors can be used as foreground colors.
 /// The first 8 colors can also be used as background colors.
 #[allow(missing_docs)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Color {
     Black = 0,
     Blue,
@@ -293,3 +306,25 @@ pub enum Color {
     Yellow,
     White,
 }
+
+impl Color {
+    /// The colors, in the order of their values.
+    const ALL: [Color; 16] = [
+        Color::Black,
+        Color::Blue,
+        Color::Green,
+        Color::Cyan,
+        Color::Red,
+        Color::Magenta,
+        Color::Brown,
+        Color::LightGray,
+        Color::DarkGray,
+        Color::LightBlue,
+        Color::LightGreen,
+        Color::LightCyan,
+        Color::LightRed,
+        Color::LightMagenta,
+        Color::Yellow,
+        Color::White,
+    ];
+}
@@ -0,0 +1,192 @@
+//! Processor contexts passed to the callbacks of the debug support protocol.
+
+use core::ffi::c_void;
+
+/// Pointer to the context of the processor, saved when a callback of the
+/// debug support protocol was triggered.
+///
+/// The layout of the context depends on the architecture, and it is
+/// accessed through the method of the running processor's architecture.
+/// Changes to the context are restored when the callback returns.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct SystemContext(*mut c_void);
+
+impl SystemContext {
+    /// Returns the context of a x86_64 processor.
+    ///
+    /// # Safety
+    ///
+    /// The running processor must be a x86_64 processor.
+    pub unsafe fn x64(&mut self) -> &mut SystemContextX64 {
+        &mut *self.0.cast()
+    }
+
+    /// Returns the context of an AArch64 processor.
+    ///
+    /// # Safety
+    ///
+    /// The running processor must be an AArch64 processor.
+    pub unsafe fn aarch64(&mut self) -> &mut SystemContextAArch64 {
+        &mut *self.0.cast()
+    }
+}
+
+/// Context of a x86_64 processor
+#[allow(missing_docs)]
+#[derive(Debug)]
+#[repr(C)]
+pub struct SystemContextX64 {
+    /// Error code pushed by the exception, or 0.
+    pub exception_data: u64,
+    /// State of the floating point and vector registers.
+    pub fx_save_state: FxSaveStateX64,
+    pub dr0: u64,
+    pub dr1: u64,
+    pub dr2: u64,
+    pub dr3: u64,
+    pub dr6: u64,
+    pub dr7: u64,
+    pub cr0: u64,
+    pub cr1: u64,
+    pub cr2: u64,
+    pub cr3: u64,
+    pub cr4: u64,
+    pub cr8: u64,
+    pub rflags: u64,
+    pub ldtr: u64,
+    pub tr: u64,
+    pub gdtr: [u64; 2],
+    pub idtr: [u64; 2],
+    pub rip: u64,
+    pub gs: u64,
+    pub fs: u64,
+    pub es: u64,
+    pub ds: u64,
+    pub cs: u64,
+    pub ss: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rbp: u64,
+    pub rsp: u64,
+    pub rbx: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rax: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+}
+
+/// State of the floating point and vector registers of a x86_64 processor,
+/// in the format of the `FXSAVE` instruction
+#[allow(missing_docs)]
+#[derive(Debug)]
+#[repr(C)]
+pub struct FxSaveStateX64 {
+    pub fcw: u16,
+    pub fsw: u16,
+    pub ftw: u16,
+    pub opcode: u16,
+    pub rip: u64,
+    pub data_offset: u64,
+    _reserved1: [u8; 8],
+    /// The x87 and MMX registers, of which only the first 10 bytes are
+    /// used.
+    pub st_mm: [[u8; 16]; 8],
+    pub xmm: [[u8; 16]; 16],
+    _reserved2: [u8; 96],
+}
+
+/// Context of an AArch64 processor
+#[derive(Debug)]
+#[repr(C)]
+pub struct SystemContextAArch64 {
+    /// The general purpose registers `x0` to `x28`.
+    pub x: [u64; 29],
+    /// The frame pointer, `x29`.
+    pub fp: u64,
+    /// The link register, `x30`.
+    pub lr: u64,
+    /// The stack pointer.
+    pub sp: u64,
+    /// The floating point and vector registers.
+    pub v: [[u64; 2]; 32],
+    /// The exception link register.
+    pub elr: u64,
+    /// The saved processor status register.
+    pub spsr: u64,
+    /// The floating point status register.
+    pub fpsr: u64,
+    /// The exception syndrome register.
+    pub esr: u64,
+    /// The fault address register.
+    pub far: u64,
+}
+
+newtype_enum! {
+/// The exceptions of x86_64 processors which can be handled by callbacks.
+pub enum ExceptionTypeX64: isize => #[allow(missing_docs)] {
+    DIVIDE_ERROR    = 0,
+    DEBUG           = 1,
+    NMI             = 2,
+    BREAKPOINT      = 3,
+    OVERFLOW        = 4,
+    BOUND           = 5,
+    INVALID_OPCODE  = 6,
+    DOUBLE_FAULT    = 8,
+    INVALID_TSS     = 10,
+    SEG_NOT_PRESENT = 11,
+    STACK_FAULT     = 12,
+    GP_FAULT        = 13,
+    PAGE_FAULT      = 14,
+    FP_ERROR        = 16,
+    ALIGNMENT_CHECK = 17,
+    MACHINE_CHECK   = 18,
+    SIMD            = 19,
+}}
+
+newtype_enum! {
+/// The exceptions of AArch64 processors which can be handled by callbacks.
+pub enum ExceptionTypeAArch64: isize => #[allow(missing_docs)] {
+    SYNCHRONOUS_EXCEPTIONS = 0,
+    IRQ                    = 1,
+    FIQ                    = 2,
+    SERROR                 = 3,
+}}
+
+/// The type of an exception, whose values depend on the architecture.
+///
+/// It is converted from and to the exception types of each architecture.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct ExceptionType(pub isize);
+
+impl From<ExceptionTypeX64> for ExceptionType {
+    fn from(exception_type: ExceptionTypeX64) -> Self {
+        ExceptionType(exception_type.0)
+    }
+}
+
+impl From<ExceptionType> for ExceptionTypeX64 {
+    fn from(exception_type: ExceptionType) -> Self {
+        ExceptionTypeX64(exception_type.0)
+    }
+}
+
+impl From<ExceptionTypeAArch64> for ExceptionType {
+    fn from(exception_type: ExceptionTypeAArch64) -> Self {
+        ExceptionType(exception_type.0)
+    }
+}
+
+impl From<ExceptionType> for ExceptionTypeAArch64 {
+    fn from(exception_type: ExceptionType) -> Self {
+        ExceptionTypeAArch64(exception_type.0)
+    }
+}
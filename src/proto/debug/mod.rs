@@ -10,23 +10,161 @@
 //! [udk]: https://firmware.intel.com/develop/intel-uefi-tools-and-utilities/intel-uefi-development-kit-debugger-tool
 
 use crate::proto::Protocol;
-use crate::unsafe_guid;
+use crate::{Guid, Identify, Result, ResultExt, Status};
+use core::ffi::c_void;
+use core::fmt;
+
+mod context;
+pub use self::context::{
+    ExceptionType, ExceptionTypeAArch64, ExceptionTypeX64, FxSaveStateX64, SystemContext,
+    SystemContextAArch64, SystemContextX64,
+};
 
 /// The debugging support protocol allows debuggers to connect to a UEFI machine.
+///
+/// Debuggers register callbacks, which are called periodically from the timer
+/// interrupt, and when the processor raises an exception. These callbacks are
+/// called directly by the interrupt handlers of the firmware, without any
+/// trampoline: they are `extern "efiapi"` functions, which receive the saved
+/// context of the processor, and can modify it to change the state of the
+/// processor when it resumes. As they can interrupt any code, including the
+/// firmware, they may not call boot services, allocate memory or log.
 #[repr(C)]
-#[unsafe_guid("2755590c-6f3c-42fa-9ea4-a3ba543cda25")]
 #[derive(Protocol)]
 pub struct DebugSupport {
     isa: ProcessorArch,
-    // FIXME: Add the mising parts of the interface. Beware that it features
-    //        unsafety in the form of an unchecked processor index.
+    get_maximum_processor_index:
+        extern "efiapi" fn(this: &mut DebugSupport, max_processor_index: &mut usize) -> Status,
+    register_periodic_callback: unsafe extern "efiapi" fn(
+        this: &mut DebugSupport,
+        processor_index: usize,
+        periodic_callback: Option<PeriodicCallback>,
+    ) -> Status,
+    register_exception_callback: unsafe extern "efiapi" fn(
+        this: &mut DebugSupport,
+        processor_index: usize,
+        exception_callback: Option<ExceptionCallback>,
+        exception_type: ExceptionType,
+    ) -> Status,
+    invalidate_instruction_cache: unsafe extern "efiapi" fn(
+        this: &mut DebugSupport,
+        processor_index: usize,
+        start: *mut c_void,
+        length: u64,
+    ) -> Status,
+}
+
+unsafe impl Identify for DebugSupport {
+    const GUID: Guid = guid!("2755590c-6f3c-42fa-9ea4-a3ba543cda25");
 }
 
+/// Callback called periodically from the timer interrupt.
+pub type PeriodicCallback = extern "efiapi" fn(system_context: SystemContext);
+
+/// Callback called when the processor raises an exception.
+pub type ExceptionCallback =
+    extern "efiapi" fn(exception_type: ExceptionType, system_context: SystemContext);
+
 impl DebugSupport {
     /// Returns the processor architecture of the running CPU.
     pub fn arch(&self) -> ProcessorArch {
         self.isa
     }
+
+    /// Returns the highest index of the processors which can be debugged.
+    pub fn get_maximum_processor_index(&mut self) -> Result<usize> {
+        let mut max_processor_index = 0;
+        (self.get_maximum_processor_index)(self, &mut max_processor_index)
+            .into_with_val(|| max_processor_index)
+    }
+
+    /// Registers a callback, called periodically on a processor.
+    ///
+    /// `ALREADY_STARTED` is returned if a callback is already registered for
+    /// this processor, and `INVALID_PARAMETER` if the processor index is
+    /// greater than `get_maximum_processor_index`.
+    ///
+    /// # Safety
+    ///
+    /// The callback must follow the rules of the callbacks described in the
+    /// documentation of this protocol, and must be unregistered before the
+    /// application exits.
+    pub unsafe fn register_periodic_callback(
+        &mut self,
+        processor_index: usize,
+        callback: PeriodicCallback,
+    ) -> Result {
+        self.check_processor_index(processor_index)?.log();
+        (self.register_periodic_callback)(self, processor_index, Some(callback)).into()
+    }
+
+    /// Unregisters the periodic callback of a processor.
+    ///
+    /// `INVALID_PARAMETER` is returned if no callback is registered.
+    pub fn unregister_periodic_callback(&mut self, processor_index: usize) -> Result {
+        self.check_processor_index(processor_index)?.log();
+        unsafe { (self.register_periodic_callback)(self, processor_index, None) }.into()
+    }
+
+    /// Registers a callback, called when a processor raises an exception.
+    ///
+    /// `ALREADY_STARTED` is returned if a callback is already registered for
+    /// this exception and processor, and `INVALID_PARAMETER` if the processor
+    /// index is greater than `get_maximum_processor_index`.
+    ///
+    /// # Safety
+    ///
+    /// The callback must follow the rules of the callbacks described in the
+    /// documentation of this protocol, and must be unregistered before the
+    /// application exits.
+    pub unsafe fn register_exception_callback(
+        &mut self,
+        processor_index: usize,
+        callback: ExceptionCallback,
+        exception_type: ExceptionType,
+    ) -> Result {
+        self.check_processor_index(processor_index)?.log();
+        (self.register_exception_callback)(self, processor_index, Some(callback), exception_type)
+            .into()
+    }
+
+    /// Unregisters the callback of an exception of a processor.
+    ///
+    /// `INVALID_PARAMETER` is returned if no callback is registered.
+    pub fn unregister_exception_callback(
+        &mut self,
+        processor_index: usize,
+        exception_type: ExceptionType,
+    ) -> Result {
+        self.check_processor_index(processor_index)?.log();
+        unsafe { (self.register_exception_callback)(self, processor_index, None, exception_type) }
+            .into()
+    }
+
+    /// Invalidates the instruction cache of a processor for a range of
+    /// memory, after it was modified, for example to insert a breakpoint.
+    ///
+    /// # Safety
+    ///
+    /// The range must be valid memory.
+    pub unsafe fn invalidate_instruction_cache(
+        &mut self,
+        processor_index: usize,
+        start: *mut c_void,
+        length: u64,
+    ) -> Result {
+        self.check_processor_index(processor_index)?.log();
+        (self.invalidate_instruction_cache)(self, processor_index, start, length).into()
+    }
+
+    /// The firmware doesn't have to check the processor index, so it is
+    /// checked before calling it.
+    fn check_processor_index(&mut self, processor_index: usize) -> Result {
+        if processor_index > self.get_maximum_processor_index()?.log() {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        Status::SUCCESS.into()
+    }
 }
 
 newtype_enum! {
@@ -55,3 +193,109 @@ pub enum ProcessorArch: u32 => {
     /// RISC-V 128-bit
     RISCV_128   = 0x5128,
 }}
+
+/// The debug port protocol gives access to the channel used by a debugger
+/// to communicate with the firmware, such as a dedicated UART or a USB
+/// debug port.
+///
+/// This channel is distinct from the serial devices of the `Serial`
+/// protocol, and can be used to log messages when the console is not
+/// visible.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct DebugPort {
+    reset: extern "efiapi" fn(this: &mut DebugPort) -> Status,
+    write: unsafe extern "efiapi" fn(
+        this: &mut DebugPort,
+        timeout: u32,
+        buffer_size: &mut usize,
+        buffer: *const u8,
+    ) -> Status,
+    read: unsafe extern "efiapi" fn(
+        this: &mut DebugPort,
+        timeout: u32,
+        buffer_size: &mut usize,
+        buffer: *mut u8,
+    ) -> Status,
+    poll: extern "efiapi" fn(this: &mut DebugPort) -> Status,
+}
+
+unsafe impl Identify for DebugPort {
+    const GUID: Guid = guid!("eba4e8d2-3858-41ec-a281-2647ba9660d0");
+}
+
+impl DebugPort {
+    /// Timeout of the writes done through `fmt::Write`, in microseconds.
+    const FMT_WRITE_TIMEOUT: u32 = 10_000;
+
+    /// Resets the debug port.
+    pub fn reset(&mut self) -> Result {
+        (self.reset)(self).into()
+    }
+
+    /// Writes bytes to the debug port, waiting at most `timeout`
+    /// microseconds, and returns the number of bytes written.
+    ///
+    /// Writing only part of the bytes before the timeout expires is not an
+    /// error. `write_all` can be used to write all of them.
+    pub fn write(&mut self, timeout: u32, data: &[u8]) -> Result<usize> {
+        let mut size = data.len();
+        match unsafe { (self.write)(self, timeout, &mut size, data.as_ptr()) } {
+            Status::TIMEOUT => Ok(size.into()),
+            status => status.into_with_val(|| size),
+        }
+    }
+
+    /// Writes all the bytes of `data` to the debug port.
+    ///
+    /// The timeout applies to each call to `write`. `TIMEOUT` is returned if
+    /// no byte could be written before it expired.
+    pub fn write_all(&mut self, timeout: u32, mut data: &[u8]) -> Result {
+        while !data.is_empty() {
+            let written = self.write(timeout, data)?.log();
+            if written == 0 {
+                return Err(Status::TIMEOUT.into());
+            }
+            data = &data[written..];
+        }
+        Status::SUCCESS.into()
+    }
+
+    /// Reads bytes from the debug port into `buffer`, waiting at most
+    /// `timeout` microseconds, and returns the number of bytes read.
+    ///
+    /// As with `write`, reading fewer bytes than the size of the buffer is
+    /// not an error.
+    pub fn read(&mut self, timeout: u32, buffer: &mut [u8]) -> Result<usize> {
+        let mut size = buffer.len();
+        match unsafe { (self.read)(self, timeout, &mut size, buffer.as_mut_ptr()) } {
+            Status::TIMEOUT => Ok(size.into()),
+            status => status.into_with_val(|| size),
+        }
+    }
+
+    /// Whether there are bytes waiting to be read from the debug port.
+    pub fn poll(&mut self) -> Result<bool> {
+        match (self.poll)(self) {
+            Status::NOT_READY => Ok(false.into()),
+            status => status.into_with_val(|| true),
+        }
+    }
+}
+
+impl fmt::Write for DebugPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Convert Rust line feeds to the line endings expected by terminals.
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.write_all(Self::FMT_WRITE_TIMEOUT, b"\r\n")
+                    .warning_as_error()
+                    .map_err(|_| fmt::Error)?;
+            }
+            self.write_all(Self::FMT_WRITE_TIMEOUT, line.as_bytes())
+                .warning_as_error()
+                .map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+//! Decompress protocol.
+//!
+//! This protocol decompresses data compressed with the EFI compression
+//! algorithm, as found in the compressed sections of firmware volumes.
+
+#[cfg(feature = "exts")]
+use crate::alloc_api::{vec, vec::Vec};
+use crate::proto::Protocol;
+use crate::{Guid, Identify, Result, Status};
+use core::convert::TryInto;
+use core::ffi::c_void;
+
+/// The Decompress protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Decompress {
+    get_info: unsafe extern "efiapi" fn(
+        this: &Decompress,
+        source: *const c_void,
+        source_size: u32,
+        destination_size: &mut u32,
+        scratch_size: &mut u32,
+    ) -> Status,
+    decompress: unsafe extern "efiapi" fn(
+        this: &Decompress,
+        source: *const c_void,
+        source_size: u32,
+        destination: *mut c_void,
+        destination_size: u32,
+        scratch: *mut c_void,
+        scratch_size: u32,
+    ) -> Status,
+}
+
+unsafe impl Identify for Decompress {
+    const GUID: Guid = guid!("d8117cfe-94a6-11d4-9a3a-0090273fc14d");
+}
+
+impl Decompress {
+    /// Returns the size of the decompressed data, and the size of the
+    /// scratch buffer needed to decompress `source`.
+    ///
+    /// Only the header of the data is checked. `INVALID_PARAMETER` is
+    /// returned if it is inconsistent with the size of `source`.
+    pub fn get_info(&self, source: &[u8]) -> Result<(usize, usize)> {
+        let source_size = source
+            .len()
+            .try_into()
+            .map_err(|_| Status::INVALID_PARAMETER)?;
+        let mut destination_size = 0;
+        let mut scratch_size = 0;
+        unsafe {
+            (self.get_info)(
+                self,
+                source.as_ptr().cast(),
+                source_size,
+                &mut destination_size,
+                &mut scratch_size,
+            )
+        }
+        .into_with_val(|| (destination_size as usize, scratch_size as usize))
+    }
+
+    /// Decompresses `source` into `destination`, using `scratch` as a work
+    /// area, and returns the filled part of `destination`.
+    ///
+    /// The sizes of the buffers are checked against the ones returned by
+    /// `get_info`, and `BUFFER_TOO_SMALL` is returned if one of them is too
+    /// small. `INVALID_PARAMETER` is returned if the data is corrupted.
+    pub fn decompress<'buf>(
+        &self,
+        source: &[u8],
+        destination: &'buf mut [u8],
+        scratch: &mut [u8],
+    ) -> Result<&'buf mut [u8]> {
+        let (destination_size, scratch_size) = self.get_info(source)?.log();
+        if destination.len() < destination_size || scratch.len() < scratch_size {
+            return Err(Status::BUFFER_TOO_SMALL.into());
+        }
+        let destination = &mut destination[..destination_size];
+        unsafe {
+            (self.decompress)(
+                self,
+                source.as_ptr().cast(),
+                source.len() as u32,
+                destination.as_mut_ptr().cast(),
+                destination_size as u32,
+                scratch.as_mut_ptr().cast(),
+                scratch_size as u32,
+            )
+        }
+        .into_with_val(move || destination)
+    }
+
+    /// Decompresses `source` into a newly allocated buffer.
+    #[cfg(feature = "exts")]
+    pub fn decompress_to_vec(&self, source: &[u8]) -> Result<Vec<u8>> {
+        let (destination_size, scratch_size) = self.get_info(source)?.log();
+        let mut destination = vec![0; destination_size];
+        let mut scratch = vec![0; scratch_size];
+        self.decompress(source, &mut destination, &mut scratch)?
+            .log();
+        Ok(destination.into())
+    }
+}
@@ -16,13 +16,16 @@
 //! the rest of the structure, and the `length` field indicates the
 //! total size of the Node including the header.
 
-use crate::{proto::Protocol, unsafe_guid};
+use crate::table::boot::BootServices;
+use crate::{proto::Protocol, CStr16, Char16, Guid, Identify, Result, Status};
+use core::fmt;
+use core::ops::Deref;
+use core::ptr::NonNull;
 
 /// Device path protocol.
 ///
 /// This can be opened on a `LoadedImage.device()` handle using the `HandleProtocol` boot service.
 #[repr(C, packed)]
-#[unsafe_guid("09576e91-6d3f-11d2-8e39-00a0c969723b")]
 #[derive(Protocol)]
 pub struct DevicePath {
     /// Type of device
@@ -35,6 +38,10 @@ pub struct DevicePath {
     pub length: u16,
 }
 
+unsafe impl Identify for DevicePath {
+    const GUID: Guid = guid!("09576e91-6d3f-11d2-8e39-00a0c969723b");
+}
+
 newtype_enum! {
 /// Type identifier for a DevicePath
 pub enum DeviceType: u8 => {
@@ -203,3 +210,103 @@ pub struct AcpiDevicePath {
     /// thus strings must not be used for the _UID in the ACPI name space.
     pub uid: u32,
 }
+
+/// Device path to text protocol.
+///
+/// This converts device paths to the text representation used by the UEFI
+/// shell, such as `PciRoot(0x0)/Pci(0x1F,0x2)/Sata(0x0,0xFFFF,0x0)`.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct DevicePathToText {
+    convert_device_node_to_text: unsafe extern "efiapi" fn(
+        device_node: *const DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> *mut Char16,
+    convert_device_path_to_text: unsafe extern "efiapi" fn(
+        device_path: *const DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> *mut Char16,
+}
+
+unsafe impl Identify for DevicePathToText {
+    const GUID: Guid = guid!("8b843e20-8132-4852-90cc-551a4e4a7f1c");
+}
+
+impl DevicePathToText {
+    /// Converts a single device path node to text.
+    ///
+    /// With `display_only`, the shorter display form of the node is used,
+    /// which can't always be converted back to a node. With
+    /// `allow_shortcuts`, the shortcut forms of nodes are used, such as
+    /// `HD(...)` for hard drive partitions.
+    ///
+    /// `OUT_OF_RESOURCES` is returned if the text can't be allocated.
+    pub fn convert_device_node_to_text<'boot>(
+        &self,
+        bt: &'boot BootServices,
+        device_node: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> Result<PoolString<'boot>> {
+        let text = unsafe {
+            (self.convert_device_node_to_text)(device_node, display_only, allow_shortcuts)
+        };
+        PoolString::new(bt, text)
+    }
+
+    /// Converts a whole device path to text, with the same options as
+    /// `convert_device_node_to_text`.
+    ///
+    /// `OUT_OF_RESOURCES` is returned if the text can't be allocated.
+    pub fn convert_device_path_to_text<'boot>(
+        &self,
+        bt: &'boot BootServices,
+        device_path: &DevicePath,
+        display_only: bool,
+        allow_shortcuts: bool,
+    ) -> Result<PoolString<'boot>> {
+        let text = unsafe {
+            (self.convert_device_path_to_text)(device_path, display_only, allow_shortcuts)
+        };
+        PoolString::new(bt, text)
+    }
+}
+
+/// A string allocated from pool memory by the firmware, which is freed when
+/// dropped.
+pub struct PoolString<'a> {
+    text: NonNull<Char16>,
+    bt: &'a BootServices,
+}
+
+impl<'a> PoolString<'a> {
+    fn new(bt: &'a BootServices, text: *mut Char16) -> Result<Self> {
+        match NonNull::new(text) {
+            Some(text) => Ok(PoolString { text, bt }.into()),
+            None => Err(Status::OUT_OF_RESOURCES.into()),
+        }
+    }
+}
+
+impl Deref for PoolString<'_> {
+    type Target = CStr16;
+
+    fn deref(&self) -> &CStr16 {
+        unsafe { CStr16::from_ptr(self.text.as_ptr()) }
+    }
+}
+
+impl fmt::Debug for PoolString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl Drop for PoolString<'_> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = self.bt.free_pool(self.text.as_ptr().cast());
+    }
+}
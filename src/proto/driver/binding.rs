@@ -0,0 +1,269 @@
+//! Driver Binding protocol.
+//!
+//! Besides the protocol itself, which can be used to inspect the drivers of
+//! the system, this module provides a way of implementing it in Rust:
+//! `DriverBindingBuilder` builds a driver binding from three Rust functions,
+//! which can then be installed with `install_driver_binding`.
+
+use crate::proto::device_path::DevicePath;
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{Guid, Handle, Identify, Result, ResultExt, Status};
+use core::ffi::c_void;
+use core::slice;
+
+/// The Driver Binding protocol
+///
+/// The firmware uses this protocol to test whether a driver supports a
+/// controller, to start the driver on it, and to stop it. When several drivers
+/// support a controller, `BootServices::connect_controller` tries the drivers
+/// chosen by the platform and the bus first, and then the other drivers by
+/// decreasing version of their driver binding. The versions from 0x0 to 0xf
+/// are reserved for the drivers of the platform, and the versions from
+/// 0xffff_fff0 to 0xffff_ffff for the drivers of hardware vendors.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct DriverBinding {
+    supported: unsafe extern "efiapi" fn(
+        this: &DriverBinding,
+        controller: Handle,
+        remaining_device_path: *const DevicePath,
+    ) -> Status,
+    start: unsafe extern "efiapi" fn(
+        this: &DriverBinding,
+        controller: Handle,
+        remaining_device_path: *const DevicePath,
+    ) -> Status,
+    stop: unsafe extern "efiapi" fn(
+        this: &DriverBinding,
+        controller: Handle,
+        number_of_children: usize,
+        child_handle_buffer: *const Handle,
+    ) -> Status,
+    version: u32,
+    image_handle: Option<Handle>,
+    driver_binding_handle: Option<Handle>,
+}
+
+unsafe impl Identify for DriverBinding {
+    const GUID: Guid = guid!("18a031ab-b443-4d1a-a5c0-0c09261e9f71");
+}
+
+impl DriverBinding {
+    /// Returns the version of the driver, used to order the drivers
+    /// supporting the same controller.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the image handle of the driver.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the driver binding was built by `DriverBindingBuilder` and
+    /// is not installed.
+    pub fn image_handle(&self) -> Handle {
+        self.image_handle
+            .expect("The driver binding is not installed")
+    }
+
+    /// Returns the handle on which this driver binding is installed, which is
+    /// used as the agent handle when the driver opens protocols.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the driver binding was built by `DriverBindingBuilder` and
+    /// is not installed.
+    pub fn driver_binding_handle(&self) -> Handle {
+        self.driver_binding_handle
+            .expect("The driver binding is not installed")
+    }
+}
+
+/// Function checking whether a driver supports a controller.
+///
+/// It is given the remaining device path passed to `connect_controller`, if
+/// any, describing the child controller to create. It must return
+/// `UNSUPPORTED` if the controller is not supported, and `ALREADY_STARTED` or
+/// `ACCESS_DENIED` if it is already managed by this driver or another one.
+/// As it is called for many controllers, it should be fast, and leave the
+/// controller as it found it.
+pub type SupportedFn = fn(
+    binding: &DriverBinding,
+    controller: Handle,
+    remaining_device_path: Option<&DevicePath>,
+) -> Result;
+
+/// Function starting a driver on a controller, which is only called if
+/// `SupportedFn` succeeded.
+///
+/// Drivers open the protocols they use on the controller with
+/// `OpenProtocolAttributes::ByDriver`, using the driver binding handle as the
+/// agent, so that the firmware knows which driver to stop later. Bus drivers
+/// create child controllers, with new handles.
+pub type StartFn = fn(
+    binding: &DriverBinding,
+    controller: Handle,
+    remaining_device_path: Option<&DevicePath>,
+) -> Result;
+
+/// Function stopping a driver on a controller.
+///
+/// If `children` is empty, the driver must stop managing the controller and
+/// close the protocols it opened. Otherwise, it must only destroy the given
+/// child controllers.
+pub type StopFn = fn(binding: &DriverBinding, controller: Handle, children: &[Handle]) -> Result;
+
+/// Builds the driver binding of a driver written in Rust
+///
+/// The functions of the driver are called by the firmware through
+/// trampolines, which convert the arguments and results between the C
+/// interface of the protocol and Rust.
+///
+/// The functions may be called again while a previous call is still in
+/// progress, for example when `StartFn` connects a child controller, or
+/// when a protocol used by the driver is uninstalled. They must be written
+/// with this in mind, and `StopFn` must be able to undo the work of
+/// `StartFn` at any time.
+#[derive(Clone, Copy, Debug)]
+pub struct DriverBindingBuilder {
+    supported: SupportedFn,
+    start: StartFn,
+    stop: StopFn,
+    version: u32,
+}
+
+impl DriverBindingBuilder {
+    /// Creates a builder for a driver with these functions, and a version of
+    /// 0x10.
+    pub fn new(supported: SupportedFn, start: StartFn, stop: StopFn) -> Self {
+        DriverBindingBuilder {
+            supported,
+            start,
+            stop,
+            version: 0x10,
+        }
+    }
+
+    /// Sets the version of the driver.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Builds the driver binding, which can be installed with
+    /// `install_driver_binding`.
+    pub fn build(self) -> DriverBindingInterface {
+        DriverBindingInterface {
+            binding: DriverBinding {
+                supported: supported_trampoline,
+                start: start_trampoline,
+                stop: stop_trampoline,
+                version: self.version,
+                image_handle: None,
+                driver_binding_handle: None,
+            },
+            supported: self.supported,
+            start: self.start,
+            stop: self.stop,
+        }
+    }
+}
+
+/// A driver binding built by `DriverBindingBuilder`
+///
+/// The functions of the driver are stored after the protocol, where the
+/// trampolines find them.
+#[repr(C)]
+pub struct DriverBindingInterface {
+    binding: DriverBinding,
+    supported: SupportedFn,
+    start: StartFn,
+    stop: StopFn,
+}
+
+impl DriverBindingInterface {
+    /// Returns the driver binding protocol.
+    pub fn binding(&self) -> &DriverBinding {
+        &self.binding
+    }
+}
+
+/// Returns the interface containing a driver binding built by
+/// `DriverBindingBuilder`.
+unsafe fn interface(binding: &DriverBinding) -> &DriverBindingInterface {
+    &*(binding as *const DriverBinding).cast()
+}
+
+/// Converts the result of the functions of the driver to a status.
+fn to_status(result: Result) -> Status {
+    match result {
+        Ok(completion) => completion.status(),
+        Err(error) => error.status(),
+    }
+}
+
+unsafe extern "efiapi" fn supported_trampoline(
+    this: &DriverBinding,
+    controller: Handle,
+    remaining_device_path: *const DevicePath,
+) -> Status {
+    to_status((interface(this).supported)(
+        this,
+        controller,
+        remaining_device_path.as_ref(),
+    ))
+}
+
+unsafe extern "efiapi" fn start_trampoline(
+    this: &DriverBinding,
+    controller: Handle,
+    remaining_device_path: *const DevicePath,
+) -> Status {
+    to_status((interface(this).start)(
+        this,
+        controller,
+        remaining_device_path.as_ref(),
+    ))
+}
+
+unsafe extern "efiapi" fn stop_trampoline(
+    this: &DriverBinding,
+    controller: Handle,
+    number_of_children: usize,
+    child_handle_buffer: *const Handle,
+) -> Status {
+    let children = if number_of_children == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(child_handle_buffer, number_of_children)
+    };
+    to_status((interface(this).stop)(this, controller, children))
+}
+
+/// Installs a driver binding on the image handle of a driver, making the
+/// driver available to `BootServices::connect_controller`.
+///
+/// The driver binding must be uninstalled with `uninstall_driver_binding`
+/// before the image is unloaded, for example before an application exits.
+pub fn install_driver_binding(
+    bt: &BootServices,
+    image_handle: Handle,
+    interface: &'static mut DriverBindingInterface,
+) -> Result {
+    interface.binding.image_handle = Some(image_handle);
+    interface.binding.driver_binding_handle = Some(image_handle);
+    let interface = interface as *mut DriverBindingInterface as *mut c_void;
+    unsafe { bt.install_protocol_interface(Some(image_handle), &DriverBinding::GUID, interface) }
+        .map_inner(|_| ())
+}
+
+/// Uninstalls the driver binding installed on the image handle of a driver.
+///
+/// The firmware first stops the driver on the controllers it manages.
+pub fn uninstall_driver_binding(bt: &BootServices, image_handle: Handle) -> Result {
+    let binding = bt.handle_protocol::<DriverBinding>(image_handle)?.log();
+    unsafe {
+        bt.uninstall_protocol_interface(image_handle, &DriverBinding::GUID, binding.get().cast())
+    }
+}
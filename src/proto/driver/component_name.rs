@@ -0,0 +1,260 @@
+//! Component Name 2 protocol.
+//!
+//! Drivers install this protocol next to their driver binding, to give
+//! user-readable names to themselves and to the controllers they manage.
+//! `install_component_name` installs it for a driver written in Rust, from
+//! a table of driver names.
+
+use crate::proto::hii::string::LanguageList;
+use crate::proto::Protocol;
+use crate::table::boot::{BootServices, MemoryType};
+use crate::{CStr16, CStr8, Char16, Char8, Guid, Handle, Identify, Result, ResultExt, Status};
+use core::{mem, ptr, slice, str};
+
+/// Maximum length, including the null terminator, of the language tags
+/// selected by the `best_*` methods.
+const MAX_LANGUAGE_SIZE: usize = 64;
+
+/// The Component Name 2 protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct ComponentName2 {
+    get_driver_name: unsafe extern "efiapi" fn(
+        this: &ComponentName2,
+        language: *const Char8,
+        driver_name: &mut *const Char16,
+    ) -> Status,
+    get_controller_name: unsafe extern "efiapi" fn(
+        this: &ComponentName2,
+        controller: Handle,
+        child: Option<Handle>,
+        language: *const Char8,
+        controller_name: &mut *const Char16,
+    ) -> Status,
+    supported_languages: *const Char8,
+}
+
+unsafe impl Identify for ComponentName2 {
+    const GUID: Guid = guid!("6a7a5cff-e8d9-4f70-bada-75ab3025ce14");
+}
+
+impl ComponentName2 {
+    /// Returns the languages in which names are available.
+    pub fn supported_languages(&self) -> LanguageList<'_> {
+        let languages = unsafe { CStr8::from_ptr(self.supported_languages) };
+        LanguageList::new(str::from_utf8(languages.to_bytes()).unwrap_or(""))
+    }
+
+    /// Returns the name of the driver, in the language `language`, which must
+    /// be one of the supported languages.
+    ///
+    /// `UNSUPPORTED` is returned if the language is not supported.
+    pub fn driver_name(&self, language: &CStr8) -> Result<&CStr16> {
+        let mut name = ptr::null();
+        unsafe { (self.get_driver_name)(self, language.as_ptr(), &mut name) }
+            .into_with_val(|| unsafe { CStr16::from_ptr(name) })
+    }
+
+    /// Returns the name of a controller managed by the driver, or of one of
+    /// the child controllers it created, in the language `language`.
+    ///
+    /// `UNSUPPORTED` is returned if the language is not supported, or if the
+    /// driver doesn't manage the controller.
+    pub fn controller_name(
+        &self,
+        controller: Handle,
+        child: Option<Handle>,
+        language: &CStr8,
+    ) -> Result<&CStr16> {
+        let mut name = ptr::null();
+        unsafe { (self.get_controller_name)(self, controller, child, language.as_ptr(), &mut name) }
+            .into_with_val(|| unsafe { CStr16::from_ptr(name) })
+    }
+
+    /// Returns the name of the driver, in the supported language which best
+    /// matches the languages of `preferred`, as chosen by
+    /// `LanguageList::best_match`, or in the first supported language if
+    /// none of them match.
+    pub fn best_driver_name(&self, preferred: &[&str]) -> Result<&CStr16> {
+        self.with_best_language(preferred, |language| self.driver_name(language))
+    }
+
+    /// Returns the name of a controller, in the supported language chosen as
+    /// by `best_driver_name`.
+    pub fn best_controller_name(
+        &self,
+        controller: Handle,
+        child: Option<Handle>,
+        preferred: &[&str],
+    ) -> Result<&CStr16> {
+        self.with_best_language(preferred, |language| {
+            self.controller_name(controller, child, language)
+        })
+    }
+
+    /// Calls `f` with the best supported language for `preferred`, as a
+    /// null-terminated string.
+    fn with_best_language<'a>(
+        &'a self,
+        preferred: &[&str],
+        f: impl FnOnce(&CStr8) -> Result<&'a CStr16>,
+    ) -> Result<&'a CStr16> {
+        let languages = self.supported_languages();
+        let language = languages
+            .best_match(preferred)
+            .or_else(|| languages.iter().next())
+            .ok_or(Status::UNSUPPORTED)?;
+        let mut buffer = [0; MAX_LANGUAGE_SIZE];
+        if language.len() >= buffer.len() {
+            return Err(Status::UNSUPPORTED.into());
+        }
+        buffer[..language.len()].copy_from_slice(language.as_bytes());
+        let language = unsafe { CStr8::from_bytes_with_nul_unchecked(&buffer[..=language.len()]) };
+        f(language)
+    }
+}
+
+/// A Component Name 2 protocol installed by `install_component_name`,
+/// followed in the same pool allocation by the supported languages and the
+/// UCS-2 names.
+#[repr(C)]
+struct NameTable {
+    protocol: ComponentName2,
+    names: &'static [(&'static str, &'static str)],
+    driver_names: *const u16,
+}
+
+/// Installs a Component Name 2 protocol on the driver binding handle of a
+/// driver, giving the name of the driver in each language of `names`.
+///
+/// The table maps RFC 4646 language tags, such as `en` or `fr-FR`, to the
+/// name of the driver in this language. The protocol returns `UNSUPPORTED`
+/// when asked for a language which isn't in the table, and for the names of
+/// controllers. The names are converted to UCS-2 in a pool allocation, and
+/// `INVALID_PARAMETER` is returned if the table is empty or if a name can't
+/// be converted.
+pub fn install_component_name(
+    bt: &BootServices,
+    driver_binding_handle: Handle,
+    names: &'static [(&'static str, &'static str)],
+) -> Result {
+    let valid = |name: &str| name.chars().all(|c| c != '\0' && (c as u32) < 0x10000);
+    if names.is_empty()
+        || !names
+            .iter()
+            .all(|(language, name)| !language.contains(';') && language.is_ascii() && valid(name))
+    {
+        return Err(Status::INVALID_PARAMETER.into());
+    }
+
+    let languages_size = names
+        .iter()
+        .map(|(language, _)| language.len() + 1)
+        .sum::<usize>();
+    let names_offset = (mem::size_of::<NameTable>() + languages_size + 1) & !1;
+    let names_len = names
+        .iter()
+        .map(|(_, name)| name.encode_utf16().count() + 1)
+        .sum::<usize>();
+    let size = names_offset + names_len * mem::size_of::<u16>();
+    let table = bt.allocate_pool(MemoryType::LOADER_DATA, size)?.log();
+
+    unsafe {
+        // Languages are separated by semicolons, with a single null
+        // terminator at the end.
+        let languages =
+            slice::from_raw_parts_mut(table.add(mem::size_of::<NameTable>()), languages_size);
+        let mut offset = 0;
+        for (language, _) in names {
+            languages[offset..offset + language.len()].copy_from_slice(language.as_bytes());
+            languages[offset + language.len()] = b';';
+            offset += language.len() + 1;
+        }
+        languages[offset - 1] = 0;
+
+        let driver_names = slice::from_raw_parts_mut(table.add(names_offset).cast(), names_len);
+        let mut codes = names
+            .iter()
+            .flat_map(|(_, name)| name.encode_utf16().chain(Some(0)));
+        for code in driver_names.iter_mut() {
+            *code = codes.next().unwrap();
+        }
+
+        table.cast::<NameTable>().write(NameTable {
+            protocol: ComponentName2 {
+                get_driver_name,
+                get_controller_name,
+                supported_languages: languages.as_ptr().cast(),
+            },
+            names,
+            driver_names: driver_names.as_ptr(),
+        });
+
+        let result = bt.install_protocol_interface(
+            Some(driver_binding_handle),
+            &ComponentName2::GUID,
+            table.cast(),
+        );
+        if result.is_err() {
+            // Ignore the result, we can't do anything about an error here.
+            let _ = bt.free_pool(table);
+        }
+        result.map_inner(|_| ())
+    }
+}
+
+/// Uninstalls a Component Name 2 protocol installed by
+/// `install_component_name`, and frees its memory.
+pub fn uninstall_component_name(bt: &BootServices, driver_binding_handle: Handle) -> Result {
+    let table = bt
+        .handle_protocol::<ComponentName2>(driver_binding_handle)?
+        .log()
+        .get();
+    unsafe {
+        bt.uninstall_protocol_interface(driver_binding_handle, &ComponentName2::GUID, table.cast())?
+            .log()
+    };
+    bt.free_pool(table.cast())
+}
+
+unsafe extern "efiapi" fn get_driver_name(
+    this: &ComponentName2,
+    language: *const Char8,
+    driver_name: &mut *const Char16,
+) -> Status {
+    let table = &*(this as *const ComponentName2).cast::<NameTable>();
+    if language.is_null() {
+        return Status::INVALID_PARAMETER;
+    }
+    let language = CStr8::from_ptr(language).to_bytes();
+    let index = table
+        .names
+        .iter()
+        .position(|(tag, _)| tag.as_bytes().eq_ignore_ascii_case(language));
+    match index {
+        Some(index) => {
+            // The names are stored one after the other, with their null
+            // terminators.
+            let mut name = table.driver_names;
+            for _ in 0..index {
+                while *name != 0 {
+                    name = name.add(1);
+                }
+                name = name.add(1);
+            }
+            *driver_name = name.cast();
+            Status::SUCCESS
+        }
+        None => Status::UNSUPPORTED,
+    }
+}
+
+unsafe extern "efiapi" fn get_controller_name(
+    _this: &ComponentName2,
+    _controller: Handle,
+    _child: Option<Handle>,
+    _language: *const Char8,
+    _controller_name: &mut *const Char16,
+) -> Status {
+    Status::UNSUPPORTED
+}
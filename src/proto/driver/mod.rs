@@ -0,0 +1,10 @@
+//! Protocols of the UEFI driver model.
+//!
+//! Drivers install a driver binding on their image handle, which the firmware
+//! uses to start them on the controllers they support, when these controllers
+//! are connected with `BootServices::connect_controller`. They can also
+//! install a component name protocol, to give names to themselves and to
+//! their controllers.
+
+pub mod binding;
+pub mod component_name;
@@ -0,0 +1,587 @@
+//! Firmware Management Protocol (FMP).
+//!
+//! This protocol is installed by the drivers of devices whose firmware can
+//! be updated, usually by the capsule update process. Each device has one or
+//! more firmware images, described by `ImageInfo` entries and identified by
+//! their index.
+
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{CStr16, Char16, Error, Guid, Identify, Result, Status};
+use bitflags::bitflags;
+use core::ffi::c_void;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+
+bitflags! {
+    /// Attributes of a firmware image.
+    #[repr(transparent)]
+    pub struct ImageAttributes: u64 {
+        /// The image can be updated by `set_image`.
+        const IMAGE_UPDATABLE = 0x0000_0000_0000_0001;
+        /// A reset is required for the new image to be used.
+        const RESET_REQUIRED = 0x0000_0000_0000_0002;
+        /// The new image must be authenticated, with its authentication
+        /// data preceding it.
+        const AUTHENTICATION_REQUIRED = 0x0000_0000_0000_0004;
+        /// The image is in use, rather than being a backup image.
+        const IN_USE = 0x0000_0000_0000_0008;
+        /// The image is a UEFI image, such as an option ROM driver.
+        const UEFI_IMAGE = 0x0000_0000_0000_0010;
+        /// The image has dependencies on other firmware images.
+        const DEPENDENCY = 0x0000_0000_0000_0020;
+    }
+}
+
+bitflags! {
+    /// Attributes of a firmware package.
+    #[repr(transparent)]
+    pub struct PackageAttributes: u64 {
+        /// The version of the package can be updated.
+        const VERSION_UPDATABLE = 0x0000_0000_0000_0001;
+        /// A reset is required for the new version to be used.
+        const RESET_REQUIRED = 0x0000_0000_0000_0002;
+        /// The new version must be authenticated.
+        const AUTHENTICATION_REQUIRED = 0x0000_0000_0000_0004;
+    }
+}
+
+bitflags! {
+    /// Result of `FirmwareManagement::check_image`.
+    #[repr(transparent)]
+    pub struct ImageUpdatable: u32 {
+        /// The image can be used to update the firmware.
+        const VALID = 0x0000_0001;
+        /// The image is not valid.
+        const INVALID = 0x0000_0002;
+        /// The image is not of the right type.
+        const INVALID_TYPE = 0x0000_0004;
+        /// The image is older than the currently installed one.
+        const INVALID_OLD = 0x0000_0008;
+        /// The image can only be used with the vendor code given to
+        /// `set_image`.
+        const VALID_WITH_VENDOR_CODE = 0x0000_0010;
+    }
+}
+
+newtype_enum! {
+/// Result of the last attempt to update a firmware image.
+pub enum LastAttemptStatus: u32 => {
+    /// The update succeeded.
+    SUCCESS                         = 0,
+    /// The update failed for an unspecified reason.
+    ERROR_UNSUCCESSFUL              = 1,
+    /// There were not enough resources to perform the update.
+    ERROR_INSUFFICIENT_RESOURCES    = 2,
+    /// The version of the image was not accepted.
+    ERROR_INCORRECT_VERSION         = 3,
+    /// The image was not valid.
+    ERROR_INVALID_FORMAT            = 4,
+    /// The image could not be authenticated.
+    ERROR_AUTH_ERROR                = 5,
+    /// The device was not running on AC power.
+    ERROR_PWR_EVT_AC                = 6,
+    /// The battery level was too low.
+    ERROR_PWR_EVT_BATT              = 7,
+    /// The dependencies of the image were not satisfied.
+    ERROR_UNSATISFIED_DEPENDENCIES  = 8,
+}}
+
+/// Layout of the fields of `EFI_FIRMWARE_IMAGE_DESCRIPTOR` present in all
+/// versions.
+#[repr(C)]
+struct RawDescriptorV1 {
+    image_index: u8,
+    image_type_id: Guid,
+    image_id: u64,
+    image_id_name: *const Char16,
+    version: u32,
+    version_name: *const Char16,
+    size: usize,
+    attributes_supported: ImageAttributes,
+    attributes_setting: ImageAttributes,
+    compatibilities: u64,
+}
+
+/// Layout of the fields of `EFI_FIRMWARE_IMAGE_DESCRIPTOR` up to version 3.
+#[repr(C)]
+struct RawDescriptorV3 {
+    v1: RawDescriptorV1,
+    lowest_supported_image_version: u32,
+    last_attempt_version: u32,
+    last_attempt_status: LastAttemptStatus,
+    hardware_instance: u64,
+}
+
+/// A firmware image of a device.
+///
+/// The fields introduced by later versions of the descriptors are `None`
+/// if the firmware described the images with an older version.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageInfo<'a> {
+    /// Index of the image, starting at 1, used to refer to it.
+    pub image_index: u8,
+    /// Type of the image, which identifies the capsules updating it.
+    pub image_type_id: Guid,
+    /// Unique identifier of the image.
+    pub image_id: u64,
+    /// Name of the image.
+    pub image_id_name: Option<&'a CStr16>,
+    /// Version of the image.
+    pub version: u32,
+    /// Version of the image, as displayed to users.
+    pub version_name: Option<&'a CStr16>,
+    /// Size of the image in bytes, or zero if unknown.
+    pub size: usize,
+    /// Attributes supported by the image.
+    pub attributes_supported: ImageAttributes,
+    /// Attributes of the image, among the supported ones.
+    pub attributes_setting: ImageAttributes,
+    /// Compatibilities of the image, whose meaning is defined by the vendor
+    /// in the upper 48 bits.
+    pub compatibilities: u64,
+    /// Lowest version to which the image can be updated, since version 2.
+    pub lowest_supported_image_version: Option<u32>,
+    /// Version given in the last update attempt, since version 3.
+    pub last_attempt_version: Option<u32>,
+    /// Result of the last update attempt, since version 3.
+    pub last_attempt_status: Option<LastAttemptStatus>,
+    /// Identifies devices of the same type, since version 3, or zero if
+    /// there is a single one.
+    pub hardware_instance: Option<u64>,
+}
+
+/// The image descriptors returned by `FirmwareManagement::get_image_info`.
+///
+/// Each descriptor is `descriptor_size` bytes long, as later versions of
+/// the descriptors have more fields. The fields are read according to both
+/// the version and the size of the descriptors, and the fields of versions
+/// above 3 are ignored.
+///
+/// ```
+/// use uefi::proto::firmware_management::{ImageAttributes, ImageDescriptors};
+///
+/// // Version 1 descriptors are 88 bytes long on 64-bit targets.
+/// let mut buffer = [0; 2 * 88];
+/// buffer[0] = 1;
+/// buffer[40..44].copy_from_slice(&0x0102u32.to_le_bytes());
+/// buffer[72] = 0x01;
+/// buffer[88] = 2;
+/// let descriptors = unsafe { ImageDescriptors::from_bytes(&buffer, 1, 88, 2) }.unwrap();
+/// let images: Vec<_> = descriptors.iter().collect();
+/// assert_eq!(images.len(), 2);
+/// assert_eq!(images[0].image_index, 1);
+/// assert_eq!(images[0].version, 0x0102);
+/// assert_eq!(images[0].attributes_setting, ImageAttributes::IMAGE_UPDATABLE);
+/// assert!(images[0].image_id_name.is_none());
+/// assert_eq!(images[0].lowest_supported_image_version, None);
+/// assert_eq!(images[1].image_index, 2);
+///
+/// // Descriptors of versions above 3 are longer, with more fields.
+/// let name = [u16::from(b'F'), u16::from(b'W'), 0];
+/// let mut buffer = [0; 128];
+/// buffer[0] = 1;
+/// buffer[32..40].copy_from_slice(&(name.as_ptr() as u64).to_le_bytes());
+/// buffer[88..92].copy_from_slice(&3u32.to_le_bytes());
+/// buffer[96..100].copy_from_slice(&2u32.to_le_bytes());
+/// let descriptors = unsafe { ImageDescriptors::from_bytes(&buffer, 4, 128, 1) }.unwrap();
+/// let image = descriptors.iter().next().unwrap();
+/// assert_eq!(image.image_id_name.unwrap(), "FW");
+/// assert_eq!(image.lowest_supported_image_version, Some(3));
+/// assert_eq!(image.last_attempt_status.unwrap().0, 2);
+///
+/// // Descriptors too short for their version are rejected.
+/// assert!(unsafe { ImageDescriptors::from_bytes(&buffer, 1, 80, 1) }.is_none());
+/// assert!(unsafe { ImageDescriptors::from_bytes(&buffer, 1, 88, 2) }.is_none());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ImageDescriptors<'a> {
+    bytes: &'a [u8],
+    version: u32,
+    descriptor_size: usize,
+    count: usize,
+}
+
+impl<'a> ImageDescriptors<'a> {
+    /// Views `count` descriptors of `descriptor_size` bytes and of version
+    /// `version` at the beginning of `bytes`.
+    ///
+    /// Returns `None` if the bytes are too short, or if the descriptors are
+    /// too short for their version.
+    ///
+    /// # Safety
+    ///
+    /// The names of the descriptors must be null, or point to
+    /// null-terminated strings which live as long as `bytes`.
+    pub unsafe fn from_bytes(
+        bytes: &'a [u8],
+        version: u32,
+        descriptor_size: usize,
+        count: usize,
+    ) -> Option<Self> {
+        let min_size = match version {
+            0 => return None,
+            1 => mem::size_of::<RawDescriptorV1>(),
+            2 => mem::size_of::<RawDescriptorV1>() + mem::size_of::<u32>(),
+            _ => mem::size_of::<RawDescriptorV3>(),
+        };
+        if descriptor_size < min_size || descriptor_size.checked_mul(count)? > bytes.len() {
+            return None;
+        }
+        Some(Self {
+            bytes: &bytes[..descriptor_size * count],
+            version,
+            descriptor_size,
+            count,
+        })
+    }
+
+    /// Version of the descriptors.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Number of descriptors.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether there are no descriptors.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Iterates over the descriptors.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = ImageInfo<'a>> + 'a {
+        let version = self.version;
+        self.bytes
+            .chunks_exact(self.descriptor_size)
+            .map(move |bytes| unsafe { read_descriptor(bytes, version) })
+    }
+}
+
+/// Reads a descriptor, which is at least as long as the fields of its
+/// version.
+///
+/// # Safety
+///
+/// The names of the descriptor must be valid for `'a`.
+unsafe fn read_descriptor<'a>(bytes: &'a [u8], version: u32) -> ImageInfo<'a> {
+    // The descriptors are copied, as they may not be aligned, and the
+    // missing fields are zeroed.
+    let mut raw = MaybeUninit::<RawDescriptorV3>::zeroed();
+    let len = bytes.len().min(mem::size_of::<RawDescriptorV3>());
+    ptr::copy_nonoverlapping(bytes.as_ptr(), raw.as_mut_ptr().cast::<u8>(), len);
+    let raw = raw.assume_init();
+
+    let string = |ptr: *const Char16| {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(CStr16::from_ptr(ptr))
+        }
+    };
+    ImageInfo {
+        image_index: raw.v1.image_index,
+        image_type_id: raw.v1.image_type_id,
+        image_id: raw.v1.image_id,
+        image_id_name: string(raw.v1.image_id_name),
+        version: raw.v1.version,
+        version_name: string(raw.v1.version_name),
+        size: raw.v1.size,
+        attributes_supported: raw.v1.attributes_supported,
+        attributes_setting: raw.v1.attributes_setting,
+        compatibilities: raw.v1.compatibilities,
+        lowest_supported_image_version: since(version, 2, raw.lowest_supported_image_version),
+        last_attempt_version: since(version, 3, raw.last_attempt_version),
+        last_attempt_status: since(version, 3, raw.last_attempt_status),
+        hardware_instance: since(version, 3, raw.hardware_instance),
+    }
+}
+
+/// Returns `value` if the descriptors are at least of version `min`.
+fn since<T>(version: u32, min: u32, value: T) -> Option<T> {
+    if version >= min {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// A string allocated by the firmware, which is freed when dropped.
+pub struct FmpString<'a> {
+    data: NonNull<Char16>,
+    bt: &'a BootServices,
+}
+
+impl FmpString<'_> {
+    fn new(data: *mut Char16, bt: &BootServices) -> Option<FmpString<'_>> {
+        NonNull::new(data).map(|data| FmpString { data, bt })
+    }
+
+    /// The contents of the string.
+    pub fn as_cstr16(&self) -> &CStr16 {
+        unsafe { CStr16::from_ptr(self.data.as_ptr()) }
+    }
+}
+
+impl fmt::Debug for FmpString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_cstr16(), f)
+    }
+}
+
+impl fmt::Display for FmpString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_cstr16(), f)
+    }
+}
+
+impl Drop for FmpString<'_> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = self.bt.free_pool(self.data.as_ptr().cast());
+    }
+}
+
+/// The images of a device and the version of its firmware package, as
+/// returned by `FirmwareManagement::get_image_info`.
+#[derive(Debug)]
+pub struct ImageInfoList<'buf, 'bt> {
+    /// Descriptors of the images.
+    pub descriptors: ImageDescriptors<'buf>,
+    /// Version of the firmware package, or `0xffff_ffff` if packages are
+    /// not supported.
+    pub package_version: u32,
+    /// Version of the firmware package, as displayed to users.
+    pub package_version_name: Option<FmpString<'bt>>,
+}
+
+/// Information about the firmware package of a device, as returned by
+/// `FirmwareManagement::get_package_info`.
+#[derive(Debug)]
+pub struct PackageInfo<'bt> {
+    /// Version of the package, or `0xffff_ffff` if packages are not
+    /// supported.
+    pub version: u32,
+    /// Version of the package, as displayed to users.
+    pub version_name: Option<FmpString<'bt>>,
+    /// Maximum length of the version name, excluding the null terminator.
+    pub version_name_max_len: u32,
+    /// Attributes supported by the package.
+    pub attributes_supported: PackageAttributes,
+    /// Attributes of the package, among the supported ones.
+    pub attributes_setting: PackageAttributes,
+}
+
+/// The progress callback of the running `set_image` call.
+static mut PROGRESS: Option<*mut dyn FnMut(usize)> = None;
+
+extern "efiapi" fn progress_trampoline(completion: usize) -> Status {
+    if let Some(progress) = unsafe { PROGRESS } {
+        unsafe { (*progress)(completion) };
+    }
+    Status::SUCCESS
+}
+
+/// The Firmware Management Protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct FirmwareManagement {
+    // Clippy correctly complains that this is too complicated, but we can't change the spec.
+    #[allow(clippy::type_complexity)]
+    get_image_info: unsafe extern "efiapi" fn(
+        this: &FirmwareManagement,
+        image_info_size: &mut usize,
+        image_info: *mut u8,
+        descriptor_version: &mut u32,
+        descriptor_count: &mut u8,
+        descriptor_size: &mut usize,
+        package_version: &mut u32,
+        package_version_name: &mut *mut Char16,
+    ) -> Status,
+    get_image: unsafe extern "efiapi" fn(
+        this: &FirmwareManagement,
+        image_index: u8,
+        image: *mut u8,
+        image_size: &mut usize,
+    ) -> Status,
+    set_image: unsafe extern "efiapi" fn(
+        this: &mut FirmwareManagement,
+        image_index: u8,
+        image: *const u8,
+        image_size: usize,
+        vendor_code: *const c_void,
+        progress: Option<extern "efiapi" fn(completion: usize) -> Status>,
+        abort_reason: &mut *mut Char16,
+    ) -> Status,
+    check_image: unsafe extern "efiapi" fn(
+        this: &FirmwareManagement,
+        image_index: u8,
+        image: *const u8,
+        image_size: usize,
+        image_updatable: &mut ImageUpdatable,
+    ) -> Status,
+    get_package_info: unsafe extern "efiapi" fn(
+        this: &FirmwareManagement,
+        package_version: &mut u32,
+        package_version_name: &mut *mut Char16,
+        package_version_name_max_len: &mut u32,
+        attributes_supported: &mut PackageAttributes,
+        attributes_setting: &mut PackageAttributes,
+    ) -> Status,
+    _set_package_info: usize,
+}
+
+unsafe impl Identify for FirmwareManagement {
+    const GUID: Guid = guid!("86c77a67-0b97-4633-a187-49104d0685c7");
+}
+
+impl FirmwareManagement {
+    /// Reads the descriptors of the images of the device into `buffer`,
+    /// along with the version of its firmware package.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size.
+    pub fn get_image_info<'buf, 'bt>(
+        &self,
+        bt: &'bt BootServices,
+        buffer: &'buf mut [u8],
+    ) -> Result<ImageInfoList<'buf, 'bt>, Option<usize>> {
+        let mut size = buffer.len();
+        let mut version = 0;
+        let mut count = 0;
+        let mut descriptor_size = 0;
+        let mut package_version = 0;
+        let mut package_version_name = ptr::null_mut();
+        let status = unsafe {
+            (self.get_image_info)(
+                self,
+                &mut size,
+                buffer.as_mut_ptr(),
+                &mut version,
+                &mut count,
+                &mut descriptor_size,
+                &mut package_version,
+                &mut package_version_name,
+            )
+        };
+        if status == Status::BUFFER_TOO_SMALL {
+            return Err(Error::new(status, Some(size)));
+        }
+        let status = status.into_with_err(|_| None)?;
+        let package_version_name = FmpString::new(package_version_name, bt);
+
+        let descriptors = unsafe {
+            ImageDescriptors::from_bytes(buffer, version, descriptor_size, usize::from(count))
+        };
+        match descriptors {
+            Some(descriptors) => Ok(status.map(|_| ImageInfoList {
+                descriptors,
+                package_version,
+                package_version_name,
+            })),
+            None => Err(Error::new(Status::VOLUME_CORRUPTED, None)),
+        }
+    }
+
+    /// Reads the firmware image `index` into `buffer`, and returns the
+    /// filled part of the buffer.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size. `UNSUPPORTED` is returned if the image cannot be
+    /// read.
+    pub fn get_image<'buf>(
+        &self,
+        index: u8,
+        buffer: &'buf mut [u8],
+    ) -> Result<&'buf mut [u8], Option<usize>> {
+        let mut size = buffer.len();
+        let status = unsafe { (self.get_image)(self, index, buffer.as_mut_ptr(), &mut size) };
+        match status {
+            Status::BUFFER_TOO_SMALL => Err(Error::new(status, Some(size))),
+            status => status.into_with(move || &mut buffer[..size], |_| None),
+        }
+    }
+
+    /// Updates the firmware image `index` with `image`.
+    ///
+    /// `vendor_code` is passed as is to the driver. `progress` is called
+    /// with the completion percentage of the update, from 1 to 100. On
+    /// failure, the driver may explain why the update was aborted in the
+    /// error data. `SECURITY_VIOLATION` is returned if the image could not
+    /// be authenticated.
+    ///
+    /// # Safety
+    ///
+    /// `vendor_code` must be valid for the driver.
+    pub unsafe fn set_image<'bt>(
+        &mut self,
+        bt: &'bt BootServices,
+        index: u8,
+        image: &[u8],
+        vendor_code: *const c_void,
+        progress: Option<&mut dyn FnMut(usize)>,
+    ) -> Result<(), Option<FmpString<'bt>>> {
+        let trampoline = progress
+            .as_ref()
+            .map(|_| progress_trampoline as extern "efiapi" fn(usize) -> Status);
+        // The callback has no context, so it is stored for the trampoline
+        // until the update is done. The previous one is restored, in case
+        // `set_image` is called from a callback.
+        let previous = PROGRESS;
+        PROGRESS = progress.map(|progress| {
+            mem::transmute::<&mut dyn FnMut(usize), *mut dyn FnMut(usize)>(progress)
+        });
+        let mut abort_reason = ptr::null_mut();
+        let status = (self.set_image)(
+            self,
+            index,
+            image.as_ptr(),
+            image.len(),
+            vendor_code,
+            trampoline,
+            &mut abort_reason,
+        );
+        PROGRESS = previous;
+        let abort_reason = FmpString::new(abort_reason, bt);
+        status.into_with_err(|_| abort_reason)
+    }
+
+    /// Checks whether `image` can be used to update the firmware image
+    /// `index`.
+    pub fn check_image(&self, index: u8, image: &[u8]) -> Result<ImageUpdatable> {
+        let mut updatable = ImageUpdatable::empty();
+        unsafe { (self.check_image)(self, index, image.as_ptr(), image.len(), &mut updatable) }
+            .into_with_val(|| updatable)
+    }
+
+    /// Returns information about the firmware package of the device.
+    ///
+    /// `UNSUPPORTED` is returned if the device has no firmware package.
+    pub fn get_package_info<'bt>(&self, bt: &'bt BootServices) -> Result<PackageInfo<'bt>> {
+        let mut version = 0;
+        let mut version_name = ptr::null_mut();
+        let mut version_name_max_len = 0;
+        let mut attributes_supported = PackageAttributes::empty();
+        let mut attributes_setting = PackageAttributes::empty();
+        let status = unsafe {
+            (self.get_package_info)(
+                self,
+                &mut version,
+                &mut version_name,
+                &mut version_name_max_len,
+                &mut attributes_supported,
+                &mut attributes_setting,
+            )
+        };
+        status.into_with_val(|| PackageInfo {
+            version,
+            version_name: FmpString::new(version_name, bt),
+            version_name_max_len,
+            attributes_supported,
+            attributes_setting,
+        })
+    }
+}
@@ -0,0 +1,148 @@
+//! Hash2 protocol.
+//!
+//! This protocol computes hashes using the firmware's implementation of
+//! various hashing algorithms. An instance of it is obtained by creating a
+//! child handle through the `Hash2ServiceBinding` protocol.
+
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::{Completion, Guid, Identify, Result, Status};
+use core::fmt;
+use core::mem;
+
+/// MD5 hashing algorithm.
+pub const ALGORITHM_MD5: Guid = guid!("0af7c79c-65b5-4319-b0ae-44ec484e4ad7");
+/// SHA-1 hashing algorithm.
+pub const ALGORITHM_SHA1: Guid = guid!("2ae9d80f-3fb2-4095-b7b1-e93157b946b6");
+/// SHA-224 hashing algorithm.
+pub const ALGORITHM_SHA224: Guid = guid!("8df01a06-9bd5-4bf7-b021-db4fd9ccf45b");
+/// SHA-256 hashing algorithm.
+pub const ALGORITHM_SHA256: Guid = guid!("51aa59de-fdf2-4ea3-bc63-875fb7842ee9");
+/// SHA-384 hashing algorithm.
+pub const ALGORITHM_SHA384: Guid = guid!("efa96432-de33-4dd2-aee6-328c33df777a");
+/// SHA-512 hashing algorithm.
+pub const ALGORITHM_SHA512: Guid = guid!("caa4381e-750c-4770-b870-7a23b4e42130");
+
+/// Service binding protocol used to create `Hash2` instances.
+pub type Hash2ServiceBinding = ServiceBinding<Hash2>;
+
+unsafe impl ChildProtocol for Hash2 {
+    const SERVICE_BINDING_GUID: Guid = guid!("da836f8d-217f-4ca0-99c2-1ca4e16077ea");
+}
+
+/// Raw hash output, large enough for all the supported algorithms.
+type RawDigest = [u8; 64];
+
+/// Digest computed by the `Hash2` protocol.
+#[derive(Clone, Copy)]
+pub struct Digest {
+    bytes: RawDigest,
+    len: usize,
+}
+
+impl Digest {
+    /// Returns the bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl PartialEq for Digest {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Digest {}
+
+impl fmt::Debug for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Digest(")?;
+        for byte in self.as_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Hash2 protocol.
+///
+/// Streaming hashes can be computed by calling `hash_init`, then `hash_update`
+/// as many times as needed, and finally `hash_final`.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Hash2 {
+    get_hash_size:
+        extern "efiapi" fn(this: &Hash2, algorithm: &Guid, hash_size: &mut usize) -> Status,
+    hash: unsafe extern "efiapi" fn(
+        this: &mut Hash2,
+        algorithm: &Guid,
+        message: *const u8,
+        message_size: usize,
+        hash: *mut RawDigest,
+    ) -> Status,
+    hash_init: extern "efiapi" fn(this: &mut Hash2, algorithm: &Guid) -> Status,
+    hash_update: unsafe extern "efiapi" fn(
+        this: &mut Hash2,
+        message: *const u8,
+        message_size: usize,
+    ) -> Status,
+    hash_final: unsafe extern "efiapi" fn(this: &mut Hash2, hash: *mut RawDigest) -> Status,
+}
+
+unsafe impl Identify for Hash2 {
+    const GUID: Guid = guid!("55b1d734-c5e1-49db-9647-b16afb0e305b");
+}
+
+impl Hash2 {
+    /// Returns the size in bytes of the digests of an algorithm.
+    ///
+    /// Unsupported algorithms result in an `UNSUPPORTED` error.
+    pub fn get_hash_size(&self, algorithm: &Guid) -> Result<usize> {
+        let mut size = 0;
+        (self.get_hash_size)(self, algorithm, &mut size).into_with_val(|| size)
+    }
+
+    /// Hashes a message in one go.
+    pub fn hash(&mut self, algorithm: &Guid, message: &[u8]) -> Result<Digest> {
+        let (status, len) = self.digest_len(algorithm)?.split();
+        let mut bytes = [0; 64];
+        unsafe { (self.hash)(self, algorithm, message.as_ptr(), message.len(), &mut bytes) }
+            .into_with_val(|| Digest { bytes, len })
+            .map(|completion| completion.with_status(status))
+    }
+
+    /// Starts a streaming hash using the given algorithm.
+    ///
+    /// Any streaming hash which is in progress is discarded.
+    pub fn hash_init(&mut self, algorithm: &Guid) -> Result {
+        (self.hash_init)(self, algorithm).into()
+    }
+
+    /// Adds a part of the message to the streaming hash.
+    pub fn hash_update(&mut self, message: &[u8]) -> Result {
+        unsafe { (self.hash_update)(self, message.as_ptr(), message.len()) }.into()
+    }
+
+    /// Finishes the streaming hash and returns its digest.
+    ///
+    /// The `algorithm` must be the one which was passed to `hash_init`,
+    /// it is used to determine the size of the digest.
+    pub fn hash_final(&mut self, algorithm: &Guid) -> Result<Digest> {
+        let (status, len) = self.digest_len(algorithm)?.split();
+        let mut bytes = [0; 64];
+        unsafe { (self.hash_final)(self, &mut bytes) }
+            .into_with_val(|| Digest { bytes, len })
+            .map(|completion| completion.with_status(status))
+    }
+
+    /// Returns the digest size of an algorithm, checking that it fits in
+    /// the output buffer.
+    fn digest_len(&self, algorithm: &Guid) -> Result<usize> {
+        let (status, len) = self.get_hash_size(algorithm)?.split();
+        if len > mem::size_of::<RawDigest>() {
+            return Err(Status::UNSUPPORTED.into());
+        }
+        Ok(Completion::new(status, len))
+    }
+}
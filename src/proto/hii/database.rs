@@ -0,0 +1,311 @@
+//! HII Database protocol.
+
+use super::{HiiHandle, PackageList, PackageType};
+use crate::proto::Protocol;
+#[cfg(feature = "exts")]
+use crate::{
+    alloc_api::{vec, vec::Vec},
+    ResultExt,
+};
+use crate::{Error, Guid, Handle, Identify, Result, Status};
+use core::convert::TryInto;
+use core::{fmt, mem, ptr};
+
+/// A keyboard layout (`EFI_HII_KEYBOARD_LAYOUT`), as returned by
+/// `HiiDatabase::get_keyboard_layout`.
+#[repr(transparent)]
+pub struct KeyboardLayout([u8]);
+
+impl KeyboardLayout {
+    /// Size of the header of a keyboard layout.
+    pub const HEADER_SIZE: usize = 23;
+
+    /// Size of each key descriptor (`EFI_KEY_DESCRIPTOR`).
+    pub const DESCRIPTOR_SIZE: usize = 16;
+
+    /// Views some bytes as a keyboard layout, checking that they hold its
+    /// header and key descriptors.
+    pub fn parse(bytes: &[u8]) -> Option<&KeyboardLayout> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return None;
+        }
+        let layout = unsafe { &*(bytes as *const [u8] as *const KeyboardLayout) };
+        let len = layout.descriptors_end();
+        if usize::from(u16::from_le_bytes([bytes[0], bytes[1]])) != bytes.len() || len > bytes.len()
+        {
+            return None;
+        }
+        Some(layout)
+    }
+
+    /// Returns the raw bytes of the layout.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the GUID identifying the layout.
+    pub fn guid(&self) -> Guid {
+        Guid::from_bytes(self.0[2..18].try_into().unwrap())
+    }
+
+    /// Returns the offset of the descriptions of the layout, from the start
+    /// of the layout.
+    pub fn descriptor_string_offset(&self) -> usize {
+        u32::from_le_bytes(self.0[18..22].try_into().unwrap()) as usize
+    }
+
+    /// Returns the raw key descriptors, each of them being
+    /// `DESCRIPTOR_SIZE` bytes long.
+    pub fn descriptors(&self) -> impl ExactSizeIterator<Item = &[u8]> {
+        self.0[Self::HEADER_SIZE..self.descriptors_end()].chunks_exact(Self::DESCRIPTOR_SIZE)
+    }
+
+    fn descriptors_end(&self) -> usize {
+        Self::HEADER_SIZE + usize::from(self.0[22]) * Self::DESCRIPTOR_SIZE
+    }
+}
+
+impl fmt::Debug for KeyboardLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyboardLayout")
+            .field("guid", &self.guid())
+            .field("descriptor_count", &self.descriptors().len())
+            .finish()
+    }
+}
+
+/// The HII Database protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct HiiDatabase {
+    new_package_list: unsafe extern "efiapi" fn(
+        this: &HiiDatabase,
+        package_list: *const u8,
+        driver_handle: Option<Handle>,
+        handle: &mut Option<HiiHandle>,
+    ) -> Status,
+    remove_package_list: extern "efiapi" fn(this: &HiiDatabase, handle: HiiHandle) -> Status,
+    _update_package_list: usize,
+    list_package_lists: unsafe extern "efiapi" fn(
+        this: &HiiDatabase,
+        package_type: PackageType,
+        package_guid: *const Guid,
+        handle_buffer_length: &mut usize,
+        handle: *mut HiiHandle,
+    ) -> Status,
+    export_package_lists: unsafe extern "efiapi" fn(
+        this: &HiiDatabase,
+        handle: Option<HiiHandle>,
+        buffer_size: &mut usize,
+        buffer: *mut u8,
+    ) -> Status,
+    _register_package_notify: usize,
+    _unregister_package_notify: usize,
+    find_keyboard_layouts: unsafe extern "efiapi" fn(
+        this: &HiiDatabase,
+        key_guid_buffer_length: &mut u16,
+        key_guid_buffer: *mut Guid,
+    ) -> Status,
+    get_keyboard_layout: unsafe extern "efiapi" fn(
+        this: &HiiDatabase,
+        key_guid: *const Guid,
+        keyboard_layout_length: &mut u16,
+        keyboard_layout: *mut u8,
+    ) -> Status,
+    set_keyboard_layout: unsafe extern "efiapi" fn(this: &HiiDatabase, key_guid: &Guid) -> Status,
+    get_package_list_handle: unsafe extern "efiapi" fn(
+        this: &HiiDatabase,
+        package_list_handle: HiiHandle,
+        driver_handle: &mut Option<Handle>,
+    ) -> Status,
+}
+
+unsafe impl Identify for HiiDatabase {
+    const GUID: Guid = guid!("ef9fc172-a1b2-4693-b327-6d32fc416042");
+}
+
+/// Converts a `BUFFER_TOO_SMALL` error into the required size.
+fn too_small<T>(
+    status: Status,
+    value: impl FnOnce() -> T,
+    size: usize,
+) -> Result<T, Option<usize>> {
+    match status {
+        Status::BUFFER_TOO_SMALL => Err(Error::new(status, Some(size))),
+        status => status.into_with(value, |_| None),
+    }
+}
+
+impl HiiDatabase {
+    /// Registers a package list, and returns its handle.
+    ///
+    /// `driver` is the handle on which the driver installed the device path
+    /// of its controller, if it publishes forms for it.
+    pub fn new_package_list(
+        &self,
+        package_list: &PackageList,
+        driver: Option<Handle>,
+    ) -> Result<HiiHandle> {
+        let mut handle = None;
+        let status = unsafe {
+            (self.new_package_list)(self, package_list.as_bytes().as_ptr(), driver, &mut handle)
+        };
+        // The firmware always returns a handle on success.
+        status.into_with_val(|| handle.unwrap())
+    }
+
+    /// Removes a package list registered by `new_package_list`.
+    pub fn remove_package_list(&self, handle: HiiHandle) -> Result {
+        (self.remove_package_list)(self, handle).into()
+    }
+
+    /// Writes the handles of the package lists containing packages of type
+    /// `package_type` to `handles`, and returns the filled part of the
+    /// buffer.
+    ///
+    /// `PackageType::ALL` matches all the package lists. For
+    /// `PackageType::TYPE_GUID`, `guid` identifies the type of the packages,
+    /// and it is ignored otherwise. If the buffer is too small,
+    /// `BUFFER_TOO_SMALL` is returned along with the required number of
+    /// handles. `NOT_FOUND` is returned if no package list matches.
+    pub fn list_package_lists<'buf>(
+        &self,
+        package_type: PackageType,
+        guid: Option<&Guid>,
+        handles: &'buf mut [HiiHandle],
+    ) -> Result<&'buf mut [HiiHandle], Option<usize>> {
+        let handle_size = mem::size_of::<HiiHandle>();
+        let mut size = mem::size_of_val(handles);
+        let guid = guid.map_or(ptr::null(), |guid| guid as *const Guid);
+        let status = unsafe {
+            (self.list_package_lists)(self, package_type, guid, &mut size, handles.as_mut_ptr())
+        };
+        too_small(
+            status,
+            move || &mut handles[..size / handle_size],
+            size / handle_size,
+        )
+    }
+
+    /// Returns the handles of the package lists containing packages of type
+    /// `package_type`, in a newly allocated vector.
+    ///
+    /// Unlike `list_package_lists`, no package list matching is not an
+    /// error.
+    #[cfg(feature = "exts")]
+    pub fn list_package_lists_to_vec(
+        &self,
+        package_type: PackageType,
+        guid: Option<&Guid>,
+    ) -> Result<Vec<HiiHandle>> {
+        let count = match self.list_package_lists(package_type, guid, &mut []) {
+            Ok(handles) => handles.log().len(),
+            Err(err) => match (err.status(), err.data()) {
+                (_, Some(count)) => *count,
+                (Status::NOT_FOUND, None) => return Ok(Vec::new().into()),
+                (status, None) => return Err(status.into()),
+            },
+        };
+        let mut handles = Vec::with_capacity(count);
+        let mut size = count * mem::size_of::<HiiHandle>();
+        let guid = guid.map_or(ptr::null(), |guid| guid as *const Guid);
+        unsafe {
+            (self.list_package_lists)(self, package_type, guid, &mut size, handles.as_mut_ptr())
+        }
+        .into_with_val(|| {
+            unsafe { handles.set_len(size / mem::size_of::<HiiHandle>()) };
+            handles
+        })
+    }
+
+    /// Writes the package list registered under `handle` to `buffer`, or all
+    /// the package lists back to back if `handle` is `None`, and returns the
+    /// filled part of the buffer.
+    ///
+    /// The lists can be parsed with `PackageList::parse`. If the buffer is
+    /// too small, `BUFFER_TOO_SMALL` is returned along with the required
+    /// size.
+    pub fn export_package_lists<'buf>(
+        &self,
+        handle: Option<HiiHandle>,
+        buffer: &'buf mut [u8],
+    ) -> Result<&'buf [u8], Option<usize>> {
+        let mut size = buffer.len();
+        let status =
+            unsafe { (self.export_package_lists)(self, handle, &mut size, buffer.as_mut_ptr()) };
+        too_small(status, move || &buffer[..size], size)
+    }
+
+    /// Exports package lists like `export_package_lists`, into a newly
+    /// allocated buffer.
+    #[cfg(feature = "exts")]
+    pub fn export_package_lists_to_vec(&self, handle: Option<HiiHandle>) -> Result<Vec<u8>> {
+        let size = match self.export_package_lists(handle, &mut []) {
+            Ok(data) => data.log().len(),
+            Err(err) => match err.data() {
+                Some(size) => *size,
+                None => return Err(err.status().into()),
+            },
+        };
+        let mut buffer = vec![0; size];
+        self.export_package_lists(handle, &mut buffer)
+            .discard_errdata()
+            .map_inner(|data| data.len())
+            .map_inner(move |size| {
+                buffer.truncate(size);
+                buffer
+            })
+    }
+
+    /// Writes the GUIDs of the keyboard layouts to `guids`, and returns the
+    /// filled part of the buffer.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required number of GUIDs.
+    pub fn find_keyboard_layouts<'buf>(
+        &self,
+        guids: &'buf mut [Guid],
+    ) -> Result<&'buf mut [Guid], Option<usize>> {
+        let guid_size = mem::size_of::<Guid>();
+        let mut size = mem::size_of_val(guids).try_into().unwrap_or(u16::MAX);
+        let status = unsafe { (self.find_keyboard_layouts)(self, &mut size, guids.as_mut_ptr()) };
+        let count = usize::from(size) / guid_size;
+        too_small(status, move || &mut guids[..count], count)
+    }
+
+    /// Reads the keyboard layout identified by `guid`, or the current one if
+    /// it is `None`, into `buffer`.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size. `NOT_FOUND` is returned if there is no such
+    /// layout.
+    pub fn get_keyboard_layout<'buf>(
+        &self,
+        guid: Option<&Guid>,
+        buffer: &'buf mut [u8],
+    ) -> Result<&'buf KeyboardLayout, Option<usize>> {
+        let mut size = buffer.len().try_into().unwrap_or(u16::MAX);
+        let guid = guid.map_or(ptr::null(), |guid| guid as *const Guid);
+        let status =
+            unsafe { (self.get_keyboard_layout)(self, guid, &mut size, buffer.as_mut_ptr()) };
+        let size = usize::from(size);
+        let layout = too_small(status, || (), size)?;
+        match KeyboardLayout::parse(&buffer[..size.min(buffer.len())]) {
+            Some(parsed) => Ok(layout.map(|_| parsed)),
+            None => Err(Error::new(Status::VOLUME_CORRUPTED, None)),
+        }
+    }
+
+    /// Sets the current keyboard layout to the one identified by `guid`.
+    pub fn set_keyboard_layout(&self, guid: &Guid) -> Result {
+        unsafe { (self.set_keyboard_layout)(self, guid) }.into()
+    }
+
+    /// Returns the driver handle given when the package list `handle` was
+    /// registered, if any.
+    pub fn get_package_list_handle(&self, handle: HiiHandle) -> Result<Option<Handle>> {
+        let mut driver = None;
+        unsafe { (self.get_package_list_handle)(self, handle, &mut driver) }
+            .into_with_val(|| driver)
+    }
+}
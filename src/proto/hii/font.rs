@@ -0,0 +1,422 @@
+//! HII Font protocol.
+//!
+//! This protocol renders strings with the fonts registered in the HII
+//! database, into a buffer of `BltPixel`s which can then be blitted with the
+//! Graphics Output Protocol, or directly to the screen.
+
+use crate::proto::console::gop::{BltPixel, GraphicsOutput};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{CStr16, Char16, Guid, Identify, Result, Status};
+use bitflags::bitflags;
+use core::convert::TryFrom;
+use core::ffi::c_void;
+use core::{fmt, ptr, slice};
+
+bitflags! {
+    /// Flags controlling how `HiiFont::string_to_image` renders a string.
+    #[repr(transparent)]
+    pub struct OutFlags: u32 {
+        /// Characters which do not fit in the image are clipped, instead of
+        /// failing with `BUFFER_TOO_SMALL`.
+        const CLIP = 0x0000_0001;
+        /// Lines which do not fit in the image are wrapped at the last
+        /// possible break opportunity.
+        const WRAP = 0x0000_0002;
+        /// With `CLIP`, lines which do not fit vertically are not drawn
+        /// at all, instead of being partially drawn.
+        const CLIP_CLEAN_Y = 0x0000_0004;
+        /// With `CLIP`, characters which do not fit horizontally are not
+        /// drawn at all, instead of being partially drawn. It cannot be
+        /// combined with `WRAP`.
+        const CLIP_CLEAN_X = 0x0000_0008;
+        /// The background color is not drawn, leaving the pixels of the
+        /// image unchanged.
+        const TRANSPARENT = 0x0000_0010;
+        /// Characters without a glyph are skipped, instead of being drawn
+        /// with a replacement glyph.
+        const IGNORE_IF_NO_GLYPH = 0x0000_0020;
+        /// Line breaks are not interpreted.
+        const IGNORE_LINE_BREAK = 0x0000_0040;
+        /// The string is drawn directly to the screen. This flag is set by
+        /// `HiiFont::string_to_screen`.
+        const DIRECT_TO_SCREEN = 0x0000_0080;
+    }
+}
+
+bitflags! {
+    /// Which fields of a `FontDisplayInfo` are replaced by the system
+    /// default ones, and how the font may be matched.
+    #[repr(transparent)]
+    pub struct FontInfoMask: u32 {
+        /// Use the system font.
+        const SYS_FONT = 0x0000_0001;
+        /// Use the size of the system font.
+        const SYS_SIZE = 0x0000_0002;
+        /// Use the style of the system font.
+        const SYS_STYLE = 0x0000_0004;
+        /// Use the system foreground color.
+        const SYS_FORE_COLOR = 0x0000_0010;
+        /// Use the system background color.
+        const SYS_BACK_COLOR = 0x0000_0020;
+        /// Allow scaling a font of another size to the requested size.
+        const RESIZE = 0x0000_1000;
+        /// Allow synthesizing the requested style from another style.
+        const RESTYLE = 0x0000_2000;
+        /// Allow any font if the requested one is missing.
+        const ANY_FONT = 0x0001_0000;
+        /// Allow any size if the requested one is missing.
+        const ANY_SIZE = 0x0002_0000;
+        /// Allow any style if the requested one is missing.
+        const ANY_STYLE = 0x0004_0000;
+    }
+}
+
+bitflags! {
+    /// Style of a font.
+    #[repr(transparent)]
+    pub struct FontStyle: u32 {
+        /// Regular characters.
+        const NORMAL = 0x0000_0000;
+        /// Bold characters.
+        const BOLD = 0x0000_0001;
+        /// Italic characters.
+        const ITALIC = 0x0000_0002;
+        /// Embossed characters.
+        const EMBOSS = 0x0001_0000;
+        /// Outlined characters.
+        const OUTLINE = 0x0002_0000;
+        /// Shadowed characters.
+        const SHADOW = 0x0004_0000;
+        /// Underlined characters.
+        const UNDERLINE = 0x0008_0000;
+        /// Double underlined characters.
+        const DBL_UNDER = 0x0010_0000;
+    }
+}
+
+/// Font and colors used to render a string (`EFI_FONT_DISPLAY_INFO`).
+///
+/// Only the fonts registered without a name can be selected this way, as
+/// the name of the font is left empty.
+///
+/// ```
+/// use uefi::proto::console::gop::BltPixel;
+/// use uefi::proto::hii::font::{FontDisplayInfo, FontInfoMask, FontStyle};
+///
+/// let info = FontDisplayInfo::new()
+///     .foreground(BltPixel::new(0xff, 0xff, 0xff))
+///     .style(FontStyle::BOLD);
+/// assert_eq!(
+///     info.mask(),
+///     FontInfoMask::SYS_FONT
+///         | FontInfoMask::SYS_SIZE
+///         | FontInfoMask::SYS_BACK_COLOR
+///         | FontInfoMask::RESTYLE
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct FontDisplayInfo {
+    foreground: BltPixel,
+    background: BltPixel,
+    mask: FontInfoMask,
+    style: FontStyle,
+    size: u16,
+    name: [Char16; 1],
+}
+
+impl FontDisplayInfo {
+    /// Starts from the system font, with its size, style and colors.
+    pub fn new() -> Self {
+        Self {
+            foreground: BltPixel::new(0, 0, 0),
+            background: BltPixel::new(0, 0, 0),
+            mask: FontInfoMask::SYS_FONT
+                | FontInfoMask::SYS_SIZE
+                | FontInfoMask::SYS_STYLE
+                | FontInfoMask::SYS_FORE_COLOR
+                | FontInfoMask::SYS_BACK_COLOR,
+            style: FontStyle::NORMAL,
+            size: 0,
+            name: [unsafe { Char16::from_u16_unchecked(0) }],
+        }
+    }
+
+    /// Sets the color of the characters.
+    pub fn foreground(mut self, color: BltPixel) -> Self {
+        self.foreground = color;
+        self.mask.remove(FontInfoMask::SYS_FORE_COLOR);
+        self
+    }
+
+    /// Sets the color behind the characters.
+    pub fn background(mut self, color: BltPixel) -> Self {
+        self.background = color;
+        self.mask.remove(FontInfoMask::SYS_BACK_COLOR);
+        self
+    }
+
+    /// Sets the style of the font, which may be synthesized from the system
+    /// font.
+    pub fn style(mut self, style: FontStyle) -> Self {
+        self.style = style;
+        self.mask.remove(FontInfoMask::SYS_STYLE);
+        self.mask.insert(FontInfoMask::RESTYLE);
+        self
+    }
+
+    /// Sets the height of the font in pixels, to which the system font may
+    /// be scaled.
+    pub fn size(mut self, size: u16) -> Self {
+        self.size = size;
+        self.mask.remove(FontInfoMask::SYS_SIZE);
+        self.mask.insert(FontInfoMask::RESIZE);
+        self
+    }
+
+    /// Adds flags to the mask, such as `ANY_SIZE` to fall back to any size.
+    pub fn with_mask(mut self, mask: FontInfoMask) -> Self {
+        self.mask.insert(mask);
+        self
+    }
+
+    /// Returns the mask of the fields replaced by the system defaults, and
+    /// of the allowed fallbacks.
+    pub fn mask(&self) -> FontInfoMask {
+        self.mask
+    }
+}
+
+impl Default for FontDisplayInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An image rendered by the firmware (`EFI_IMAGE_OUTPUT`).
+#[repr(C)]
+struct ImageOutput {
+    width: u16,
+    height: u16,
+    /// Either the bitmap, or the GOP with `DIRECT_TO_SCREEN`.
+    image: *mut c_void,
+}
+
+/// The glyph of a character, returned by `HiiFont::get_glyph`, which is
+/// freed when dropped.
+pub struct Glyph<'a> {
+    image: *mut ImageOutput,
+    baseline: usize,
+    bt: &'a BootServices,
+}
+
+impl Glyph<'_> {
+    /// Width of the glyph, in pixels.
+    pub fn width(&self) -> usize {
+        usize::from(unsafe { (*self.image).width })
+    }
+
+    /// Height of the glyph, in pixels.
+    pub fn height(&self) -> usize {
+        usize::from(unsafe { (*self.image).height })
+    }
+
+    /// Pixels of the glyph, row by row, which can be blitted with
+    /// `BltOp::BufferToVideo`.
+    pub fn pixels(&self) -> &[BltPixel] {
+        let bitmap = unsafe { (*self.image).image }.cast::<BltPixel>();
+        if bitmap.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(bitmap, self.width() * self.height()) }
+        }
+    }
+
+    /// Offset of the baseline of the glyph from its bottom, in pixels.
+    pub fn baseline(&self) -> usize {
+        self.baseline
+    }
+}
+
+impl fmt::Debug for Glyph<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Glyph")
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .field("baseline", &self.baseline)
+            .finish()
+    }
+}
+
+impl Drop for Glyph<'_> {
+    fn drop(&mut self) {
+        // Ignore the results, we can't do anything about an error here.
+        let bitmap = unsafe { (*self.image).image };
+        if !bitmap.is_null() {
+            let _ = self.bt.free_pool(bitmap.cast());
+        }
+        let _ = self.bt.free_pool(self.image.cast());
+    }
+}
+
+/// The HII Font protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct HiiFont {
+    // Clippy correctly complains that this is too complicated, but we can't change the spec.
+    #[allow(clippy::type_complexity)]
+    string_to_image: unsafe extern "efiapi" fn(
+        this: &HiiFont,
+        flags: OutFlags,
+        string: *const Char16,
+        string_info: *const FontDisplayInfo,
+        blt: &mut *mut ImageOutput,
+        blt_x: usize,
+        blt_y: usize,
+        row_info_array: *mut *mut c_void,
+        row_info_array_size: *mut usize,
+        column_info_array: *mut usize,
+    ) -> Status,
+    _string_id_to_image: usize,
+    get_glyph: unsafe extern "efiapi" fn(
+        this: &HiiFont,
+        char: Char16,
+        string_info: *const FontDisplayInfo,
+        blt: &mut *mut ImageOutput,
+        baseline: *mut usize,
+    ) -> Status,
+    _get_font_info: usize,
+}
+
+unsafe impl Identify for HiiFont {
+    const GUID: Guid = guid!("e9ca4775-8657-47fc-97e7-7ed65a084324");
+}
+
+impl HiiFont {
+    /// Renders `string` into `pixels`, an image of `width` pixels per row,
+    /// with the top left corner of the text at `(x, y)`.
+    ///
+    /// The font and colors are the system defaults if `info` is `None`.
+    /// The height of the image is the number of complete rows of the
+    /// buffer, which can then be blitted with `BltOp::BufferToVideo`.
+    ///
+    /// Unless `OutFlags::CLIP` is given, `BUFFER_TOO_SMALL` is returned if
+    /// the text does not fit in the image. `INVALID_PARAMETER` is returned if
+    /// the origin is outside of the image, if the image is larger than
+    /// 65535 pixels in either dimension, or if the flags are an invalid
+    /// combination, such as `CLIP_CLEAN_X` without `CLIP`.
+    pub fn string_to_image(
+        &self,
+        flags: OutFlags,
+        string: &CStr16,
+        info: Option<&FontDisplayInfo>,
+        pixels: &mut [BltPixel],
+        width: usize,
+        (x, y): (usize, usize),
+    ) -> Result {
+        let height = pixels.len().checked_div(width).unwrap_or(0);
+        let mut image = ImageOutput {
+            width: u16::try_from(width).map_err(|_| Status::INVALID_PARAMETER)?,
+            height: u16::try_from(height).map_err(|_| Status::INVALID_PARAMETER)?,
+            image: pixels.as_mut_ptr().cast(),
+        };
+        if x >= width || y >= height {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        self.render(
+            flags - OutFlags::DIRECT_TO_SCREEN,
+            string,
+            info,
+            &mut image,
+            x,
+            y,
+        )
+    }
+
+    /// Renders `string` directly to the screen, with the top left corner of
+    /// the text at `(x, y)`.
+    ///
+    /// The other parameters behave as with `string_to_image`, the image
+    /// being the whole screen.
+    pub fn string_to_screen(
+        &self,
+        flags: OutFlags,
+        string: &CStr16,
+        info: Option<&FontDisplayInfo>,
+        gop: &mut GraphicsOutput,
+        (x, y): (usize, usize),
+    ) -> Result {
+        let (width, height) = gop.current_mode_info().resolution();
+        let mut image = ImageOutput {
+            width: u16::try_from(width).map_err(|_| Status::INVALID_PARAMETER)?,
+            height: u16::try_from(height).map_err(|_| Status::INVALID_PARAMETER)?,
+            image: (gop as *mut GraphicsOutput).cast(),
+        };
+        if x >= width || y >= height {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        self.render(
+            flags | OutFlags::DIRECT_TO_SCREEN,
+            string,
+            info,
+            &mut image,
+            x,
+            y,
+        )
+    }
+
+    fn render(
+        &self,
+        flags: OutFlags,
+        string: &CStr16,
+        info: Option<&FontDisplayInfo>,
+        image: &mut ImageOutput,
+        x: usize,
+        y: usize,
+    ) -> Result {
+        let info = info.map_or(ptr::null(), |info| info as *const FontDisplayInfo);
+        let mut image: *mut ImageOutput = image;
+        unsafe {
+            (self.string_to_image)(
+                self,
+                flags,
+                string.as_ptr(),
+                info,
+                &mut image,
+                x,
+                y,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        }
+        .into()
+    }
+
+    /// Returns the glyph of `c`, rendered with the font and colors of
+    /// `info`, or the system defaults if it is `None`.
+    ///
+    /// If the font has no glyph for this character, a replacement glyph is
+    /// returned with a `WARN_UNKNOWN_GLYPH` warning.
+    pub fn get_glyph<'bt>(
+        &self,
+        bt: &'bt BootServices,
+        c: Char16,
+        info: Option<&FontDisplayInfo>,
+    ) -> Result<Glyph<'bt>> {
+        let info = info.map_or(ptr::null(), |info| info as *const FontDisplayInfo);
+        let mut image = ptr::null_mut();
+        let mut baseline = 0;
+        let baseline = unsafe { (self.get_glyph)(self, c, info, &mut image, &mut baseline) }
+            .into_with_val(|| baseline)?;
+        if image.is_null() {
+            return Err(Status::DEVICE_ERROR.into());
+        }
+        Ok(baseline.map(|baseline| Glyph {
+            image,
+            baseline,
+            bt,
+        }))
+    }
+}
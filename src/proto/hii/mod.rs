@@ -0,0 +1,242 @@
+//! Human Interface Infrastructure (HII) protocols.
+//!
+//! The HII database holds the forms, strings, fonts, images and keyboard
+//! layouts published by drivers, grouped in package lists. Each package list
+//! is registered under an `HiiHandle`, which the other HII protocols use to
+//! refer to it.
+
+use crate::{Error, Guid, Result, Status};
+use core::convert::TryInto;
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr::NonNull;
+
+pub mod database;
+pub mod font;
+pub mod string;
+
+/// Handle of a package list registered in the HII database.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct HiiHandle(NonNull<c_void>);
+
+/// Identifier of a string in the string packages of a package list, which
+/// is the same in all languages.
+pub type StringId = u16;
+
+newtype_enum! {
+/// Type of a package (`EFI_HII_PACKAGE_*`).
+pub enum PackageType: u8 => {
+    /// Matches packages of all types, when listing package lists.
+    ALL             = 0x00,
+    /// Package whose format is identified by a GUID.
+    TYPE_GUID       = 0x01,
+    /// Forms, in the Internal Forms Representation.
+    FORMS           = 0x02,
+    /// Strings of a language.
+    STRINGS         = 0x04,
+    /// Font glyphs.
+    FONTS           = 0x05,
+    /// Images.
+    IMAGES          = 0x06,
+    /// Simplified font glyphs, with fixed sizes.
+    SIMPLE_FONTS    = 0x07,
+    /// Device path of the driver which published the package list.
+    DEVICE_PATH     = 0x08,
+    /// Keyboard layouts.
+    KEYBOARD_LAYOUT = 0x09,
+    /// Animations.
+    ANIMATIONS      = 0x0a,
+    /// Ends a package list.
+    END             = 0xdf,
+}}
+
+/// A package of a `PackageList`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Package<'a> {
+    /// Type of the package.
+    pub package_type: PackageType,
+    /// Contents of the package, without its header.
+    pub data: &'a [u8],
+}
+
+impl Package<'_> {
+    /// Size of the header of a package.
+    pub const HEADER_SIZE: usize = 4;
+}
+
+/// A list of packages, published together by a driver.
+///
+/// This is a byte-level view of an `EFI_HII_PACKAGE_LIST_HEADER` followed by
+/// its packages, whose lengths have been validated. Lists can be parsed from
+/// the data exported by the HII database with `parse`, or written to a
+/// buffer with `build`.
+///
+/// ```
+/// use uefi::proto::hii::{Package, PackageList, PackageType};
+/// use uefi::Guid;
+///
+/// let guid = Guid::from_values(0x12345678, 0x9abc, 0xdef0, 0x1234, [0; 6]);
+/// let packages = [
+///     Package { package_type: PackageType::TYPE_GUID, data: &[0xaa; 20] },
+///     Package { package_type: PackageType::FORMS, data: &[0xbb; 3] },
+/// ];
+///
+/// let mut buffer = [0; 128];
+/// let list = PackageList::build(&mut buffer, guid, &packages)
+///     .unwrap()
+///     .unwrap();
+/// let size = list.as_bytes().len();
+/// // The packages are followed by an end package.
+/// assert_eq!(size, PackageList::HEADER_SIZE + 3 * Package::HEADER_SIZE + 23);
+///
+/// // Parsing back the list, with trailing bytes belonging to another list.
+/// let (parsed, rest) = PackageList::parse(&buffer[..size + 3]).unwrap();
+/// assert_eq!(rest.len(), 3);
+/// assert_eq!(parsed.guid(), guid);
+/// assert!(parsed.packages().eq(packages.iter().copied()));
+///
+/// // Truncated lists are rejected.
+/// assert!(PackageList::parse(&buffer[..size - 1]).is_none());
+/// buffer[PackageList::HEADER_SIZE] = 0xff;
+/// assert!(PackageList::parse(&buffer[..size]).is_none());
+/// ```
+#[repr(transparent)]
+pub struct PackageList([u8]);
+
+/// Reads a little endian `u32` at the given offset.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Splits the package at the beginning of `bytes`, or returns `None` if it
+/// is truncated.
+fn split_package(bytes: &[u8]) -> Option<(Package<'_>, &[u8])> {
+    if bytes.len() < Package::HEADER_SIZE {
+        return None;
+    }
+    let header = read_u32(bytes, 0);
+    let len = (header & 0x00ff_ffff) as usize;
+    if len < Package::HEADER_SIZE || len > bytes.len() {
+        return None;
+    }
+    let package = Package {
+        package_type: PackageType((header >> 24) as u8),
+        data: &bytes[Package::HEADER_SIZE..len],
+    };
+    Some((package, &bytes[len..]))
+}
+
+impl PackageList {
+    /// Size of the header of a package list.
+    pub const HEADER_SIZE: usize = 20;
+
+    /// Parses the package list at the beginning of `bytes`.
+    ///
+    /// Exporting all the package lists of the HII database returns them back
+    /// to back, so the bytes following the list are returned too. `None` is
+    /// returned if the list or one of its packages is truncated.
+    pub fn parse(bytes: &[u8]) -> Option<(&PackageList, &[u8])> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return None;
+        }
+        let list_size = read_u32(bytes, 16) as usize;
+        if list_size < Self::HEADER_SIZE || list_size > bytes.len() {
+            return None;
+        }
+
+        let mut packages = &bytes[Self::HEADER_SIZE..list_size];
+        while !packages.is_empty() {
+            packages = split_package(packages)?.1;
+        }
+        let list = unsafe { Self::from_bytes_unchecked(&bytes[..list_size]) };
+        Some((list, &bytes[list_size..]))
+    }
+
+    /// Writes a package list made of `packages` to `buffer`, followed by an
+    /// end package.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size. `INVALID_PARAMETER` is returned if a package is
+    /// larger than 16 MiB.
+    pub fn build<'buf>(
+        buffer: &'buf mut [u8],
+        guid: Guid,
+        packages: &[Package],
+    ) -> Result<&'buf PackageList, Option<usize>> {
+        let end = Package {
+            package_type: PackageType::END,
+            data: &[],
+        };
+        let mut list_size = Self::HEADER_SIZE;
+        for package in packages.iter().chain(Some(&end)) {
+            let len = Package::HEADER_SIZE + package.data.len();
+            if len > 0x00ff_ffff {
+                return Err(Error::new(Status::INVALID_PARAMETER, None));
+            }
+            list_size += len;
+        }
+        let list_size_u32: u32 = match list_size.try_into() {
+            Ok(size) => size,
+            Err(_) => return Err(Error::new(Status::INVALID_PARAMETER, None)),
+        };
+        if buffer.len() < list_size {
+            return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(list_size)));
+        }
+
+        let (header, mut data) = buffer[..list_size].split_at_mut(Self::HEADER_SIZE);
+        header[..16].copy_from_slice(&guid.to_bytes());
+        header[16..20].copy_from_slice(&list_size_u32.to_le_bytes());
+        for package in packages.iter().chain(Some(&end)) {
+            let len = Package::HEADER_SIZE + package.data.len();
+            let (slot, rest) = data.split_at_mut(len);
+            let package_header = len as u32 | u32::from(package.package_type.0) << 24;
+            slot[..Package::HEADER_SIZE].copy_from_slice(&package_header.to_le_bytes());
+            slot[Package::HEADER_SIZE..].copy_from_slice(package.data);
+            data = rest;
+        }
+
+        Ok(unsafe { Self::from_bytes_unchecked(&buffer[..list_size]) }.into())
+    }
+
+    /// Views some bytes as a package list, without validating them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be a well-formed package list, with no trailing bytes.
+    pub unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &PackageList {
+        &*(bytes as *const [u8] as *const PackageList)
+    }
+
+    /// Returns the raw bytes of the list.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the GUID identifying the list.
+    pub fn guid(&self) -> Guid {
+        Guid::from_bytes(self.0[..16].try_into().unwrap())
+    }
+
+    /// Returns an iterator over the packages of the list, until the end
+    /// package.
+    pub fn packages(&self) -> impl Iterator<Item = Package<'_>> + Clone {
+        let mut bytes = &self.0[Self::HEADER_SIZE..];
+        core::iter::from_fn(move || {
+            let (package, rest) = split_package(bytes)?;
+            bytes = rest;
+            Some(package)
+        })
+        .take_while(|package| package.package_type != PackageType::END)
+    }
+}
+
+impl fmt::Debug for PackageList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PackageList")
+            .field("guid", &self.guid())
+            .field("len", &self.0.len())
+            .field("package_count", &self.packages().count())
+            .finish()
+    }
+}
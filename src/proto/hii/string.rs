@@ -0,0 +1,281 @@
+//! HII String protocol.
+
+use super::{HiiHandle, StringId};
+use crate::proto::Protocol;
+use crate::{CStr16, CStr8, Char16, Char8, Error, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+use core::{fmt, ptr, str};
+
+/// A list of RFC 4646 language tags, such as `en-US;fr-FR`, as returned by
+/// `HiiString::get_languages`.
+///
+/// ```
+/// use uefi::proto::hii::string::LanguageList;
+///
+/// let list = LanguageList::new("en;fr-FR;de-DE");
+/// assert!(list.iter().eq(["en", "fr-FR", "de-DE"]));
+///
+/// // Exact matches are preferred, then the more general tags.
+/// assert_eq!(list.best_match(&["fr-fr"]), Some("fr-FR"));
+/// assert_eq!(list.best_match(&["en-US", "fr-FR"]), Some("en"));
+/// // Failing that, a more specific tag is accepted.
+/// assert_eq!(list.best_match(&["it", "de"]), Some("de-DE"));
+/// assert_eq!(list.best_match(&["ja-JP"]), None);
+///
+/// assert_eq!(LanguageList::new("").iter().count(), 0);
+/// ```
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct LanguageList<'a>(&'a str);
+
+impl<'a> LanguageList<'a> {
+    /// Wraps a list of language tags separated by semicolons.
+    pub fn new(list: &'a str) -> Self {
+        Self(list)
+    }
+
+    /// Returns the raw list.
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+
+    /// Iterates over the language tags.
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> + Clone {
+        self.0.split(';').filter(|tag| !tag.is_empty())
+    }
+
+    /// Returns the tag of the list which best matches the tags in
+    /// `preferred`, in decreasing order of preference.
+    ///
+    /// Tags are compared without regard to case. For each preferred tag,
+    /// the list is searched for the tag itself, and then for more general
+    /// tags, by removing its subtags from the end, so that `en` matches
+    /// `en-US`. If none of the preferred tags match this way, the first tag
+    /// of the list which is more specific than a preferred tag is returned,
+    /// so that `en-GB` matches `en`.
+    pub fn best_match(&self, preferred: &[&str]) -> Option<&'a str> {
+        for &tag in preferred {
+            let mut tag = tag;
+            loop {
+                if let Some(found) = self.iter().find(|lang| lang.eq_ignore_ascii_case(tag)) {
+                    return Some(found);
+                }
+                match tag.rfind('-') {
+                    Some(index) => tag = &tag[..index],
+                    None => break,
+                }
+            }
+        }
+        preferred.iter().find_map(|tag| {
+            self.iter().find(|lang| {
+                lang.len() > tag.len()
+                    && lang.as_bytes()[tag.len()] == b'-'
+                    && lang[..tag.len()].eq_ignore_ascii_case(tag)
+            })
+        })
+    }
+}
+
+impl fmt::Debug for LanguageList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// The HII String protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct HiiString {
+    new_string: unsafe extern "efiapi" fn(
+        this: &HiiString,
+        package_list: HiiHandle,
+        string_id: &mut StringId,
+        language: *const Char8,
+        language_name: *const Char16,
+        string: *const Char16,
+        string_font_info: *const c_void,
+    ) -> Status,
+    get_string: unsafe extern "efiapi" fn(
+        this: &HiiString,
+        language: *const Char8,
+        package_list: HiiHandle,
+        string_id: StringId,
+        string: *mut Char16,
+        string_size: &mut usize,
+        string_font_info: *mut *mut c_void,
+    ) -> Status,
+    set_string: unsafe extern "efiapi" fn(
+        this: &HiiString,
+        package_list: HiiHandle,
+        string_id: StringId,
+        language: *const Char8,
+        string: *const Char16,
+        string_font_info: *const c_void,
+    ) -> Status,
+    get_languages: unsafe extern "efiapi" fn(
+        this: &HiiString,
+        package_list: HiiHandle,
+        languages: *mut u8,
+        languages_size: &mut usize,
+    ) -> Status,
+    get_secondary_languages: unsafe extern "efiapi" fn(
+        this: &HiiString,
+        package_list: HiiHandle,
+        primary_language: *const Char8,
+        secondary_languages: *mut u8,
+        secondary_languages_size: &mut usize,
+    ) -> Status,
+}
+
+unsafe impl Identify for HiiString {
+    const GUID: Guid = guid!("0fd96974-23aa-4cdc-b9cb-98d17750322a");
+}
+
+/// Converts a language list written by the firmware into a `LanguageList`.
+fn language_list(
+    status: Status,
+    buffer: &[u8],
+    size: usize,
+) -> Result<LanguageList<'_>, Option<usize>> {
+    if status == Status::BUFFER_TOO_SMALL {
+        return Err(Error::new(status, Some(size)));
+    }
+    status.into_with_err(|_| None)?.log();
+    let list = &buffer[..size.min(buffer.len())];
+    let len = list.iter().position(|&c| c == 0).unwrap_or(list.len());
+    match str::from_utf8(&list[..len]) {
+        Ok(list) => Ok(LanguageList::new(list).into()),
+        Err(_) => Err(Error::new(Status::VOLUME_CORRUPTED, None)),
+    }
+}
+
+impl HiiString {
+    /// Adds a string to the package list `package_list`, in the language
+    /// `language`, and returns its identifier.
+    ///
+    /// The string is added to the string package of this language, which is
+    /// created if needed, with `language_name` as the name of the language.
+    pub fn new_string(
+        &self,
+        package_list: HiiHandle,
+        language: &CStr8,
+        language_name: Option<&CStr16>,
+        string: &CStr16,
+    ) -> Result<StringId> {
+        let mut id = 0;
+        let language_name = language_name.map_or(ptr::null(), CStr16::as_ptr);
+        unsafe {
+            (self.new_string)(
+                self,
+                package_list,
+                &mut id,
+                language.as_ptr(),
+                language_name,
+                string.as_ptr(),
+                ptr::null(),
+            )
+        }
+        .into_with_val(|| id)
+    }
+
+    /// Reads the string `id` of the package list `package_list`, in the
+    /// language `language`, into `buffer`.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required number of characters. `INVALID_LANGUAGE` is returned if
+    /// the package list has no strings in this language, and `NOT_FOUND` if
+    /// it has no such string.
+    pub fn get_string<'buf>(
+        &self,
+        language: &CStr8,
+        package_list: HiiHandle,
+        id: StringId,
+        buffer: &'buf mut [u16],
+    ) -> Result<&'buf CStr16, Option<usize>> {
+        let mut size = buffer.len() * 2;
+        let status = unsafe {
+            (self.get_string)(
+                self,
+                language.as_ptr(),
+                package_list,
+                id,
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+                ptr::null_mut(),
+            )
+        };
+        if status == Status::BUFFER_TOO_SMALL {
+            return Err(Error::new(status, Some(size / 2 + size % 2)));
+        }
+        status.into_with_err(|_| None)?.log();
+        let len = buffer.iter().position(|&c| c == 0);
+        match len {
+            Some(len) => Ok(unsafe { CStr16::from_u16_with_nul_unchecked(&buffer[..=len]) }.into()),
+            None => Err(Error::new(Status::VOLUME_CORRUPTED, None)),
+        }
+    }
+
+    /// Changes the string `id` of the package list `package_list`, in the
+    /// language `language`.
+    pub fn set_string(
+        &self,
+        package_list: HiiHandle,
+        id: StringId,
+        language: &CStr8,
+        string: &CStr16,
+    ) -> Result {
+        unsafe {
+            (self.set_string)(
+                self,
+                package_list,
+                id,
+                language.as_ptr(),
+                string.as_ptr(),
+                ptr::null(),
+            )
+        }
+        .into()
+    }
+
+    /// Reads the languages of the strings of the package list
+    /// `package_list` into `buffer`.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size.
+    pub fn get_languages<'buf>(
+        &self,
+        package_list: HiiHandle,
+        buffer: &'buf mut [u8],
+    ) -> Result<LanguageList<'buf>, Option<usize>> {
+        let mut size = buffer.len();
+        let status =
+            unsafe { (self.get_languages)(self, package_list, buffer.as_mut_ptr(), &mut size) };
+        language_list(status, buffer, size)
+    }
+
+    /// Reads the secondary languages of the package list `package_list`
+    /// for the language `primary` into `buffer`.
+    ///
+    /// These are the languages which can be used as a substitute for some
+    /// strings of the primary language, such as a dialect. If the buffer is
+    /// too small, `BUFFER_TOO_SMALL` is returned along with the required
+    /// size. `INVALID_LANGUAGE` is returned if the package list has no
+    /// strings in the primary language.
+    pub fn get_secondary_languages<'buf>(
+        &self,
+        package_list: HiiHandle,
+        primary: &CStr8,
+        buffer: &'buf mut [u8],
+    ) -> Result<LanguageList<'buf>, Option<usize>> {
+        let mut size = buffer.len();
+        let status = unsafe {
+            (self.get_secondary_languages)(
+                self,
+                package_list,
+                primary.as_ptr(),
+                buffer.as_mut_ptr(),
+                &mut size,
+            )
+        };
+        language_list(status, buffer, size)
+    }
+}
@@ -1,25 +1,25 @@
 //! `LoadedImage` protocol.
 
 use crate::{
-    data_types::{CStr16, Char16},
+    data_types::{ucs2, CStr16, Char16},
+    proto::device_path::DevicePath,
     proto::Protocol,
     table::boot::MemoryType,
-    unsafe_guid, Handle, Status,
+    Guid, Handle, Identify, Status,
 };
-use core::{ffi::c_void, str};
+use core::{ffi::c_void, slice, str};
 
 /// The LoadedImage protocol. This can be opened on any image handle using the `HandleProtocol` boot service.
 #[repr(C)]
-#[unsafe_guid("5b1b31a1-9562-11d2-8e3f-00a0c969723b")]
 #[derive(Protocol)]
 pub struct LoadedImage {
     revision: u32,
-    parent_handle: Handle,
+    parent_handle: Option<Handle>,
     system_table: *const c_void,
 
     // Source location of the image
-    device_handle: Handle,
-    _file_path: *const c_void, // TODO: not supported yet
+    device_handle: Option<Handle>,
+    file_path: *const DevicePath,
     _reserved: *const c_void,
 
     // Image load options
@@ -36,6 +36,10 @@ pub struct LoadedImage {
     unload: extern "efiapi" fn(image_handle: Handle) -> Status,
 }
 
+unsafe impl Identify for LoadedImage {
+    const GUID: Guid = guid!("5b1b31a1-9562-11d2-8e3f-00a0c969723b");
+}
+
 /// Errors that can be raised during parsing of the load options.
 #[derive(Debug)]
 pub enum LoadOptionsError {
@@ -46,11 +50,24 @@ pub enum LoadOptionsError {
 }
 
 impl LoadedImage {
+    /// Returns a handle to the image which loaded this image, if any.
+    pub fn parent(&self) -> Option<Handle> {
+        self.parent_handle
+    }
+
     /// Returns a handle to the storage device on which the image is located.
-    pub fn device(&self) -> Handle {
+    ///
+    /// Images which were loaded from a memory buffer have no device handle.
+    pub fn device(&self) -> Option<Handle> {
         self.device_handle
     }
 
+    /// Returns the path of the image file on its device, usually a file path
+    /// media device path, if the image was loaded from a file.
+    pub fn file_path(&self) -> Option<&DevicePath> {
+        unsafe { self.file_path.as_ref() }
+    }
+
     /// Get the load options of the given image. If the image was executed from the EFI shell, or from a boot
     /// option, this is the command line that was used to execute it as a string. If no options were given, this
     /// returns `Ok("")`.
@@ -59,12 +76,26 @@ impl LoadedImage {
             Ok("")
         } else {
             let ucs2_slice = unsafe { CStr16::from_ptr(self.load_options).to_u16_slice() };
-            let length =
-                ucs2::decode(ucs2_slice, buffer).map_err(|_| LoadOptionsError::BufferTooSmall)?;
+            let length = ucs2::decode(ucs2_slice, buffer)
+                .map_err(|_| LoadOptionsError::BufferTooSmall)?
+                .log();
             str::from_utf8(&buffer[0..length]).map_err(|_| LoadOptionsError::NotValidUtf8)
         }
     }
 
+    /// Returns the raw load options of the image, or `None` if it has none.
+    ///
+    /// Boot options may pass binary data, which is why `load_options` only
+    /// makes sense for images launched with a command line.
+    pub fn load_options_as_bytes(&self) -> Option<&[u8]> {
+        if self.load_options.is_null() {
+            None
+        } else {
+            let size = self.load_options_size as usize;
+            Some(unsafe { slice::from_raw_parts(self.load_options.cast(), size) })
+        }
+    }
+
     /// Set the load options for the image. This can be used prior to
     /// calling `BootServices.start_image` to control the command line
     /// passed to the image.
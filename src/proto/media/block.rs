@@ -1,11 +1,10 @@
 //! Block I/O protocols.
 
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Result, Status};
+use crate::{Guid, Identify, Result, Status};
 
 /// The Block I/O protocol.
 #[repr(C)]
-#[unsafe_guid("964e5b21-6459-11d2-8e39-00a0c969723b")]
 #[derive(Protocol)]
 pub struct BlockIO {
     revision: u64,
@@ -29,6 +28,10 @@ pub struct BlockIO {
     flush_blocks: extern "efiapi" fn(this: &BlockIO) -> Status,
 }
 
+unsafe impl Identify for BlockIO {
+    const GUID: Guid = guid!("964e5b21-6459-11d2-8e39-00a0c969723b");
+}
+
 impl BlockIO {
     /// Pointer for block IO media.
     pub fn media(&self) -> &BlockIOMedia {
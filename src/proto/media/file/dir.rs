@@ -1,7 +1,13 @@
 use super::{File, FileHandle, FileInfo, FromUefi, RegularFile};
+#[cfg(feature = "exts")]
+use super::{FileAttribute, FileMode, FileType};
 use crate::data_types::Align;
 use crate::prelude::*;
 use crate::Result;
+#[cfg(feature = "exts")]
+use crate::Status;
+#[cfg(feature = "exts")]
+use alloc_api::{boxed::Box, vec::Vec};
 use core::ffi::c_void;
 
 /// A `FileHandle` that is also a directory.
@@ -57,6 +63,75 @@ impl Directory {
         })
     }
 
+    /// Read the next directory entry into a box
+    ///
+    /// The size of the entry is first queried from the firmware, and the entry is then read into
+    /// a buffer of this size, with the alignment of `FileInfo`. If there are no more directory
+    /// entries, return an empty optional.
+    ///
+    /// # Errors
+    /// * `uefi::Status::NO_MEDIA`           The device has no media
+    /// * `uefi::Status::DEVICE_ERROR`       The device reported an error, the file was deleted,
+    ///   or the end of the file was reached before the `read()`.
+    /// * `uefi::Status::VOLUME_CORRUPTED`   The filesystem structures are corrupted
+    #[cfg(feature = "exts")]
+    pub fn read_boxed_entry(&mut self) -> Result<Option<Box<FileInfo>>> {
+        super::read_boxed(|buffer| self.read_entry(buffer))
+    }
+
+    /// Open a file by its path, relative to this directory
+    ///
+    /// The components of the path may be separated by either `\` or `/`. Each
+    /// intermediate component is opened read-only as a directory, and closed once the next one
+    /// is open, and the last component is opened with `open_mode` and `attributes`, like with
+    /// `File::open`. Missing intermediate directories are not created.
+    ///
+    /// The path is always relative to this directory, even if it starts with a separator. Empty
+    /// components, for example from double separators, and `.` components are ignored. `..`
+    /// components remove the previous component of the path, and can't go above this directory.
+    ///
+    /// # Errors
+    /// * `uefi::Status::INVALID_PARAMETER`  The path has no components, or a `..` component goes
+    ///   above this directory
+    /// * `uefi::Status::NOT_FOUND`          A component of the path does not exist, or an
+    ///   intermediate component is not a directory
+    /// * The errors of `File::open`
+    #[cfg(feature = "exts")]
+    pub fn open_path(
+        &mut self,
+        path: &str,
+        open_mode: FileMode,
+        attributes: FileAttribute,
+    ) -> Result<FileHandle> {
+        let mut components = Vec::new();
+        for component in path.split(&['\\', '/'][..]) {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop().ok_or(Status::INVALID_PARAMETER)?;
+                }
+                component => components.push(component),
+            }
+        }
+        let (last, intermediates) = components.split_last().ok_or(Status::INVALID_PARAMETER)?;
+
+        let mut parent: Option<Directory> = None;
+        for component in intermediates {
+            let directory = parent.as_mut().unwrap_or(&mut *self);
+            let file = directory
+                .open(component, FileMode::Read, FileAttribute::empty())?
+                .log();
+            match file.into_type()?.log() {
+                FileType::Dir(directory) => parent = Some(directory),
+                FileType::Regular(_) => return Err(Status::NOT_FOUND.into()),
+            }
+        }
+        parent
+            .as_mut()
+            .unwrap_or(self)
+            .open(last, open_mode, attributes)
+    }
+
     /// Start over the process of enumerating directory entries
     pub fn reset_entry_readout(&mut self) -> Result {
         self.0.set_position(0)
@@ -1,7 +1,7 @@
 use super::FileAttribute;
 use crate::data_types::{chars::NUL_16, Align};
 use crate::table::runtime::Time;
-use crate::{unsafe_guid, CStr16, Char16, Identify};
+use crate::{CStr16, Char16, Guid, Identify};
 use core::cmp;
 use core::convert::TryInto;
 use core::ffi::c_void;
@@ -151,9 +151,12 @@ pub enum FileInfoCreationError {
 ///   existing file in the same directory.
 /// - If a file is read-only, the only allowed change is to remove the read-only
 ///   attribute. Other changes must be carried out in a separate transaction.
-#[unsafe_guid("09576e92-6d3f-11d2-8e39-00a0c969723b")]
 pub type FileInfo = NamedFileProtocolInfo<FileInfoHeader>;
 
+unsafe impl Identify for FileInfo {
+    const GUID: Guid = guid!("09576e92-6d3f-11d2-8e39-00a0c969723b");
+}
+
 /// Header for generic file information
 #[derive(Debug)]
 #[repr(C)]
@@ -198,7 +201,7 @@ impl FileInfo {
             attribute,
         };
         let info = Self::new_impl(storage, header, file_name)?;
-        info.header.size = mem::size_of_val(&info) as u64;
+        info.header.size = mem::size_of_val(info) as u64;
         Ok(info)
     }
 
@@ -246,9 +249,12 @@ impl FileProtocolInfo for FileInfo {}
 ///
 /// Please note that only the system volume's volume label may be set using
 /// this information structure. Consider using `FileSystemVolumeLabel` instead.
-#[unsafe_guid("09576e93-6d3f-11d2-8e39-00a0c969723b")]
 pub type FileSystemInfo = NamedFileProtocolInfo<FileSystemInfoHeader>;
 
+unsafe impl Identify for FileSystemInfo {
+    const GUID: Guid = guid!("09576e93-6d3f-11d2-8e39-00a0c969723b");
+}
+
 /// Header for system volume information
 #[derive(Debug)]
 #[repr(C)]
@@ -287,7 +293,7 @@ impl FileSystemInfo {
             block_size,
         };
         let info = Self::new_impl(storage, header, volume_label)?;
-        info.header.size = mem::size_of_val(&info) as u64;
+        info.header.size = mem::size_of_val(info) as u64;
         Ok(info)
     }
 
@@ -322,9 +328,12 @@ impl FileProtocolInfo for FileSystemInfo {}
 /// System volume label
 ///
 /// May only be obtained on the root directory's file handle.
-#[unsafe_guid("db47d7d3-fe81-11d3-9a35-0090273fc14d")]
 pub type FileSystemVolumeLabel = NamedFileProtocolInfo<FileSystemVolumeLabelHeader>;
 
+unsafe impl Identify for FileSystemVolumeLabel {
+    const GUID: Guid = guid!("db47d7d3-fe81-11d3-9a35-0090273fc14d");
+}
+
 /// Header for system volume label information
 #[derive(Debug)]
 #[repr(C)]
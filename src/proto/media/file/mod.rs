@@ -9,11 +9,19 @@
 mod dir;
 mod info;
 mod regular;
+#[cfg(feature = "exts")]
+mod walk;
 
+use crate::data_types::ucs2;
 use crate::prelude::*;
+#[cfg(feature = "exts")]
+use crate::{data_types::Align, Completion};
 use crate::{CStr16, Char16, Guid, Result, Status};
 #[cfg(feature = "exts")]
-use alloc_api::{alloc::Layout, boxed::Box};
+use alloc_api::{
+    alloc::{dealloc, Layout},
+    boxed::Box,
+};
 use bitflags::bitflags;
 use core::ffi::c_void;
 use core::mem;
@@ -23,6 +31,8 @@ pub use self::info::{
     FileInfo, FileInfoHeader, FileProtocolInfo, FileSystemInfo, FileSystemInfoHeader,
     FileSystemVolumeLabel, FileSystemVolumeLabelHeader, FromUefi, NamedFileProtocolInfo,
 };
+#[cfg(feature = "exts")]
+pub use self::walk::{WalkDir, WalkEntry};
 pub use self::{dir::Directory, regular::RegularFile};
 
 /// Common interface to `FileHandle`, `RegularFile`, and `Directory`.
@@ -59,26 +69,43 @@ pub trait File: Sized {
         attributes: FileAttribute,
     ) -> Result<FileHandle> {
         const BUF_SIZE: usize = 255;
-        if filename.len() > BUF_SIZE {
-            Err(Status::INVALID_PARAMETER.into())
-        } else {
-            let mut buf = [0u16; BUF_SIZE + 1];
-            let mut ptr = ptr::null_mut();
-
-            let len = ucs2::encode(filename, &mut buf)?;
-            let filename = unsafe { CStr16::from_u16_with_nul_unchecked(&buf[..=len]) };
-
-            unsafe {
-                (self.imp().open)(
-                    self.imp(),
-                    &mut ptr,
-                    filename.as_ptr(),
-                    open_mode,
-                    attributes,
-                )
+        let mut buf = [0u16; BUF_SIZE + 1];
+
+        let len = match ucs2::encode_str(filename, &mut buf) {
+            Ok(len) => len.log(),
+            Err(err) if err.status() == Status::BUFFER_TOO_SMALL => {
+                return Err(Status::INVALID_PARAMETER.into());
             }
-            .into_with_val(|| unsafe { FileHandle::new(ptr) })
+            Err(err) => return Err(err.status().into()),
+        };
+        let filename = unsafe { CStr16::from_u16_with_nul_unchecked(&buf[..len]) };
+
+        self.open_cstr16(filename, open_mode, attributes)
+    }
+
+    /// Try to open a file relative to this file, using an UCS-2 file name.
+    ///
+    /// This works like `open`, but avoids converting the file name, and has
+    /// no limit on its length. See `open` for the semantics of the arguments
+    /// and the possible errors.
+    fn open_cstr16(
+        &mut self,
+        filename: &CStr16,
+        open_mode: FileMode,
+        attributes: FileAttribute,
+    ) -> Result<FileHandle> {
+        let mut ptr = ptr::null_mut();
+
+        unsafe {
+            (self.imp().open)(
+                self.imp(),
+                &mut ptr,
+                filename.as_ptr(),
+                open_mode,
+                attributes,
+            )
         }
+        .into_with_val(|| unsafe { FileHandle::new(ptr) })
     }
 
     /// Close this file handle. Same as dropping this structure.
@@ -156,7 +183,7 @@ pub trait File: Sized {
     /// * `uefi::Status::VOLUME_FULL`       Not enough space left on the volume to change the info
     fn set_info<Info: FileProtocolInfo + ?Sized>(&mut self, info: &Info) -> Result {
         let info_ptr = info as *const Info as *const c_void;
-        let info_size = mem::size_of_val(&info);
+        let info_size = mem::size_of_val(info);
         unsafe { (self.imp().set_info)(self.imp(), &Info::GUID, info_size, info_ptr).into() }
     }
 
@@ -175,43 +202,85 @@ pub trait File: Sized {
 
     #[cfg(feature = "exts")]
     /// Get the dynamically allocated info for a file
+    ///
+    /// The size of the info is first queried from the firmware, and the info
+    /// is then read into a buffer of this size, with the alignment of `Info`.
     fn get_boxed_info<Info: FileProtocolInfo + ?Sized>(&mut self) -> Result<Box<Info>> {
-        // Initially try get_info with an empty array, this should always fail
-        // as all Info types at least need room for a null-terminator.
-        let size = match self
-            .get_info::<Info>(&mut [])
-            .expect_error("zero sized get_info unexpectedly succeeded")
-            .split()
-        {
-            (s, None) => return Err(s.into()),
+        read_boxed(|buffer| self.get_info::<Info>(buffer).map_inner(Some))
+            .map_inner(|info| info.expect("get_info returned no info"))
+    }
+}
+
+/// Reads a dynamically sized structure into a box, using a function reading
+/// it into a buffer, which may return `None`, and which reports the required
+/// size of the buffer when it is too small.
+///
+/// `read` is first called with an empty buffer, which should always fail as
+/// all the structures need room for a null-terminated name, and then with a
+/// buffer of the required size.
+#[cfg(feature = "exts")]
+fn read_boxed<Info: Align + FromUefi + ?Sized>(
+    mut read: impl FnMut(&mut [u8]) -> Result<Option<&mut Info>, Option<usize>>,
+) -> Result<Option<Box<Info>>> {
+    let size = match read(&mut []) {
+        Ok(completion) => {
+            return Ok(completion.map(|info| {
+                assert!(info.is_none(), "zero sized read unexpectedly succeeded");
+                None
+            }))
+        }
+        Err(error) => match error.split() {
+            (status, None) => return Err(status.into()),
             (_, Some(size)) => size,
-        };
+        },
+    };
 
-        // We add trailing padding because the size of a rust structure must
-        // always be a multiple of alignment.
-        let layout = Layout::from_size_align(size, Info::alignment())
-            .unwrap()
-            .pad_to_align();
-        let mut buffer = crate::exts::allocate_buffer(layout);
-        let buffer_start = buffer.as_ptr();
-
-        let info = self
-            .get_info(&mut buffer)
-            .discard_errdata()?
-            .map(|info_ref| {
-                // This operation is safe because info uses the exact memory
-                // of the provied buffer (so no memory is leaked), and the box
-                // is created if and only if buffer is leaked (so no memory can
-                // ever be freed twice).
-
-                assert_eq!(mem::size_of_val(info_ref), layout.size());
-                assert_eq!(info_ref as *const Info as *const u8, buffer_start);
-                unsafe { Box::from_raw(info_ref as *mut _) }
-            });
-        mem::forget(buffer);
-
-        Ok(info)
-    }
+    // We add trailing padding because the size of a rust structure must
+    // always be a multiple of alignment.
+    let layout = Layout::from_size_align(size, Info::alignment())
+        .unwrap()
+        .pad_to_align();
+    // The buffer is freed manually, as a `Box<[u8]>` would be freed with the
+    // alignment of `u8` instead of the alignment of the layout.
+    let buffer = Box::leak(crate::exts::allocate_buffer(layout));
+    let buffer_start = buffer.as_mut_ptr();
+    let free_buffer = || unsafe { dealloc(buffer_start, layout) };
+
+    let (status, info) = match read(buffer).discard_errdata() {
+        Ok(completion) => completion.split(),
+        Err(error) => {
+            free_buffer();
+            return Err(error);
+        }
+    };
+    let info = match info {
+        Some(info) => info,
+        None => {
+            free_buffer();
+            return Ok(Completion::new(status, None));
+        }
+    };
+    assert_eq!(info as *mut Info as *mut u8, buffer_start);
+
+    // A box must be freed with the layout of its contents. This is usually
+    // the layout of the buffer, but the firmware may have asked for more
+    // room than needed, in which case the info is moved to a buffer of the
+    // right size.
+    let info_layout = Layout::for_value(info);
+    assert!(info_layout.size() <= layout.size());
+    let info = if info_layout == layout {
+        // This operation is safe because info uses the exact memory of the
+        // buffer, which is not freed.
+        unsafe { Box::from_raw(info) }
+    } else {
+        let copy = Box::leak(crate::exts::allocate_buffer(info_layout));
+        unsafe {
+            ptr::copy_nonoverlapping(buffer_start, copy.as_mut_ptr(), info_layout.size());
+            free_buffer();
+            Box::from_raw(Info::from_uefi(copy.as_mut_ptr().cast()))
+        }
+    };
+    Ok(Completion::new(status, Some(info)))
 }
 
 // Internal File helper methods to access the funciton pointer table.
@@ -234,7 +303,7 @@ impl<T: File> FileInternal for T {}
 pub struct FileHandle(*mut FileImpl);
 
 impl FileHandle {
-    pub(super) unsafe fn new(ptr: *mut FileImpl) -> Self {
+    pub(crate) unsafe fn new(ptr: *mut FileImpl) -> Self {
         Self(ptr)
     }
 
@@ -270,7 +339,7 @@ impl Drop for FileHandle {
 
 /// The function pointer table for the File protocol.
 #[repr(C)]
-pub(super) struct FileImpl {
+pub(crate) struct FileImpl {
     revision: u64,
     open: unsafe extern "efiapi" fn(
         this: &mut FileImpl,
@@ -1,5 +1,13 @@
+#[cfg(feature = "exts")]
+use super::FileInfo;
 use super::{File, FileHandle, FileInternal};
+#[cfg(feature = "exts")]
+use crate::ResultExt;
 use crate::{Result, Status};
+#[cfg(feature = "exts")]
+use alloc_api::vec::Vec;
+#[cfg(feature = "exts")]
+use core::cmp;
 
 /// A `FileHandle` that is also a regular (data) file.
 ///
@@ -50,6 +58,43 @@ impl RegularFile {
         )
     }
 
+    /// Read the data from the current position to the end of the file
+    ///
+    /// The data is read in chunks of at most 1 MiB, into a vector sized from the size of the
+    /// file. Reading continues until the firmware reports the end of the file, even if the file
+    /// is larger than its reported size.
+    ///
+    /// # Errors
+    /// * The errors of `read`, `get_position` and `File::get_boxed_info`
+    #[cfg(feature = "exts")]
+    pub fn read_to_vec(&mut self) -> Result<Vec<u8>> {
+        const CHUNK_SIZE: usize = 1 << 20;
+        // Size of the reads once the expected data has been read, to detect
+        // the end of the file.
+        const PROBE_SIZE: usize = 4096;
+
+        let size = self.get_boxed_info::<FileInfo>()?.log().file_size();
+        let position = self.get_position()?.log();
+        let expected = size.saturating_sub(position) as usize;
+
+        let mut data = Vec::with_capacity(expected);
+        loop {
+            let len = data.len();
+            let chunk = if len < expected {
+                cmp::min(expected - len, CHUNK_SIZE)
+            } else {
+                PROBE_SIZE
+            };
+            data.resize(len + chunk, 0);
+            let read = self.read(&mut data[len..]).discard_errdata()?.log();
+            data.truncate(len + read);
+            if read == 0 {
+                // The end of the file was reached.
+                return Ok(data.into());
+            }
+        }
+    }
+
     /// Write data to file
     ///
     /// Write `buffer` to file, increment the file pointer.
@@ -0,0 +1,190 @@
+use super::{Directory, File, FileAttribute, FileInfo, FileMode, FileType};
+use crate::{Error, Result};
+use alloc_api::{boxed::Box, string::String, vec::Vec};
+use core::fmt::Write;
+
+/// Attribute of FAT volume labels, which some firmware report as entries of
+/// the root directory.
+const VOLUME_LABEL: u64 = 0x08;
+
+/// Depth-first iterator over the entries of a directory tree
+///
+/// The entries of each directory are yielded in the order the firmware
+/// returns them, each subdirectory being followed by its own entries. The
+/// `.` and `..` entries are skipped, as well as volume labels. Subdirectories
+/// are read even if they have the `READ_ONLY` attribute.
+///
+/// If a subdirectory can't be opened or read, an error whose data is the path
+/// of the subdirectory is yielded, and the walk continues with the next
+/// entries of its parent.
+pub struct WalkDir<'a> {
+    root: &'a mut Directory,
+    /// The subdirectories being read, with their paths, from the outermost.
+    stack: Vec<(Directory, String)>,
+    max_depth: usize,
+    /// An error to yield before reading the next entry.
+    error: Option<Error<String>>,
+    started: bool,
+    finished: bool,
+}
+
+impl<'a> WalkDir<'a> {
+    /// Walks the tree of the directory `root`, from its first entry.
+    pub fn new(root: &'a mut Directory) -> Self {
+        WalkDir {
+            root,
+            stack: Vec::new(),
+            max_depth: 32,
+            error: None,
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// Sets the maximum depth of the walk, which is 32 by default
+    ///
+    /// The entries of the root directory have a depth of 1, and the
+    /// subdirectories at the maximum depth are yielded but not read. Each
+    /// level of the walk keeps a directory open.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns the directory being read.
+    fn current(&mut self) -> &mut Directory {
+        match self.stack.last_mut() {
+            Some((directory, _)) => directory,
+            None => &mut *self.root,
+        }
+    }
+
+    /// Stops reading the current directory after an error, returning its path.
+    fn abandon_current(&mut self, error: Error) -> Error<String> {
+        let path = match self.stack.pop() {
+            Some((_, path)) => path,
+            None => {
+                self.finished = true;
+                String::new()
+            }
+        };
+        Error::new(error.status(), path)
+    }
+
+    /// Opens the subdirectory described by `entry`, in the current directory.
+    fn descend(&mut self, entry: &WalkEntry) {
+        let opened = self
+            .current()
+            .open_cstr16(
+                entry.info.file_name(),
+                FileMode::Read,
+                FileAttribute::empty(),
+            )
+            .and_then(|file| file.log().into_type());
+        match opened {
+            Ok(completion) => {
+                if let FileType::Dir(directory) = completion.log() {
+                    self.stack.push((directory, entry.path.clone()));
+                }
+            }
+            Err(error) => self.error = Some(Error::new(error.status(), entry.path.clone())),
+        }
+    }
+}
+
+impl<'a> Iterator for WalkDir<'a> {
+    type Item = Result<WalkEntry, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+        if self.finished {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            if let Err(error) = self.root.reset_entry_readout() {
+                return Some(Err(self.abandon_current(error)));
+            }
+        }
+
+        loop {
+            let info = match self.current().read_boxed_entry() {
+                Ok(completion) => completion.log(),
+                Err(error) => return Some(Err(self.abandon_current(error))),
+            };
+            let info = match info {
+                Some(info) => info,
+                None => {
+                    // The end of the directory was reached.
+                    if self.stack.pop().is_none() {
+                        self.finished = true;
+                        return None;
+                    }
+                    continue;
+                }
+            };
+
+            let name = info.file_name();
+            if name == "." || name == ".." || info.attribute().bits() & VOLUME_LABEL != 0 {
+                continue;
+            }
+
+            let mut path = String::new();
+            if let Some((_, parent)) = self.stack.last() {
+                path.push_str(parent);
+                path.push('\\');
+            }
+            // Writing to a `String` can't fail.
+            let _ = write!(path, "{}", name);
+
+            let entry = WalkEntry {
+                path,
+                depth: self.stack.len() + 1,
+                info,
+            };
+            if entry.is_dir() && entry.depth < self.max_depth {
+                self.descend(&entry);
+            }
+            return Some(Ok(entry.into()));
+        }
+    }
+}
+
+/// An entry of a directory tree, yielded by `WalkDir`
+#[derive(Debug)]
+pub struct WalkEntry {
+    path: String,
+    depth: usize,
+    info: Box<FileInfo>,
+}
+
+impl WalkEntry {
+    /// Returns the path of the entry, relative to the root of the walk, with
+    /// components separated by `\`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the depth of the entry, which is 1 for the entries of the
+    /// root of the walk.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the information about the entry.
+    pub fn info(&self) -> &FileInfo {
+        &self.info
+    }
+
+    /// Returns whether the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.info.attribute().contains(FileAttribute::DIRECTORY)
+    }
+
+    /// Returns the information about the entry, as a box.
+    pub fn into_info(self) -> Box<FileInfo> {
+        self.info
+    }
+}
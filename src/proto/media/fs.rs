@@ -2,7 +2,7 @@
 
 use super::file::{Directory, FileHandle, FileImpl};
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Result, Status};
+use crate::{Guid, Identify, Result, Status};
 use core::ptr;
 
 /// Allows access to a FAT-12/16/32 file system.
@@ -10,7 +10,6 @@ use core::ptr;
 /// This interface is implemented by some storage devices
 /// to allow file access to the contained file systems.
 #[repr(C)]
-#[unsafe_guid("964e5b22-6459-11d2-8e39-00a0c969723b")]
 #[derive(Protocol)]
 pub struct SimpleFileSystem {
     revision: u64,
@@ -18,6 +17,10 @@ pub struct SimpleFileSystem {
         extern "efiapi" fn(this: &mut SimpleFileSystem, root: &mut *mut FileImpl) -> Status,
 }
 
+unsafe impl Identify for SimpleFileSystem {
+    const GUID: Guid = guid!("964e5b22-6459-11d2-8e39-00a0c969723b");
+}
+
 impl SimpleFileSystem {
     /// Open the root directory on a volume.
     ///
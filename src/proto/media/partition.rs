@@ -1,7 +1,7 @@
 //! Partition information protocol.
 
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Char16, Guid};
+use crate::{Char16, Guid, Identify};
 
 newtype_enum! {
     /// MBR OS type.
@@ -54,31 +54,13 @@ newtype_enum! {
     /// Partition Type GUIDs.
     pub enum GptPartitionType: Guid => {
         /// Indicates a partition entry is unused.
-        UNUSED_ENTRY = Guid::from_values(
-            0x00000000,
-            0x0000,
-            0x0000,
-            0x0000,
-            [0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-        ),
+        UNUSED_ENTRY = guid!("00000000-0000-0000-0000-000000000000"),
 
         /// EFI System Partition.
-        EFI_SYSTEM_PARTITION = Guid::from_values(
-            0xc12a7328,
-            0xf81f,
-            0x11d2,
-            0xba4b,
-            [0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b],
-        ),
+        EFI_SYSTEM_PARTITION = guid!("c12a7328-f81f-11d2-ba4b-00a0c93ec93b"),
 
         /// Partition containing a legacy MBR.
-        LEGACY_MBR = Guid::from_values(
-            0x024dee41,
-            0x33e7,
-            0x11d3,
-            0x9d69,
-            [0x00, 0x08, 0xc7, 0x81, 0xf3, 0x9f],
-        ),
+        LEGACY_MBR = guid!("024dee41-33e7-11d3-9d69-0008c781f39f"),
     }
 }
 
@@ -149,7 +131,6 @@ newtype_enum! {
 /// Protocol for accessing partition information.
 #[repr(C)]
 #[repr(packed)]
-#[unsafe_guid("8cf2f62c-bc9b-4821-808d-ec9ec421a1a0")]
 #[derive(Clone, Copy, Protocol)]
 pub struct PartitionInfo {
     /// Revision of the partition info protocol.
@@ -163,6 +144,10 @@ pub struct PartitionInfo {
     record: PartitionInfoRecord,
 }
 
+unsafe impl Identify for PartitionInfo {
+    const GUID: Guid = guid!("8cf2f62c-bc9b-4821-808d-ec9ec421a1a0");
+}
+
 impl PartitionInfo {
     /// True if the partition is an EFI system partition.
     pub fn is_system(&self) -> bool {
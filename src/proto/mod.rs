@@ -18,6 +18,11 @@ use crate::Identify;
 /// protocol's GUID using the following syntax:
 ///
 /// ```
+/// #![feature(negative_impls)]
+///
+/// use uefi::proto::Protocol;
+/// use uefi::unsafe_guid;
+///
 /// #[unsafe_guid("12345678-9abc-def0-1234-56789abcdef0")]
 /// #[derive(Protocol)]
 /// struct DummyProtocol {}
@@ -26,10 +31,29 @@ pub trait Protocol: Identify {}
 
 pub use uefi_macros::Protocol;
 
+pub mod acpi;
+pub mod adapter_info;
 pub mod console;
 pub mod debug;
+pub mod decompress;
 pub mod device_path;
+pub mod driver;
+pub mod firmware_management;
+pub mod hash2;
+pub mod hii;
 pub mod loaded_image;
 pub mod media;
+pub mod network;
+pub mod pci;
 pub mod pi;
+pub mod pkcs7;
+pub mod rng;
+pub mod security;
+pub mod service_binding;
+pub mod shell;
 pub mod shim;
+pub mod tcg;
+pub mod timestamp;
+pub mod unicode_collation;
+pub mod usb;
+pub mod variable_policy;
@@ -0,0 +1,419 @@
+//! Address Resolution Protocol.
+//!
+//! This protocol resolves protocol addresses, such as IPv4 addresses, into
+//! hardware addresses, and manages the ARP cache of a network interface.
+//! Instances are obtained by creating a child handle through the
+//! `ArpServiceBinding` protocol.
+//!
+//! Addresses are passed as byte slices, whose length must match the address
+//! lengths of the instance: the length of the station address for protocol
+//! addresses, and the length of the hardware address of the interface for
+//! hardware addresses.
+
+use super::with_event;
+use crate::data_types::{Ipv4Address, MacAddress};
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{Completion, Event, Guid, Identify, Result, Status};
+use core::convert::TryFrom;
+use core::ffi::c_void;
+use core::{fmt, ptr, slice};
+
+/// Service binding protocol used to create `Arp` instances.
+pub type ArpServiceBinding = ServiceBinding<Arp>;
+
+unsafe impl ChildProtocol for Arp {
+    const SERVICE_BINDING_GUID: Guid = guid!("f44c00ee-1f2c-4a00-aa09-1c9f3e0800a3");
+}
+
+/// EtherType of IPv4, the protocol type used to resolve IPv4 addresses.
+pub const ETHER_TYPE_IPV4: u16 = 0x0800;
+
+/// Configuration of an `Arp` instance.
+///
+/// The timeouts are in units of 100 nanoseconds. Zero selects the default
+/// value of the protocol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConfigData<'a> {
+    /// Type of the protocol addresses, as an EtherType.
+    pub protocol_type: u16,
+    /// Protocol address of this instance, which is used as the sender of the
+    /// requests. Its length is the length of all the protocol addresses.
+    pub station_address: &'a [u8],
+    /// Lifetime of the dynamic entries of the cache.
+    pub entry_timeout: u32,
+    /// Number of retries of a request, before it fails.
+    pub retry_count: u32,
+    /// Time between the retries of a request.
+    pub retry_timeout: u32,
+}
+
+impl<'a> ConfigData<'a> {
+    /// Creates a configuration resolving IPv4 addresses, with the default
+    /// timeouts.
+    pub fn ipv4(station_address: &'a Ipv4Address) -> Self {
+        Self {
+            protocol_type: ETHER_TYPE_IPV4,
+            station_address: &station_address.0,
+            entry_timeout: 0,
+            retry_count: 0,
+            retry_timeout: 0,
+        }
+    }
+}
+
+/// The `EFI_ARP_CONFIG_DATA` structure.
+#[repr(C)]
+struct RawConfigData {
+    sw_address_type: u16,
+    sw_address_length: u8,
+    station_address: *const c_void,
+    entry_timeout: u32,
+    retry_count: u32,
+    retry_timeout: u32,
+}
+
+/// An address by which cache entries are selected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Address<'a> {
+    /// A protocol address, such as an IPv4 address.
+    Protocol(&'a [u8]),
+    /// A hardware address, such as a MAC address.
+    Hardware(&'a [u8]),
+}
+
+impl Address<'_> {
+    /// Returns whether this is a protocol address, and a pointer to it.
+    fn as_raw(&self) -> (bool, *const c_void) {
+        match self {
+            Address::Protocol(address) => (true, address.as_ptr().cast()),
+            Address::Hardware(address) => (false, address.as_ptr().cast()),
+        }
+    }
+}
+
+/// Size of the fixed part of an `EFI_ARP_FIND_DATA` entry.
+const FIND_DATA_SIZE: usize = 12;
+
+/// An entry of the ARP cache (`EFI_ARP_FIND_DATA`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CacheEntry<'a> {
+    /// Whether the entry denies the resolution of its address, instead of
+    /// resolving it.
+    pub deny: bool,
+    /// Whether the entry is static, and never expires.
+    pub is_static: bool,
+    /// Type of the hardware address, as defined by the ARP hardware types
+    /// of RFC 1700, such as 1 for Ethernet.
+    pub hw_address_type: u16,
+    /// Type of the protocol address, as an EtherType.
+    pub protocol_type: u16,
+    /// The protocol address, which is empty for entries denying a hardware
+    /// address.
+    pub protocol_address: &'a [u8],
+    /// The hardware address, which is empty for entries denying a protocol
+    /// address.
+    pub hw_address: &'a [u8],
+}
+
+impl<'a> CacheEntry<'a> {
+    /// Parses an entry, whose fixed part is followed by the protocol and
+    /// hardware addresses.
+    ///
+    /// The entries are packed, so their fields are read byte by byte.
+    fn parse(entry: &'a [u8]) -> Option<Self> {
+        let header = entry.get(..FIND_DATA_SIZE)?;
+        let u16_at = |offset: usize| u16::from_ne_bytes([header[offset], header[offset + 1]]);
+        let hw_len = header[10] as usize;
+        let sw_len = header[11] as usize;
+        let addresses = &entry[FIND_DATA_SIZE..];
+        let protocol_address = addresses.get(..sw_len)?;
+        let hw_address = addresses.get(sw_len..sw_len + hw_len)?;
+        Some(Self {
+            deny: header[4] != 0,
+            is_static: header[5] != 0,
+            hw_address_type: u16_at(6),
+            protocol_type: u16_at(8),
+            protocol_address,
+            hw_address,
+        })
+    }
+}
+
+/// Entries of the ARP cache returned by `Arp::find`, which are freed when
+/// dropped.
+pub struct CacheEntries<'a> {
+    buffer: *mut u8,
+    entry_length: usize,
+    count: usize,
+    bt: &'a BootServices,
+}
+
+impl CacheEntries<'_> {
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Iterates over the entries.
+    ///
+    /// Entries which are too short for the addresses they contain are
+    /// skipped.
+    pub fn iter(&self) -> impl Iterator<Item = CacheEntry<'_>> {
+        let bytes: &[u8] = if self.buffer.is_null() || self.entry_length == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.buffer, self.entry_length * self.count) }
+        };
+        bytes
+            .chunks_exact(self.entry_length.max(1))
+            .filter_map(CacheEntry::parse)
+    }
+}
+
+impl fmt::Debug for CacheEntries<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Drop for CacheEntries<'_> {
+    fn drop(&mut self) {
+        if !self.buffer.is_null() {
+            // Ignore the result, we can't do anything about an error here.
+            let _ = self.bt.free_pool(self.buffer);
+        }
+    }
+}
+
+/// The ARP protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Arp {
+    configure: unsafe extern "efiapi" fn(this: &Arp, config: *const RawConfigData) -> Status,
+    add: unsafe extern "efiapi" fn(
+        this: &Arp,
+        deny: bool,
+        target_sw_address: *const c_void,
+        target_hw_address: *const c_void,
+        timeout: u32,
+        overwrite: bool,
+    ) -> Status,
+    find: unsafe extern "efiapi" fn(
+        this: &Arp,
+        by_sw_address: bool,
+        address: *const c_void,
+        entry_length: *mut u32,
+        entry_count: *mut u32,
+        entries: *mut *mut u8,
+        refresh: bool,
+    ) -> Status,
+    delete: unsafe extern "efiapi" fn(
+        this: &Arp,
+        by_sw_address: bool,
+        address: *const c_void,
+    ) -> Status,
+    flush: extern "efiapi" fn(this: &Arp) -> Status,
+    request: unsafe extern "efiapi" fn(
+        this: &Arp,
+        target_sw_address: *const c_void,
+        resolved_event: Option<Event>,
+        target_hw_address: *mut c_void,
+    ) -> Status,
+    cancel: unsafe extern "efiapi" fn(
+        this: &Arp,
+        target_sw_address: *const c_void,
+        resolved_event: Option<Event>,
+    ) -> Status,
+}
+
+unsafe impl Identify for Arp {
+    const GUID: Guid = guid!("f4b427bb-ba21-4f16-bc4e-43e416ab619c");
+}
+
+impl Arp {
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// Resetting the instance cancels its pending requests, and removes the
+    /// cache entries it added. A configured instance must be reset before
+    /// it can be configured with another station address.
+    ///
+    /// `INVALID_PARAMETER` is returned if the station address is longer
+    /// than 255 bytes.
+    pub fn configure(&mut self, config: Option<&ConfigData>) -> Result {
+        let raw = match config {
+            Some(config) => Some(RawConfigData {
+                sw_address_type: config.protocol_type,
+                sw_address_length: u8::try_from(config.station_address.len())
+                    .map_err(|_| Status::INVALID_PARAMETER)?,
+                station_address: config.station_address.as_ptr().cast(),
+                entry_timeout: config.entry_timeout,
+                retry_count: config.retry_count,
+                retry_timeout: config.retry_timeout,
+            }),
+            None => None,
+        };
+        let raw = raw.as_ref().map_or(ptr::null(), |raw| raw as *const _);
+        unsafe { (self.configure)(self, raw) }.into()
+    }
+
+    /// Adds an entry resolving `protocol_address` into `hw_address` to the
+    /// cache.
+    ///
+    /// The entry expires after `timeout` units of 100 nanoseconds, or never
+    /// if it is zero. If an entry already exists for one of the addresses,
+    /// `ACCESS_DENIED` is returned unless `overwrite` is set.
+    pub fn add(
+        &mut self,
+        protocol_address: &[u8],
+        hw_address: &[u8],
+        timeout: u32,
+        overwrite: bool,
+    ) -> Result {
+        unsafe {
+            (self.add)(
+                self,
+                false,
+                protocol_address.as_ptr().cast(),
+                hw_address.as_ptr().cast(),
+                timeout,
+                overwrite,
+            )
+        }
+        .into()
+    }
+
+    /// Adds an entry denying the resolution of an address to the cache.
+    ///
+    /// Denying a protocol address prevents it from being resolved, while
+    /// denying a hardware address prevents any protocol address from being
+    /// resolved into it.
+    pub fn deny(&mut self, address: Address, timeout: u32, overwrite: bool) -> Result {
+        let (sw_address, hw_address) = match address {
+            Address::Protocol(address) => (address.as_ptr().cast(), ptr::null()),
+            Address::Hardware(address) => (ptr::null(), address.as_ptr().cast()),
+        };
+        unsafe { (self.add)(self, true, sw_address, hw_address, timeout, overwrite) }.into()
+    }
+
+    /// Returns the cache entries matching `address`, or all of them if it is
+    /// `None`.
+    ///
+    /// If `refresh` is set, the timeouts of the matching entries are reset.
+    /// `NOT_FOUND` is returned if no entry matches.
+    pub fn find<'bt>(
+        &self,
+        bt: &'bt BootServices,
+        address: Option<Address>,
+        refresh: bool,
+    ) -> Result<CacheEntries<'bt>> {
+        let (by_sw_address, address) =
+            address.map_or((false, ptr::null()), |address| address.as_raw());
+        let mut entry_length = 0;
+        let mut entry_count = 0;
+        let mut entries = ptr::null_mut();
+        unsafe {
+            (self.find)(
+                self,
+                by_sw_address,
+                address,
+                &mut entry_length,
+                &mut entry_count,
+                &mut entries,
+                refresh,
+            )
+        }
+        .into_with_val(|| CacheEntries {
+            buffer: entries,
+            entry_length: entry_length as usize,
+            count: entry_count as usize,
+            bt,
+        })
+    }
+
+    /// Deletes the cache entries matching `address`, or all of them if it is
+    /// `None`.
+    ///
+    /// `NOT_FOUND` is returned if no entry matches.
+    pub fn delete(&mut self, address: Option<Address>) -> Result {
+        let (by_sw_address, address) =
+            address.map_or((false, ptr::null()), |address| address.as_raw());
+        unsafe { (self.delete)(self, by_sw_address, address) }.into()
+    }
+
+    /// Deletes all the dynamic entries of the cache.
+    pub fn flush(&mut self) -> Result {
+        (self.flush)(self).into()
+    }
+
+    /// Starts resolving `protocol_address`, and stores the resulting hardware
+    /// address in `hw_address`.
+    ///
+    /// Returns `true` if the address was resolved from the cache. Otherwise,
+    /// a request is sent, and `event` is signaled once it completes. If the
+    /// request times out, `event` is signaled as well, but `hw_address` is
+    /// left zeroed.
+    ///
+    /// # Safety
+    ///
+    /// `hw_address` must not be moved or freed until the request has
+    /// completed, or has been cancelled.
+    pub unsafe fn request(
+        &mut self,
+        protocol_address: &[u8],
+        event: Option<&Event>,
+        hw_address: &mut MacAddress,
+    ) -> Result<bool> {
+        let event = event.map(|event| event.unsafe_clone());
+        let status = (self.request)(
+            self,
+            protocol_address.as_ptr().cast(),
+            event,
+            (hw_address as *mut MacAddress).cast(),
+        );
+        match status {
+            Status::NOT_READY => Ok(false.into()),
+            status => status.into_with_val(|| true),
+        }
+    }
+
+    /// Cancels the pending requests for `protocol_address` which signal
+    /// `event`. If either is `None`, the requests for any address or
+    /// signaling any event are cancelled.
+    pub fn cancel(&mut self, protocol_address: Option<&[u8]>, event: Option<&Event>) -> Result {
+        let address = protocol_address.map_or(ptr::null(), |address| address.as_ptr().cast());
+        let event = event.map(|event| unsafe { event.unsafe_clone() });
+        unsafe { (self.cancel)(self, address, event) }.into()
+    }
+
+    /// Resolves `protocol_address` into a hardware address, sending a
+    /// request and waiting for the reply if it is not in the cache.
+    ///
+    /// `TIMEOUT` is returned if no reply is received.
+    pub fn resolve(&mut self, bt: &BootServices, protocol_address: &[u8]) -> Result<MacAddress> {
+        let mut hw_address = MacAddress([0; 32]);
+        with_event(bt, |event| {
+            let (status, resolved) =
+                unsafe { self.request(protocol_address, Some(event), &mut hw_address) }?.split();
+            if !resolved {
+                if let Err(err) = bt.wait_for_event(slice::from_ref(event)) {
+                    self.cancel(Some(protocol_address), Some(event))?.log();
+                    return Err(err.status().into());
+                }
+            }
+            Ok(Completion::new(status, ()))
+        })?
+        .log();
+        // A request which timed out leaves the address zeroed.
+        if hw_address == MacAddress([0; 32]) {
+            return Err(Status::TIMEOUT.into());
+        }
+        Ok(Completion::from(hw_address))
+    }
+}
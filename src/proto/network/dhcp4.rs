@@ -0,0 +1,986 @@
+//! DHCP over IPv4 protocol.
+//!
+//! This protocol acquires and manages a DHCP lease for a network interface.
+//! Instances of the protocol are obtained by creating a child handle through
+//! the `Dhcp4ServiceBinding` protocol, which is installed on the handle of the
+//! network interface.
+//!
+//! Most applications should rather use the DHCP policy of the `Ip4Config2`
+//! protocol, which configures the interface with the obtained lease. This
+//! protocol gives full control over the exchange, such as which options are
+//! requested and which vendor class is announced.
+
+use crate::data_types::{Ipv4Address, MacAddress};
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{Error, Event, Guid, Identify, Result, Status};
+use core::convert::TryInto;
+use core::ffi::c_void;
+use core::ops::Deref;
+use core::{fmt, mem, ptr, slice};
+
+/// Service binding protocol used to create `Dhcp4` instances.
+pub type Dhcp4ServiceBinding = ServiceBinding<Dhcp4>;
+
+unsafe impl ChildProtocol for Dhcp4 {
+    const SERVICE_BINDING_GUID: Guid = guid!("9d9a39d8-bd42-4a73-a4d5-8ee94be11380");
+}
+
+newtype_enum! {
+/// State of the DHCP client (`EFI_DHCP4_STATE`).
+pub enum State: u32 => {
+    /// The client is stopped.
+    STOPPED      = 0,
+    /// The client is configured, but has no lease.
+    INIT         = 1,
+    /// The client is collecting offers from DHCP servers.
+    SELECTING    = 2,
+    /// The client requested a lease from the selected server.
+    REQUESTING   = 3,
+    /// The client has a lease.
+    BOUND        = 4,
+    /// The client is renewing its lease with the server which granted it.
+    RENEWING     = 5,
+    /// The client is renewing its lease with any server.
+    REBINDING    = 6,
+    /// The client is configured with a previously allocated address.
+    INIT_REBOOT  = 7,
+    /// The client is confirming a previously allocated address.
+    REBOOTING    = 8,
+}}
+
+newtype_enum! {
+/// Code of a DHCP option, as defined in RFC 2132.
+pub enum OptionCode: u8 => {
+    /// Padding, which has no length nor data.
+    PAD                    = 0,
+    /// Subnet mask of the client.
+    SUBNET_MASK            = 1,
+    /// Routers on the subnet of the client.
+    ROUTER                 = 3,
+    /// DNS servers.
+    DNS_SERVER             = 6,
+    /// Name of the client.
+    HOST_NAME              = 12,
+    /// Domain name of the client.
+    DOMAIN_NAME            = 15,
+    /// Address requested by the client.
+    REQUESTED_ADDRESS      = 50,
+    /// Duration of the lease, in seconds.
+    LEASE_TIME             = 51,
+    /// Whether the file and server name fields hold options.
+    OVERLOAD               = 52,
+    /// Type of the DHCP message, as a `MessageType`.
+    MESSAGE_TYPE           = 53,
+    /// Address of the server.
+    SERVER_ID              = 54,
+    /// Options requested by the client.
+    PARAMETER_REQUEST_LIST = 55,
+    /// Maximum size of the messages accepted by the client.
+    MAX_MESSAGE_SIZE       = 57,
+    /// Vendor class of the client.
+    VENDOR_CLASS_ID        = 60,
+    /// Unique identifier of the client.
+    CLIENT_ID              = 61,
+    /// End of the options.
+    END                    = 255,
+}}
+
+newtype_enum! {
+/// Type of a DHCP message, as defined in RFC 2132.
+pub enum MessageType: u8 => {
+    /// Client broadcast to locate servers.
+    DISCOVER = 1,
+    /// Server offer of a lease.
+    OFFER    = 2,
+    /// Client request for the offered lease.
+    REQUEST  = 3,
+    /// Client refusal of the offered address.
+    DECLINE  = 4,
+    /// Server acknowledgement of the lease.
+    ACK      = 5,
+    /// Server refusal of the request.
+    NAK      = 6,
+    /// Client release of its lease.
+    RELEASE  = 7,
+    /// Client request for configuration parameters only.
+    INFORM   = 8,
+}}
+
+/// A DHCP option (`EFI_DHCP4_PACKET_OPTION`).
+///
+/// An option is made of its code, its length and its data, and can be
+/// written to a buffer with `build` or one of the typed builders.
+///
+/// ```
+/// use uefi::data_types::Ipv4Address;
+/// use uefi::proto::network::dhcp4::{DhcpOption, OptionCode};
+///
+/// let mut buffer = [0; 16];
+/// let option = DhcpOption::parameter_request_list(
+///     &mut buffer,
+///     &[OptionCode::SUBNET_MASK, OptionCode::ROUTER],
+/// ).unwrap().unwrap();
+/// assert_eq!(option.as_bytes(), &[55, 2, 1, 3]);
+///
+/// let option = DhcpOption::ipv4(
+///     &mut buffer,
+///     OptionCode::REQUESTED_ADDRESS,
+///     Ipv4Address([10, 0, 2, 15]),
+/// ).unwrap().unwrap();
+/// assert_eq!(option.code(), OptionCode::REQUESTED_ADDRESS);
+/// assert_eq!(option.as_ipv4(), Some(Ipv4Address([10, 0, 2, 15])));
+///
+/// let error = DhcpOption::build(&mut buffer[..4], OptionCode::HOST_NAME, b"uefi")
+///     .unwrap_err();
+/// assert_eq!(error.data(), &Some(6));
+/// ```
+#[repr(transparent)]
+pub struct DhcpOption([u8]);
+
+impl DhcpOption {
+    /// Writes an option to `buffer`, which is returned on success.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size. If the data is larger than 255 bytes,
+    /// `INVALID_PARAMETER` is returned.
+    pub fn build<'buf>(
+        buffer: &'buf mut [u8],
+        code: OptionCode,
+        data: &[u8],
+    ) -> Result<&'buf DhcpOption, Option<usize>> {
+        let len: u8 = match data.len().try_into() {
+            Ok(len) => len,
+            Err(_) => return Err(Error::new(Status::INVALID_PARAMETER, None)),
+        };
+        let size = 2 + data.len();
+        if buffer.len() < size {
+            return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(size)));
+        }
+
+        buffer[0] = code.0;
+        buffer[1] = len;
+        buffer[2..size].copy_from_slice(data);
+        Ok(unsafe { Self::from_bytes_unchecked(&buffer[..size]) }.into())
+    }
+
+    /// Writes a parameter request list option, which asks the server to
+    /// send the given options.
+    pub fn parameter_request_list<'buf>(
+        buffer: &'buf mut [u8],
+        codes: &[OptionCode],
+    ) -> Result<&'buf DhcpOption, Option<usize>> {
+        // `OptionCode` is a transparent wrapper around `u8`.
+        let codes = unsafe { slice::from_raw_parts(codes.as_ptr().cast::<u8>(), codes.len()) };
+        Self::build(buffer, OptionCode::PARAMETER_REQUEST_LIST, codes)
+    }
+
+    /// Writes an option holding an address.
+    pub fn ipv4(
+        buffer: &mut [u8],
+        code: OptionCode,
+        address: Ipv4Address,
+    ) -> Result<&DhcpOption, Option<usize>> {
+        Self::build(buffer, code, &address.octets())
+    }
+
+    /// Interprets bytes as an option, which must be exactly as long as its
+    /// length field says.
+    pub fn from_bytes(bytes: &[u8]) -> Option<&DhcpOption> {
+        if bytes.len() < 2 || bytes.len() != 2 + usize::from(bytes[1]) {
+            return None;
+        }
+        Some(unsafe { Self::from_bytes_unchecked(bytes) })
+    }
+
+    /// Interprets bytes as an option, without checking them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must hold a code, a length, and as much data as the length
+    /// says.
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &DhcpOption {
+        &*(bytes as *const [u8] as *const DhcpOption)
+    }
+
+    /// Interprets a pointer to an option returned by the firmware.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to a valid option, which lives for `'a`.
+    unsafe fn from_ptr<'a>(ptr: *const u8) -> &'a DhcpOption {
+        let len = 2 + usize::from(*ptr.add(1));
+        Self::from_bytes_unchecked(slice::from_raw_parts(ptr, len))
+    }
+
+    /// Returns the raw bytes of the option, including its code and length.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the code of the option.
+    pub fn code(&self) -> OptionCode {
+        OptionCode(self.0[0])
+    }
+
+    /// Returns the data of the option.
+    pub fn data(&self) -> &[u8] {
+        &self.0[2..]
+    }
+
+    /// Returns the address held by the option, if its data has the size of
+    /// an address.
+    pub fn as_ipv4(&self) -> Option<Ipv4Address> {
+        Some(Ipv4Address(self.data().try_into().ok()?))
+    }
+
+    /// Returns the addresses held by the option, such as the routers or the
+    /// DNS servers.
+    pub fn as_ipv4_list(&self) -> impl Iterator<Item = Ipv4Address> + '_ {
+        self.data()
+            .chunks_exact(4)
+            .map(|chunk| Ipv4Address(chunk.try_into().unwrap()))
+    }
+
+    /// Returns the 32-bit number held by the option, such as the lease time.
+    pub fn as_u32(&self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.data().try_into().ok()?))
+    }
+}
+
+impl fmt::Debug for DhcpOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DhcpOption")
+            .field("code", &self.code())
+            .field("data", &self.data())
+            .finish()
+    }
+}
+
+/// A DHCP packet (`EFI_DHCP4_PACKET`).
+///
+/// The packet starts with its size and length, in native byte order, which
+/// are followed by the DHCP message, in network byte order.
+///
+/// ```
+/// use uefi::data_types::Ipv4Address;
+/// use uefi::proto::network::dhcp4::{Message, MessageType, OptionCode, Packet};
+///
+/// // A DHCPACK sent by the DHCP server of QEMU's user-mode network stack.
+/// let mut bytes = vec![0; Packet::OPTIONS_OFFSET];
+/// bytes[8..12].copy_from_slice(&[2, 1, 6, 0]);
+/// bytes[12..16].copy_from_slice(&[0x3c, 0x6f, 0x2a, 0x91]);
+/// bytes[24..28].copy_from_slice(&[10, 0, 2, 15]);
+/// bytes[28..32].copy_from_slice(&[10, 0, 2, 2]);
+/// bytes[36..42].copy_from_slice(&[0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+/// bytes[244..248].copy_from_slice(&Message::MAGIC.to_be_bytes());
+/// bytes.extend_from_slice(&[
+///     53, 1, 5,
+///     54, 4, 10, 0, 2, 2,
+///     51, 4, 0x00, 0x01, 0x51, 0x80,
+///     1, 4, 255, 255, 255, 0,
+///     3, 4, 10, 0, 2, 2,
+///     6, 4, 10, 0, 2, 3,
+///     255, 0, 0,
+/// ]);
+/// let size = bytes.len() as u32;
+/// bytes[0..4].copy_from_slice(&size.to_le_bytes());
+/// bytes[4..8].copy_from_slice(&(size - 8).to_le_bytes());
+///
+/// let packet = Packet::from_bytes(&bytes).unwrap();
+/// assert_eq!(packet.op(), 2);
+/// assert_eq!(packet.xid(), 0x3c6f_2a91);
+/// assert_eq!(packet.your_address(), Ipv4Address([10, 0, 2, 15]));
+/// assert_eq!(packet.server_address(), Ipv4Address([10, 0, 2, 2]));
+/// assert_eq!(packet.client_hw_address(), &[0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+/// assert_eq!(packet.server_name(), b"");
+///
+/// assert_eq!(packet.message_type(), Some(MessageType::ACK));
+/// assert_eq!(packet.options().count(), 6);
+/// let lease_time = packet.option(OptionCode::LEASE_TIME).unwrap();
+/// assert_eq!(lease_time.as_u32(), Some(86400));
+/// let dns = packet.option(OptionCode::DNS_SERVER).unwrap();
+/// assert_eq!(dns.as_ipv4_list().collect::<Vec<_>>(), [Ipv4Address([10, 0, 2, 3])]);
+/// assert!(packet.option(OptionCode::HOST_NAME).is_none());
+///
+/// // Packets with a truncated header or a wrong magic number are rejected.
+/// assert!(Packet::from_bytes(&bytes[..200]).is_none());
+/// bytes[244] = 0;
+/// assert!(Packet::from_bytes(&bytes).is_none());
+/// ```
+#[repr(transparent)]
+pub struct Packet([u8]);
+
+impl Packet {
+    /// Offset of the DHCP message, after the size and length fields.
+    const MESSAGE_OFFSET: usize = 8;
+    /// Offset of the options, after the fixed header and the magic number.
+    pub const OPTIONS_OFFSET: usize = Self::MESSAGE_OFFSET + Message::OPTIONS_OFFSET;
+
+    /// Interprets bytes as a packet.
+    ///
+    /// Returns `None` if the bytes are shorter than the length of the packet,
+    /// or if the packet does not hold a DHCP message.
+    pub fn from_bytes(bytes: &[u8]) -> Option<&Packet> {
+        let length = u32::from_le_bytes(bytes.get(4..8)?.try_into().unwrap());
+        let end = Self::MESSAGE_OFFSET.checked_add(length.try_into().ok()?)?;
+        let bytes = bytes.get(..end)?;
+        Message::from_bytes(&bytes[Self::MESSAGE_OFFSET..])?;
+        Some(unsafe { &*(bytes as *const [u8] as *const Packet) })
+    }
+
+    /// Interprets a pointer to a packet returned by the firmware.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to a valid packet, which lives for `'a`.
+    unsafe fn from_ptr<'a>(ptr: *const u8) -> &'a Packet {
+        let length = ptr::read_unaligned(ptr.add(4).cast::<u32>());
+        let bytes = slice::from_raw_parts(ptr, Self::MESSAGE_OFFSET + length as usize);
+        &*(bytes as *const [u8] as *const Packet)
+    }
+
+    /// Size of the buffer holding the packet, which can be larger than the
+    /// packet itself.
+    fn buffer_size(&self) -> usize {
+        u32::from_le_bytes(self.0[0..4].try_into().unwrap()) as usize
+    }
+
+    /// Returns the raw bytes of the packet, including its size and length.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the DHCP message held by the packet.
+    pub fn message(&self) -> &Message {
+        unsafe { Message::from_bytes_unchecked(&self.0[Self::MESSAGE_OFFSET..]) }
+    }
+}
+
+impl Deref for Packet {
+    type Target = Message;
+
+    fn deref(&self) -> &Message {
+        self.message()
+    }
+}
+
+impl fmt::Debug for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("op", &self.op())
+            .field("xid", &self.xid())
+            .field("message_type", &self.message_type())
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// A DHCP message, in network byte order.
+///
+/// This is the payload of a `Packet`, and is also how other protocols store
+/// DHCP messages, such as the cached packets of the PXE base code protocol.
+///
+/// ```
+/// use uefi::proto::network::dhcp4::{Message, MessageType};
+///
+/// let mut bytes = [0; 300];
+/// bytes[0] = 1;
+/// bytes[236..240].copy_from_slice(&Message::MAGIC.to_be_bytes());
+/// bytes[240..243].copy_from_slice(&[53, 1, 1]);
+///
+/// // The trailing zeros of the buffer are skipped as padding.
+/// let message = Message::from_bytes(&bytes).unwrap();
+/// assert_eq!(message.op(), 1);
+/// assert_eq!(message.message_type(), Some(MessageType::DISCOVER));
+/// assert_eq!(message.options().count(), 1);
+/// ```
+#[repr(transparent)]
+pub struct Message([u8]);
+
+impl Message {
+    /// Magic number which precedes the options.
+    pub const MAGIC: u32 = 0x6382_5363;
+    /// Offset of the options, after the fixed header and the magic number.
+    pub const OPTIONS_OFFSET: usize = 236 + 4;
+
+    /// Interprets bytes as a message.
+    ///
+    /// Returns `None` if the bytes are too short to hold the fixed header, or
+    /// if the magic number is wrong.
+    pub fn from_bytes(bytes: &[u8]) -> Option<&Message> {
+        if bytes.len() < Self::OPTIONS_OFFSET {
+            return None;
+        }
+        let message = unsafe { Self::from_bytes_unchecked(bytes) };
+        if message.be_u32(Self::OPTIONS_OFFSET - 4) != Self::MAGIC {
+            return None;
+        }
+        Some(message)
+    }
+
+    /// Interprets bytes as a message, without checking them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be at least as long as the fixed header.
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &Message {
+        &*(bytes as *const [u8] as *const Message)
+    }
+
+    fn be_u32(&self, offset: usize) -> u32 {
+        u32::from_be_bytes(self.0[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn address(&self, offset: usize) -> Ipv4Address {
+        Ipv4Address(self.0[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Returns the bytes of a null-terminated string field.
+    fn string(&self, range: core::ops::Range<usize>) -> &[u8] {
+        let field = &self.0[range];
+        let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+        &field[..len]
+    }
+
+    /// Returns the raw bytes of the message.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the operation code: 1 for requests, and 2 for replies.
+    pub fn op(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Returns the transaction ID, which matches replies with requests.
+    pub fn xid(&self) -> u32 {
+        self.be_u32(4)
+    }
+
+    /// Returns the address of the client, if it already has one.
+    pub fn client_address(&self) -> Ipv4Address {
+        self.address(12)
+    }
+
+    /// Returns the address assigned to the client by the server.
+    pub fn your_address(&self) -> Ipv4Address {
+        self.address(16)
+    }
+
+    /// Returns the address of the next server to use, such as a TFTP
+    /// server.
+    pub fn server_address(&self) -> Ipv4Address {
+        self.address(20)
+    }
+
+    /// Returns the address of the relay agent.
+    pub fn gateway_address(&self) -> Ipv4Address {
+        self.address(24)
+    }
+
+    /// Returns the hardware address of the client.
+    pub fn client_hw_address(&self) -> &[u8] {
+        let len = usize::from(self.0[2]).min(16);
+        &self.0[28..28 + len]
+    }
+
+    /// Returns the host name of the server, if any.
+    pub fn server_name(&self) -> &[u8] {
+        self.string(44..108)
+    }
+
+    /// Returns the name of the boot file, if any.
+    pub fn boot_file_name(&self) -> &[u8] {
+        self.string(108..236)
+    }
+
+    /// Returns an iterator over the options of the message.
+    ///
+    /// Padding is skipped, and the iteration stops at the end option. Options
+    /// stored in the server name and boot file name fields, as signaled by
+    /// the overload option, are not included.
+    pub fn options(&self) -> Options<'_> {
+        Options {
+            bytes: &self.0[Self::OPTIONS_OFFSET..],
+        }
+    }
+
+    /// Returns the first option with the given code.
+    pub fn option(&self, code: OptionCode) -> Option<&DhcpOption> {
+        self.options().find(|option| option.code() == code)
+    }
+
+    /// Returns the type of the message.
+    pub fn message_type(&self) -> Option<MessageType> {
+        let option = self.option(OptionCode::MESSAGE_TYPE)?;
+        match option.data() {
+            &[ty] => Some(MessageType(ty)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Message")
+            .field("op", &self.op())
+            .field("xid", &self.xid())
+            .field("message_type", &self.message_type())
+            .finish()
+    }
+}
+
+/// Iterator over the options of a `Message`.
+#[derive(Clone, Debug)]
+pub struct Options<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for Options<'a> {
+    type Item = &'a DhcpOption;
+
+    fn next(&mut self) -> Option<&'a DhcpOption> {
+        loop {
+            let code = OptionCode(*self.bytes.first()?);
+            if code == OptionCode::PAD {
+                self.bytes = &self.bytes[1..];
+                continue;
+            }
+            if code == OptionCode::END {
+                self.bytes = &[];
+                return None;
+            }
+            let size = 2 + usize::from(*self.bytes.get(1)?);
+            if self.bytes.len() < size {
+                // The option is truncated.
+                self.bytes = &[];
+                return None;
+            }
+            let (option, rest) = self.bytes.split_at(size);
+            self.bytes = rest;
+            return Some(unsafe { DhcpOption::from_bytes_unchecked(option) });
+        }
+    }
+}
+
+/// A packet allocated by the firmware, which is freed when dropped.
+pub struct PoolPacket<'a> {
+    packet: &'a Packet,
+    bt: &'a BootServices,
+}
+
+impl Deref for PoolPacket<'_> {
+    type Target = Packet;
+
+    fn deref(&self) -> &Packet {
+        self.packet
+    }
+}
+
+impl fmt::Debug for PoolPacket<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.packet.fmt(f)
+    }
+}
+
+impl Drop for PoolPacket<'_> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = self.bt.free_pool(self.packet.0.as_ptr() as *mut u8);
+    }
+}
+
+/// Packets received by `Dhcp4::transmit_receive`, which are freed when
+/// dropped.
+pub struct Responses<'a> {
+    buffer: *mut u8,
+    count: usize,
+    bt: &'a BootServices,
+}
+
+impl Responses<'_> {
+    /// Number of received packets.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether no packet was received.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns an iterator over the received packets.
+    pub fn iter(&self) -> impl Iterator<Item = &Packet> {
+        let mut next = self.buffer as *const u8;
+        (0..self.count).map(move |_| {
+            // The packets are stored back to back, each in a buffer of the
+            // size given by its first field.
+            let packet = unsafe { Packet::from_ptr(next) };
+            next = unsafe { next.add(packet.buffer_size()) };
+            packet
+        })
+    }
+}
+
+impl fmt::Debug for Responses<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Drop for Responses<'_> {
+    fn drop(&mut self) {
+        if !self.buffer.is_null() {
+            // Ignore the result, we can't do anything about an error here.
+            let _ = self.bt.free_pool(self.buffer);
+        }
+    }
+}
+
+/// Maximum number of options passed to `Dhcp4::configure`, `Dhcp4::build`
+/// and `Dhcp4::parse`.
+pub const MAX_OPTIONS: usize = 64;
+
+/// Configuration of a `Dhcp4` instance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConfigData<'a> {
+    /// Time to wait for offers after each discover message, in seconds. The
+    /// number of attempts is the length of the slice, and the defaults of
+    /// the protocol are used if it is empty.
+    pub discover_timeouts: &'a [u32],
+    /// Time to wait for an acknowledgement after each request message, in
+    /// seconds. The number of attempts is the length of the slice, and the
+    /// defaults of the protocol are used if it is empty.
+    pub request_timeouts: &'a [u32],
+    /// A previously allocated address to request again, or zero to request a
+    /// new one.
+    pub client_address: Ipv4Address,
+    /// Options appended to all the packets sent by the client, such as a
+    /// parameter request list or a vendor class.
+    pub options: &'a [&'a DhcpOption],
+}
+
+/// The `EFI_DHCP4_CONFIG_DATA` structure.
+#[repr(C)]
+struct RawConfigData {
+    discover_try_count: u32,
+    discover_timeout: *const u32,
+    request_try_count: u32,
+    request_timeout: *const u32,
+    client_address: Ipv4Address,
+    callback: *const c_void,
+    callback_context: *mut c_void,
+    option_count: u32,
+    option_list: *const *const u8,
+}
+
+/// The `EFI_DHCP4_MODE_DATA` structure.
+#[repr(C)]
+struct RawModeData {
+    state: State,
+    config: RawConfigData,
+    client_address: Ipv4Address,
+    client_mac_address: MacAddress,
+    server_address: Ipv4Address,
+    router_address: Ipv4Address,
+    subnet_mask: Ipv4Address,
+    lease_time: u32,
+    reply_packet: *const u8,
+}
+
+/// State of a `Dhcp4` instance and of its lease.
+#[derive(Debug)]
+pub struct ModeData<'a> {
+    /// State of the client.
+    pub state: State,
+    /// Address of the client.
+    pub client_address: Ipv4Address,
+    /// Hardware address of the client.
+    pub client_mac_address: MacAddress,
+    /// Address of the server which granted the lease.
+    pub server_address: Ipv4Address,
+    /// Address of the default router.
+    pub router_address: Ipv4Address,
+    /// Subnet mask of the client address.
+    pub subnet_mask: Ipv4Address,
+    /// Duration of the lease, in seconds. All ones means an infinite lease.
+    pub lease_time: u32,
+    /// The last acknowledgement received from the server, if any.
+    pub reply_packet: Option<&'a Packet>,
+}
+
+/// An address and port on which replies are received during
+/// `Dhcp4::transmit_receive` (`EFI_DHCP4_LISTEN_POINT`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct ListenPoint {
+    /// Address to listen on. Zero uses the address of the client.
+    pub listen_address: Ipv4Address,
+    /// Subnet mask of the address.
+    pub subnet_mask: Ipv4Address,
+    /// Port to listen on. Zero uses the DHCP client port.
+    pub listen_port: u16,
+}
+
+/// Where a packet is sent by `Dhcp4::transmit_receive`.
+#[derive(Clone, Copy, Debug)]
+pub struct TransmitReceiveParams<'a> {
+    /// Address of the server.
+    pub remote_address: Ipv4Address,
+    /// Port of the server. Zero uses the DHCP server port.
+    pub remote_port: u16,
+    /// Relay agent to send the packet through, or zero to send it directly.
+    pub gateway_address: Ipv4Address,
+    /// Where the replies are received. If empty, they are received on the
+    /// address of the client and on the DHCP client port.
+    pub listen_points: &'a [ListenPoint],
+    /// Time to collect replies, in seconds.
+    pub timeout: u32,
+}
+
+/// The `EFI_DHCP4_TRANSMIT_RECEIVE_TOKEN` structure.
+#[repr(C)]
+struct TransmitReceiveToken {
+    status: Status,
+    completion_event: Option<Event>,
+    remote_address: Ipv4Address,
+    remote_port: u16,
+    gateway_address: Ipv4Address,
+    listen_point_count: u32,
+    listen_points: *const ListenPoint,
+    timeout_value: u32,
+    packet: *const u8,
+    response_count: u32,
+    response_list: *mut u8,
+}
+
+/// Converts options to the array of pointers expected by the firmware.
+fn option_pointers(options: &[&DhcpOption]) -> Option<[*const u8; MAX_OPTIONS]> {
+    if options.len() > MAX_OPTIONS {
+        return None;
+    }
+    let mut pointers = [ptr::null(); MAX_OPTIONS];
+    for (pointer, option) in pointers.iter_mut().zip(options) {
+        *pointer = option.0.as_ptr();
+    }
+    Some(pointers)
+}
+
+/// The DHCP4 protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Dhcp4 {
+    get_mode_data: unsafe extern "efiapi" fn(this: &Dhcp4, mode: *mut RawModeData) -> Status,
+    configure: unsafe extern "efiapi" fn(this: &Dhcp4, config: *const RawConfigData) -> Status,
+    start: unsafe extern "efiapi" fn(this: &Dhcp4, completion_event: Option<Event>) -> Status,
+    renew_rebind: unsafe extern "efiapi" fn(
+        this: &Dhcp4,
+        rebind_request: bool,
+        completion_event: Option<Event>,
+    ) -> Status,
+    release: extern "efiapi" fn(this: &Dhcp4) -> Status,
+    stop: extern "efiapi" fn(this: &Dhcp4) -> Status,
+    build: unsafe extern "efiapi" fn(
+        this: &Dhcp4,
+        seed_packet: *const u8,
+        delete_count: u32,
+        delete_list: *const OptionCode,
+        append_count: u32,
+        append_list: *const *const u8,
+        new_packet: *mut *mut u8,
+    ) -> Status,
+    transmit_receive:
+        unsafe extern "efiapi" fn(this: &Dhcp4, token: *mut TransmitReceiveToken) -> Status,
+    parse: unsafe extern "efiapi" fn(
+        this: &Dhcp4,
+        packet: *const u8,
+        option_count: *mut u32,
+        option_list: *mut *const u8,
+    ) -> Status,
+}
+
+unsafe impl Identify for Dhcp4 {
+    const GUID: Guid = guid!("8a219718-4ef5-4761-91c8-c0f04bda9e56");
+}
+
+impl Dhcp4 {
+    /// Returns the state of this instance and of its lease.
+    pub fn get_mode_data(&self) -> Result<ModeData<'_>> {
+        let mut mode = mem::MaybeUninit::<RawModeData>::zeroed();
+        unsafe { (self.get_mode_data)(self, mode.as_mut_ptr()) }.into_with_val(|| {
+            let mode = unsafe { mode.assume_init() };
+            ModeData {
+                state: mode.state,
+                client_address: mode.client_address,
+                client_mac_address: mode.client_mac_address,
+                server_address: mode.server_address,
+                router_address: mode.router_address,
+                subnet_mask: mode.subnet_mask,
+                lease_time: mode.lease_time,
+                reply_packet: if mode.reply_packet.is_null() {
+                    None
+                } else {
+                    Some(unsafe { Packet::from_ptr(mode.reply_packet) })
+                },
+            }
+        })
+    }
+
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// The configuration is copied by the protocol. `ACCESS_DENIED` is
+    /// returned if another instance is already configured on the same
+    /// interface, and `INVALID_PARAMETER` if there are more than
+    /// `MAX_OPTIONS` options.
+    pub fn configure(&mut self, config: Option<&ConfigData>) -> Result {
+        let config = match config {
+            Some(config) => config,
+            None => return unsafe { (self.configure)(self, ptr::null()) }.into(),
+        };
+        let options = option_pointers(config.options).ok_or(Status::INVALID_PARAMETER)?;
+        let raw = RawConfigData {
+            discover_try_count: config.discover_timeouts.len() as u32,
+            discover_timeout: config.discover_timeouts.as_ptr(),
+            request_try_count: config.request_timeouts.len() as u32,
+            request_timeout: config.request_timeouts.as_ptr(),
+            client_address: config.client_address,
+            callback: ptr::null(),
+            callback_context: ptr::null_mut(),
+            option_count: config.options.len() as u32,
+            option_list: options.as_ptr(),
+        };
+        unsafe { (self.configure)(self, &raw) }.into()
+    }
+
+    /// Starts acquiring a lease.
+    ///
+    /// If `event` is `None`, this blocks until the lease is acquired, or the
+    /// exchange fails. Otherwise, this returns immediately, and the event is
+    /// signaled once the exchange is over, after which `get_mode_data` tells
+    /// whether it succeeded.
+    pub fn start(&mut self, event: Option<&Event>) -> Result {
+        let event = event.map(|event| unsafe { event.unsafe_clone() });
+        unsafe { (self.start)(self, event) }.into()
+    }
+
+    /// Extends the lease, with the server which granted it, or with any
+    /// server if `rebind` is set.
+    ///
+    /// Like with `start`, this blocks until the exchange is over if `event`
+    /// is `None`.
+    pub fn renew_rebind(&mut self, rebind: bool, event: Option<&Event>) -> Result {
+        let event = event.map(|event| unsafe { event.unsafe_clone() });
+        unsafe { (self.renew_rebind)(self, rebind, event) }.into()
+    }
+
+    /// Gives the lease back to the server, and stops the client.
+    pub fn release(&mut self) -> Result {
+        (self.release)(self).into()
+    }
+
+    /// Stops the client, without releasing the lease.
+    pub fn stop(&mut self) -> Result {
+        (self.stop)(self).into()
+    }
+
+    /// Builds a new packet from a seed packet, by removing the options with
+    /// the codes in `delete`, and adding the options in `append`.
+    ///
+    /// `INVALID_PARAMETER` is returned if there are more than `MAX_OPTIONS`
+    /// options to append.
+    pub fn build<'bt>(
+        &self,
+        bt: &'bt BootServices,
+        seed: &Packet,
+        delete: &[OptionCode],
+        append: &[&DhcpOption],
+    ) -> Result<PoolPacket<'bt>> {
+        let options = option_pointers(append).ok_or(Status::INVALID_PARAMETER)?;
+        let mut packet = ptr::null_mut();
+        unsafe {
+            (self.build)(
+                self,
+                seed.0.as_ptr(),
+                delete.len() as u32,
+                delete.as_ptr(),
+                append.len() as u32,
+                options.as_ptr(),
+                &mut packet,
+            )
+        }
+        .into_with_val(|| PoolPacket {
+            packet: unsafe { Packet::from_ptr(packet) },
+            bt,
+        })
+    }
+
+    /// Sends a packet built with `build`, and collects the replies for the
+    /// timeout of the parameters.
+    ///
+    /// This blocks until the timeout expires. `TIMEOUT` is returned if no
+    /// reply was received.
+    pub fn transmit_receive<'bt>(
+        &mut self,
+        bt: &'bt BootServices,
+        packet: &Packet,
+        params: &TransmitReceiveParams,
+    ) -> Result<Responses<'bt>> {
+        let mut token = TransmitReceiveToken {
+            status: Status::NOT_READY,
+            completion_event: None,
+            remote_address: params.remote_address,
+            remote_port: params.remote_port,
+            gateway_address: params.gateway_address,
+            listen_point_count: params.listen_points.len() as u32,
+            listen_points: params.listen_points.as_ptr(),
+            timeout_value: params.timeout,
+            packet: packet.0.as_ptr(),
+            response_count: 0,
+            response_list: ptr::null_mut(),
+        };
+        unsafe { (self.transmit_receive)(self, &mut token) }
+            .into_result()?
+            .log();
+        let responses = Responses {
+            buffer: token.response_list,
+            count: token.response_count as usize,
+            bt,
+        };
+        token.status.into_with_val(|| responses)
+    }
+
+    /// Parses the options of a packet, and stores them in `options`. The
+    /// number of options is returned.
+    ///
+    /// If `options` is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the number of options in the packet. The options can also be parsed
+    /// without the firmware with `Packet::options`.
+    pub fn parse<'packet>(
+        &self,
+        packet: &'packet Packet,
+        options: &mut [Option<&'packet DhcpOption>],
+    ) -> Result<usize, Option<usize>> {
+        let mut pointers = [ptr::null(); MAX_OPTIONS];
+        let mut count = options.len().min(MAX_OPTIONS) as u32;
+        let status =
+            unsafe { (self.parse)(self, packet.0.as_ptr(), &mut count, pointers.as_mut_ptr()) };
+        status.into_with(
+            || {
+                let count = count as usize;
+                for (option, &pointer) in options.iter_mut().zip(&pointers[..count]) {
+                    *option = Some(unsafe { DhcpOption::from_ptr(pointer) });
+                }
+                count
+            },
+            |status| {
+                if status == Status::BUFFER_TOO_SMALL {
+                    Some(count as usize)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+}
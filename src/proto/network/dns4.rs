@@ -0,0 +1,766 @@
+//! DNS over IPv4 protocol.
+//!
+//! This protocol resolves host names into addresses, and addresses into host
+//! names, by querying the DNS servers of a network interface. Instances of
+//! the protocol are obtained by creating a child handle through the
+//! `Dns4ServiceBinding` protocol.
+//!
+//! The responses are allocated by the firmware, and are wrapped in types
+//! which free them when dropped.
+
+use super::with_event;
+use crate::data_types::Ipv4Address;
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+#[cfg(feature = "exts")]
+use crate::{
+    alloc_api::vec::Vec,
+    data_types::{ucs2, IpAddress},
+    ResultExt,
+};
+use crate::{CStr16, CStr8, Char16, Char8, Event, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+use core::{fmt, mem, ptr, slice};
+
+/// Service binding protocol used to create `Dns4` instances.
+pub type Dns4ServiceBinding = ServiceBinding<Dns4>;
+
+unsafe impl ChildProtocol for Dns4 {
+    const SERVICE_BINDING_GUID: Guid = guid!("b625b186-e063-44f7-8905-6a74dc6f52b4");
+}
+
+/// IP protocol number of UDP, the only transport supported by most
+/// implementations.
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/// Maximum length of a host name, including the null terminator.
+#[cfg(feature = "exts")]
+const MAX_HOST_NAME_LEN: usize = 256;
+
+newtype_enum! {
+/// Type of a DNS resource record, as defined in RFC 1035 and RFC 3596.
+pub enum RecordType: u16 => {
+    /// Host address.
+    A     = 1,
+    /// Authoritative name server.
+    NS    = 2,
+    /// Canonical name of an alias.
+    CNAME = 5,
+    /// Start of a zone of authority.
+    SOA   = 6,
+    /// Domain name pointer, used for reverse lookups.
+    PTR   = 12,
+    /// Mail exchange.
+    MX    = 15,
+    /// Text strings.
+    TXT   = 16,
+    /// IPv6 host address.
+    AAAA  = 28,
+    /// Service location.
+    SRV   = 33,
+}}
+
+newtype_enum! {
+/// Class of a DNS resource record, as defined in RFC 1035.
+pub enum RecordClass: u16 => {
+    /// The Internet.
+    IN = 1,
+}}
+
+/// Configuration of a `Dns4` instance.
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigData<'a> {
+    /// DNS servers to query, in order of preference. If empty, the servers
+    /// are obtained from the configuration of the interface.
+    pub dns_servers: &'a [Ipv4Address],
+    /// Whether to use the default address of the interface, instead of
+    /// `station_ip` and `subnet_mask`.
+    pub use_default_setting: bool,
+    /// Whether to cache the responses, and answer queries from the cache.
+    pub enable_dns_cache: bool,
+    /// Address of this instance.
+    pub station_ip: Ipv4Address,
+    /// Subnet mask of `station_ip`.
+    pub subnet_mask: Ipv4Address,
+    /// Local port of the queries. Zero picks an ephemeral port.
+    pub local_port: u16,
+    /// Number of retransmissions of a query before giving up.
+    pub retry_count: u32,
+    /// Time between retransmissions, in seconds. Implementations use at
+    /// least 2 seconds.
+    pub retry_interval: u32,
+}
+
+impl Default for ConfigData<'_> {
+    fn default() -> Self {
+        Self {
+            dns_servers: &[],
+            use_default_setting: true,
+            enable_dns_cache: true,
+            station_ip: Ipv4Address::UNSPECIFIED,
+            subnet_mask: Ipv4Address::UNSPECIFIED,
+            local_port: 0,
+            retry_count: 3,
+            retry_interval: 2,
+        }
+    }
+}
+
+/// The `EFI_DNS4_CONFIG_DATA` structure.
+#[repr(C)]
+struct RawConfigData {
+    dns_server_count: usize,
+    dns_server_list: *mut Ipv4Address,
+    use_default_setting: bool,
+    enable_dns_cache: bool,
+    protocol: u8,
+    station_ip: Ipv4Address,
+    subnet_mask: Ipv4Address,
+    local_port: u16,
+    retry_count: u32,
+    retry_interval: u32,
+}
+
+/// The `EFI_DNS4_CACHE_ENTRY` structure.
+#[repr(C)]
+struct RawCacheEntry {
+    host_name: *const Char16,
+    ip_address: *const Ipv4Address,
+    timeout: u32,
+}
+
+/// The `EFI_DNS4_MODE_DATA` structure.
+#[repr(C)]
+struct RawModeData {
+    config: RawConfigData,
+    dns_server_count: u32,
+    dns_server_list: *mut Ipv4Address,
+    dns_cache_count: u32,
+    dns_cache_list: *mut RawCacheEntry,
+}
+
+/// Builds a slice from a pointer returned by the firmware, which is null if
+/// the slice is empty.
+unsafe fn raw_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if ptr.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    }
+}
+
+/// Frees a buffer allocated by the firmware, if it is not null.
+fn free<T>(bt: &BootServices, ptr: *mut T) {
+    if !ptr.is_null() {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = bt.free_pool(ptr.cast());
+    }
+}
+
+/// An entry of the DNS cache.
+#[derive(Clone, Copy, Debug)]
+pub struct CacheEntry<'a> {
+    /// The host name.
+    pub host_name: &'a CStr16,
+    /// The address of the host.
+    pub address: Ipv4Address,
+    /// Remaining lifetime of the entry, in seconds.
+    pub timeout: u32,
+}
+
+/// State of a `Dns4` instance, which is freed when dropped.
+pub struct ModeData<'a> {
+    raw: RawModeData,
+    bt: &'a BootServices,
+}
+
+impl ModeData<'_> {
+    /// The configuration of the instance.
+    pub fn config(&self) -> ConfigData<'_> {
+        let config = &self.raw.config;
+        ConfigData {
+            dns_servers: unsafe { raw_slice(config.dns_server_list, config.dns_server_count) },
+            use_default_setting: config.use_default_setting,
+            enable_dns_cache: config.enable_dns_cache,
+            station_ip: config.station_ip,
+            subnet_mask: config.subnet_mask,
+            local_port: config.local_port,
+            retry_count: config.retry_count,
+            retry_interval: config.retry_interval,
+        }
+    }
+
+    /// The DNS servers queried by the instance.
+    pub fn dns_servers(&self) -> &[Ipv4Address] {
+        unsafe { raw_slice(self.raw.dns_server_list, self.raw.dns_server_count as usize) }
+    }
+
+    /// Returns an iterator over the entries of the DNS cache.
+    pub fn cache(&self) -> impl Iterator<Item = CacheEntry<'_>> {
+        let entries =
+            unsafe { raw_slice(self.raw.dns_cache_list, self.raw.dns_cache_count as usize) };
+        entries.iter().map(|entry| CacheEntry {
+            host_name: unsafe { CStr16::from_ptr(entry.host_name) },
+            address: unsafe { *entry.ip_address },
+            timeout: entry.timeout,
+        })
+    }
+}
+
+impl fmt::Debug for ModeData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModeData")
+            .field("config", &self.config())
+            .field("dns_servers", &self.dns_servers())
+            .field("cache_len", &self.raw.dns_cache_count)
+            .finish()
+    }
+}
+
+impl Drop for ModeData<'_> {
+    fn drop(&mut self) {
+        // The lists are copies allocated for the caller, while the strings
+        // and addresses of the cache entries belong to the protocol.
+        free(self.bt, self.raw.config.dns_server_list);
+        free(self.bt, self.raw.dns_server_list);
+        free(self.bt, self.raw.dns_cache_list);
+    }
+}
+
+/// The `DNS_HOST_TO_ADDR_DATA` structure.
+#[repr(C)]
+struct HostToAddrData {
+    ip_count: u32,
+    ip_list: *mut Ipv4Address,
+}
+
+/// The `DNS_ADDR_TO_HOST_DATA` structure.
+#[repr(C)]
+struct AddrToHostData {
+    host_name: *mut Char16,
+}
+
+/// The `DNS_GENERAL_LOOKUP_DATA` structure.
+#[repr(C)]
+struct GeneralLookupData {
+    rr_count: usize,
+    rr_list: *mut RawResourceRecord,
+}
+
+/// The `DNS_RESOURCE_RECORD` structure.
+#[repr(C)]
+struct RawResourceRecord {
+    q_name: *mut Char8,
+    q_type: RecordType,
+    q_class: RecordClass,
+    ttl: u32,
+    data_length: u16,
+    r_data: *mut u8,
+}
+
+/// Addresses of a host, which are freed when dropped.
+pub struct Addresses<'a> {
+    data: *mut HostToAddrData,
+    bt: &'a BootServices,
+}
+
+impl Addresses<'_> {
+    /// The addresses of the host.
+    pub fn as_slice(&self) -> &[Ipv4Address] {
+        let data = unsafe { &*self.data };
+        unsafe { raw_slice(data.ip_list, data.ip_count as usize) }
+    }
+}
+
+impl fmt::Debug for Addresses<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl Drop for Addresses<'_> {
+    fn drop(&mut self) {
+        free(self.bt, unsafe { (*self.data).ip_list });
+        free(self.bt, self.data);
+    }
+}
+
+/// Name of a host, which is freed when dropped.
+pub struct HostName<'a> {
+    data: *mut AddrToHostData,
+    bt: &'a BootServices,
+}
+
+impl HostName<'_> {
+    /// The name of the host.
+    pub fn as_cstr16(&self) -> &CStr16 {
+        unsafe { CStr16::from_ptr((*self.data).host_name) }
+    }
+}
+
+impl fmt::Debug for HostName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("HostName")
+            .field(&self.as_cstr16().to_u16_slice())
+            .finish()
+    }
+}
+
+impl Drop for HostName<'_> {
+    fn drop(&mut self) {
+        free(self.bt, unsafe { (*self.data).host_name });
+        free(self.bt, self.data);
+    }
+}
+
+/// A DNS resource record.
+#[derive(Clone, Copy, Debug)]
+pub struct ResourceRecord<'a> {
+    /// The name which the record is about.
+    pub name: &'a CStr8,
+    /// The type of the record.
+    pub record_type: RecordType,
+    /// The class of the record.
+    pub class: RecordClass,
+    /// The time for which the record can be cached, in seconds.
+    pub ttl: u32,
+    /// The data of the record, whose format depends on its type.
+    pub data: &'a [u8],
+}
+
+/// Resource records returned by a general lookup, which are freed when
+/// dropped.
+pub struct ResourceRecords<'a> {
+    data: *mut GeneralLookupData,
+    bt: &'a BootServices,
+}
+
+impl ResourceRecords<'_> {
+    fn raw_records(&self) -> &[RawResourceRecord] {
+        let data = unsafe { &*self.data };
+        unsafe { raw_slice(data.rr_list, data.rr_count) }
+    }
+
+    /// Number of records.
+    pub fn len(&self) -> usize {
+        self.raw_records().len()
+    }
+
+    /// Whether there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the records.
+    pub fn iter(&self) -> impl Iterator<Item = ResourceRecord<'_>> {
+        self.raw_records().iter().map(|record| ResourceRecord {
+            name: unsafe { CStr8::from_ptr(record.q_name) },
+            record_type: record.q_type,
+            class: record.q_class,
+            ttl: record.ttl,
+            data: unsafe { raw_slice(record.r_data, usize::from(record.data_length)) },
+        })
+    }
+}
+
+impl fmt::Debug for ResourceRecords<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Drop for ResourceRecords<'_> {
+    fn drop(&mut self) {
+        for record in self.raw_records() {
+            free(self.bt, record.q_name);
+            free(self.bt, record.r_data);
+        }
+        free(self.bt, unsafe { (*self.data).rr_list });
+        free(self.bt, self.data);
+    }
+}
+
+/// A completion token, used to track an asynchronous query
+/// (`EFI_DNS4_COMPLETION_TOKEN`).
+///
+/// Each kind of query uses a wrapper of this token, which gives access to
+/// its response.
+#[repr(C)]
+pub struct CompletionToken {
+    event: Event,
+    status: Status,
+    retry_count: u32,
+    retry_interval: u32,
+    response: *mut c_void,
+}
+
+impl CompletionToken {
+    unsafe fn new(event: &Event) -> Self {
+        Self {
+            event: event.unsafe_clone(),
+            status: Status::NOT_READY,
+            retry_count: 0,
+            retry_interval: 0,
+            response: ptr::null_mut(),
+        }
+    }
+
+    /// Status of the query, which is `NOT_READY` until it completes.
+    pub fn status(&self) -> Status {
+        // The protocol updates the status behind our back.
+        unsafe { ptr::read_volatile(&self.status) }
+    }
+
+    /// Whether the query has completed.
+    pub fn is_complete(&self) -> bool {
+        self.status() != Status::NOT_READY
+    }
+
+    /// Overrides the retransmission settings of the configuration for this
+    /// query. Zero keeps the configured value.
+    pub fn set_retry(&mut self, count: u32, interval: u32) {
+        self.retry_count = count;
+        self.retry_interval = interval;
+    }
+
+    /// Takes ownership of the response of a successful query.
+    fn take_response<T>(&mut self) -> Option<*mut T> {
+        if self.status() != Status::SUCCESS || self.response.is_null() {
+            return None;
+        }
+        Some(mem::replace(&mut self.response, ptr::null_mut()).cast())
+    }
+}
+
+/// Implements the constructor and accessors shared by all the tokens
+/// wrapping a `CompletionToken`.
+macro_rules! query_token {
+    ($token:ident) => {
+        impl $token {
+            /// Creates a token which signals `event` on completion.
+            ///
+            /// # Safety
+            ///
+            /// The event must stay open as long as the token is in use.
+            pub unsafe fn new(event: &Event) -> Self {
+                Self(CompletionToken::new(event))
+            }
+
+            /// Status of the query, which is `NOT_READY` until it completes.
+            pub fn status(&self) -> Status {
+                self.0.status()
+            }
+
+            /// Whether the query has completed.
+            pub fn is_complete(&self) -> bool {
+                self.0.is_complete()
+            }
+
+            /// The underlying completion token, which can be passed to
+            /// `Dns4::cancel`.
+            pub fn completion_token(&mut self) -> &mut CompletionToken {
+                &mut self.0
+            }
+        }
+    };
+}
+
+/// A token used to resolve a host name into addresses.
+#[repr(transparent)]
+pub struct HostNameToIpToken(CompletionToken);
+
+query_token!(HostNameToIpToken);
+
+impl HostNameToIpToken {
+    /// Takes the addresses found by a successful query.
+    ///
+    /// Returns `None` if the query has not succeeded, or if they were
+    /// already taken.
+    pub fn take_addresses<'bt>(&mut self, bt: &'bt BootServices) -> Option<Addresses<'bt>> {
+        let data = self.0.take_response()?;
+        Some(Addresses { data, bt })
+    }
+}
+
+/// A token used to resolve an address into a host name.
+#[repr(transparent)]
+pub struct IpToHostNameToken(CompletionToken);
+
+query_token!(IpToHostNameToken);
+
+impl IpToHostNameToken {
+    /// Takes the host name found by a successful query.
+    ///
+    /// Returns `None` if the query has not succeeded, or if it was already
+    /// taken.
+    pub fn take_host_name<'bt>(&mut self, bt: &'bt BootServices) -> Option<HostName<'bt>> {
+        let data = self.0.take_response()?;
+        Some(HostName { data, bt })
+    }
+}
+
+/// A token used to look up resource records.
+#[repr(transparent)]
+pub struct LookupToken(CompletionToken);
+
+query_token!(LookupToken);
+
+impl LookupToken {
+    /// Takes the records found by a successful query.
+    ///
+    /// Returns `None` if the query has not succeeded, or if they were
+    /// already taken.
+    pub fn take_records<'bt>(&mut self, bt: &'bt BootServices) -> Option<ResourceRecords<'bt>> {
+        let data = self.0.take_response()?;
+        Some(ResourceRecords { data, bt })
+    }
+}
+
+/// The DNS4 protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Dns4 {
+    get_mode_data: unsafe extern "efiapi" fn(this: &Dns4, mode: *mut RawModeData) -> Status,
+    configure: unsafe extern "efiapi" fn(this: &Dns4, config: *const RawConfigData) -> Status,
+    host_name_to_ip: unsafe extern "efiapi" fn(
+        this: &Dns4,
+        host_name: *const Char16,
+        token: *mut CompletionToken,
+    ) -> Status,
+    ip_to_host_name: unsafe extern "efiapi" fn(
+        this: &Dns4,
+        ip_address: Ipv4Address,
+        token: *mut CompletionToken,
+    ) -> Status,
+    general_lookup: unsafe extern "efiapi" fn(
+        this: &Dns4,
+        q_name: *const Char8,
+        q_type: RecordType,
+        q_class: RecordClass,
+        token: *mut CompletionToken,
+    ) -> Status,
+    update_dns_cache: unsafe extern "efiapi" fn(
+        this: &Dns4,
+        delete: bool,
+        override_existing: bool,
+        entry: RawCacheEntry,
+    ) -> Status,
+    poll: extern "efiapi" fn(this: &Dns4) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: &Dns4, token: *mut CompletionToken) -> Status,
+}
+
+unsafe impl Identify for Dns4 {
+    const GUID: Guid = guid!("ae3d28cc-e05b-4fa1-a011-7eb55a3f1401");
+}
+
+impl Dns4 {
+    /// Returns the configuration, the servers and the cache of this
+    /// instance.
+    pub fn get_mode_data<'bt>(&self, bt: &'bt BootServices) -> Result<ModeData<'bt>> {
+        let mut raw = mem::MaybeUninit::<RawModeData>::zeroed();
+        unsafe { (self.get_mode_data)(self, raw.as_mut_ptr()) }.into_with_val(|| ModeData {
+            raw: unsafe { raw.assume_init() },
+            bt,
+        })
+    }
+
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// Resetting the instance cancels the pending queries.
+    pub fn configure(&mut self, config: Option<&ConfigData>) -> Result {
+        let config = config.map(|config| RawConfigData {
+            dns_server_count: config.dns_servers.len(),
+            dns_server_list: config.dns_servers.as_ptr() as *mut _,
+            use_default_setting: config.use_default_setting,
+            enable_dns_cache: config.enable_dns_cache,
+            protocol: IP_PROTOCOL_UDP,
+            station_ip: config.station_ip,
+            subnet_mask: config.subnet_mask,
+            local_port: config.local_port,
+            retry_count: config.retry_count,
+            retry_interval: config.retry_interval,
+        });
+        let config = config
+            .as_ref()
+            .map_or(ptr::null(), |config| config as *const _);
+        unsafe { (self.configure)(self, config) }.into()
+    }
+
+    /// Starts resolving a host name into addresses.
+    ///
+    /// # Safety
+    ///
+    /// The name and the token must not be moved, modified or freed until the
+    /// token has completed.
+    pub unsafe fn host_name_to_ip(
+        &mut self,
+        host_name: &CStr16,
+        token: &mut HostNameToIpToken,
+    ) -> Result {
+        (self.host_name_to_ip)(self, host_name.as_ptr(), token.completion_token()).into()
+    }
+
+    /// Starts resolving an address into a host name.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved, modified or freed until it has
+    /// completed.
+    pub unsafe fn ip_to_host_name(
+        &mut self,
+        address: Ipv4Address,
+        token: &mut IpToHostNameToken,
+    ) -> Result {
+        (self.ip_to_host_name)(self, address, token.completion_token()).into()
+    }
+
+    /// Starts looking up the records of a name, with an arbitrary type and
+    /// class.
+    ///
+    /// # Safety
+    ///
+    /// The name and the token must not be moved, modified or freed until the
+    /// token has completed.
+    pub unsafe fn general_lookup(
+        &mut self,
+        name: &CStr8,
+        record_type: RecordType,
+        class: RecordClass,
+        token: &mut LookupToken,
+    ) -> Result {
+        (self.general_lookup)(
+            self,
+            name.as_ptr(),
+            record_type,
+            class,
+            token.completion_token(),
+        )
+        .into()
+    }
+
+    /// Adds an entry to the DNS cache, which expires after `timeout`
+    /// seconds. If the entry exists, its timeout is only updated if
+    /// `override_existing` is set.
+    pub fn add_cache_entry(
+        &mut self,
+        host_name: &CStr16,
+        address: Ipv4Address,
+        timeout: u32,
+        override_existing: bool,
+    ) -> Result {
+        let entry = RawCacheEntry {
+            host_name: host_name.as_ptr(),
+            ip_address: &address,
+            timeout,
+        };
+        unsafe { (self.update_dns_cache)(self, false, override_existing, entry) }.into()
+    }
+
+    /// Removes an entry from the DNS cache.
+    pub fn remove_cache_entry(&mut self, host_name: &CStr16, address: Ipv4Address) -> Result {
+        let entry = RawCacheEntry {
+            host_name: host_name.as_ptr(),
+            ip_address: &address,
+            timeout: 0,
+        };
+        unsafe { (self.update_dns_cache)(self, true, false, entry) }.into()
+    }
+
+    /// Polls the network interface for received packets.
+    ///
+    /// This is not required for the queries to make progress, but can be
+    /// used to speed them up.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+
+    /// Cancels a pending query, or all of them if `token` is `None`.
+    ///
+    /// The tokens of the cancelled queries complete with `ABORTED`.
+    pub fn cancel(&mut self, token: Option<&mut CompletionToken>) -> Result {
+        let token = token.map_or(ptr::null_mut(), |token| token as *mut _);
+        unsafe { (self.cancel)(self, token) }.into()
+    }
+
+    /// Resolves a host name into addresses, and waits for the response.
+    pub fn host_name_to_ip_blocking<'bt>(
+        &mut self,
+        bt: &'bt BootServices,
+        host_name: &CStr16,
+    ) -> Result<Addresses<'bt>> {
+        with_event(bt, |event| {
+            let mut token = unsafe { HostNameToIpToken::new(event) };
+            unsafe { self.host_name_to_ip(host_name, &mut token) }?.log();
+            self.wait(bt, token.completion_token())?.log();
+            let response = token.take_addresses(bt).ok_or(Status::NOT_FOUND)?;
+            Ok(response.into())
+        })
+    }
+
+    /// Resolves an address into a host name, and waits for the response.
+    pub fn ip_to_host_name_blocking<'bt>(
+        &mut self,
+        bt: &'bt BootServices,
+        address: Ipv4Address,
+    ) -> Result<HostName<'bt>> {
+        with_event(bt, |event| {
+            let mut token = unsafe { IpToHostNameToken::new(event) };
+            unsafe { self.ip_to_host_name(address, &mut token) }?.log();
+            self.wait(bt, token.completion_token())?.log();
+            let response = token.take_host_name(bt).ok_or(Status::NOT_FOUND)?;
+            Ok(response.into())
+        })
+    }
+
+    /// Looks up the records of a name, and waits for the response.
+    pub fn general_lookup_blocking<'bt>(
+        &mut self,
+        bt: &'bt BootServices,
+        name: &CStr8,
+        record_type: RecordType,
+        class: RecordClass,
+    ) -> Result<ResourceRecords<'bt>> {
+        with_event(bt, |event| {
+            let mut token = unsafe { LookupToken::new(event) };
+            unsafe { self.general_lookup(name, record_type, class, &mut token) }?.log();
+            self.wait(bt, token.completion_token())?.log();
+            let response = token.take_records(bt).ok_or(Status::NOT_FOUND)?;
+            Ok(response.into())
+        })
+    }
+
+    /// Resolves a host name into addresses.
+    ///
+    /// `INVALID_PARAMETER` is returned if the name is longer than 255
+    /// characters, and `UNSUPPORTED` if it cannot be encoded in UCS-2.
+    #[cfg(feature = "exts")]
+    pub fn resolve(&mut self, bt: &BootServices, host_name: &str) -> Result<Vec<IpAddress>> {
+        let mut buffer = [0; MAX_HOST_NAME_LEN];
+        let len = match ucs2::encode_str(host_name, &mut buffer) {
+            Ok(len) => len.log(),
+            Err(err) if err.status() == Status::BUFFER_TOO_SMALL => {
+                return Err(Status::INVALID_PARAMETER.into())
+            }
+            Err(err) => return Err(err.status().into()),
+        };
+        let host_name = unsafe { CStr16::from_u16_with_nul_unchecked(&buffer[..len]) };
+        self.host_name_to_ip_blocking(bt, host_name)
+            .map_inner(|addresses| {
+                addresses
+                    .as_slice()
+                    .iter()
+                    .map(|&address| IpAddress::new_v4(address))
+                    .collect()
+            })
+    }
+
+    /// Waits for a query to complete, and cancels it if waiting fails.
+    fn wait(&mut self, bt: &BootServices, token: &mut CompletionToken) -> Result {
+        if let Err(err) = bt.wait_for_event(slice::from_ref(&token.event)) {
+            self.cancel(Some(token))?.log();
+            return Err(err.status().into());
+        }
+        token.status().into()
+    }
+}
@@ -0,0 +1,1009 @@
+//! HTTP protocol.
+//!
+//! This protocol sends HTTP requests and receives their responses, on top of
+//! a TCP connection managed by the firmware. Instances are obtained by
+//! creating a child handle through the `HttpServiceBinding` protocol.
+//!
+//! A request is sent with `Http::request`, and its response is received with
+//! one or more calls to `Http::response`: the first one receives the status
+//! code, the headers and the start of the body, the following ones receive
+//! the rest of the body. The body is delivered as it was sent by the server,
+//! so a body sent with the chunked transfer coding must be decoded with
+//! `ChunkedDecoder`. For simple downloads, `Http::get` does all of this.
+
+use super::with_event;
+use crate::data_types::{Ipv4Address, Ipv6Address};
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+#[cfg(feature = "exts")]
+use crate::{
+    alloc_api::{vec, vec::Vec},
+    data_types::ucs2,
+};
+use crate::{CStr16, CStr8, Char16, Char8, Event, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::{fmt, ptr, slice};
+
+/// Service binding protocol used to create `Http` instances.
+pub type HttpServiceBinding = ServiceBinding<Http>;
+
+unsafe impl ChildProtocol for Http {
+    const SERVICE_BINDING_GUID: Guid = guid!("bdc8e6af-d9bc-4379-a72a-e0c4e75dae1c");
+}
+
+/// Size of the buffer in which `Http::get` receives the body.
+#[cfg(feature = "exts")]
+const GET_BUFFER_SIZE: usize = 0x4000;
+
+newtype_enum! {
+/// Version of the HTTP protocol.
+pub enum Version: u32 => {
+    /// HTTP/1.0.
+    HTTP_1_0    = 0,
+    /// HTTP/1.1.
+    HTTP_1_1    = 1,
+    /// A version which is not supported by the protocol.
+    UNSUPPORTED = 2,
+}}
+
+newtype_enum! {
+/// Method of an HTTP request.
+pub enum Method: u32 => {
+    /// Transfers the target resource.
+    GET     = 0,
+    /// Processes the body of the request.
+    POST    = 1,
+    /// Partially modifies the target resource.
+    PATCH   = 2,
+    /// Describes the options of the target resource.
+    OPTIONS = 3,
+    /// Establishes a tunnel to the server.
+    CONNECT = 4,
+    /// Like `GET`, but only transfers the headers of the response.
+    HEAD    = 5,
+    /// Replaces the target resource with the body of the request.
+    PUT     = 6,
+    /// Removes the target resource.
+    DELETE  = 7,
+    /// Loops the request back to the client.
+    TRACE   = 8,
+}}
+
+newtype_enum! {
+/// Status code of an HTTP response.
+///
+/// The values are indices defined by the UEFI specification, not
+/// the numeric status codes; use `StatusCode::code` to get the latter.
+pub enum StatusCode: u32 => {
+    /// A status code which is not known by the protocol.
+    UNSUPPORTED                     = 0,
+    /// 100 Continue.
+    CONTINUE                        = 1,
+    /// 101 Switching Protocols.
+    SWITCHING_PROTOCOLS             = 2,
+    /// 200 OK.
+    OK                              = 3,
+    /// 201 Created.
+    CREATED                         = 4,
+    /// 202 Accepted.
+    ACCEPTED                        = 5,
+    /// 203 Non-Authoritative Information.
+    NON_AUTHORITATIVE_INFORMATION   = 6,
+    /// 204 No Content.
+    NO_CONTENT                      = 7,
+    /// 205 Reset Content.
+    RESET_CONTENT                   = 8,
+    /// 206 Partial Content.
+    PARTIAL_CONTENT                 = 9,
+    /// 300 Multiple Choices.
+    MULTIPLE_CHOICES                = 10,
+    /// 301 Moved Permanently.
+    MOVED_PERMANENTLY               = 11,
+    /// 302 Found.
+    FOUND                           = 12,
+    /// 303 See Other.
+    SEE_OTHER                       = 13,
+    /// 304 Not Modified.
+    NOT_MODIFIED                    = 14,
+    /// 305 Use Proxy.
+    USE_PROXY                       = 15,
+    /// 307 Temporary Redirect.
+    TEMPORARY_REDIRECT              = 16,
+    /// 400 Bad Request.
+    BAD_REQUEST                     = 17,
+    /// 401 Unauthorized.
+    UNAUTHORIZED                    = 18,
+    /// 402 Payment Required.
+    PAYMENT_REQUIRED                = 19,
+    /// 403 Forbidden.
+    FORBIDDEN                       = 20,
+    /// 404 Not Found.
+    NOT_FOUND                       = 21,
+    /// 405 Method Not Allowed.
+    METHOD_NOT_ALLOWED              = 22,
+    /// 406 Not Acceptable.
+    NOT_ACCEPTABLE                  = 23,
+    /// 407 Proxy Authentication Required.
+    PROXY_AUTHENTICATION_REQUIRED   = 24,
+    /// 408 Request Timeout.
+    REQUEST_TIME_OUT                = 25,
+    /// 409 Conflict.
+    CONFLICT                        = 26,
+    /// 410 Gone.
+    GONE                            = 27,
+    /// 411 Length Required.
+    LENGTH_REQUIRED                 = 28,
+    /// 412 Precondition Failed.
+    PRECONDITION_FAILED             = 29,
+    /// 413 Payload Too Large.
+    REQUEST_ENTITY_TOO_LARGE        = 30,
+    /// 414 URI Too Long.
+    REQUEST_URI_TOO_LARGE           = 31,
+    /// 415 Unsupported Media Type.
+    UNSUPPORTED_MEDIA_TYPE          = 32,
+    /// 416 Range Not Satisfiable.
+    REQUESTED_RANGE_NOT_SATISFIED   = 33,
+    /// 417 Expectation Failed.
+    EXPECTATION_FAILED              = 34,
+    /// 500 Internal Server Error.
+    INTERNAL_SERVER_ERROR           = 35,
+    /// 501 Not Implemented.
+    NOT_IMPLEMENTED                 = 36,
+    /// 502 Bad Gateway.
+    BAD_GATEWAY                     = 37,
+    /// 503 Service Unavailable.
+    SERVICE_UNAVAILABLE             = 38,
+    /// 504 Gateway Timeout.
+    GATEWAY_TIME_OUT                = 39,
+    /// 505 HTTP Version Not Supported.
+    HTTP_VERSION_NOT_SUPPORTED      = 40,
+    /// 308 Permanent Redirect.
+    PERMANENT_REDIRECT              = 41,
+}}
+
+impl StatusCode {
+    /// The numeric status code, or `None` if the status code is not known.
+    ///
+    /// ```
+    /// use uefi::proto::network::http::StatusCode;
+    ///
+    /// assert_eq!(StatusCode::OK.code(), Some(200));
+    /// assert_eq!(StatusCode::TEMPORARY_REDIRECT.code(), Some(307));
+    /// assert_eq!(StatusCode::PERMANENT_REDIRECT.code(), Some(308));
+    /// assert_eq!(StatusCode::UNSUPPORTED.code(), None);
+    /// ```
+    pub fn code(self) -> Option<u16> {
+        const CODES: [u16; 41] = [
+            100, 101, 200, 201, 202, 203, 204, 205, 206, 300, 301, 302, 303, 304, 305, 307, 400,
+            401, 402, 403, 404, 405, 406, 407, 408, 409, 410, 411, 412, 413, 414, 415, 416, 417,
+            500, 501, 502, 503, 504, 505, 308,
+        ];
+        let index = (self.0 as usize).checked_sub(1)?;
+        CODES.get(index).copied()
+    }
+
+    /// Whether this is a 2xx status code, which indicates that the request
+    /// was processed successfully.
+    pub fn is_success(self) -> bool {
+        matches!(self.code(), Some(200..=299))
+    }
+}
+
+/// Local endpoint of an instance using IPv4 (`EFI_HTTPv4_ACCESS_POINT`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct Ipv4AccessPoint {
+    /// Use the default address of the interface, as configured by DHCP or
+    /// the `Ip4Config2` protocol, instead of `local_address`.
+    pub use_default_address: bool,
+    /// Local address.
+    pub local_address: Ipv4Address,
+    /// Subnet mask of the local address.
+    pub local_subnet: Ipv4Address,
+    /// Local port. Zero picks an ephemeral port.
+    pub local_port: u16,
+}
+
+impl Default for Ipv4AccessPoint {
+    fn default() -> Self {
+        Self {
+            use_default_address: true,
+            local_address: Ipv4Address::UNSPECIFIED,
+            local_subnet: Ipv4Address::UNSPECIFIED,
+            local_port: 0,
+        }
+    }
+}
+
+/// Local endpoint of an instance using IPv6 (`EFI_HTTPv6_ACCESS_POINT`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct Ipv6AccessPoint {
+    /// Local address.
+    pub local_address: Ipv6Address,
+    /// Local port. Zero picks an ephemeral port.
+    pub local_port: u16,
+}
+
+/// Local endpoint of an instance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccessPoint {
+    /// Connect to the server over IPv4.
+    Ipv4(Ipv4AccessPoint),
+    /// Connect to the server over IPv6.
+    Ipv6(Ipv6AccessPoint),
+}
+
+/// Configuration of an `Http` instance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConfigData {
+    /// Version of the protocol used for the requests.
+    pub version: Version,
+    /// Timeout of the requests, in milliseconds.
+    pub timeout_ms: u32,
+    /// Local endpoint of the connections.
+    pub access_point: AccessPoint,
+}
+
+impl Default for ConfigData {
+    /// Uses HTTP/1.1 over IPv4, with the default address of the interface
+    /// and a timeout of 5 seconds.
+    fn default() -> Self {
+        Self {
+            version: Version::HTTP_1_1,
+            timeout_ms: 5000,
+            access_point: AccessPoint::Ipv4(Ipv4AccessPoint::default()),
+        }
+    }
+}
+
+/// The access point union of `EFI_HTTP_CONFIG_DATA`.
+#[repr(C)]
+union RawAccessPoint {
+    ipv4: Ipv4AccessPoint,
+    ipv6: Ipv6AccessPoint,
+}
+
+/// The `EFI_HTTP_CONFIG_DATA` structure.
+#[repr(C)]
+struct RawConfigData {
+    version: Version,
+    timeout_ms: u32,
+    local_address_is_ipv6: bool,
+    access_point: *mut RawAccessPoint,
+}
+
+/// A header of a request or a response (`EFI_HTTP_HEADER`).
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Header<'a> {
+    name: *const Char8,
+    value: *const Char8,
+    _strings: PhantomData<&'a CStr8>,
+}
+
+impl<'a> Header<'a> {
+    /// Creates a header with a name and a value.
+    pub fn new(name: &'a CStr8, value: &'a CStr8) -> Self {
+        Self {
+            name: name.as_ptr(),
+            value: value.as_ptr(),
+            _strings: PhantomData,
+        }
+    }
+
+    /// The name of the header.
+    pub fn name(&self) -> &'a CStr8 {
+        unsafe { CStr8::from_ptr(self.name) }
+    }
+
+    /// The value of the header.
+    pub fn value(&self) -> &'a CStr8 {
+        unsafe { CStr8::from_ptr(self.value) }
+    }
+}
+
+impl fmt::Debug for Header<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Header")
+            .field("name", &self.name().to_bytes())
+            .field("value", &self.value().to_bytes())
+            .finish()
+    }
+}
+
+/// The headers of a response, which are freed when dropped.
+pub struct Headers<'a> {
+    headers: *mut Header<'static>,
+    count: usize,
+    bt: &'a BootServices,
+}
+
+impl Headers<'_> {
+    /// The headers, in the order in which they were received.
+    pub fn as_slice(&self) -> &[Header<'_>] {
+        if self.headers.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.headers, self.count) }
+        }
+    }
+
+    /// The value of the first header named `name`, which is compared
+    /// without regard to case.
+    pub fn get(&self, name: &str) -> Option<&CStr8> {
+        self.as_slice()
+            .iter()
+            .find(|header| {
+                header
+                    .name()
+                    .to_bytes()
+                    .eq_ignore_ascii_case(name.as_bytes())
+            })
+            .map(|header| header.value())
+    }
+}
+
+impl fmt::Debug for Headers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl Drop for Headers<'_> {
+    fn drop(&mut self) {
+        // The names and values are allocated separately from the array.
+        for header in self.as_slice() {
+            free(self.bt, header.name as *mut Char8);
+            free(self.bt, header.value as *mut Char8);
+        }
+        free(self.bt, self.headers);
+    }
+}
+
+/// Frees a buffer allocated by the firmware, if it is not null.
+fn free<T>(bt: &BootServices, ptr: *mut T) {
+    if !ptr.is_null() {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = bt.free_pool(ptr.cast());
+    }
+}
+
+/// The `EFI_HTTP_REQUEST_DATA` structure.
+#[repr(C)]
+struct RequestData {
+    method: Method,
+    url: *const Char16,
+}
+
+/// The `EFI_HTTP_RESPONSE_DATA` structure.
+#[repr(C)]
+struct ResponseData {
+    status_code: StatusCode,
+}
+
+/// The `EFI_HTTP_MESSAGE` structure.
+#[repr(C)]
+struct Message {
+    /// Points to the request or response data, or is null when only the
+    /// body is transferred.
+    data: *mut c_void,
+    header_count: usize,
+    headers: *mut Header<'static>,
+    body_length: usize,
+    body: *mut c_void,
+}
+
+/// A completion token, used to track a request or a response
+/// (`EFI_HTTP_TOKEN`).
+#[repr(C)]
+pub struct CompletionToken {
+    event: Event,
+    status: Status,
+    message: *mut Message,
+}
+
+impl CompletionToken {
+    unsafe fn new(event: &Event) -> Self {
+        Self {
+            event: event.unsafe_clone(),
+            status: Status::NOT_READY,
+            message: ptr::null_mut(),
+        }
+    }
+
+    /// Status of the operation, which is `NOT_READY` until it completes.
+    pub fn status(&self) -> Status {
+        // The protocol updates the status behind our back.
+        unsafe { ptr::read_volatile(&self.status) }
+    }
+
+    /// Whether the operation has completed.
+    pub fn is_complete(&self) -> bool {
+        self.status() != Status::NOT_READY
+    }
+}
+
+/// Implements the accessors shared by the request and response tokens.
+macro_rules! completion_token_accessors {
+    ($token:ident) => {
+        impl $token<'_> {
+            /// Status of the operation, which is `NOT_READY` until it
+            /// completes.
+            pub fn status(&self) -> Status {
+                self.completion.status()
+            }
+
+            /// Whether the operation has completed.
+            pub fn is_complete(&self) -> bool {
+                self.completion.is_complete()
+            }
+
+            /// The underlying completion token, which can be passed to
+            /// `Http::cancel`.
+            pub fn completion_token(&mut self) -> &mut CompletionToken {
+                &mut self.completion
+            }
+        }
+    };
+}
+
+/// A token used to send a request.
+pub struct RequestToken<'a> {
+    completion: CompletionToken,
+    message: Message,
+    request: RequestData,
+    _data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> RequestToken<'a> {
+    /// Creates a token which sends a request, and signals `event` once it
+    /// has been sent.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(
+        event: &Event,
+        method: Method,
+        url: &'a CStr16,
+        headers: &'a [Header<'a>],
+        body: &'a [u8],
+    ) -> Self {
+        Self {
+            completion: CompletionToken::new(event),
+            message: Message {
+                data: ptr::null_mut(),
+                header_count: headers.len(),
+                headers: headers.as_ptr() as *mut _,
+                body_length: body.len(),
+                body: body.as_ptr() as *mut _,
+            },
+            request: RequestData {
+                method,
+                url: url.as_ptr(),
+            },
+            _data: PhantomData,
+        }
+    }
+}
+
+completion_token_accessors!(RequestToken);
+
+/// A token used to receive a response.
+pub struct ResponseToken<'a> {
+    completion: CompletionToken,
+    message: Message,
+    response: Option<ResponseData>,
+    _body: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> ResponseToken<'a> {
+    /// Creates a token which receives the status code, the headers and the
+    /// start of the body of a response into `body`, and signals `event`
+    /// once they are received.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(event: &Event, body: &'a mut [u8]) -> Self {
+        Self::with_response(
+            event,
+            body,
+            Some(ResponseData {
+                status_code: StatusCode::UNSUPPORTED,
+            }),
+        )
+    }
+
+    /// Creates a token which receives the next part of the body of a
+    /// response into `body`, and signals `event` once it is received.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn body_only(event: &Event, body: &'a mut [u8]) -> Self {
+        Self::with_response(event, body, None)
+    }
+
+    unsafe fn with_response(
+        event: &Event,
+        body: &'a mut [u8],
+        response: Option<ResponseData>,
+    ) -> Self {
+        Self {
+            completion: CompletionToken::new(event),
+            message: Message {
+                data: ptr::null_mut(),
+                header_count: 0,
+                headers: ptr::null_mut(),
+                body_length: body.len(),
+                body: body.as_mut_ptr().cast(),
+            },
+            response,
+            _body: PhantomData,
+        }
+    }
+
+    /// The status code of the response, if this token receives it.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        self.response.as_ref().map(|response| response.status_code)
+    }
+
+    /// Size of the part of the body which was received.
+    pub fn body_len(&self) -> usize {
+        self.message.body_length
+    }
+
+    /// Takes the headers of the response, if they were received.
+    ///
+    /// They are leaked if the token is dropped without taking them.
+    pub fn take_headers<'bt>(&mut self, bt: &'bt BootServices) -> Option<Headers<'bt>> {
+        if self.message.headers.is_null() {
+            return None;
+        }
+        let headers = Headers {
+            headers: self.message.headers,
+            count: self.message.header_count,
+            bt,
+        };
+        self.message.headers = ptr::null_mut();
+        self.message.header_count = 0;
+        Some(headers)
+    }
+}
+
+completion_token_accessors!(ResponseToken);
+
+/// The head of a response, received by `Http::receive_response`.
+#[derive(Debug)]
+pub struct Response<'a> {
+    /// Status code of the response.
+    pub status_code: StatusCode,
+    /// Headers of the response.
+    pub headers: Headers<'a>,
+    /// Size of the start of the body, which was received along with the
+    /// headers.
+    pub body_len: usize,
+}
+
+/// State of a `ChunkedDecoder`.
+#[derive(Clone, Copy, Debug)]
+enum ChunkState {
+    /// In the size of a chunk.
+    Size { size: usize, digits: bool },
+    /// In the extensions of a chunk, after its size.
+    Extensions { size: usize },
+    /// After the CR ending the line of the size.
+    SizeLf { size: usize },
+    /// In the data of a chunk.
+    Data { remaining: usize },
+    /// Expecting the CR after the data of a chunk.
+    DataCr,
+    /// Expecting the LF after the data of a chunk.
+    DataLf,
+    /// At the start of a line of the trailer.
+    TrailerStart,
+    /// In a header of the trailer.
+    Trailer,
+    /// After the CR ending the trailer.
+    TrailerLf,
+    /// After the end of the body.
+    Done,
+}
+
+/// Decoder for a body sent with the chunked transfer coding.
+///
+/// The body can be fed to the decoder in parts of any size, as it is
+/// received.
+///
+/// ```
+/// use uefi::proto::network::http::ChunkedDecoder;
+///
+/// let mut decoder = ChunkedDecoder::new();
+/// let mut body = [0; 16];
+/// let mut len = 0;
+/// let mut sink = |data: &[u8]| {
+///     body[len..len + data.len()].copy_from_slice(data);
+///     len += data.len();
+/// };
+/// decoder.decode(b"5\r\nhello\r\n7;ext=1\r\n, w", &mut sink).unwrap();
+/// assert!(!decoder.is_complete());
+/// decoder.decode(b"orld\r\n0\r\nX-Trailer: 1\r\n\r\n", &mut sink).unwrap();
+/// assert!(decoder.is_complete());
+/// assert_eq!(&body[..len], b"hello, world");
+///
+/// let mut decoder = ChunkedDecoder::new();
+/// assert!(decoder.decode(b"5\r\nhello\n", |_| ()).is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChunkedDecoder {
+    state: ChunkState,
+}
+
+impl ChunkedDecoder {
+    /// Creates a decoder for a new body.
+    pub fn new() -> Self {
+        Self {
+            state: ChunkState::Size {
+                size: 0,
+                digits: false,
+            },
+        }
+    }
+
+    /// Whether the whole body has been decoded.
+    pub fn is_complete(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+
+    /// Decodes the next part of the body, passing the data it contains to
+    /// `sink`.
+    ///
+    /// `PROTOCOL_ERROR` is returned if the body is malformed, or if data
+    /// follows its end.
+    pub fn decode(&mut self, mut input: &[u8], mut sink: impl FnMut(&[u8])) -> Result {
+        while let Some((&byte, rest)) = input.split_first() {
+            if let ChunkState::Data { remaining } = self.state {
+                let len = remaining.min(input.len());
+                let (data, rest) = input.split_at(len);
+                sink(data);
+                input = rest;
+                self.state = if len == remaining {
+                    ChunkState::DataCr
+                } else {
+                    ChunkState::Data {
+                        remaining: remaining - len,
+                    }
+                };
+                continue;
+            }
+            self.state = self.next_state(byte).ok_or(Status::PROTOCOL_ERROR)?;
+            input = rest;
+        }
+        Ok(().into())
+    }
+
+    /// Returns the state following `byte`, or `None` if it is unexpected.
+    fn next_state(&self, byte: u8) -> Option<ChunkState> {
+        let state = match (self.state, byte) {
+            (ChunkState::Size { size, .. }, b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F') => {
+                let digit = (byte as char).to_digit(16)? as usize;
+                ChunkState::Size {
+                    size: size.checked_mul(16)?.checked_add(digit)?,
+                    digits: true,
+                }
+            }
+            (ChunkState::Size { size, digits: true }, b';' | b' ' | b'\t') => {
+                ChunkState::Extensions { size }
+            }
+            (ChunkState::Size { size, digits: true }, b'\r')
+            | (ChunkState::Extensions { size }, b'\r') => ChunkState::SizeLf { size },
+            (ChunkState::Extensions { size }, _) if byte != b'\n' => {
+                ChunkState::Extensions { size }
+            }
+            (ChunkState::SizeLf { size: 0 }, b'\n') => ChunkState::TrailerStart,
+            (ChunkState::SizeLf { size }, b'\n') => ChunkState::Data { remaining: size },
+            (ChunkState::DataCr, b'\r') => ChunkState::DataLf,
+            (ChunkState::DataLf, b'\n') => ChunkState::Size {
+                size: 0,
+                digits: false,
+            },
+            (ChunkState::TrailerStart, b'\r') => ChunkState::TrailerLf,
+            (ChunkState::TrailerStart, _) | (ChunkState::Trailer, _) if byte != b'\n' => {
+                ChunkState::Trailer
+            }
+            (ChunkState::Trailer, b'\n') => ChunkState::TrailerStart,
+            (ChunkState::TrailerLf, b'\n') => ChunkState::Done,
+            _ => return None,
+        };
+        Some(state)
+    }
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The HTTP protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Http {
+    get_mode_data: unsafe extern "efiapi" fn(this: &Http, config: *mut RawConfigData) -> Status,
+    configure: unsafe extern "efiapi" fn(this: &Http, config: *const RawConfigData) -> Status,
+    request: unsafe extern "efiapi" fn(this: &Http, token: *mut CompletionToken) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: &Http, token: *mut CompletionToken) -> Status,
+    response: unsafe extern "efiapi" fn(this: &Http, token: *mut CompletionToken) -> Status,
+    poll: extern "efiapi" fn(this: &Http) -> Status,
+}
+
+unsafe impl Identify for Http {
+    const GUID: Guid = guid!("7a59b29b-910b-4171-8242-a85a0df25b5b");
+}
+
+impl Http {
+    /// Returns the configuration of this instance.
+    ///
+    /// `NOT_STARTED` is returned if the instance has not been configured.
+    pub fn get_mode_data(&self) -> Result<ConfigData> {
+        // The caller provides the storage of the access point, which must
+        // be large enough for both address families.
+        let mut access_point = MaybeUninit::<RawAccessPoint>::zeroed();
+        let mut raw = RawConfigData {
+            version: Version::UNSUPPORTED,
+            timeout_ms: 0,
+            local_address_is_ipv6: false,
+            access_point: access_point.as_mut_ptr(),
+        };
+        unsafe { (self.get_mode_data)(self, &mut raw) }.into_with_val(|| {
+            let access_point = unsafe { access_point.assume_init() };
+            ConfigData {
+                version: raw.version,
+                timeout_ms: raw.timeout_ms,
+                access_point: if raw.local_address_is_ipv6 {
+                    AccessPoint::Ipv6(unsafe { access_point.ipv6 })
+                } else {
+                    AccessPoint::Ipv4(unsafe { access_point.ipv4 })
+                },
+            }
+        })
+    }
+
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// Resetting the instance closes its connection and cancels all the
+    /// pending operations. A configured instance must be reset before it
+    /// can be configured again.
+    pub fn configure(&mut self, config: Option<&ConfigData>) -> Result {
+        let mut access_point = config.map(|config| match config.access_point {
+            AccessPoint::Ipv4(ipv4) => RawAccessPoint { ipv4 },
+            AccessPoint::Ipv6(ipv6) => RawAccessPoint { ipv6 },
+        });
+        let raw = config.map(|config| RawConfigData {
+            version: config.version,
+            timeout_ms: config.timeout_ms,
+            local_address_is_ipv6: matches!(config.access_point, AccessPoint::Ipv6(_)),
+            access_point: access_point.as_mut().unwrap(),
+        });
+        let raw = raw.as_ref().map_or(ptr::null(), |raw| raw as *const _);
+        unsafe { (self.configure)(self, raw) }.into()
+    }
+
+    /// Sends a request, and signals the token once it has been sent.
+    ///
+    /// The firmware resolves the host name of the URL, and connects to the
+    /// server if needed. The headers are sent as they are, so they should
+    /// contain at least a `Host` header.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved or dropped until it completes.
+    pub unsafe fn request(&mut self, token: &mut RequestToken) -> Result {
+        token.message.data = (&mut token.request as *mut RequestData).cast();
+        token.completion.message = &mut token.message;
+        (self.request)(self, &mut token.completion).into()
+    }
+
+    /// Receives a part of the response to the last request, and signals the
+    /// token once it has been received.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved or dropped until it completes.
+    pub unsafe fn response(&mut self, token: &mut ResponseToken) -> Result {
+        token.message.data = token.response.as_mut().map_or(ptr::null_mut(), |response| {
+            (response as *mut ResponseData).cast()
+        });
+        token.completion.message = &mut token.message;
+        (self.response)(self, &mut token.completion).into()
+    }
+
+    /// Cancels a pending operation, or all of them if `token` is `None`.
+    ///
+    /// The tokens of the cancelled operations complete with `ABORTED`.
+    pub fn cancel(&mut self, token: Option<&mut CompletionToken>) -> Result {
+        let token = token.map_or(ptr::null_mut(), |token| token as *mut _);
+        unsafe { (self.cancel)(self, token) }.into()
+    }
+
+    /// Polls the network interface for received packets and finished
+    /// transmissions.
+    ///
+    /// This is not required for the operations to make progress, but can be
+    /// used to speed them up.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+
+    /// Sends a request, and waits until it has been sent.
+    pub fn send_request(
+        &mut self,
+        bt: &BootServices,
+        method: Method,
+        url: &CStr16,
+        headers: &[Header],
+        body: &[u8],
+    ) -> Result {
+        with_event(bt, |event| {
+            let mut token = unsafe { RequestToken::new(event, method, url, headers, body) };
+            unsafe { self.request(&mut token) }?.log();
+            self.wait(bt, token.completion_token())
+        })
+    }
+
+    /// Receives the status code and the headers of the response to the last
+    /// request, along with the start of its body, which is stored in `body`.
+    pub fn receive_response<'bt>(
+        &mut self,
+        bt: &'bt BootServices,
+        body: &mut [u8],
+    ) -> Result<Response<'bt>> {
+        with_event(bt, |event| {
+            let mut token = unsafe { ResponseToken::new(event, body) };
+            unsafe { self.response(&mut token) }?.log();
+            let completion = self.wait(bt, token.completion_token());
+            // The headers must be freed even if the response failed.
+            let headers = token.take_headers(bt).unwrap_or(Headers {
+                headers: ptr::null_mut(),
+                count: 0,
+                bt,
+            });
+            completion?.log();
+            Ok(Response {
+                status_code: token.status_code().unwrap(),
+                headers,
+                body_len: token.body_len(),
+            }
+            .into())
+        })
+    }
+
+    /// Receives the next part of the body of the response to the last
+    /// request into `body`, and returns its size.
+    ///
+    /// This waits until some data is received, and returns zero once the
+    /// server has closed the connection.
+    pub fn receive_body(&mut self, bt: &BootServices, body: &mut [u8]) -> Result<usize> {
+        with_event(bt, |event| {
+            let mut token = unsafe { ResponseToken::body_only(event, body) };
+            unsafe { self.response(&mut token) }?.log();
+            match self.wait(bt, token.completion_token()) {
+                Ok(completion) => Ok(completion.map(|()| token.body_len())),
+                Err(err) if err.status() == Status::CONNECTION_FIN => Ok(0.into()),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Downloads the resource at `url` with a `GET` request, and returns
+    /// its body.
+    ///
+    /// The instance must have been configured. The body may be delimited by
+    /// its length, sent with the chunked transfer coding, or end when the
+    /// server closes the connection.
+    ///
+    /// `INVALID_PARAMETER` is returned if the URL has no host, and
+    /// `HTTP_ERROR` if the status code of the response is not 2xx. If the
+    /// connection is closed before the end of the body, `CONNECTION_FIN` is
+    /// returned.
+    #[cfg(feature = "exts")]
+    pub fn get(&mut self, bt: &BootServices, url: &str) -> Result<Vec<u8>> {
+        // Every UTF-8 byte is encoded as at most one UCS-2 character.
+        let mut url_buffer = vec![0; url.len() + 1];
+        let len = ucs2::encode_str(url, &mut url_buffer)
+            .map_err(|err| err.status())?
+            .log();
+        let url_ucs2 = unsafe { CStr16::from_u16_with_nul_unchecked(&url_buffer[..len]) };
+
+        // HTTP/1.1 servers require a `Host` header, which the firmware does
+        // not add by itself.
+        let authority = url
+            .find("://")
+            .map(|start| &url[start + 3..])
+            .map(|rest| rest.split(&['/', '?', '#'][..]).next().unwrap())
+            .map(|authority| authority.rsplit('@').next().unwrap())
+            .filter(|authority| !authority.is_empty())
+            .ok_or(Status::INVALID_PARAMETER)?;
+        let mut host = Vec::with_capacity(authority.len() + 1);
+        host.extend_from_slice(authority.as_bytes());
+        host.push(0);
+        let cstr = |bytes: &'static [u8]| CStr8::from_bytes_with_nul(bytes).unwrap();
+        let headers = [
+            Header::new(cstr(b"Host\0"), CStr8::from_bytes_with_nul(&host).unwrap()),
+            Header::new(cstr(b"Accept\0"), cstr(b"*/*\0")),
+            Header::new(cstr(b"User-Agent\0"), cstr(b"uefi-rs\0")),
+        ];
+        self.send_request(bt, Method::GET, url_ucs2, &headers, &[])?
+            .log();
+
+        let mut buffer = vec![0; GET_BUFFER_SIZE];
+        let response = self.receive_response(bt, &mut buffer)?.log();
+        if !response.status_code.is_success() {
+            return Err(Status::HTTP_ERROR.into());
+        }
+        let is_chunked = response
+            .headers
+            .get("Transfer-Encoding")
+            .and_then(|value| core::str::from_utf8(value.to_bytes()).ok())
+            .and_then(|value| value.rsplit(',').next())
+            .filter(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+            .is_some();
+        let content_length = response
+            .headers
+            .get("Content-Length")
+            .and_then(|value| core::str::from_utf8(value.to_bytes()).ok())
+            .and_then(|value| value.trim().parse::<usize>().ok());
+        let mut len = response.body_len;
+        drop(response);
+
+        let mut decoder = ChunkedDecoder::new();
+        let mut body = Vec::with_capacity(content_length.unwrap_or(0));
+        loop {
+            let data = &buffer[..len];
+            if is_chunked {
+                decoder
+                    .decode(data, |data| body.extend_from_slice(data))?
+                    .log();
+                if decoder.is_complete() {
+                    break;
+                }
+            } else {
+                body.extend_from_slice(data);
+                if matches!(content_length, Some(content_length) if body.len() >= content_length) {
+                    break;
+                }
+            }
+
+            len = self.receive_body(bt, &mut buffer)?.log();
+            if len == 0 {
+                // Without a length or chunks, the body ends with the
+                // connection.
+                if is_chunked || content_length.is_some() {
+                    return Err(Status::CONNECTION_FIN.into());
+                }
+                break;
+            }
+        }
+        if let Some(content_length) = content_length {
+            body.truncate(content_length);
+        }
+        Ok(body.into())
+    }
+
+    /// Waits until the token completes, and returns its status.
+    ///
+    /// If waiting fails, the operation is cancelled, so that the token can
+    /// be freed.
+    fn wait(&mut self, bt: &BootServices, token: &mut CompletionToken) -> Result {
+        if let Err(err) = bt.wait_for_event(slice::from_ref(&token.event)) {
+            self.cancel(Some(token))?.log();
+            return Err(err.status().into());
+        }
+        token.status().into()
+    }
+}
@@ -0,0 +1,141 @@
+//! IPv4 Configuration II protocol.
+//!
+//! This protocol is installed on the handle of a network interface, and
+//! manages its IPv4 configuration: whether the address is set manually or
+//! obtained through DHCP, the gateways, and the DNS servers.
+//!
+//! Most of the protocol is shared with the IPv6 Configuration protocol, and
+//! is defined in the `ipconfig` module.
+
+use super::ipconfig::{interface_info_accessors, IpConfig, IpConfigVersion};
+use super::V4;
+use crate::data_types::{Ipv4Address, MacAddress};
+use crate::{Guid, Result};
+use core::slice;
+
+/// The IPv4 Configuration II protocol
+pub type Ip4Config2 = IpConfig<V4>;
+
+impl IpConfigVersion for V4 {
+    type DataType = DataType;
+    type Policy = Policy;
+    type InterfaceInfo = InterfaceInfo;
+    const INTERFACE_INFO: DataType = DataType::INTERFACE_INFO;
+    const POLICY: DataType = DataType::POLICY;
+    const GATEWAY: DataType = DataType::GATEWAY;
+    const DNS_SERVER: DataType = DataType::DNS_SERVER;
+    const GUID: Guid = guid!("5b446ed1-e30b-4faa-871a-3654eca36080");
+
+    fn assigned_address(info: &InterfaceInfo) -> Option<Ipv4Address> {
+        Some(info.station_address).filter(|&address| address != Ipv4Address::UNSPECIFIED)
+    }
+}
+
+newtype_enum! {
+/// Kind of configuration data (`EFI_IP4_CONFIG2_DATA_TYPE`).
+pub enum DataType: u32 => {
+    /// Current state of the interface, as an `InterfaceInfo`.
+    INTERFACE_INFO = 0,
+    /// How the interface is configured, as a `Policy`.
+    POLICY         = 1,
+    /// Manually set address, as a `ManualAddress`.
+    MANUAL_ADDRESS = 2,
+    /// Gateway addresses.
+    GATEWAY        = 3,
+    /// DNS server addresses.
+    DNS_SERVER     = 4,
+}}
+
+newtype_enum! {
+/// How the interface obtains its configuration.
+pub enum Policy: u32 => {
+    /// The configuration is set manually.
+    STATIC = 0,
+    /// The configuration is obtained through DHCP.
+    DHCP   = 1,
+}}
+
+/// A manually configured address (`EFI_IP4_CONFIG2_MANUAL_ADDRESS`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct ManualAddress {
+    /// Address of the interface.
+    pub address: Ipv4Address,
+    /// Subnet mask of the address.
+    pub subnet_mask: Ipv4Address,
+}
+
+/// An entry of the routing table (`EFI_IP4_ROUTE_TABLE`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct RouteEntry {
+    /// Address of the destination subnet.
+    pub subnet_address: Ipv4Address,
+    /// Mask of the destination subnet.
+    pub subnet_mask: Ipv4Address,
+    /// Gateway through which the subnet is reached, or zero if it is
+    /// directly reachable.
+    pub gateway_address: Ipv4Address,
+}
+
+/// Size of the name of an interface, including the null terminator.
+const INTERFACE_INFO_NAME_SIZE: usize = 32;
+
+/// Current state of a network interface
+/// (`EFI_IP4_CONFIG2_INTERFACE_INFO`).
+///
+/// This is stored in a buffer along with the routing table of the interface.
+#[repr(C)]
+pub struct InterfaceInfo {
+    name: [u16; INTERFACE_INFO_NAME_SIZE],
+    if_type: u8,
+    hw_address_size: u32,
+    hw_address: MacAddress,
+    station_address: Ipv4Address,
+    subnet_mask: Ipv4Address,
+    route_table_size: u32,
+    route_table: *const RouteEntry,
+}
+
+interface_info_accessors!(InterfaceInfo);
+
+impl InterfaceInfo {
+    /// Address of the interface, which is zero until one is assigned.
+    pub fn station_address(&self) -> Ipv4Address {
+        self.station_address
+    }
+
+    /// Subnet mask of the address of the interface.
+    pub fn subnet_mask(&self) -> Ipv4Address {
+        self.subnet_mask
+    }
+
+    /// Routing table of the interface.
+    pub fn route_table(&self) -> &[RouteEntry] {
+        if self.route_table.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.route_table, self.route_table_size as usize) }
+    }
+}
+
+impl Ip4Config2 {
+    /// Returns the manually set address.
+    ///
+    /// `NOT_FOUND` is returned if no address was set.
+    pub fn manual_address(&self) -> Result<ManualAddress> {
+        self.get_fixed(DataType::MANUAL_ADDRESS)
+    }
+
+    /// Sets the address of the interface, or clears it if `address` is
+    /// `None`. This is only allowed with the static policy.
+    ///
+    /// Applying the address can take some time, in which case `NOT_READY`
+    /// is returned, and the interface info is updated once it is done.
+    pub fn set_manual_address(&mut self, address: Option<&ManualAddress>) -> Result {
+        self.set(
+            DataType::MANUAL_ADDRESS,
+            address.map_or(&[], slice::from_ref),
+        )
+    }
+}
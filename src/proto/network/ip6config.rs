@@ -0,0 +1,249 @@
+//! IPv6 Configuration protocol.
+//!
+//! This protocol is installed on the handle of a network interface, and
+//! manages its IPv6 configuration: whether the addresses are set manually or
+//! obtained through stateless autoconfiguration and DHCPv6, the gateways, and
+//! the DNS servers.
+//!
+//! Before an address is assigned to the interface, the neighbor discovery
+//! protocol checks that no other node uses it. Until this duplicate address
+//! detection succeeds, the address is tentative, and does not appear in the
+//! interface info. A link-local address is assigned as soon as the interface
+//! is started, whatever the policy.
+//!
+//! Most of the protocol is shared with the IPv4 Configuration II protocol,
+//! and is defined in the `ipconfig` module.
+
+use super::ipconfig::{interface_info_accessors, IpConfig, IpConfigVersion};
+use super::V6;
+use crate::data_types::{Ipv6Address, MacAddress};
+use crate::{Guid, Result};
+use core::slice;
+
+/// The IPv6 Configuration protocol
+pub type Ip6Config = IpConfig<V6>;
+
+impl IpConfigVersion for V6 {
+    type DataType = DataType;
+    type Policy = Policy;
+    type InterfaceInfo = InterfaceInfo;
+    const INTERFACE_INFO: DataType = DataType::INTERFACE_INFO;
+    const POLICY: DataType = DataType::POLICY;
+    const GATEWAY: DataType = DataType::GATEWAY;
+    const DNS_SERVER: DataType = DataType::DNS_SERVER;
+    const GUID: Guid = guid!("937fe521-95ae-4d1a-8929-48bcd90ad31a");
+
+    fn assigned_address(info: &InterfaceInfo) -> Option<Ipv6Address> {
+        info.addresses().first().map(|info| info.address)
+    }
+}
+
+newtype_enum! {
+/// Kind of configuration data (`EFI_IP6_CONFIG_DATA_TYPE`).
+pub enum DataType: u32 => {
+    /// Current state of the interface, as an `InterfaceInfo`.
+    INTERFACE_INFO             = 0,
+    /// Identifier used to generate the link-local address, as an
+    /// `InterfaceId`.
+    ALT_INTERFACE_ID           = 1,
+    /// How the interface is configured, as a `Policy`.
+    POLICY                     = 2,
+    /// Number of neighbor solicitations sent for the duplicate address
+    /// detection, as a `DupAddrDetectTransmits`.
+    DUP_ADDR_DETECT_TRANSMITS  = 3,
+    /// Manually set addresses, as `ManualAddress` entries.
+    MANUAL_ADDRESS             = 4,
+    /// Gateway addresses.
+    GATEWAY                    = 5,
+    /// DNS server addresses.
+    DNS_SERVER                 = 6,
+}}
+
+newtype_enum! {
+/// How the interface obtains its configuration.
+pub enum Policy: u32 => {
+    /// The configuration is set manually.
+    MANUAL    = 0,
+    /// The configuration is obtained through stateless autoconfiguration,
+    /// and DHCPv6 if the routers ask for it.
+    AUTOMATIC = 1,
+}}
+
+/// Identifier of the interface, from which the link-local address is
+/// derived (`EFI_IP6_CONFIG_INTERFACE_ID`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct InterfaceId(pub [u8; 8]);
+
+/// Number of neighbor solicitations sent to check that an address is not
+/// used by another node, before assigning it
+/// (`EFI_IP6_CONFIG_DUP_ADDR_DETECT_TRANSMITS`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct DupAddrDetectTransmits {
+    /// Number of solicitations. Zero disables the duplicate address
+    /// detection.
+    pub dup_addr_detect_transmits: u32,
+}
+
+newtype_enum! {
+/// State of an entry of the neighbor cache (`EFI_IP6_NEIGHBOR_STATE`), as
+/// defined by the neighbor discovery protocol of RFC 4861.
+pub enum NeighborState: u32 => {
+    /// Address resolution is in progress, and the link-layer address of the
+    /// neighbor is not known yet.
+    INCOMPLETE = 0,
+    /// The neighbor was recently known to be reachable.
+    REACHABLE  = 1,
+    /// The neighbor is no longer known to be reachable, but no attempt is
+    /// made to check it until traffic is sent to it.
+    STALE      = 2,
+    /// The neighbor is no longer known to be reachable, and traffic was
+    /// recently sent to it. Probing is delayed to give upper layers a chance
+    /// to confirm its reachability.
+    DELAY      = 3,
+    /// The neighbor is no longer known to be reachable, and neighbor
+    /// solicitations are sent to check it.
+    PROBE      = 4,
+}}
+
+/// An entry of the neighbor cache (`EFI_IP6_NEIGHBOR_CACHE`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct NeighborCache {
+    /// Address of the neighbor.
+    pub neighbor: Ipv6Address,
+    /// Link-layer address of the neighbor.
+    pub link_address: MacAddress,
+    /// Reachability of the neighbor.
+    pub state: NeighborState,
+}
+
+/// A manually configured address (`EFI_IP6_CONFIG_MANUAL_ADDRESS`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct ManualAddress {
+    /// Address of the interface.
+    pub address: Ipv6Address,
+    /// Whether the address is an anycast address, which is not checked for
+    /// duplicates.
+    pub is_anycast: bool,
+    /// Length of the prefix of the address.
+    pub prefix_length: u8,
+}
+
+/// An address of the interface (`EFI_IP6_ADDRESS_INFO`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct AddressInfo {
+    /// The address.
+    pub address: Ipv6Address,
+    /// Length of the prefix of the address.
+    pub prefix_length: u8,
+}
+
+/// An entry of the routing table (`EFI_IP6_ROUTE_TABLE`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct RouteEntry {
+    /// Gateway through which the destination is reached, or zero if it is
+    /// directly reachable.
+    pub gateway: Ipv6Address,
+    /// Address of the destination prefix.
+    pub destination: Ipv6Address,
+    /// Length of the destination prefix.
+    pub prefix_length: u8,
+}
+
+/// Size of the name of an interface, including the null terminator.
+const INTERFACE_INFO_NAME_SIZE: usize = 32;
+
+/// Current state of a network interface (`EFI_IP6_CONFIG_INTERFACE_INFO`).
+///
+/// This is stored in a buffer along with the addresses and the routing table
+/// of the interface.
+#[repr(C)]
+pub struct InterfaceInfo {
+    name: [u16; INTERFACE_INFO_NAME_SIZE],
+    if_type: u8,
+    hw_address_size: u32,
+    hw_address: MacAddress,
+    address_info_count: u32,
+    address_info: *const AddressInfo,
+    route_count: u32,
+    route_table: *const RouteEntry,
+}
+
+interface_info_accessors!(InterfaceInfo);
+
+impl InterfaceInfo {
+    /// Addresses assigned to the interface, which have passed the duplicate
+    /// address detection.
+    pub fn addresses(&self) -> &[AddressInfo] {
+        if self.address_info.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.address_info, self.address_info_count as usize) }
+    }
+
+    /// Routing table of the interface.
+    pub fn route_table(&self) -> &[RouteEntry] {
+        if self.route_table.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.route_table, self.route_count as usize) }
+    }
+}
+
+impl Ip6Config {
+    /// Returns the identifier from which the link-local address is derived.
+    pub fn alt_interface_id(&self) -> Result<InterfaceId> {
+        self.get_fixed(DataType::ALT_INTERFACE_ID)
+    }
+
+    /// Sets the identifier from which the link-local address is derived.
+    ///
+    /// This restarts the configuration of the addresses of the interface.
+    pub fn set_alt_interface_id(&mut self, id: &InterfaceId) -> Result {
+        self.set(DataType::ALT_INTERFACE_ID, slice::from_ref(id))
+    }
+
+    /// Returns the number of neighbor solicitations sent to check that an
+    /// address is not used by another node.
+    pub fn dup_addr_detect_transmits(&self) -> Result<DupAddrDetectTransmits> {
+        self.get_fixed(DataType::DUP_ADDR_DETECT_TRANSMITS)
+    }
+
+    /// Sets the number of neighbor solicitations sent to check that an
+    /// address is not used by another node.
+    pub fn set_dup_addr_detect_transmits(&mut self, transmits: &DupAddrDetectTransmits) -> Result {
+        self.set(
+            DataType::DUP_ADDR_DETECT_TRANSMITS,
+            slice::from_ref(transmits),
+        )
+    }
+
+    /// Reads the manually set addresses into `buffer`, and returns them.
+    ///
+    /// If the buffer is too small, a `BUFFER_TOO_SMALL` error is returned,
+    /// along with the required number of addresses. `NOT_FOUND` is returned
+    /// if no address was set.
+    pub fn manual_addresses<'buf>(
+        &self,
+        buffer: &'buf mut [ManualAddress],
+    ) -> Result<&'buf [ManualAddress], Option<usize>> {
+        self.get_array(DataType::MANUAL_ADDRESS, buffer)
+    }
+
+    /// Sets the addresses of the interface, or clears them if `addresses` is
+    /// empty. This is only allowed with the manual policy, and link-local
+    /// addresses are rejected.
+    ///
+    /// The addresses are only assigned once the duplicate address detection
+    /// succeeds, in which case `NOT_READY` is returned, and the interface
+    /// info is updated once it is done. Addresses which are found to be
+    /// duplicates are not assigned.
+    pub fn set_manual_addresses(&mut self, addresses: &[ManualAddress]) -> Result {
+        self.set(DataType::MANUAL_ADDRESS, addresses)
+    }
+}
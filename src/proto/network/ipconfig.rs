@@ -0,0 +1,316 @@
+//! IP configuration protocols, for IPv4 or IPv6.
+//!
+//! These protocols are installed on the handle of a network interface, and
+//! manage its configuration: whether the address is set manually or obtained
+//! automatically, the gateways, and the DNS servers.
+//!
+//! The protocol is generic over the IP version, which selects its GUID and
+//! the types of its data. The `ip4config2` and `ip6config` modules provide
+//! the definitions specific to each version.
+
+use super::IpVersion;
+use crate::data_types::Align;
+use crate::proto::Protocol;
+use crate::table::boot::{BootServices, EventType, TimerTrigger, Tpl};
+use crate::{Event, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+use core::mem::{self, MaybeUninit};
+use core::{ptr, slice};
+
+/// A version of IP whose configuration is managed by the `IpConfig`
+/// protocol.
+pub trait IpConfigVersion: IpVersion {
+    /// Kind of configuration data.
+    type DataType: Copy;
+    /// How the interface obtains its configuration.
+    type Policy: Copy;
+    /// Current state of the interface.
+    type InterfaceInfo: Align;
+    /// Data type of the interface info.
+    const INTERFACE_INFO: Self::DataType;
+    /// Data type of the policy.
+    const POLICY: Self::DataType;
+    /// Data type of the gateway addresses.
+    const GATEWAY: Self::DataType;
+    /// Data type of the DNS server addresses.
+    const DNS_SERVER: Self::DataType;
+    /// GUID of the protocol.
+    const GUID: Guid;
+
+    /// Returns an address assigned to the interface, if there is one.
+    fn assigned_address(info: &Self::InterfaceInfo) -> Option<Self::Address>;
+}
+
+/// Implements the accessors shared by the interface info of all the
+/// versions, which start with the same fields.
+macro_rules! interface_info_accessors {
+    ($info:ident) => {
+        impl $info {
+            /// Name of the interface, such as `eth0`.
+            ///
+            /// Returns `None` if the firmware did not terminate the name.
+            pub fn name(&self) -> Option<&$crate::CStr16> {
+                let len = self.name.iter().position(|&c| c == 0)?;
+                $crate::CStr16::from_u16_with_nul(&self.name[..=len]).ok()
+            }
+
+            /// Type of the interface, as defined by the ARP hardware types of
+            /// RFC 1700, such as 1 for Ethernet.
+            pub fn if_type(&self) -> u8 {
+                self.if_type
+            }
+
+            /// Hardware address of the interface.
+            pub fn hw_address(&self) -> &[u8] {
+                let len = (self.hw_address_size as usize).min(self.hw_address.0.len());
+                &self.hw_address.0[..len]
+            }
+        }
+
+        impl $crate::data_types::Align for $info {
+            fn alignment() -> usize {
+                core::mem::align_of::<Self>()
+            }
+        }
+    };
+}
+
+pub(super) use interface_info_accessors;
+
+/// The IPv4 Configuration II and IPv6 Configuration protocols
+#[repr(C)]
+#[derive(Protocol)]
+pub struct IpConfig<V: IpConfigVersion> {
+    set_data: unsafe extern "efiapi" fn(
+        this: &Self,
+        data_type: V::DataType,
+        data_size: usize,
+        data: *const c_void,
+    ) -> Status,
+    get_data: unsafe extern "efiapi" fn(
+        this: &Self,
+        data_type: V::DataType,
+        data_size: &mut usize,
+        data: *mut c_void,
+    ) -> Status,
+    register_data_notify:
+        unsafe extern "efiapi" fn(this: &Self, data_type: V::DataType, event: Event) -> Status,
+    unregister_data_notify:
+        unsafe extern "efiapi" fn(this: &Self, data_type: V::DataType, event: Event) -> Status,
+}
+
+unsafe impl<V: IpConfigVersion> Identify for IpConfig<V> {
+    const GUID: Guid = V::GUID;
+}
+
+impl<V: IpConfigVersion> IpConfig<V> {
+    /// Reads the current state of the interface into `buffer`.
+    ///
+    /// If the buffer is too small to hold the state, its addresses and its
+    /// routing table, a `BUFFER_TOO_SMALL` error is returned, along with the
+    /// required size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is not suitably aligned for an `InterfaceInfo`.
+    pub fn interface_info<'buf>(
+        &self,
+        buffer: &'buf mut [u8],
+    ) -> Result<&'buf V::InterfaceInfo, Option<usize>> {
+        V::InterfaceInfo::assert_aligned(buffer);
+        let mut size = buffer.len();
+        let status = if size < mem::size_of::<V::InterfaceInfo>() {
+            // Let the firmware report the required size.
+            size = 0;
+            unsafe { (self.get_data)(self, V::INTERFACE_INFO, &mut size, ptr::null_mut()) }
+        } else {
+            unsafe {
+                (self.get_data)(
+                    self,
+                    V::INTERFACE_INFO,
+                    &mut size,
+                    buffer.as_mut_ptr().cast(),
+                )
+            }
+        };
+        let buffer = &*buffer;
+        status.into_with(
+            move || unsafe { &*buffer.as_ptr().cast::<V::InterfaceInfo>() },
+            |status| {
+                if status == Status::BUFFER_TOO_SMALL {
+                    Some(size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Returns how the interface obtains its configuration.
+    pub fn policy(&self) -> Result<V::Policy> {
+        self.get_fixed(V::POLICY)
+    }
+
+    /// Sets how the interface obtains its configuration.
+    ///
+    /// Changing the policy clears the manual addresses, the gateways and the
+    /// DNS servers. With the automatic policies, the address is obtained in
+    /// the background, which `wait_for_address` can wait for.
+    pub fn set_policy(&mut self, policy: V::Policy) -> Result {
+        self.set(V::POLICY, slice::from_ref(&policy))
+    }
+
+    /// Reads the gateway addresses into `buffer`, and returns them.
+    ///
+    /// If the buffer is too small, a `BUFFER_TOO_SMALL` error is returned,
+    /// along with the required number of addresses. `NOT_FOUND` is returned
+    /// if no gateway is set.
+    pub fn gateways<'buf>(
+        &self,
+        buffer: &'buf mut [V::Address],
+    ) -> Result<&'buf [V::Address], Option<usize>> {
+        self.get_array(V::GATEWAY, buffer)
+    }
+
+    /// Sets the gateway addresses, or clears them if `gateways` is empty.
+    /// This is only allowed with the static IPv4 and manual IPv6 policies.
+    pub fn set_gateways(&mut self, gateways: &[V::Address]) -> Result {
+        self.set(V::GATEWAY, gateways)
+    }
+
+    /// Reads the DNS server addresses into `buffer`, and returns them.
+    ///
+    /// If the buffer is too small, a `BUFFER_TOO_SMALL` error is returned,
+    /// along with the required number of addresses. `NOT_FOUND` is returned
+    /// if no DNS server is set.
+    pub fn dns_servers<'buf>(
+        &self,
+        buffer: &'buf mut [V::Address],
+    ) -> Result<&'buf [V::Address], Option<usize>> {
+        self.get_array(V::DNS_SERVER, buffer)
+    }
+
+    /// Sets the DNS server addresses, or clears them if `servers` is empty.
+    /// This is only allowed with the static IPv4 and manual IPv6 policies.
+    pub fn set_dns_servers(&mut self, servers: &[V::Address]) -> Result {
+        self.set(V::DNS_SERVER, servers)
+    }
+
+    /// Registers an event which is signaled when some kind of data changes.
+    pub fn register_data_notify(&mut self, data_type: V::DataType, event: &Event) -> Result {
+        unsafe { (self.register_data_notify)(self, data_type, event.unsafe_clone()) }.into()
+    }
+
+    /// Unregisters an event registered with `register_data_notify`.
+    pub fn unregister_data_notify(&mut self, data_type: V::DataType, event: &Event) -> Result {
+        unsafe { (self.unregister_data_notify)(self, data_type, event.unsafe_clone()) }.into()
+    }
+
+    /// Waits until the interface has been assigned an address, and returns
+    /// it.
+    ///
+    /// This is mostly useful after switching to an automatic policy, or
+    /// after setting a manual IPv6 address, which is only assigned once the
+    /// duplicate address detection succeeds. If no address is assigned
+    /// within `timeout` microseconds, `TIMEOUT` is returned.
+    pub fn wait_for_address(&mut self, bt: &BootServices, timeout: u64) -> Result<V::Address> {
+        let notify = unsafe { bt.create_event(EventType::empty(), Tpl::CALLBACK, None) }?.log();
+        let timer = match unsafe { bt.create_event(EventType::TIMER, Tpl::CALLBACK, None) } {
+            Ok(timer) => timer.log(),
+            Err(err) => {
+                let _ = bt.close_event(notify);
+                return Err(err);
+            }
+        };
+        let result = self.wait_for_address_with(bt, &notify, &timer, timeout);
+        // Closing the events can only fail if they are invalid, which is not
+        // the case here.
+        let _ = bt.close_event(timer);
+        let _ = bt.close_event(notify);
+        result
+    }
+
+    fn wait_for_address_with(
+        &mut self,
+        bt: &BootServices,
+        notify: &Event,
+        timer: &Event,
+        timeout: u64,
+    ) -> Result<V::Address> {
+        self.register_data_notify(V::INTERFACE_INFO, notify)?.log();
+        let result = (|| {
+            bt.set_timer(timer, TimerTrigger::Relative(timeout.saturating_mul(10)))?
+                .log();
+            let events = unsafe { [notify.unsafe_clone(), timer.unsafe_clone()] };
+            loop {
+                // Room for the interface info and a reasonable routing table.
+                let mut buffer = MaybeUninit::<[u64; 64]>::uninit();
+                let buffer = unsafe {
+                    slice::from_raw_parts_mut(
+                        buffer.as_mut_ptr().cast::<u8>(),
+                        mem::size_of::<[u64; 64]>(),
+                    )
+                };
+                let info = self
+                    .interface_info(buffer)
+                    .map_err(|err| err.status())?
+                    .log();
+                if let Some(address) = V::assigned_address(info) {
+                    return Ok(address.into());
+                }
+                let index = bt
+                    .wait_for_event(&events)
+                    .map_err(|err| err.status())?
+                    .log();
+                if index == 1 {
+                    return Err(Status::TIMEOUT.into());
+                }
+            }
+        })();
+        let _ = self.unregister_data_notify(V::INTERFACE_INFO, notify);
+        result
+    }
+
+    /// Reads fixed-size data, which must be plain data for which all zeroes
+    /// is a valid value.
+    pub(super) fn get_fixed<T>(&self, data_type: V::DataType) -> Result<T> {
+        let mut data = MaybeUninit::<T>::zeroed();
+        let mut size = mem::size_of::<T>();
+        unsafe { (self.get_data)(self, data_type, &mut size, data.as_mut_ptr().cast()) }
+            .into_with_val(|| unsafe { data.assume_init() })
+    }
+
+    /// Reads an array of data, such as a list of addresses.
+    pub(super) fn get_array<'buf, T>(
+        &self,
+        data_type: V::DataType,
+        buffer: &'buf mut [T],
+    ) -> Result<&'buf [T], Option<usize>> {
+        let element_size = mem::size_of::<T>();
+        let mut size = mem::size_of_val(buffer);
+        let status =
+            unsafe { (self.get_data)(self, data_type, &mut size, buffer.as_mut_ptr().cast()) };
+        let buffer = &*buffer;
+        status.into_with(
+            move || &buffer[..size / element_size],
+            |status| {
+                if status == Status::BUFFER_TOO_SMALL {
+                    Some(size / element_size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Writes an array of data, which is cleared if it is empty.
+    pub(super) fn set<T>(&mut self, data_type: V::DataType, data: &[T]) -> Result {
+        let size = mem::size_of_val(data);
+        let data = if data.is_empty() {
+            ptr::null()
+        } else {
+            data.as_ptr().cast()
+        };
+        unsafe { (self.set_data)(self, data_type, size, data) }.into()
+    }
+}
@@ -0,0 +1,429 @@
+//! Managed Network Protocol
+//!
+//! This protocol shares a network interface between several consumers, each
+//! of which gets its own instance of the protocol. Instances are obtained by
+//! creating a child handle through the `ManagedNetworkServiceBinding`
+//! protocol, which is installed on the handle of the network interface.
+
+use super::snp::NetworkMode;
+pub use super::Fragment;
+use crate::data_types::{IpAddress, MacAddress};
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::table::runtime::Time;
+use crate::{Completion, Event, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::{ptr, slice};
+
+/// Service binding protocol used to create `ManagedNetwork` instances.
+pub type ManagedNetworkServiceBinding = ServiceBinding<ManagedNetwork>;
+
+unsafe impl ChildProtocol for ManagedNetwork {
+    const SERVICE_BINDING_GUID: Guid = guid!("f36ff770-a7e1-42cf-9ed2-56f0f271f44c");
+}
+
+/// Configuration of a `ManagedNetwork` instance.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct ConfigData {
+    /// Time after which received packets which were not collected are
+    /// dropped, in microseconds. Zero means that they are never dropped.
+    pub received_queue_timeout: u32,
+    /// Time after which packets which could not be transmitted are dropped,
+    /// in microseconds. Zero means that they are never dropped.
+    pub transmit_queue_timeout: u32,
+    /// Protocol type of the packets to receive, such as 0x0800 for IPv4.
+    /// Zero means that packets of all types are received.
+    pub protocol_type_filter: u16,
+    /// Receive packets sent to the address of the interface.
+    pub enable_unicast_receive: bool,
+    /// Receive packets sent to the multicast groups which were joined.
+    pub enable_multicast_receive: bool,
+    /// Receive packets sent to the broadcast address.
+    pub enable_broadcast_receive: bool,
+    /// Receive all packets, whatever their destination.
+    pub enable_promiscuous_receive: bool,
+    /// Drop the queued packets when the instance is reset.
+    pub flush_queues_on_reset: bool,
+    /// Record when packets are received.
+    pub enable_receive_timestamps: bool,
+    /// Do not poll the interface in the background. `ManagedNetwork::poll`
+    /// must then be called to make progress.
+    pub disable_background_polling: bool,
+}
+
+/// Maximum number of fragments in a `TransmitData`.
+pub const MAX_FRAGMENTS: usize = 16;
+
+/// A packet to transmit (`EFI_MANAGED_NETWORK_TRANSMIT_DATA`).
+///
+/// The packet is made of up to `MAX_FRAGMENTS` fragments, which are sent
+/// back to back. This allows sending headers and payloads stored in
+/// different buffers without copying them.
+#[derive(Debug)]
+#[repr(C)]
+pub struct TransmitData<'a> {
+    dest_addr: *const MacAddress,
+    src_addr: *const MacAddress,
+    protocol_type: u16,
+    data_length: u32,
+    header_length: u16,
+    fragment_count: u16,
+    fragments: [Fragment<'a>; MAX_FRAGMENTS],
+    _addrs: PhantomData<&'a MacAddress>,
+}
+
+impl<'a> TransmitData<'a> {
+    /// Describes a packet whose media header is built by the protocol.
+    ///
+    /// The source address defaults to the address of the interface. `None`
+    /// is returned if there are no fragments, too many of them, or if the
+    /// packet is too large.
+    pub fn new(
+        dest_addr: &'a MacAddress,
+        src_addr: Option<&'a MacAddress>,
+        protocol_type: u16,
+        fragments: &[Fragment<'a>],
+    ) -> Option<Self> {
+        let mut data = Self::with_header(0, fragments)?;
+        data.dest_addr = dest_addr;
+        data.src_addr = src_addr.map_or(ptr::null(), |addr| addr as *const _);
+        data.protocol_type = protocol_type;
+        Some(data)
+    }
+
+    /// Describes a packet whose first `header_length` bytes are its media
+    /// header, which is sent as is.
+    ///
+    /// `None` is returned if there are no fragments, too many of them, or
+    /// if the packet is too large.
+    pub fn with_header(header_length: u16, fragments: &[Fragment<'a>]) -> Option<Self> {
+        if fragments.is_empty() || fragments.len() > MAX_FRAGMENTS {
+            return None;
+        }
+        let total = fragments
+            .iter()
+            .try_fold(0u32, |total, fragment| total.checked_add(fragment.len))?;
+        let data_length = total.checked_sub(u32::from(header_length))?;
+
+        let mut table = [Fragment::EMPTY; MAX_FRAGMENTS];
+        table[..fragments.len()].copy_from_slice(fragments);
+        Some(Self {
+            dest_addr: ptr::null(),
+            src_addr: ptr::null(),
+            protocol_type: 0,
+            data_length,
+            header_length,
+            fragment_count: fragments.len() as u16,
+            fragments: table,
+            _addrs: PhantomData,
+        })
+    }
+}
+
+/// The `EFI_MANAGED_NETWORK_RECEIVE_DATA` structure.
+#[repr(C)]
+struct ReceiveData {
+    timestamp: Time,
+    recycle_event: Event,
+    packet_length: u32,
+    header_length: u32,
+    address_length: u32,
+    data_length: u32,
+    broadcast: bool,
+    multicast: bool,
+    promiscuous: bool,
+    protocol_type: u16,
+    dest_addr: *const u8,
+    src_addr: *const u8,
+    media_header: *const u8,
+    packet_data: *const u8,
+}
+
+/// A packet received by `ManagedNetwork::receive`.
+///
+/// The packet is stored in a buffer owned by the protocol, which must be
+/// given back with `recycle` once the packet has been processed. Dropping
+/// the packet without recycling it leaks the buffer.
+pub struct ReceivedPacket<'a> {
+    data: &'a ReceiveData,
+}
+
+impl<'a> ReceivedPacket<'a> {
+    /// Time at which the packet was received, if timestamps were enabled.
+    pub fn timestamp(&self) -> &Time {
+        &self.data.timestamp
+    }
+
+    /// Size of the packet, including the media header.
+    pub fn len(&self) -> usize {
+        self.data.packet_length as usize
+    }
+
+    /// Whether the packet is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.packet_length == 0
+    }
+
+    /// The media header of the packet.
+    pub fn header(&self) -> &'a [u8] {
+        unsafe { slice::from_raw_parts(self.data.media_header, self.data.header_length as usize) }
+    }
+
+    /// The data of the packet, following the media header.
+    pub fn data(&self) -> &'a [u8] {
+        unsafe { slice::from_raw_parts(self.data.packet_data, self.data.data_length as usize) }
+    }
+
+    /// Destination address of the packet.
+    pub fn dest_addr(&self) -> &'a [u8] {
+        unsafe { slice::from_raw_parts(self.data.dest_addr, self.data.address_length as usize) }
+    }
+
+    /// Source address of the packet.
+    pub fn src_addr(&self) -> &'a [u8] {
+        unsafe { slice::from_raw_parts(self.data.src_addr, self.data.address_length as usize) }
+    }
+
+    /// Protocol type of the packet.
+    pub fn protocol_type(&self) -> u16 {
+        self.data.protocol_type
+    }
+
+    /// Whether the packet was sent to the broadcast address.
+    pub fn is_broadcast(&self) -> bool {
+        self.data.broadcast
+    }
+
+    /// Whether the packet was sent to a multicast address.
+    pub fn is_multicast(&self) -> bool {
+        self.data.multicast
+    }
+
+    /// Whether the packet was only received because of promiscuous mode.
+    pub fn is_promiscuous(&self) -> bool {
+        self.data.promiscuous
+    }
+
+    /// Gives the buffer of the packet back to the protocol.
+    pub fn recycle(self, bt: &BootServices) -> Result {
+        bt.signal_event(&self.data.recycle_event)
+    }
+}
+
+/// A completion token, used to track an asynchronous transmission or
+/// reception (`EFI_MANAGED_NETWORK_COMPLETION_TOKEN`).
+#[repr(C)]
+pub struct CompletionToken {
+    event: Event,
+    status: Status,
+    packet: *mut c_void,
+}
+
+impl CompletionToken {
+    /// Creates a token which signals `event` on completion.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(event: &Event) -> Self {
+        Self {
+            event: event.unsafe_clone(),
+            status: Status::NOT_READY,
+            packet: ptr::null_mut(),
+        }
+    }
+
+    /// Status of the operation, which is `NOT_READY` until it completes.
+    pub fn status(&self) -> Status {
+        // The protocol updates the status behind our back.
+        unsafe { ptr::read_volatile(&self.status) }
+    }
+
+    /// Whether the operation has completed.
+    pub fn is_complete(&self) -> bool {
+        self.status() != Status::NOT_READY
+    }
+
+    /// The received packet, once a reception has completed successfully.
+    pub fn received_packet(&self) -> Option<ReceivedPacket<'_>> {
+        if self.status() != Status::SUCCESS {
+            return None;
+        }
+        let data = unsafe { (self.packet as *const ReceiveData).as_ref()? };
+        Some(ReceivedPacket { data })
+    }
+}
+
+/// The Managed Network Protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct ManagedNetwork {
+    get_mode_data: unsafe extern "efiapi" fn(
+        this: &ManagedNetwork,
+        config: *mut ConfigData,
+        snp_mode: *mut NetworkMode,
+    ) -> Status,
+    configure:
+        unsafe extern "efiapi" fn(this: &ManagedNetwork, config: *const ConfigData) -> Status,
+    mcast_ip_to_mac: extern "efiapi" fn(
+        this: &ManagedNetwork,
+        ipv6: bool,
+        ip: &IpAddress,
+        mac: &mut MacAddress,
+    ) -> Status,
+    groups: unsafe extern "efiapi" fn(
+        this: &ManagedNetwork,
+        join: bool,
+        mac: *const MacAddress,
+    ) -> Status,
+    transmit:
+        unsafe extern "efiapi" fn(this: &ManagedNetwork, token: *mut CompletionToken) -> Status,
+    receive:
+        unsafe extern "efiapi" fn(this: &ManagedNetwork, token: *mut CompletionToken) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: &ManagedNetwork, token: *mut CompletionToken) -> Status,
+    poll: extern "efiapi" fn(this: &ManagedNetwork) -> Status,
+}
+
+unsafe impl Identify for ManagedNetwork {
+    const GUID: Guid = guid!("7ab33a91-ace5-4326-b572-e7ee33d39f16");
+}
+
+impl ManagedNetwork {
+    /// Returns the configuration of this instance, and the state of the
+    /// underlying network interface.
+    ///
+    /// `NOT_STARTED` is returned if the instance has not been configured.
+    pub fn get_mode_data(&self) -> Result<(ConfigData, NetworkMode)> {
+        let mut config = ConfigData::default();
+        let mut mode = MaybeUninit::<NetworkMode>::zeroed();
+        unsafe { (self.get_mode_data)(self, &mut config, mode.as_mut_ptr()) }
+            .into_with_val(|| (config, unsafe { mode.assume_init() }))
+    }
+
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// Resetting the instance cancels all the pending operations, and leaves
+    /// all the multicast groups.
+    pub fn configure(&mut self, config: Option<&ConfigData>) -> Result {
+        let config = config.map_or(ptr::null(), |config| config as *const _);
+        unsafe { (self.configure)(self, config) }.into()
+    }
+
+    /// Converts a multicast IP address to a multicast MAC address.
+    pub fn mcast_ip_to_mac(&self, ipv6: bool, ip: &IpAddress) -> Result<MacAddress> {
+        let mut mac = MacAddress::default();
+        (self.mcast_ip_to_mac)(self, ipv6, ip, &mut mac).into_with_val(|| mac)
+    }
+
+    /// Joins a multicast group.
+    pub fn join_group(&mut self, mac: &MacAddress) -> Result {
+        unsafe { (self.groups)(self, true, mac) }.into()
+    }
+
+    /// Leaves a multicast group, or all of them if `mac` is `None`.
+    pub fn leave_group(&mut self, mac: Option<&MacAddress>) -> Result {
+        let mac = mac.map_or(ptr::null(), |mac| mac as *const _);
+        unsafe { (self.groups)(self, false, mac) }.into()
+    }
+
+    /// Queues a packet for transmission. Completion is reported through the
+    /// token.
+    ///
+    /// # Safety
+    ///
+    /// The token and the packet, including the buffers of its fragments,
+    /// must not be moved, modified or freed until the token has completed or
+    /// has been cancelled.
+    pub unsafe fn transmit_async(
+        &mut self,
+        token: &mut CompletionToken,
+        data: &TransmitData,
+    ) -> Result {
+        token.packet = data as *const TransmitData as *mut c_void;
+        (self.transmit)(self, token).into()
+    }
+
+    /// Queues a request to receive a packet. Completion is reported through
+    /// the token, which then holds the packet.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved, modified or freed until it has completed,
+    /// or has been cancelled.
+    pub unsafe fn receive_async(&mut self, token: &mut CompletionToken) -> Result {
+        token.packet = ptr::null_mut();
+        (self.receive)(self, token).into()
+    }
+
+    /// Cancels a pending operation, or all of them if `token` is `None`.
+    ///
+    /// The tokens of the cancelled operations complete with `ABORTED`.
+    pub fn cancel(&mut self, token: Option<&mut CompletionToken>) -> Result {
+        let token = token.map_or(ptr::null_mut(), |token| token as *mut _);
+        unsafe { (self.cancel)(self, token) }.into()
+    }
+
+    /// Polls the network interface for received packets and finished
+    /// transmissions.
+    ///
+    /// This returns `NOT_READY` if no packet was received, and `TIMEOUT`
+    /// if the interface did not respond.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+
+    /// Transmits a packet, polling the interface until it has been sent.
+    ///
+    /// `event` is signaled on completion, and must be an event which is not
+    /// used for anything else, such as one created by
+    /// `BootServices::create_event` without notification function.
+    pub fn transmit(&mut self, event: &Event, data: &TransmitData) -> Result {
+        let mut token = unsafe { CompletionToken::new(event) };
+        unsafe { self.transmit_async(&mut token, data) }?.log();
+        self.wait(&mut token, None)
+    }
+
+    /// Receives a packet, polling the interface at most `max_polls` times.
+    ///
+    /// Returns `None` if no packet was received in time. The token must have
+    /// been created with an event which is not used for anything else. The
+    /// packet borrows the token, which cannot be reused until the packet has
+    /// been recycled.
+    pub fn receive<'token>(
+        &mut self,
+        token: &'token mut CompletionToken,
+        max_polls: usize,
+    ) -> Result<Option<ReceivedPacket<'token>>> {
+        unsafe { self.receive_async(token) }?.log();
+        match self.wait(token, Some(max_polls)) {
+            Ok(completion) => {
+                let (status, ()) = completion.split();
+                Ok(Completion::new(status, token.received_packet()))
+            }
+            Err(err) if err.status() == Status::TIMEOUT => Ok(None.into()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Polls the interface until the token completes, and returns its status.
+    ///
+    /// If `max_polls` is reached, the operation is cancelled and `TIMEOUT`
+    /// is returned.
+    fn wait(&mut self, token: &mut CompletionToken, max_polls: Option<usize>) -> Result {
+        let mut polls = 0;
+        while !token.is_complete() {
+            if matches!(max_polls, Some(max) if polls >= max) {
+                self.cancel(Some(token))?.log();
+                return Err(Status::TIMEOUT.into());
+            }
+            // Polling fails when there is nothing to do, which can be ignored.
+            let _ = self.poll();
+            polls += 1;
+        }
+        token.status().into()
+    }
+}
@@ -0,0 +1,141 @@
+//! Network access protocols.
+//!
+//! These protocols can be used to interact with network resources.
+
+use crate::data_types::{Ipv4Address, Ipv6Address};
+use crate::table::boot::{BootServices, EventType, Tpl};
+use crate::{Event, Result};
+use core::convert::TryInto;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::ptr;
+
+pub mod arp;
+pub mod dhcp4;
+pub mod dns4;
+pub mod http;
+pub mod ip4config2;
+pub mod ip6config;
+pub mod ipconfig;
+pub mod mnp;
+pub mod mtftp4;
+pub mod pxe;
+pub mod snp;
+pub mod tcp;
+pub mod tcp4;
+pub mod tcp6;
+pub mod tls;
+pub mod udp;
+pub mod udp4;
+pub mod udp6;
+pub mod vlan;
+
+/// Version of the Internet Protocol.
+///
+/// The protocols which exist for both IPv4 and IPv6, such as `tcp::Tcp`,
+/// are generic over it. This trait is sealed, and only implemented by `V4`
+/// and `V6`.
+pub trait IpVersion: private::Sealed {
+    /// Type of the addresses.
+    type Address: Copy + Debug + Default + Eq;
+}
+
+/// IPv4, as used by the `Tcp4`, `Udp4` and `Ip4Config2` protocols.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum V4 {}
+
+impl IpVersion for V4 {
+    type Address = Ipv4Address;
+}
+
+/// IPv6, as used by the `Tcp6`, `Udp6` and `Ip6Config` protocols.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum V6 {}
+
+impl IpVersion for V6 {
+    type Address = Ipv6Address;
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::V4 {}
+    impl Sealed for super::V6 {}
+}
+
+/// A fragment of data to transmit.
+///
+/// Packets can be made of several fragments, which are sent back to back.
+/// This allows sending headers and payloads stored in different buffers
+/// without copying them.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Fragment<'a> {
+    len: u32,
+    buffer: *const u8,
+    _data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Fragment<'a> {
+    /// Creates a fragment from some data, or returns `None` if it is too
+    /// large.
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        Some(Self {
+            len: data.len().try_into().ok()?,
+            buffer: data.as_ptr(),
+            _data: PhantomData,
+        })
+    }
+
+    /// An empty fragment.
+    const EMPTY: Fragment<'static> = Fragment {
+        len: 0,
+        buffer: ptr::null(),
+        _data: PhantomData,
+    };
+}
+
+/// A buffer in which received data is stored.
+///
+/// Like `Fragment`, this allows receiving data directly into several
+/// buffers.
+#[derive(Debug)]
+#[repr(C)]
+pub struct FragmentMut<'a> {
+    len: u32,
+    buffer: *mut u8,
+    _data: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> FragmentMut<'a> {
+    /// Creates a fragment from a buffer, or returns `None` if it is too
+    /// large.
+    pub fn new(buffer: &'a mut [u8]) -> Option<Self> {
+        Some(Self {
+            len: buffer.len().try_into().ok()?,
+            buffer: buffer.as_mut_ptr(),
+            _data: PhantomData,
+        })
+    }
+}
+
+impl Default for FragmentMut<'_> {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            buffer: ptr::null_mut(),
+            _data: PhantomData,
+        }
+    }
+}
+
+/// Runs `f` with an event for the tokens of blocking operations, which is
+/// closed afterwards.
+fn with_event<T>(bt: &BootServices, f: impl FnOnce(&Event) -> Result<T>) -> Result<T> {
+    let event = unsafe { bt.create_event(EventType::empty(), Tpl::CALLBACK, None) }?.log();
+    let result = f(&event);
+    // Closing the event can only fail if it is invalid, which is not the
+    // case here.
+    let _ = bt.close_event(event);
+    result
+}
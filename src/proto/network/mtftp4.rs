@@ -0,0 +1,762 @@
+//! TFTP over IPv4 protocol.
+//!
+//! This protocol downloads and uploads files with TFTP, and supports the
+//! multicast extension (MTFTP). Instances of the protocol are obtained by
+//! creating a child handle through the `Mtftp4ServiceBinding` protocol.
+//!
+//! All the transfers are performed synchronously, and return once the whole
+//! file has been transferred.
+
+#[cfg(feature = "exts")]
+use crate::alloc_api::{vec, vec::Vec};
+use crate::data_types::Ipv4Address;
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{CStr8, Char8, Completion, Error, Event, Guid, Identify, Result, Status};
+use core::convert::TryInto;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::{fmt, mem, ptr, slice, str};
+
+/// Service binding protocol used to create `Mtftp4` instances.
+pub type Mtftp4ServiceBinding = ServiceBinding<Mtftp4>;
+
+unsafe impl ChildProtocol for Mtftp4 {
+    const SERVICE_BINDING_GUID: Guid = guid!("2fe800be-8f01-4aa6-946b-d71388e1833f");
+}
+
+newtype_enum! {
+/// Operation code of a TFTP packet, as defined in RFC 1350 and RFC 2347.
+pub enum Opcode: u16 => {
+    /// Read request.
+    RRQ   = 1,
+    /// Write request.
+    WRQ   = 2,
+    /// Data block.
+    DATA  = 3,
+    /// Acknowledgement of a data block.
+    ACK   = 4,
+    /// Error.
+    ERROR = 5,
+    /// Acknowledgement of the options of a request.
+    OACK  = 6,
+    /// Directory request, an MTFTP extension.
+    DIR   = 7,
+    /// Data block with a 64-bit block number, an MTFTP extension.
+    DATA8 = 8,
+    /// Acknowledgement with 64-bit block numbers, an MTFTP extension.
+    ACK8  = 9,
+}}
+
+/// A TFTP packet (`EFI_MTFTP4_PACKET`).
+///
+/// The packet is in network byte order, and starts with its operation code.
+///
+/// ```
+/// use uefi::proto::network::mtftp4::{Opcode, Packet, TransferOptions};
+///
+/// let oack = b"\x00\x06tsize\x0020000\x00BLKSIZE\x001468\x00";
+/// let packet = Packet::from_bytes(oack).unwrap();
+/// assert_eq!(packet.opcode(), Opcode::OACK);
+/// assert_eq!(packet.options().count(), 2);
+///
+/// let options = TransferOptions::from_packet(packet);
+/// assert_eq!(options.tsize, Some(20000));
+/// assert_eq!(options.blksize, Some(1468));
+/// assert_eq!(options.timeout, None);
+///
+/// let data = Packet::from_bytes(b"\x00\x03\x00\x01hello").unwrap();
+/// assert_eq!(data.data(), Some((1, &b"hello"[..])));
+/// assert_eq!(TransferOptions::from_packet(data), TransferOptions::default());
+///
+/// let error = Packet::from_bytes(b"\x00\x05\x00\x01File not found\x00").unwrap();
+/// assert_eq!(error.error(), Some((1, &b"File not found"[..])));
+///
+/// assert!(Packet::from_bytes(b"\x00").is_none());
+/// ```
+#[repr(transparent)]
+pub struct Packet([u8]);
+
+impl Packet {
+    /// Interprets bytes as a packet, or returns `None` if they are too short
+    /// to hold an operation code.
+    pub fn from_bytes(bytes: &[u8]) -> Option<&Packet> {
+        if bytes.len() < 2 {
+            return None;
+        }
+        Some(unsafe { &*(bytes as *const [u8] as *const Packet) })
+    }
+
+    /// Returns the raw bytes of the packet.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the operation code of the packet.
+    pub fn opcode(&self) -> Opcode {
+        Opcode(self.be_u16(0))
+    }
+
+    fn be_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes(self.0[offset..offset + 2].try_into().unwrap())
+    }
+
+    /// Returns the block number and the payload of a data packet.
+    pub fn data(&self) -> Option<(u16, &[u8])> {
+        if self.opcode() != Opcode::DATA || self.0.len() < 4 {
+            return None;
+        }
+        Some((self.be_u16(2), &self.0[4..]))
+    }
+
+    /// Returns the error code and the message of an error packet.
+    pub fn error(&self) -> Option<(u16, &[u8])> {
+        if self.opcode() != Opcode::ERROR || self.0.len() < 4 {
+            return None;
+        }
+        let message = &self.0[4..];
+        let len = message
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(message.len());
+        Some((self.be_u16(2), &message[..len]))
+    }
+
+    /// Returns an iterator over the names and values of the options of an
+    /// option acknowledgement packet.
+    ///
+    /// The iterator is empty for other packets.
+    pub fn options(&self) -> PacketOptions<'_> {
+        let bytes = if self.opcode() == Opcode::OACK {
+            &self.0[2..]
+        } else {
+            &[]
+        };
+        PacketOptions { bytes }
+    }
+}
+
+impl fmt::Debug for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("opcode", &self.opcode())
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// Iterator over the options of an option acknowledgement `Packet`.
+#[derive(Clone, Debug)]
+pub struct PacketOptions<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PacketOptions<'a> {
+    fn next_string(&mut self) -> Option<&'a [u8]> {
+        let len = self.bytes.iter().position(|&b| b == 0)?;
+        let string = &self.bytes[..len];
+        self.bytes = &self.bytes[len + 1..];
+        Some(string)
+    }
+}
+
+impl<'a> Iterator for PacketOptions<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.next_string()?;
+        let value = self.next_string()?;
+        Some((name, value))
+    }
+}
+
+/// The standard options acknowledged by a server, as defined in RFC 2348
+/// and RFC 2349.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TransferOptions {
+    /// Size of the file, in bytes.
+    pub tsize: Option<u64>,
+    /// Size of the data blocks, in bytes.
+    pub blksize: Option<u16>,
+    /// Retransmission timeout, in seconds.
+    pub timeout: Option<u8>,
+}
+
+impl TransferOptions {
+    /// Parses the options of an option acknowledgement packet.
+    ///
+    /// Unknown and malformed options are ignored, and other packets have no
+    /// options.
+    pub fn from_packet(packet: &Packet) -> Self {
+        fn parse<T: str::FromStr>(value: &[u8]) -> Option<T> {
+            str::from_utf8(value).ok()?.parse().ok()
+        }
+
+        let mut options = Self::default();
+        for (name, value) in packet.options() {
+            // Option names are case insensitive.
+            if name.eq_ignore_ascii_case(b"tsize") {
+                options.tsize = parse(value);
+            } else if name.eq_ignore_ascii_case(b"blksize") {
+                options.blksize = parse(value);
+            } else if name.eq_ignore_ascii_case(b"timeout") {
+                options.timeout = parse(value);
+            }
+        }
+        options
+    }
+}
+
+/// An option of a request (`EFI_MTFTP4_OPTION`).
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct TftpOption<'a> {
+    name: *const Char8,
+    value: *const Char8,
+    _strings: PhantomData<&'a CStr8>,
+}
+
+impl<'a> TftpOption<'a> {
+    /// Creates an option with a name and a value.
+    pub fn new(name: &'a CStr8, value: &'a CStr8) -> Self {
+        Self {
+            name: name.as_ptr(),
+            value: value.as_ptr(),
+            _strings: PhantomData,
+        }
+    }
+
+    /// Creates a `tsize` option which asks the server for the size of the
+    /// file.
+    pub fn tsize_query() -> TftpOption<'static> {
+        unsafe {
+            TftpOption::new(
+                CStr8::from_bytes_with_nul_unchecked(b"tsize\0"),
+                CStr8::from_bytes_with_nul_unchecked(b"0\0"),
+            )
+        }
+    }
+
+    /// The name of the option.
+    pub fn name(&self) -> &'a CStr8 {
+        unsafe { CStr8::from_ptr(self.name) }
+    }
+
+    /// The value of the option.
+    pub fn value(&self) -> &'a CStr8 {
+        unsafe { CStr8::from_ptr(self.value) }
+    }
+}
+
+impl fmt::Debug for TftpOption<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TftpOption")
+            .field("name", &self.name().to_bytes())
+            .field("value", &self.value().to_bytes())
+            .finish()
+    }
+}
+
+/// Configuration of a `Mtftp4` instance (`EFI_MTFTP4_CONFIG_DATA`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct ConfigData {
+    /// Whether to use the default address of the interface, instead of
+    /// `station_ip` and `subnet_mask`.
+    pub use_default_setting: bool,
+    /// Address of this instance.
+    pub station_ip: Ipv4Address,
+    /// Subnet mask of `station_ip`.
+    pub subnet_mask: Ipv4Address,
+    /// Local port of the transfers. Zero picks an ephemeral port.
+    pub local_port: u16,
+    /// Gateway used to reach the server, or zero to use none.
+    pub gateway_ip: Ipv4Address,
+    /// Address of the server.
+    pub server_ip: Ipv4Address,
+    /// Port of the server to which the requests are sent.
+    pub initial_server_port: u16,
+    /// Number of times a packet is sent before giving up.
+    pub try_count: u16,
+    /// Time to wait for a reply, in seconds.
+    pub timeout_value: u16,
+}
+
+impl ConfigData {
+    /// Creates a configuration which uses the default address of the
+    /// interface to reach the TFTP server at `server_ip`.
+    pub fn new(server_ip: Ipv4Address) -> Self {
+        Self {
+            use_default_setting: true,
+            station_ip: Ipv4Address::UNSPECIFIED,
+            subnet_mask: Ipv4Address::UNSPECIFIED,
+            local_port: 0,
+            gateway_ip: Ipv4Address::UNSPECIFIED,
+            server_ip,
+            initial_server_port: 69,
+            try_count: 3,
+            timeout_value: 3,
+        }
+    }
+}
+
+/// Settings overriding the configuration for a single transfer
+/// (`EFI_MTFTP4_OVERRIDE_DATA`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct OverrideData {
+    /// Gateway used to reach the server, or zero to use none.
+    pub gateway_ip: Ipv4Address,
+    /// Address of the server.
+    pub server_ip: Ipv4Address,
+    /// Port of the server to which the request is sent.
+    pub server_port: u16,
+    /// Number of times a packet is sent before giving up.
+    pub try_count: u16,
+    /// Time to wait for a reply, in seconds.
+    pub timeout_value: u16,
+}
+
+/// Parameters of a transfer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransferParams<'a> {
+    /// Settings overriding the configuration, if any.
+    pub override_data: Option<&'a OverrideData>,
+    /// Options sent with the request.
+    pub options: &'a [TftpOption<'a>],
+}
+
+/// The `EFI_MTFTP4_MODE_DATA` structure.
+#[repr(C)]
+struct RawModeData {
+    config: ConfigData,
+    supported_option_count: u8,
+    supported_options: *const *const Char8,
+    unsupported_option_count: u8,
+    unsupported_options: *const *const Char8,
+}
+
+/// State of a `Mtftp4` instance.
+pub struct ModeData<'a> {
+    raw: RawModeData,
+    _protocol: PhantomData<&'a Mtftp4>,
+}
+
+impl ModeData<'_> {
+    /// The configuration of the instance.
+    pub fn config(&self) -> &ConfigData {
+        &self.raw.config
+    }
+
+    /// Names of the options supported by the instance.
+    pub fn supported_options(&self) -> impl Iterator<Item = &CStr8> {
+        unsafe { Self::names(self.raw.supported_options, self.raw.supported_option_count) }
+    }
+
+    /// Names of the options known but not supported by the instance.
+    pub fn unsupported_options(&self) -> impl Iterator<Item = &CStr8> {
+        unsafe {
+            Self::names(
+                self.raw.unsupported_options,
+                self.raw.unsupported_option_count,
+            )
+        }
+    }
+
+    unsafe fn names<'a>(names: *const *const Char8, count: u8) -> impl Iterator<Item = &'a CStr8> {
+        let names = if names.is_null() {
+            &[]
+        } else {
+            slice::from_raw_parts(names, count.into())
+        };
+        names.iter().map(|&name| CStr8::from_ptr(name))
+    }
+}
+
+impl fmt::Debug for ModeData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModeData")
+            .field("config", self.config())
+            .field("supported_option_count", &self.raw.supported_option_count)
+            .finish()
+    }
+}
+
+/// A packet allocated by the firmware, which is freed when dropped.
+pub struct PoolPacket<'a> {
+    packet: &'a Packet,
+    bt: &'a BootServices,
+}
+
+impl Deref for PoolPacket<'_> {
+    type Target = Packet;
+
+    fn deref(&self) -> &Packet {
+        self.packet
+    }
+}
+
+impl fmt::Debug for PoolPacket<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.packet.fmt(f)
+    }
+}
+
+impl Drop for PoolPacket<'_> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = self.bt.free_pool(self.packet.0.as_ptr() as *mut u8);
+    }
+}
+
+/// Options parsed by `Mtftp4::parse_options`, which are freed when dropped.
+pub struct ParsedOptions<'a> {
+    list: *mut TftpOption<'a>,
+    count: usize,
+    bt: &'a BootServices,
+}
+
+impl<'a> ParsedOptions<'a> {
+    /// The parsed options, which point into the packet.
+    pub fn as_slice(&self) -> &[TftpOption<'a>] {
+        if self.list.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.list, self.count) }
+        }
+    }
+}
+
+impl fmt::Debug for ParsedOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl Drop for ParsedOptions<'_> {
+    fn drop(&mut self) {
+        if !self.list.is_null() {
+            // Ignore the result, we can't do anything about an error here.
+            let _ = self.bt.free_pool(self.list.cast());
+        }
+    }
+}
+
+type CheckPacketFn = unsafe extern "efiapi" fn(
+    this: &Mtftp4,
+    token: &mut Token,
+    len: u16,
+    packet: *const u8,
+) -> Status;
+
+/// The `EFI_MTFTP4_TOKEN` structure.
+#[repr(C)]
+struct Token {
+    status: Status,
+    event: Option<Event>,
+    override_data: *const OverrideData,
+    filename: *const Char8,
+    mode_str: *const Char8,
+    option_count: u32,
+    option_list: *const TftpOption<'static>,
+    buffer_size: u64,
+    buffer: *mut c_void,
+    context: *mut c_void,
+    check_packet: Option<CheckPacketFn>,
+    timeout_callback: *const c_void,
+    packet_needed: *const c_void,
+}
+
+impl Token {
+    /// Creates a token for a synchronous transfer, in binary mode.
+    fn new(filename: &CStr8, params: &TransferParams) -> core::result::Result<Self, Status> {
+        Ok(Self {
+            status: Status::NOT_READY,
+            event: None,
+            override_data: params
+                .override_data
+                .map_or(ptr::null(), |data| data as *const _),
+            filename: filename.as_ptr(),
+            mode_str: ptr::null(),
+            option_count: params
+                .options
+                .len()
+                .try_into()
+                .map_err(|_| Status::INVALID_PARAMETER)?,
+            option_list: params.options.as_ptr().cast(),
+            buffer_size: 0,
+            buffer: ptr::null_mut(),
+            context: ptr::null_mut(),
+            check_packet: None,
+            timeout_callback: ptr::null(),
+            packet_needed: ptr::null(),
+        })
+    }
+
+    fn with_buffer(mut self, buffer: *mut u8, len: usize) -> Self {
+        self.buffer = buffer.cast();
+        self.buffer_size = len as u64;
+        self
+    }
+}
+
+/// The MTFTP4 protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Mtftp4 {
+    get_mode_data: unsafe extern "efiapi" fn(this: &Mtftp4, mode: *mut RawModeData) -> Status,
+    configure: unsafe extern "efiapi" fn(this: &Mtftp4, config: *const ConfigData) -> Status,
+    get_info: unsafe extern "efiapi" fn(
+        this: &Mtftp4,
+        override_data: *const OverrideData,
+        filename: *const Char8,
+        mode_str: *const Char8,
+        option_count: u8,
+        option_list: *const TftpOption,
+        packet_length: *mut u32,
+        packet: *mut *mut u8,
+    ) -> Status,
+    parse_options: unsafe extern "efiapi" fn(
+        this: &Mtftp4,
+        packet_len: u32,
+        packet: *const u8,
+        option_count: *mut u32,
+        option_list: *mut *mut TftpOption,
+    ) -> Status,
+    read_file: unsafe extern "efiapi" fn(this: &Mtftp4, token: *mut Token) -> Status,
+    write_file: unsafe extern "efiapi" fn(this: &Mtftp4, token: *mut Token) -> Status,
+    read_directory: unsafe extern "efiapi" fn(this: &Mtftp4, token: *mut Token) -> Status,
+    poll: extern "efiapi" fn(this: &Mtftp4) -> Status,
+}
+
+unsafe impl Identify for Mtftp4 {
+    const GUID: Guid = guid!("78247c57-63db-4708-99c2-a8b4a9a61f6b");
+}
+
+impl Mtftp4 {
+    /// Returns the configuration of this instance, and the options it
+    /// supports.
+    pub fn get_mode_data(&self) -> Result<ModeData<'_>> {
+        let mut raw = mem::MaybeUninit::<RawModeData>::zeroed();
+        unsafe { (self.get_mode_data)(self, raw.as_mut_ptr()) }.into_with_val(|| ModeData {
+            raw: unsafe { raw.assume_init() },
+            _protocol: PhantomData,
+        })
+    }
+
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// Resetting the instance aborts the transfer in progress.
+    pub fn configure(&mut self, config: Option<&ConfigData>) -> Result {
+        let config = config.map_or(ptr::null(), |config| config as *const _);
+        unsafe { (self.configure)(self, config) }.into()
+    }
+
+    /// Sends a read request for a file, and returns the first reply of the
+    /// server.
+    ///
+    /// This is usually used to learn the size of a file, by sending a
+    /// `TftpOption::tsize_query` and parsing the reply with
+    /// `TransferOptions::from_packet`. If the server replies with an error
+    /// packet, `TFTP_ERROR` is returned along with the packet.
+    pub fn get_info<'bt>(
+        &mut self,
+        bt: &'bt BootServices,
+        filename: &CStr8,
+        params: &TransferParams,
+    ) -> Result<PoolPacket<'bt>, Option<PoolPacket<'bt>>> {
+        let option_count = match params.options.len().try_into() {
+            Ok(count) => count,
+            Err(_) => return Err(Error::new(Status::INVALID_PARAMETER, None)),
+        };
+        let mut len = 0;
+        let mut packet = ptr::null_mut();
+        let status = unsafe {
+            (self.get_info)(
+                self,
+                params
+                    .override_data
+                    .map_or(ptr::null(), |data| data as *const _),
+                filename.as_ptr(),
+                ptr::null(),
+                option_count,
+                params.options.as_ptr(),
+                &mut len,
+                &mut packet,
+            )
+        };
+        let packet = if packet.is_null() {
+            None
+        } else {
+            let bytes = unsafe { slice::from_raw_parts(packet, len as usize) };
+            Some(PoolPacket {
+                packet: unsafe { &*(bytes as *const [u8] as *const Packet) },
+                bt,
+            })
+        };
+        if status.is_success() || status.is_warning() {
+            let packet = packet.expect("GetInfo succeeded without a packet");
+            Ok(Completion::new(status, packet))
+        } else {
+            Err(Error::new(status, packet))
+        }
+    }
+
+    /// Parses the options of an option acknowledgement packet with the
+    /// firmware.
+    ///
+    /// The options can also be parsed without the firmware with
+    /// `Packet::options`.
+    pub fn parse_options<'a>(
+        &self,
+        bt: &'a BootServices,
+        packet: &'a Packet,
+    ) -> Result<ParsedOptions<'a>> {
+        let mut count = 0;
+        let mut list = ptr::null_mut();
+        unsafe {
+            (self.parse_options)(
+                self,
+                packet.0.len() as u32,
+                packet.0.as_ptr(),
+                &mut count,
+                &mut list,
+            )
+        }
+        .into_with_val(|| ParsedOptions {
+            list: list.cast(),
+            count: count as usize,
+            bt,
+        })
+    }
+
+    /// Downloads a file into `buffer`, and returns its size.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned. The size
+    /// of the file can be learned beforehand with `get_info`.
+    pub fn read_file(
+        &mut self,
+        filename: &CStr8,
+        params: &TransferParams,
+        buffer: &mut [u8],
+    ) -> Result<usize, Option<usize>> {
+        let mut token = Token::new(filename, params)
+            .map_err(|status| Error::new(status, None))?
+            .with_buffer(buffer.as_mut_ptr(), buffer.len());
+        unsafe { (self.read_file)(self, &mut token) }
+            .into_with(|| token.buffer_size as usize, |_| None)
+    }
+
+    /// Downloads a file, and calls `callback` for each received packet.
+    ///
+    /// This does not require knowing the size of the file in advance. The
+    /// payload of the data packets is available through `Packet::data`.
+    /// Returning an error status from the callback aborts the transfer.
+    pub fn read_file_with<F>(
+        &mut self,
+        filename: &CStr8,
+        params: &TransferParams,
+        mut callback: F,
+    ) -> Result
+    where
+        F: FnMut(&Packet) -> Status,
+    {
+        // Use a trampoline to handle the impedance mismatch between Rust & C
+        unsafe extern "efiapi" fn check_packet<F: FnMut(&Packet) -> Status>(
+            _this: &Mtftp4,
+            token: &mut Token,
+            len: u16,
+            packet: *const u8,
+        ) -> Status {
+            let callback = &mut *token.context.cast::<F>();
+            match Packet::from_bytes(slice::from_raw_parts(packet, len.into())) {
+                Some(packet) => callback(packet), // SAFETY: Aborting panics are assumed here
+                None => Status::SUCCESS,
+            }
+        }
+
+        let mut token = Token::new(filename, params)?;
+        token.context = (&mut callback as *mut F).cast();
+        token.check_packet = Some(check_packet::<F>);
+        unsafe { (self.read_file)(self, &mut token) }.into()
+    }
+
+    /// Downloads a file into a vector.
+    ///
+    /// The vector is sized from the `tsize` option if the server supports
+    /// it, and grown as packets are received otherwise.
+    #[cfg(feature = "exts")]
+    pub fn read_file_to_vec(
+        &mut self,
+        bt: &BootServices,
+        filename: &CStr8,
+        params: &TransferParams,
+    ) -> Result<Vec<u8>> {
+        let mut options = Vec::from(params.options);
+        options.push(TftpOption::tsize_query());
+        let info_params = TransferParams {
+            override_data: params.override_data,
+            options: &options,
+        };
+        let info = self
+            .get_info(bt, filename, &info_params)
+            .map_err(|err| Error::from(err.status()))?
+            .log();
+        let size = TransferOptions::from_packet(&info).tsize;
+        drop(info);
+
+        if let Some(size) = size {
+            let size = size.try_into().map_err(|_| Status::OUT_OF_RESOURCES)?;
+            let mut buffer = vec![0; size];
+            let len = self
+                .read_file(filename, params, &mut buffer)
+                .map_err(|err| Error::from(err.status()))?
+                .log();
+            buffer.truncate(len);
+            Ok(buffer.into())
+        } else {
+            let mut buffer = Vec::new();
+            self.read_file_with(filename, params, |packet| {
+                if let Some((_, data)) = packet.data() {
+                    buffer.extend_from_slice(data);
+                }
+                Status::SUCCESS
+            })?
+            .log();
+            Ok(buffer.into())
+        }
+    }
+
+    /// Uploads `data` as a file.
+    pub fn write_file(&mut self, filename: &CStr8, params: &TransferParams, data: &[u8]) -> Result {
+        // The protocol only reads from the buffer of write requests.
+        let mut token =
+            Token::new(filename, params)?.with_buffer(data.as_ptr() as *mut u8, data.len());
+        unsafe { (self.write_file)(self, &mut token) }.into()
+    }
+
+    /// Downloads the listing of a directory into `buffer`, and returns its
+    /// size.
+    ///
+    /// The format of the listing depends on the server. If the buffer is too
+    /// small, `BUFFER_TOO_SMALL` is returned.
+    pub fn read_directory(
+        &mut self,
+        directory: &CStr8,
+        params: &TransferParams,
+        buffer: &mut [u8],
+    ) -> Result<usize, Option<usize>> {
+        let mut token = Token::new(directory, params)
+            .map_err(|status| Error::new(status, None))?
+            .with_buffer(buffer.as_mut_ptr(), buffer.len());
+        unsafe { (self.read_directory)(self, &mut token) }
+            .into_with(|| token.buffer_size as usize, |_| None)
+    }
+
+    /// Polls the network interface for received packets.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+}
@@ -0,0 +1,973 @@
+//! PXE base code protocol.
+//!
+//! This protocol gives access to the network stack used by the firmware to
+//! boot over the network. It exposes the packets exchanged with the DHCP and
+//! boot servers when the system was booted over the network, and can be used
+//! to perform the same exchanges again, or to download files with TFTP.
+
+use super::dhcp4::Message;
+use crate::data_types::{IpAddress, MacAddress};
+use crate::proto::Protocol;
+use crate::{CStr8, Char8, Guid, Identify, Result, Status};
+use bitflags::bitflags;
+use core::{fmt, mem, ptr};
+
+newtype_enum! {
+/// Type of a boot server, used by `BaseCode::discover`.
+pub enum BootstrapType: u16 => {
+    /// PXE bootstrap server.
+    BOOTSTRAP        = 0,
+    /// Microsoft Windows NT Boot Server.
+    MS_WINNT_RIS     = 1,
+    /// Intel LANDesk Configuration Manager.
+    INTEL_LCM        = 2,
+    /// DOS/UNDI.
+    DOSUNDI          = 3,
+    /// NEC ESMPRO.
+    NEC_ESMPRO       = 4,
+    /// IBM WSoD.
+    IBM_WSOD         = 5,
+    /// IBM LCCM.
+    IBM_LCCM         = 6,
+    /// CA Unicenter TNG.
+    CA_UNICENTER_TNG = 7,
+    /// HP OpenView.
+    HP_OPENVIEW      = 8,
+    /// Altiris 9.
+    ALTIRIS_9        = 9,
+    /// Altiris 10.
+    ALTIRIS_10       = 10,
+    /// Altiris 11.
+    ALTIRIS_11       = 11,
+    /// Red Hat installation.
+    REDHAT_INSTALL   = 13,
+    /// Red Hat boot.
+    REDHAT_BOOT      = 14,
+    /// Rembo.
+    REMBO            = 15,
+    /// BEOBoot.
+    BEOBOOT          = 16,
+    /// PXE test server.
+    PXETEST          = 65535,
+}}
+
+/// An IP address, with the alignment of `EFI_IP_ADDRESS`.
+#[derive(Clone, Copy, Default)]
+#[repr(C, align(4))]
+struct AlignedIpAddress(IpAddress);
+
+impl From<&IpAddress> for AlignedIpAddress {
+    fn from(address: &IpAddress) -> Self {
+        AlignedIpAddress(*address)
+    }
+}
+
+/// Maximum size of a packet cached in the mode data.
+const PACKET_SIZE: usize = 1472;
+
+/// The `EFI_PXE_BASE_CODE_PACKET` union.
+#[derive(Clone, Copy)]
+#[repr(C, align(4))]
+struct RawPacket([u8; PACKET_SIZE]);
+
+impl RawPacket {
+    const EMPTY: RawPacket = RawPacket([0; PACKET_SIZE]);
+
+    /// Copies a message into a packet buffer, or returns `None` if it is too
+    /// large.
+    fn new(message: &Message) -> Option<Self> {
+        let bytes = message.as_bytes();
+        let mut packet = Self::EMPTY;
+        packet.0.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        Some(packet)
+    }
+
+    fn message(&self, valid: bool) -> Option<&Message> {
+        if valid {
+            Message::from_bytes(&self.0)
+        } else {
+            None
+        }
+    }
+}
+
+bitflags! {
+    /// Which packets are received by the UDP functions of the base code.
+    pub struct IpFilters: u8 {
+        /// Packets sent to the station address.
+        const STATION_IP = 0x01;
+        /// Broadcast packets.
+        const BROADCAST = 0x02;
+        /// All the packets.
+        const PROMISCUOUS = 0x04;
+        /// All the multicast packets.
+        const PROMISCUOUS_MULTICAST = 0x08;
+    }
+}
+
+/// Maximum number of addresses in an `IpFilter`.
+const IP_FILTER_LEN: usize = 8;
+
+/// The IP receive filter of the base code (`EFI_PXE_BASE_CODE_IP_FILTER`).
+#[repr(C)]
+pub struct IpFilter {
+    filters: u8,
+    ip_cnt: u8,
+    reserved: u16,
+    ip_list: [AlignedIpAddress; IP_FILTER_LEN],
+}
+
+impl IpFilter {
+    /// The kinds of packets which are received.
+    pub fn filters(&self) -> IpFilters {
+        IpFilters::from_bits_truncate(self.filters)
+    }
+
+    /// Other addresses for which packets are received.
+    pub fn addresses(&self) -> impl Iterator<Item = IpAddress> + '_ {
+        let len = usize::from(self.ip_cnt).min(IP_FILTER_LEN);
+        self.ip_list[..len].iter().map(|address| address.0)
+    }
+}
+
+impl fmt::Debug for IpFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IpFilter")
+            .field("filters", &self.filters())
+            .field("ip_cnt", &self.ip_cnt)
+            .finish()
+    }
+}
+
+/// Maximum number of entries in the ARP cache and the route table.
+const TABLE_LEN: usize = 8;
+
+/// An entry of the ARP cache (`EFI_PXE_BASE_CODE_ARP_ENTRY`).
+#[repr(C)]
+pub struct ArpEntry {
+    ip_addr: AlignedIpAddress,
+    mac_addr: MacAddress,
+}
+
+impl ArpEntry {
+    /// The IP address of the host.
+    pub fn ip_address(&self) -> IpAddress {
+        self.ip_addr.0
+    }
+
+    /// The hardware address of the host.
+    pub fn mac_address(&self) -> MacAddress {
+        self.mac_addr
+    }
+}
+
+impl fmt::Debug for ArpEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ArpEntry")
+            .field("ip_address", &self.ip_address())
+            .field("mac_address", &self.mac_address())
+            .finish()
+    }
+}
+
+/// An entry of the route table (`EFI_PXE_BASE_CODE_ROUTE_ENTRY`).
+#[repr(C)]
+pub struct RouteEntry {
+    ip_addr: AlignedIpAddress,
+    subnet_mask: AlignedIpAddress,
+    gw_addr: AlignedIpAddress,
+}
+
+impl RouteEntry {
+    /// The destination of the route.
+    pub fn ip_address(&self) -> IpAddress {
+        self.ip_addr.0
+    }
+
+    /// The subnet mask of the destination.
+    pub fn subnet_mask(&self) -> IpAddress {
+        self.subnet_mask.0
+    }
+
+    /// The gateway through which the destination is reached.
+    pub fn gateway_address(&self) -> IpAddress {
+        self.gw_addr.0
+    }
+}
+
+impl fmt::Debug for RouteEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RouteEntry")
+            .field("ip_address", &self.ip_address())
+            .field("subnet_mask", &self.subnet_mask())
+            .field("gateway_address", &self.gateway_address())
+            .finish()
+    }
+}
+
+/// The last ICMP error received (`EFI_PXE_BASE_CODE_ICMP_ERROR`).
+#[repr(C)]
+pub struct IcmpError {
+    ty: u8,
+    code: u8,
+    checksum: u16,
+    u: u32,
+    data: [u8; 494],
+}
+
+impl IcmpError {
+    /// The ICMP message type.
+    pub fn ty(&self) -> u8 {
+        self.ty
+    }
+
+    /// The ICMP message code.
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    /// The type-specific field of the header, such as the MTU of a
+    /// fragmentation error.
+    pub fn rest_of_header(&self) -> u32 {
+        self.u
+    }
+
+    /// The data of the message, which holds the start of the packet which
+    /// caused the error.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl fmt::Debug for IcmpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IcmpError")
+            .field("ty", &self.ty)
+            .field("code", &self.code)
+            .finish()
+    }
+}
+
+/// The last TFTP error received (`EFI_PXE_BASE_CODE_TFTP_ERROR`).
+#[repr(C)]
+pub struct TftpError {
+    error_code: u8,
+    error_string: [u8; 127],
+}
+
+impl TftpError {
+    /// The TFTP error code.
+    pub fn error_code(&self) -> u8 {
+        self.error_code
+    }
+
+    /// The error message sent by the server.
+    pub fn message(&self) -> &[u8] {
+        let len = self
+            .error_string
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.error_string.len());
+        &self.error_string[..len]
+    }
+}
+
+impl fmt::Debug for TftpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TftpError")
+            .field("error_code", &self.error_code)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+/// State of the base code, and the packets it exchanged
+/// (`EFI_PXE_BASE_CODE_MODE`).
+#[repr(C)]
+pub struct Mode {
+    started: bool,
+    ipv6_available: bool,
+    ipv6_supported: bool,
+    using_ipv6: bool,
+    bis_supported: bool,
+    bis_detected: bool,
+    auto_arp: bool,
+    send_guid: bool,
+    dhcp_discover_valid: bool,
+    dhcp_ack_received: bool,
+    proxy_offer_received: bool,
+    pxe_discover_valid: bool,
+    pxe_reply_received: bool,
+    pxe_bis_reply_received: bool,
+    icmp_error_received: bool,
+    tftp_error_received: bool,
+    make_callbacks: bool,
+    ttl: u8,
+    tos: u8,
+    station_ip: AlignedIpAddress,
+    subnet_mask: AlignedIpAddress,
+    dhcp_discover: RawPacket,
+    dhcp_ack: RawPacket,
+    proxy_offer: RawPacket,
+    pxe_discover: RawPacket,
+    pxe_reply: RawPacket,
+    pxe_bis_reply: RawPacket,
+    ip_filter: IpFilter,
+    arp_cache_entries: u32,
+    arp_cache: [ArpEntry; TABLE_LEN],
+    route_table_entries: u32,
+    route_table: [RouteEntry; TABLE_LEN],
+    icmp_error: IcmpError,
+    tftp_error: TftpError,
+}
+
+// The layout of the mode data is easy to get wrong, so check it against the
+// sizes given by the specification.
+const _: () = assert!(mem::size_of::<RawPacket>() == 1472);
+const _: () = assert!(mem::size_of::<IpFilter>() == 132);
+const _: () = assert!(mem::size_of::<ArpEntry>() == 48);
+const _: () = assert!(mem::size_of::<RouteEntry>() == 48);
+const _: () = assert!(mem::size_of::<IcmpError>() == 504);
+const _: () = assert!(mem::size_of::<TftpError>() == 128);
+const _: () = assert!(mem::size_of::<Mode>() == 10424);
+const _: () = assert!(mem::size_of::<DiscoverInfo>() == 24 + 20 * MAX_BOOT_SERVERS);
+const _: () = assert!(mem::size_of::<MtftpInfo>() == 24);
+
+impl Mode {
+    /// Whether the base code has been started.
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    /// Whether IPv6 is supported by the network interface.
+    pub fn ipv6_available(&self) -> bool {
+        self.ipv6_available
+    }
+
+    /// Whether IPv6 is supported by the base code.
+    pub fn ipv6_supported(&self) -> bool {
+        self.ipv6_supported
+    }
+
+    /// Whether the base code was started with IPv6.
+    pub fn using_ipv6(&self) -> bool {
+        self.using_ipv6
+    }
+
+    /// Whether the Boot Integrity Services are supported.
+    pub fn bis_supported(&self) -> bool {
+        self.bis_supported
+    }
+
+    /// Whether the Boot Integrity Services were detected.
+    pub fn bis_detected(&self) -> bool {
+        self.bis_detected
+    }
+
+    /// Whether the ARP cache is updated automatically.
+    pub fn auto_arp(&self) -> bool {
+        self.auto_arp
+    }
+
+    /// Whether the GUID of the system is sent as the client ID.
+    pub fn send_guid(&self) -> bool {
+        self.send_guid
+    }
+
+    /// Whether the callback protocol is called during the exchanges.
+    pub fn make_callbacks(&self) -> bool {
+        self.make_callbacks
+    }
+
+    /// The time-to-live of the sent packets.
+    pub fn ttl(&self) -> u8 {
+        self.ttl
+    }
+
+    /// The type of service of the sent packets.
+    pub fn tos(&self) -> u8 {
+        self.tos
+    }
+
+    /// The address of the station.
+    pub fn station_ip(&self) -> IpAddress {
+        self.station_ip.0
+    }
+
+    /// The subnet mask of the station address.
+    pub fn subnet_mask(&self) -> IpAddress {
+        self.subnet_mask.0
+    }
+
+    /// The last DHCP discover sent, if any.
+    pub fn dhcp_discover(&self) -> Option<&Message> {
+        self.dhcp_discover.message(self.dhcp_discover_valid)
+    }
+
+    /// The DHCP acknowledgement received, if any.
+    pub fn dhcp_ack(&self) -> Option<&Message> {
+        self.dhcp_ack.message(self.dhcp_ack_received)
+    }
+
+    /// The proxy DHCP offer received, if any.
+    pub fn proxy_offer(&self) -> Option<&Message> {
+        self.proxy_offer.message(self.proxy_offer_received)
+    }
+
+    /// The last boot server discover sent, if any.
+    pub fn pxe_discover(&self) -> Option<&Message> {
+        self.pxe_discover.message(self.pxe_discover_valid)
+    }
+
+    /// The boot server reply received, if any.
+    pub fn pxe_reply(&self) -> Option<&Message> {
+        self.pxe_reply.message(self.pxe_reply_received)
+    }
+
+    /// The boot server reply with Boot Integrity Services received, if any.
+    pub fn pxe_bis_reply(&self) -> Option<&Message> {
+        self.pxe_bis_reply.message(self.pxe_bis_reply_received)
+    }
+
+    /// The IP receive filter.
+    pub fn ip_filter(&self) -> &IpFilter {
+        &self.ip_filter
+    }
+
+    /// The entries of the ARP cache.
+    pub fn arp_cache(&self) -> &[ArpEntry] {
+        let len = (self.arp_cache_entries as usize).min(TABLE_LEN);
+        &self.arp_cache[..len]
+    }
+
+    /// The entries of the route table.
+    pub fn route_table(&self) -> &[RouteEntry] {
+        let len = (self.route_table_entries as usize).min(TABLE_LEN);
+        &self.route_table[..len]
+    }
+
+    /// The last ICMP error received, if any.
+    pub fn icmp_error(&self) -> Option<&IcmpError> {
+        if self.icmp_error_received {
+            Some(&self.icmp_error)
+        } else {
+            None
+        }
+    }
+
+    /// The last TFTP error received, if any.
+    pub fn tftp_error(&self) -> Option<&TftpError> {
+        if self.tftp_error_received {
+            Some(&self.tftp_error)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mode")
+            .field("started", &self.started)
+            .field("using_ipv6", &self.using_ipv6)
+            .field("station_ip", &self.station_ip())
+            .field("subnet_mask", &self.subnet_mask())
+            .field("dhcp_ack", &self.dhcp_ack())
+            .field("proxy_offer", &self.proxy_offer())
+            .field("pxe_reply", &self.pxe_reply())
+            .finish()
+    }
+}
+
+/// A boot server accepted by `BaseCode::discover`.
+#[derive(Clone, Copy, Debug)]
+pub struct BootServer {
+    /// The type of the server.
+    pub server_type: BootstrapType,
+    /// Whether any reply is accepted from this server, instead of only the
+    /// replies matching `server_type`.
+    pub accept_any_response: bool,
+    /// The address of the server.
+    pub ip_address: IpAddress,
+}
+
+/// Maximum number of servers in `DiscoverParams::servers`.
+pub const MAX_BOOT_SERVERS: usize = 8;
+
+/// The `EFI_PXE_BASE_CODE_SRVLIST` structure.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct RawBootServer {
+    ty: u16,
+    accept_any_response: bool,
+    reserved: u8,
+    ip_addr: AlignedIpAddress,
+}
+
+/// The `EFI_PXE_BASE_CODE_DISCOVER_INFO` structure, with room for
+/// `MAX_BOOT_SERVERS` servers.
+#[repr(C)]
+struct DiscoverInfo {
+    use_m_cast: bool,
+    use_b_cast: bool,
+    use_u_cast: bool,
+    must_use_list: bool,
+    server_m_cast_ip: AlignedIpAddress,
+    ip_cnt: u16,
+    srv_list: [RawBootServer; MAX_BOOT_SERVERS],
+}
+
+/// How boot servers are discovered by `BaseCode::discover`.
+#[derive(Clone, Copy, Debug)]
+pub struct DiscoverParams<'a> {
+    /// Whether to send the request to `server_mcast_ip`.
+    pub use_mcast: bool,
+    /// Whether to broadcast the request.
+    pub use_bcast: bool,
+    /// Whether to send the request to each server of `servers`.
+    pub use_ucast: bool,
+    /// Whether to only accept replies from the servers of `servers`.
+    pub must_use_list: bool,
+    /// The multicast address used if `use_mcast` is set.
+    pub server_mcast_ip: IpAddress,
+    /// The known boot servers, up to `MAX_BOOT_SERVERS` of them.
+    pub servers: &'a [BootServer],
+}
+
+/// Multicast TFTP parameters (`EFI_PXE_BASE_CODE_MTFTP_INFO`).
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C, align(4))]
+pub struct MtftpInfo {
+    /// The multicast address on which the file is sent.
+    pub mcast_ip: IpAddress,
+    /// The client port on which the file is sent.
+    pub client_port: u16,
+    /// The port of the server.
+    pub server_port: u16,
+    /// Time to listen for an existing transfer before starting one, in
+    /// seconds.
+    pub listen_timeout: u16,
+    /// Time to wait for a reply before retransmitting a request, in
+    /// seconds.
+    pub transmit_timeout: u16,
+}
+
+/// Operation performed by `BaseCode::mtftp`
+/// (`EFI_PXE_BASE_CODE_TFTP_OPCODE`).
+#[derive(Clone, Copy)]
+#[repr(u32)]
+enum TftpOpcode {
+    TftpGetFileSize = 1,
+    TftpReadFile,
+    TftpWriteFile,
+    TftpReadDirectory,
+    MtftpGetFileSize,
+    MtftpReadFile,
+    MtftpReadDirectory,
+}
+
+/// Packets to store in the mode data with `BaseCode::set_packets`.
+///
+/// For each packet, `None` leaves it unchanged, `Some(None)` marks it as
+/// not received, and `Some(Some(message))` replaces it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NewPackets<'a> {
+    /// The DHCP discover.
+    pub dhcp_discover: Option<Option<&'a Message>>,
+    /// The DHCP acknowledgement.
+    pub dhcp_ack: Option<Option<&'a Message>>,
+    /// The proxy DHCP offer.
+    pub proxy_offer: Option<Option<&'a Message>>,
+    /// The boot server discover.
+    pub pxe_discover: Option<Option<&'a Message>>,
+    /// The boot server reply.
+    pub pxe_reply: Option<Option<&'a Message>>,
+    /// The boot server reply with Boot Integrity Services.
+    pub pxe_bis_reply: Option<Option<&'a Message>>,
+}
+
+/// A packet passed to `SetPackets`, with its validity flag.
+struct NewPacket {
+    valid: bool,
+    packet: RawPacket,
+}
+
+impl NewPacket {
+    fn new(new: Option<Option<&Message>>) -> core::result::Result<Option<Self>, Status> {
+        new.map(|message| {
+            let packet = match message {
+                Some(message) => RawPacket::new(message).ok_or(Status::BAD_BUFFER_SIZE)?,
+                None => RawPacket::EMPTY,
+            };
+            Ok(NewPacket {
+                valid: message.is_some(),
+                packet,
+            })
+        })
+        .transpose()
+    }
+
+    fn valid_ptr(new: &Option<Self>) -> *const bool {
+        new.as_ref().map_or(ptr::null(), |new| &new.valid)
+    }
+
+    fn packet_ptr(new: &Option<Self>) -> *const RawPacket {
+        match new {
+            Some(new) if new.valid => &new.packet,
+            _ => ptr::null(),
+        }
+    }
+}
+
+/// The PXE base code protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct BaseCode {
+    revision: u64,
+    start: extern "efiapi" fn(this: &BaseCode, use_ipv6: bool) -> Status,
+    stop: extern "efiapi" fn(this: &BaseCode) -> Status,
+    dhcp: extern "efiapi" fn(this: &BaseCode, sort_offers: bool) -> Status,
+    discover: unsafe extern "efiapi" fn(
+        this: &BaseCode,
+        ty: BootstrapType,
+        layer: *mut u16,
+        use_bis: bool,
+        info: *const DiscoverInfo,
+    ) -> Status,
+    mtftp: unsafe extern "efiapi" fn(
+        this: &BaseCode,
+        operation: TftpOpcode,
+        buffer: *mut u8,
+        overwrite: bool,
+        buffer_size: *mut u64,
+        block_size: *const usize,
+        server_ip: *const AlignedIpAddress,
+        filename: *const Char8,
+        info: *const MtftpInfo,
+        dont_use_buffer: bool,
+    ) -> Status,
+    udp_write: usize,
+    udp_read: usize,
+    set_ip_filter: usize,
+    arp: usize,
+    set_parameters: usize,
+    set_station_ip: unsafe extern "efiapi" fn(
+        this: &BaseCode,
+        new_station_ip: *const AlignedIpAddress,
+        new_subnet_mask: *const AlignedIpAddress,
+    ) -> Status,
+    set_packets: unsafe extern "efiapi" fn(
+        this: &BaseCode,
+        new_dhcp_discover_valid: *const bool,
+        new_dhcp_ack_received: *const bool,
+        new_proxy_offer_received: *const bool,
+        new_pxe_discover_valid: *const bool,
+        new_pxe_reply_received: *const bool,
+        new_pxe_bis_reply_received: *const bool,
+        new_dhcp_discover: *const RawPacket,
+        new_dhcp_ack: *const RawPacket,
+        new_proxy_offer: *const RawPacket,
+        new_pxe_discover: *const RawPacket,
+        new_pxe_reply: *const RawPacket,
+        new_pxe_bis_reply: *const RawPacket,
+    ) -> Status,
+    mode: *const Mode,
+}
+
+unsafe impl Identify for BaseCode {
+    const GUID: Guid = guid!("03c4e603-ac28-11d3-9a2d-0090273fc14d");
+}
+
+impl BaseCode {
+    /// Returns the state of the base code, and the packets it exchanged.
+    pub fn mode(&self) -> &Mode {
+        unsafe { &*self.mode }
+    }
+
+    /// Starts the base code, with IPv4 or IPv6.
+    ///
+    /// `ALREADY_STARTED` is returned if the base code is already running,
+    /// for instance because the system was booted over the network.
+    pub fn start(&mut self, use_ipv6: bool) -> Result {
+        (self.start)(self, use_ipv6).into()
+    }
+
+    /// Stops the base code.
+    pub fn stop(&mut self) -> Result {
+        (self.stop)(self).into()
+    }
+
+    /// Acquires an address with DHCP, and stores the exchanged packets in
+    /// the mode data.
+    ///
+    /// If `sort_offers` is set, all the offers are collected and the best
+    /// one is selected. Otherwise, the first usable offer is selected.
+    pub fn dhcp(&mut self, sort_offers: bool) -> Result {
+        (self.dhcp)(self, sort_offers).into()
+    }
+
+    /// Discovers a boot server, and stores the exchanged packets in the
+    /// mode data.
+    ///
+    /// `layer` is the boot layer to request, and is updated with the layer
+    /// of the reply. If `params` is `None`, the discovery method is taken
+    /// from the options of the DHCP acknowledgement or proxy offer.
+    pub fn discover(
+        &mut self,
+        ty: BootstrapType,
+        layer: &mut u16,
+        use_bis: bool,
+        params: Option<&DiscoverParams>,
+    ) -> Result {
+        let info = match params {
+            Some(params) => {
+                if params.servers.len() > MAX_BOOT_SERVERS {
+                    return Err(Status::INVALID_PARAMETER.into());
+                }
+                let mut srv_list = [RawBootServer::default(); MAX_BOOT_SERVERS];
+                for (raw, server) in srv_list.iter_mut().zip(params.servers) {
+                    *raw = RawBootServer {
+                        ty: server.server_type.0,
+                        accept_any_response: server.accept_any_response,
+                        reserved: 0,
+                        ip_addr: (&server.ip_address).into(),
+                    };
+                }
+                Some(DiscoverInfo {
+                    use_m_cast: params.use_mcast,
+                    use_b_cast: params.use_bcast,
+                    use_u_cast: params.use_ucast,
+                    must_use_list: params.must_use_list,
+                    server_m_cast_ip: (&params.server_mcast_ip).into(),
+                    ip_cnt: params.servers.len() as u16,
+                    srv_list,
+                })
+            }
+            None => None,
+        };
+        let info = info.as_ref().map_or(ptr::null(), |info| info as *const _);
+        unsafe { (self.discover)(self, ty, layer, use_bis, info) }.into()
+    }
+
+    /// Performs a TFTP operation, and returns the updated size.
+    fn mtftp(
+        &mut self,
+        operation: TftpOpcode,
+        server_ip: &IpAddress,
+        filename: &CStr8,
+        buffer: Option<(*mut u8, usize)>,
+        overwrite: bool,
+        info: Option<&MtftpInfo>,
+    ) -> Result<u64, Option<usize>> {
+        let server_ip = AlignedIpAddress::from(server_ip);
+        let (buffer, mut size) =
+            buffer.map_or((ptr::null_mut(), 0), |(buffer, len)| (buffer, len as u64));
+        let info = info.map_or(ptr::null(), |info| info as *const _);
+        let status = unsafe {
+            (self.mtftp)(
+                self,
+                operation,
+                buffer,
+                overwrite,
+                &mut size,
+                ptr::null(),
+                &server_ip,
+                filename.as_ptr(),
+                info,
+                false,
+            )
+        };
+        status.into_with(|| size, |_| None)
+    }
+
+    /// Returns the size of a file on a TFTP server.
+    pub fn tftp_get_file_size(&mut self, server_ip: &IpAddress, filename: &CStr8) -> Result<u64> {
+        self.mtftp(
+            TftpOpcode::TftpGetFileSize,
+            server_ip,
+            filename,
+            None,
+            false,
+            None,
+        )
+        .map_err(|err| err.status().into())
+    }
+
+    /// Downloads a file from a TFTP server into `buffer`, and returns its
+    /// size.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned. The size
+    /// of the file can be learned beforehand with `tftp_get_file_size`.
+    pub fn tftp_read_file(
+        &mut self,
+        server_ip: &IpAddress,
+        filename: &CStr8,
+        buffer: &mut [u8],
+    ) -> Result<usize, Option<usize>> {
+        let buffer = (buffer.as_mut_ptr(), buffer.len());
+        self.mtftp(
+            TftpOpcode::TftpReadFile,
+            server_ip,
+            filename,
+            Some(buffer),
+            false,
+            None,
+        )
+        .map(|completion| completion.map(|size| size as usize))
+    }
+
+    /// Uploads `data` as a file to a TFTP server, replacing the existing
+    /// file if `overwrite` is set.
+    pub fn tftp_write_file(
+        &mut self,
+        server_ip: &IpAddress,
+        filename: &CStr8,
+        overwrite: bool,
+        data: &[u8],
+    ) -> Result {
+        // The protocol only reads from the buffer of write requests.
+        let buffer = (data.as_ptr() as *mut u8, data.len());
+        self.mtftp(
+            TftpOpcode::TftpWriteFile,
+            server_ip,
+            filename,
+            Some(buffer),
+            overwrite,
+            None,
+        )
+        .map(|completion| completion.map(|_| ()))
+        .map_err(|err| err.status().into())
+    }
+
+    /// Downloads the listing of a directory from a TFTP server into
+    /// `buffer`, and returns its size.
+    pub fn tftp_read_directory(
+        &mut self,
+        server_ip: &IpAddress,
+        directory: &CStr8,
+        buffer: &mut [u8],
+    ) -> Result<usize, Option<usize>> {
+        let buffer = (buffer.as_mut_ptr(), buffer.len());
+        self.mtftp(
+            TftpOpcode::TftpReadDirectory,
+            server_ip,
+            directory,
+            Some(buffer),
+            false,
+            None,
+        )
+        .map(|completion| completion.map(|size| size as usize))
+    }
+
+    /// Returns the size of a file on a multicast TFTP server.
+    pub fn mtftp_get_file_size(
+        &mut self,
+        server_ip: &IpAddress,
+        filename: &CStr8,
+        info: &MtftpInfo,
+    ) -> Result<u64> {
+        self.mtftp(
+            TftpOpcode::MtftpGetFileSize,
+            server_ip,
+            filename,
+            None,
+            false,
+            Some(info),
+        )
+        .map_err(|err| err.status().into())
+    }
+
+    /// Downloads a file from a multicast TFTP server into `buffer`, and
+    /// returns its size.
+    pub fn mtftp_read_file(
+        &mut self,
+        server_ip: &IpAddress,
+        filename: &CStr8,
+        info: &MtftpInfo,
+        buffer: &mut [u8],
+    ) -> Result<usize, Option<usize>> {
+        let buffer = (buffer.as_mut_ptr(), buffer.len());
+        self.mtftp(
+            TftpOpcode::MtftpReadFile,
+            server_ip,
+            filename,
+            Some(buffer),
+            false,
+            Some(info),
+        )
+        .map(|completion| completion.map(|size| size as usize))
+    }
+
+    /// Downloads the listing of a directory from a multicast TFTP server
+    /// into `buffer`, and returns its size.
+    pub fn mtftp_read_directory(
+        &mut self,
+        server_ip: &IpAddress,
+        directory: &CStr8,
+        info: &MtftpInfo,
+        buffer: &mut [u8],
+    ) -> Result<usize, Option<usize>> {
+        let buffer = (buffer.as_mut_ptr(), buffer.len());
+        self.mtftp(
+            TftpOpcode::MtftpReadDirectory,
+            server_ip,
+            directory,
+            Some(buffer),
+            false,
+            Some(info),
+        )
+        .map(|completion| completion.map(|size| size as usize))
+    }
+
+    /// Changes the station address and subnet mask. `None` leaves the value
+    /// unchanged.
+    pub fn set_station_ip(
+        &mut self,
+        new_station_ip: Option<&IpAddress>,
+        new_subnet_mask: Option<&IpAddress>,
+    ) -> Result {
+        let station_ip = new_station_ip.map(AlignedIpAddress::from);
+        let subnet_mask = new_subnet_mask.map(AlignedIpAddress::from);
+        unsafe {
+            (self.set_station_ip)(
+                self,
+                station_ip.as_ref().map_or(ptr::null(), |ip| ip as *const _),
+                subnet_mask
+                    .as_ref()
+                    .map_or(ptr::null(), |ip| ip as *const _),
+            )
+        }
+        .into()
+    }
+
+    /// Replaces the packets stored in the mode data.
+    ///
+    /// `BAD_BUFFER_SIZE` is returned if a message is longer than the 1472
+    /// bytes which can be stored.
+    pub fn set_packets(&mut self, packets: &NewPackets) -> Result {
+        let dhcp_discover = NewPacket::new(packets.dhcp_discover)?;
+        let dhcp_ack = NewPacket::new(packets.dhcp_ack)?;
+        let proxy_offer = NewPacket::new(packets.proxy_offer)?;
+        let pxe_discover = NewPacket::new(packets.pxe_discover)?;
+        let pxe_reply = NewPacket::new(packets.pxe_reply)?;
+        let pxe_bis_reply = NewPacket::new(packets.pxe_bis_reply)?;
+        unsafe {
+            (self.set_packets)(
+                self,
+                NewPacket::valid_ptr(&dhcp_discover),
+                NewPacket::valid_ptr(&dhcp_ack),
+                NewPacket::valid_ptr(&proxy_offer),
+                NewPacket::valid_ptr(&pxe_discover),
+                NewPacket::valid_ptr(&pxe_reply),
+                NewPacket::valid_ptr(&pxe_bis_reply),
+                NewPacket::packet_ptr(&dhcp_discover),
+                NewPacket::packet_ptr(&dhcp_ack),
+                NewPacket::packet_ptr(&proxy_offer),
+                NewPacket::packet_ptr(&pxe_discover),
+                NewPacket::packet_ptr(&pxe_reply),
+                NewPacket::packet_ptr(&pxe_bis_reply),
+            )
+        }
+        .into()
+    }
+}
@@ -0,0 +1,658 @@
+//! Simple Network Protocol
+//!
+//! Provides a packet level interface to a network adapter.
+//! Once the adapter is initialized, the protocol can transmit and receive
+//! raw Ethernet frames.
+
+use crate::data_types::{IpAddress, MacAddress};
+use crate::proto::Protocol;
+use crate::{Error, Event, Guid, Identify, Result, Status};
+use bitflags::bitflags;
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr::{self, NonNull};
+
+/// The Simple Network Protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct SimpleNetwork {
+    revision: u64,
+    start: extern "efiapi" fn(this: &SimpleNetwork) -> Status,
+    stop: extern "efiapi" fn(this: &SimpleNetwork) -> Status,
+    initialize: extern "efiapi" fn(
+        this: &SimpleNetwork,
+        extra_rx_buffer_size: usize,
+        extra_tx_buffer_size: usize,
+    ) -> Status,
+    reset: extern "efiapi" fn(this: &SimpleNetwork, extended_verification: bool) -> Status,
+    shutdown: extern "efiapi" fn(this: &SimpleNetwork) -> Status,
+    receive_filters: unsafe extern "efiapi" fn(
+        this: &SimpleNetwork,
+        enable: u32,
+        disable: u32,
+        reset_mcast_filter: bool,
+        mcast_filter_count: usize,
+        mcast_filter: *const MacAddress,
+    ) -> Status,
+    station_address: unsafe extern "efiapi" fn(
+        this: &SimpleNetwork,
+        reset: bool,
+        new: *const MacAddress,
+    ) -> Status,
+    statistics: unsafe extern "efiapi" fn(
+        this: &SimpleNetwork,
+        reset: bool,
+        statistics_size: *mut usize,
+        statistics_table: *mut u64,
+    ) -> Status,
+    mcast_ip_to_mac: extern "efiapi" fn(
+        this: &SimpleNetwork,
+        ipv6: bool,
+        ip: &IpAddress,
+        mac: &mut MacAddress,
+    ) -> Status,
+    nv_data: unsafe extern "efiapi" fn(
+        this: &SimpleNetwork,
+        read_write: bool,
+        offset: usize,
+        buffer_size: usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    get_status: unsafe extern "efiapi" fn(
+        this: &SimpleNetwork,
+        interrupt_status: *mut u32,
+        tx_buf: *mut *mut c_void,
+    ) -> Status,
+    transmit: unsafe extern "efiapi" fn(
+        this: &SimpleNetwork,
+        header_size: usize,
+        buffer_size: usize,
+        buffer: *const c_void,
+        src_addr: *const MacAddress,
+        dest_addr: *const MacAddress,
+        protocol: *const u16,
+    ) -> Status,
+    receive: unsafe extern "efiapi" fn(
+        this: &SimpleNetwork,
+        header_size: *mut usize,
+        buffer_size: &mut usize,
+        buffer: *mut c_void,
+        src_addr: *mut MacAddress,
+        dest_addr: *mut MacAddress,
+        protocol: *mut u16,
+    ) -> Status,
+    wait_for_packet: Event,
+    mode: *const NetworkMode,
+}
+
+unsafe impl Identify for SimpleNetwork {
+    const GUID: Guid = guid!("a19832b9-ac25-11d3-9a2d-0090273fc14d");
+}
+
+impl SimpleNetwork {
+    /// Changes the state of the network interface from "stopped" to "started".
+    pub fn start(&mut self) -> Result {
+        (self.start)(self).into()
+    }
+
+    /// Changes the state of the network interface from "started" to "stopped".
+    pub fn stop(&mut self) -> Result {
+        (self.stop)(self).into()
+    }
+
+    /// Resets the network adapter and allocates the transmit and receive
+    /// buffers required by the network interface, optionally with some
+    /// extra space for received and transmitted frames.
+    pub fn initialize(
+        &mut self,
+        extra_rx_buffer_size: usize,
+        extra_tx_buffer_size: usize,
+    ) -> Result {
+        (self.initialize)(self, extra_rx_buffer_size, extra_tx_buffer_size).into()
+    }
+
+    /// Resets the network adapter and reinitializes it with the parameters
+    /// provided in the previous call to `initialize`.
+    pub fn reset(&mut self, extended_verification: bool) -> Result {
+        (self.reset)(self, extended_verification).into()
+    }
+
+    /// Resets the network adapter and leaves it in a state that is safe
+    /// for another driver to initialize.
+    pub fn shutdown(&mut self) -> Result {
+        (self.shutdown)(self).into()
+    }
+
+    /// Enables and disables receive filters. Filters which are in neither
+    /// set keep their current state.
+    pub fn receive_filters(&mut self, enable: ReceiveFlags, disable: ReceiveFlags) -> Result {
+        unsafe {
+            (self.receive_filters)(self, enable.bits(), disable.bits(), false, 0, ptr::null())
+        }
+        .into()
+    }
+
+    /// Replaces the multicast addresses of the receive filter, and enables
+    /// the reception of multicast frames.
+    ///
+    /// If there are more addresses than `NetworkMode::max_mcast_filter_count`,
+    /// `INVALID_PARAMETER` is returned.
+    pub fn set_mcast_filter(&mut self, filter: &[MacAddress]) -> Result {
+        if filter.is_empty() || filter.len() > self.mode().max_mcast_filter_count() as usize {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        unsafe {
+            (self.receive_filters)(
+                self,
+                ReceiveFlags::MULTICAST.bits(),
+                0,
+                false,
+                filter.len(),
+                filter.as_ptr(),
+            )
+        }
+        .into()
+    }
+
+    /// Resets the multicast addresses of the receive filter to their
+    /// default, and disables the reception of multicast frames.
+    pub fn reset_mcast_filter(&mut self) -> Result {
+        unsafe {
+            (self.receive_filters)(
+                self,
+                0,
+                ReceiveFlags::MULTICAST.bits(),
+                true,
+                0,
+                ptr::null(),
+            )
+        }
+        .into()
+    }
+
+    /// Sets the MAC address of the network interface, or resets it to the
+    /// permanent address if `new` is `None`.
+    pub fn station_address(&mut self, new: Option<&MacAddress>) -> Result {
+        let new_ptr = new.map_or(ptr::null(), |addr| addr as *const _);
+        unsafe { (self.station_address)(self, new.is_none(), new_ptr) }.into()
+    }
+
+    /// Reads the statistics of the network interface.
+    ///
+    /// Firmware may only support some statistics, which are then returned as
+    /// `None`. Many interfaces do not support statistics at all, in which
+    /// case `UNSUPPORTED` is returned.
+    pub fn statistics(&mut self) -> Result<NetworkStatistics> {
+        let mut table = [0; STATISTICS_COUNT];
+        let mut size = core::mem::size_of_val(&table);
+        let status = unsafe { (self.statistics)(self, false, &mut size, table.as_mut_ptr()) };
+
+        // Firmware which supports more statistics than we know of fills in
+        // the whole table, and reports the size that it would need.
+        let status = if status == Status::BUFFER_TOO_SMALL {
+            Status::SUCCESS
+        } else {
+            status
+        };
+        let count = (size / core::mem::size_of::<u64>()).min(STATISTICS_COUNT);
+        status.into_with_val(|| NetworkStatistics { table, count })
+    }
+
+    /// Resets the statistics of the network interface.
+    pub fn reset_statistics(&mut self) -> Result {
+        unsafe { (self.statistics)(self, true, ptr::null_mut(), ptr::null_mut()) }.into()
+    }
+
+    /// Converts a multicast IP address to the multicast MAC address which
+    /// frames sent to it are addressed to.
+    pub fn mcast_ip_to_mac(&mut self, ipv6: bool, ip: &IpAddress) -> Result<MacAddress> {
+        let mut mac = MacAddress::default();
+        (self.mcast_ip_to_mac)(self, ipv6, ip, &mut mac).into_with_val(|| mac)
+    }
+
+    /// Reads from the non-volatile storage of the network interface.
+    ///
+    /// The offset and the size of the buffer must be multiples of
+    /// `NetworkMode::nv_ram_access_size`.
+    pub fn read_nv_data(&mut self, offset: usize, buffer: &mut [u8]) -> Result {
+        unsafe {
+            (self.nv_data)(
+                self,
+                true,
+                offset,
+                buffer.len(),
+                buffer.as_mut_ptr() as *mut c_void,
+            )
+        }
+        .into()
+    }
+
+    /// Writes to the non-volatile storage of the network interface.
+    ///
+    /// The offset and the size of the buffer must be multiples of
+    /// `NetworkMode::nv_ram_access_size`.
+    pub fn write_nv_data(&mut self, offset: usize, buffer: &[u8]) -> Result {
+        unsafe {
+            (self.nv_data)(
+                self,
+                false,
+                offset,
+                buffer.len(),
+                buffer.as_ptr() as *mut c_void,
+            )
+        }
+        .into()
+    }
+
+    /// Reads the interrupt status of the network interface, and the
+    /// transmit buffer which was recycled by the interface, if any.
+    ///
+    /// This also acknowledges the interrupts which are reported.
+    pub fn get_status(&mut self) -> Result<NetworkStatus> {
+        let mut interrupt_status = 0;
+        let mut tx_buf = ptr::null_mut();
+        unsafe { (self.get_status)(self, &mut interrupt_status, &mut tx_buf) }.into_with_val(|| {
+            NetworkStatus {
+                interrupt_status: InterruptStatus::from_bits_truncate(interrupt_status),
+                recycled_tx_buffer: NonNull::new(tx_buf as *mut u8),
+            }
+        })
+    }
+
+    /// Places a frame in the transmit queue of the network interface, and
+    /// waits until the interface has finished transmitting it.
+    ///
+    /// If `header_size` is not zero, the first `header_size` bytes of the
+    /// buffer are overwritten by the interface with the media header, in which case the destination
+    /// address and the protocol must be given. The source address then
+    /// defaults to the current address of the interface.
+    ///
+    /// The interface keeps using the buffer after it has been queued, until
+    /// it is recycled and reported by `get_status`. This function only
+    /// returns once that has happened, so that the buffer is not freed or
+    /// modified while the frame is being sent. Any other buffer recycled in
+    /// the meantime is ignored.
+    pub fn transmit(
+        &mut self,
+        header_size: usize,
+        buffer: &mut [u8],
+        src_addr: Option<&MacAddress>,
+        dest_addr: Option<&MacAddress>,
+        protocol: Option<u16>,
+    ) -> Result {
+        let as_ptr = |addr: Option<&MacAddress>| addr.map_or(ptr::null(), |addr| addr as *const _);
+        let protocol_ptr = protocol.as_ref().map_or(ptr::null(), |p| p as *const _);
+        let status = unsafe {
+            (self.transmit)(
+                self,
+                header_size,
+                buffer.len(),
+                buffer.as_mut_ptr() as *const c_void,
+                as_ptr(src_addr),
+                as_ptr(dest_addr),
+                protocol_ptr,
+            )
+        };
+        let (status, ()) = status.into_result()?.split();
+
+        loop {
+            let recycled = self.get_status()?.log().recycled_tx_buffer;
+            if recycled.map(NonNull::as_ptr) == Some(buffer.as_ptr() as *mut u8) {
+                return Ok(status.into());
+            }
+        }
+    }
+
+    /// Receives a frame from the network interface into `buffer`.
+    ///
+    /// Returns `None` if no frame has been received yet, so that this can be
+    /// called in a polling loop. If the buffer is too small for the frame,
+    /// `BUFFER_TOO_SMALL` is returned along with the required size.
+    pub fn receive(&mut self, buffer: &mut [u8]) -> Result<Option<ReceivedFrame>, Option<usize>> {
+        let mut header_size = 0;
+        let mut buffer_size = buffer.len();
+        let mut src_addr = MacAddress::default();
+        let mut dest_addr = MacAddress::default();
+        let mut protocol = 0;
+        let status = unsafe {
+            (self.receive)(
+                self,
+                &mut header_size,
+                &mut buffer_size,
+                buffer.as_mut_ptr() as *mut c_void,
+                &mut src_addr,
+                &mut dest_addr,
+                &mut protocol,
+            )
+        };
+        match status {
+            Status::NOT_READY => Ok(None.into()),
+            Status::BUFFER_TOO_SMALL => Err(Error::new(status, Some(buffer_size))),
+            _ => status.into_with(
+                || {
+                    Some(ReceivedFrame {
+                        len: buffer_size,
+                        header_size,
+                        src_addr,
+                        dest_addr,
+                        protocol,
+                    })
+                },
+                |_| None,
+            ),
+        }
+    }
+
+    /// Event which is signaled when a frame has been received.
+    ///
+    /// Use it with `BootServices::wait_for_event` to wait for frames
+    /// instead of polling `receive`.
+    pub fn wait_for_packet(&self) -> &Event {
+        &self.wait_for_packet
+    }
+
+    /// Returns the current state and the capabilities of the network
+    /// interface.
+    pub fn mode(&self) -> &NetworkMode {
+        unsafe { &*self.mode }
+    }
+}
+
+bitflags! {
+    /// Kinds of frames which the network interface can receive.
+    pub struct ReceiveFlags: u32 {
+        /// Frames sent to the current address of the interface.
+        const UNICAST = 0x01;
+        /// Frames sent to one of the multicast addresses of the filter.
+        const MULTICAST = 0x02;
+        /// Frames sent to the broadcast address.
+        const BROADCAST = 0x04;
+        /// All frames, whatever their destination.
+        const PROMISCUOUS = 0x08;
+        /// All multicast frames.
+        const PROMISCUOUS_MULTICAST = 0x10;
+    }
+}
+
+bitflags! {
+    /// Interrupts reported by `SimpleNetwork::get_status`.
+    pub struct InterruptStatus: u32 {
+        /// A frame has been received.
+        const RECEIVE = 0x01;
+        /// A frame has been transmitted.
+        const TRANSMIT = 0x02;
+        /// A command has completed.
+        const COMMAND = 0x04;
+        /// A software interrupt has been raised.
+        const SOFTWARE = 0x08;
+    }
+}
+
+/// Status of the network interface, as returned by
+/// `SimpleNetwork::get_status`.
+#[derive(Clone, Copy, Debug)]
+pub struct NetworkStatus {
+    /// Interrupts which were pending.
+    pub interrupt_status: InterruptStatus,
+    /// A transmit buffer which the interface has finished using, if any.
+    ///
+    /// `SimpleNetwork::transmit` already waits for its buffer to be
+    /// recycled, so this is only useful to know which frames were sent.
+    pub recycled_tx_buffer: Option<NonNull<u8>>,
+}
+
+/// Information about a frame received by `SimpleNetwork::receive`.
+#[derive(Clone, Copy, Debug)]
+pub struct ReceivedFrame {
+    /// Size of the frame, including the media header.
+    pub len: usize,
+    /// Size of the media header of the frame.
+    pub header_size: usize,
+    /// Source address of the frame.
+    pub src_addr: MacAddress,
+    /// Destination address of the frame.
+    pub dest_addr: MacAddress,
+    /// Protocol of the frame, such as 0x0800 for IPv4 over Ethernet.
+    pub protocol: u16,
+}
+
+/// Number of statistics in `EFI_NETWORK_STATISTICS`.
+const STATISTICS_COUNT: usize = 26;
+
+/// Statistics of a network interface, as returned by
+/// `SimpleNetwork::statistics`.
+///
+/// Each statistic is `None` if the firmware does not provide it.
+#[derive(Clone, Copy)]
+pub struct NetworkStatistics {
+    table: [u64; STATISTICS_COUNT],
+    count: usize,
+}
+
+impl NetworkStatistics {
+    /// Reads a statistic, which firmware sets to all ones if it is not
+    /// supported.
+    fn get(&self, index: usize) -> Option<u64> {
+        Some(self.table[index]).filter(|&value| index < self.count && value != u64::MAX)
+    }
+}
+
+macro_rules! statistics {
+    ($($(#[$attr:meta])* $name:ident = $index:expr,)*) => {
+        impl NetworkStatistics {
+            $(
+                $(#[$attr])*
+                pub fn $name(&self) -> Option<u64> {
+                    self.get($index)
+                }
+            )*
+        }
+
+        impl fmt::Debug for NetworkStatistics {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let mut debug = f.debug_struct("NetworkStatistics");
+                $(
+                    if let Some(value) = self.$name() {
+                        debug.field(stringify!($name), &value);
+                    }
+                )*
+                debug.finish()
+            }
+        }
+    };
+}
+
+statistics! {
+    /// Total number of frames received, including errors and dropped frames.
+    rx_total_frames = 0,
+    /// Number of valid frames received and copied into receive buffers.
+    rx_good_frames = 1,
+    /// Number of frames below the minimum length for the media.
+    rx_undersize_frames = 2,
+    /// Number of frames above the maximum length for the media.
+    rx_oversize_frames = 3,
+    /// Number of valid frames that were dropped because the receive buffers
+    /// were full.
+    rx_dropped_frames = 4,
+    /// Number of valid unicast frames received and not dropped.
+    rx_unicast_frames = 5,
+    /// Number of valid broadcast frames received and not dropped.
+    rx_broadcast_frames = 6,
+    /// Number of valid multicast frames received and not dropped.
+    rx_multicast_frames = 7,
+    /// Number of frames with CRC or alignment errors.
+    rx_crc_error_frames = 8,
+    /// Total number of bytes received, including errors and dropped frames.
+    rx_total_bytes = 9,
+    /// Total number of frames transmitted, including errors and dropped
+    /// frames.
+    tx_total_frames = 10,
+    /// Number of valid frames transmitted.
+    tx_good_frames = 11,
+    /// Number of frames below the minimum length for the media.
+    tx_undersize_frames = 12,
+    /// Number of frames above the maximum length for the media.
+    tx_oversize_frames = 13,
+    /// Number of valid frames that were dropped because the transmit buffers
+    /// were full.
+    tx_dropped_frames = 14,
+    /// Number of valid unicast frames transmitted and not dropped.
+    tx_unicast_frames = 15,
+    /// Number of valid broadcast frames transmitted and not dropped.
+    tx_broadcast_frames = 16,
+    /// Number of valid multicast frames transmitted and not dropped.
+    tx_multicast_frames = 17,
+    /// Number of frames with CRC or alignment errors.
+    tx_crc_error_frames = 18,
+    /// Total number of bytes transmitted, including errors and dropped
+    /// frames.
+    tx_total_bytes = 19,
+    /// Number of collisions detected on this subnet.
+    collisions = 20,
+    /// Number of frames destined for unsupported protocols.
+    unsupported_protocol = 21,
+    /// Number of valid frames received that were duplicated.
+    rx_duplicated_frames = 22,
+    /// Number of encrypted frames received that failed to decrypt.
+    rx_decrypt_error_frames = 23,
+    /// Number of frames that failed to transmit after exceeding the retry
+    /// limit.
+    tx_error_frames = 24,
+    /// Number of frames transmitted successfully after more than one attempt.
+    tx_retry_frames = 25,
+}
+
+newtype_enum! {
+/// State of the network interface.
+pub enum NetworkState: u32 => {
+    /// The interface is stopped.
+    STOPPED     = 0,
+    /// The interface is started, but not initialized.
+    STARTED     = 1,
+    /// The interface is initialized, and can transmit and receive frames.
+    INITIALIZED = 2,
+}}
+
+/// Maximum number of multicast addresses in the receive filter.
+const MAX_MCAST_FILTER_COUNT: usize = 16;
+
+/// Current state and capabilities of a network interface.
+#[repr(C)]
+#[derive(Debug)]
+pub struct NetworkMode {
+    state: NetworkState,
+    hw_address_size: u32,
+    media_header_size: u32,
+    max_packet_size: u32,
+    nv_ram_size: u32,
+    nv_ram_access_size: u32,
+    receive_filter_mask: u32,
+    receive_filter_setting: u32,
+    max_mcast_filter_count: u32,
+    mcast_filter_count: u32,
+    mcast_filter: [MacAddress; MAX_MCAST_FILTER_COUNT],
+    current_address: MacAddress,
+    broadcast_address: MacAddress,
+    permanent_address: MacAddress,
+    if_type: u8,
+    mac_address_changeable: bool,
+    multiple_tx_supported: bool,
+    media_present_supported: bool,
+    media_present: bool,
+}
+
+impl NetworkMode {
+    /// Current state of the interface.
+    pub fn state(&self) -> NetworkState {
+        self.state
+    }
+
+    /// Size of the MAC addresses of the interface, in bytes.
+    pub fn hw_address_size(&self) -> u32 {
+        self.hw_address_size
+    }
+
+    /// Size of the media header of the frames, in bytes.
+    pub fn media_header_size(&self) -> u32 {
+        self.media_header_size
+    }
+
+    /// Maximum size of the data of a frame, excluding the media header.
+    pub fn max_packet_size(&self) -> u32 {
+        self.max_packet_size
+    }
+
+    /// Size of the non-volatile storage of the interface, in bytes.
+    pub fn nv_ram_size(&self) -> u32 {
+        self.nv_ram_size
+    }
+
+    /// Granularity of accesses to the non-volatile storage, in bytes.
+    pub fn nv_ram_access_size(&self) -> u32 {
+        self.nv_ram_access_size
+    }
+
+    /// Receive filters which are supported by the interface.
+    pub fn receive_filter_mask(&self) -> ReceiveFlags {
+        ReceiveFlags::from_bits_truncate(self.receive_filter_mask)
+    }
+
+    /// Receive filters which are currently enabled.
+    pub fn receive_filter_setting(&self) -> ReceiveFlags {
+        ReceiveFlags::from_bits_truncate(self.receive_filter_setting)
+    }
+
+    /// Maximum number of multicast addresses in the receive filter.
+    pub fn max_mcast_filter_count(&self) -> u32 {
+        self.max_mcast_filter_count
+    }
+
+    /// Multicast addresses which are currently in the receive filter.
+    pub fn mcast_filter(&self) -> &[MacAddress] {
+        let count = (self.mcast_filter_count as usize).min(MAX_MCAST_FILTER_COUNT);
+        &self.mcast_filter[..count]
+    }
+
+    /// Current address of the interface.
+    pub fn current_address(&self) -> &MacAddress {
+        &self.current_address
+    }
+
+    /// Broadcast address of the network.
+    pub fn broadcast_address(&self) -> &MacAddress {
+        &self.broadcast_address
+    }
+
+    /// Permanent address of the interface.
+    pub fn permanent_address(&self) -> &MacAddress {
+        &self.permanent_address
+    }
+
+    /// Type of the interface, as defined by the IANA `ifType` numbers.
+    /// This is 1 for Ethernet.
+    pub fn if_type(&self) -> u8 {
+        self.if_type
+    }
+
+    /// Whether the address of the interface can be changed with
+    /// `SimpleNetwork::station_address`.
+    pub fn mac_address_changeable(&self) -> bool {
+        self.mac_address_changeable
+    }
+
+    /// Whether several frames can be queued for transmission at once.
+    pub fn multiple_tx_supported(&self) -> bool {
+        self.multiple_tx_supported
+    }
+
+    /// Whether the interface can detect if a cable is plugged in.
+    pub fn media_present_supported(&self) -> bool {
+        self.media_present_supported
+    }
+
+    /// Whether a cable is plugged in. This is only meaningful if
+    /// `media_present_supported` is true.
+    pub fn media_present(&self) -> bool {
+        self.media_present
+    }
+}
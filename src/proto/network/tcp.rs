@@ -0,0 +1,641 @@
+//! TCP protocol, over IPv4 or IPv6.
+//!
+//! Each instance of the protocol handles one TCP connection. Instances are
+//! obtained by creating a child handle through the `TcpServiceBinding`
+//! protocol, which is installed on the handle of the network interface.
+//!
+//! The protocol is generic over the IP version, which selects its GUID and
+//! the type of its endpoints. The `tcp4` and `tcp6` modules provide the
+//! definitions specific to each version.
+//!
+//! All the data path operations are asynchronous: they take a token, which
+//! is updated and signaled by the protocol once they complete. For simple
+//! uses, `connect_blocking`, `send_all`, `recv` and `close_blocking` create
+//! their own token and wait for its completion.
+
+use super::{with_event, Fragment, FragmentMut, IpVersion};
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{Event, Guid, Handle, Identify, Result, Status};
+use core::ffi::c_void;
+use core::fmt::Debug;
+use core::mem::MaybeUninit;
+use core::{ptr, slice};
+
+/// A version of IP over which the TCP protocol runs.
+pub trait TcpVersion: IpVersion {
+    /// Fields of the IP header of the packets sent by an instance.
+    type IpFields: Copy + Debug + Default + Eq;
+    /// Local and remote endpoints of a connection.
+    type AccessPoint: Copy + Debug + Default + Eq;
+    /// Type of the function which manages the routing table of an instance.
+    /// Only IPv4 instances have one, IPv6 uses the zero-sized `()`.
+    type Routes;
+    /// GUID of the protocol.
+    const GUID: Guid;
+    /// GUID of the service binding protocol.
+    const SERVICE_BINDING_GUID: Guid;
+}
+
+/// Service binding protocol used to create `Tcp` instances.
+pub type TcpServiceBinding<V> = ServiceBinding<Tcp<V>>;
+
+unsafe impl<V: TcpVersion> ChildProtocol for Tcp<V> {
+    const SERVICE_BINDING_GUID: Guid = V::SERVICE_BINDING_GUID;
+}
+
+newtype_enum! {
+/// State of a TCP connection.
+pub enum ConnectionState: u32 => {
+    /// No connection.
+    CLOSED       = 0,
+    /// Waiting for an incoming connection.
+    LISTEN       = 1,
+    /// A connection request was sent.
+    SYN_SENT     = 2,
+    /// A connection request was received and answered.
+    SYN_RECEIVED = 3,
+    /// The connection is open, and data can be exchanged.
+    ESTABLISHED  = 4,
+    /// The connection is being closed locally.
+    FIN_WAIT1    = 5,
+    /// The connection was closed locally, waiting for the remote endpoint to
+    /// close it.
+    FIN_WAIT2    = 6,
+    /// Both endpoints are closing the connection at the same time.
+    CLOSING      = 7,
+    /// The connection is closed, waiting for late packets to expire.
+    TIME_WAIT    = 8,
+    /// The remote endpoint closed the connection.
+    CLOSE_WAIT   = 9,
+    /// Waiting for the acknowledgement of the final close request.
+    LAST_ACK     = 10,
+}}
+
+/// Advanced connection options (`EFI_TCP4_OPTION` and `EFI_TCP6_OPTION`).
+///
+/// Timeouts are in seconds. The current options of an instance can be
+/// obtained with `Tcp::get_mode_data`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct Options {
+    /// Size of the receive buffer.
+    pub receive_buffer_size: u32,
+    /// Size of the send buffer.
+    pub send_buffer_size: u32,
+    /// Maximum number of pending connections of a listening instance.
+    pub max_syn_backlog: u32,
+    /// Timeout for establishing a connection.
+    pub connection_timeout: u32,
+    /// Number of times data is retransmitted before the connection is reset.
+    pub data_retries: u32,
+    /// Time spent in the `FIN_WAIT2` state.
+    pub fin_timeout: u32,
+    /// Time spent in the `TIME_WAIT` state.
+    pub time_wait_timeout: u32,
+    /// Number of unanswered keep-alive probes before the connection is reset.
+    pub keep_alive_probes: u32,
+    /// Idle time before keep-alive probes are sent.
+    pub keep_alive_time: u32,
+    /// Interval between keep-alive probes.
+    pub keep_alive_interval: u32,
+    /// Enable the Nagle algorithm.
+    pub enable_nagle: bool,
+    /// Enable the TCP timestamp option.
+    pub enable_timestamp: bool,
+    /// Enable the TCP window scale option.
+    pub enable_window_scaling: bool,
+    /// Enable selective acknowledgements.
+    pub enable_selective_ack: bool,
+    /// Enable path MTU discovery.
+    pub enable_path_mtu_discovery: bool,
+}
+
+/// Configuration of a `Tcp` instance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConfigData<V: TcpVersion> {
+    /// Fields of the IP header of the packets.
+    pub ip_fields: V::IpFields,
+    /// Endpoints of the connection.
+    pub access_point: V::AccessPoint,
+    /// Advanced options, or `None` to use the defaults of the protocol.
+    pub options: Option<Options>,
+}
+
+impl<V: TcpVersion> ConfigData<V> {
+    /// Creates a configuration for the given endpoints, with the default IP
+    /// header fields and options.
+    pub fn new(access_point: V::AccessPoint) -> Self {
+        Self {
+            ip_fields: V::IpFields::default(),
+            access_point,
+            options: None,
+        }
+    }
+}
+
+/// The `EFI_TCP4_CONFIG_DATA` and `EFI_TCP6_CONFIG_DATA` structures.
+#[repr(C)]
+struct RawConfigData<V: TcpVersion> {
+    ip_fields: V::IpFields,
+    access_point: V::AccessPoint,
+    control_option: *mut Options,
+}
+
+/// Maximum number of fragments in a `TransmitData` or `ReceiveData`.
+pub const MAX_FRAGMENTS: usize = 16;
+
+/// Data to transmit (`EFI_TCP4_TRANSMIT_DATA` and `EFI_TCP6_TRANSMIT_DATA`).
+#[derive(Debug)]
+#[repr(C)]
+pub struct TransmitData<'a> {
+    push: bool,
+    urgent: bool,
+    data_length: u32,
+    fragment_count: u32,
+    fragments: [Fragment<'a>; MAX_FRAGMENTS],
+}
+
+impl<'a> TransmitData<'a> {
+    /// Describes data made of up to `MAX_FRAGMENTS` fragments.
+    ///
+    /// `None` is returned if there are no fragments, too many of them, or if
+    /// the data is too large.
+    pub fn new(fragments: &[Fragment<'a>]) -> Option<Self> {
+        if fragments.is_empty() || fragments.len() > MAX_FRAGMENTS {
+            return None;
+        }
+        let data_length = fragments
+            .iter()
+            .try_fold(0u32, |total, fragment| total.checked_add(fragment.len))?;
+
+        let mut table = [Fragment::EMPTY; MAX_FRAGMENTS];
+        table[..fragments.len()].copy_from_slice(fragments);
+        Some(Self {
+            push: false,
+            urgent: false,
+            data_length,
+            fragment_count: fragments.len() as u32,
+            fragments: table,
+        })
+    }
+
+    /// Sets the push flag, which asks the receiver to deliver the data to
+    /// the application immediately.
+    pub fn set_push(&mut self, push: bool) {
+        self.push = push;
+    }
+
+    /// Sends the data as urgent data.
+    pub fn set_urgent(&mut self, urgent: bool) {
+        self.urgent = urgent;
+    }
+}
+
+/// Buffers in which received data is stored (`EFI_TCP4_RECEIVE_DATA` and
+/// `EFI_TCP6_RECEIVE_DATA`).
+///
+/// Once a reception completes, the protocol updates the length of the data
+/// and of each buffer to what was actually received.
+#[derive(Debug)]
+#[repr(C)]
+pub struct ReceiveData<'a> {
+    urgent: bool,
+    data_length: u32,
+    fragment_count: u32,
+    fragments: [FragmentMut<'a>; MAX_FRAGMENTS],
+}
+
+impl<'a> ReceiveData<'a> {
+    /// Receives data into up to `MAX_FRAGMENTS` buffers.
+    ///
+    /// `None` is returned if there are no buffers, too many of them, or if
+    /// they are too large.
+    pub fn new(buffers: impl IntoIterator<Item = FragmentMut<'a>>) -> Option<Self> {
+        let mut fragments: [FragmentMut<'a>; MAX_FRAGMENTS] = Default::default();
+        let mut count = 0;
+        let mut data_length = 0u32;
+        for buffer in buffers {
+            data_length = data_length.checked_add(buffer.len)?;
+            *fragments.get_mut(count)? = buffer;
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some(Self {
+            urgent: false,
+            data_length,
+            fragment_count: count as u32,
+            fragments,
+        })
+    }
+
+    /// Size of the received data, or of the buffers if nothing was received
+    /// yet.
+    pub fn len(&self) -> usize {
+        self.data_length as usize
+    }
+
+    /// Whether no data was received.
+    pub fn is_empty(&self) -> bool {
+        self.data_length == 0
+    }
+
+    /// Whether the data was received as urgent data.
+    pub fn is_urgent(&self) -> bool {
+        self.urgent
+    }
+}
+
+/// A completion token, used to track an asynchronous operation
+/// (`EFI_TCP4_COMPLETION_TOKEN` and `EFI_TCP6_COMPLETION_TOKEN`).
+///
+/// This is also the token used by `Tcp::connect`.
+#[repr(C)]
+pub struct CompletionToken {
+    event: Event,
+    status: Status,
+}
+
+impl CompletionToken {
+    /// Creates a token which signals `event` on completion.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(event: &Event) -> Self {
+        Self {
+            event: event.unsafe_clone(),
+            status: Status::NOT_READY,
+        }
+    }
+
+    /// Status of the operation, which is `NOT_READY` until it completes.
+    pub fn status(&self) -> Status {
+        // The protocol updates the status behind our back.
+        unsafe { ptr::read_volatile(&self.status) }
+    }
+
+    /// Whether the operation has completed.
+    pub fn is_complete(&self) -> bool {
+        self.status() != Status::NOT_READY
+    }
+}
+
+/// Implements the accessors shared by all the tokens wrapping a
+/// `CompletionToken`.
+macro_rules! completion_token_accessors {
+    ($token:ident) => {
+        impl $token {
+            /// Status of the operation, which is `NOT_READY` until it
+            /// completes.
+            pub fn status(&self) -> Status {
+                self.completion.status()
+            }
+
+            /// Whether the operation has completed.
+            pub fn is_complete(&self) -> bool {
+                self.completion.is_complete()
+            }
+
+            /// The underlying completion token, which can be passed to
+            /// `Tcp::cancel`.
+            pub fn completion_token(&mut self) -> &mut CompletionToken {
+                &mut self.completion
+            }
+        }
+    };
+}
+
+/// A token used to accept a connection (`EFI_TCP4_LISTEN_TOKEN` and
+/// `EFI_TCP6_LISTEN_TOKEN`).
+#[repr(C)]
+pub struct ListenToken {
+    completion: CompletionToken,
+    new_child: Option<Handle>,
+}
+
+impl ListenToken {
+    /// Creates a token which signals `event` on completion.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(event: &Event) -> Self {
+        Self {
+            completion: CompletionToken::new(event),
+            new_child: None,
+        }
+    }
+
+    /// The handle of the `Tcp` instance created for the accepted
+    /// connection, once the token has completed successfully.
+    ///
+    /// The instance is a child of the same service binding as the listening
+    /// instance, and must eventually be destroyed through it.
+    pub fn new_child(&self) -> Option<Handle> {
+        if self.status() != Status::SUCCESS {
+            return None;
+        }
+        unsafe { ptr::read_volatile(&self.new_child) }
+    }
+}
+
+completion_token_accessors!(ListenToken);
+
+/// A token used to transmit or receive data (`EFI_TCP4_IO_TOKEN` and
+/// `EFI_TCP6_IO_TOKEN`).
+#[repr(C)]
+pub struct IoToken {
+    completion: CompletionToken,
+    packet: *mut c_void,
+}
+
+impl IoToken {
+    /// Creates a token which signals `event` on completion.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(event: &Event) -> Self {
+        Self {
+            completion: CompletionToken::new(event),
+            packet: ptr::null_mut(),
+        }
+    }
+}
+
+completion_token_accessors!(IoToken);
+
+/// A token used to close a connection (`EFI_TCP4_CLOSE_TOKEN` and
+/// `EFI_TCP6_CLOSE_TOKEN`).
+#[repr(C)]
+pub struct CloseToken {
+    completion: CompletionToken,
+    abort_on_close: bool,
+}
+
+impl CloseToken {
+    /// Creates a token which signals `event` on completion.
+    ///
+    /// If `abort` is set, the connection is reset instead of being closed
+    /// gracefully, and pending data is discarded.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(event: &Event, abort: bool) -> Self {
+        Self {
+            completion: CompletionToken::new(event),
+            abort_on_close: abort,
+        }
+    }
+}
+
+completion_token_accessors!(CloseToken);
+
+/// The TCP4 and TCP6 protocols
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Tcp<V: TcpVersion> {
+    get_mode_data: unsafe extern "efiapi" fn(
+        this: &Self,
+        state: *mut ConnectionState,
+        config: *mut RawConfigData<V>,
+        ip_mode: *mut c_void,
+        mnp_config: *mut c_void,
+        snp_mode: *mut c_void,
+    ) -> Status,
+    configure: unsafe extern "efiapi" fn(this: &Self, config: *const RawConfigData<V>) -> Status,
+    pub(super) routes: V::Routes,
+    connect: unsafe extern "efiapi" fn(this: &Self, token: *mut CompletionToken) -> Status,
+    accept: unsafe extern "efiapi" fn(this: &Self, token: *mut ListenToken) -> Status,
+    transmit: unsafe extern "efiapi" fn(this: &Self, token: *mut IoToken) -> Status,
+    receive: unsafe extern "efiapi" fn(this: &Self, token: *mut IoToken) -> Status,
+    close: unsafe extern "efiapi" fn(this: &Self, token: *mut CloseToken) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: &Self, token: *mut CompletionToken) -> Status,
+    poll: extern "efiapi" fn(this: &Self) -> Status,
+}
+
+unsafe impl<V: TcpVersion> Identify for Tcp<V> {
+    const GUID: Guid = V::GUID;
+}
+
+impl<V: TcpVersion> Tcp<V> {
+    /// Returns the state of the connection and the configuration of this
+    /// instance.
+    ///
+    /// `NOT_STARTED` is returned if the instance has not been configured.
+    pub fn get_mode_data(&self) -> Result<(ConnectionState, ConfigData<V>)> {
+        let mut state = ConnectionState::CLOSED;
+        let mut options = MaybeUninit::<Options>::zeroed();
+        let mut config = RawConfigData {
+            ip_fields: V::IpFields::default(),
+            access_point: V::AccessPoint::default(),
+            control_option: options.as_mut_ptr(),
+        };
+        unsafe {
+            (self.get_mode_data)(
+                self,
+                &mut state,
+                &mut config,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        }
+        .into_with_val(|| {
+            let config = ConfigData {
+                ip_fields: config.ip_fields,
+                access_point: config.access_point,
+                options: Some(unsafe { options.assume_init() }),
+            };
+            (state, config)
+        })
+    }
+
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// Resetting the instance aborts the connection and cancels all the
+    /// pending operations. A configured instance must be reset before it
+    /// can be configured again.
+    ///
+    /// `NO_MAPPING` is returned if the default address is requested, but the
+    /// interface has not been assigned one yet.
+    pub fn configure(&mut self, config: Option<&ConfigData<V>>) -> Result {
+        let mut options = config.and_then(|config| config.options);
+        let raw = config.map(|config| RawConfigData {
+            ip_fields: config.ip_fields,
+            access_point: config.access_point,
+            control_option: options
+                .as_mut()
+                .map_or(ptr::null_mut(), |options| options as *mut _),
+        });
+        let raw = raw.as_ref().map_or(ptr::null(), |raw| raw as *const _);
+        unsafe { (self.configure)(self, raw) }.into()
+    }
+
+    /// Starts connecting to the remote endpoint of an active instance.
+    /// Completion is reported through the token.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved, modified or freed until it has completed,
+    /// or has been cancelled.
+    pub unsafe fn connect(&mut self, token: &mut CompletionToken) -> Result {
+        (self.connect)(self, token).into()
+    }
+
+    /// Starts waiting for an incoming connection on a passive instance.
+    /// Completion is reported through the token, which then holds the handle
+    /// of a new instance for the connection.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved, modified or freed until it has completed,
+    /// or has been cancelled.
+    pub unsafe fn accept(&mut self, token: &mut ListenToken) -> Result {
+        (self.accept)(self, token).into()
+    }
+
+    /// Queues data for transmission. Completion is reported through the
+    /// token.
+    ///
+    /// # Safety
+    ///
+    /// The token and the data, including the buffers of its fragments, must
+    /// not be moved, modified or freed until the token has completed or has
+    /// been cancelled.
+    pub unsafe fn transmit(&mut self, token: &mut IoToken, data: &TransmitData) -> Result {
+        token.packet = data as *const TransmitData as *mut c_void;
+        (self.transmit)(self, token).into()
+    }
+
+    /// Queues a request to receive data. Completion is reported through the
+    /// token, and the data is stored in the buffers of `data`.
+    ///
+    /// The token completes with `CONNECTION_FIN` once the remote endpoint
+    /// has closed the connection and all the data has been received.
+    ///
+    /// # Safety
+    ///
+    /// The token and the buffers must not be moved, modified or freed until
+    /// the token has completed or has been cancelled.
+    pub unsafe fn receive(&mut self, token: &mut IoToken, data: &mut ReceiveData) -> Result {
+        token.packet = data as *mut ReceiveData as *mut c_void;
+        (self.receive)(self, token).into()
+    }
+
+    /// Starts closing the connection. Completion is reported through the
+    /// token.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved, modified or freed until it has completed.
+    pub unsafe fn close(&mut self, token: &mut CloseToken) -> Result {
+        (self.close)(self, token).into()
+    }
+
+    /// Cancels a pending operation, or all of them if `token` is `None`.
+    ///
+    /// The tokens of the cancelled operations complete with `ABORTED`.
+    pub fn cancel(&mut self, token: Option<&mut CompletionToken>) -> Result {
+        let token = token.map_or(ptr::null_mut(), |token| token as *mut _);
+        unsafe { (self.cancel)(self, token) }.into()
+    }
+
+    /// Polls the network interface for received packets and finished
+    /// transmissions.
+    ///
+    /// This is not required for the operations to make progress, but can be
+    /// used to speed them up.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+
+    /// Connects to the remote endpoint of an active instance, and waits for
+    /// the connection to be established.
+    pub fn connect_blocking(&mut self, bt: &BootServices) -> Result {
+        with_event(bt, |event| {
+            let mut token = unsafe { CompletionToken::new(event) };
+            unsafe { self.connect(&mut token) }?.log();
+            self.wait(bt, &mut token)
+        })
+    }
+
+    /// Waits for an incoming connection on a passive instance, and returns
+    /// the handle of the new instance created for it.
+    pub fn accept_blocking(&mut self, bt: &BootServices) -> Result<Handle> {
+        with_event(bt, |event| {
+            let mut token = unsafe { ListenToken::new(event) };
+            unsafe { self.accept(&mut token) }?.log();
+            self.wait(bt, token.completion_token())?.log();
+            Ok(token
+                .new_child()
+                .expect("accept succeeded without a child handle")
+                .into())
+        })
+    }
+
+    /// Sends all of `data`, and waits until it has been queued for
+    /// transmission.
+    pub fn send_all(&mut self, bt: &BootServices, data: &[u8]) -> Result {
+        with_event(bt, |event| {
+            // The length of a transmission is limited to 32 bits.
+            for chunk in data.chunks(u32::MAX as usize) {
+                let fragment = Fragment::new(chunk).unwrap();
+                let mut data = TransmitData::new(slice::from_ref(&fragment)).unwrap();
+                data.set_push(true);
+                let mut token = unsafe { IoToken::new(event) };
+                unsafe { self.transmit(&mut token, &data) }?.log();
+                self.wait(bt, token.completion_token())?.log();
+            }
+            Ok(().into())
+        })
+    }
+
+    /// Receives data into `buffer`, and returns its size.
+    ///
+    /// This waits until some data is received, and returns zero once the
+    /// remote endpoint has closed the connection.
+    pub fn recv(&mut self, bt: &BootServices, buffer: &mut [u8]) -> Result<usize> {
+        let len = buffer.len().min(u32::MAX as usize);
+        let fragment = FragmentMut::new(&mut buffer[..len]).unwrap();
+        let mut data = ReceiveData::new(Some(fragment)).unwrap();
+        with_event(bt, |event| {
+            let mut token = unsafe { IoToken::new(event) };
+            unsafe { self.receive(&mut token, &mut data) }?.log();
+            match self.wait(bt, token.completion_token()) {
+                Ok(completion) => Ok(completion.map(|()| data.len())),
+                Err(err) if err.status() == Status::CONNECTION_FIN => Ok(0.into()),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Closes the connection, and waits until it is closed.
+    ///
+    /// If `abort` is set, the connection is reset instead of being closed
+    /// gracefully, and pending data is discarded.
+    pub fn close_blocking(&mut self, bt: &BootServices, abort: bool) -> Result {
+        with_event(bt, |event| {
+            let mut token = unsafe { CloseToken::new(event, abort) };
+            unsafe { self.close(&mut token) }?.log();
+            self.wait(bt, token.completion_token())
+        })
+    }
+
+    /// Waits until the token completes, and returns its status.
+    ///
+    /// If waiting fails, the operation is cancelled, so that the token can
+    /// be freed.
+    fn wait(&mut self, bt: &BootServices, token: &mut CompletionToken) -> Result {
+        if let Err(err) = bt.wait_for_event(slice::from_ref(&token.event)) {
+            self.cancel(Some(token))?.log();
+            return Err(err.status().into());
+        }
+        token.status().into()
+    }
+}
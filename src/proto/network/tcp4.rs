@@ -0,0 +1,112 @@
+//! TCP over IPv4 protocol.
+//!
+//! Each instance of the protocol handles one TCP connection. Instances are
+//! obtained by creating a child handle through the `Tcp4ServiceBinding`
+//! protocol, which is installed on the handle of the network interface.
+//!
+//! Most of the protocol is shared with TCP over IPv6, and is defined in the
+//! `tcp` module.
+
+use super::tcp::{self, Tcp, TcpServiceBinding, TcpVersion};
+use super::V4;
+use crate::data_types::Ipv4Address;
+use crate::{Guid, Result, Status};
+
+pub use super::tcp::{
+    CloseToken, CompletionToken, ConnectionState, IoToken, ListenToken, Options, ReceiveData,
+    TransmitData, MAX_FRAGMENTS,
+};
+
+/// The TCP4 protocol
+pub type Tcp4 = Tcp<V4>;
+
+/// Service binding protocol used to create `Tcp4` instances.
+pub type Tcp4ServiceBinding = TcpServiceBinding<V4>;
+
+/// Configuration of a `Tcp4` instance.
+pub type ConfigData = tcp::ConfigData<V4>;
+
+/// Type of the function which manages the routing table of a `Tcp4`
+/// instance.
+pub type RoutesFn = extern "efiapi" fn(
+    this: &Tcp4,
+    delete_route: bool,
+    subnet_address: &Ipv4Address,
+    subnet_mask: &Ipv4Address,
+    gateway_address: &Ipv4Address,
+) -> Status;
+
+impl TcpVersion for V4 {
+    type IpFields = IpFields;
+    type AccessPoint = AccessPoint;
+    type Routes = RoutesFn;
+    const GUID: Guid = guid!("65530bc7-a359-410f-b010-5aadc7ec2b62");
+    const SERVICE_BINDING_GUID: Guid = guid!("00720665-67eb-4a99-baf7-d3c33a1c7cc9");
+}
+
+/// Fields of the IPv4 header of the packets sent by a `Tcp4` instance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct IpFields {
+    /// Type of service field.
+    pub type_of_service: u8,
+    /// Time to live field.
+    pub time_to_live: u8,
+}
+
+impl Default for IpFields {
+    fn default() -> Self {
+        Self {
+            type_of_service: 0,
+            time_to_live: 64,
+        }
+    }
+}
+
+/// The local and remote endpoints of a connection (`EFI_TCP4_ACCESS_POINT`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct AccessPoint {
+    /// Use the default address of the interface, as configured by DHCP or
+    /// the `Ip4Config2` protocol, instead of `station_address`.
+    pub use_default_address: bool,
+    /// Local address.
+    pub station_address: Ipv4Address,
+    /// Subnet mask of the local address.
+    pub subnet_mask: Ipv4Address,
+    /// Local port. Zero picks an ephemeral port for active connections.
+    pub station_port: u16,
+    /// Remote address. For passive connections, zero accepts connections
+    /// from any address.
+    pub remote_address: Ipv4Address,
+    /// Remote port. For passive connections, zero accepts connections from
+    /// any port.
+    pub remote_port: u16,
+    /// Actively connect to the remote endpoint, instead of listening for
+    /// incoming connections.
+    pub active: bool,
+}
+
+impl Tcp4 {
+    /// Adds a route to the routing table of this instance.
+    ///
+    /// A zero subnet address and mask add a default route.
+    pub fn add_route(
+        &mut self,
+        subnet_address: &Ipv4Address,
+        subnet_mask: &Ipv4Address,
+        gateway_address: &Ipv4Address,
+    ) -> Result {
+        (self.routes)(self, false, subnet_address, subnet_mask, gateway_address).into()
+    }
+
+    /// Deletes a route from the routing table of this instance.
+    pub fn delete_route(
+        &mut self,
+        subnet_address: &Ipv4Address,
+        subnet_mask: &Ipv4Address,
+        gateway_address: &Ipv4Address,
+    ) -> Result {
+        (self.routes)(self, true, subnet_address, subnet_mask, gateway_address).into()
+    }
+}
@@ -0,0 +1,72 @@
+//! TCP over IPv6 protocol.
+//!
+//! This works like TCP over IPv4, except for the endpoints, and for the
+//! routing table which is managed by the IPv6 layer. The protocol is defined
+//! in the `tcp` module.
+
+use super::tcp::{self, Tcp, TcpServiceBinding, TcpVersion};
+use super::V6;
+use crate::data_types::Ipv6Address;
+use crate::Guid;
+
+pub use super::tcp::{
+    CloseToken, CompletionToken, ConnectionState, IoToken, ListenToken, Options, ReceiveData,
+    TransmitData, MAX_FRAGMENTS,
+};
+
+/// The TCP6 protocol
+pub type Tcp6 = Tcp<V6>;
+
+/// Service binding protocol used to create `Tcp6` instances.
+pub type Tcp6ServiceBinding = TcpServiceBinding<V6>;
+
+/// Configuration of a `Tcp6` instance.
+pub type ConfigData = tcp::ConfigData<V6>;
+
+impl TcpVersion for V6 {
+    type IpFields = IpFields;
+    type AccessPoint = AccessPoint;
+    type Routes = ();
+    const GUID: Guid = guid!("46e44855-bd60-4ab7-ab0d-a679b9447d77");
+    const SERVICE_BINDING_GUID: Guid = guid!("ec20eb79-6c1a-4664-9a0d-d2e4cc16d664");
+}
+
+/// Fields of the IPv6 header of the packets sent by a `Tcp6` instance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct IpFields {
+    /// Traffic class field.
+    pub traffic_class: u8,
+    /// Hop limit field.
+    pub hop_limit: u8,
+}
+
+impl Default for IpFields {
+    fn default() -> Self {
+        Self {
+            traffic_class: 0,
+            hop_limit: 64,
+        }
+    }
+}
+
+/// The local and remote endpoints of a connection (`EFI_TCP6_ACCESS_POINT`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct AccessPoint {
+    /// Local address. Zero picks an address of the interface for active
+    /// connections, and accepts connections to any address for passive
+    /// ones.
+    pub station_address: Ipv6Address,
+    /// Local port. Zero picks an ephemeral port for active connections.
+    pub station_port: u16,
+    /// Remote address. For passive connections, zero accepts connections
+    /// from any address.
+    pub remote_address: Ipv6Address,
+    /// Remote port. For passive connections, zero accepts connections from
+    /// any port.
+    pub remote_port: u16,
+    /// Actively connect to the remote endpoint, instead of listening for
+    /// incoming connections.
+    pub active: bool,
+}
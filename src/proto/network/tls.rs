@@ -0,0 +1,155 @@
+//! TLS Configuration protocol.
+//!
+//! This protocol sets the certificates and keys used by a TLS session. It is
+//! installed on the children of the `TlsServiceBinding` protocol, along with
+//! the TLS protocol itself. HTTPS boot creates such a child for each HTTP
+//! instance, and configures it with the CA certificates stored in the
+//! `TlsCaCertificate` variable.
+//!
+//! The firmware expects a single DER-encoded X.509 certificate for each
+//! `set_data` call, while the variable holds signature lists of
+//! certificates, which can be built with `ca_certificate_list`.
+
+use crate::proto::pkcs7::{SignatureData, SignatureList, CERT_X509_GUID};
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+#[cfg(feature = "exts")]
+use crate::{
+    alloc_api::{vec, vec::Vec},
+    ResultExt,
+};
+use crate::{Error, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+
+/// Service binding protocol used to create TLS sessions, which carry the
+/// `TlsConfiguration` protocol.
+pub type TlsServiceBinding = ServiceBinding<TlsConfiguration>;
+
+unsafe impl ChildProtocol for TlsConfiguration {
+    const SERVICE_BINDING_GUID: Guid = guid!("952cb795-ff36-48cf-a249-4df486d6ab8d");
+}
+
+/// GUID of the variable holding the CA certificates used by HTTPS boot.
+pub const TLS_CA_CERTIFICATE_GUID: Guid = guid!("fd2340d0-3dab-4349-a6c7-3b4f12b48eae");
+
+newtype_enum! {
+/// Kind of configuration data (`EFI_TLS_CONFIG_DATA_TYPE`).
+pub enum TlsConfigDataType: u32 => {
+    /// Certificate of this host, as DER-encoded X.509, used for client
+    /// authentication.
+    HOST_PUBLIC_CERT     = 0,
+    /// Private key of this host, as DER or PEM.
+    HOST_PRIVATE_KEY     = 1,
+    /// A trusted CA certificate, as DER-encoded X.509. Each call adds one
+    /// certificate.
+    CA_CERTIFICATE       = 2,
+    /// A certificate revocation list.
+    CERT_REVOCATION_LIST = 3,
+}}
+
+/// Writes a signature list holding one DER-encoded X.509 certificate to
+/// `buffer`, in the format of the `TlsCaCertificate` variable.
+///
+/// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with the
+/// required size.
+///
+/// ```
+/// use uefi::proto::network::tls::ca_certificate_list;
+/// use uefi::proto::pkcs7::{SignatureList, CERT_X509_GUID};
+/// use uefi::Guid;
+///
+/// let owner = Guid::from_values(0x12345678, 0x9abc, 0xdef0, 0x1234, [0; 6]);
+/// let certificate = [0x30, 0x03, 0x02, 0x01, 0x00];
+///
+/// let mut buffer = [0; 64];
+/// let list = ca_certificate_list(&mut buffer, owner, &certificate)
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(list.as_bytes().len(), SignatureList::HEADER_SIZE + 16 + 5);
+/// assert_eq!(list.signature_type(), CERT_X509_GUID);
+/// ```
+pub fn ca_certificate_list<'buf>(
+    buffer: &'buf mut [u8],
+    owner: Guid,
+    certificate: &[u8],
+) -> Result<&'buf SignatureList, Option<usize>> {
+    SignatureList::build(
+        buffer,
+        CERT_X509_GUID,
+        &[SignatureData {
+            owner,
+            data: certificate,
+        }],
+    )
+}
+
+/// The TLS Configuration protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct TlsConfiguration {
+    set_data: unsafe extern "efiapi" fn(
+        this: &mut TlsConfiguration,
+        data_type: TlsConfigDataType,
+        data: *const c_void,
+        data_size: usize,
+    ) -> Status,
+    get_data: unsafe extern "efiapi" fn(
+        this: &TlsConfiguration,
+        data_type: TlsConfigDataType,
+        data: *mut c_void,
+        data_size: &mut usize,
+    ) -> Status,
+}
+
+unsafe impl Identify for TlsConfiguration {
+    const GUID: Guid = guid!("1682fe44-bd7a-4407-b7c7-dca37ca3922d");
+}
+
+impl TlsConfiguration {
+    /// Sets some configuration data.
+    ///
+    /// `INVALID_PARAMETER` is returned if the data is malformed, and
+    /// `UNSUPPORTED` if the firmware does not support this kind of data.
+    pub fn set_data(&mut self, data_type: TlsConfigDataType, data: &[u8]) -> Result {
+        unsafe { (self.set_data)(self, data_type, data.as_ptr().cast(), data.len()) }.into()
+    }
+
+    /// Reads some configuration data into `buffer`, and returns its size.
+    ///
+    /// If the buffer is too small, a `BUFFER_TOO_SMALL` error is returned,
+    /// along with the required size. `NOT_FOUND` is returned if the data was
+    /// not set. Firmware usually does not allow reading the private key and
+    /// the CA certificates back, in which case `UNSUPPORTED` is returned.
+    pub fn get_data(
+        &self,
+        data_type: TlsConfigDataType,
+        buffer: &mut [u8],
+    ) -> Result<usize, Option<usize>> {
+        let mut size = buffer.len();
+        let status =
+            unsafe { (self.get_data)(self, data_type, buffer.as_mut_ptr().cast(), &mut size) };
+        match status {
+            Status::BUFFER_TOO_SMALL => Err(Error::new(status, Some(size))),
+            status => status.into_with(|| size, |_| None),
+        }
+    }
+
+    /// Reads some configuration data into a newly allocated buffer.
+    #[cfg(feature = "exts")]
+    pub fn get_data_to_vec(&self, data_type: TlsConfigDataType) -> Result<Vec<u8>> {
+        let size = match self.get_data(data_type, &mut []) {
+            Ok(size) => size.log(),
+            Err(err) => match err.data() {
+                Some(size) => *size,
+                None => return Err(err.status().into()),
+            },
+        };
+        let mut buffer = vec![0; size];
+        self.get_data(data_type, &mut buffer)
+            .discard_errdata()
+            .map_inner(|size| {
+                buffer.truncate(size);
+                buffer
+            })
+    }
+}
@@ -0,0 +1,424 @@
+//! UDP protocol, over IPv4 or IPv6.
+//!
+//! Instances of the protocol are obtained by creating a child handle through
+//! the `UdpServiceBinding` protocol, which is installed on the handle of the
+//! network interface.
+//!
+//! Transmissions and receptions are asynchronous: they take a token, which
+//! is updated and signaled by the protocol once they complete. For simple
+//! uses, `send_to` and `recv_from` create their own token and wait for its
+//! completion.
+//!
+//! The protocol is generic over the IP version, which selects its GUID, the
+//! type of its addresses and its configuration. The `udp4` and `udp6`
+//! modules provide the definitions specific to each version.
+
+use super::{with_event, Fragment, IpVersion};
+use crate::proto::service_binding::{ChildProtocol, ServiceBinding};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::table::runtime::Time;
+use crate::{Completion, Event, Guid, Identify, Result, Status};
+use core::ffi::c_void;
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+use core::{ptr, slice};
+
+/// A version of IP over which the UDP protocol runs.
+pub trait UdpVersion: IpVersion {
+    /// Configuration of an instance.
+    type ConfigData: Copy + Debug + Default + Eq;
+    /// Type of the function which manages the routing table of an instance.
+    /// Only IPv4 instances have one, IPv6 uses the zero-sized `()`.
+    type Routes;
+    /// Type of the gateway of a transmission. Only IPv4 transmissions can
+    /// override the routing table, IPv6 uses the zero-sized `()`.
+    type Gateway: Copy + Debug;
+    /// Gateway of a transmission which uses the routing table.
+    const DEFAULT_GATEWAY: Self::Gateway;
+    /// GUID of the protocol.
+    const GUID: Guid;
+    /// GUID of the service binding protocol.
+    const SERVICE_BINDING_GUID: Guid;
+}
+
+/// Service binding protocol used to create `Udp` instances.
+pub type UdpServiceBinding<V> = ServiceBinding<Udp<V>>;
+
+unsafe impl<V: UdpVersion> ChildProtocol for Udp<V> {
+    const SERVICE_BINDING_GUID: Guid = V::SERVICE_BINDING_GUID;
+}
+
+/// Endpoints of a datagram (`EFI_UDP4_SESSION_DATA` and
+/// `EFI_UDP6_SESSION_DATA`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct SessionData<A> {
+    /// Source address. When transmitting, zero uses the local address.
+    pub source_address: A,
+    /// Source port. When transmitting, zero uses the local port.
+    pub source_port: u16,
+    /// Destination address.
+    pub destination_address: A,
+    /// Destination port.
+    pub destination_port: u16,
+}
+
+/// Maximum number of fragments in a `TransmitData`.
+pub const MAX_FRAGMENTS: usize = 16;
+
+/// A datagram to transmit (`EFI_UDP4_TRANSMIT_DATA` and
+/// `EFI_UDP6_TRANSMIT_DATA`).
+#[derive(Debug)]
+#[repr(C)]
+pub struct TransmitData<'a, V: UdpVersion> {
+    session: *const SessionData<V::Address>,
+    pub(super) gateway: V::Gateway,
+    data_length: u32,
+    fragment_count: u32,
+    fragments: [Fragment<'a>; MAX_FRAGMENTS],
+    _session: PhantomData<&'a SessionData<V::Address>>,
+}
+
+impl<'a, V: UdpVersion> TransmitData<'a, V> {
+    /// Describes a datagram made of up to `MAX_FRAGMENTS` fragments.
+    ///
+    /// If `session` is `None`, the datagram is sent to the remote endpoint
+    /// of the configuration. `None` is returned if there are no fragments,
+    /// too many of them, or if the datagram is too large.
+    pub fn new(
+        session: Option<&'a SessionData<V::Address>>,
+        fragments: &[Fragment<'a>],
+    ) -> Option<Self> {
+        if fragments.is_empty() || fragments.len() > MAX_FRAGMENTS {
+            return None;
+        }
+        let data_length = fragments
+            .iter()
+            .try_fold(0u32, |total, fragment| total.checked_add(fragment.len))?;
+
+        let mut table = [Fragment::EMPTY; MAX_FRAGMENTS];
+        table[..fragments.len()].copy_from_slice(fragments);
+        Some(Self {
+            session: session.map_or(ptr::null(), |session| session as *const _),
+            gateway: V::DEFAULT_GATEWAY,
+            data_length,
+            fragment_count: fragments.len() as u32,
+            fragments: table,
+            _session: PhantomData,
+        })
+    }
+}
+
+/// The `EFI_UDP4_RECEIVE_DATA` and `EFI_UDP6_RECEIVE_DATA` structures.
+#[repr(C)]
+struct ReceiveData<A> {
+    timestamp: Time,
+    recycle_signal: Event,
+    session: SessionData<A>,
+    data_length: u32,
+    fragment_count: u32,
+    // This is actually an array of `fragment_count` fragments.
+    fragment_table: [Fragment<'static>; 1],
+}
+
+/// A datagram received by the `Udp` protocol.
+///
+/// The datagram is stored in buffers owned by the protocol, which are given
+/// back to it when this is dropped.
+pub struct ReceivedDatagram<'a, A> {
+    data: &'a ReceiveData<A>,
+    bt: &'a BootServices,
+}
+
+impl<'a, A> ReceivedDatagram<'a, A> {
+    /// Time at which the datagram was received.
+    pub fn timestamp(&self) -> &Time {
+        &self.data.timestamp
+    }
+
+    /// Endpoints of the datagram.
+    pub fn session(&self) -> &SessionData<A> {
+        &self.data.session
+    }
+
+    /// Size of the datagram.
+    pub fn len(&self) -> usize {
+        self.data.data_length as usize
+    }
+
+    /// Whether the datagram is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.data_length == 0
+    }
+
+    /// The fragments making up the datagram.
+    pub fn fragments(&self) -> impl Iterator<Item = &[u8]> {
+        let table = unsafe {
+            slice::from_raw_parts(
+                self.data.fragment_table.as_ptr(),
+                self.data.fragment_count as usize,
+            )
+        };
+        table.iter().map(|fragment| unsafe {
+            slice::from_raw_parts(fragment.buffer, fragment.len as usize)
+        })
+    }
+
+    /// Copies as much of the datagram as fits into `buffer`, and returns the
+    /// number of bytes copied.
+    pub fn copy_to(&self, buffer: &mut [u8]) -> usize {
+        let mut copied = 0;
+        for fragment in self.fragments() {
+            let len = fragment.len().min(buffer.len() - copied);
+            buffer[copied..copied + len].copy_from_slice(&fragment[..len]);
+            copied += len;
+        }
+        copied
+    }
+}
+
+impl<A: Debug> Debug for ReceivedDatagram<'_, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReceivedDatagram")
+            .field("session", self.session())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<A> Drop for ReceivedDatagram<'_, A> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = self.bt.signal_event(&self.data.recycle_signal);
+    }
+}
+
+/// A completion token, used to track an asynchronous transmission or
+/// reception (`EFI_UDP4_COMPLETION_TOKEN` and `EFI_UDP6_COMPLETION_TOKEN`).
+#[repr(C)]
+pub struct CompletionToken {
+    event: Event,
+    status: Status,
+    packet: *mut c_void,
+}
+
+impl CompletionToken {
+    /// Creates a token which signals `event` on completion.
+    ///
+    /// # Safety
+    ///
+    /// The event must stay open as long as the token is in use.
+    pub unsafe fn new(event: &Event) -> Self {
+        Self {
+            event: event.unsafe_clone(),
+            status: Status::NOT_READY,
+            packet: ptr::null_mut(),
+        }
+    }
+
+    /// Status of the operation, which is `NOT_READY` until it completes.
+    pub fn status(&self) -> Status {
+        // The protocol updates the status behind our back.
+        unsafe { ptr::read_volatile(&self.status) }
+    }
+
+    /// Whether the operation has completed.
+    pub fn is_complete(&self) -> bool {
+        self.status() != Status::NOT_READY
+    }
+
+    /// Takes the received datagram, once a reception has completed
+    /// successfully.
+    ///
+    /// The datagram borrows the token, which cannot be reused until the
+    /// datagram has been dropped. `A` must be the address type of the
+    /// instance which received the datagram.
+    pub fn take_datagram<'a, A>(
+        &'a mut self,
+        bt: &'a BootServices,
+    ) -> Option<ReceivedDatagram<'a, A>> {
+        if self.status() != Status::SUCCESS {
+            return None;
+        }
+        let packet = unsafe { ptr::read_volatile(&self.packet) };
+        self.packet = ptr::null_mut();
+        let data = unsafe { (packet as *const ReceiveData<A>).as_ref()? };
+        Some(ReceivedDatagram { data, bt })
+    }
+}
+
+/// The UDP4 and UDP6 protocols
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Udp<V: UdpVersion> {
+    get_mode_data: unsafe extern "efiapi" fn(
+        this: &Self,
+        config: *mut V::ConfigData,
+        ip_mode: *mut c_void,
+        mnp_config: *mut c_void,
+        snp_mode: *mut c_void,
+    ) -> Status,
+    configure: unsafe extern "efiapi" fn(this: &Self, config: *const V::ConfigData) -> Status,
+    groups: unsafe extern "efiapi" fn(
+        this: &Self,
+        join: bool,
+        multicast_address: *const V::Address,
+    ) -> Status,
+    pub(super) routes: V::Routes,
+    transmit: unsafe extern "efiapi" fn(this: &Self, token: *mut CompletionToken) -> Status,
+    receive: unsafe extern "efiapi" fn(this: &Self, token: *mut CompletionToken) -> Status,
+    cancel: unsafe extern "efiapi" fn(this: &Self, token: *mut CompletionToken) -> Status,
+    poll: extern "efiapi" fn(this: &Self) -> Status,
+}
+
+unsafe impl<V: UdpVersion> Identify for Udp<V> {
+    const GUID: Guid = V::GUID;
+}
+
+impl<V: UdpVersion> Udp<V> {
+    /// Returns the configuration of this instance.
+    ///
+    /// `NOT_STARTED` is returned if the instance has not been configured.
+    pub fn get_mode_data(&self) -> Result<V::ConfigData> {
+        let mut config = V::ConfigData::default();
+        unsafe {
+            (self.get_mode_data)(
+                self,
+                &mut config,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        }
+        .into_with_val(|| config)
+    }
+
+    /// Configures this instance, or resets it if `config` is `None`.
+    ///
+    /// Resetting the instance cancels all the pending operations, and leaves
+    /// all the multicast groups.
+    ///
+    /// `NO_MAPPING` is returned if the default address is requested, but the
+    /// interface has not been assigned one yet.
+    pub fn configure(&mut self, config: Option<&V::ConfigData>) -> Result {
+        let config = config.map_or(ptr::null(), |config| config as *const _);
+        unsafe { (self.configure)(self, config) }.into()
+    }
+
+    /// Joins a multicast group.
+    pub fn join_group(&mut self, address: &V::Address) -> Result {
+        unsafe { (self.groups)(self, true, address) }.into()
+    }
+
+    /// Leaves a multicast group, or all of them if `address` is `None`.
+    pub fn leave_group(&mut self, address: Option<&V::Address>) -> Result {
+        let address = address.map_or(ptr::null(), |address| address as *const _);
+        unsafe { (self.groups)(self, false, address) }.into()
+    }
+
+    /// Queues a datagram for transmission. Completion is reported through
+    /// the token.
+    ///
+    /// # Safety
+    ///
+    /// The token and the datagram, including its session data and the
+    /// buffers of its fragments, must not be moved, modified or freed until
+    /// the token has completed or has been cancelled.
+    pub unsafe fn transmit(
+        &mut self,
+        token: &mut CompletionToken,
+        data: &TransmitData<V>,
+    ) -> Result {
+        token.packet = data as *const TransmitData<V> as *mut c_void;
+        (self.transmit)(self, token).into()
+    }
+
+    /// Queues a request to receive a datagram. Completion is reported
+    /// through the token, from which the datagram can then be taken.
+    ///
+    /// # Safety
+    ///
+    /// The token must not be moved, modified or freed until it has completed,
+    /// or has been cancelled.
+    pub unsafe fn receive(&mut self, token: &mut CompletionToken) -> Result {
+        token.packet = ptr::null_mut();
+        (self.receive)(self, token).into()
+    }
+
+    /// Cancels a pending operation, or all of them if `token` is `None`.
+    ///
+    /// The tokens of the cancelled operations complete with `ABORTED`.
+    pub fn cancel(&mut self, token: Option<&mut CompletionToken>) -> Result {
+        let token = token.map_or(ptr::null_mut(), |token| token as *mut _);
+        unsafe { (self.cancel)(self, token) }.into()
+    }
+
+    /// Polls the network interface for received packets and finished
+    /// transmissions.
+    ///
+    /// This is not required for the operations to make progress, but can be
+    /// used to speed them up.
+    pub fn poll(&mut self) -> Result {
+        (self.poll)(self).into()
+    }
+
+    /// Sends `data` as a single datagram to `address` and `port`, and waits
+    /// until it has been transmitted.
+    pub fn send_to(
+        &mut self,
+        bt: &BootServices,
+        data: &[u8],
+        address: V::Address,
+        port: u16,
+    ) -> Result {
+        let session = SessionData {
+            destination_address: address,
+            destination_port: port,
+            ..SessionData::default()
+        };
+        let fragment = Fragment::new(data).ok_or(Status::BAD_BUFFER_SIZE)?;
+        let data = TransmitData::new(Some(&session), slice::from_ref(&fragment))
+            .ok_or(Status::BAD_BUFFER_SIZE)?;
+        with_event(bt, |event| {
+            let mut token = unsafe { CompletionToken::new(event) };
+            unsafe { self.transmit(&mut token, &data) }?.log();
+            self.wait(bt, &mut token)
+        })
+    }
+
+    /// Receives a datagram into `buffer`, and returns the number of bytes
+    /// stored in the buffer and the endpoints of the datagram.
+    ///
+    /// Datagrams larger than the buffer are truncated. This waits until a
+    /// datagram is received, or until the receive timeout of the
+    /// configuration expires, in which case `TIMEOUT` is returned.
+    pub fn recv_from(
+        &mut self,
+        bt: &BootServices,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SessionData<V::Address>)> {
+        with_event(bt, |event| {
+            let mut token = unsafe { CompletionToken::new(event) };
+            unsafe { self.receive(&mut token) }?.log();
+            let (status, ()) = self.wait(bt, &mut token)?.split();
+            let datagram = token
+                .take_datagram::<V::Address>(bt)
+                .expect("receive succeeded without a datagram");
+            let len = datagram.copy_to(buffer);
+            Ok(Completion::new(status, (len, *datagram.session())))
+        })
+    }
+
+    /// Waits until the token completes, and returns its status.
+    ///
+    /// If waiting fails, the operation is cancelled, so that the token can
+    /// be freed.
+    fn wait(&mut self, bt: &BootServices, token: &mut CompletionToken) -> Result {
+        if let Err(err) = bt.wait_for_event(slice::from_ref(&token.event)) {
+            self.cancel(Some(token))?.log();
+            return Err(err.status().into());
+        }
+        token.status().into()
+    }
+}
@@ -0,0 +1,146 @@
+//! UDP over IPv4 protocol.
+//!
+//! Instances of the protocol are obtained by creating a child handle through
+//! the `Udp4ServiceBinding` protocol, which is installed on the handle of the
+//! network interface.
+//!
+//! Most of the protocol is shared with UDP over IPv6, and is defined in the
+//! `udp` module.
+
+use super::udp::{self, Udp, UdpServiceBinding, UdpVersion};
+use super::V4;
+use crate::data_types::Ipv4Address;
+use crate::{Guid, Result, Status};
+use core::ptr;
+
+pub use super::udp::{CompletionToken, MAX_FRAGMENTS};
+
+/// The UDP4 protocol
+pub type Udp4 = Udp<V4>;
+
+/// Service binding protocol used to create `Udp4` instances.
+pub type Udp4ServiceBinding = UdpServiceBinding<V4>;
+
+/// Endpoints of an IPv4 datagram.
+pub type SessionData = udp::SessionData<Ipv4Address>;
+
+/// An IPv4 datagram to transmit.
+pub type TransmitData<'a> = udp::TransmitData<'a, V4>;
+
+/// An IPv4 datagram received by the `Udp4` protocol.
+pub type ReceivedDatagram<'a> = udp::ReceivedDatagram<'a, Ipv4Address>;
+
+/// Type of the function which manages the routing table of a `Udp4`
+/// instance.
+pub type RoutesFn = extern "efiapi" fn(
+    this: &Udp4,
+    delete_route: bool,
+    subnet_address: &Ipv4Address,
+    subnet_mask: &Ipv4Address,
+    gateway_address: &Ipv4Address,
+) -> Status;
+
+impl UdpVersion for V4 {
+    type ConfigData = ConfigData;
+    type Routes = RoutesFn;
+    type Gateway = *const Ipv4Address;
+    const DEFAULT_GATEWAY: Self::Gateway = ptr::null();
+    const GUID: Guid = guid!("3ad9df29-4501-478d-b1f8-7f7fe70e50f3");
+    const SERVICE_BINDING_GUID: Guid = guid!("83f01464-99bd-45e5-b383-af6305d8e9e6");
+}
+
+/// Configuration of a `Udp4` instance (`EFI_UDP4_CONFIG_DATA`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct ConfigData {
+    /// Receive datagrams sent to the broadcast address.
+    pub accept_broadcast: bool,
+    /// Receive all datagrams, whatever their destination.
+    pub accept_promiscuous: bool,
+    /// Receive datagrams sent to any port.
+    pub accept_any_port: bool,
+    /// Allow other instances to use the same local port.
+    pub allow_duplicate_port: bool,
+    /// Type of service field of the IP packets.
+    pub type_of_service: u8,
+    /// Time to live field of the IP packets.
+    pub time_to_live: u8,
+    /// Set the "don't fragment" flag of the IP packets.
+    pub do_not_fragment: bool,
+    /// Time after which receptions fail with `TIMEOUT`, in microseconds.
+    /// Zero means that they never time out.
+    pub receive_timeout: u32,
+    /// Time after which transmissions fail with `TIMEOUT`, in microseconds.
+    /// Zero means that they never time out.
+    pub transmit_timeout: u32,
+    /// Use the default address of the interface, as configured by DHCP or
+    /// the `Ip4Config2` protocol, instead of `station_address`.
+    pub use_default_address: bool,
+    /// Local address.
+    pub station_address: Ipv4Address,
+    /// Subnet mask of the local address.
+    pub subnet_mask: Ipv4Address,
+    /// Local port. Zero picks an ephemeral port.
+    pub station_port: u16,
+    /// Remote address. Zero allows exchanging datagrams with any address, in
+    /// which case the destination must be given for each transmission.
+    pub remote_address: Ipv4Address,
+    /// Remote port. Zero allows exchanging datagrams with any port.
+    pub remote_port: u16,
+}
+
+impl Default for ConfigData {
+    /// Creates a configuration with a time to live of 64, using the default
+    /// address of the interface and an ephemeral port.
+    fn default() -> Self {
+        Self {
+            accept_broadcast: false,
+            accept_promiscuous: false,
+            accept_any_port: false,
+            allow_duplicate_port: false,
+            type_of_service: 0,
+            time_to_live: 64,
+            do_not_fragment: false,
+            receive_timeout: 0,
+            transmit_timeout: 0,
+            use_default_address: true,
+            station_address: Ipv4Address::UNSPECIFIED,
+            subnet_mask: Ipv4Address::UNSPECIFIED,
+            station_port: 0,
+            remote_address: Ipv4Address::UNSPECIFIED,
+            remote_port: 0,
+        }
+    }
+}
+
+impl<'a> TransmitData<'a> {
+    /// Sends the datagram through `gateway`, instead of using the routing
+    /// table.
+    pub fn set_gateway(&mut self, gateway: &'a Ipv4Address) {
+        self.gateway = gateway;
+    }
+}
+
+impl Udp4 {
+    /// Adds a route to the routing table of this instance.
+    ///
+    /// A zero subnet address and mask add a default route.
+    pub fn add_route(
+        &mut self,
+        subnet_address: &Ipv4Address,
+        subnet_mask: &Ipv4Address,
+        gateway_address: &Ipv4Address,
+    ) -> Result {
+        (self.routes)(self, false, subnet_address, subnet_mask, gateway_address).into()
+    }
+
+    /// Deletes a route from the routing table of this instance.
+    pub fn delete_route(
+        &mut self,
+        subnet_address: &Ipv4Address,
+        subnet_mask: &Ipv4Address,
+        gateway_address: &Ipv4Address,
+    ) -> Result {
+        (self.routes)(self, true, subnet_address, subnet_mask, gateway_address).into()
+    }
+}
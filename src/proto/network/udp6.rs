@@ -0,0 +1,88 @@
+//! UDP over IPv6 protocol.
+//!
+//! This works like UDP over IPv4, except for the configuration, and for the
+//! routing table which is managed by the IPv6 layer. The protocol is defined
+//! in the `udp` module.
+
+use super::udp::{self, Udp, UdpServiceBinding, UdpVersion};
+use super::V6;
+use crate::data_types::Ipv6Address;
+use crate::Guid;
+
+pub use super::udp::{CompletionToken, MAX_FRAGMENTS};
+
+/// The UDP6 protocol
+pub type Udp6 = Udp<V6>;
+
+/// Service binding protocol used to create `Udp6` instances.
+pub type Udp6ServiceBinding = UdpServiceBinding<V6>;
+
+/// Endpoints of an IPv6 datagram.
+pub type SessionData = udp::SessionData<Ipv6Address>;
+
+/// An IPv6 datagram to transmit.
+pub type TransmitData<'a> = udp::TransmitData<'a, V6>;
+
+/// An IPv6 datagram received by the `Udp6` protocol.
+pub type ReceivedDatagram<'a> = udp::ReceivedDatagram<'a, Ipv6Address>;
+
+impl UdpVersion for V6 {
+    type ConfigData = ConfigData;
+    type Routes = ();
+    type Gateway = ();
+    const DEFAULT_GATEWAY: Self::Gateway = ();
+    const GUID: Guid = guid!("4f948815-b4b9-43cb-8a33-90e060b34955");
+    const SERVICE_BINDING_GUID: Guid = guid!("66ed4721-3c98-4d3e-81e3-d03dd39a7254");
+}
+
+/// Configuration of a `Udp6` instance (`EFI_UDP6_CONFIG_DATA`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct ConfigData {
+    /// Receive all datagrams, whatever their destination.
+    pub accept_promiscuous: bool,
+    /// Receive datagrams sent to any port.
+    pub accept_any_port: bool,
+    /// Allow other instances to use the same local port.
+    pub allow_duplicate_port: bool,
+    /// Traffic class field of the IP packets.
+    pub traffic_class: u8,
+    /// Hop limit field of the IP packets.
+    pub hop_limit: u8,
+    /// Time after which receptions fail with `TIMEOUT`, in microseconds.
+    /// Zero means that they never time out.
+    pub receive_timeout: u32,
+    /// Time after which transmissions fail with `TIMEOUT`, in microseconds.
+    /// Zero means that they never time out.
+    pub transmit_timeout: u32,
+    /// Local address. Zero picks an address of the interface for each
+    /// transmission, and receives datagrams sent to any of them.
+    pub station_address: Ipv6Address,
+    /// Local port. Zero picks an ephemeral port.
+    pub station_port: u16,
+    /// Remote address. Zero allows exchanging datagrams with any address, in
+    /// which case the destination must be given for each transmission.
+    pub remote_address: Ipv6Address,
+    /// Remote port. Zero allows exchanging datagrams with any port.
+    pub remote_port: u16,
+}
+
+impl Default for ConfigData {
+    /// Creates a configuration with a hop limit of 64, using any address of
+    /// the interface and an ephemeral port.
+    fn default() -> Self {
+        Self {
+            accept_promiscuous: false,
+            accept_any_port: false,
+            allow_duplicate_port: false,
+            traffic_class: 0,
+            hop_limit: 64,
+            receive_timeout: 0,
+            transmit_timeout: 0,
+            station_address: Ipv6Address::default(),
+            station_port: 0,
+            remote_address: Ipv6Address::default(),
+            remote_port: 0,
+        }
+    }
+}
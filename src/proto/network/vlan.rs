@@ -0,0 +1,97 @@
+//! VLAN Configuration protocol.
+//!
+//! This protocol is installed on the handle of a network interface, and
+//! configures the 802.1Q VLANs of the interface. Each VLAN gets its own child
+//! handle, on which the network stack is started.
+//!
+//! Changing the VLANs restarts the network stack of the interface, so this
+//! must be done before any other network protocol is used.
+
+use crate::proto::Protocol;
+#[cfg(feature = "exts")]
+use crate::{alloc_api::vec::Vec, table::boot::BootServices};
+use crate::{Guid, Identify, Result, Status};
+#[cfg(feature = "exts")]
+use core::{ptr, slice};
+
+/// Highest valid VLAN identifier, since 4095 is reserved.
+pub const MAX_VLAN_ID: u16 = 4094;
+
+/// Highest valid priority of a VLAN.
+pub const MAX_PRIORITY: u8 = 7;
+
+/// A configured VLAN (`EFI_VLAN_FIND_DATA`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct VlanEntry {
+    /// Identifier of the VLAN.
+    pub vlan_id: u16,
+    /// Priority of the frames sent on the VLAN.
+    pub priority: u8,
+}
+
+/// The VLAN Configuration protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct VlanConfig {
+    set: extern "efiapi" fn(this: &mut VlanConfig, vlan_id: u16, priority: u8) -> Status,
+    find: unsafe extern "efiapi" fn(
+        this: &VlanConfig,
+        vlan_id: *const u16,
+        number_of_vlan: *mut u16,
+        entries: *mut *mut VlanEntry,
+    ) -> Status,
+    remove: extern "efiapi" fn(this: &mut VlanConfig, vlan_id: u16) -> Status,
+}
+
+unsafe impl Identify for VlanConfig {
+    const GUID: Guid = guid!("9e23d768-d2f3-4366-9fc3-3a7aba864374");
+}
+
+impl VlanConfig {
+    /// Creates the VLAN `vlan_id`, or updates its priority if it exists.
+    ///
+    /// VLAN 0 stands for untagged frames, for which only the priority is
+    /// sent. `INVALID_PARAMETER` is returned if the identifier is greater
+    /// than `MAX_VLAN_ID`, or the priority greater than `MAX_PRIORITY`.
+    pub fn set(&mut self, vlan_id: u16, priority: u8) -> Result {
+        if vlan_id > MAX_VLAN_ID || priority > MAX_PRIORITY {
+            return Status::INVALID_PARAMETER.into();
+        }
+        (self.set)(self, vlan_id, priority).into()
+    }
+
+    /// Returns the configured VLANs, or only the VLAN `vlan_id` if it is
+    /// given.
+    ///
+    /// The list is empty if there is no matching VLAN.
+    #[cfg(feature = "exts")]
+    pub fn find(&self, bt: &BootServices, vlan_id: Option<u16>) -> Result<Vec<VlanEntry>> {
+        let vlan_id = vlan_id.as_ref().map_or(ptr::null(), |id| id as *const _);
+        let mut count = 0;
+        let mut entries = ptr::null_mut();
+        let status = unsafe { (self.find)(self, vlan_id, &mut count, &mut entries) };
+        if status == Status::NOT_FOUND {
+            return Ok(Vec::new().into());
+        }
+        status.into_with_val(|| {
+            if entries.is_null() {
+                return Vec::new();
+            }
+            let list = unsafe { slice::from_raw_parts(entries, count as usize) }.to_vec();
+            // Ignore the result, we can't do anything about an error here.
+            let _ = bt.free_pool(entries.cast());
+            list
+        })
+    }
+
+    /// Removes the VLAN `vlan_id`.
+    ///
+    /// `NOT_FOUND` is returned if the VLAN is not configured.
+    pub fn remove(&mut self, vlan_id: u16) -> Result {
+        if vlan_id > MAX_VLAN_ID {
+            return Status::INVALID_PARAMETER.into();
+        }
+        (self.remove)(self, vlan_id).into()
+    }
+}
@@ -0,0 +1,460 @@
+//! PCI I/O protocol.
+
+use super::{Attributes, DmaBuffer, DmaOperation, DmaProtocol, IoValue, IoWidth, Mapping};
+use crate::data_types::PhysicalAddress;
+use crate::proto::Protocol;
+use crate::table::boot::MemoryType;
+use crate::{Guid, Identify, Result, ResultExt, Status};
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::{fmt, ptr};
+
+/// Number of BARs of a PCI function.
+pub const BAR_COUNT: u8 = 6;
+
+/// Offset of a register in the configuration space of a PCI function.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[repr(transparent)]
+pub struct ConfigOffset(pub u32);
+
+impl ConfigOffset {
+    /// Identifier of the vendor, as a `u16`.
+    pub const VENDOR_ID: Self = Self(0x00);
+    /// Identifier of the device, as a `u16`.
+    pub const DEVICE_ID: Self = Self(0x02);
+    /// Command register, as a `u16`.
+    pub const COMMAND: Self = Self(0x04);
+    /// Status register, as a `u16`.
+    pub const STATUS: Self = Self(0x06);
+    /// Revision of the device, as a `u8`.
+    pub const REVISION_ID: Self = Self(0x08);
+    /// Programming interface, as a `u8`.
+    pub const PROG_IF: Self = Self(0x09);
+    /// Subclass code, as a `u8`.
+    pub const SUBCLASS: Self = Self(0x0a);
+    /// Class code, as a `u8`.
+    pub const CLASS: Self = Self(0x0b);
+    /// Header type, as a `u8`.
+    pub const HEADER_TYPE: Self = Self(0x0e);
+    /// Pointer to the list of capabilities, as a `u8`.
+    pub const CAPABILITIES_POINTER: Self = Self(0x34);
+
+    /// Offset of the BAR `index`, as a `u32`. The BARs of 64-bit memory span
+    /// two consecutive registers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not lower than `BAR_COUNT`.
+    pub fn bar(index: u8) -> Self {
+        assert!(index < BAR_COUNT, "invalid BAR index {}", index);
+        Self(0x10 + 4 * u32::from(index))
+    }
+}
+
+/// Location of a PCI function.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct PciLocation {
+    /// Segment, or PCI domain, of the bus.
+    pub segment: usize,
+    /// Number of the bus.
+    pub bus: usize,
+    /// Number of the device on the bus.
+    pub device: usize,
+    /// Number of the function in the device.
+    pub function: usize,
+}
+
+impl fmt::Display for PciLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{:x}",
+            self.segment, self.bus, self.device, self.function
+        )
+    }
+}
+
+/// Operation performed by the `attributes` function
+/// (`EFI_PCI_IO_PROTOCOL_ATTRIBUTE_OPERATION`).
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+struct AttributeOperation(u32);
+
+impl AttributeOperation {
+    const GET: Self = Self(0);
+    const SET: Self = Self(1);
+    const ENABLE: Self = Self(2);
+    const DISABLE: Self = Self(3);
+    const SUPPORTED: Self = Self(4);
+}
+
+/// Functions accessing a BAR (`EFI_PCI_IO_PROTOCOL_ACCESS`).
+#[repr(C)]
+struct BarAccess {
+    read: unsafe extern "efiapi" fn(
+        this: &PciIo,
+        width: IoWidth,
+        bar_index: u8,
+        offset: u64,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    write: unsafe extern "efiapi" fn(
+        this: &PciIo,
+        width: IoWidth,
+        bar_index: u8,
+        offset: u64,
+        count: usize,
+        buffer: *const c_void,
+    ) -> Status,
+}
+
+/// Functions accessing the configuration space
+/// (`EFI_PCI_IO_PROTOCOL_CONFIG_ACCESS`).
+#[repr(C)]
+struct ConfigAccess {
+    read: unsafe extern "efiapi" fn(
+        this: &PciIo,
+        width: IoWidth,
+        offset: u32,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    write: unsafe extern "efiapi" fn(
+        this: &PciIo,
+        width: IoWidth,
+        offset: u32,
+        count: usize,
+        buffer: *const c_void,
+    ) -> Status,
+}
+
+/// The PCI I/O protocol
+///
+/// This protocol is installed on the handle of every PCI function. The
+/// offsets of the BAR accesses are relative to the start of the BAR.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct PciIo {
+    _poll_mem: usize,
+    _poll_io: usize,
+    mem: BarAccess,
+    io: BarAccess,
+    pci: ConfigAccess,
+    _copy_mem: usize,
+    map: unsafe extern "efiapi" fn(
+        this: &PciIo,
+        operation: DmaOperation,
+        host_address: *const c_void,
+        number_of_bytes: &mut usize,
+        device_address: &mut PhysicalAddress,
+        mapping: &mut *mut c_void,
+    ) -> Status,
+    unmap: unsafe extern "efiapi" fn(this: &PciIo, mapping: *mut c_void) -> Status,
+    allocate_buffer: unsafe extern "efiapi" fn(
+        this: &PciIo,
+        alloc_ty: u32,
+        mem_ty: MemoryType,
+        pages: usize,
+        host_address: &mut *mut c_void,
+        attributes: Attributes,
+    ) -> Status,
+    free_buffer:
+        unsafe extern "efiapi" fn(this: &PciIo, pages: usize, host_address: *mut c_void) -> Status,
+    flush: extern "efiapi" fn(this: &PciIo) -> Status,
+    get_location: extern "efiapi" fn(
+        this: &PciIo,
+        segment: &mut usize,
+        bus: &mut usize,
+        device: &mut usize,
+        function: &mut usize,
+    ) -> Status,
+    attributes: extern "efiapi" fn(
+        this: &mut PciIo,
+        operation: AttributeOperation,
+        attributes: Attributes,
+        result: *mut Attributes,
+    ) -> Status,
+    _get_bar_attributes: usize,
+    _set_bar_attributes: usize,
+    _rom_size: u64,
+    _rom_image: *const c_void,
+}
+
+unsafe impl Identify for PciIo {
+    const GUID: Guid = guid!("4cf5b200-68b8-4ca5-9eec-b23e3f50029a");
+}
+
+/// Checks that `bar_index` designates one of the BARs.
+fn check_bar(bar_index: u8) -> Result {
+    if bar_index < BAR_COUNT {
+        Status::SUCCESS.into()
+    } else {
+        Status::INVALID_PARAMETER.into()
+    }
+}
+
+impl PciIo {
+    /// Reads a register of the configuration space.
+    ///
+    /// The offset must be aligned to the size of `T`.
+    pub fn pci_read<T: IoValue>(&self, offset: ConfigOffset) -> Result<T> {
+        let mut value = T::default();
+        unsafe { (self.pci.read)(self, T::WIDTH, offset.0, 1, (&mut value as *mut T).cast()) }
+            .into_with_val(|| value)
+    }
+
+    /// Writes a register of the configuration space.
+    ///
+    /// The offset must be aligned to the size of `T`.
+    pub fn pci_write<T: IoValue>(&self, offset: ConfigOffset, value: T) -> Result {
+        unsafe { (self.pci.write)(self, T::WIDTH, offset.0, 1, (&value as *const T).cast()) }.into()
+    }
+
+    /// Reads a value from the memory BAR `bar_index`.
+    ///
+    /// `INVALID_PARAMETER` is returned if the BAR does not exist, and
+    /// `UNSUPPORTED` if it is not a memory BAR or the offset is out of its
+    /// range.
+    pub fn mem_read<T: IoValue>(&self, bar_index: u8, offset: u64) -> Result<T> {
+        check_bar(bar_index)?.log();
+        let mut value = T::default();
+        unsafe {
+            (self.mem.read)(
+                self,
+                T::WIDTH,
+                bar_index,
+                offset,
+                1,
+                (&mut value as *mut T).cast(),
+            )
+        }
+        .into_with_val(|| value)
+    }
+
+    /// Writes a value to the memory BAR `bar_index`.
+    pub fn mem_write<T: IoValue>(&self, bar_index: u8, offset: u64, value: T) -> Result {
+        check_bar(bar_index)?.log();
+        unsafe {
+            (self.mem.write)(
+                self,
+                T::WIDTH,
+                bar_index,
+                offset,
+                1,
+                (&value as *const T).cast(),
+            )
+        }
+        .into()
+    }
+
+    /// Reads a value from the I/O BAR `bar_index`.
+    ///
+    /// `INVALID_PARAMETER` is returned if the BAR does not exist, and
+    /// `UNSUPPORTED` if it is not an I/O BAR or the offset is out of its
+    /// range.
+    pub fn io_read<T: IoValue>(&self, bar_index: u8, offset: u64) -> Result<T> {
+        check_bar(bar_index)?.log();
+        let mut value = T::default();
+        unsafe {
+            (self.io.read)(
+                self,
+                T::WIDTH,
+                bar_index,
+                offset,
+                1,
+                (&mut value as *mut T).cast(),
+            )
+        }
+        .into_with_val(|| value)
+    }
+
+    /// Writes a value to the I/O BAR `bar_index`.
+    pub fn io_write<T: IoValue>(&self, bar_index: u8, offset: u64, value: T) -> Result {
+        check_bar(bar_index)?.log();
+        unsafe {
+            (self.io.write)(
+                self,
+                T::WIDTH,
+                bar_index,
+                offset,
+                1,
+                (&value as *const T).cast(),
+            )
+        }
+        .into()
+    }
+
+    /// Returns the location of this PCI function.
+    pub fn get_location(&self) -> Result<PciLocation> {
+        let mut location = PciLocation::default();
+        (self.get_location)(
+            self,
+            &mut location.segment,
+            &mut location.bus,
+            &mut location.device,
+            &mut location.function,
+        )
+        .into_with_val(|| location)
+    }
+
+    /// Returns the attributes enabled on this PCI function.
+    pub fn attributes(&mut self) -> Result<Attributes> {
+        self.attribute_operation(AttributeOperation::GET, Attributes::empty())
+    }
+
+    /// Returns the attributes supported by this PCI function.
+    pub fn supported_attributes(&mut self) -> Result<Attributes> {
+        self.attribute_operation(AttributeOperation::SUPPORTED, Attributes::empty())
+    }
+
+    /// Sets the attributes enabled on this PCI function.
+    ///
+    /// `UNSUPPORTED` is returned if some of the attributes are not supported.
+    pub fn set_attributes(&mut self, attributes: Attributes) -> Result {
+        self.attribute_operation(AttributeOperation::SET, attributes)
+            .map_inner(|_| ())
+    }
+
+    /// Enables some attributes, keeping the others unchanged.
+    pub fn enable_attributes(&mut self, attributes: Attributes) -> Result {
+        self.attribute_operation(AttributeOperation::ENABLE, attributes)
+            .map_inner(|_| ())
+    }
+
+    /// Disables some attributes, keeping the others unchanged.
+    pub fn disable_attributes(&mut self, attributes: Attributes) -> Result {
+        self.attribute_operation(AttributeOperation::DISABLE, attributes)
+            .map_inner(|_| ())
+    }
+
+    /// Maps `buffer` so that the device can read from it.
+    pub fn map_read<'buf>(&self, buffer: &'buf [u8]) -> Result<Mapping<'_, 'buf, Self>> {
+        unsafe { self.map(DmaOperation::BUS_MASTER_READ, buffer.as_ptr(), buffer.len()) }
+    }
+
+    /// Maps `buffer` so that the device can write to it.
+    pub fn map_write<'buf>(&self, buffer: &'buf mut [u8]) -> Result<Mapping<'_, 'buf, Self>> {
+        unsafe {
+            self.map(
+                DmaOperation::BUS_MASTER_WRITE,
+                buffer.as_ptr(),
+                buffer.len(),
+            )
+        }
+    }
+
+    /// Maps a buffer allocated by `allocate_buffer`, so that the device and
+    /// the processor can both access it.
+    pub fn map_common_buffer<'buf>(
+        &self,
+        buffer: &'buf mut DmaBuffer<Self>,
+    ) -> Result<Mapping<'_, 'buf, Self>> {
+        unsafe {
+            self.map(
+                DmaOperation::BUS_MASTER_COMMON_BUFFER,
+                buffer.host_address(),
+                buffer.len(),
+            )
+        }
+    }
+
+    /// Maps `len` bytes of host memory at `host_address` for a DMA transfer.
+    ///
+    /// # Safety
+    ///
+    /// The memory must stay valid, and must not be accessed in a way which
+    /// conflicts with `operation`, until the mapping is dropped.
+    unsafe fn map<'buf>(
+        &self,
+        operation: DmaOperation,
+        host_address: *const u8,
+        len: usize,
+    ) -> Result<Mapping<'_, 'buf, Self>> {
+        let mut mapped_len = len;
+        let mut device_address = PhysicalAddress::default();
+        let mut mapping = ptr::null_mut();
+        (self.map)(
+            self,
+            operation,
+            host_address.cast(),
+            &mut mapped_len,
+            &mut device_address,
+            &mut mapping,
+        )
+        .into_with_val(|| Mapping {
+            pci: self,
+            mapping,
+            operation,
+            device_address,
+            len: mapped_len,
+            _buffer: PhantomData,
+        })
+    }
+
+    /// Allocates `pages` pages of memory, which can be mapped as a common
+    /// buffer.
+    ///
+    /// The memory type must be `BOOT_SERVICES_DATA` or
+    /// `RUNTIME_SERVICES_DATA`, otherwise `INVALID_PARAMETER` is returned.
+    /// Only the `MEMORY_WRITE_COMBINE`, `MEMORY_CACHED` and
+    /// `DUAL_ADDRESS_CYCLE` attributes are allowed, and `UNSUPPORTED` is
+    /// returned for the others. The buffer is zeroed.
+    pub fn allocate_buffer(
+        &self,
+        mem_ty: MemoryType,
+        pages: usize,
+        attributes: Attributes,
+    ) -> Result<DmaBuffer<'_, Self>> {
+        if mem_ty != MemoryType::BOOT_SERVICES_DATA && mem_ty != MemoryType::RUNTIME_SERVICES_DATA {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        let allowed = Attributes::MEMORY_WRITE_COMBINE
+            | Attributes::MEMORY_CACHED
+            | Attributes::DUAL_ADDRESS_CYCLE;
+        if !allowed.contains(attributes) {
+            return Err(Status::UNSUPPORTED.into());
+        }
+
+        let mut host_address = ptr::null_mut();
+        // The allocation type is ignored, any pages are allocated.
+        unsafe { (self.allocate_buffer)(self, 0, mem_ty, pages, &mut host_address, attributes) }
+            .into_result()?
+            .log();
+        let host_address = NonNull::new(host_address.cast()).ok_or(Status::OUT_OF_RESOURCES)?;
+        let mut buffer = DmaBuffer {
+            pci: self,
+            host_address,
+            pages,
+        };
+        buffer.as_mut_slice().fill(0);
+        Ok(buffer.into())
+    }
+
+    /// Flushes the posted writes of the device to host memory.
+    pub fn flush(&self) -> Result {
+        (self.flush)(self).into()
+    }
+
+    fn attribute_operation(
+        &mut self,
+        operation: AttributeOperation,
+        attributes: Attributes,
+    ) -> Result<Attributes> {
+        let mut result = Attributes::empty();
+        (self.attributes)(self, operation, attributes, &mut result).into_with_val(|| result)
+    }
+}
+
+impl DmaProtocol for PciIo {}
+
+impl super::private::Dma for PciIo {
+    unsafe fn raw_unmap(&self, mapping: *mut c_void) -> Status {
+        (self.unmap)(self, mapping)
+    }
+
+    unsafe fn raw_free_buffer(&self, pages: usize, host_address: *mut c_void) -> Status {
+        (self.free_buffer)(self, pages, host_address)
+    }
+}
@@ -0,0 +1,289 @@
+//! PCI protocols.
+//!
+//! The PCI I/O protocol is installed on the handle of every PCI function, and
+//! gives access to its configuration space, its BARs, and DMA. The PCI Root
+//! Bridge I/O protocol gives access to everything below a root bridge,
+//! including functions which have no handle.
+
+use crate::data_types::{PhysicalAddress, PAGE_SIZE};
+use crate::Result;
+use bitflags::bitflags;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::{fmt, slice};
+
+pub mod io;
+pub mod root_bridge;
+
+newtype_enum! {
+/// Width and pattern of an access (`EFI_PCI_IO_PROTOCOL_WIDTH`).
+///
+/// Plain accesses move each element to or from consecutive addresses. FIFO
+/// accesses use the same device address for all the elements, and fill
+/// accesses write the same element to consecutive device addresses.
+pub enum IoWidth: u32 => {
+    /// 8-bit accesses.
+    U8       = 0,
+    /// 16-bit accesses.
+    U16      = 1,
+    /// 32-bit accesses.
+    U32      = 2,
+    /// 64-bit accesses.
+    U64      = 3,
+    /// 8-bit accesses, all at the same device address.
+    FIFO_U8  = 4,
+    /// 16-bit accesses, all at the same device address.
+    FIFO_U16 = 5,
+    /// 32-bit accesses, all at the same device address.
+    FIFO_U32 = 6,
+    /// 64-bit accesses, all at the same device address.
+    FIFO_U64 = 7,
+    /// 8-bit accesses, all with the same value.
+    FILL_U8  = 8,
+    /// 16-bit accesses, all with the same value.
+    FILL_U16 = 9,
+    /// 32-bit accesses, all with the same value.
+    FILL_U32 = 10,
+    /// 64-bit accesses, all with the same value.
+    FILL_U64 = 11,
+}}
+
+/// A value which can be read or written by a single access.
+///
+/// This is implemented by `u8`, `u16`, `u32` and `u64`.
+pub trait IoValue: Copy + Default + private::Sealed {
+    /// Width of the accesses to values of this type.
+    const WIDTH: IoWidth;
+}
+
+impl IoValue for u8 {
+    const WIDTH: IoWidth = IoWidth::U8;
+}
+
+impl IoValue for u16 {
+    const WIDTH: IoWidth = IoWidth::U16;
+}
+
+impl IoValue for u32 {
+    const WIDTH: IoWidth = IoWidth::U32;
+}
+
+impl IoValue for u64 {
+    const WIDTH: IoWidth = IoWidth::U64;
+}
+
+/// A protocol which maps host memory for DMA transfers, and allocates common
+/// buffers: `PciIo` or `PciRootBridgeIo`.
+pub trait DmaProtocol: private::Dma {}
+
+mod private {
+    use crate::Status;
+    use core::ffi::c_void;
+
+    pub trait Sealed {}
+
+    pub trait Dma {
+        unsafe fn raw_unmap(&self, mapping: *mut c_void) -> Status;
+        unsafe fn raw_free_buffer(&self, pages: usize, host_address: *mut c_void) -> Status;
+    }
+
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
+}
+
+bitflags! {
+    /// Attributes of a PCI controller, which control the resources it
+    /// decodes and how it accesses memory.
+    #[repr(transparent)]
+    pub struct Attributes: u64 {
+        /// Decodes the I/O ports of the ISA motherboard devices.
+        const ISA_MOTHERBOARD_IO = 0x0001;
+        /// Decodes the I/O ports of the ISA bus.
+        const ISA_IO = 0x0002;
+        /// Decodes the I/O ports of the VGA palette.
+        const VGA_PALETTE_IO = 0x0004;
+        /// Decodes the VGA framebuffer.
+        const VGA_MEMORY = 0x0008;
+        /// Decodes the I/O ports of the VGA controller.
+        const VGA_IO = 0x0010;
+        /// Decodes the I/O ports of the primary IDE controller.
+        const IDE_PRIMARY_IO = 0x0020;
+        /// Decodes the I/O ports of the secondary IDE controller.
+        const IDE_SECONDARY_IO = 0x0040;
+        /// Memory BARs can be mapped as write-combining.
+        const MEMORY_WRITE_COMBINE = 0x0080;
+        /// Decodes its I/O BARs.
+        const IO = 0x0100;
+        /// Decodes its memory BARs.
+        const MEMORY = 0x0200;
+        /// Can initiate DMA.
+        const BUS_MASTER = 0x0400;
+        /// Memory BARs can be mapped as cached.
+        const MEMORY_CACHED = 0x0800;
+        /// Memory BARs can be disabled.
+        const MEMORY_DISABLE = 0x1000;
+        /// The controller is soldered on the motherboard.
+        const EMBEDDED_DEVICE = 0x2000;
+        /// The option ROM of the controller is not on the controller itself.
+        const EMBEDDED_ROM = 0x4000;
+        /// Can access memory above 4 GiB through DMA.
+        const DUAL_ADDRESS_CYCLE = 0x8000;
+        /// Decodes the ISA I/O ports with 16-bit addresses.
+        const ISA_IO_16 = 0x1_0000;
+        /// Decodes the VGA palette I/O ports with 16-bit addresses.
+        const VGA_PALETTE_IO_16 = 0x2_0000;
+        /// Decodes the VGA I/O ports with 16-bit addresses.
+        const VGA_IO_16 = 0x4_0000;
+    }
+}
+
+newtype_enum! {
+/// Direction of a DMA transfer (`EFI_PCI_IO_PROTOCOL_OPERATION`).
+pub enum DmaOperation: u32 => {
+    /// The device reads from host memory.
+    BUS_MASTER_READ          = 0,
+    /// The device writes to host memory.
+    BUS_MASTER_WRITE         = 1,
+    /// The device and the processor both access a buffer allocated by
+    /// `allocate_buffer`.
+    BUS_MASTER_COMMON_BUFFER = 2,
+    /// The device reads from host memory, which may be above 4 GiB. This is
+    /// only supported by `PciRootBridgeIo`.
+    BUS_MASTER_READ_64          = 3,
+    /// The device writes to host memory, which may be above 4 GiB. This is
+    /// only supported by `PciRootBridgeIo`.
+    BUS_MASTER_WRITE_64         = 4,
+    /// Common buffer, which may be above 4 GiB. This is only supported by
+    /// `PciRootBridgeIo`.
+    BUS_MASTER_COMMON_BUFFER_64 = 5,
+}}
+
+/// Host memory made accessible to a device by a `map_*` function, which is
+/// unmapped when dropped.
+///
+/// The device must use `device_address` to access the memory, which may
+/// differ from its host address, for example when there is an IOMMU or when
+/// the device cannot reach the memory and it is bounced through another
+/// buffer. Only `len` bytes may have been mapped, in which case the rest of
+/// the buffer must be mapped separately.
+///
+/// For bus master writes, the data written by the device is only guaranteed
+/// to be in the host buffer once the mapping has been unmapped.
+#[must_use]
+pub struct Mapping<'pci, 'buf, P: DmaProtocol> {
+    pci: &'pci P,
+    mapping: *mut c_void,
+    operation: DmaOperation,
+    device_address: PhysicalAddress,
+    len: usize,
+    _buffer: PhantomData<&'buf mut [u8]>,
+}
+
+impl<P: DmaProtocol> Mapping<'_, '_, P> {
+    /// Direction of the transfers.
+    pub fn operation(&self) -> DmaOperation {
+        self.operation
+    }
+
+    /// Address of the memory, as seen by the device.
+    pub fn device_address(&self) -> PhysicalAddress {
+        self.device_address
+    }
+
+    /// Number of bytes which were mapped.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no byte was mapped.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Unmaps the memory, returning the status of the operation, which
+    /// dropping the mapping ignores.
+    pub fn unmap(self) -> Result {
+        let status = unsafe { self.pci.raw_unmap(self.mapping) };
+        core::mem::forget(self);
+        status.into()
+    }
+}
+
+impl<P: DmaProtocol> fmt::Debug for Mapping<'_, '_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mapping")
+            .field("operation", &self.operation)
+            .field("device_address", &self.device_address)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<P: DmaProtocol> Drop for Mapping<'_, '_, P> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = unsafe { self.pci.raw_unmap(self.mapping) };
+    }
+}
+
+/// A buffer allocated by an `allocate_buffer` function, which can be accessed by
+/// both the processor and the device once it is mapped as a common buffer.
+/// It is freed when dropped.
+pub struct DmaBuffer<'pci, P: DmaProtocol> {
+    pci: &'pci P,
+    host_address: NonNull<u8>,
+    pages: usize,
+}
+
+impl<P: DmaProtocol> DmaBuffer<'_, P> {
+    /// Size of the buffer in bytes.
+    pub fn len(&self) -> usize {
+        self.pages * PAGE_SIZE as usize
+    }
+
+    /// Whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.pages == 0
+    }
+
+    /// Host address of the buffer, which the device must not use.
+    pub fn host_address(&self) -> *mut u8 {
+        self.host_address.as_ptr()
+    }
+
+    /// Contents of the buffer.
+    ///
+    /// While the buffer is mapped, the device may change them at any time,
+    /// so they should be accessed with volatile reads through
+    /// `host_address` instead.
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.host_address.as_ptr(), self.len()) }
+    }
+
+    /// Mutable contents of the buffer.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.host_address.as_ptr(), self.len()) }
+    }
+}
+
+impl<P: DmaProtocol> fmt::Debug for DmaBuffer<'_, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DmaBuffer")
+            .field("host_address", &self.host_address)
+            .field("pages", &self.pages)
+            .finish()
+    }
+}
+
+impl<P: DmaProtocol> Drop for DmaBuffer<'_, P> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = unsafe {
+            self.pci
+                .raw_free_buffer(self.pages, self.host_address.as_ptr().cast())
+        };
+    }
+}
@@ -0,0 +1,553 @@
+//! PCI Root Bridge I/O protocol.
+//!
+//! This protocol is installed on the handle of every PCI root bridge, and
+//! gives access to the configuration space of all the functions below it,
+//! whether or not they have a handle, and to the memory and I/O space
+//! decoded by the bridge.
+
+use super::io::ConfigOffset;
+use super::{Attributes, DmaBuffer, DmaOperation, DmaProtocol, IoValue, IoWidth, Mapping};
+use crate::data_types::PhysicalAddress;
+use crate::proto::Protocol;
+use crate::table::boot::MemoryType;
+use crate::{Guid, Handle, Identify, Result, Status};
+use core::convert::TryInto;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use core::{fmt, slice};
+
+/// Address of a register in the configuration space of a PCI function
+/// (`EFI_PCI_ROOT_BRIDGE_IO_PROTOCOL_PCI_ADDRESS`).
+///
+/// ```
+/// use uefi::proto::pci::io::ConfigOffset;
+/// use uefi::proto::pci::root_bridge::PciAddress;
+///
+/// let address = PciAddress::new(2, 31, 7, ConfigOffset::DEVICE_ID);
+/// assert_eq!(address.as_u64(), 0x021f_0702);
+/// assert_eq!(address.bus(), 2);
+/// assert_eq!(address.device(), 31);
+/// assert_eq!(address.function(), 7);
+/// assert_eq!(address.register(), ConfigOffset::DEVICE_ID);
+///
+/// // Extended registers of PCI Express are stored separately.
+/// let address = PciAddress::new(0, 1, 0, ConfigOffset(0x100));
+/// assert_eq!(address.as_u64(), 0x100_0001_0000);
+/// assert_eq!(address.register(), ConfigOffset(0x100));
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[repr(transparent)]
+pub struct PciAddress(u64);
+
+impl PciAddress {
+    /// Creates the address of register `register` of function `function` of
+    /// device `device` on bus `bus`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the device is not lower than 32, the function is not lower
+    /// than 8, or the register is not lower than 4096.
+    pub fn new(bus: u8, device: u8, function: u8, register: ConfigOffset) -> Self {
+        assert!(device < 32, "invalid PCI device {}", device);
+        assert!(function < 8, "invalid PCI function {}", function);
+        assert!(
+            register.0 < 0x1000,
+            "invalid PCI register {:#x}",
+            register.0
+        );
+        let location = u64::from(bus) << 24 | u64::from(device) << 16 | u64::from(function) << 8;
+        if register.0 < 0x100 {
+            Self(location | u64::from(register.0))
+        } else {
+            Self(location | u64::from(register.0) << 32)
+        }
+    }
+
+    /// Returns the encoded address.
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Number of the bus.
+    pub fn bus(self) -> u8 {
+        (self.0 >> 24) as u8
+    }
+
+    /// Number of the device on the bus.
+    pub fn device(self) -> u8 {
+        (self.0 >> 16) as u8
+    }
+
+    /// Number of the function in the device.
+    pub fn function(self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    /// Offset of the register in the configuration space.
+    pub fn register(self) -> ConfigOffset {
+        match (self.0 >> 32) as u32 {
+            0 => ConfigOffset(self.0 as u8 as u32),
+            extended => ConfigOffset(extended),
+        }
+    }
+}
+
+newtype_enum! {
+/// Kind of resources described by an `AddressSpace`.
+pub enum ResourceKind: u8 => {
+    /// Memory space.
+    MEMORY = 0,
+    /// I/O space.
+    IO     = 1,
+    /// Bus numbers.
+    BUS    = 2,
+}}
+
+/// A range of resources decoded by a root bridge, described by an ACPI QWORD
+/// address space descriptor.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AddressSpace {
+    /// Kind of the resources.
+    pub kind: ResourceKind,
+    /// General flags of the descriptor, describing how the range is decoded.
+    pub general_flags: u8,
+    /// Flags specific to the kind of resources, such as the cacheability of
+    /// memory.
+    pub type_flags: u8,
+    /// Granularity of the addresses. For memory ranges, firmware usually
+    /// stores the width of the addresses instead, 32 or 64.
+    pub granularity: u64,
+    /// Start of the range.
+    pub min: u64,
+    /// End of the range, which firmware often leaves zero, so `len` should
+    /// be used instead.
+    pub max: u64,
+    /// Offset to add to the addresses of the bus to get host addresses.
+    pub translation_offset: u64,
+    /// Length of the range.
+    pub len: u64,
+}
+
+impl AddressSpace {
+    /// Tag of ACPI QWORD address space descriptors.
+    const TAG: u8 = 0x8a;
+    /// Size of ACPI QWORD address space descriptors, including the tag and
+    /// the length.
+    const SIZE: usize = 46;
+
+    /// Exclusive end of the range.
+    pub fn end(&self) -> u64 {
+        self.min.wrapping_add(self.len)
+    }
+}
+
+/// Tag of the ACPI end descriptor, which terminates the list.
+const END_TAG: u8 = 0x79;
+
+/// Returns the size of the descriptor at the beginning of `bytes`, given its
+/// first bytes, or `None` if they are truncated.
+fn descriptor_size(bytes: &[u8]) -> Option<usize> {
+    let tag = *bytes.first()?;
+    if tag & 0x80 != 0 {
+        // Large descriptors store their length after the tag.
+        let len = u16::from_le_bytes([*bytes.get(1)?, *bytes.get(2)?]);
+        Some(3 + usize::from(len))
+    } else {
+        // Small descriptors store their length in the tag.
+        Some(1 + usize::from(tag & 0x07))
+    }
+}
+
+/// Resources decoded by a root bridge, as a list of ACPI resource
+/// descriptors.
+///
+/// The QWORD address space descriptors can be iterated over, and the other
+/// descriptors are skipped.
+///
+/// ```
+/// use uefi::proto::pci::root_bridge::{ResourceKind, Resources};
+///
+/// let mut bytes = [0; 46 * 2 + 2];
+/// // A 64 KiB I/O range, and a 256 MiB memory range.
+/// for (descriptor, (kind, min, len)) in bytes
+///     .chunks_mut(46)
+///     .zip([(1u8, 0x6000u64, 0xa000u64), (0, 0x8000_0000, 0x1000_0000)])
+/// {
+///     descriptor[0] = 0x8a;
+///     descriptor[1] = 0x2b;
+///     descriptor[3] = kind;
+///     descriptor[14..22].copy_from_slice(&min.to_le_bytes());
+///     descriptor[38..46].copy_from_slice(&len.to_le_bytes());
+/// }
+/// bytes[92] = 0x79;
+///
+/// let resources = Resources::parse(&bytes).unwrap();
+/// assert_eq!(resources.as_bytes().len(), bytes.len());
+/// let spaces: Vec<_> = resources.address_spaces().collect();
+/// assert_eq!(spaces.len(), 2);
+/// assert_eq!(spaces[0].kind, ResourceKind::IO);
+/// assert_eq!(spaces[0].end(), 0x1_0000);
+/// assert_eq!(spaces[1].kind, ResourceKind::MEMORY);
+/// assert_eq!(spaces[1].min, 0x8000_0000);
+///
+/// // The list must be terminated.
+/// assert!(Resources::parse(&bytes[..92]).is_none());
+/// ```
+#[derive(Clone, Copy)]
+pub struct Resources<'a>(&'a [u8]);
+
+impl<'a> Resources<'a> {
+    /// Parses the list of descriptors at the beginning of `bytes`, up to its
+    /// end descriptor. `None` is returned if the list is truncated, or a
+    /// QWORD address space descriptor is too short.
+    pub fn parse(bytes: &'a [u8]) -> Option<Self> {
+        let mut offset = 0;
+        loop {
+            let descriptor = &bytes[offset..];
+            let size = descriptor_size(descriptor)?;
+            if size > descriptor.len() {
+                return None;
+            }
+            if descriptor[0] == AddressSpace::TAG && size < AddressSpace::SIZE {
+                return None;
+            }
+            offset += size;
+            if descriptor[0] == END_TAG {
+                return Some(Self(&bytes[..offset]));
+            }
+        }
+    }
+
+    /// Returns the raw descriptors, including the end descriptor.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Iterates over the QWORD address space descriptors.
+    pub fn address_spaces(&self) -> impl Iterator<Item = AddressSpace> + 'a {
+        let mut bytes = self.0;
+        core::iter::from_fn(move || loop {
+            let size = descriptor_size(bytes)?;
+            let (descriptor, rest) = bytes.split_at(size);
+            bytes = rest;
+            match descriptor[0] {
+                END_TAG => return None,
+                AddressSpace::TAG => {
+                    let u64_at = |offset: usize| {
+                        u64::from_le_bytes(descriptor[offset..offset + 8].try_into().unwrap())
+                    };
+                    return Some(AddressSpace {
+                        kind: ResourceKind(descriptor[3]),
+                        general_flags: descriptor[4],
+                        type_flags: descriptor[5],
+                        granularity: u64_at(6),
+                        min: u64_at(14),
+                        max: u64_at(22),
+                        translation_offset: u64_at(30),
+                        len: u64_at(38),
+                    });
+                }
+                _ => {}
+            }
+        })
+    }
+}
+
+impl fmt::Debug for Resources<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.address_spaces()).finish()
+    }
+}
+
+/// Maximum size of the resource descriptors returned by the firmware, which
+/// bounds the search for their end.
+const MAX_RESOURCES_SIZE: usize = 0x1_0000;
+
+/// Functions accessing memory or I/O space
+/// (`EFI_PCI_ROOT_BRIDGE_IO_PROTOCOL_ACCESS`).
+#[repr(C)]
+struct Access {
+    read: unsafe extern "efiapi" fn(
+        this: &PciRootBridgeIo,
+        width: IoWidth,
+        address: u64,
+        count: usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    write: unsafe extern "efiapi" fn(
+        this: &PciRootBridgeIo,
+        width: IoWidth,
+        address: u64,
+        count: usize,
+        buffer: *const c_void,
+    ) -> Status,
+}
+
+/// The PCI Root Bridge I/O protocol
+///
+/// Memory and I/O addresses are the ones of the bus, which may differ from
+/// host addresses by the translation offset of the `AddressSpace`.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct PciRootBridgeIo {
+    parent_handle: Handle,
+    _poll_mem: usize,
+    _poll_io: usize,
+    mem: Access,
+    io: Access,
+    pci: Access,
+    _copy_mem: usize,
+    map: unsafe extern "efiapi" fn(
+        this: &PciRootBridgeIo,
+        operation: DmaOperation,
+        host_address: *const c_void,
+        number_of_bytes: &mut usize,
+        device_address: &mut PhysicalAddress,
+        mapping: &mut *mut c_void,
+    ) -> Status,
+    unmap: unsafe extern "efiapi" fn(this: &PciRootBridgeIo, mapping: *mut c_void) -> Status,
+    allocate_buffer: unsafe extern "efiapi" fn(
+        this: &PciRootBridgeIo,
+        alloc_ty: u32,
+        mem_ty: MemoryType,
+        pages: usize,
+        host_address: &mut *mut c_void,
+        attributes: Attributes,
+    ) -> Status,
+    free_buffer: unsafe extern "efiapi" fn(
+        this: &PciRootBridgeIo,
+        pages: usize,
+        host_address: *mut c_void,
+    ) -> Status,
+    flush: extern "efiapi" fn(this: &PciRootBridgeIo) -> Status,
+    get_attributes: extern "efiapi" fn(
+        this: &PciRootBridgeIo,
+        supports: *mut Attributes,
+        attributes: *mut Attributes,
+    ) -> Status,
+    _set_attributes: usize,
+    configuration: extern "efiapi" fn(this: &PciRootBridgeIo, resources: &mut *const u8) -> Status,
+    segment_number: u32,
+}
+
+unsafe impl Identify for PciRootBridgeIo {
+    const GUID: Guid = guid!("2f707ebb-4a1a-11d4-9a38-0090273fc14d");
+}
+
+impl PciRootBridgeIo {
+    /// Handle of the host bridge which this root bridge belongs to.
+    pub fn parent_handle(&self) -> Handle {
+        self.parent_handle
+    }
+
+    /// Segment, or PCI domain, of the buses below this root bridge.
+    pub fn segment_number(&self) -> u32 {
+        self.segment_number
+    }
+
+    /// Reads a register of the configuration space of a function.
+    ///
+    /// Functions which do not exist read as all ones.
+    pub fn pci_read<T: IoValue>(&self, address: PciAddress) -> Result<T> {
+        unsafe { Self::read(&self.pci, self, address.0) }
+    }
+
+    /// Writes a register of the configuration space of a function.
+    pub fn pci_write<T: IoValue>(&self, address: PciAddress, value: T) -> Result {
+        unsafe { Self::write(&self.pci, self, address.0, value) }
+    }
+
+    /// Reads a value from memory space.
+    pub fn mem_read<T: IoValue>(&self, address: u64) -> Result<T> {
+        unsafe { Self::read(&self.mem, self, address) }
+    }
+
+    /// Writes a value to memory space.
+    pub fn mem_write<T: IoValue>(&self, address: u64, value: T) -> Result {
+        unsafe { Self::write(&self.mem, self, address, value) }
+    }
+
+    /// Reads a value from I/O space.
+    pub fn io_read<T: IoValue>(&self, address: u64) -> Result<T> {
+        unsafe { Self::read(&self.io, self, address) }
+    }
+
+    /// Writes a value to I/O space.
+    pub fn io_write<T: IoValue>(&self, address: u64, value: T) -> Result {
+        unsafe { Self::write(&self.io, self, address, value) }
+    }
+
+    unsafe fn read<T: IoValue>(access: &Access, this: &Self, address: u64) -> Result<T> {
+        let mut value = T::default();
+        (access.read)(this, T::WIDTH, address, 1, (&mut value as *mut T).cast())
+            .into_with_val(|| value)
+    }
+
+    unsafe fn write<T: IoValue>(access: &Access, this: &Self, address: u64, value: T) -> Result {
+        (access.write)(this, T::WIDTH, address, 1, (&value as *const T).cast()).into()
+    }
+
+    /// Returns the attributes enabled on this root bridge.
+    pub fn attributes(&self) -> Result<Attributes> {
+        let mut attributes = Attributes::empty();
+        (self.get_attributes)(self, ptr::null_mut(), &mut attributes).into_with_val(|| attributes)
+    }
+
+    /// Returns the attributes supported by this root bridge.
+    pub fn supported_attributes(&self) -> Result<Attributes> {
+        let mut supports = Attributes::empty();
+        (self.get_attributes)(self, &mut supports, ptr::null_mut()).into_with_val(|| supports)
+    }
+
+    /// Returns the resources decoded by this root bridge.
+    ///
+    /// The descriptors belong to the firmware, and stay valid until the
+    /// resources of the bridge are reallocated. `PROTOCOL_ERROR` is returned
+    /// if they are malformed.
+    pub fn configuration(&self) -> Result<Resources<'_>> {
+        let mut resources = ptr::null();
+        (self.configuration)(self, &mut resources)
+            .into_result()?
+            .log();
+        if resources.is_null() {
+            return Err(Status::PROTOCOL_ERROR.into());
+        }
+
+        // Find the end of the list, without reading past it.
+        let mut size = 0;
+        loop {
+            let tag = unsafe { *resources.add(size) };
+            let header_len = if tag & 0x80 != 0 { 3 } else { 1 };
+            let header = unsafe { slice::from_raw_parts(resources.add(size), header_len) };
+            size += descriptor_size(header).ok_or(Status::PROTOCOL_ERROR)?;
+            if tag == END_TAG {
+                break;
+            }
+            if size > MAX_RESOURCES_SIZE {
+                return Err(Status::PROTOCOL_ERROR.into());
+            }
+        }
+        let bytes = unsafe { slice::from_raw_parts(resources, size) };
+        Resources::parse(bytes)
+            .map(|resources| resources.into())
+            .ok_or_else(|| Status::PROTOCOL_ERROR.into())
+    }
+
+    /// Maps `buffer` so that devices can read from it.
+    pub fn map_read<'buf>(&self, buffer: &'buf [u8]) -> Result<Mapping<'_, 'buf, Self>> {
+        unsafe { self.map(DmaOperation::BUS_MASTER_READ, buffer.as_ptr(), buffer.len()) }
+    }
+
+    /// Maps `buffer` so that devices can write to it.
+    pub fn map_write<'buf>(&self, buffer: &'buf mut [u8]) -> Result<Mapping<'_, 'buf, Self>> {
+        unsafe {
+            self.map(
+                DmaOperation::BUS_MASTER_WRITE,
+                buffer.as_ptr(),
+                buffer.len(),
+            )
+        }
+    }
+
+    /// Maps a buffer allocated by `allocate_buffer`, so that devices and the
+    /// processor can both access it.
+    pub fn map_common_buffer<'buf>(
+        &self,
+        buffer: &'buf mut DmaBuffer<Self>,
+    ) -> Result<Mapping<'_, 'buf, Self>> {
+        unsafe {
+            self.map(
+                DmaOperation::BUS_MASTER_COMMON_BUFFER,
+                buffer.host_address(),
+                buffer.len(),
+            )
+        }
+    }
+
+    /// Maps `len` bytes of host memory at `host_address` for a DMA transfer.
+    ///
+    /// # Safety
+    ///
+    /// The memory must stay valid, and must not be accessed in a way which
+    /// conflicts with `operation`, until the mapping is dropped.
+    unsafe fn map<'buf>(
+        &self,
+        operation: DmaOperation,
+        host_address: *const u8,
+        len: usize,
+    ) -> Result<Mapping<'_, 'buf, Self>> {
+        let mut mapped_len = len;
+        let mut device_address = PhysicalAddress::default();
+        let mut mapping = ptr::null_mut();
+        (self.map)(
+            self,
+            operation,
+            host_address.cast(),
+            &mut mapped_len,
+            &mut device_address,
+            &mut mapping,
+        )
+        .into_with_val(|| Mapping {
+            pci: self,
+            mapping,
+            operation,
+            device_address,
+            len: mapped_len,
+            _buffer: PhantomData,
+        })
+    }
+
+    /// Allocates `pages` pages of memory, which can be mapped as a common
+    /// buffer.
+    ///
+    /// The restrictions are the same as for `PciIo::allocate_buffer`. The
+    /// buffer is zeroed.
+    pub fn allocate_buffer(
+        &self,
+        mem_ty: MemoryType,
+        pages: usize,
+        attributes: Attributes,
+    ) -> Result<DmaBuffer<'_, Self>> {
+        if mem_ty != MemoryType::BOOT_SERVICES_DATA && mem_ty != MemoryType::RUNTIME_SERVICES_DATA {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+        let allowed = Attributes::MEMORY_WRITE_COMBINE
+            | Attributes::MEMORY_CACHED
+            | Attributes::DUAL_ADDRESS_CYCLE;
+        if !allowed.contains(attributes) {
+            return Err(Status::UNSUPPORTED.into());
+        }
+
+        let mut host_address = ptr::null_mut();
+        // The allocation type is ignored, any pages are allocated.
+        unsafe { (self.allocate_buffer)(self, 0, mem_ty, pages, &mut host_address, attributes) }
+            .into_result()?
+            .log();
+        let host_address = NonNull::new(host_address.cast()).ok_or(Status::OUT_OF_RESOURCES)?;
+        let mut buffer = DmaBuffer {
+            pci: self,
+            host_address,
+            pages,
+        };
+        buffer.as_mut_slice().fill(0);
+        Ok(buffer.into())
+    }
+
+    /// Flushes the posted writes of the devices to host memory.
+    pub fn flush(&self) -> Result {
+        (self.flush)(self).into()
+    }
+}
+
+impl DmaProtocol for PciRootBridgeIo {}
+
+impl super::private::Dma for PciRootBridgeIo {
+    unsafe fn raw_unmap(&self, mapping: *mut c_void) -> Status {
+        (self.unmap)(self, mapping)
+    }
+
+    unsafe fn raw_free_buffer(&self, pages: usize, host_address: *mut c_void) -> Status {
+        (self.free_buffer)(self, pages, host_address)
+    }
+}
@@ -0,0 +1,485 @@
+//! Firmware Volume 2 protocol.
+//!
+//! Firmware volumes hold the files of the firmware, in the Firmware File
+//! System (FFS) format: drivers, applications, and raw files such as logos or
+//! microcode. Each file is identified by a GUID, and made of sections, such
+//! as a PE32 image, or a user interface section holding its name.
+//!
+//! The sections of a file may be compressed, or wrapped in a GUID-defined
+//! section, which is extracted when it is read. Reading such a section fails
+//! with `UNSUPPORTED` if the firmware has no means to extract it. Sections
+//! which are signed are verified when they are extracted, and the outcome is
+//! reported by the `AuthenticationStatus` of the read, which should be
+//! checked before trusting the data.
+
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{Guid, Handle, Identify, Result, Status};
+use bitflags::bitflags;
+use core::ffi::c_void;
+use core::ptr::{self, NonNull};
+use core::{fmt, slice};
+
+newtype_enum! {
+/// Type of a file (`EFI_FV_FILETYPE`).
+pub enum FileType: u8 => {
+    /// Matches files of all types, when iterating over files.
+    ALL                     = 0x00,
+    /// Raw data.
+    RAW                     = 0x01,
+    /// Sections of any kind.
+    FREEFORM                = 0x02,
+    /// The security core.
+    SECURITY_CORE           = 0x03,
+    /// The PEI core.
+    PEI_CORE                = 0x04,
+    /// The DXE core.
+    DXE_CORE                = 0x05,
+    /// A PEI module.
+    PEIM                    = 0x06,
+    /// A DXE driver.
+    DRIVER                  = 0x07,
+    /// A PEI module which is also a DXE driver.
+    COMBINED_PEIM_DRIVER    = 0x08,
+    /// A UEFI application.
+    APPLICATION             = 0x09,
+    /// A Management Mode driver.
+    MM                      = 0x0a,
+    /// A firmware volume image.
+    FIRMWARE_VOLUME_IMAGE   = 0x0b,
+    /// A Management Mode driver which is also a DXE driver.
+    COMBINED_MM_DXE         = 0x0c,
+    /// The Management Mode core.
+    MM_CORE                 = 0x0d,
+    /// A standalone Management Mode driver.
+    MM_STANDALONE           = 0x0e,
+    /// The standalone Management Mode core.
+    MM_CORE_STANDALONE      = 0x0f,
+    /// Padding between files.
+    FFS_PAD                 = 0xf0,
+}}
+
+newtype_enum! {
+/// Type of a section of a file (`EFI_SECTION_TYPE`).
+pub enum SectionType: u8 => {
+    /// Matches sections of all types.
+    ALL                     = 0x00,
+    /// Compressed sections.
+    COMPRESSION             = 0x01,
+    /// Sections whose format is identified by a GUID, such as signed ones.
+    GUID_DEFINED            = 0x02,
+    /// Sections which are not used by the firmware.
+    DISPOSABLE              = 0x03,
+    /// A PE32+ image.
+    PE32                    = 0x10,
+    /// A position-independent PE32+ image.
+    PIC                     = 0x11,
+    /// A Terse Executable image.
+    TE                      = 0x12,
+    /// Dependency expression of a DXE driver.
+    DXE_DEPEX               = 0x13,
+    /// Version of the file.
+    VERSION                 = 0x14,
+    /// Name of the file, as a null-terminated UCS-2 string.
+    USER_INTERFACE          = 0x15,
+    /// A 16-bit real mode image.
+    COMPATIBILITY16         = 0x16,
+    /// A firmware volume image.
+    FIRMWARE_VOLUME_IMAGE   = 0x17,
+    /// Raw data whose format is identified by a GUID.
+    FREEFORM_SUBTYPE_GUID   = 0x18,
+    /// Raw data.
+    RAW                     = 0x19,
+    /// Dependency expression of a PEI module.
+    PEI_DEPEX               = 0x1b,
+    /// Dependency expression of a Management Mode driver.
+    MM_DEPEX                = 0x1c,
+}}
+
+bitflags! {
+    /// Attributes of a firmware volume.
+    #[repr(transparent)]
+    pub struct VolumeAttributes: u64 {
+        /// Reads can be disabled.
+        const READ_DISABLE_CAP = 0x0000_0000_0000_0001;
+        /// Reads can be enabled.
+        const READ_ENABLE_CAP = 0x0000_0000_0000_0002;
+        /// Reads are enabled.
+        const READ_STATUS = 0x0000_0000_0000_0004;
+        /// Writes can be disabled.
+        const WRITE_DISABLE_CAP = 0x0000_0000_0000_0008;
+        /// Writes can be enabled.
+        const WRITE_ENABLE_CAP = 0x0000_0000_0000_0010;
+        /// Writes are enabled.
+        const WRITE_STATUS = 0x0000_0000_0000_0020;
+        /// The attributes can be locked.
+        const LOCK_CAP = 0x0000_0000_0000_0040;
+        /// The attributes are locked.
+        const LOCK_STATUS = 0x0000_0000_0000_0080;
+        /// Writes are reliable, and leave the previous file in place if
+        /// they are interrupted.
+        const WRITE_POLICY_RELIABLE = 0x0000_0000_0000_0100;
+        /// Reads can be locked.
+        const READ_LOCK_CAP = 0x0000_0000_0000_1000;
+        /// Reads are locked.
+        const READ_LOCK_STATUS = 0x0000_0000_0000_2000;
+        /// Writes can be locked.
+        const WRITE_LOCK_CAP = 0x0000_0000_0000_4000;
+        /// Writes are locked.
+        const WRITE_LOCK_STATUS = 0x0000_0000_0000_8000;
+        /// Mask of the alignment of the volume, as a power of two.
+        const ALIGNMENT = 0x0000_0000_001f_0000;
+    }
+}
+
+bitflags! {
+    /// Attributes of a file.
+    #[repr(transparent)]
+    pub struct FileAttributes: u32 {
+        /// Mask of the alignment of the file, as a power of two.
+        const ALIGNMENT = 0x0000_001f;
+        /// The file must be at a fixed address.
+        const FIXED = 0x0000_0100;
+        /// The file is memory mapped.
+        const MEMORY_MAPPED = 0x0000_0200;
+    }
+}
+
+impl FileAttributes {
+    /// Alignment of the file, in bytes.
+    pub fn alignment(&self) -> usize {
+        1 << (*self & Self::ALIGNMENT).bits()
+    }
+}
+
+bitflags! {
+    /// Outcome of the verification of the signed sections encountered while
+    /// reading a file or a section.
+    #[repr(transparent)]
+    pub struct AuthenticationStatus: u32 {
+        /// The platform overrode the outcome of the verification.
+        const PLATFORM_OVERRIDE = 0x0000_0001;
+        /// Some sections are signed.
+        const IMAGE_SIGNED = 0x0000_0002;
+        /// Some signed sections could not be verified.
+        const NOT_TESTED = 0x0000_0004;
+        /// The verification of some signed sections failed.
+        const TEST_FAILED = 0x0000_0008;
+    }
+}
+
+/// Data read from a firmware volume, which is freed when dropped.
+pub struct FvBuffer<'a> {
+    data: NonNull<u8>,
+    len: usize,
+    bt: &'a BootServices,
+}
+
+impl FvBuffer<'_> {
+    /// The data.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data.as_ptr(), self.len) }
+    }
+}
+
+impl fmt::Debug for FvBuffer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FvBuffer").field("len", &self.len).finish()
+    }
+}
+
+impl Drop for FvBuffer<'_> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = self.bt.free_pool(self.data.as_ptr());
+    }
+}
+
+/// Information about a file, as returned by `FirmwareVolume::get_file_info`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FileInfo {
+    /// Type of the file.
+    pub file_type: FileType,
+    /// Attributes of the file.
+    pub attributes: FileAttributes,
+    /// Size of the file, without its header.
+    pub size: usize,
+}
+
+/// A file read by `FirmwareVolume::read_file`.
+#[derive(Debug)]
+pub struct File<'a> {
+    /// Type of the file.
+    pub file_type: FileType,
+    /// Attributes of the file.
+    pub attributes: FileAttributes,
+    /// Outcome of the verification of its signed sections.
+    pub authentication_status: AuthenticationStatus,
+    /// Contents of the file, without its header.
+    pub data: FvBuffer<'a>,
+}
+
+/// A section read by `FirmwareVolume::read_section`.
+#[derive(Debug)]
+pub struct Section<'a> {
+    /// Outcome of the verification of the signed sections enclosing it.
+    pub authentication_status: AuthenticationStatus,
+    /// Contents of the section, without its header.
+    pub data: FvBuffer<'a>,
+}
+
+/// A file found by `FirmwareVolume::files`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FileEntry {
+    /// Name of the file.
+    pub name: Guid,
+    /// Type of the file.
+    pub file_type: FileType,
+    /// Attributes of the file.
+    pub attributes: FileAttributes,
+    /// Size of the file, without its header.
+    pub size: usize,
+}
+
+/// The Firmware Volume 2 protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct FirmwareVolume {
+    get_volume_attributes:
+        extern "efiapi" fn(this: &FirmwareVolume, attributes: &mut VolumeAttributes) -> Status,
+    _set_volume_attributes: usize,
+    read_file: unsafe extern "efiapi" fn(
+        this: &FirmwareVolume,
+        name_guid: &Guid,
+        buffer: *mut *mut c_void,
+        buffer_size: &mut usize,
+        found_type: &mut FileType,
+        file_attributes: &mut FileAttributes,
+        authentication_status: &mut AuthenticationStatus,
+    ) -> Status,
+    read_section: unsafe extern "efiapi" fn(
+        this: &FirmwareVolume,
+        name_guid: &Guid,
+        section_type: SectionType,
+        section_instance: usize,
+        buffer: &mut *mut c_void,
+        buffer_size: &mut usize,
+        authentication_status: &mut AuthenticationStatus,
+    ) -> Status,
+    _write_file: usize,
+    get_next_file: unsafe extern "efiapi" fn(
+        this: &FirmwareVolume,
+        key: *mut c_void,
+        file_type: &mut FileType,
+        name_guid: &mut Guid,
+        attributes: &mut FileAttributes,
+        size: &mut usize,
+    ) -> Status,
+    key_size: u32,
+    parent_handle: Option<Handle>,
+    _get_info: usize,
+    _set_info: usize,
+}
+
+unsafe impl Identify for FirmwareVolume {
+    const GUID: Guid = guid!("220e73b6-6bdb-4413-8405-b974b108619a");
+}
+
+impl FirmwareVolume {
+    /// Returns the attributes of the volume.
+    pub fn get_volume_attributes(&self) -> Result<VolumeAttributes> {
+        let mut attributes = VolumeAttributes::empty();
+        (self.get_volume_attributes)(self, &mut attributes).into_with_val(|| attributes)
+    }
+
+    /// Returns the handle of the firmware volume containing the image of
+    /// this volume, if it was extracted from a file of another volume.
+    pub fn parent_handle(&self) -> Option<Handle> {
+        self.parent_handle
+    }
+
+    /// Returns the type, attributes and size of the file `name`, without
+    /// reading it.
+    ///
+    /// `NOT_FOUND` is returned if there is no such file, and `ACCESS_DENIED`
+    /// if the volume cannot be read.
+    pub fn get_file_info(&self, name: &Guid) -> Result<FileInfo> {
+        let mut size = 0;
+        let mut file_type = FileType::ALL;
+        let mut attributes = FileAttributes::empty();
+        let mut authentication_status = AuthenticationStatus::empty();
+        unsafe {
+            (self.read_file)(
+                self,
+                name,
+                ptr::null_mut(),
+                &mut size,
+                &mut file_type,
+                &mut attributes,
+                &mut authentication_status,
+            )
+        }
+        .into_with_val(|| FileInfo {
+            file_type,
+            attributes,
+            size,
+        })
+    }
+
+    /// Reads the file `name` into a newly allocated buffer.
+    ///
+    /// `NOT_FOUND` is returned if there is no such file, and `ACCESS_DENIED`
+    /// if the volume cannot be read.
+    pub fn read_file<'bt>(&self, bt: &'bt BootServices, name: &Guid) -> Result<File<'bt>> {
+        let mut buffer = ptr::null_mut();
+        let mut size = 0;
+        let mut file_type = FileType::ALL;
+        let mut attributes = FileAttributes::empty();
+        let mut authentication_status = AuthenticationStatus::empty();
+        let status = unsafe {
+            (self.read_file)(
+                self,
+                name,
+                &mut buffer,
+                &mut size,
+                &mut file_type,
+                &mut attributes,
+                &mut authentication_status,
+            )
+        };
+        let data = fv_buffer(status, bt, buffer, size)?;
+        Ok(data.map(|data| File {
+            file_type,
+            attributes,
+            authentication_status,
+            data,
+        }))
+    }
+
+    /// Reads the section `instance` of type `section_type` of the file
+    /// `name` into a newly allocated buffer, counting from 0.
+    ///
+    /// Encapsulating sections, such as compressed ones, are extracted to
+    /// look for the section. `NOT_FOUND` is returned if there is no such
+    /// file or section, `UNSUPPORTED` if an encapsulating section cannot be
+    /// extracted, and `ACCESS_DENIED` if the volume cannot be read.
+    pub fn read_section<'bt>(
+        &self,
+        bt: &'bt BootServices,
+        name: &Guid,
+        section_type: SectionType,
+        instance: usize,
+    ) -> Result<Section<'bt>> {
+        let mut buffer = ptr::null_mut();
+        let mut size = 0;
+        let mut authentication_status = AuthenticationStatus::empty();
+        let status = unsafe {
+            (self.read_section)(
+                self,
+                name,
+                section_type,
+                instance,
+                &mut buffer,
+                &mut size,
+                &mut authentication_status,
+            )
+        };
+        let data = fv_buffer(status, bt, buffer, size)?;
+        Ok(data.map(|data| Section {
+            authentication_status,
+            data,
+        }))
+    }
+
+    /// Iterates over the files of type `file_type` of the volume, or over
+    /// all of them for `FileType::ALL`.
+    ///
+    /// `UNSUPPORTED` is returned if the iteration key of the volume is
+    /// larger than expected.
+    pub fn files(&self, file_type: FileType) -> Result<Files<'_>> {
+        if self.key_size as usize > MAX_KEY_SIZE {
+            return Err(Status::UNSUPPORTED.into());
+        }
+        Ok(Files {
+            volume: self,
+            file_type,
+            key: [0; MAX_KEY_SIZE / 8],
+            done: false,
+        }
+        .into())
+    }
+}
+
+/// Takes ownership of a buffer allocated by the firmware.
+fn fv_buffer(
+    status: Status,
+    bt: &BootServices,
+    buffer: *mut c_void,
+    size: usize,
+) -> Result<FvBuffer<'_>> {
+    let completion = status.into_result()?;
+    match NonNull::new(buffer.cast()) {
+        Some(data) => Ok(completion.map(|_| FvBuffer {
+            data,
+            len: size,
+            bt,
+        })),
+        None => Err(Status::DEVICE_ERROR.into()),
+    }
+}
+
+/// Maximum size of the iteration key of a volume.
+const MAX_KEY_SIZE: usize = 64;
+
+/// Iterator over the files of a volume, returned by `FirmwareVolume::files`.
+pub struct Files<'a> {
+    volume: &'a FirmwareVolume,
+    file_type: FileType,
+    key: [u64; MAX_KEY_SIZE / 8],
+    done: bool,
+}
+
+impl fmt::Debug for Files<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Files")
+            .field("file_type", &self.file_type)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl Iterator for Files<'_> {
+    type Item = Result<FileEntry>;
+
+    fn next(&mut self) -> Option<Result<FileEntry>> {
+        if self.done {
+            return None;
+        }
+        let mut file_type = self.file_type;
+        let mut name = Guid::from_values(0, 0, 0, 0, [0; 6]);
+        let mut attributes = FileAttributes::empty();
+        let mut size = 0;
+        let status = unsafe {
+            (self.volume.get_next_file)(
+                self.volume,
+                self.key.as_mut_ptr().cast(),
+                &mut file_type,
+                &mut name,
+                &mut attributes,
+                &mut size,
+            )
+        };
+        if status.is_error() {
+            self.done = true;
+            if status == Status::NOT_FOUND {
+                return None;
+            }
+        }
+        Some(status.into_with_val(|| FileEntry {
+            name,
+            file_type,
+            attributes,
+            size,
+        }))
+    }
+}
@@ -12,7 +12,7 @@
 //! * maintaining MP-related processor status
 
 use crate::proto::Protocol;
-use crate::{unsafe_guid, Result, Status};
+use crate::{Guid, Identify, Result, Status};
 use bitflags::bitflags;
 use core::convert::TryInto;
 use core::ffi::c_void;
@@ -91,7 +91,6 @@ pub struct CpuPhysicalLocation {
 
 /// Protocol that provides services needed for multi-processor management.
 #[repr(C)]
-#[unsafe_guid("3fdda605-a76e-4f46-ad29-12f4531b3d08")]
 #[derive(Protocol)]
 pub struct MpServices {
     get_number_of_processors: extern "efiapi" fn(
@@ -136,6 +135,10 @@ pub struct MpServices {
     who_am_i: extern "efiapi" fn(this: *const MpServices, processor_number: *mut usize) -> Status,
 }
 
+unsafe impl Identify for MpServices {
+    const GUID: Guid = guid!("3fdda605-a76e-4f46-ad29-12f4531b3d08");
+}
+
 impl MpServices {
     /// Retrieves the number of logical processors and the number of enabled logical processors in the system.
     pub fn get_number_of_processors(&self) -> Result<ProcessorCount> {
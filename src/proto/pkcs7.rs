@@ -0,0 +1,367 @@
+//! PKCS7 verify protocol, and signature lists.
+//!
+//! Signature lists (`EFI_SIGNATURE_LIST`) are the format used by the secure
+//! boot databases (`db`, `dbx`, ...) to store certificates and hashes, and are
+//! also used to pass trust anchors to the PKCS7 verify protocol.
+
+use crate::proto::Protocol;
+use crate::{Error, Guid, Identify, Result, Status};
+use core::convert::TryInto;
+use core::ffi::c_void;
+use core::{fmt, mem, ptr};
+
+/// Signature type of SHA-256 hashes.
+pub const CERT_SHA256_GUID: Guid = guid!("c1c41626-504c-4092-aca9-41f936934328");
+/// Signature type of DER-encoded X.509 certificates.
+pub const CERT_X509_GUID: Guid = guid!("a5c059a1-94e4-4aa7-87b5-ab155c2bf072");
+/// Signature type of SHA-256 hashes of X.509 certificates.
+pub const CERT_X509_SHA256_GUID: Guid = guid!("3bd2a492-96c0-4079-b420-fcf98ef103ed");
+
+/// A list of signatures which all have the same type and size.
+///
+/// This is a byte-level view of an `EFI_SIGNATURE_LIST`, whose header has
+/// been validated. Lists can be parsed from the contents of a signature
+/// database with `parse`, or written to a buffer with `build`.
+///
+/// ```
+/// use uefi::proto::pkcs7::{SignatureData, SignatureList, CERT_SHA256_GUID};
+/// use uefi::Guid;
+///
+/// let owner = Guid::from_values(0x12345678, 0x9abc, 0xdef0, 0x1234, [0; 6]);
+/// let hashes = [
+///     SignatureData { owner, data: &[0xaa; 32] },
+///     SignatureData { owner, data: &[0xbb; 32] },
+/// ];
+///
+/// // Asking for the required size.
+/// let required = SignatureList::build(&mut [], CERT_SHA256_GUID, &hashes)
+///     .unwrap_err()
+///     .data()
+///     .unwrap();
+/// assert_eq!(required, SignatureList::HEADER_SIZE + 2 * (16 + 32));
+///
+/// let mut buffer = [0; 256];
+/// let list = SignatureList::build(&mut buffer, CERT_SHA256_GUID, &hashes)
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(list.as_bytes().len(), required);
+///
+/// // Parsing back the list, with trailing bytes belonging to another list.
+/// let (parsed, rest) = SignatureList::parse(&buffer[..required + 3]).unwrap();
+/// assert_eq!(rest.len(), 3);
+/// assert_eq!(parsed.signature_type(), CERT_SHA256_GUID);
+/// assert_eq!(parsed.signature_size(), 16 + 32);
+/// assert!(parsed.header().is_empty());
+/// assert_eq!(parsed.signatures().len(), 2);
+/// assert!(parsed.signatures().zip(&hashes).all(|(a, b)| a == *b));
+///
+/// // Truncated and inconsistent lists are rejected.
+/// assert!(SignatureList::parse(&buffer[..required - 1]).is_none());
+/// assert!(SignatureList::parse(&buffer[..SignatureList::HEADER_SIZE - 1]).is_none());
+/// buffer[24] = 47;
+/// assert!(SignatureList::parse(&buffer[..required]).is_none());
+///
+/// // Signatures of different sizes cannot be stored in the same list.
+/// let mixed = [hashes[0], SignatureData { owner, data: &[0xcc; 20] }];
+/// let error = SignatureList::build(&mut buffer, CERT_SHA256_GUID, &mixed).unwrap_err();
+/// assert_eq!(error.status(), uefi::Status::INVALID_PARAMETER);
+/// ```
+#[repr(transparent)]
+pub struct SignatureList([u8]);
+
+/// A signature stored in a `SignatureList` (`EFI_SIGNATURE_DATA`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SignatureData<'a> {
+    /// Identifies the agent which added the signature.
+    pub owner: Guid,
+    /// The signature itself, whose format depends on the type of the list.
+    pub data: &'a [u8],
+}
+
+/// Reads a little endian `u32` from a header at the given offset.
+fn read_u32(bytes: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+/// Reads a GUID at the beginning of some bytes.
+fn read_guid(bytes: &[u8]) -> Guid {
+    Guid::from_bytes(bytes[..16].try_into().unwrap())
+}
+
+impl SignatureList {
+    /// Size of the fixed part of the header of a signature list.
+    pub const HEADER_SIZE: usize = 28;
+
+    /// Parses the signature list at the beginning of `bytes`.
+    ///
+    /// Signature databases are made of several lists stored back to back,
+    /// so the bytes following the list are returned too. `None` is returned
+    /// if the list is malformed.
+    pub fn parse(bytes: &[u8]) -> Option<(&SignatureList, &[u8])> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return None;
+        }
+        let list_size = read_u32(bytes, 16);
+        let header_size = read_u32(bytes, 20);
+        let signature_size = read_u32(bytes, 24);
+
+        // Every signature starts with the GUID of its owner.
+        let signatures_size = list_size.checked_sub(header_size.checked_add(Self::HEADER_SIZE)?)?;
+        if list_size > bytes.len()
+            || signature_size < mem::size_of::<Guid>()
+            || signatures_size % signature_size != 0
+        {
+            return None;
+        }
+
+        let (list, rest) = bytes.split_at(list_size);
+        Some((unsafe { Self::from_bytes_unchecked(list) }, rest))
+    }
+
+    /// Writes a signature list to `buffer`, which is returned on success.
+    ///
+    /// All the signatures must have the same size, otherwise (or if there are
+    /// no signatures) an `INVALID_PARAMETER` error is returned. If the buffer
+    /// is too small, `BUFFER_TOO_SMALL` is returned along with the required
+    /// size.
+    pub fn build<'buf>(
+        buffer: &'buf mut [u8],
+        signature_type: Guid,
+        signatures: &[SignatureData],
+    ) -> Result<&'buf SignatureList, Option<usize>> {
+        let data_size = match signatures.first() {
+            Some(first) => first.data.len(),
+            None => return Err(Error::new(Status::INVALID_PARAMETER, None)),
+        };
+        if signatures.iter().any(|sig| sig.data.len() != data_size) {
+            return Err(Error::new(Status::INVALID_PARAMETER, None));
+        }
+
+        let signature_size = mem::size_of::<Guid>() + data_size;
+        let list_size = Self::HEADER_SIZE + signatures.len() * signature_size;
+        let list_size_u32: u32 = match list_size.try_into() {
+            Ok(size) => size,
+            Err(_) => return Err(Error::new(Status::INVALID_PARAMETER, None)),
+        };
+        if buffer.len() < list_size {
+            return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(list_size)));
+        }
+
+        let (header, mut data) = buffer[..list_size].split_at_mut(Self::HEADER_SIZE);
+        header[..16].copy_from_slice(&signature_type.to_bytes());
+        header[16..20].copy_from_slice(&list_size_u32.to_le_bytes());
+        header[20..24].copy_from_slice(&0u32.to_le_bytes());
+        header[24..28].copy_from_slice(&(signature_size as u32).to_le_bytes());
+        for signature in signatures {
+            let (slot, rest) = data.split_at_mut(signature_size);
+            slot[..16].copy_from_slice(&signature.owner.to_bytes());
+            slot[16..].copy_from_slice(signature.data);
+            data = rest;
+        }
+
+        Ok(unsafe { Self::from_bytes_unchecked(&buffer[..list_size]) }.into())
+    }
+
+    /// Views some bytes as a signature list, without validating them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be a well-formed signature list, with no trailing bytes.
+    pub unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &SignatureList {
+        &*(bytes as *const [u8] as *const SignatureList)
+    }
+
+    /// Returns the raw bytes of the list.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the type of the signatures in this list, such as `CERT_X509_GUID`.
+    pub fn signature_type(&self) -> Guid {
+        read_guid(&self.0)
+    }
+
+    /// Returns the type-specific header of the list.
+    pub fn header(&self) -> &[u8] {
+        let header_size = read_u32(&self.0, 20);
+        &self.0[Self::HEADER_SIZE..Self::HEADER_SIZE + header_size]
+    }
+
+    /// Returns the size of each signature, including the owner GUID.
+    pub fn signature_size(&self) -> usize {
+        read_u32(&self.0, 24)
+    }
+
+    /// Returns an iterator over the signatures of the list.
+    pub fn signatures(&self) -> impl ExactSizeIterator<Item = SignatureData<'_>> + Clone {
+        let start = Self::HEADER_SIZE + self.header().len();
+        self.0[start..]
+            .chunks_exact(self.signature_size())
+            .map(|signature| SignatureData {
+                owner: read_guid(signature),
+                data: &signature[mem::size_of::<Guid>()..],
+            })
+    }
+}
+
+impl fmt::Debug for SignatureList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SignatureList")
+            .field("signature_type", &self.signature_type())
+            .field("header_size", &self.header().len())
+            .field("signature_size", &self.signature_size())
+            .field("signature_count", &self.signatures().len())
+            .finish()
+    }
+}
+
+/// Maximum number of signature lists in a database passed to `Pkcs7Verify`.
+pub const MAX_DB_LISTS: usize = 16;
+
+/// Converts a database into the NULL-terminated list of pointers expected by
+/// the firmware, or `None` if it has too many lists.
+fn db_pointers(db: &[&SignatureList]) -> Option<[*const c_void; MAX_DB_LISTS + 1]> {
+    if db.len() > MAX_DB_LISTS {
+        return None;
+    }
+    let mut pointers = [ptr::null(); MAX_DB_LISTS + 1];
+    for (pointer, list) in pointers.iter_mut().zip(db) {
+        *pointer = list.as_bytes().as_ptr() as *const c_void;
+    }
+    Some(pointers)
+}
+
+/// Returns a pointer to a database, or NULL for an empty one.
+fn db_ptr(db: &[&SignatureList], pointers: &[*const c_void]) -> *const *const c_void {
+    if db.is_empty() {
+        ptr::null()
+    } else {
+        pointers.as_ptr()
+    }
+}
+
+/// Protocol verifying PKCS#7 signatures against trust anchors.
+///
+/// Each of the databases is a set of signature lists. The `allowed_db`
+/// contains the trusted certificates, the `revoked_db` contains the revoked
+/// certificates or hashes, and the `timestamp_db` contains the certificates
+/// trusted for timestamping, which allow signatures to outlive the revocation
+/// of their signer. The latter two may be empty. Each database can contain
+/// up to `MAX_DB_LISTS` signature lists.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Pkcs7Verify {
+    verify_buffer: unsafe extern "efiapi" fn(
+        this: &Pkcs7Verify,
+        signed_data: *const u8,
+        signed_data_size: usize,
+        in_data: *const u8,
+        in_data_size: usize,
+        allowed_db: *const *const c_void,
+        revoked_db: *const *const c_void,
+        timestamp_db: *const *const c_void,
+        content: *mut u8,
+        content_size: &mut usize,
+    ) -> Status,
+    verify_signature: unsafe extern "efiapi" fn(
+        this: &Pkcs7Verify,
+        signature: *const u8,
+        signature_size: usize,
+        in_hash: *const u8,
+        in_hash_size: usize,
+        allowed_db: *const *const c_void,
+        revoked_db: *const *const c_void,
+        timestamp_db: *const *const c_void,
+    ) -> Status,
+}
+
+unsafe impl Identify for Pkcs7Verify {
+    const GUID: Guid = guid!("47889fb2-d671-4fab-a0ca-df0e44df70d6");
+}
+
+impl Pkcs7Verify {
+    /// Verifies a PKCS#7 signed data blob.
+    ///
+    /// For detached signatures, the signed content must be passed as
+    /// `in_data`. For embedded signatures, `in_data` must be `None`, and the
+    /// extracted content is written to `content`, whose filled part is
+    /// returned. If that buffer is too small, `BUFFER_TOO_SMALL` is returned
+    /// along with the size of the content.
+    ///
+    /// A `SECURITY_VIOLATION` error is returned if the signature is invalid
+    /// or is not trusted.
+    pub fn verify_buffer<'buf>(
+        &self,
+        signed_data: &[u8],
+        in_data: Option<&[u8]>,
+        allowed_db: &[&SignatureList],
+        revoked_db: &[&SignatureList],
+        timestamp_db: &[&SignatureList],
+        content: &'buf mut [u8],
+    ) -> Result<&'buf mut [u8], Option<usize>> {
+        let too_many_lists = || Error::new(Status::INVALID_PARAMETER, None);
+        let allowed = db_pointers(allowed_db).ok_or_else(too_many_lists)?;
+        let revoked = db_pointers(revoked_db).ok_or_else(too_many_lists)?;
+        let timestamp = db_pointers(timestamp_db).ok_or_else(too_many_lists)?;
+        let (in_data_ptr, in_data_size) =
+            in_data.map_or((ptr::null(), 0), |d| (d.as_ptr(), d.len()));
+        let mut content_size = content.len();
+
+        unsafe {
+            (self.verify_buffer)(
+                self,
+                signed_data.as_ptr(),
+                signed_data.len(),
+                in_data_ptr,
+                in_data_size,
+                db_ptr(allowed_db, &allowed),
+                db_ptr(revoked_db, &revoked),
+                db_ptr(timestamp_db, &timestamp),
+                content.as_mut_ptr(),
+                &mut content_size,
+            )
+        }
+        .into_with(
+            move || &mut content[..content_size],
+            |status| {
+                if status == Status::BUFFER_TOO_SMALL {
+                    Some(content_size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Verifies a detached PKCS#7 signature of some data, given the hash of
+    /// that data.
+    ///
+    /// A `SECURITY_VIOLATION` error is returned if the signature is invalid
+    /// or is not trusted.
+    pub fn verify_signature(
+        &self,
+        signature: &[u8],
+        in_hash: &[u8],
+        allowed_db: &[&SignatureList],
+        revoked_db: &[&SignatureList],
+        timestamp_db: &[&SignatureList],
+    ) -> Result {
+        let allowed = db_pointers(allowed_db).ok_or(Status::INVALID_PARAMETER)?;
+        let revoked = db_pointers(revoked_db).ok_or(Status::INVALID_PARAMETER)?;
+        let timestamp = db_pointers(timestamp_db).ok_or(Status::INVALID_PARAMETER)?;
+
+        unsafe {
+            (self.verify_signature)(
+                self,
+                signature.as_ptr(),
+                signature.len(),
+                in_hash.as_ptr(),
+                in_hash.len(),
+                db_ptr(allowed_db, &allowed),
+                db_ptr(revoked_db, &revoked),
+                db_ptr(timestamp_db, &timestamp),
+            )
+        }
+        .into()
+    }
+}
@@ -0,0 +1,168 @@
+//! Random number generator protocol.
+
+use crate::proto::Protocol;
+use crate::{Guid, Identify, Result, Status};
+use core::{mem, ptr};
+
+/// SP800-90 Hash_DRBG, using SHA-256.
+pub const ALGORITHM_SP800_90_HASH_256: Guid = guid!("a7af67cb-603b-4d42-ba21-70bfb6293f96");
+/// SP800-90 HMAC_DRBG, using SHA-256.
+pub const ALGORITHM_SP800_90_HMAC_256: Guid = guid!("c5149b43-ae85-4f53-9982-b94335d3a9e7");
+/// SP800-90 CTR_DRBG, using AES-256.
+pub const ALGORITHM_SP800_90_CTR_256: Guid = guid!("44f0de6e-4d8c-4045-a8c7-4dd168856b9e");
+/// ANSI X9.31, using 3DES.
+pub const ALGORITHM_X9_31_3DES: Guid = guid!("63c4785a-ca34-4012-a3c8-0b6a324f5546");
+/// ANSI X9.31, using AES.
+pub const ALGORITHM_X9_31_AES: Guid = guid!("acd03321-777e-4d3d-b1c8-20cfd88820c9");
+/// Raw entropy, straight from the hardware noise source.
+pub const ALGORITHM_RAW: Guid = guid!("e43176d7-b6e8-4827-b784-7ffdc4b68561");
+
+/// Random number generator protocol.
+///
+/// This protocol provides random numbers for use in applications, or entropy
+/// for seeding other random number generators.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Rng {
+    get_info: unsafe extern "efiapi" fn(
+        this: &mut Rng,
+        algorithm_list_size: &mut usize,
+        algorithm_list: *mut Guid,
+    ) -> Status,
+    get_rng: unsafe extern "efiapi" fn(
+        this: &mut Rng,
+        algorithm: *const Guid,
+        value_length: usize,
+        value: *mut u8,
+    ) -> Status,
+}
+
+unsafe impl Identify for Rng {
+    const GUID: Guid = guid!("3152bca5-eade-433d-862e-c01cdc291f44");
+}
+
+impl Rng {
+    /// Retrieves the algorithms supported by this generator.
+    ///
+    /// The algorithms are written to `algorithms`, and the filled part of the
+    /// buffer is returned. If the buffer is too small, `BUFFER_TOO_SMALL` is
+    /// returned along with the number of supported algorithms.
+    pub fn get_info<'buf>(
+        &mut self,
+        algorithms: &'buf mut [Guid],
+    ) -> Result<&'buf mut [Guid], Option<usize>> {
+        let guid_size = mem::size_of::<Guid>();
+        let mut list_size = mem::size_of_val(algorithms);
+        let list = if algorithms.is_empty() {
+            ptr::null_mut()
+        } else {
+            algorithms.as_mut_ptr()
+        };
+
+        unsafe { (self.get_info)(self, &mut list_size, list) }.into_with(
+            move || &mut algorithms[..list_size / guid_size],
+            |status| {
+                if status == Status::BUFFER_TOO_SMALL {
+                    Some(list_size / guid_size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Fills a buffer with random bytes.
+    ///
+    /// If `algorithm` is `None`, the generator's default algorithm is used.
+    /// Otherwise, it must be one of the algorithms reported by `get_info`,
+    /// or an `UNSUPPORTED` error is returned.
+    ///
+    /// Requesting zero bytes is an `INVALID_PARAMETER` error.
+    pub fn get_rng(&mut self, algorithm: Option<&Guid>, buffer: &mut [u8]) -> Result {
+        if buffer.is_empty() {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        let algorithm = algorithm.map_or(ptr::null(), |guid| guid as *const Guid);
+        unsafe { (self.get_rng)(self, algorithm, buffer.len(), buffer.as_mut_ptr()) }.into()
+    }
+}
+
+/// Adapter implementing the `rand_core` traits on top of the RNG protocol.
+///
+/// This allows using the firmware's generator with the `rand` ecosystem, for
+/// instance to seed a faster userspace generator.
+///
+/// Firmware errors are reported by `try_fill_bytes` as `rand_core` errors
+/// whose code is `rand_core::Error::CUSTOM_START` plus the low bits of the
+/// UEFI status. A buffer is only ever reported as filled if the firmware
+/// filled all of it without warnings.
+#[cfg(feature = "rand_core")]
+pub struct RngAdapter<'rng> {
+    rng: &'rng mut Rng,
+    algorithm: Option<Guid>,
+}
+
+#[cfg(feature = "rand_core")]
+impl<'rng> RngAdapter<'rng> {
+    /// Wraps an RNG protocol, using its default algorithm.
+    pub fn new(rng: &'rng mut Rng) -> Self {
+        Self {
+            rng,
+            algorithm: None,
+        }
+    }
+
+    /// Wraps an RNG protocol, using the given algorithm.
+    pub fn with_algorithm(rng: &'rng mut Rng, algorithm: Guid) -> Self {
+        Self {
+            rng,
+            algorithm: Some(algorithm),
+        }
+    }
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::RngCore for RngAdapter<'_> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(err) = self.try_fill_bytes(dest) {
+            panic!("Failed to get random bytes from the firmware: {}", err);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> core::result::Result<(), rand_core::Error> {
+        // The protocol rejects empty buffers, but there is nothing to do.
+        if dest.is_empty() {
+            return Ok(());
+        }
+
+        let status = match self.rng.get_rng(self.algorithm.as_ref(), dest) {
+            Ok(completion) => completion.status(),
+            Err(err) => err.status(),
+        };
+        if status.is_success() {
+            Ok(())
+        } else {
+            // Do not let callers mistake partially filled buffers for random data.
+            dest.iter_mut().for_each(|byte| *byte = 0);
+            let code = rand_core::Error::CUSTOM_START + (status.0 as u32 & 0x3fff_ffff);
+            Err(core::num::NonZeroU32::new(code).unwrap().into())
+        }
+    }
+}
+
+// The protocol's algorithms are all meant to be cryptographically secure.
+#[cfg(feature = "rand_core")]
+impl rand_core::CryptoRng for RngAdapter<'_> {}
@@ -0,0 +1,185 @@
+//! Security architectural protocols.
+//!
+//! The firmware calls these protocols to authenticate images before loading
+//! them. Replacing their functions allows platform code to enforce its own
+//! image authentication policy.
+
+use crate::proto::device_path::DevicePath;
+use crate::proto::Protocol;
+use crate::{Guid, Identify, Result, Status};
+use core::cell::UnsafeCell;
+use core::slice;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+type FileAuthenticationFn = unsafe extern "efiapi" fn(
+    this: *const Security2,
+    device_path: *const DevicePath,
+    file_buffer: *const u8,
+    file_size: usize,
+    boot_policy: bool,
+) -> Status;
+
+/// Security2 architectural protocol, used by the firmware to authenticate
+/// images and files.
+///
+/// Only one instance of this protocol is installed, by the platform firmware.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Security2 {
+    file_authentication: FileAuthenticationFn,
+}
+
+unsafe impl Identify for Security2 {
+    const GUID: Guid = guid!("94ab2f58-1438-4ef1-9152-18941a3a0e68");
+}
+
+/// Policy callback of a `Security2Hook`.
+///
+/// It receives the device path of the file being authenticated and the
+/// contents of the file, if they are known, as well as whether the file is
+/// being loaded by the boot manager. Returning `SUCCESS` lets the original
+/// authentication function decide. Any other status is returned to the
+/// firmware as is, which denies the file if it is an error.
+pub type AuthenticationPolicy =
+    fn(device_path: Option<&DevicePath>, file: Option<&[u8]>, boot_policy: bool) -> Status;
+
+/// State of the installed hook.
+#[derive(Clone, Copy)]
+struct HookState {
+    original: FileAuthenticationFn,
+    policy: AuthenticationPolicy,
+}
+
+/// Storage of the currently installed hook.
+struct HookCell {
+    state: UnsafeCell<Option<HookState>>,
+    /// Whether the state is in use.
+    busy: AtomicBool,
+}
+
+// SAFETY: the state is only accessed with the `busy` flag set. Boot services
+// code only runs on the bootstrap processor, but the firmware could still
+// authenticate an image from an event notification function.
+unsafe impl Sync for HookCell {}
+
+impl HookCell {
+    /// Runs `f` with exclusive access to the hook state, or returns `None` if
+    /// the state is already in use.
+    fn with<R>(&self, f: impl FnOnce(&mut Option<HookState>) -> R) -> Option<R> {
+        if self.busy.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: the `busy` flag makes this the only reference to the state.
+        let result = f(unsafe { &mut *self.state.get() });
+        self.busy.store(false, Ordering::Release);
+        Some(result)
+    }
+}
+
+static HOOK: HookCell = HookCell {
+    state: UnsafeCell::new(None),
+    busy: AtomicBool::new(false),
+};
+
+impl Security2 {
+    /// Authenticates a file, as the firmware does before loading an image.
+    ///
+    /// At least one of the device path and the file contents should be
+    /// given. A `SECURITY_VIOLATION` or `ACCESS_DENIED` error means that the
+    /// file must not be used.
+    pub fn file_authentication(
+        &self,
+        device_path: Option<&DevicePath>,
+        file: Option<&[u8]>,
+        boot_policy: bool,
+    ) -> Result {
+        let device_path = device_path.map_or(core::ptr::null(), |path| path as *const _);
+        let (file_buffer, file_size) =
+            file.map_or((core::ptr::null(), 0), |file| (file.as_ptr(), file.len()));
+        unsafe {
+            (self.file_authentication)(self, device_path, file_buffer, file_size, boot_policy)
+        }
+        .into()
+    }
+
+    /// Replaces the authentication function of the protocol with one which
+    /// calls `policy`, and then the original function if the policy returned
+    /// `SUCCESS`.
+    ///
+    /// The original function is restored when the returned hook is dropped.
+    /// Only one hook can be installed at a time, otherwise `ALREADY_STARTED`
+    /// is returned.
+    ///
+    /// # Safety
+    ///
+    /// The hook points to code in the current image, so it must be dropped
+    /// before the image exits or is unloaded, and before the boot services
+    /// are exited. Leaking it would make the firmware call into freed memory
+    /// on the next image load, which usually breaks the boot flow.
+    ///
+    /// The policy is called by the firmware in the middle of loading an
+    /// image, so it should not load images itself.
+    pub unsafe fn hook(&mut self, policy: AuthenticationPolicy) -> Result<Security2Hook<'_>> {
+        let original = self.file_authentication;
+        let installed = HOOK.with(|hook| {
+            if hook.is_some() {
+                return false;
+            }
+            *hook = Some(HookState { original, policy });
+            true
+        });
+        if installed != Some(true) {
+            return Err(Status::ALREADY_STARTED.into());
+        }
+        self.file_authentication = hooked_file_authentication;
+        Ok(Security2Hook { protocol: self }.into())
+    }
+}
+
+/// Authentication function installed by `Security2::hook`.
+unsafe extern "efiapi" fn hooked_file_authentication(
+    this: *const Security2,
+    device_path: *const DevicePath,
+    file_buffer: *const u8,
+    file_size: usize,
+    boot_policy: bool,
+) -> Status {
+    // The state is copied out, so that the policy and the original function
+    // run without holding it.
+    let hook = match HOOK.with(|hook| *hook) {
+        Some(Some(hook)) => hook,
+        // The hook is always installed before this function.
+        _ => return Status::ABORTED,
+    };
+
+    let file = if file_buffer.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(file_buffer, file_size))
+    };
+    let status = (hook.policy)(device_path.as_ref(), file, boot_policy);
+    if status != Status::SUCCESS {
+        return status;
+    }
+    (hook.original)(this, device_path, file_buffer, file_size, boot_policy)
+}
+
+/// An authentication policy installed with `Security2::hook`.
+///
+/// Dropping it restores the original authentication function.
+pub struct Security2Hook<'a> {
+    protocol: &'a mut Security2,
+}
+
+impl Security2Hook<'_> {
+    /// Removes the hook. This is the same as dropping it.
+    pub fn unhook(self) {}
+}
+
+impl Drop for Security2Hook<'_> {
+    fn drop(&mut self) {
+        if let Some(Some(hook)) = HOOK.with(Option::take) {
+            self.protocol.file_authentication = hook.original;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Service binding protocols.
+//!
+//! Many protocols, including most of the network stack, are not installed
+//! directly on a device handle. Instead, the device handle carries a service
+//! binding protocol, which creates a new child handle for every user of the
+//! service. The protocol itself is then installed on that child handle.
+//!
+//! All service binding protocols share the same interface, and only differ in
+//! their GUID. Here, they are represented by `ServiceBinding<P>`, where `P` is
+//! the protocol installed on the children.
+//!
+//! ```no_run
+//! use uefi::prelude::*;
+//! use uefi::proto::hash2::Hash2;
+//! use uefi::proto::service_binding::ServiceBinding;
+//! # use uefi::table::boot::BootServices;
+//!
+//! # fn example(bt: &BootServices, image: Handle) -> uefi::Result {
+//! let binding = bt.locate_protocol::<ServiceBinding<Hash2>>()?.log();
+//! let binding = unsafe { &mut *binding.get() };
+//!
+//! // The child is destroyed when `child` goes out of scope, after the
+//! // protocol has been closed.
+//! let child = binding.create_scoped_child()?.log();
+//! let hash2 = child.open(bt, image)?.log();
+//! let hash2 = unsafe { &mut *hash2.get() };
+//! # let _ = hash2;
+//! # Ok(().into())
+//! # }
+//! ```
+
+use crate::proto::Protocol;
+use crate::table::boot::{
+    BootServices, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol,
+};
+use crate::{Completion, Guid, Handle, Identify, Result, Status};
+use core::fmt;
+use core::marker::PhantomData;
+
+/// A protocol which is installed on the children of a service binding.
+///
+/// # Safety
+///
+/// `SERVICE_BINDING_GUID` must be the GUID of the service binding protocol
+/// whose children carry this protocol.
+pub unsafe trait ChildProtocol: Protocol {
+    /// GUID of the service binding protocol which creates the children.
+    const SERVICE_BINDING_GUID: Guid;
+}
+
+/// Service binding protocol used to create children carrying protocol `P`.
+#[repr(C)]
+pub struct ServiceBinding<P: ChildProtocol> {
+    create_child: extern "efiapi" fn(this: &mut Self, child: &mut Option<Handle>) -> Status,
+    destroy_child: extern "efiapi" fn(this: &mut Self, child: Handle) -> Status,
+    _protocol: PhantomData<P>,
+}
+
+unsafe impl<P: ChildProtocol> Identify for ServiceBinding<P> {
+    const GUID: Guid = P::SERVICE_BINDING_GUID;
+}
+
+impl<P: ChildProtocol> Protocol for ServiceBinding<P> {}
+
+impl<P: ChildProtocol> !Send for ServiceBinding<P> {}
+
+impl<P: ChildProtocol> !Sync for ServiceBinding<P> {}
+
+impl<P: ChildProtocol> ServiceBinding<P> {
+    /// Creates a new child handle, on which the protocol `P` is installed.
+    ///
+    /// The child must later be destroyed with `destroy_child`. Use
+    /// `create_scoped_child` to have that done automatically.
+    pub fn create_child(&mut self) -> Result<ChildHandle> {
+        let mut child = None;
+        (self.create_child)(self, &mut child).into_with_val(|| {
+            ChildHandle(child.expect("create_child succeeded without a child handle"))
+        })
+    }
+
+    /// Destroys a child handle which was created by `create_child`.
+    ///
+    /// The firmware refuses to destroy a child while its protocol is still
+    /// opened, in which case an `ACCESS_DENIED` error is returned, and the
+    /// child handle is given back.
+    pub fn destroy_child(&mut self, child: ChildHandle) -> Result<(), ChildHandle> {
+        let handle = child.0;
+        (self.destroy_child)(self, handle).into_with(|| (), |_| ChildHandle(handle))
+    }
+
+    /// Creates a new child handle, which is destroyed when the returned
+    /// `ScopedChild` is dropped.
+    pub fn create_scoped_child(&mut self) -> Result<ScopedChild<'_, P>> {
+        let (status, child) = self.create_child()?.split();
+        let child = ScopedChild {
+            binding: self,
+            handle: child.0,
+        };
+        Ok(Completion::new(status, child))
+    }
+}
+
+/// A child handle created by a `ServiceBinding`.
+///
+/// The handle should be passed back to `ServiceBinding::destroy_child` once
+/// the child is no longer needed, otherwise the resources associated with it
+/// are leaked.
+#[must_use]
+pub struct ChildHandle(Handle);
+
+impl ChildHandle {
+    /// Returns the handle on which the child protocol is installed.
+    pub fn handle(&self) -> Handle {
+        self.0
+    }
+}
+
+impl fmt::Debug for ChildHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ChildHandle").field(&self.0).finish()
+    }
+}
+
+/// A child handle which is destroyed when dropped.
+///
+/// Protocols opened on the child borrow it, so they are always closed before
+/// the child is destroyed.
+pub struct ScopedChild<'a, P: ChildProtocol> {
+    binding: &'a mut ServiceBinding<P>,
+    handle: Handle,
+}
+
+impl<'a, P: ChildProtocol> ScopedChild<'a, P> {
+    /// Returns the handle on which the child protocol is installed.
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    /// Opens the child protocol, on behalf of the `agent` image.
+    ///
+    /// If this fails, the child is still destroyed once the `ScopedChild` is
+    /// dropped, so `?` can be used without leaking it.
+    pub fn open<'b>(
+        &'b self,
+        bt: &'b BootServices,
+        agent: Handle,
+    ) -> Result<ScopedProtocol<'b, P>> {
+        let params = OpenProtocolParams {
+            handle: self.handle,
+            agent,
+            controller: None,
+        };
+        bt.open_protocol(params, OpenProtocolAttributes::GetProtocol)
+    }
+}
+
+impl<'a, P: ChildProtocol> Drop for ScopedChild<'a, P> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let destroy_child = self.binding.destroy_child;
+        let _ = destroy_child(self.binding, self.handle);
+    }
+}
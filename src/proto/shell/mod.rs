@@ -0,0 +1,355 @@
+//! UEFI Shell protocols.
+//!
+//! The shell installs the `Shell` protocol when it starts, which gives the
+//! applications it launches access to its environment variables, its
+//! current directories, its file system mappings, and lets them run other
+//! commands. Applications which were not launched from the shell, for
+//! example because they were booted directly, will not find it.
+//!
+//! The arguments of an application are passed by the `ShellParameters`
+//! protocol, in the `params` module.
+
+#[cfg(feature = "exts")]
+use crate::alloc_api::vec::Vec;
+use crate::proto::device_path::DevicePath;
+use crate::proto::media::file::{FileHandle, FileMode};
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{CStr16, Char16, Event, Guid, Handle, Identify, Result, Status};
+use core::ffi::c_void;
+use core::ptr::{self, NonNull};
+use core::{fmt, mem};
+
+pub mod params;
+
+/// The Shell protocol
+///
+/// The protocol is shared by all the applications run by a shell instance,
+/// and its functions do not take a pointer to it, so its methods take
+/// `&self`.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Shell {
+    execute: unsafe extern "efiapi" fn(
+        parent_image_handle: &Handle,
+        command_line: *const Char16,
+        environment: *const *const Char16,
+        status_code: &mut Status,
+    ) -> Status,
+    get_env: unsafe extern "efiapi" fn(name: *const Char16) -> *const Char16,
+    set_env: unsafe extern "efiapi" fn(
+        name: *const Char16,
+        value: *const Char16,
+        volatile: bool,
+    ) -> Status,
+    _get_alias: usize,
+    _set_alias: usize,
+    _get_help_text: usize,
+    get_device_path_from_map:
+        unsafe extern "efiapi" fn(mapping: *const Char16) -> *const DevicePath,
+    get_map_from_device_path:
+        unsafe extern "efiapi" fn(device_path: &mut *const DevicePath) -> *const Char16,
+    _get_device_path_from_file_path: usize,
+    _get_file_path_from_device_path: usize,
+    _set_map: usize,
+    get_cur_dir: unsafe extern "efiapi" fn(file_system_mapping: *const Char16) -> *const Char16,
+    set_cur_dir:
+        unsafe extern "efiapi" fn(file_system: *const Char16, dir: *const Char16) -> Status,
+    _open_file_list: usize,
+    _free_file_list: usize,
+    _remove_dup_in_file_list: usize,
+    batch_is_active: extern "efiapi" fn() -> bool,
+    is_root_shell: extern "efiapi" fn() -> bool,
+    _enable_page_break: usize,
+    _disable_page_break: usize,
+    _get_page_break: usize,
+    _get_device_name: usize,
+    _get_file_info: usize,
+    _set_file_info: usize,
+    open_file_by_name: unsafe extern "efiapi" fn(
+        file_name: *const Char16,
+        file_handle: &mut *mut c_void,
+        open_mode: FileMode,
+    ) -> Status,
+    close_file: unsafe extern "efiapi" fn(file_handle: *mut c_void) -> Status,
+    _create_file: usize,
+    read_file: unsafe extern "efiapi" fn(
+        file_handle: *mut c_void,
+        read_size: &mut usize,
+        buffer: *mut c_void,
+    ) -> Status,
+    write_file: unsafe extern "efiapi" fn(
+        file_handle: *mut c_void,
+        buffer_size: &mut usize,
+        buffer: *const c_void,
+    ) -> Status,
+    delete_file: unsafe extern "efiapi" fn(file_handle: *mut c_void) -> Status,
+    _delete_file_by_name: usize,
+    get_file_position:
+        unsafe extern "efiapi" fn(file_handle: *mut c_void, position: &mut u64) -> Status,
+    set_file_position: unsafe extern "efiapi" fn(file_handle: *mut c_void, position: u64) -> Status,
+    flush_file: unsafe extern "efiapi" fn(file_handle: *mut c_void) -> Status,
+    _find_files: usize,
+    _find_files_in_dir: usize,
+    get_file_size: unsafe extern "efiapi" fn(file_handle: *mut c_void, size: &mut u64) -> Status,
+    _open_root: usize,
+    _open_root_by_handle: usize,
+    execution_break: Event,
+    major_version: u32,
+    minor_version: u32,
+}
+
+unsafe impl Identify for Shell {
+    const GUID: Guid = guid!("6302d008-7f9b-4f30-87ac-60c9fef5da4e");
+}
+
+impl Shell {
+    /// Finds the protocol of the shell which launched this application.
+    ///
+    /// Returns `None` if the application was not launched from the shell.
+    pub fn find(bt: &BootServices) -> Option<&Shell> {
+        let shell = bt.locate_protocol::<Shell>().ok()?.log();
+        Some(unsafe { &*shell.get() })
+    }
+
+    /// Version of the shell specification implemented by the shell, as a
+    /// major and a minor version.
+    pub fn version(&self) -> (u32, u32) {
+        (self.major_version, self.minor_version)
+    }
+
+    /// Runs a command line, as if it was typed in the shell, and returns the
+    /// status returned by the command.
+    ///
+    /// `parent_image` is the handle of the calling application. The command
+    /// runs with the environment variables of the shell.
+    pub fn execute(&self, parent_image: Handle, command_line: &CStr16) -> Result<Status> {
+        let mut status_code = Status::SUCCESS;
+        unsafe {
+            (self.execute)(
+                &parent_image,
+                command_line.as_ptr(),
+                ptr::null(),
+                &mut status_code,
+            )
+        }
+        .into_with_val(|| status_code)
+    }
+
+    /// Runs a command line like `execute`, with only the environment
+    /// variables in `environment`, given as `name=value` strings.
+    #[cfg(feature = "exts")]
+    pub fn execute_with_env(
+        &self,
+        parent_image: Handle,
+        command_line: &CStr16,
+        environment: &[&CStr16],
+    ) -> Result<Status> {
+        let mut environment: Vec<_> = environment.iter().map(|var| var.as_ptr()).collect();
+        environment.push(ptr::null());
+        let mut status_code = Status::SUCCESS;
+        unsafe {
+            (self.execute)(
+                &parent_image,
+                command_line.as_ptr(),
+                environment.as_ptr(),
+                &mut status_code,
+            )
+        }
+        .into_with_val(|| status_code)
+    }
+
+    /// Returns the value of the environment variable `name`, or `None` if
+    /// it does not exist.
+    ///
+    /// The value is owned by the shell, and becomes invalid when the
+    /// variable is changed.
+    pub fn get_env(&self, name: &CStr16) -> Option<&CStr16> {
+        unsafe { opt_cstr16((self.get_env)(name.as_ptr())) }
+    }
+
+    /// Iterates over the names of the environment variables.
+    pub fn env_names(&self) -> impl Iterator<Item = &CStr16> {
+        let mut names = unsafe { (self.get_env)(ptr::null()) };
+        core::iter::from_fn(move || {
+            // The names are a sequence of strings, terminated by an empty one.
+            let name = unsafe { opt_cstr16(names)? };
+            if name.to_u16_slice().is_empty() {
+                return None;
+            }
+            names = unsafe { names.add(name.to_u16_slice_with_nul().len()) };
+            Some(name)
+        })
+    }
+
+    /// Sets the environment variable `name` to `value`, creating it if
+    /// needed. An empty value deletes the variable.
+    ///
+    /// Volatile variables are lost when the shell exits, others are stored
+    /// in UEFI variables.
+    pub fn set_env(&self, name: &CStr16, value: &CStr16, volatile: bool) -> Result {
+        unsafe { (self.set_env)(name.as_ptr(), value.as_ptr(), volatile) }.into()
+    }
+
+    /// Returns the current directory of the file system mapped as
+    /// `file_system`, or of the current file system if it is `None`.
+    ///
+    /// Returns `None` if there is no such file system or no current
+    /// directory.
+    pub fn get_cur_dir(&self, file_system: Option<&CStr16>) -> Option<&CStr16> {
+        let file_system = file_system.map_or(ptr::null(), CStr16::as_ptr);
+        unsafe { opt_cstr16((self.get_cur_dir)(file_system)) }
+    }
+
+    /// Sets the current directory of the file system mapped as
+    /// `file_system`, or of the current file system if it is `None`.
+    ///
+    /// If `file_system` is `None` and `dir` starts with a mapping, such as
+    /// `fs0:\efi`, the current file system is changed as well.
+    pub fn set_cur_dir(&self, file_system: Option<&CStr16>, dir: &CStr16) -> Result {
+        let file_system = file_system.map_or(ptr::null(), CStr16::as_ptr);
+        unsafe { (self.set_cur_dir)(file_system, dir.as_ptr()) }.into()
+    }
+
+    /// Returns the device path mapped as `mapping`, such as `fs0:`.
+    pub fn get_device_path_from_map(&self, mapping: &CStr16) -> Option<&DevicePath> {
+        unsafe { (self.get_device_path_from_map)(mapping.as_ptr()).as_ref() }
+    }
+
+    /// Returns the mappings of the device at `device_path`, as a list
+    /// separated by semicolons, such as `fs0:;blk0:`.
+    ///
+    /// Returns `None` if the device has no mapping.
+    pub fn get_map_from_device_path(&self, device_path: &DevicePath) -> Option<&CStr16> {
+        let mut device_path: *const DevicePath = device_path;
+        unsafe { opt_cstr16((self.get_map_from_device_path)(&mut device_path)) }
+    }
+
+    /// Whether a script is running.
+    pub fn batch_is_active(&self) -> bool {
+        (self.batch_is_active)()
+    }
+
+    /// Whether this shell is the root shell, and not one started by another
+    /// shell.
+    pub fn is_root_shell(&self) -> bool {
+        (self.is_root_shell)()
+    }
+
+    /// Event signaled when the user asks to stop the running command, with
+    /// `Ctrl-C`.
+    pub fn execution_break(&self) -> Event {
+        unsafe { self.execution_break.unsafe_clone() }
+    }
+
+    /// Opens the file at `path`, which may be relative to the current
+    /// directory or start with a mapping, such as `fs0:\efi\boot`.
+    ///
+    /// The shell also recognizes special names, such as `NUL` or the
+    /// standard streams, which are not opened through a file system.
+    pub fn open_file_by_name(&self, path: &CStr16, open_mode: FileMode) -> Result<ShellFile<'_>> {
+        let mut handle = ptr::null_mut();
+        unsafe { (self.open_file_by_name)(path.as_ptr(), &mut handle, open_mode) }.into_with_val(
+            || ShellFile {
+                handle: NonNull::new(handle).unwrap(),
+                shell: self,
+            },
+        )
+    }
+}
+
+/// Converts a possibly null string owned by the shell.
+unsafe fn opt_cstr16<'a>(ptr: *const Char16) -> Option<&'a CStr16> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr16::from_ptr(ptr))
+    }
+}
+
+/// A file opened through the shell, which is closed when dropped.
+pub struct ShellFile<'a> {
+    handle: NonNull<c_void>,
+    shell: &'a Shell,
+}
+
+impl ShellFile<'_> {
+    /// Reads from the current position of the file into `buffer`, and
+    /// returns the number of bytes read, which is zero at the end of the
+    /// file.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        let mut size = buffer.len();
+        unsafe { (self.shell.read_file)(self.raw(), &mut size, buffer.as_mut_ptr().cast()) }
+            .into_with_val(|| size)
+    }
+
+    /// Writes `buffer` at the current position of the file.
+    ///
+    /// On error, the number of bytes which were written is returned.
+    pub fn write(&mut self, buffer: &[u8]) -> Result<(), usize> {
+        let mut size = buffer.len();
+        unsafe { (self.shell.write_file)(self.raw(), &mut size, buffer.as_ptr().cast()) }
+            .into_with_err(|_| size)
+    }
+
+    /// Returns the current position in the file.
+    pub fn get_position(&mut self) -> Result<u64> {
+        let mut position = 0;
+        unsafe { (self.shell.get_file_position)(self.raw(), &mut position) }
+            .into_with_val(|| position)
+    }
+
+    /// Sets the current position in the file.
+    pub fn set_position(&mut self, position: u64) -> Result {
+        unsafe { (self.shell.set_file_position)(self.raw(), position) }.into()
+    }
+
+    /// Returns the size of the file.
+    pub fn get_size(&mut self) -> Result<u64> {
+        let mut size = 0;
+        unsafe { (self.shell.get_file_size)(self.raw(), &mut size) }.into_with_val(|| size)
+    }
+
+    /// Flushes the data written to the file.
+    pub fn flush(&mut self) -> Result {
+        unsafe { (self.shell.flush_file)(self.raw()) }.into()
+    }
+
+    /// Deletes and closes the file.
+    pub fn delete(self) -> Result {
+        let status = unsafe { (self.shell.delete_file)(self.raw()) };
+        mem::forget(self);
+        status.into()
+    }
+
+    /// Converts the file into a handle of the File protocol.
+    ///
+    /// # Safety
+    ///
+    /// The shell handles are File protocol handles in the EDK2
+    /// implementation of the shell, but the specification does not require
+    /// it. Closing the handle through the File protocol also bypasses the
+    /// bookkeeping of the shell.
+    pub unsafe fn into_file_handle(self) -> FileHandle {
+        let handle = FileHandle::new(self.raw().cast());
+        mem::forget(self);
+        handle
+    }
+
+    fn raw(&self) -> *mut c_void {
+        self.handle.as_ptr()
+    }
+}
+
+impl fmt::Debug for ShellFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ShellFile").field(&self.handle).finish()
+    }
+}
+
+impl Drop for ShellFile<'_> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = unsafe { (self.shell.close_file)(self.raw()) };
+    }
+}
@@ -0,0 +1,180 @@
+//! Shell Parameters protocol.
+//!
+//! The shell installs this protocol on the handle of each application it
+//! launches, to pass it its arguments and its standard streams, which may
+//! be redirected to files.
+//!
+//! Applications which were not launched from the shell only get their load
+//! options, which `args` splits with the quoting rules of the shell.
+
+use super::{Shell, ShellFile};
+use crate::proto::Protocol;
+#[cfg(feature = "exts")]
+use crate::{
+    alloc_api::{
+        string::{String, ToString},
+        vec::Vec,
+    },
+    proto::loaded_image::LoadedImage,
+    table::boot::BootServices,
+    Handle, Result,
+};
+use crate::{CStr16, Char16, Guid, Identify};
+use core::ffi::c_void;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+use core::slice;
+#[cfg(feature = "exts")]
+use core::{iter::Peekable, str::Chars};
+
+/// The Shell Parameters protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct ShellParameters {
+    argv: *const *const Char16,
+    argc: usize,
+    stdin: *mut c_void,
+    stdout: *mut c_void,
+    stderr: *mut c_void,
+}
+
+unsafe impl Identify for ShellParameters {
+    const GUID: Guid = guid!("752f3136-4e16-4fdc-a22a-e5f46812f4ca");
+}
+
+impl ShellParameters {
+    /// Iterates over the arguments, the first one being the name of the
+    /// application as it was typed.
+    ///
+    /// Quotes have already been removed by the shell.
+    pub fn args(&self) -> impl ExactSizeIterator<Item = &CStr16> {
+        let argv = if self.argv.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.argv, self.argc) }
+        };
+        argv.iter().map(|&arg| unsafe { CStr16::from_ptr(arg) })
+    }
+
+    /// Copies the arguments into Rust strings.
+    #[cfg(feature = "exts")]
+    pub fn args_to_vec(&self) -> Vec<String> {
+        self.args().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Standard input of the application, which may be redirected from a
+    /// file.
+    ///
+    /// The handle is owned by the shell, and must not be closed.
+    pub fn stdin<'a>(&self, shell: &'a Shell) -> Option<ManuallyDrop<ShellFile<'a>>> {
+        std_handle(self.stdin, shell)
+    }
+
+    /// Standard output of the application, which may be redirected to a
+    /// file.
+    ///
+    /// The handle is owned by the shell, and must not be closed.
+    pub fn stdout<'a>(&self, shell: &'a Shell) -> Option<ManuallyDrop<ShellFile<'a>>> {
+        std_handle(self.stdout, shell)
+    }
+
+    /// Standard error of the application, which may be redirected to a
+    /// file.
+    ///
+    /// The handle is owned by the shell, and must not be closed.
+    pub fn stderr<'a>(&self, shell: &'a Shell) -> Option<ManuallyDrop<ShellFile<'a>>> {
+        std_handle(self.stderr, shell)
+    }
+}
+
+fn std_handle(handle: *mut c_void, shell: &Shell) -> Option<ManuallyDrop<ShellFile<'_>>> {
+    let handle = NonNull::new(handle)?;
+    Some(ManuallyDrop::new(ShellFile { handle, shell }))
+}
+
+/// Returns the arguments of the application `image`.
+///
+/// If it was launched from the shell, they are taken from the
+/// `ShellParameters` protocol. Otherwise, they are split from its load
+/// options by `split_args`, and there are none if the load options are not
+/// a command line.
+#[cfg(feature = "exts")]
+pub fn args(bt: &BootServices, image: Handle) -> Result<Vec<String>> {
+    if let Ok(params) = bt.handle_protocol::<ShellParameters>(image) {
+        let params = unsafe { &*params.log().get() };
+        return Ok(params.args_to_vec().into());
+    }
+
+    let loaded_image = bt.handle_protocol::<LoadedImage>(image)?.log();
+    let loaded_image = unsafe { &*loaded_image.get() };
+    let options = match loaded_image.load_options_as_bytes() {
+        Some(options) if options.len() % 2 == 0 => options,
+        _ => return Ok(Vec::new().into()),
+    };
+    let codes = options
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&code| code != 0);
+    let command_line: String = match char::decode_utf16(codes).collect() {
+        Ok(command_line) => command_line,
+        Err(_) => return Ok(Vec::new().into()),
+    };
+    Ok(split_args(&command_line).collect::<Vec<_>>().into())
+}
+
+/// Splits a command line into arguments, with the rules of the shell.
+///
+/// Arguments are separated by spaces or tabs. Double quotes group spaces
+/// into an argument, and are removed. The `^` character escapes the
+/// character which follows it, including quotes and itself. Backslashes
+/// separate directories in UEFI paths, and are kept as is.
+///
+/// ```
+/// use uefi::proto::shell::params::split_args;
+///
+/// let args: Vec<_> = split_args(r#"app.efi  fs0:\efi\boot "two words" ""  say^"hi^"  ^^"#).collect();
+/// assert_eq!(
+///     args,
+///     ["app.efi", r"fs0:\efi\boot", "two words", "", "say\"hi\"", "^"]
+/// );
+///
+/// let args: Vec<_> = split_args(r#"a"b c"d"#).collect();
+/// assert_eq!(args, ["ab cd"]);
+///
+/// assert_eq!(split_args("  ").count(), 0);
+/// ```
+#[cfg(feature = "exts")]
+pub fn split_args(command_line: &str) -> SplitArgs<'_> {
+    SplitArgs {
+        chars: command_line.chars().peekable(),
+    }
+}
+
+/// Iterator over the arguments of a command line, returned by `split_args`.
+#[cfg(feature = "exts")]
+#[derive(Clone, Debug)]
+pub struct SplitArgs<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+#[cfg(feature = "exts")]
+impl Iterator for SplitArgs<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while self.chars.next_if(|&c| c == ' ' || c == '\t').is_some() {}
+        self.chars.peek()?;
+
+        let mut arg = String::new();
+        let mut quoted = false;
+        while let Some(c) = self.chars.next() {
+            match c {
+                ' ' | '\t' if !quoted => break,
+                '"' => quoted = !quoted,
+                '^' => arg.extend(self.chars.next()),
+                c => arg.push(c),
+            }
+        }
+        Some(arg)
+    }
+}
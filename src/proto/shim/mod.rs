@@ -9,29 +9,79 @@
 
 use crate::proto::Protocol;
 use crate::result::Error;
-use crate::{unsafe_guid, Result, Status};
+use crate::{Guid, Identify, Result, Status};
 use core::convert::TryInto;
 use core::ffi::c_void;
 use core::mem::MaybeUninit;
 
-// The `PE_COFF_LOADER_IMAGE_CONTEXT` type. None of our methods need to inspect
-// the fields of this struct, we just need to make sure it is the right size.
+/// Information about a PE/COFF image, as parsed by shim
+/// (`PE_COFF_LOADER_IMAGE_CONTEXT`).
+///
+/// The layout of this structure has been stable across shim versions.
+#[derive(Debug)]
 #[repr(C)]
-struct Context {
-    _image_address: u64,
-    _image_size: u64,
-    _entry_point: u64,
-    _size_of_headers: usize,
-    _image_type: u16,
-    _number_of_sections: u16,
-    _section_alignment: u32,
+pub struct Context {
+    image_address: u64,
+    image_size: u64,
+    entry_point: u64,
+    size_of_headers: usize,
+    image_type: u16,
+    number_of_sections: u16,
+    section_alignment: u32,
+    // The pointers point into the image buffer, so they are not exposed.
     _first_section: *const c_void,
     _reloc_dir: *const c_void,
     _sec_dir: *const c_void,
-    _number_of_rva_and_sizes: u64,
+    number_of_rva_and_sizes: u64,
     _pe_hdr: *const c_void,
 }
 
+// Make sure that the structure matches the one of shim.
+#[cfg(target_pointer_width = "64")]
+const _: [(); 80] = [(); core::mem::size_of::<Context>()];
+
+impl Context {
+    /// Preferred load address of the image.
+    pub fn image_address(&self) -> u64 {
+        self.image_address
+    }
+
+    /// Size of the image once loaded in memory.
+    pub fn image_size(&self) -> u64 {
+        self.image_size
+    }
+
+    /// Address of the entry point, relative to the image base.
+    pub fn entry_point(&self) -> u64 {
+        self.entry_point
+    }
+
+    /// Size of the headers of the image.
+    pub fn size_of_headers(&self) -> usize {
+        self.size_of_headers
+    }
+
+    /// Subsystem of the image, such as 10 for an EFI application.
+    pub fn image_type(&self) -> u16 {
+        self.image_type
+    }
+
+    /// Number of sections in the image.
+    pub fn number_of_sections(&self) -> u16 {
+        self.number_of_sections
+    }
+
+    /// Alignment of the sections once loaded in memory.
+    pub fn section_alignment(&self) -> u32 {
+        self.section_alignment
+    }
+
+    /// Number of entries in the data directory of the image.
+    pub fn number_of_rva_and_sizes(&self) -> u64 {
+        self.number_of_rva_and_sizes
+    }
+}
+
 const SHA1_DIGEST_SIZE: usize = 20;
 const SHA256_DIGEST_SIZE: usize = 32;
 
@@ -65,8 +115,11 @@ macro_rules! shim_function {
 /// application may itself be a bootloader that needs to validate
 /// another EFI application before running it, and the shim lock
 /// protocol exists to support that.
+///
+/// The protocol is only installed when the current image was started by
+/// shim, so whether `BootServices::locate_protocol` succeeds is a cheap way
+/// to know if we are running under shim.
 #[repr(C)]
-#[unsafe_guid("605dab50-e046-4300-abb6-3dd810dd8b23")]
 #[derive(Protocol)]
 pub struct ShimLock {
     verify: shim_function! { fn(buffer: *const u8, size: u32) -> Status },
@@ -82,6 +135,10 @@ pub struct ShimLock {
     context: shim_function! { fn(buffer: *const u8, size: u32, context: *mut Context) -> Status },
 }
 
+unsafe impl Identify for ShimLock {
+    const GUID: Guid = guid!("605dab50-e046-4300-abb6-3dd810dd8b23");
+}
+
 impl ShimLock {
     /// Verify that an EFI application is signed by the certificate
     /// embedded in shim.
@@ -96,6 +153,22 @@ impl ShimLock {
             .map_err(|_| Error::from(Status::BAD_BUFFER_SIZE))?;
         (self.verify)(buffer.as_ptr(), size).into()
     }
+    /// Parse the headers of an EFI application.
+    ///
+    /// The buffer's size must fit in a `u32`; if that condition is not
+    /// met then a `BAD_BUFFER_SIZE` error will be returned and the shim
+    /// lock protocol will not be called.
+    pub fn context(&self, buffer: &[u8]) -> Result<Context> {
+        let size: u32 = buffer
+            .len()
+            .try_into()
+            .map_err(|_| Error::from(Status::BAD_BUFFER_SIZE))?;
+
+        let mut context = MaybeUninit::<Context>::uninit();
+        (self.context)(buffer.as_ptr(), size, context.as_mut_ptr())
+            .into_with_val(|| unsafe { context.assume_init() })
+    }
+
     /// Compute the Authenticode Hash of the provided EFI application.
     ///
     /// The buffer's size must fit in a `u32`; if that condition is not
@@ -109,14 +182,15 @@ impl ShimLock {
             .map_err(|_| Error::from(Status::BAD_BUFFER_SIZE))?;
 
         let mut context = MaybeUninit::<Context>::uninit();
-        let comp1 = (self.context)(ptr, size, context.as_mut_ptr())?;
+        let comp1 = (self.context)(ptr, size, context.as_mut_ptr()).into_result()?;
         let comp2 = (self.hash)(
             ptr,
             size,
             context.as_mut_ptr(),
             &mut hashes.sha256,
             &mut hashes.sha1,
-        )?;
+        )
+        .into_result()?;
         Ok(comp1.with_status(comp2.status()))
     }
 }
@@ -0,0 +1,82 @@
+//! Trusted Computing Group protocols.
+//!
+//! These protocols provide access to the TPM, which is used to measure the
+//! boot process. The `v1` module supports TPM 1.2 devices, while the `v2`
+//! module supports TPM 2.0 devices.
+
+pub mod v1;
+pub mod v2;
+
+/// Index of a Platform Configuration Register.
+///
+/// The PC Client platform specification defines 24 PCRs, so the index is
+/// guaranteed to be at most 23.
+///
+/// ```
+/// use uefi::proto::tcg::PcrIndex;
+///
+/// assert_eq!(PcrIndex::new(8).map(PcrIndex::value), Some(8));
+/// assert!(PcrIndex::new(24).is_none());
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[repr(transparent)]
+pub struct PcrIndex(u32);
+
+impl PcrIndex {
+    /// Highest valid PCR index.
+    pub const MAX: u32 = 23;
+
+    /// Creates a PCR index, or returns `None` if it is out of range.
+    pub const fn new(index: u32) -> Option<Self> {
+        if index <= Self::MAX {
+            Some(PcrIndex(index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the numerical value of the index.
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+}
+
+newtype_enum! {
+/// Type of an event recorded in the TPM event log.
+///
+/// New event types are regularly added by the TCG specifications, so only
+/// the most common ones are listed here.
+pub enum EventType: u32 => {
+    /// Event recorded before the firmware started measuring code.
+    PREBOOT_CERT                = 0x0000_0000,
+    /// Measurement of POST code, such as the firmware itself.
+    POST_CODE                   = 0x0000_0001,
+    /// Event which is logged but not extended into a PCR.
+    NO_ACTION                   = 0x0000_0003,
+    /// Separates pre-boot and OS-present measurements.
+    SEPARATOR                   = 0x0000_0004,
+    /// Action performed by the firmware, described by a string.
+    ACTION                      = 0x0000_0005,
+    /// Event tagged by the platform manufacturer.
+    EVENT_TAG                   = 0x0000_0006,
+    /// Measurement of the firmware's build information.
+    S_CRTM_CONTENTS             = 0x0000_0007,
+    /// Version of the firmware.
+    S_CRTM_VERSION              = 0x0000_0008,
+    /// Measurement of CPU microcode.
+    CPU_MICROCODE               = 0x0000_0009,
+    /// Measurement of the initial program loader, such as a kernel.
+    IPL                         = 0x0000_000d,
+    /// Measurement of a UEFI variable.
+    EFI_VARIABLE_DRIVER_CONFIG  = 0x8000_0001,
+    /// Measurement of a boot variable.
+    EFI_VARIABLE_BOOT           = 0x8000_0002,
+    /// Measurement of a UEFI application.
+    EFI_BOOT_SERVICES_APPLICATION = 0x8000_0003,
+    /// Measurement of a boot services driver.
+    EFI_BOOT_SERVICES_DRIVER    = 0x8000_0004,
+    /// Measurement of a runtime services driver.
+    EFI_RUNTIME_SERVICES_DRIVER = 0x8000_0005,
+    /// Action performed by the firmware, such as calling an application.
+    EFI_ACTION                  = 0x8000_0007,
+}}
@@ -0,0 +1,346 @@
+//! TCG protocol, for TPM 1.2 devices.
+//!
+//! TPM 1.2 only supports SHA-1, so all the digests of this protocol are
+//! 20 byte arrays.
+
+use super::{EventType, PcrIndex};
+use crate::data_types::PhysicalAddress;
+use crate::proto::Protocol;
+use crate::{Completion, Error, Guid, Identify, Result, Status};
+use core::convert::TryInto;
+use core::fmt;
+
+/// A SHA-1 digest.
+pub type Sha1Digest = [u8; 20];
+
+/// `TPM_ALG_SHA`, the only algorithm supported by TPM 1.2.
+const ALGORITHM_SHA1: u32 = 0x0000_0004;
+
+/// Size of `TCG_EFI_BOOT_SERVICE_CAPABILITY`.
+const CAPABILITY_SIZE: usize = 12;
+
+/// Version of a structure or protocol (`TCG_VERSION`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Version {
+    /// Major version.
+    pub major: u8,
+    /// Minor version.
+    pub minor: u8,
+    /// Major revision.
+    pub rev_major: u8,
+    /// Minor revision.
+    pub rev_minor: u8,
+}
+
+impl Version {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            major: bytes[0],
+            minor: bytes[1],
+            rev_major: bytes[2],
+            rev_minor: bytes[3],
+        }
+    }
+}
+
+/// State of the protocol and of the TPM, as returned by `Tcg::status_check`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StatusCheck {
+    /// Version of the capability structure returned by the firmware.
+    pub structure_version: Version,
+    /// Version of the TCG EFI protocol specification.
+    pub protocol_version: Version,
+    /// Bitmap of the supported hashing algorithms.
+    pub hash_algorithm_bitmap: u8,
+    /// Whether a TPM is present.
+    pub tpm_present: bool,
+    /// Whether the TPM is deactivated. A deactivated TPM cannot be used
+    /// until it is activated again in the firmware setup.
+    pub tpm_deactivated: bool,
+    /// Feature flags of the TCG implementation.
+    pub feature_flags: u32,
+    /// Address of the first entry of the event log, or `None` if there is no
+    /// log.
+    pub event_log_location: Option<PhysicalAddress>,
+    /// Address of the last entry of the event log, or `None` if it is empty.
+    pub event_log_last_entry: Option<PhysicalAddress>,
+}
+
+impl StatusCheck {
+    /// Returns whether the TPM is present and activated, and can thus be
+    /// used to measure events.
+    pub fn is_usable(&self) -> bool {
+        self.tpm_present && !self.tpm_deactivated
+    }
+}
+
+/// An event to be logged with the TCG protocol (`TCG_PCR_EVENT`).
+///
+/// ```
+/// use uefi::proto::tcg::{v1::PcrEvent, EventType, PcrIndex};
+///
+/// let pcr = PcrIndex::new(8).unwrap();
+/// let mut buffer = [0; 64];
+/// let event = PcrEvent::build(&mut buffer, pcr, EventType::IPL, [0xaa; 20], b"kernel")
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(event.as_bytes().len(), PcrEvent::HEADER_SIZE + 6);
+/// assert_eq!(event.pcr_index(), pcr);
+/// assert_eq!(event.digest(), [0xaa; 20]);
+/// assert_eq!(event.event_data(), b"kernel");
+/// ```
+#[repr(transparent)]
+pub struct PcrEvent([u8]);
+
+impl PcrEvent {
+    /// Size of the header preceding the event data.
+    pub const HEADER_SIZE: usize = 32;
+
+    /// Writes an event to `buffer`, which is returned on success.
+    ///
+    /// The digest is only used by `Tcg::log_event`, as it is overwritten by
+    /// `Tcg::hash_log_extend_event`. If the buffer is too small,
+    /// `BUFFER_TOO_SMALL` is returned along with the required size. If the
+    /// event data is too large to be described by the header,
+    /// `INVALID_PARAMETER` is returned.
+    pub fn build<'buf>(
+        buffer: &'buf mut [u8],
+        pcr_index: PcrIndex,
+        event_type: EventType,
+        digest: Sha1Digest,
+        event_data: &[u8],
+    ) -> Result<&'buf mut PcrEvent, Option<usize>> {
+        let data_size: u32 = match event_data.len().try_into() {
+            Ok(size) => size,
+            Err(_) => return Err(Error::new(Status::INVALID_PARAMETER, None)),
+        };
+        let size = Self::HEADER_SIZE + event_data.len();
+        if buffer.len() < size {
+            return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(size)));
+        }
+
+        let event = &mut buffer[..size];
+        event[0..4].copy_from_slice(&pcr_index.value().to_le_bytes());
+        event[4..8].copy_from_slice(&event_type.0.to_le_bytes());
+        event[8..28].copy_from_slice(&digest);
+        event[28..32].copy_from_slice(&data_size.to_le_bytes());
+        event[Self::HEADER_SIZE..].copy_from_slice(event_data);
+
+        Ok(unsafe { &mut *(event as *mut [u8] as *mut PcrEvent) }.into())
+    }
+
+    /// Returns the raw bytes of the event.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the PCR which the event is measured into.
+    pub fn pcr_index(&self) -> PcrIndex {
+        let index = u32::from_le_bytes(self.0[0..4].try_into().unwrap());
+        PcrIndex::new(index).unwrap()
+    }
+
+    /// Returns the type of the event.
+    pub fn event_type(&self) -> EventType {
+        EventType(u32::from_le_bytes(self.0[4..8].try_into().unwrap()))
+    }
+
+    /// Returns the digest of the event.
+    pub fn digest(&self) -> Sha1Digest {
+        self.0[8..28].try_into().unwrap()
+    }
+
+    /// Returns the data which is recorded in the event log.
+    pub fn event_data(&self) -> &[u8] {
+        &self.0[Self::HEADER_SIZE..]
+    }
+}
+
+impl fmt::Debug for PcrEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PcrEvent")
+            .field("pcr_index", &self.pcr_index())
+            .field("event_type", &self.event_type())
+            .field("digest", &self.digest())
+            .field("event_data_len", &self.event_data().len())
+            .finish()
+    }
+}
+
+/// TCG protocol, giving access to a TPM 1.2 device.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Tcg {
+    status_check: unsafe extern "efiapi" fn(
+        this: &mut Tcg,
+        capability: *mut u8,
+        feature_flags: &mut u32,
+        event_log_location: &mut PhysicalAddress,
+        event_log_last_entry: &mut PhysicalAddress,
+    ) -> Status,
+    hash_all: unsafe extern "efiapi" fn(
+        this: &mut Tcg,
+        data: *const u8,
+        data_len: u64,
+        algorithm: u32,
+        digest_len: &mut u64,
+        digest: &mut *mut u8,
+    ) -> Status,
+    log_event: unsafe extern "efiapi" fn(
+        this: &mut Tcg,
+        event: *const u8,
+        event_number: &mut u32,
+        flags: u32,
+    ) -> Status,
+    pass_through_to_tpm: unsafe extern "efiapi" fn(
+        this: &mut Tcg,
+        input_size: u32,
+        input: *const u8,
+        output_size: u32,
+        output: *mut u8,
+    ) -> Status,
+    hash_log_extend_event: unsafe extern "efiapi" fn(
+        this: &mut Tcg,
+        data: PhysicalAddress,
+        data_len: u64,
+        algorithm: u32,
+        event: *mut u8,
+        event_number: &mut u32,
+        event_log_last_entry: &mut PhysicalAddress,
+    ) -> Status,
+}
+
+unsafe impl Identify for Tcg {
+    const GUID: Guid = guid!("f541796d-a62e-4954-a775-9584f61b9cdd");
+}
+
+/// Flag of `Tcg::log_event` which prevents extending the PCR.
+const LOG_EVENT_NO_EXTEND: u32 = 0x0000_0001;
+
+impl Tcg {
+    /// Returns the state of the protocol and of the TPM.
+    ///
+    /// The firmware reports an absent or deactivated TPM with a
+    /// `DEVICE_ERROR`. As this is a common situation, the state is still
+    /// returned in that case, and `StatusCheck::is_usable` should be used to
+    /// know whether the TPM can be used.
+    pub fn status_check(&mut self) -> Result<StatusCheck> {
+        let mut capability = [0; CAPABILITY_SIZE];
+        capability[0] = CAPABILITY_SIZE as u8;
+        let mut feature_flags = 0;
+        let mut location = PhysicalAddress::default();
+        let mut last_entry = PhysicalAddress::default();
+
+        let status = unsafe {
+            (self.status_check)(
+                self,
+                capability.as_mut_ptr(),
+                &mut feature_flags,
+                &mut location,
+                &mut last_entry,
+            )
+        };
+        if status.is_error() && status != Status::DEVICE_ERROR {
+            return Err(status.into());
+        }
+
+        let non_null = |addr: PhysicalAddress| Some(addr).filter(|addr| addr.as_u64() != 0);
+        let state = StatusCheck {
+            structure_version: Version::from_bytes(&capability[1..5]),
+            protocol_version: Version::from_bytes(&capability[5..9]),
+            hash_algorithm_bitmap: capability[9],
+            tpm_present: capability[10] != 0,
+            tpm_deactivated: capability[11] != 0,
+            feature_flags,
+            event_log_location: non_null(location),
+            event_log_last_entry: non_null(last_entry),
+        };
+        if status.is_success() || status.is_warning() {
+            Ok(Completion::new(status, state))
+        } else if state.is_usable() {
+            Err(status.into())
+        } else {
+            Ok(state.into())
+        }
+    }
+
+    /// Computes the SHA-1 digest of `data` using the TPM.
+    ///
+    /// The digest is written to a buffer passed to the firmware, so that it
+    /// never needs to allocate memory.
+    pub fn hash_all(&mut self, data: &[u8]) -> Result<Sha1Digest> {
+        let mut digest = [0; 20];
+        let mut digest_len = digest.len() as u64;
+        let mut digest_ptr = digest.as_mut_ptr();
+        let status = unsafe {
+            (self.hash_all)(
+                self,
+                data.as_ptr(),
+                data.len() as u64,
+                ALGORITHM_SHA1,
+                &mut digest_len,
+                &mut digest_ptr,
+            )
+        };
+        status.into_with_val(|| digest)
+    }
+
+    /// Adds an event to the event log, and returns its number.
+    ///
+    /// Unless `extend` is false, the PCR of the event is also extended with
+    /// the digest of the event.
+    pub fn log_event(&mut self, event: &PcrEvent, extend: bool) -> Result<u32> {
+        let flags = if extend { 0 } else { LOG_EVENT_NO_EXTEND };
+        let mut event_number = 0;
+        unsafe { (self.log_event)(self, event.as_bytes().as_ptr(), &mut event_number, flags) }
+            .into_with_val(|| event_number)
+    }
+
+    /// Sends a raw TPM 1.2 command to the TPM, and writes its response to
+    /// `output`.
+    pub fn pass_through_to_tpm(&mut self, input: &[u8], output: &mut [u8]) -> Result {
+        let input_size = input
+            .len()
+            .try_into()
+            .map_err(|_| Error::from(Status::BAD_BUFFER_SIZE))?;
+        let output_size = output.len().try_into().unwrap_or(u32::MAX);
+        unsafe {
+            (self.pass_through_to_tpm)(
+                self,
+                input_size,
+                input.as_ptr(),
+                output_size,
+                output.as_mut_ptr(),
+            )
+        }
+        .into()
+    }
+
+    /// Hashes `data`, extends the PCR of the `event` with the digest, and
+    /// records the event in the event log.
+    ///
+    /// The digest of the event is updated with the digest of the data. The
+    /// number of the event and the address of the last entry of the log are
+    /// returned.
+    pub fn hash_log_extend_event(
+        &mut self,
+        data: &[u8],
+        event: &mut PcrEvent,
+    ) -> Result<(u32, PhysicalAddress)> {
+        let data_addr = PhysicalAddress::new(data.as_ptr() as u64);
+        let mut event_number = 0;
+        let mut last_entry = PhysicalAddress::default();
+        unsafe {
+            (self.hash_log_extend_event)(
+                self,
+                data_addr,
+                data.len() as u64,
+                ALGORITHM_SHA1,
+                event.0.as_mut_ptr(),
+                &mut event_number,
+                &mut last_entry,
+            )
+        }
+        .into_with_val(|| (event_number, last_entry))
+    }
+}
@@ -0,0 +1,400 @@
+//! TCG2 protocol, for TPM 2.0 devices.
+
+use super::{EventType, PcrIndex};
+use crate::data_types::PhysicalAddress;
+use crate::proto::Protocol;
+use crate::{Error, Guid, Identify, Result, Status};
+use bitflags::bitflags;
+use core::convert::TryInto;
+use core::fmt;
+
+bitflags! {
+    /// Hashing algorithms, which can be supported by the TPM or used as
+    /// PCR banks.
+    #[repr(transparent)]
+    pub struct HashAlgorithm: u32 {
+        /// SHA-1
+        const SHA1 = 0x0000_0001;
+        /// SHA-256
+        const SHA256 = 0x0000_0002;
+        /// SHA-384
+        const SHA384 = 0x0000_0004;
+        /// SHA-512
+        const SHA512 = 0x0000_0008;
+        /// SM3-256
+        const SM3_256 = 0x0000_0010;
+    }
+}
+
+bitflags! {
+    /// Formats of the event log.
+    #[repr(transparent)]
+    pub struct EventLogFormat: u32 {
+        /// TCG 1.2 format, with SHA-1 digests only.
+        const TCG_1_2 = 0x0000_0001;
+        /// TCG 2.0 crypto-agile format.
+        const TCG_2 = 0x0000_0002;
+    }
+}
+
+bitflags! {
+    /// Flags for `Tcg::hash_log_extend_event`.
+    #[repr(transparent)]
+    pub struct HashLogExtendEventFlags: u64 {
+        /// Only extend the PCR, without logging the event.
+        const EXTEND_ONLY = 0x0000_0000_0000_0001;
+        /// The data to hash is a PE/COFF image. Only the parts of the image
+        /// covered by the Authenticode hash are measured.
+        const PE_COFF_IMAGE = 0x0000_0000_0000_0010;
+    }
+}
+
+/// Version of a structure or protocol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Version {
+    /// Major version.
+    pub major: u8,
+    /// Minor version.
+    pub minor: u8,
+}
+
+/// Size of `EFI_TCG2_BOOT_SERVICE_CAPABILITY` in version 1.1 of the spec.
+const CAPABILITY_SIZE: usize = 30;
+/// Size of `EFI_TCG2_BOOT_SERVICE_CAPABILITY` in version 1.0 of the spec,
+/// which lacks the PCR bank fields.
+const CAPABILITY_SIZE_1_0: usize = 22;
+
+/// Capabilities of the TCG2 protocol and of the TPM.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BootServiceCapability {
+    /// Version of the capability structure returned by the firmware.
+    pub structure_version: Version,
+    /// Version of the TCG2 protocol.
+    pub protocol_version: Version,
+    /// Hashing algorithms supported by the TPM.
+    pub hash_algorithms: HashAlgorithm,
+    /// Event log formats supported by the firmware.
+    pub supported_event_logs: EventLogFormat,
+    /// Whether a TPM is present. If not, the other TPM fields are meaningless.
+    pub tpm_present: bool,
+    /// Maximum size of a command which can be sent to the TPM.
+    pub max_command_size: u16,
+    /// Maximum size of a response of the TPM.
+    pub max_response_size: u16,
+    /// Vendor ID of the TPM manufacturer.
+    pub manufacturer_id: u32,
+    /// Number of PCR banks supported by the TPM.
+    ///
+    /// This is `None` for firmware implementing version 1.0 of the
+    /// capability structure.
+    pub number_of_pcr_banks: Option<u32>,
+    /// Currently active PCR banks.
+    ///
+    /// This is `None` for firmware implementing version 1.0 of the
+    /// capability structure.
+    pub active_pcr_banks: Option<HashAlgorithm>,
+}
+
+impl BootServiceCapability {
+    /// Parses the packed `EFI_TCG2_BOOT_SERVICE_CAPABILITY` structure.
+    ///
+    /// Older firmware returns a shorter structure, so the fields are only
+    /// read if they fit in the size reported by the firmware.
+    fn parse(bytes: &[u8; CAPABILITY_SIZE]) -> Option<Self> {
+        let size = usize::from(bytes[0]).min(CAPABILITY_SIZE);
+        if size < CAPABILITY_SIZE_1_0 {
+            return None;
+        }
+        let bytes = &bytes[..size];
+        let u16_at =
+            |offset: usize| u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap());
+        let u32_at = |offset: usize| -> Option<u32> {
+            let field = bytes.get(offset..offset + 4)?;
+            Some(u32::from_le_bytes(field.try_into().unwrap()))
+        };
+
+        Some(Self {
+            structure_version: Version {
+                major: bytes[1],
+                minor: bytes[2],
+            },
+            protocol_version: Version {
+                major: bytes[3],
+                minor: bytes[4],
+            },
+            hash_algorithms: HashAlgorithm::from_bits_truncate(u32_at(5)?),
+            supported_event_logs: EventLogFormat::from_bits_truncate(u32_at(9)?),
+            tpm_present: bytes[13] != 0,
+            max_command_size: u16_at(14),
+            max_response_size: u16_at(16),
+            manufacturer_id: u32_at(18)?,
+            number_of_pcr_banks: u32_at(22),
+            active_pcr_banks: u32_at(26).map(HashAlgorithm::from_bits_truncate),
+        })
+    }
+}
+
+/// An event to be measured with `Tcg::hash_log_extend_event`
+/// (`EFI_TCG2_EVENT`).
+///
+/// The event is made of a header describing the PCR to extend and the type
+/// of the event, followed by event data which is recorded in the log.
+///
+/// ```
+/// use uefi::proto::tcg::{v2::PcrEvent, EventType, PcrIndex};
+///
+/// let pcr = PcrIndex::new(8).unwrap();
+/// let mut buffer = [0; 64];
+/// let event = PcrEvent::build(&mut buffer, pcr, EventType::IPL, b"kernel")
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(event.as_bytes().len(), PcrEvent::HEADER_SIZE + 6);
+/// assert_eq!(event.pcr_index(), pcr);
+/// assert_eq!(event.event_type(), EventType::IPL);
+/// assert_eq!(event.event_data(), b"kernel");
+///
+/// let error = PcrEvent::build(&mut buffer[..10], pcr, EventType::IPL, b"kernel").unwrap_err();
+/// assert_eq!(error.data(), &Some(PcrEvent::HEADER_SIZE + 6));
+/// ```
+#[repr(transparent)]
+pub struct PcrEvent([u8]);
+
+/// Size of `EFI_TCG2_EVENT_HEADER`.
+const EVENT_HEADER_SIZE: u32 = 14;
+/// Version of `EFI_TCG2_EVENT_HEADER`.
+const EVENT_HEADER_VERSION: u16 = 1;
+
+impl PcrEvent {
+    /// Size of the headers preceding the event data.
+    pub const HEADER_SIZE: usize = 4 + EVENT_HEADER_SIZE as usize;
+
+    /// Writes an event to `buffer`, which is returned on success.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size. If the event data is too large to be described by
+    /// the header, `INVALID_PARAMETER` is returned.
+    pub fn build<'buf>(
+        buffer: &'buf mut [u8],
+        pcr_index: PcrIndex,
+        event_type: EventType,
+        event_data: &[u8],
+    ) -> Result<&'buf PcrEvent, Option<usize>> {
+        let size = Self::HEADER_SIZE + event_data.len();
+        let size_u32: u32 = match size.try_into() {
+            Ok(size) => size,
+            Err(_) => return Err(Error::new(Status::INVALID_PARAMETER, None)),
+        };
+        if buffer.len() < size {
+            return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(size)));
+        }
+
+        let event = &mut buffer[..size];
+        event[0..4].copy_from_slice(&size_u32.to_le_bytes());
+        event[4..8].copy_from_slice(&EVENT_HEADER_SIZE.to_le_bytes());
+        event[8..10].copy_from_slice(&EVENT_HEADER_VERSION.to_le_bytes());
+        event[10..14].copy_from_slice(&pcr_index.value().to_le_bytes());
+        event[14..18].copy_from_slice(&event_type.0.to_le_bytes());
+        event[Self::HEADER_SIZE..].copy_from_slice(event_data);
+
+        Ok(unsafe { &*(&buffer[..size] as *const [u8] as *const PcrEvent) }.into())
+    }
+
+    /// Returns the raw bytes of the event.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the PCR which the event is measured into.
+    pub fn pcr_index(&self) -> PcrIndex {
+        let index = u32::from_le_bytes(self.0[10..14].try_into().unwrap());
+        PcrIndex::new(index).unwrap()
+    }
+
+    /// Returns the type of the event.
+    pub fn event_type(&self) -> EventType {
+        EventType(u32::from_le_bytes(self.0[14..18].try_into().unwrap()))
+    }
+
+    /// Returns the data which is recorded in the event log.
+    pub fn event_data(&self) -> &[u8] {
+        &self.0[Self::HEADER_SIZE..]
+    }
+}
+
+impl fmt::Debug for PcrEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PcrEvent")
+            .field("pcr_index", &self.pcr_index())
+            .field("event_type", &self.event_type())
+            .field("event_data_len", &self.event_data().len())
+            .finish()
+    }
+}
+
+/// Location of the event log in memory, as returned by `Tcg::get_event_log`.
+///
+/// The event log can be handed over to the OS, which uses it to check the
+/// measurements stored in the PCRs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EventLog {
+    /// Format of the event log.
+    pub format: EventLogFormat,
+    /// Address of the first entry of the log, or `None` if there is no log.
+    pub location: Option<PhysicalAddress>,
+    /// Address of the last entry of the log, or `None` if it is empty.
+    pub last_entry: Option<PhysicalAddress>,
+    /// Whether some events could not be added because the log was full.
+    pub truncated: bool,
+}
+
+/// TCG2 protocol, giving access to a TPM 2.0 device.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Tcg {
+    get_capability: unsafe extern "efiapi" fn(this: &mut Tcg, capability: *mut u8) -> Status,
+    get_event_log: extern "efiapi" fn(
+        this: &mut Tcg,
+        format: EventLogFormat,
+        location: &mut PhysicalAddress,
+        last_entry: &mut PhysicalAddress,
+        truncated: &mut bool,
+    ) -> Status,
+    hash_log_extend_event: unsafe extern "efiapi" fn(
+        this: &mut Tcg,
+        flags: HashLogExtendEventFlags,
+        data_to_hash: PhysicalAddress,
+        data_to_hash_len: u64,
+        event: *const u8,
+    ) -> Status,
+    submit_command: unsafe extern "efiapi" fn(
+        this: &mut Tcg,
+        input_size: u32,
+        input: *const u8,
+        output_size: u32,
+        output: *mut u8,
+    ) -> Status,
+    get_active_pcr_banks:
+        extern "efiapi" fn(this: &mut Tcg, active_pcr_banks: &mut HashAlgorithm) -> Status,
+    set_active_pcr_banks:
+        extern "efiapi" fn(this: &mut Tcg, active_pcr_banks: HashAlgorithm) -> Status,
+    get_result_of_set_active_pcr_banks: extern "efiapi" fn(
+        this: &mut Tcg,
+        operation_present: &mut u32,
+        response: &mut u32,
+    ) -> Status,
+}
+
+unsafe impl Identify for Tcg {
+    const GUID: Guid = guid!("607f766c-7455-42be-930b-e4d76db2720f");
+}
+
+impl Tcg {
+    /// Retrieves the capabilities of the protocol and of the TPM.
+    ///
+    /// If the firmware returns a capability structure which is too short to
+    /// be valid, a `DEVICE_ERROR` is returned.
+    pub fn get_capability(&mut self) -> Result<BootServiceCapability> {
+        let mut bytes = [0; CAPABILITY_SIZE];
+        bytes[0] = CAPABILITY_SIZE as u8;
+        let (status, ()) = unsafe { (self.get_capability)(self, bytes.as_mut_ptr()) }
+            .into_result()?
+            .split();
+        BootServiceCapability::parse(&bytes)
+            .ok_or_else(|| Status::DEVICE_ERROR.into())
+            .map(|capability| crate::Completion::new(status, capability))
+    }
+
+    /// Returns the location of the event log in the given format.
+    ///
+    /// If the format is not supported by the firmware, `INVALID_PARAMETER`
+    /// is returned.
+    pub fn get_event_log(&mut self, format: EventLogFormat) -> Result<EventLog> {
+        let mut location = PhysicalAddress::default();
+        let mut last_entry = PhysicalAddress::default();
+        let mut truncated = false;
+        let non_null = |addr: PhysicalAddress| Some(addr).filter(|addr| addr.as_u64() != 0);
+
+        (self.get_event_log)(self, format, &mut location, &mut last_entry, &mut truncated)
+            .into_with_val(|| EventLog {
+                format,
+                location: non_null(location),
+                last_entry: non_null(last_entry),
+                truncated,
+            })
+    }
+
+    /// Hashes `data`, extends the PCR of the `event` with the digest, and
+    /// records the event in the event log.
+    ///
+    /// This is typically used to measure a kernel image before starting it,
+    /// in which case the `PE_COFF_IMAGE` flag should be set.
+    pub fn hash_log_extend_event(
+        &mut self,
+        flags: HashLogExtendEventFlags,
+        data: &[u8],
+        event: &PcrEvent,
+    ) -> Result {
+        let data_addr = PhysicalAddress::new(data.as_ptr() as u64);
+        unsafe {
+            (self.hash_log_extend_event)(
+                self,
+                flags,
+                data_addr,
+                data.len() as u64,
+                event.as_bytes().as_ptr(),
+            )
+        }
+        .into()
+    }
+
+    /// Sends a raw TPM 2.0 command to the TPM, and writes its response to
+    /// `output`.
+    ///
+    /// The command must not be larger than the maximum command size of the
+    /// TPM, otherwise a `BAD_BUFFER_SIZE` error is returned. The output buffer
+    /// should be at least as large as the maximum response size. The actual
+    /// size of the response can be read from its header.
+    pub fn submit_command(&mut self, input: &[u8], output: &mut [u8]) -> Result {
+        let capability = self.get_capability()?.log();
+        if !capability.tpm_present {
+            return Err(Status::NOT_FOUND.into());
+        }
+        if input.len() > usize::from(capability.max_command_size) {
+            return Err(Status::BAD_BUFFER_SIZE.into());
+        }
+        let output_size = output.len().try_into().unwrap_or(u32::MAX);
+
+        unsafe {
+            (self.submit_command)(
+                self,
+                input.len() as u32,
+                input.as_ptr(),
+                output_size,
+                output.as_mut_ptr(),
+            )
+        }
+        .into()
+    }
+
+    /// Returns the currently active PCR banks.
+    pub fn get_active_pcr_banks(&mut self) -> Result<HashAlgorithm> {
+        let mut banks = HashAlgorithm::empty();
+        (self.get_active_pcr_banks)(self, &mut banks).into_with_val(|| banks)
+    }
+
+    /// Requests a change of the active PCR banks, which is applied by the
+    /// firmware on the next reboot.
+    pub fn set_active_pcr_banks(&mut self, banks: HashAlgorithm) -> Result {
+        (self.set_active_pcr_banks)(self, banks).into()
+    }
+
+    /// Returns the result of the last `set_active_pcr_banks` request.
+    ///
+    /// This is `None` if there was no request, and otherwise the response
+    /// code of the TPM for the request.
+    pub fn get_result_of_set_active_pcr_banks(&mut self) -> Result<Option<u32>> {
+        let (mut present, mut response) = (0, 0);
+        (self.get_result_of_set_active_pcr_banks)(self, &mut present, &mut response)
+            .into_with_val(|| if present != 0 { Some(response) } else { None })
+    }
+}
@@ -0,0 +1,138 @@
+//! Timestamp protocol.
+//!
+//! This protocol gives access to a free-running counter, which can be used
+//! to measure the time taken by some code with a high resolution.
+//!
+//! As the protocol may not be installed, `TscTimer` provides the same
+//! interface on x86 using the time stamp counter of the processor.
+
+use crate::proto::Protocol;
+use crate::{Guid, Identify, Result, Status};
+use core::time::Duration;
+
+/// The Timestamp protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct Timestamp {
+    get_timestamp: extern "efiapi" fn() -> u64,
+    get_properties: extern "efiapi" fn(properties: &mut TimestampProperties) -> Status,
+}
+
+unsafe impl Identify for Timestamp {
+    const GUID: Guid = guid!("afbfde41-2e6e-4262-ba65-62b9236e5495");
+}
+
+impl Timestamp {
+    /// Returns the current value of the counter.
+    ///
+    /// The counter counts up from 0 to `TimestampProperties::end_value`,
+    /// and then wraps around to 0.
+    pub fn get_timestamp(&self) -> u64 {
+        (self.get_timestamp)()
+    }
+
+    /// Returns the frequency and the end value of the counter.
+    pub fn get_properties(&self) -> Result<TimestampProperties> {
+        let mut properties = TimestampProperties {
+            frequency: 0,
+            end_value: 0,
+        };
+        (self.get_properties)(&mut properties).into_with_val(|| properties)
+    }
+}
+
+/// Properties of the counter returned by `get_timestamp`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct TimestampProperties {
+    /// Frequency of the counter, in Hz.
+    pub frequency: u64,
+    /// Value of the counter after which it wraps around to 0.
+    pub end_value: u64,
+}
+
+impl TimestampProperties {
+    /// Returns the time elapsed between two values of the counter.
+    ///
+    /// If `end` is lower than `start`, the counter is assumed to have
+    /// wrapped around once in between.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use uefi::proto::timestamp::TimestampProperties;
+    ///
+    /// let properties = TimestampProperties {
+    ///     frequency: 1_000,
+    ///     end_value: 0xffff,
+    /// };
+    /// assert_eq!(properties.elapsed(100, 350), Duration::from_millis(250));
+    /// assert_eq!(properties.elapsed(0xfff0, 10), Duration::from_millis(26));
+    /// ```
+    pub fn elapsed(&self, start: u64, end: u64) -> Duration {
+        let ticks = if end >= start {
+            end - start
+        } else {
+            self.end_value - start + end + 1
+        };
+        if self.frequency == 0 {
+            return Duration::from_secs(0);
+        }
+        let secs = ticks / self.frequency;
+        let nanos = (ticks % self.frequency) as u128 * 1_000_000_000 / self.frequency as u128;
+        Duration::new(secs, nanos as u32)
+    }
+}
+
+/// Timer using the time stamp counter of x86 processors, for firmware which
+/// doesn't install the Timestamp protocol
+///
+/// The frequency of the counter is measured against `BootServices::stall`
+/// when the timer is created, and is only as precise as the firmware's stall.
+/// The counter is assumed to run at a constant rate, which is the case on
+/// the processors of the last decade.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[derive(Clone, Copy, Debug)]
+pub struct TscTimer {
+    properties: TimestampProperties,
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl TscTimer {
+    /// Duration of the calibration, in microseconds.
+    const CALIBRATION_TIME: u64 = 10_000;
+
+    /// Creates a timer, measuring the frequency of the counter.
+    ///
+    /// This stalls the processor for 10 milliseconds.
+    pub fn calibrate(bt: &crate::table::boot::BootServices) -> Self {
+        let start = Self::read();
+        bt.stall(Self::CALIBRATION_TIME as usize);
+        let end = Self::read();
+        let frequency = end.wrapping_sub(start) * (1_000_000 / Self::CALIBRATION_TIME);
+        TscTimer {
+            properties: TimestampProperties {
+                frequency,
+                end_value: u64::MAX,
+            },
+        }
+    }
+
+    /// Returns the current value of the counter.
+    pub fn get_timestamp(&self) -> u64 {
+        Self::read()
+    }
+
+    /// Returns the measured frequency and the end value of the counter.
+    pub fn get_properties(&self) -> TimestampProperties {
+        self.properties
+    }
+
+    fn read() -> u64 {
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::_rdtsc;
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64::_rdtsc;
+
+        unsafe { _rdtsc() }
+    }
+}
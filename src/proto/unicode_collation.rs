@@ -0,0 +1,294 @@
+//! Unicode Collation protocol.
+//!
+//! This protocol compares strings without regard to case, in the rules of a
+//! language, and converts file names to and from the OEM character set of
+//! FAT volumes. It is used by the FAT driver to look up file names.
+//!
+//! As the protocol may not be installed, `metai_match` implements its
+//! wildcard matching in Rust, and `matches` uses the protocol if available
+//! and falls back to `metai_match` otherwise.
+
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+use crate::{CStr16, CStr8, Char16, Char8, Error, Guid, Identify, Result, Status};
+use core::cmp::Ordering;
+
+/// The Unicode Collation protocol
+#[repr(C)]
+#[derive(Protocol)]
+pub struct UnicodeCollation {
+    stri_coll: unsafe extern "efiapi" fn(
+        this: &UnicodeCollation,
+        s1: *const Char16,
+        s2: *const Char16,
+    ) -> isize,
+    metai_match: unsafe extern "efiapi" fn(
+        this: &UnicodeCollation,
+        string: *const Char16,
+        pattern: *const Char16,
+    ) -> bool,
+    str_lwr: unsafe extern "efiapi" fn(this: &UnicodeCollation, string: *mut Char16),
+    str_upr: unsafe extern "efiapi" fn(this: &UnicodeCollation, string: *mut Char16),
+    fat_to_str: unsafe extern "efiapi" fn(
+        this: &UnicodeCollation,
+        fat_size: usize,
+        fat: *const Char8,
+        string: *mut Char16,
+    ),
+    str_to_fat: unsafe extern "efiapi" fn(
+        this: &UnicodeCollation,
+        string: *const Char16,
+        fat_size: usize,
+        fat: *mut Char8,
+    ) -> bool,
+    supported_languages: *const Char8,
+}
+
+unsafe impl Identify for UnicodeCollation {
+    const GUID: Guid = guid!("a4c751fc-23ae-4c3e-92e9-4964cf63f349");
+}
+
+impl UnicodeCollation {
+    /// Compares two strings without regard to case.
+    pub fn stri_coll(&self, s1: &CStr16, s2: &CStr16) -> Ordering {
+        let order = unsafe { (self.stri_coll)(self, s1.as_ptr(), s2.as_ptr()) };
+        order.cmp(&0)
+    }
+
+    /// Whether `string` matches `pattern`, without regard to case.
+    ///
+    /// The wildcards are the ones described by `metai_match`.
+    pub fn metai_match(&self, string: &CStr16, pattern: &CStr16) -> bool {
+        unsafe { (self.metai_match)(self, string.as_ptr(), pattern.as_ptr()) }
+    }
+
+    /// Converts `string` to lower case, into `buffer`.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required number of characters, including the null terminator.
+    pub fn str_lwr<'buf>(
+        &self,
+        string: &CStr16,
+        buffer: &'buf mut [u16],
+    ) -> Result<&'buf CStr16, Option<usize>> {
+        let buffer = copy_string(string, buffer)?.log();
+        unsafe { (self.str_lwr)(self, buffer.as_mut_ptr().cast()) };
+        Ok(unsafe { CStr16::from_u16_with_nul_unchecked(buffer) }.into())
+    }
+
+    /// Converts `string` to upper case, into `buffer`.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required number of characters, including the null terminator.
+    pub fn str_upr<'buf>(
+        &self,
+        string: &CStr16,
+        buffer: &'buf mut [u16],
+    ) -> Result<&'buf CStr16, Option<usize>> {
+        let buffer = copy_string(string, buffer)?.log();
+        unsafe { (self.str_upr)(self, buffer.as_mut_ptr().cast()) };
+        Ok(unsafe { CStr16::from_u16_with_nul_unchecked(buffer) }.into())
+    }
+
+    /// Converts a file name in the OEM character set of FAT volumes into
+    /// `buffer`, stopping at the first null byte, if any.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required number of characters, which is one more than the length
+    /// of `fat`.
+    pub fn fat_to_str<'buf>(
+        &self,
+        fat: &[u8],
+        buffer: &'buf mut [u16],
+    ) -> Result<&'buf CStr16, Option<usize>> {
+        if buffer.len() <= fat.len() {
+            return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(fat.len() + 1)));
+        }
+        unsafe {
+            (self.fat_to_str)(
+                self,
+                fat.len(),
+                fat.as_ptr().cast(),
+                buffer.as_mut_ptr().cast(),
+            )
+        };
+        // The firmware always terminates the string.
+        let len = buffer.iter().position(|&c| c == 0).unwrap();
+        Ok(unsafe { CStr16::from_u16_with_nul_unchecked(&buffer[..=len]) }.into())
+    }
+
+    /// Converts `string` into the OEM character set of FAT volumes, in
+    /// `fat`.
+    ///
+    /// Periods and spaces are removed, and the characters which are not
+    /// valid in FAT file names are replaced by `_`. The conversion stops
+    /// when `fat` is full, and the bytes following the converted characters
+    /// are left unchanged. Returns `true` if some characters were replaced.
+    pub fn str_to_fat(&self, string: &CStr16, fat: &mut [u8]) -> bool {
+        unsafe { (self.str_to_fat)(self, string.as_ptr(), fat.len(), fat.as_mut_ptr().cast()) }
+    }
+
+    /// Returns the languages supported by this implementation, as a list of
+    /// RFC 4646 language tags separated by semicolons.
+    pub fn supported_languages(&self) -> &CStr8 {
+        unsafe { CStr8::from_ptr(self.supported_languages) }
+    }
+}
+
+/// Copies `string` and its null terminator at the beginning of `buffer`.
+fn copy_string<'buf>(
+    string: &CStr16,
+    buffer: &'buf mut [u16],
+) -> Result<&'buf mut [u16], Option<usize>> {
+    let codes = string.to_u16_slice_with_nul();
+    if buffer.len() < codes.len() {
+        return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(codes.len())));
+    }
+    let buffer = &mut buffer[..codes.len()];
+    buffer.copy_from_slice(codes);
+    Ok(buffer.into())
+}
+
+/// Whether `string` matches `pattern`, without regard to case, using the
+/// Unicode Collation protocol if it is installed, and `metai_match`
+/// otherwise.
+pub fn matches(bt: &BootServices, string: &CStr16, pattern: &CStr16) -> bool {
+    match bt.locate_protocol::<UnicodeCollation>() {
+        Ok(collation) => {
+            let collation = unsafe { &*collation.log().get() };
+            collation.metai_match(string, pattern)
+        }
+        Err(_) => metai_match(string, pattern),
+    }
+}
+
+/// Whether `string` matches `pattern`, without regard to case, with the
+/// wildcards of `UnicodeCollation::metai_match`.
+///
+/// This is an implementation of the protocol function which doesn't need
+/// the firmware, and matches the behavior of the English implementation of
+/// the protocol found in EDK2. Letters of the ASCII and Latin-1 ranges are
+/// compared without regard to case. In the pattern:
+/// - `*` matches any sequence of characters, including an empty one.
+/// - `?` matches any character.
+/// - `[chars]` matches any of the characters in the brackets, where `a-z`
+///   matches a range of characters. Sets which are not closed never match.
+///
+/// There is no escape character: wildcards are matched literally by
+/// putting them in a set, such as `[*]` or `[[]`.
+///
+/// ```
+/// use uefi::proto::unicode_collation::metai_match;
+/// # use uefi::CStr16;
+/// # fn matches(string: &str, pattern: &str) -> bool {
+/// #     let string: Vec<u16> = string.encode_utf16().chain(Some(0)).collect();
+/// #     let pattern: Vec<u16> = pattern.encode_utf16().chain(Some(0)).collect();
+/// #     metai_match(
+/// #         CStr16::from_u16_with_nul(&string).unwrap(),
+/// #         CStr16::from_u16_with_nul(&pattern).unwrap(),
+/// #     )
+/// # }
+///
+/// assert!(matches("BOOTX64.EFI", "*.efi"));
+/// assert!(matches("bootx64.efi", "BOOT???.EFI"));
+/// assert!(!matches("boot.efi", "BOOT???.EFI"));
+/// assert!(matches("", "*"));
+/// assert!(matches("startup.nsh", "s*t*p.*"));
+///
+/// // Sets and ranges, which are also case-insensitive.
+/// assert!(matches("kernel-5.efi", "kernel-[0-9].efi"));
+/// assert!(!matches("kernel-a.efi", "kernel-[0-9].efi"));
+/// assert!(matches("vmlinuz", "vmlinu[xyz]"));
+/// assert!(matches("VMLINUZ", "vmlinu[a-z]"));
+/// assert!(matches("ÉCRAN", "écran"));
+/// // Malformed sets never match.
+/// assert!(!matches("a", "[a"));
+/// assert!(!matches("a", "[b-]"));
+/// assert!(!matches("a", "[]"));
+///
+/// // Wildcards are escaped with sets.
+/// assert!(matches("a*b", "a[*]b"));
+/// assert!(!matches("axb", "a[*]b"));
+/// assert!(matches("what?", "what[?]"));
+/// assert!(matches("[x]", "[[]x]"));
+/// ```
+pub fn metai_match(string: &CStr16, pattern: &CStr16) -> bool {
+    match_codes(string.to_u16_slice(), pattern.to_u16_slice())
+}
+
+fn match_codes(mut string: &[u16], mut pattern: &[u16]) -> bool {
+    while let Some((&p, rest)) = pattern.split_first() {
+        pattern = rest;
+        match p {
+            STAR => loop {
+                if match_codes(string, pattern) {
+                    return true;
+                }
+                match string.split_first() {
+                    Some((_, rest)) => string = rest,
+                    None => return false,
+                }
+            },
+            QUESTION_MARK => match string.split_first() {
+                Some((_, rest)) => string = rest,
+                None => return false,
+            },
+            LEFT_BRACKET => match string.split_first() {
+                Some((&c, rest)) => {
+                    pattern = match match_set(c, pattern) {
+                        Some(rest) => rest,
+                        None => return false,
+                    };
+                    string = rest;
+                }
+                None => return false,
+            },
+            p => match string.split_first() {
+                Some((&c, rest)) if to_upper(c) == to_upper(p) => string = rest,
+                _ => return false,
+            },
+        }
+    }
+    string.is_empty()
+}
+
+/// Matches `c` against the set at the beginning of `pattern`, after the
+/// opening bracket, and returns the rest of the pattern if it matches.
+fn match_set(c: u16, pattern: &[u16]) -> Option<&[u16]> {
+    let end = pattern.iter().position(|&p| p == RIGHT_BRACKET)?;
+    let rest = &pattern[end + 1..];
+    let c = to_upper(c);
+    let mut previous = 0;
+    let mut set = pattern[..end].iter();
+    while let Some(&p) = set.next() {
+        let p = if p == DASH {
+            let last = *set.next()?;
+            if (to_upper(previous)..=to_upper(last)).contains(&c) {
+                return Some(rest);
+            }
+            last
+        } else {
+            p
+        };
+        if to_upper(p) == c {
+            return Some(rest);
+        }
+        previous = p;
+    }
+    None
+}
+
+const STAR: u16 = b'*' as u16;
+const QUESTION_MARK: u16 = b'?' as u16;
+const LEFT_BRACKET: u16 = b'[' as u16;
+const RIGHT_BRACKET: u16 = b']' as u16;
+const DASH: u16 = b'-' as u16;
+
+/// Converts the lower case letters of the ASCII and Latin-1 ranges to upper
+/// case.
+fn to_upper(c: u16) -> u16 {
+    match c {
+        0x61..=0x7a | 0xe0..=0xf6 | 0xf8..=0xfe => c - 0x20,
+        c => c,
+    }
+}
@@ -0,0 +1,881 @@
+//! USB I/O protocol.
+
+use crate::proto::Protocol;
+use crate::table::boot::BootServices;
+#[cfg(feature = "exts")]
+use crate::{alloc_api::boxed::Box, Completion};
+use crate::{CStr16, Char16, Error, Guid, Identify, Result, Status};
+use bitflags::bitflags;
+use core::ffi::c_void;
+use core::{fmt, ptr, slice};
+
+bitflags! {
+    /// Errors reported by the USB host controller for a transfer
+    /// (`EFI_USB_ERR_*`).
+    #[repr(transparent)]
+    pub struct UsbStatus: u32 {
+        /// The transfer was not executed.
+        const NOT_EXECUTED = 0x001;
+        /// The endpoint stalled, for example because the request is not
+        /// supported by the device.
+        const STALL = 0x002;
+        /// A buffer error occurred.
+        const BUFFER = 0x004;
+        /// The device sent more data than expected.
+        const BABBLE = 0x008;
+        /// The device was not ready.
+        const NAK = 0x010;
+        /// A CRC error occurred.
+        const CRC = 0x020;
+        /// The transfer timed out.
+        const TIMEOUT = 0x040;
+        /// A bit stuffing error occurred.
+        const BIT_STUFF = 0x080;
+        /// An error occurred in the host controller.
+        const SYSTEM = 0x100;
+    }
+}
+
+/// Direction of a control request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Data is sent to the device, if any.
+    HostToDevice,
+    /// Data is received from the device.
+    DeviceToHost,
+}
+
+newtype_enum! {
+/// Kind of a control request.
+pub enum RequestKind: u8 => {
+    /// Requests defined by the USB specification.
+    STANDARD = 0,
+    /// Requests defined by the class of the device or interface.
+    CLASS    = 1,
+    /// Requests defined by the vendor of the device.
+    VENDOR   = 2,
+}}
+
+newtype_enum! {
+/// Recipient of a control request.
+pub enum Recipient: u8 => {
+    /// The device itself.
+    DEVICE    = 0,
+    /// An interface, whose number is the index of the request.
+    INTERFACE = 1,
+    /// An endpoint, whose address is the index of the request.
+    ENDPOINT  = 2,
+    /// Another recipient.
+    OTHER     = 3,
+}}
+
+/// Characteristics of a control request (`bmRequestType`), which are its
+/// direction, its kind and its recipient.
+///
+/// ```
+/// use uefi::proto::usb::io::{Direction, Recipient, RequestKind, RequestType};
+///
+/// let request_type =
+///     RequestType::new(Direction::DeviceToHost, RequestKind::CLASS, Recipient::INTERFACE);
+/// assert_eq!(request_type.0, 0xa1);
+/// assert_eq!(request_type.direction(), Direction::DeviceToHost);
+/// assert_eq!(request_type.kind(), RequestKind::CLASS);
+/// assert_eq!(request_type.recipient(), Recipient::INTERFACE);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct RequestType(pub u8);
+
+impl RequestType {
+    /// Creates a request type from its parts.
+    pub fn new(direction: Direction, kind: RequestKind, recipient: Recipient) -> Self {
+        let direction = match direction {
+            Direction::HostToDevice => 0,
+            Direction::DeviceToHost => 0x80,
+        };
+        Self(direction | (kind.0 & 0x03) << 5 | recipient.0 & 0x1f)
+    }
+
+    /// Direction of the request.
+    pub fn direction(self) -> Direction {
+        if self.0 & 0x80 == 0 {
+            Direction::HostToDevice
+        } else {
+            Direction::DeviceToHost
+        }
+    }
+
+    /// Kind of the request.
+    pub fn kind(self) -> RequestKind {
+        RequestKind((self.0 >> 5) & 0x03)
+    }
+
+    /// Recipient of the request.
+    pub fn recipient(self) -> Recipient {
+        Recipient(self.0 & 0x1f)
+    }
+}
+
+newtype_enum! {
+/// Code of a standard control request (`bRequest`). Class and vendor
+/// requests define their own codes.
+pub enum Request: u8 => {
+    /// Returns the status of the recipient.
+    GET_STATUS        = 0,
+    /// Disables a feature of the recipient.
+    CLEAR_FEATURE     = 1,
+    /// Enables a feature of the recipient.
+    SET_FEATURE       = 3,
+    /// Sets the address of the device.
+    SET_ADDRESS       = 5,
+    /// Returns a descriptor.
+    GET_DESCRIPTOR    = 6,
+    /// Updates a descriptor.
+    SET_DESCRIPTOR    = 7,
+    /// Returns the active configuration.
+    GET_CONFIGURATION = 8,
+    /// Selects the active configuration.
+    SET_CONFIGURATION = 9,
+    /// Returns the alternate setting of an interface.
+    GET_INTERFACE     = 10,
+    /// Selects the alternate setting of an interface.
+    SET_INTERFACE     = 11,
+    /// Returns the frame number of an isochronous endpoint.
+    SYNCH_FRAME       = 12,
+}}
+
+newtype_enum! {
+/// Type of a descriptor (`bDescriptorType`).
+pub enum DescriptorType: u8 => {
+    /// Device descriptor.
+    DEVICE        = 1,
+    /// Configuration descriptor.
+    CONFIGURATION = 2,
+    /// String descriptor, or table of languages for index 0.
+    STRING        = 3,
+    /// Interface descriptor.
+    INTERFACE     = 4,
+    /// Endpoint descriptor.
+    ENDPOINT      = 5,
+    /// HID class descriptor.
+    HID           = 0x21,
+    /// HID report descriptor.
+    REPORT        = 0x22,
+}}
+
+/// The setup packet of a control request, without the length of its data
+/// stage, which is given by the data buffer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DeviceRequest {
+    /// Direction, kind and recipient of the request.
+    pub request_type: RequestType,
+    /// Code of the request.
+    pub request: Request,
+    /// Parameter of the request.
+    pub value: u16,
+    /// Index or offset, such as the number of an interface.
+    pub index: u16,
+}
+
+impl DeviceRequest {
+    /// Creates a standard `GET_DESCRIPTOR` request for the descriptor of type
+    /// `ty` with index `index`, in language `lang_id` for string
+    /// descriptors.
+    pub fn get_descriptor(ty: DescriptorType, index: u8, lang_id: u16) -> Self {
+        Self {
+            request_type: RequestType::new(
+                Direction::DeviceToHost,
+                RequestKind::STANDARD,
+                Recipient::DEVICE,
+            ),
+            request: Request::GET_DESCRIPTOR,
+            value: u16::from(ty.0) << 8 | u16::from(index),
+            index: lang_id,
+        }
+    }
+}
+
+/// The `EFI_USB_DEVICE_REQUEST` structure.
+#[repr(C)]
+struct RawDeviceRequest {
+    request_type: RequestType,
+    request: Request,
+    value: u16,
+    index: u16,
+    length: u16,
+}
+
+/// Data of a transfer, whose direction must match the one of the request or
+/// of the endpoint.
+#[derive(Debug)]
+pub enum DataStage<'a> {
+    /// Data received from the device.
+    In(&'a mut [u8]),
+    /// Data sent to the device.
+    Out(&'a [u8]),
+    /// No data, which is only allowed for control transfers.
+    None,
+}
+
+impl DataStage<'_> {
+    /// Returns the direction, address and length of the data.
+    fn as_raw(&mut self) -> (DataDirection, *mut c_void, usize) {
+        match self {
+            DataStage::In(data) => (DataDirection::IN, data.as_mut_ptr().cast(), data.len()),
+            DataStage::Out(data) => (DataDirection::OUT, data.as_ptr() as *mut c_void, data.len()),
+            DataStage::None => (DataDirection::NONE, ptr::null_mut(), 0),
+        }
+    }
+}
+
+/// Direction of the data stage of a transfer (`EFI_USB_DATA_DIRECTION`).
+#[derive(Clone, Copy, Eq, PartialEq)]
+#[repr(transparent)]
+struct DataDirection(u32);
+
+impl DataDirection {
+    const IN: Self = Self(0);
+    const OUT: Self = Self(1);
+    const NONE: Self = Self(2);
+}
+
+/// Descriptor of a USB device (`EFI_USB_DEVICE_DESCRIPTOR`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct DeviceDescriptor {
+    /// Size of the descriptor.
+    pub length: u8,
+    /// Type of the descriptor.
+    pub descriptor_type: u8,
+    /// Version of the USB specification, in BCD.
+    pub bcd_usb: u16,
+    /// Class code of the device, or zero if it is given by the interfaces.
+    pub device_class: u8,
+    /// Subclass code of the device.
+    pub device_subclass: u8,
+    /// Protocol code of the device.
+    pub device_protocol: u8,
+    /// Maximum packet size of the control endpoint.
+    pub max_packet_size0: u8,
+    /// Identifier of the vendor.
+    pub id_vendor: u16,
+    /// Identifier of the product.
+    pub id_product: u16,
+    /// Release number of the device, in BCD.
+    pub bcd_device: u16,
+    /// Index of the string descriptor of the manufacturer, or zero.
+    pub str_manufacturer: u8,
+    /// Index of the string descriptor of the product, or zero.
+    pub str_product: u8,
+    /// Index of the string descriptor of the serial number, or zero.
+    pub str_serial_number: u8,
+    /// Number of configurations.
+    pub num_configurations: u8,
+}
+
+/// Descriptor of the active configuration of a USB device
+/// (`EFI_USB_CONFIG_DESCRIPTOR`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct ConfigDescriptor {
+    /// Size of the descriptor.
+    pub length: u8,
+    /// Type of the descriptor.
+    pub descriptor_type: u8,
+    /// Size of the configuration descriptor, and of the interface, endpoint
+    /// and class descriptors which follow it.
+    pub total_length: u16,
+    /// Number of interfaces.
+    pub num_interfaces: u8,
+    /// Value which selects this configuration.
+    pub configuration_value: u8,
+    /// Index of the string descriptor of the configuration, or zero.
+    pub configuration: u8,
+    /// Whether the device is self-powered and supports remote wakeup.
+    pub attributes: u8,
+    /// Maximum power consumption, in units of 2 mA.
+    pub max_power: u8,
+}
+
+/// Descriptor of a USB interface (`EFI_USB_INTERFACE_DESCRIPTOR`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct InterfaceDescriptor {
+    /// Size of the descriptor.
+    pub length: u8,
+    /// Type of the descriptor.
+    pub descriptor_type: u8,
+    /// Number of the interface.
+    pub interface_number: u8,
+    /// Alternate setting of the interface.
+    pub alternate_setting: u8,
+    /// Number of endpoints, excluding the control endpoint.
+    pub num_endpoints: u8,
+    /// Class code of the interface.
+    pub interface_class: u8,
+    /// Subclass code of the interface.
+    pub interface_subclass: u8,
+    /// Protocol code of the interface.
+    pub interface_protocol: u8,
+    /// Index of the string descriptor of the interface, or zero.
+    pub interface: u8,
+}
+
+/// Descriptor of a USB endpoint (`EFI_USB_ENDPOINT_DESCRIPTOR`).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct EndpointDescriptor {
+    /// Size of the descriptor.
+    pub length: u8,
+    /// Type of the descriptor.
+    pub descriptor_type: u8,
+    /// Number of the endpoint, with the direction in the high bit.
+    pub endpoint_address: u8,
+    /// Transfer type of the endpoint, in the low two bits.
+    pub attributes: u8,
+    /// Maximum packet size of the endpoint.
+    pub max_packet_size: u16,
+    /// Polling interval of the endpoint, in frames or microframes.
+    pub interval: u8,
+}
+
+newtype_enum! {
+/// Transfer type of an endpoint.
+pub enum TransferType: u8 => {
+    /// Control transfers, for requests.
+    CONTROL     = 0,
+    /// Isochronous transfers, for streams with a guaranteed bandwidth.
+    ISOCHRONOUS = 1,
+    /// Bulk transfers, for large and reliable transfers.
+    BULK        = 2,
+    /// Interrupt transfers, for small and periodic transfers.
+    INTERRUPT   = 3,
+}}
+
+impl EndpointDescriptor {
+    /// Direction of the transfers of the endpoint.
+    pub fn direction(&self) -> Direction {
+        endpoint_direction(self.endpoint_address)
+    }
+
+    /// Transfer type of the endpoint.
+    pub fn transfer_type(&self) -> TransferType {
+        TransferType(self.attributes & 0x03)
+    }
+}
+
+/// Returns the direction of the transfers of an endpoint, given by the high
+/// bit of its address.
+fn endpoint_direction(address: u8) -> Direction {
+    if address & 0x80 == 0 {
+        Direction::HostToDevice
+    } else {
+        Direction::DeviceToHost
+    }
+}
+
+/// Function called by the firmware when an asynchronous transfer completes
+/// (`EFI_ASYNC_USB_TRANSFER_CALLBACK`).
+///
+/// It receives the data, its length, the context given when the transfer was
+/// started, and the `UsbStatus` of the transfer.
+pub type AsyncCallback = extern "efiapi" fn(
+    data: *mut c_void,
+    data_length: usize,
+    context: *mut c_void,
+    status: UsbStatus,
+) -> Status;
+
+/// An asynchronous interrupt transfer started by
+/// `UsbIo::async_interrupt_transfer`, which polls the endpoint until it is
+/// cancelled or dropped.
+///
+/// While it exists, the protocol cannot be used for other transfers. If it
+/// is leaked, the polling never stops, and the callback stays allocated.
+#[cfg(feature = "exts")]
+pub struct AsyncInterruptTransfer<'a, F: FnMut(&[u8], UsbStatus) + 'static> {
+    usb: &'a mut UsbIo,
+    endpoint: u8,
+    callback: *mut F,
+}
+
+#[cfg(feature = "exts")]
+impl<F: FnMut(&[u8], UsbStatus) + 'static> AsyncInterruptTransfer<'_, F> {
+    /// Address of the polled endpoint.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// Stops polling the endpoint, returning the status of the operation,
+    /// which dropping the transfer ignores.
+    pub fn cancel(mut self) -> Result {
+        let result = unsafe { self.stop() };
+        core::mem::forget(self);
+        result
+    }
+
+    /// Stops polling the endpoint, and frees the callback.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called once.
+    unsafe fn stop(&mut self) -> Result {
+        let status = (self.usb.async_interrupt_transfer)(
+            self.usb,
+            self.endpoint,
+            false,
+            0,
+            0,
+            None,
+            ptr::null_mut(),
+        );
+        // The firmware no longer calls the callback once the transfer is
+        // cancelled.
+        if status.is_success() {
+            drop(Box::from_raw(self.callback));
+        }
+        status.into()
+    }
+}
+
+#[cfg(feature = "exts")]
+impl<F: FnMut(&[u8], UsbStatus) + 'static> fmt::Debug for AsyncInterruptTransfer<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncInterruptTransfer")
+            .field("endpoint", &self.endpoint)
+            .finish()
+    }
+}
+
+#[cfg(feature = "exts")]
+impl<F: FnMut(&[u8], UsbStatus) + 'static> Drop for AsyncInterruptTransfer<'_, F> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = unsafe { self.stop() };
+    }
+}
+
+/// Calls the closure of an `AsyncInterruptTransfer` with the received data.
+#[cfg(feature = "exts")]
+extern "efiapi" fn async_interrupt_trampoline<F: FnMut(&[u8], UsbStatus)>(
+    data: *mut c_void,
+    data_length: usize,
+    context: *mut c_void,
+    status: UsbStatus,
+) -> Status {
+    let callback = unsafe { &mut *context.cast::<F>() };
+    let data = if data.is_null() {
+        &[][..]
+    } else {
+        unsafe { slice::from_raw_parts(data.cast::<u8>(), data_length) }
+    };
+    callback(data, status);
+    Status::SUCCESS
+}
+
+/// A string descriptor, which is freed when dropped.
+pub struct UsbString<'a> {
+    data: *mut Char16,
+    bt: &'a BootServices,
+}
+
+impl UsbString<'_> {
+    /// The contents of the string.
+    pub fn as_cstr16(&self) -> &CStr16 {
+        unsafe { CStr16::from_ptr(self.data) }
+    }
+}
+
+impl fmt::Debug for UsbString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("UsbString")
+            .field(&self.as_cstr16().to_u16_slice())
+            .finish()
+    }
+}
+
+impl fmt::Display for UsbString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_cstr16(), f)
+    }
+}
+
+impl Drop for UsbString<'_> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = self.bt.free_pool(self.data.cast());
+    }
+}
+
+/// The USB I/O protocol
+///
+/// This protocol is installed on the handle of every interface of a USB
+/// device. Failed transfers return an error holding the `UsbStatus` reported
+/// by the host controller, such as `STALL`.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct UsbIo {
+    control_transfer: unsafe extern "efiapi" fn(
+        this: &mut UsbIo,
+        request: &RawDeviceRequest,
+        direction: DataDirection,
+        timeout: u32,
+        data: *mut c_void,
+        data_length: usize,
+        status: &mut UsbStatus,
+    ) -> Status,
+    bulk_transfer: unsafe extern "efiapi" fn(
+        this: &mut UsbIo,
+        endpoint: u8,
+        data: *mut c_void,
+        data_length: &mut usize,
+        timeout: usize,
+        status: &mut UsbStatus,
+    ) -> Status,
+    async_interrupt_transfer: unsafe extern "efiapi" fn(
+        this: &mut UsbIo,
+        endpoint: u8,
+        is_new_transfer: bool,
+        polling_interval: usize,
+        data_length: usize,
+        callback: Option<AsyncCallback>,
+        context: *mut c_void,
+    ) -> Status,
+    sync_interrupt_transfer: unsafe extern "efiapi" fn(
+        this: &mut UsbIo,
+        endpoint: u8,
+        data: *mut c_void,
+        data_length: &mut usize,
+        timeout: usize,
+        status: &mut UsbStatus,
+    ) -> Status,
+    isochronous_transfer: unsafe extern "efiapi" fn(
+        this: &mut UsbIo,
+        endpoint: u8,
+        data: *mut c_void,
+        data_length: usize,
+        status: &mut UsbStatus,
+    ) -> Status,
+    async_isochronous_transfer: unsafe extern "efiapi" fn(
+        this: &mut UsbIo,
+        endpoint: u8,
+        data: *mut c_void,
+        data_length: usize,
+        callback: AsyncCallback,
+        context: *mut c_void,
+    ) -> Status,
+    get_device_descriptor:
+        extern "efiapi" fn(this: &UsbIo, descriptor: &mut DeviceDescriptor) -> Status,
+    get_config_descriptor:
+        extern "efiapi" fn(this: &UsbIo, descriptor: &mut ConfigDescriptor) -> Status,
+    get_interface_descriptor:
+        extern "efiapi" fn(this: &UsbIo, descriptor: &mut InterfaceDescriptor) -> Status,
+    get_endpoint_descriptor:
+        extern "efiapi" fn(this: &UsbIo, index: u8, descriptor: &mut EndpointDescriptor) -> Status,
+    get_string_descriptor: extern "efiapi" fn(
+        this: &UsbIo,
+        lang_id: u16,
+        string_id: u8,
+        string: &mut *mut Char16,
+    ) -> Status,
+    get_supported_languages: extern "efiapi" fn(
+        this: &UsbIo,
+        lang_id_table: &mut *const u16,
+        table_size: &mut u16,
+    ) -> Status,
+    port_reset: extern "efiapi" fn(this: &mut UsbIo) -> Status,
+}
+
+unsafe impl Identify for UsbIo {
+    const GUID: Guid = guid!("2b2f68d6-0cd2-44cf-8e8b-bba20b1b5b75");
+}
+
+impl UsbIo {
+    /// Performs a control transfer on the default control endpoint.
+    ///
+    /// `timeout_ms` is in milliseconds, zero meaning to wait forever.
+    /// `INVALID_PARAMETER` is returned if the direction of the data stage
+    /// does not match the one of the request, or the data is longer than
+    /// 65535 bytes.
+    pub fn control_transfer(
+        &mut self,
+        request: &DeviceRequest,
+        mut data: DataStage,
+        timeout_ms: u32,
+    ) -> Result<(), UsbStatus> {
+        let (direction, data, len) = data.as_raw();
+        let expected = match request.request_type.direction() {
+            Direction::DeviceToHost => DataDirection::IN,
+            Direction::HostToDevice if len == 0 => DataDirection::NONE,
+            Direction::HostToDevice => DataDirection::OUT,
+        };
+        if direction != expected || len > usize::from(u16::MAX) {
+            return Err(Error::new(Status::INVALID_PARAMETER, UsbStatus::empty()));
+        }
+
+        let raw = RawDeviceRequest {
+            request_type: request.request_type,
+            request: request.request,
+            value: request.value,
+            index: request.index,
+            length: len as u16,
+        };
+        let mut usb_status = UsbStatus::empty();
+        unsafe {
+            (self.control_transfer)(
+                self,
+                &raw,
+                direction,
+                timeout_ms,
+                data,
+                len,
+                &mut usb_status,
+            )
+        }
+        .into_with(|| (), |_| usb_status)
+    }
+
+    /// Performs a bulk transfer on endpoint `endpoint`, and returns the
+    /// number of bytes transferred.
+    ///
+    /// The endpoint address includes the direction bit, which must match the
+    /// direction of the data. `timeout_ms` is in milliseconds, zero meaning
+    /// to wait forever. `INVALID_PARAMETER` is returned if the interface has
+    /// no such bulk endpoint.
+    pub fn bulk_transfer(
+        &mut self,
+        endpoint: u8,
+        mut data: DataStage,
+        timeout_ms: usize,
+    ) -> Result<usize, UsbStatus> {
+        self.check_endpoint(endpoint, TransferType::BULK, &data)?
+            .log();
+        let (_, data, mut len) = data.as_raw();
+        let mut usb_status = UsbStatus::empty();
+        unsafe { (self.bulk_transfer)(self, endpoint, data, &mut len, timeout_ms, &mut usb_status) }
+            .into_with(|| len, |_| usb_status)
+    }
+
+    /// Performs an interrupt transfer on endpoint `endpoint`, and returns
+    /// the number of bytes transferred.
+    ///
+    /// The rules are the same as for `bulk_transfer`, for an interrupt
+    /// endpoint.
+    pub fn sync_interrupt_transfer(
+        &mut self,
+        endpoint: u8,
+        mut data: DataStage,
+        timeout_ms: usize,
+    ) -> Result<usize, UsbStatus> {
+        self.check_endpoint(endpoint, TransferType::INTERRUPT, &data)?
+            .log();
+        let (_, data, mut len) = data.as_raw();
+        let mut usb_status = UsbStatus::empty();
+        unsafe {
+            (self.sync_interrupt_transfer)(
+                self,
+                endpoint,
+                data,
+                &mut len,
+                timeout_ms,
+                &mut usb_status,
+            )
+        }
+        .into_with(|| len, |_| usb_status)
+    }
+
+    /// Polls the interrupt IN endpoint `endpoint` every `polling_interval_ms`
+    /// milliseconds, for up to `data_length` bytes, and calls `callback` with
+    /// the received data and the status of each completed transfer.
+    ///
+    /// The callback runs in the timer interrupt, at `Tpl::CALLBACK`. The
+    /// polling continues until the returned transfer is cancelled or
+    /// dropped. `INVALID_PARAMETER` is returned if the interface has no such
+    /// endpoint, or the interval is not between 1 and 255 milliseconds.
+    #[cfg(feature = "exts")]
+    pub fn async_interrupt_transfer<F: FnMut(&[u8], UsbStatus) + 'static>(
+        &mut self,
+        endpoint: u8,
+        polling_interval_ms: usize,
+        data_length: usize,
+        callback: F,
+    ) -> Result<AsyncInterruptTransfer<'_, F>> {
+        self.check_endpoint(endpoint, TransferType::INTERRUPT, &DataStage::In(&mut []))
+            .map_err(|err| Error::from(err.status()))?
+            .log();
+        if polling_interval_ms == 0 || polling_interval_ms > 255 {
+            return Err(Status::INVALID_PARAMETER.into());
+        }
+
+        let callback = Box::into_raw(Box::new(callback));
+        let status = unsafe {
+            (self.async_interrupt_transfer)(
+                self,
+                endpoint,
+                true,
+                polling_interval_ms,
+                data_length,
+                Some(async_interrupt_trampoline::<F>),
+                callback.cast(),
+            )
+        };
+        if status.is_error() {
+            drop(unsafe { Box::from_raw(callback) });
+            return Err(status.into());
+        }
+        Ok(Completion::new(
+            status,
+            AsyncInterruptTransfer {
+                usb: self,
+                endpoint,
+                callback,
+            },
+        ))
+    }
+
+    /// Performs an isochronous transfer on endpoint `endpoint`.
+    ///
+    /// The rules are the same as for `bulk_transfer`, for an isochronous
+    /// endpoint. Many implementations do not support isochronous transfers,
+    /// and return `UNSUPPORTED`.
+    pub fn isochronous_transfer(
+        &mut self,
+        endpoint: u8,
+        mut data: DataStage,
+    ) -> Result<(), UsbStatus> {
+        self.check_endpoint(endpoint, TransferType::ISOCHRONOUS, &data)?
+            .log();
+        let (_, data, len) = data.as_raw();
+        let mut usb_status = UsbStatus::empty();
+        unsafe { (self.isochronous_transfer)(self, endpoint, data, len, &mut usb_status) }
+            .into_with(|| (), |_| usb_status)
+    }
+
+    /// Starts an isochronous transfer on endpoint `endpoint`, and returns
+    /// immediately. `callback` is called with `context` once the transfer
+    /// completes.
+    ///
+    /// # Safety
+    ///
+    /// `data` and `context` must stay valid until the callback is called.
+    pub unsafe fn async_isochronous_transfer(
+        &mut self,
+        endpoint: u8,
+        mut data: DataStage,
+        callback: AsyncCallback,
+        context: *mut c_void,
+    ) -> Result {
+        self.check_endpoint(endpoint, TransferType::ISOCHRONOUS, &data)
+            .map_err(|err| Error::from(err.status()))?
+            .log();
+        let (_, data, len) = data.as_raw();
+        (self.async_isochronous_transfer)(self, endpoint, data, len, callback, context).into()
+    }
+
+    /// Resets the port of the device, and restores its configuration.
+    ///
+    /// Hubs cannot be reset this way.
+    pub fn port_reset(&mut self) -> Result {
+        (self.port_reset)(self).into()
+    }
+
+    /// Checks that the interface has endpoint `endpoint`, with transfer type
+    /// `ty`, and that it transfers data in the direction of `data`.
+    fn check_endpoint(
+        &self,
+        endpoint: u8,
+        ty: TransferType,
+        data: &DataStage,
+    ) -> Result<(), UsbStatus> {
+        let invalid = || Error::new(Status::INVALID_PARAMETER, UsbStatus::empty());
+        let direction = match data {
+            DataStage::In(_) => Direction::DeviceToHost,
+            DataStage::Out(_) => Direction::HostToDevice,
+            DataStage::None => return Err(invalid()),
+        };
+        if endpoint_direction(endpoint) != direction {
+            return Err(invalid());
+        }
+        let interface = self
+            .get_interface_descriptor()
+            .map_err(|err| Error::new(err.status(), UsbStatus::empty()))?
+            .log();
+        let found = (0..interface.num_endpoints)
+            .filter_map(|index| self.get_endpoint_descriptor(index).ok())
+            .map(|descriptor| descriptor.log())
+            .any(|descriptor| {
+                descriptor.endpoint_address == endpoint && descriptor.transfer_type() == ty
+            });
+        if found {
+            Ok(().into())
+        } else {
+            Err(invalid())
+        }
+    }
+
+    /// Returns the descriptor of the device.
+    pub fn get_device_descriptor(&self) -> Result<DeviceDescriptor> {
+        let mut descriptor = DeviceDescriptor::default();
+        (self.get_device_descriptor)(self, &mut descriptor).into_with_val(|| descriptor)
+    }
+
+    /// Returns the descriptor of the active configuration of the device.
+    ///
+    /// `NOT_FOUND` is returned if the device is not configured.
+    pub fn get_config_descriptor(&self) -> Result<ConfigDescriptor> {
+        let mut descriptor = ConfigDescriptor::default();
+        (self.get_config_descriptor)(self, &mut descriptor).into_with_val(|| descriptor)
+    }
+
+    /// Returns the descriptor of the interface which this protocol belongs
+    /// to.
+    pub fn get_interface_descriptor(&self) -> Result<InterfaceDescriptor> {
+        let mut descriptor = InterfaceDescriptor::default();
+        (self.get_interface_descriptor)(self, &mut descriptor).into_with_val(|| descriptor)
+    }
+
+    /// Returns the descriptor of the endpoint `index` of the interface,
+    /// which must be lower than its number of endpoints. The control
+    /// endpoint has no descriptor.
+    ///
+    /// `NOT_FOUND` is returned if the index is out of range.
+    pub fn get_endpoint_descriptor(&self, index: u8) -> Result<EndpointDescriptor> {
+        let mut descriptor = EndpointDescriptor::default();
+        (self.get_endpoint_descriptor)(self, index, &mut descriptor).into_with_val(|| descriptor)
+    }
+
+    /// Returns the string descriptor `index` in the language `lang_id`.
+    ///
+    /// `NOT_FOUND` is returned if the device does not have this string in
+    /// this language.
+    pub fn get_string_descriptor<'bt>(
+        &self,
+        bt: &'bt BootServices,
+        lang_id: u16,
+        index: u8,
+    ) -> Result<UsbString<'bt>> {
+        let mut data = ptr::null_mut();
+        (self.get_string_descriptor)(self, lang_id, index, &mut data)
+            .into_result()?
+            .log();
+        if data.is_null() {
+            return Err(Status::NOT_FOUND.into());
+        }
+        Ok(UsbString { data, bt }.into())
+    }
+
+    /// Returns the identifiers of the languages of the string descriptors of
+    /// the device.
+    pub fn get_supported_languages(&self) -> Result<&[u16]> {
+        let mut table = ptr::null();
+        let mut size = 0;
+        (self.get_supported_languages)(self, &mut table, &mut size).into_with_val(|| {
+            if table.is_null() {
+                &[][..]
+            } else {
+                // The size is in bytes.
+                unsafe { slice::from_raw_parts(table, usize::from(size) / 2) }
+            }
+        })
+    }
+}
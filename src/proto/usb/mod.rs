@@ -0,0 +1,6 @@
+//! USB protocols.
+//!
+//! The USB I/O protocol is installed on the handle of every interface of the
+//! USB devices, and performs transfers with the device.
+
+pub mod io;
@@ -0,0 +1,545 @@
+//! Variable policy protocol.
+//!
+//! This protocol is not part of the UEFI specification, but is implemented
+//! by EDK2-based firmware. It restricts which values UEFI variables can have,
+//! and allows locking them so that they cannot be modified anymore, even at
+//! runtime.
+
+use crate::proto::Protocol;
+use crate::table::runtime::VariableAttributes;
+use crate::{CStr16, Error, Guid, Identify, Result, Status};
+use core::convert::TryInto;
+use core::{fmt, mem, ptr};
+
+/// Version of the `VARIABLE_POLICY_ENTRY` structure.
+const ENTRY_REVISION: u32 = 0x0001_0000;
+
+/// Value of the maximum size meaning that variables have no maximum size.
+pub const NO_MAX_SIZE: u32 = u32::MAX;
+
+newtype_enum! {
+/// How a variable is locked by a policy.
+pub enum LockPolicyType: u8 => {
+    /// The variable is never locked.
+    NO_LOCK             = 0,
+    /// The variable is locked as soon as the policies are locked.
+    LOCK_NOW            = 1,
+    /// The variable is locked once it has been created.
+    LOCK_ON_CREATE      = 2,
+    /// The variable is locked once another variable has a given value.
+    LOCK_ON_VAR_STATE   = 3,
+}}
+
+/// How a variable is locked by a policy, for `PolicyBuilder::lock_policy`.
+#[derive(Clone, Copy, Debug)]
+pub enum LockPolicy<'a> {
+    /// The variable is never locked.
+    NoLock,
+    /// The variable is locked as soon as the policies are locked.
+    LockNow,
+    /// The variable is locked once it has been created.
+    LockOnCreate,
+    /// The variable is locked once the given variable is set to a single byte
+    /// equal to `value`.
+    LockOnVarState {
+        /// Vendor GUID of the variable to check.
+        namespace: Guid,
+        /// Name of the variable to check, which must not be empty.
+        name: &'a CStr16,
+        /// Value of the variable which triggers the lock.
+        value: u8,
+    },
+}
+
+/// Name of a variable stored in a policy entry, which may contain `#`
+/// wildcards matching any hexadecimal digit.
+///
+/// Names are not aligned inside of entries, so they cannot be borrowed as a
+/// `CStr16`.
+#[derive(Clone, Copy)]
+pub struct PolicyName<'a>(&'a [u8]);
+
+impl<'a> PolicyName<'a> {
+    /// Returns an iterator over the UCS-2 characters of the name, without
+    /// the null terminator.
+    pub fn chars(&self) -> impl Iterator<Item = u16> + 'a {
+        self.0
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+    }
+}
+
+impl PartialEq<CStr16> for PolicyName<'_> {
+    fn eq(&self, other: &CStr16) -> bool {
+        self.chars().eq(other.to_u16_slice().iter().copied())
+    }
+}
+
+impl PartialEq<str> for PolicyName<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.chars().eq(other.encode_utf16())
+    }
+}
+
+impl fmt::Debug for PolicyName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+/// Invalid UCS-2 code points are displayed as the replacement character.
+impl fmt::Display for PolicyName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in core::char::decode_utf16(self.chars()) {
+            fmt::Write::write_char(f, c.unwrap_or(core::char::REPLACEMENT_CHARACTER))?;
+        }
+        Ok(())
+    }
+}
+
+/// Lock of a policy entry depending on the state of another variable
+/// (`VARIABLE_LOCK_ON_VAR_STATE_POLICY`).
+#[derive(Clone, Copy, Debug)]
+pub struct VarStateLock<'a> {
+    /// Vendor GUID of the variable to check.
+    pub namespace: Guid,
+    /// Name of the variable to check.
+    pub name: PolicyName<'a>,
+    /// Value of the variable which triggers the lock.
+    pub value: u8,
+}
+
+/// Builder for a `PolicyEntry`.
+///
+/// By default, the policy matches all the variables of the namespace,
+/// places no restriction on them, and does not lock them.
+///
+/// ```
+/// use uefi::guid;
+/// use uefi::proto::variable_policy::{LockPolicy, LockPolicyType, PolicyBuilder};
+/// use uefi::table::runtime::VariableAttributes;
+/// use uefi::data_types::ucs2;
+/// use uefi::{CStr16, ResultExt};
+///
+/// fn ucs2<'buf>(s: &str, buf: &'buf mut [u16]) -> &'buf CStr16 {
+///     let len = ucs2::encode_str(s, buf).unwrap_success();
+///     CStr16::from_u16_with_nul(&buf[..len]).unwrap()
+/// }
+///
+/// let vendor = guid!("9baf21cf-e187-497e-ae77-5bd8b0e09703");
+/// let (mut name, mut state_name) = ([0; 16], [0; 16]);
+/// let name = ucs2("Boot####", &mut name);
+/// let state_name = ucs2("Locked", &mut state_name);
+///
+/// let mut buffer = [0; 128];
+/// let entry = PolicyBuilder::new(vendor)
+///     .name(name)
+///     .max_size(64)
+///     .attributes_cant_have(VariableAttributes::RUNTIME_ACCESS)
+///     .lock_policy(LockPolicy::LockOnVarState {
+///         namespace: vendor,
+///         name: state_name,
+///         value: 1,
+///     })
+///     .build(&mut buffer)
+///     .unwrap()
+///     .unwrap();
+///
+/// // 44 byte header, 18 + 14 bytes of lock policy and an 18 byte name.
+/// assert_eq!(entry.as_bytes().len(), 94);
+/// assert_eq!(entry.namespace(), vendor);
+/// assert!(entry.name().unwrap() == *"Boot####");
+/// assert_eq!(entry.min_size(), 0);
+/// assert_eq!(entry.max_size(), 64);
+/// assert_eq!(entry.lock_policy_type(), LockPolicyType::LOCK_ON_VAR_STATE);
+/// let lock = entry.lock_on_var_state().unwrap();
+/// assert!(lock.name == *state_name);
+/// assert_eq!(lock.value, 1);
+///
+/// let error = PolicyBuilder::new(vendor).build(&mut buffer[..40]).unwrap_err();
+/// assert_eq!(error.data(), &Some(44));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct PolicyBuilder<'a> {
+    namespace: Guid,
+    name: Option<&'a CStr16>,
+    min_size: u32,
+    max_size: u32,
+    attributes_must_have: VariableAttributes,
+    attributes_cant_have: VariableAttributes,
+    lock_policy: LockPolicy<'a>,
+}
+
+impl<'a> PolicyBuilder<'a> {
+    /// Starts building a policy for the variables of a vendor.
+    pub fn new(namespace: Guid) -> Self {
+        Self {
+            namespace,
+            name: None,
+            min_size: 0,
+            max_size: NO_MAX_SIZE,
+            attributes_must_have: VariableAttributes::empty(),
+            attributes_cant_have: VariableAttributes::empty(),
+            lock_policy: LockPolicy::NoLock,
+        }
+    }
+
+    /// Restricts the policy to the variables with this name. Each `#` in
+    /// the name matches any hexadecimal digit, as used by `Boot####`.
+    pub fn name(mut self, name: &'a CStr16) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Sets the minimum size of the variables.
+    pub fn min_size(mut self, size: u32) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    /// Sets the maximum size of the variables.
+    pub fn max_size(mut self, size: u32) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Sets the attributes which the variables must have.
+    pub fn attributes_must_have(mut self, attributes: VariableAttributes) -> Self {
+        self.attributes_must_have = attributes;
+        self
+    }
+
+    /// Sets the attributes which the variables must not have.
+    pub fn attributes_cant_have(mut self, attributes: VariableAttributes) -> Self {
+        self.attributes_cant_have = attributes;
+        self
+    }
+
+    /// Sets how the variables are locked.
+    pub fn lock_policy(mut self, lock_policy: LockPolicy<'a>) -> Self {
+        self.lock_policy = lock_policy;
+        self
+    }
+
+    /// Writes the policy entry to `buffer`, which is returned on success.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size. If the entry is too large or the name of the lock
+    /// variable is empty, `INVALID_PARAMETER` is returned.
+    pub fn build(self, buffer: &mut [u8]) -> Result<&PolicyEntry, Option<usize>> {
+        let invalid = || Error::new(Status::INVALID_PARAMETER, None);
+
+        let (lock_type, lock_size) = match self.lock_policy {
+            LockPolicy::NoLock => (LockPolicyType::NO_LOCK, 0),
+            LockPolicy::LockNow => (LockPolicyType::LOCK_NOW, 0),
+            LockPolicy::LockOnCreate => (LockPolicyType::LOCK_ON_CREATE, 0),
+            LockPolicy::LockOnVarState { name, .. } => {
+                if name.is_empty() {
+                    return Err(invalid());
+                }
+                let name_size = mem::size_of_val(name.to_u16_slice_with_nul());
+                (
+                    LockPolicyType::LOCK_ON_VAR_STATE,
+                    VAR_STATE_HEADER_SIZE + name_size,
+                )
+            }
+        };
+        let offset_to_name = PolicyEntry::HEADER_SIZE + lock_size;
+        let name_size = self
+            .name
+            .map_or(0, |name| mem::size_of_val(name.to_u16_slice_with_nul()));
+        let size = offset_to_name + name_size;
+
+        let size_u16: u16 = size.try_into().map_err(|_| invalid())?;
+        if buffer.len() < size {
+            return Err(Error::new(Status::BUFFER_TOO_SMALL, Some(size)));
+        }
+
+        let entry = &mut buffer[..size];
+        entry[0..4].copy_from_slice(&ENTRY_REVISION.to_le_bytes());
+        entry[4..6].copy_from_slice(&size_u16.to_le_bytes());
+        entry[6..8].copy_from_slice(&(offset_to_name as u16).to_le_bytes());
+        entry[8..24].copy_from_slice(&self.namespace.to_bytes());
+        entry[24..28].copy_from_slice(&self.min_size.to_le_bytes());
+        entry[28..32].copy_from_slice(&self.max_size.to_le_bytes());
+        entry[32..36].copy_from_slice(&self.attributes_must_have.bits().to_le_bytes());
+        entry[36..40].copy_from_slice(&self.attributes_cant_have.bits().to_le_bytes());
+        entry[40] = lock_type.0;
+        entry[41..44].fill(0);
+
+        if let LockPolicy::LockOnVarState {
+            namespace,
+            name,
+            value,
+        } = self.lock_policy
+        {
+            let lock = &mut entry[PolicyEntry::HEADER_SIZE..offset_to_name];
+            lock[0..16].copy_from_slice(&namespace.to_bytes());
+            lock[16] = value;
+            lock[17] = 0;
+            write_name(&mut lock[VAR_STATE_HEADER_SIZE..], name);
+        }
+        if let Some(name) = self.name {
+            write_name(&mut entry[offset_to_name..], name);
+        }
+
+        Ok(unsafe { PolicyEntry::from_bytes_unchecked(entry) }.into())
+    }
+}
+
+/// Size of the fixed part of `VARIABLE_LOCK_ON_VAR_STATE_POLICY`.
+const VAR_STATE_HEADER_SIZE: usize = 18;
+
+/// Writes a null-terminated name as little endian UCS-2.
+fn write_name(buffer: &mut [u8], name: &CStr16) {
+    for (dst, c) in buffer.chunks_exact_mut(2).zip(name.to_u16_slice_with_nul()) {
+        dst.copy_from_slice(&c.to_le_bytes());
+    }
+}
+
+/// Reads a little endian `u32` from an entry at the given offset.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads a little endian `u16` from an entry at the given offset.
+fn read_u16(bytes: &[u8], offset: usize) -> usize {
+    usize::from(u16::from_le_bytes(
+        bytes[offset..offset + 2].try_into().unwrap(),
+    ))
+}
+
+/// Reads a null-terminated name, returning `None` if there is no terminator.
+fn read_name(bytes: &[u8]) -> Option<PolicyName<'_>> {
+    let len = bytes.chunks_exact(2).position(|c| c == [0, 0])?;
+    Some(PolicyName(&bytes[..2 * len]))
+}
+
+/// A variable policy (`VARIABLE_POLICY_ENTRY`).
+///
+/// Entries are built with a `PolicyBuilder`, or read from the list of
+/// registered policies returned by `VariablePolicy::dump_variable_policy`.
+#[repr(transparent)]
+pub struct PolicyEntry([u8]);
+
+impl PolicyEntry {
+    /// Size of the fixed part of the header of an entry.
+    pub const HEADER_SIZE: usize = 44;
+
+    /// Parses the entry at the beginning of `bytes`.
+    ///
+    /// Registered policies are stored back to back, so the bytes following
+    /// the entry are returned too. `None` is returned if the entry is
+    /// malformed.
+    pub fn parse(bytes: &[u8]) -> Option<(&PolicyEntry, &[u8])> {
+        if bytes.len() < Self::HEADER_SIZE || read_u32(bytes, 0) != ENTRY_REVISION {
+            return None;
+        }
+        let size = read_u16(bytes, 4);
+        let offset_to_name = read_u16(bytes, 6);
+        if size > bytes.len()
+            || offset_to_name < Self::HEADER_SIZE
+            || offset_to_name > size
+            || (size - offset_to_name) & 1 != 0
+        {
+            return None;
+        }
+
+        let (entry, rest) = bytes.split_at(size);
+        if offset_to_name < size {
+            read_name(&entry[offset_to_name..])?;
+        }
+        if LockPolicyType(entry[40]) == LockPolicyType::LOCK_ON_VAR_STATE {
+            let lock = &entry[Self::HEADER_SIZE..offset_to_name];
+            if lock.len() < VAR_STATE_HEADER_SIZE {
+                return None;
+            }
+            read_name(&lock[VAR_STATE_HEADER_SIZE..])?;
+        }
+        Some((unsafe { Self::from_bytes_unchecked(entry) }, rest))
+    }
+
+    /// Views some bytes as a policy entry, without validating them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be a well-formed entry, with no trailing bytes.
+    pub unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &PolicyEntry {
+        &*(bytes as *const [u8] as *const PolicyEntry)
+    }
+
+    /// Returns the raw bytes of the entry.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the vendor GUID of the variables of this policy.
+    pub fn namespace(&self) -> Guid {
+        Guid::from_bytes(self.0[8..24].try_into().unwrap())
+    }
+
+    /// Returns the name of the variables of this policy, or `None` if it
+    /// applies to all the variables of the namespace.
+    pub fn name(&self) -> Option<PolicyName<'_>> {
+        let offset_to_name = read_u16(&self.0, 6);
+        if offset_to_name == self.0.len() {
+            None
+        } else {
+            read_name(&self.0[offset_to_name..])
+        }
+    }
+
+    /// Returns the minimum size of the variables.
+    pub fn min_size(&self) -> u32 {
+        read_u32(&self.0, 24)
+    }
+
+    /// Returns the maximum size of the variables, which is `NO_MAX_SIZE` if
+    /// there is none.
+    pub fn max_size(&self) -> u32 {
+        read_u32(&self.0, 28)
+    }
+
+    /// Returns the attributes which the variables must have.
+    pub fn attributes_must_have(&self) -> VariableAttributes {
+        VariableAttributes::from_bits_truncate(read_u32(&self.0, 32))
+    }
+
+    /// Returns the attributes which the variables must not have.
+    pub fn attributes_cant_have(&self) -> VariableAttributes {
+        VariableAttributes::from_bits_truncate(read_u32(&self.0, 36))
+    }
+
+    /// Returns how the variables are locked.
+    pub fn lock_policy_type(&self) -> LockPolicyType {
+        LockPolicyType(self.0[40])
+    }
+
+    /// Returns the variable which triggers the lock, if the lock policy is
+    /// `LOCK_ON_VAR_STATE`.
+    pub fn lock_on_var_state(&self) -> Option<VarStateLock<'_>> {
+        if self.lock_policy_type() != LockPolicyType::LOCK_ON_VAR_STATE {
+            return None;
+        }
+        let lock = &self.0[Self::HEADER_SIZE..read_u16(&self.0, 6)];
+        Some(VarStateLock {
+            namespace: Guid::from_bytes(lock[0..16].try_into().unwrap()),
+            value: lock[16],
+            name: read_name(&lock[VAR_STATE_HEADER_SIZE..])?,
+        })
+    }
+}
+
+impl fmt::Debug for PolicyEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PolicyEntry")
+            .field("namespace", &self.namespace())
+            .field("name", &self.name())
+            .field("min_size", &self.min_size())
+            .field("max_size", &self.max_size())
+            .field("attributes_must_have", &self.attributes_must_have())
+            .field("attributes_cant_have", &self.attributes_cant_have())
+            .field("lock_policy_type", &self.lock_policy_type())
+            .field("lock_on_var_state", &self.lock_on_var_state())
+            .finish()
+    }
+}
+
+/// Iterator over the policies returned by
+/// `VariablePolicy::dump_variable_policy`.
+#[derive(Clone, Debug)]
+pub struct PolicyEntries<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for PolicyEntries<'a> {
+    type Item = &'a PolicyEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entry, rest) = PolicyEntry::parse(self.bytes)?;
+        self.bytes = rest;
+        Some(entry)
+    }
+}
+
+/// The EDK2 variable policy protocol.
+#[repr(C)]
+#[derive(Protocol)]
+pub struct VariablePolicy {
+    revision: u64,
+    disable_variable_policy: extern "efiapi" fn() -> Status,
+    is_variable_policy_enabled: extern "efiapi" fn(state: &mut bool) -> Status,
+    register_variable_policy: unsafe extern "efiapi" fn(entry: *const u8) -> Status,
+    dump_variable_policy: unsafe extern "efiapi" fn(policy: *mut u8, size: &mut u32) -> Status,
+    lock_variable_policy: extern "efiapi" fn() -> Status,
+}
+
+unsafe impl Identify for VariablePolicy {
+    const GUID: Guid = guid!("81d1675c-86f6-48df-bd95-9a6e4f0925c3");
+}
+
+impl VariablePolicy {
+    /// Returns the revision of the protocol.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Disables the enforcement of the variable policies until the next
+    /// reboot.
+    ///
+    /// This fails with `WRITE_PROTECTED` once the policies have been locked.
+    pub fn disable_variable_policy(&mut self) -> Result {
+        (self.disable_variable_policy)().into()
+    }
+
+    /// Returns whether the variable policies are enforced.
+    pub fn is_variable_policy_enabled(&mut self) -> Result<bool> {
+        let mut state = false;
+        (self.is_variable_policy_enabled)(&mut state).into_with_val(|| state)
+    }
+
+    /// Registers a new variable policy.
+    ///
+    /// This fails with `ALREADY_STARTED` if a policy already exists for the
+    /// same variables, and with `WRITE_PROTECTED` once the policies have been
+    /// locked.
+    pub fn register_variable_policy(&mut self, entry: &PolicyEntry) -> Result {
+        unsafe { (self.register_variable_policy)(entry.as_bytes().as_ptr()) }.into()
+    }
+
+    /// Reads the registered variable policies into `buffer`.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the required size.
+    pub fn dump_variable_policy<'buf>(
+        &mut self,
+        buffer: &'buf mut [u8],
+    ) -> Result<PolicyEntries<'buf>, Option<usize>> {
+        let mut size = buffer.len().try_into().unwrap_or(u32::MAX);
+        let ptr = if buffer.is_empty() {
+            ptr::null_mut()
+        } else {
+            buffer.as_mut_ptr()
+        };
+        let status = unsafe { (self.dump_variable_policy)(ptr, &mut size) };
+        let (buffer, size): (&'buf [u8], usize) = (buffer, size as usize);
+        status.into_with(
+            move || PolicyEntries {
+                bytes: &buffer[..size],
+            },
+            |status| {
+                if status == Status::BUFFER_TOO_SMALL {
+                    Some(size)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Locks the variable policies, so that they cannot be registered or
+    /// disabled anymore until the next reboot.
+    pub fn lock_variable_policy(&mut self) -> Result {
+        (self.lock_variable_policy)().into()
+    }
+}
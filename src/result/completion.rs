@@ -1,4 +1,4 @@
-use super::Status;
+use super::{Error, Status};
 use log::warn;
 
 /// This type is used when an UEFI operation has completed, but some non-fatal
@@ -37,25 +37,46 @@ impl<T> Completion<T> {
         self.result
     }
 
-    /// Assume that no warning occured, panic if not
+    /// Access the inner value, logging the warning if there is any
+    ///
+    /// This is the same as `log`. Callers which must not proceed in the
+    /// presence of warnings should use `expect_success` or `into_result`.
     pub fn unwrap(self) -> T {
+        self.log()
+    }
+
+    /// Assume that no warning occured, panic with provided message if not
+    pub fn expect_success(self, msg: &str) -> T {
         if self.status != Status::SUCCESS {
-            unwrap_failed(
-                "Called `Completion::unwrap()` with a warning status",
-                self.status,
-            );
+            unwrap_failed(msg, self.status);
         }
         self.result
     }
 
     /// Assume that no warning occured, panic with provided message if not
+    ///
+    /// This is the same as `expect_success`.
     pub fn expect(self, msg: &str) -> T {
-        if self.status != Status::SUCCESS {
-            unwrap_failed(msg, self.status);
-        }
+        self.expect_success(msg)
+    }
+
+    /// Access the inner value, silently discarding the warning if there is any
+    pub fn ignore_warning(self) -> T {
         self.result
     }
 
+    /// Treat warnings as errors, discarding the inner value in that case
+    ///
+    /// This is useful for strict callers that want to propagate warnings with
+    /// the `?` operator.
+    pub fn into_result(self) -> core::result::Result<T, Error> {
+        if self.status.is_success() {
+            Ok(self.result)
+        } else {
+            Err(self.status.into())
+        }
+    }
+
     /// Transform the inner value without unwrapping the Completion
     pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Completion<U> {
         Completion {
@@ -97,7 +118,7 @@ impl<T> From<T> for Completion<T> {
 #[cold]
 fn built_with_error(error: Status) -> ! {
     panic!(
-        "Completion was incorrectly built with error status: {:?}",
+        "Completion was incorrectly built with error status: {}",
         error
     )
 }
@@ -105,11 +126,11 @@ fn built_with_error(error: Status) -> ! {
 #[inline(never)]
 #[cold]
 fn unwrap_failed(msg: &str, warning: Status) -> ! {
-    panic!("{}: {:?}", msg, warning)
+    panic!("{}: {}", msg, warning)
 }
 
 #[inline(never)]
 #[cold]
 fn log_warning(warning: Status) {
-    warn!("Encountered UEFI warning: {:?}", warning)
+    warn!("Encountered UEFI warning: {}", warning)
 }
@@ -1,8 +1,27 @@
 use super::Status;
-use core::fmt::Debug;
+use core::fmt::{self, Debug};
 
 /// Errors emitted from UEFI entry point must propagate erronerous UEFI statuses,
 /// and may optionally propagate additional entry point-specific data.
+///
+/// Errors with additional data can be converted into plain errors, either
+/// explicitly or, for the common buffer size payloads, with `?`:
+///
+/// ```
+/// use uefi::{Error, Status};
+///
+/// let error = Error::new(Status::BUFFER_TOO_SMALL, Some(42));
+/// assert_eq!(*error.data(), Some(42));
+///
+/// fn plain(error: Error<Option<usize>>) -> uefi::Result {
+///     Err(error)?
+/// }
+/// let plain = plain(error).unwrap_err();
+/// assert_eq!(plain.status(), Status::BUFFER_TOO_SMALL);
+///
+/// let error = Error::new(Status::NOT_FOUND, "missing");
+/// assert_eq!(error.discard_data().status(), Status::NOT_FOUND);
+/// ```
 #[derive(Debug)]
 pub struct Error<Data: Debug = ()> {
     status: Status,
@@ -10,18 +29,31 @@ pub struct Error<Data: Debug = ()> {
 }
 
 impl<Data: Debug> Error<Data> {
+    /// Build an error from an error status and some additional data
     pub fn new(status: Status, data: Data) -> Self {
         Self { status, data }
     }
 
+    /// Extract the status of this error
     pub fn status(&self) -> Status {
         self.status
     }
 
+    /// Access the additional data attached to this error
     pub fn data(&self) -> &Data {
         &self.data
     }
 
+    /// Transform the additional data attached to this error
+    pub fn map_data<Mapped: Debug>(self, f: impl FnOnce(Data) -> Mapped) -> Error<Mapped> {
+        Error::new(self.status, f(self.data))
+    }
+
+    /// Discard the additional data attached to this error, keeping the status
+    pub fn discard_data(self) -> Error {
+        self.status.into()
+    }
+
     /// Split this error into its inner status and error data
     pub fn split(self) -> (Status, Data) {
         (self.status, self.data)
@@ -36,10 +68,23 @@ impl From<Status> for Error<()> {
     }
 }
 
-// FIXME: This conversion will go away along with usage of the ucs2 crate
+// The buffer size payloads of the BUFFER_TOO_SMALL pattern can be dropped
+// implicitly, so that `?` works in functions returning payload-free errors.
+
+impl From<Error<Option<usize>>> for Error<()> {
+    fn from(error: Error<Option<usize>>) -> Self {
+        error.discard_data()
+    }
+}
+
+impl From<Error<usize>> for Error<()> {
+    fn from(error: Error<usize>) -> Self {
+        error.discard_data()
+    }
+}
 
-impl From<ucs2::Error> for Error<()> {
-    fn from(other: ucs2::Error) -> Self {
-        Status::from(other).into()
+impl<Data: Debug> fmt::Display for Error<Data> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UEFI error {}", self.status)
     }
 }
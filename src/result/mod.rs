@@ -35,6 +35,19 @@ pub trait ResultExt<Output, ErrData: Debug> {
     fn status(&self) -> Status;
 
     /// Ignore warnings, keeping a trace of them in the logs
+    ///
+    /// ```
+    /// use uefi::{Completion, Result, ResultExt, Status};
+    ///
+    /// let success: Result<u32> = Ok(42.into());
+    /// assert_eq!(success.log_warning().unwrap(), 42);
+    ///
+    /// let warning: Result<u32> = Ok(Completion::new(Status::WARN_STALE_DATA, 42));
+    /// assert_eq!(warning.log_warning().unwrap(), 42);
+    ///
+    /// let error: Result<u32, Option<usize>> = Err(uefi::Error::new(Status::BUFFER_TOO_SMALL, Some(8)));
+    /// assert_eq!(*error.log_warning().unwrap_err().data(), Some(8));
+    /// ```
     fn log_warning(self) -> core::result::Result<Output, Error<ErrData>>;
 
     /// Expect success without warnings, panic otherwise
@@ -43,6 +56,39 @@ pub trait ResultExt<Output, ErrData: Debug> {
     /// Expect success without warnings, panic with provided message otherwise
     fn expect_success(self, msg: &str) -> Output;
 
+    /// Ignore warnings, keeping a trace of them in the logs, and panic with
+    /// `context` and the status of the error otherwise
+    ///
+    /// The additional data of the error is part of the panic message too,
+    /// unless it is empty like `()`. Unlike `expect_success`, this doesn't
+    /// panic on warnings.
+    ///
+    /// ```
+    /// use uefi::{Completion, Result, ResultExt, Status};
+    ///
+    /// let success: Result<u32> = Ok(42.into());
+    /// assert_eq!(success.expect_msg("Failed to get the answer"), 42);
+    ///
+    /// let warning: Result<u32> = Ok(Completion::new(Status::WARN_STALE_DATA, 42));
+    /// assert_eq!(warning.expect_msg("Failed to get the answer"), 42);
+    /// ```
+    ///
+    /// ```should_panic
+    /// # use uefi::{Result, ResultExt, Status};
+    /// // Panics with "Failed to get the answer: NOT_FOUND"
+    /// let error: Result<u32> = Err(Status::NOT_FOUND.into());
+    /// error.expect_msg("Failed to get the answer");
+    /// ```
+    ///
+    /// ```should_panic
+    /// # use uefi::{Result, ResultExt, Status};
+    /// // Panics with "Failed to read the entry: BUFFER_TOO_SMALL (Some(8))"
+    /// let error: Result<u32, Option<usize>> =
+    ///     Err(uefi::Error::new(Status::BUFFER_TOO_SMALL, Some(8)));
+    /// error.expect_msg("Failed to read the entry");
+    /// ```
+    fn expect_msg(self, context: &str) -> Output;
+
     /// Expect error, panic with provided message otherwise, discarding output
     fn expect_error(self, msg: &str) -> Error<ErrData>;
 
@@ -53,6 +99,23 @@ pub trait ResultExt<Output, ErrData: Debug> {
     fn discard_errdata(self) -> Result<Output>;
 
     /// Treat warnings as errors
+    ///
+    /// The error built from a warning has the default data of `ErrData`.
+    ///
+    /// ```
+    /// use uefi::{Completion, Result, ResultExt, Status};
+    ///
+    /// let success: Result<u32> = Ok(42.into());
+    /// assert_eq!(success.warning_as_error().unwrap(), 42);
+    ///
+    /// let warning: Result<u32, Option<usize>> = Ok(Completion::new(Status::WARN_STALE_DATA, 42));
+    /// let error = warning.warning_as_error().unwrap_err();
+    /// assert_eq!(error.status(), Status::WARN_STALE_DATA);
+    /// assert_eq!(*error.data(), None);
+    ///
+    /// let error: Result<u32, Option<usize>> = Err(uefi::Error::new(Status::BUFFER_TOO_SMALL, Some(8)));
+    /// assert_eq!(*error.warning_as_error().unwrap_err().data(), Some(8));
+    /// ```
     fn warning_as_error(self) -> core::result::Result<Output, Error<ErrData>>
     where
         ErrData: Default;
@@ -71,11 +134,26 @@ impl<Output, ErrData: Debug> ResultExt<Output, ErrData> for Result<Output, ErrDa
     }
 
     fn unwrap_success(self) -> Output {
-        self.unwrap().unwrap()
+        self.unwrap()
+            .expect_success("Called `unwrap_success()` with a warning status")
     }
 
     fn expect_success(self, msg: &str) -> Output {
-        self.expect(msg).expect(msg)
+        self.expect(msg).expect_success(msg)
+    }
+
+    fn expect_msg(self, context: &str) -> Output {
+        match self {
+            Ok(completion) => completion.log(),
+            Err(error) => {
+                let (status, data) = error.split();
+                if core::mem::size_of::<ErrData>() == 0 {
+                    expect_msg_failed(context, status, None)
+                } else {
+                    expect_msg_failed(context, status, Some(&data))
+                }
+            }
+        }
     }
 
     fn expect_error(self, msg: &str) -> Error<ErrData> {
@@ -87,10 +165,7 @@ impl<Output, ErrData: Debug> ResultExt<Output, ErrData> for Result<Output, ErrDa
     }
 
     fn discard_errdata(self) -> Result<Output> {
-        match self {
-            Ok(o) => Ok(o),
-            Err(e) => Err(e.status().into()),
-        }
+        self.map_err(Error::discard_data)
     }
 
     fn warning_as_error(self) -> core::result::Result<Output, Error<ErrData>>
@@ -104,3 +179,13 @@ impl<Output, ErrData: Debug> ResultExt<Output, ErrData> for Result<Output, ErrDa
         }
     }
 }
+
+// This is a separate function to reduce the code size of `expect_msg`
+#[inline(never)]
+#[cold]
+fn expect_msg_failed(context: &str, status: Status, data: Option<&dyn Debug>) -> ! {
+    match data {
+        Some(data) => panic!("{}: {} ({:?})", context, status, data),
+        None => panic!("{}: {}", context, status),
+    }
+}
@@ -1,13 +1,21 @@
 use super::{Completion, Error, Result};
+use core::fmt::{self, Debug};
+#[cfg(feature = "unstable")]
 use core::{
     convert::Infallible,
+    num::NonZeroUsize,
     ops::{ControlFlow, FromResidual, Try},
 };
-use core::{fmt::Debug, num::NonZeroUsize};
 
 /// Bit indicating that an UEFI status code is an error
+///
+/// Status codes have the width of the native word, so this is bit 31 on 32-bit
+/// targets and bit 63 on 64-bit ones.
 const ERROR_BIT: usize = 1 << (core::mem::size_of::<usize>() * 8 - 1);
 
+/// Bit indicating that an UEFI status code is reserved for OEM use
+const OEM_BIT: usize = ERROR_BIT >> 1;
+
 newtype_enum! {
 /// UEFI uses status codes in order to report successes, errors, and warnings.
 ///
@@ -16,6 +24,21 @@ newtype_enum! {
 /// enum, as injecting an unknown value in a Rust enum is undefined behaviour.
 ///
 /// For lack of a better option, we therefore model them as a newtype of usize.
+///
+/// Status codes are displayed using their name in the spec, and unknown codes
+/// (such as OEM-specific ones) are displayed using their raw value:
+///
+/// ```
+/// use uefi::Status;
+///
+/// assert_eq!(format!("{}", Status::BUFFER_TOO_SMALL), "BUFFER_TOO_SMALL");
+/// assert_eq!(format!("{}", Status::WARN_UNKNOWN_GLYPH), "WARN_UNKNOWN_GLYPH");
+///
+/// let oem_error = Status((1 << (usize::BITS - 1)) | (1 << (usize::BITS - 2)) | 0x42);
+/// assert!(oem_error.is_error());
+/// assert_eq!(format!("{}", oem_error), format!("{:#x}", oem_error.0));
+/// assert_eq!(format!("{:?}", oem_error), format!("Status({})", oem_error.0));
+/// ```
 #[must_use]
 pub enum Status: usize => {
     /// The operation completed successfully.
@@ -105,6 +128,12 @@ pub enum Status: usize => {
     IP_ADDRESS_CONFLICT     = ERROR_BIT | 34,
     /// A HTTP error occurred during the network operation.
     HTTP_ERROR              = ERROR_BIT | 35,
+    /// The remote endpoint closed the TCP connection.
+    CONNECTION_FIN          = ERROR_BIT | 104,
+    /// The remote endpoint reset the TCP connection.
+    CONNECTION_RESET        = ERROR_BIT | 105,
+    /// The remote endpoint refused the TCP connection.
+    CONNECTION_REFUSED      = ERROR_BIT | 106,
 }}
 
 impl Status {
@@ -126,6 +155,56 @@ impl Status {
         self.0 & ERROR_BIT != 0
     }
 
+    /// Returns true if the status code is reserved for OEM use.
+    ///
+    /// These codes have the second most significant bit set, and can be
+    /// either warnings or errors. Since they are implementation-specific,
+    /// they are displayed using their raw value.
+    ///
+    /// ```
+    /// use uefi::{ResultExt, Status};
+    ///
+    /// let error_bit = 1 << (usize::BITS - 1);
+    /// let oem_bit = 1 << (usize::BITS - 2);
+    ///
+    /// let oem_warning = Status(oem_bit | 1);
+    /// assert!(oem_warning.is_oem() && oem_warning.is_warning());
+    /// let oem_error = Status(error_bit | oem_bit | 1);
+    /// assert!(oem_error.is_oem() && oem_error.is_error());
+    /// assert!(!Status::BUFFER_TOO_SMALL.is_oem());
+    ///
+    /// // Unknown values are preserved through conversions.
+    /// for &status in &[oem_warning, oem_error, Status(error_bit | 0x1234)] {
+    ///     assert_eq!(uefi::Result::from(status).status(), status);
+    ///     assert_eq!(format!("{}", status), format!("{:#x}", status.0));
+    /// }
+    /// ```
+    #[inline]
+    pub fn is_oem(self) -> bool {
+        self.0 & OEM_BIT != 0
+    }
+
+    /// Converts this status code into a result without any value.
+    ///
+    /// This is the stable way to propagate a status code with `?`:
+    ///
+    /// ```
+    /// use uefi::Status;
+    ///
+    /// fn check(status: Status) -> uefi::Result {
+    ///     status.into_result()?;
+    ///     Ok(().into())
+    /// }
+    /// assert!(check(Status::SUCCESS).is_ok());
+    /// assert!(check(Status::WARN_UNKNOWN_GLYPH).is_ok());
+    /// assert_eq!(check(Status::NOT_FOUND).unwrap_err().status(), Status::NOT_FOUND);
+    /// ```
+    #[inline]
+    #[allow(clippy::result_unit_err)]
+    pub fn into_result(self) -> Result<(), ()> {
+        self.into()
+    }
+
     /// Converts this status code into a result with a given value.
     #[inline]
     #[allow(clippy::result_unit_err)]
@@ -165,6 +244,15 @@ impl Status {
     }
 }
 
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.variant_name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{:#x}", self.0),
+        }
+    }
+}
+
 // An UEFI status is equivalent to a Result with no data or error payload
 impl From<Status> for Result<(), ()> {
     #[inline]
@@ -173,8 +261,14 @@ impl From<Status> for Result<(), ()> {
     }
 }
 
+// With the `unstable` feature, the `?` operator can also be applied directly
+// to a `Status`. This relies on the `Try` trait, which is still being reworked
+// upstream, so use `Status::into_result` whenever possible.
+
+#[cfg(feature = "unstable")]
 pub struct StatusResidual(NonZeroUsize);
 
+#[cfg(feature = "unstable")]
 impl Try for Status {
     type Output = Completion<()>;
     type Residual = StatusResidual;
@@ -191,18 +285,21 @@ impl Try for Status {
     }
 }
 
+#[cfg(feature = "unstable")]
 impl FromResidual for Status {
     fn from_residual(r: StatusResidual) -> Self {
         Status(r.0.into())
     }
 }
 
+#[cfg(feature = "unstable")]
 impl<T> FromResidual<StatusResidual> for Result<T, ()> {
     fn from_residual(r: StatusResidual) -> Self {
         Err(Status(r.0.into()).into())
     }
 }
 
+#[cfg(feature = "unstable")]
 impl FromResidual<core::result::Result<Infallible, Error>> for Status {
     fn from_residual(r: core::result::Result<Infallible, Error>) -> Self {
         match r {
@@ -212,14 +309,201 @@ impl FromResidual<core::result::Result<Infallible, Error>> for Status {
     }
 }
 
-// FIXME: This conversion will go away along with usage of the ucs2 crate
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResultExt;
 
-impl From<ucs2::Error> for Status {
-    fn from(other: ucs2::Error) -> Self {
-        use ucs2::Error;
-        match other {
-            Error::BufferOverflow => Status::BUFFER_TOO_SMALL,
-            Error::MultiByte => Status::UNSUPPORTED,
+    extern crate std;
+    use std::format;
+
+    #[test]
+    fn display_error() {
+        assert_eq!(format!("{}", Status::BUFFER_TOO_SMALL), "BUFFER_TOO_SMALL");
+        assert_eq!(
+            format!("{:?}", Status::BUFFER_TOO_SMALL),
+            "BUFFER_TOO_SMALL"
+        );
+        assert_eq!(
+            format!("{}", Status::CONNECTION_REFUSED),
+            "CONNECTION_REFUSED"
+        );
+    }
+
+    #[test]
+    fn display_warning() {
+        assert_eq!(
+            format!("{}", Status::WARN_UNKNOWN_GLYPH),
+            "WARN_UNKNOWN_GLYPH"
+        );
+        assert_eq!(
+            format!("{:?}", Status::WARN_RESET_REQUIRED),
+            "WARN_RESET_REQUIRED"
+        );
+        assert_eq!(format!("{}", Status::SUCCESS), "SUCCESS");
+    }
+
+    #[test]
+    fn display_oem() {
+        let oem_warning = Status(OEM_BIT | 1);
+        assert_eq!(format!("{}", oem_warning), format!("{:#x}", OEM_BIT | 1));
+        assert_eq!(
+            format!("{:?}", oem_warning),
+            format!("Status({})", OEM_BIT | 1)
+        );
+
+        let oem_error = Status(ERROR_BIT | OEM_BIT | 0x42);
+        assert_eq!(
+            format!("{}", oem_error),
+            format!("{:#x}", ERROR_BIT | OEM_BIT | 0x42)
+        );
+        assert_eq!(
+            format!("{:?}", oem_error),
+            format!("Status({})", ERROR_BIT | OEM_BIT | 0x42)
+        );
+    }
+
+    #[test]
+    fn display_unknown() {
+        // Codes which are neither defined by the spec nor OEM-specific.
+        assert_eq!(
+            format!("{}", Status(ERROR_BIT | 29)),
+            format!("{:#x}", ERROR_BIT | 29)
+        );
+        assert_eq!(format!("{}", Status(8)), "0x8");
+    }
+
+    #[test]
+    fn spec_codes_round_trip() {
+        let codes = [
+            (Status::SUCCESS, "SUCCESS", 0),
+            (Status::WARN_UNKNOWN_GLYPH, "WARN_UNKNOWN_GLYPH", 1),
+            (Status::WARN_DELETE_FAILURE, "WARN_DELETE_FAILURE", 2),
+            (Status::WARN_WRITE_FAILURE, "WARN_WRITE_FAILURE", 3),
+            (Status::WARN_BUFFER_TOO_SMALL, "WARN_BUFFER_TOO_SMALL", 4),
+            (Status::WARN_STALE_DATA, "WARN_STALE_DATA", 5),
+            (Status::WARN_FILE_SYSTEM, "WARN_FILE_SYSTEM", 6),
+            (Status::WARN_RESET_REQUIRED, "WARN_RESET_REQUIRED", 7),
+            (Status::LOAD_ERROR, "LOAD_ERROR", ERROR_BIT | 1),
+            (
+                Status::INVALID_PARAMETER,
+                "INVALID_PARAMETER",
+                ERROR_BIT | 2,
+            ),
+            (Status::UNSUPPORTED, "UNSUPPORTED", ERROR_BIT | 3),
+            (Status::BAD_BUFFER_SIZE, "BAD_BUFFER_SIZE", ERROR_BIT | 4),
+            (Status::BUFFER_TOO_SMALL, "BUFFER_TOO_SMALL", ERROR_BIT | 5),
+            (Status::NOT_READY, "NOT_READY", ERROR_BIT | 6),
+            (Status::DEVICE_ERROR, "DEVICE_ERROR", ERROR_BIT | 7),
+            (Status::WRITE_PROTECTED, "WRITE_PROTECTED", ERROR_BIT | 8),
+            (Status::OUT_OF_RESOURCES, "OUT_OF_RESOURCES", ERROR_BIT | 9),
+            (Status::VOLUME_CORRUPTED, "VOLUME_CORRUPTED", ERROR_BIT | 10),
+            (Status::VOLUME_FULL, "VOLUME_FULL", ERROR_BIT | 11),
+            (Status::NO_MEDIA, "NO_MEDIA", ERROR_BIT | 12),
+            (Status::MEDIA_CHANGED, "MEDIA_CHANGED", ERROR_BIT | 13),
+            (Status::NOT_FOUND, "NOT_FOUND", ERROR_BIT | 14),
+            (Status::ACCESS_DENIED, "ACCESS_DENIED", ERROR_BIT | 15),
+            (Status::NO_RESPONSE, "NO_RESPONSE", ERROR_BIT | 16),
+            (Status::NO_MAPPING, "NO_MAPPING", ERROR_BIT | 17),
+            (Status::TIMEOUT, "TIMEOUT", ERROR_BIT | 18),
+            (Status::NOT_STARTED, "NOT_STARTED", ERROR_BIT | 19),
+            (Status::ALREADY_STARTED, "ALREADY_STARTED", ERROR_BIT | 20),
+            (Status::ABORTED, "ABORTED", ERROR_BIT | 21),
+            (Status::ICMP_ERROR, "ICMP_ERROR", ERROR_BIT | 22),
+            (Status::TFTP_ERROR, "TFTP_ERROR", ERROR_BIT | 23),
+            (Status::PROTOCOL_ERROR, "PROTOCOL_ERROR", ERROR_BIT | 24),
+            (
+                Status::INCOMPATIBLE_VERSION,
+                "INCOMPATIBLE_VERSION",
+                ERROR_BIT | 25,
+            ),
+            (
+                Status::SECURITY_VIOLATION,
+                "SECURITY_VIOLATION",
+                ERROR_BIT | 26,
+            ),
+            (Status::CRC_ERROR, "CRC_ERROR", ERROR_BIT | 27),
+            (Status::END_OF_MEDIA, "END_OF_MEDIA", ERROR_BIT | 28),
+            (Status::END_OF_FILE, "END_OF_FILE", ERROR_BIT | 31),
+            (Status::INVALID_LANGUAGE, "INVALID_LANGUAGE", ERROR_BIT | 32),
+            (Status::COMPROMISED_DATA, "COMPROMISED_DATA", ERROR_BIT | 33),
+            (
+                Status::IP_ADDRESS_CONFLICT,
+                "IP_ADDRESS_CONFLICT",
+                ERROR_BIT | 34,
+            ),
+            (Status::HTTP_ERROR, "HTTP_ERROR", ERROR_BIT | 35),
+            (Status::CONNECTION_FIN, "CONNECTION_FIN", ERROR_BIT | 104),
+            (
+                Status::CONNECTION_RESET,
+                "CONNECTION_RESET",
+                ERROR_BIT | 105,
+            ),
+            (
+                Status::CONNECTION_REFUSED,
+                "CONNECTION_REFUSED",
+                ERROR_BIT | 106,
+            ),
+        ];
+        for &(status, name, value) in &codes {
+            assert_eq!(status.0, value);
+            assert_eq!(Status(value), status);
+            assert_eq!(format!("{}", status), name);
+            assert_eq!(format!("{:?}", status), name);
+            assert!(!status.is_oem());
+            assert_eq!(status.is_success(), value == 0);
+            assert_eq!(status.is_warning(), name.starts_with("WARN_"));
+            assert_eq!(
+                status.is_error(),
+                name != "SUCCESS" && !name.starts_with("WARN_")
+            );
+            assert_eq!(Result::from(status).status(), status);
         }
     }
+
+    #[test]
+    fn oem_codes_round_trip() {
+        let codes = [
+            OEM_BIT,
+            OEM_BIT | 1,
+            OEM_BIT | 0xffff,
+            ERROR_BIT | OEM_BIT,
+            ERROR_BIT | OEM_BIT | 1,
+            ERROR_BIT | OEM_BIT | 0x1234,
+            usize::MAX,
+        ];
+        for &value in &codes {
+            let status = Status(value);
+            assert!(status.is_oem());
+            assert_eq!(status.is_error(), value & ERROR_BIT != 0);
+            assert_eq!(status.is_warning(), value & ERROR_BIT == 0);
+            assert!(!status.is_success());
+            assert_eq!(Result::from(status).status(), status);
+            assert_eq!(Result::from(status).is_err(), status.is_error());
+        }
+    }
+
+    #[test]
+    fn unknown_codes_round_trip() {
+        for &value in &[
+            8,
+            0x1234,
+            ERROR_BIT | 29,
+            ERROR_BIT | 36,
+            ERROR_BIT | 0x1234,
+        ] {
+            let status = Status(value);
+            assert!(!status.is_oem());
+            assert_eq!(Result::from(status).status(), status);
+        }
+    }
+
+    #[test]
+    fn native_width() {
+        // EFI_STATUS is as wide as the native word, on 32-bit and 64-bit targets.
+        assert_eq!(ERROR_BIT, 1 << (usize::BITS - 1));
+        assert_eq!(OEM_BIT, 1 << (usize::BITS - 2));
+        assert!(Status(ERROR_BIT).is_error());
+        assert!(Status(ERROR_BIT - 1).is_warning());
+    }
 }
@@ -1,16 +1,26 @@
 //! UEFI services available during boot.
 
 use super::Header;
-use crate::data_types::Align;
+use crate::data_types::{Align, PhysicalAddress, VirtualAddress};
 use crate::proto::{device_path::DevicePath, Protocol};
 #[cfg(feature = "exts")]
-use crate::proto::{loaded_image::LoadedImage, media::fs::SimpleFileSystem};
+use crate::proto::{
+    loaded_image::LoadedImage,
+    media::{
+        file::{Directory, FileAttribute, FileMode, FileType},
+        fs::SimpleFileSystem,
+    },
+};
+#[cfg(feature = "exts")]
+use crate::ResultExt;
 use crate::{Char16, Event, Guid, Handle, Result, Status};
 #[cfg(feature = "exts")]
 use alloc_api::vec::Vec;
 use bitflags::bitflags;
 use core::cell::UnsafeCell;
 use core::ffi::c_void;
+#[cfg(feature = "exts")]
+use core::marker::PhantomData;
 use core::mem::{self, MaybeUninit};
 use core::{ptr, slice};
 
@@ -28,9 +38,9 @@ pub struct BootServices {
         alloc_ty: u32,
         mem_ty: MemoryType,
         count: usize,
-        addr: &mut u64,
+        addr: &mut PhysicalAddress,
     ) -> Status,
-    free_pages: extern "efiapi" fn(addr: u64, pages: usize) -> Status,
+    free_pages: extern "efiapi" fn(addr: PhysicalAddress, pages: usize) -> Status,
     get_memory_map: unsafe extern "efiapi" fn(
         size: &mut usize,
         map: *mut MemoryDescriptor,
@@ -53,17 +63,26 @@ pub struct BootServices {
     set_timer: unsafe extern "efiapi" fn(event: Event, ty: u32, trigger_time: u64) -> Status,
     wait_for_event: unsafe extern "efiapi" fn(
         number_of_events: usize,
-        events: *mut Event,
+        events: *const Event,
         out_index: *mut usize,
     ) -> Status,
-    signal_event: usize,
-    close_event: usize,
+    signal_event: unsafe extern "efiapi" fn(event: Event) -> Status,
+    close_event: unsafe extern "efiapi" fn(event: Event) -> Status,
     check_event: usize,
 
     // Protocol handlers
-    install_protocol_interface: usize,
+    install_protocol_interface: unsafe extern "efiapi" fn(
+        handle: &mut Option<Handle>,
+        protocol: &Guid,
+        interface_type: u32,
+        interface: *mut c_void,
+    ) -> Status,
     reinstall_protocol_interface: usize,
-    uninstall_protocol_interface: usize,
+    uninstall_protocol_interface: unsafe extern "efiapi" fn(
+        handle: Handle,
+        protocol: &Guid,
+        interface: *mut c_void,
+    ) -> Status,
     handle_protocol:
         extern "efiapi" fn(handle: Handle, proto: &Guid, out_proto: &mut *mut c_void) -> Status,
     _reserved: usize,
@@ -112,12 +131,33 @@ pub struct BootServices {
     ) -> Status,
 
     // Driver support services
-    connect_controller: usize,
-    disconnect_controller: usize,
+    connect_controller: unsafe extern "efiapi" fn(
+        controller: Handle,
+        driver_image: *const Option<Handle>,
+        remaining_device_path: *const DevicePath,
+        recursive: bool,
+    ) -> Status,
+    disconnect_controller: extern "efiapi" fn(
+        controller: Handle,
+        driver_image: Option<Handle>,
+        child: Option<Handle>,
+    ) -> Status,
 
     // Protocol open / close services
-    open_protocol: usize,
-    close_protocol: usize,
+    open_protocol: extern "efiapi" fn(
+        handle: Handle,
+        protocol: &Guid,
+        interface: &mut *mut c_void,
+        agent_handle: Handle,
+        controller_handle: Option<Handle>,
+        attributes: u32,
+    ) -> Status,
+    close_protocol: extern "efiapi" fn(
+        handle: Handle,
+        protocol: &Guid,
+        agent_handle: Handle,
+        controller_handle: Option<Handle>,
+    ) -> Status,
     open_protocol_information: usize,
 
     // Library services
@@ -136,7 +176,8 @@ pub struct BootServices {
     uninstall_multiple_protocol_interfaces: usize,
 
     // CRC services
-    calculate_crc32: usize,
+    calculate_crc32:
+        unsafe extern "efiapi" fn(data: *const c_void, data_size: usize, crc32: &mut u32) -> Status,
 
     // Misc services
     copy_mem: unsafe extern "efiapi" fn(dest: *mut u8, src: *const u8, len: usize),
@@ -178,17 +219,17 @@ impl BootServices {
         ty: AllocateType,
         mem_ty: MemoryType,
         count: usize,
-    ) -> Result<u64> {
+    ) -> Result<PhysicalAddress> {
         let (ty, mut addr) = match ty {
-            AllocateType::AnyPages => (0, 0),
-            AllocateType::MaxAddress(addr) => (1, addr as u64),
-            AllocateType::Address(addr) => (2, addr as u64),
+            AllocateType::AnyPages => (0, PhysicalAddress::default()),
+            AllocateType::MaxAddress(addr) => (1, addr),
+            AllocateType::Address(addr) => (2, addr),
         };
         (self.allocate_pages)(ty, mem_ty, count, &mut addr).into_with_val(|| addr)
     }
 
     /// Frees memory pages allocated by UEFI.
-    pub fn free_pages(&self, addr: u64, count: usize) -> Result {
+    pub fn free_pages(&self, addr: PhysicalAddress, count: usize) -> Result {
         (self.free_pages)(addr, count).into()
     }
 
@@ -229,13 +270,19 @@ impl BootServices {
     ///
     /// If you want to store the resulting memory map without having to keep
     /// the buffer around, you can use `.copied().collect()` on the iterator.
+    ///
+    /// If the buffer is too small, `BUFFER_TOO_SMALL` is returned along with
+    /// the size of the current memory map.
     pub fn memory_map<'buf>(
         &self,
         buffer: &'buf mut [u8],
-    ) -> Result<(
-        MemoryMapKey,
-        impl ExactSizeIterator<Item = &'buf MemoryDescriptor> + Clone,
-    )> {
+    ) -> Result<
+        (
+            MemoryMapKey,
+            impl ExactSizeIterator<Item = &'buf MemoryDescriptor> + Clone,
+        ),
+        Option<usize>,
+    > {
         let mut map_size = buffer.len();
         MemoryDescriptor::assert_aligned(buffer);
         #[allow(clippy::cast_ptr_alignment)]
@@ -259,16 +306,20 @@ impl BootServices {
                 &mut entry_version,
             )
         }
-        .into_with_val(move || {
-            let len = map_size / entry_size;
-            let iter = MemoryMapIter {
-                buffer,
-                entry_size,
-                index: 0,
-                len,
-            };
-            (map_key, iter)
-        })
+        .into_with(
+            move || {
+                let len = map_size / entry_size;
+                let iter = MemoryMapIter::new(buffer, entry_size, len);
+                (map_key, iter)
+            },
+            |status| {
+                if status == Status::BUFFER_TOO_SMALL {
+                    Some(map_size)
+                } else {
+                    None
+                }
+            },
+        )
     }
 
     /// Allocates from a memory pool. The pointer will be 8-byte aligned.
@@ -293,6 +344,9 @@ impl BootServices {
     /// will be delivered next time `wait_for_event` or `check_event` is called.
     /// In both cases, a `notify_fn` callback must be specified.
     ///
+    /// The returned event is owned by the caller, and should be closed with
+    /// `close_event` once it is not needed anymore.
+    ///
     /// # Safety
     ///
     /// This function is unsafe because callbacks must handle exit from boot
@@ -301,21 +355,21 @@ impl BootServices {
         &self,
         event_ty: EventType,
         notify_tpl: Tpl,
-        notify_fn: Option<fn(Event)>,
+        notify_fn: Option<fn(&Event)>,
     ) -> Result<Event> {
         // Prepare storage for the output Event
         let mut event = MaybeUninit::<Event>::uninit();
 
         // Use a trampoline to handle the impedance mismatch between Rust & C
         unsafe extern "efiapi" fn notify_trampoline(e: Event, ctx: *mut c_void) {
-            let notify_fn: fn(Event) = mem::transmute(ctx);
-            notify_fn(e); // SAFETY: Aborting panics are assumed here
+            let notify_fn: fn(&Event) = mem::transmute(ctx);
+            notify_fn(&e); // SAFETY: Aborting panics are assumed here
         }
         let (notify_func, notify_ctx) = notify_fn
             .map(|notify_fn| {
                 (
                     Some(notify_trampoline as EventNotifyFn),
-                    notify_fn as fn(Event) as *mut c_void,
+                    notify_fn as fn(&Event) as *mut c_void,
                 )
             })
             .unwrap_or((None, ptr::null_mut()));
@@ -358,8 +412,8 @@ impl BootServices {
     /// To check if an event is signaled without waiting, an already signaled
     /// event can be used as the last event in the slice being checked, or the
     /// check_event() interface may be used.
-    pub fn wait_for_event(&self, events: &mut [Event]) -> Result<usize, Option<usize>> {
-        let (number_of_events, events) = (events.len(), events.as_mut_ptr());
+    pub fn wait_for_event(&self, events: &[Event]) -> Result<usize, Option<usize>> {
+        let (number_of_events, events) = (events.len(), events.as_ptr());
         let mut index = MaybeUninit::<usize>::uninit();
         unsafe { (self.wait_for_event)(number_of_events, events, index.as_mut_ptr()) }.into_with(
             || unsafe { index.assume_init() },
@@ -373,14 +427,75 @@ impl BootServices {
         )
     }
 
+    /// Places an event in the signaled state.
+    ///
+    /// If the event has a notification function, it is queued. Protocols
+    /// also use events to learn that the caller is done with some of their
+    /// resources, such as a received network packet.
+    pub fn signal_event(&self, event: &Event) -> Result {
+        // The event is passed by value, but it is not consumed.
+        unsafe { (self.signal_event)(event.unsafe_clone()) }.into()
+    }
+
+    /// Closes an event, removing it from any event group it belongs to.
+    ///
+    /// Once closed, the event is freed by the firmware, and must not be used
+    /// anymore. This is enforced by taking ownership of the `Event`.
+    pub fn close_event(&self, event: Event) -> Result {
+        unsafe { (self.close_event)(event) }.into()
+    }
+
     /// Sets the trigger for `EventType::TIMER` event.
-    pub fn set_timer(&self, event: Event, trigger_time: TimerTrigger) -> Result {
+    pub fn set_timer(&self, event: &Event, trigger_time: TimerTrigger) -> Result {
         let (ty, time) = match trigger_time {
             TimerTrigger::Cancel => (0, 0),
             TimerTrigger::Periodic(hundreds_ns) => (1, hundreds_ns),
             TimerTrigger::Relative(hundreds_ns) => (2, hundreds_ns),
         };
-        unsafe { (self.set_timer)(event, ty, time) }.into()
+        // The event is passed by value, but it is not consumed.
+        unsafe { (self.set_timer)(event.unsafe_clone(), ty, time) }.into()
+    }
+
+    /// Installs a protocol interface on a handle, and returns the handle.
+    ///
+    /// If `handle` is `None`, a new handle is created. `INVALID_PARAMETER` is
+    /// returned if the protocol is already installed on the handle.
+    ///
+    /// # Safety
+    ///
+    /// The interface must be a valid instance of the protocol identified by
+    /// `protocol`, and must remain valid until it is uninstalled, as it is
+    /// used by the firmware and the other UEFI images.
+    pub unsafe fn install_protocol_interface(
+        &self,
+        handle: Option<Handle>,
+        protocol: &Guid,
+        interface: *mut c_void,
+    ) -> Result<Handle> {
+        let mut handle = handle;
+        // Only native interfaces are defined by the specification.
+        let native_interface = 0;
+        (self.install_protocol_interface)(&mut handle, protocol, native_interface, interface)
+            .into_with_val(|| handle.unwrap())
+    }
+
+    /// Removes a protocol interface from a handle.
+    ///
+    /// The firmware first disconnects the drivers which opened the interface,
+    /// and `ACCESS_DENIED` is returned if the interface is still in use.
+    /// The handle is deleted when its last protocol is uninstalled.
+    ///
+    /// # Safety
+    ///
+    /// The interface must have been installed on the handle for this
+    /// protocol, and must not be used by the caller afterwards.
+    pub unsafe fn uninstall_protocol_interface(
+        &self,
+        handle: Handle,
+        protocol: &Guid,
+        interface: *mut c_void,
+    ) -> Result {
+        (self.uninstall_protocol_interface)(handle, protocol, interface).into()
     }
 
     /// Query a handle for a certain protocol.
@@ -400,6 +515,51 @@ impl BootServices {
         })
     }
 
+    /// Opens a protocol interface on a handle, on behalf of an agent.
+    ///
+    /// Unlike `handle_protocol`, this records the agent (usually the image
+    /// handle of the caller) and optional controller as users of the
+    /// interface in the firmware's protocol database. The interface is closed
+    /// again when the returned `ScopedProtocol` is dropped.
+    ///
+    /// Depending on the `attributes`, the firmware may refuse to open an
+    /// interface which is already in use, with an `ACCESS_DENIED` or
+    /// `ALREADY_STARTED` error.
+    pub fn open_protocol<P: Protocol>(
+        &self,
+        params: OpenProtocolParams,
+        attributes: OpenProtocolAttributes,
+    ) -> Result<ScopedProtocol<'_, P>> {
+        let mut ptr = ptr::null_mut();
+        (self.open_protocol)(
+            params.handle,
+            &P::GUID,
+            &mut ptr,
+            params.agent,
+            params.controller,
+            attributes as u32,
+        )
+        .into_with_val(|| {
+            let ptr = ptr as *mut P as *mut UnsafeCell<P>;
+            ScopedProtocol {
+                interface: unsafe { &*ptr },
+                params,
+                boot_services: self,
+            }
+        })
+    }
+
+    /// Closes a protocol interface which was opened with `open_protocol`.
+    ///
+    /// This is normally done by dropping the `ScopedProtocol`, but can be
+    /// used to observe errors, for example when the interface was already
+    /// closed by a driver being disconnected.
+    pub fn close_protocol<P: Protocol>(&self, protocol: ScopedProtocol<P>) -> Result {
+        let params = protocol.params;
+        mem::forget(protocol);
+        (self.close_protocol)(params.handle, &P::GUID, params.agent, params.controller).into()
+    }
+
     /// Enumerates all handles installed on the system which match a certain query.
     ///
     /// You should first call this function with `None` for the output buffer,
@@ -410,15 +570,32 @@ impl BootServices {
         &self,
         search_ty: SearchType,
         output: Option<&mut [Handle]>,
+    ) -> Result<usize> {
+        match output {
+            Some(buffer) => unsafe {
+                self.locate_handle_raw(search_ty, buffer.as_mut_ptr(), buffer.len())
+            },
+            None => unsafe { self.locate_handle_raw(search_ty, ptr::null_mut(), 0) },
+        }
+    }
+
+    /// Version of `locate_handle` which writes up to `len` handles to a
+    /// possibly uninitialized buffer.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be valid for writing `len` handles, or null.
+    unsafe fn locate_handle_raw(
+        &self,
+        search_ty: SearchType,
+        buffer: *mut Handle,
+        len: usize,
     ) -> Result<usize> {
         let handle_size = mem::size_of::<Handle>();
 
         const NULL_BUFFER: *mut Handle = ptr::null_mut();
 
-        let (mut buffer_size, buffer) = match output {
-            Some(buffer) => (buffer.len() * handle_size, buffer.as_mut_ptr()),
-            None => (0, NULL_BUFFER),
-        };
+        let mut buffer_size = len * handle_size;
 
         // Obtain the needed data from the parameters.
         let (ty, guid, key) = match search_ty {
@@ -426,7 +603,7 @@ impl BootServices {
             SearchType::ByProtocol(guid) => (2, guid as *const _, ptr::null_mut()),
         };
 
-        let status = unsafe { (self.locate_handle)(ty, guid, key, &mut buffer_size, buffer) };
+        let status = (self.locate_handle)(ty, guid, key, &mut buffer_size, buffer);
 
         // Must convert the returned size (in bytes) to length (number of elements).
         let buffer_len = buffer_size / handle_size;
@@ -440,10 +617,10 @@ impl BootServices {
     /// Locates the handle to a device on the device path that supports the specified protocol.
     pub fn locate_device_path<P: Protocol>(&self, device_path: &mut DevicePath) -> Result<Handle> {
         unsafe {
-            let mut handle = Handle::uninitialized();
+            let mut handle = MaybeUninit::uninit();
             let mut device_path_ptr = device_path as *mut DevicePath;
-            (self.locate_device_path)(&P::GUID, &mut device_path_ptr, &mut handle)
-                .into_with_val(|| handle)
+            (self.locate_device_path)(&P::GUID, &mut device_path_ptr, handle.as_mut_ptr())
+                .into_with_val(|| handle.assume_init())
         }
     }
 
@@ -457,16 +634,16 @@ impl BootServices {
             let boot_policy = 0;
             let device_path = ptr::null();
             let source_size = source_buffer.len();
-            let mut image_handle = Handle::uninitialized();
+            let mut image_handle = MaybeUninit::uninit();
             (self.load_image)(
                 boot_policy,
                 parent_image_handle,
                 device_path,
                 source_buffer.as_ptr(),
                 source_size,
-                &mut image_handle,
+                image_handle.as_mut_ptr(),
             )
-            .into_with_val(|| image_handle)
+            .into_with_val(|| image_handle.assume_init())
         }
     }
 
@@ -552,6 +729,54 @@ impl BootServices {
         unsafe { (self.set_watchdog_timer)(timeout, watchdog_code, data_len, data) }.into()
     }
 
+    /// Connects drivers to a controller.
+    ///
+    /// If `driver_image` is `None`, all the drivers which support the
+    /// controller are started on it, in the order given by their driver
+    /// binding versions. Otherwise, only the driver with this image handle is
+    /// tried. `remaining_device_path` is passed to the drivers, to only create
+    /// the child controller it describes. If `recursive` is `true`, drivers
+    /// are also connected to the child controllers which are created.
+    ///
+    /// `NOT_FOUND` is returned if no driver was started on the controller.
+    pub fn connect_controller(
+        &self,
+        controller: Handle,
+        driver_image: Option<Handle>,
+        remaining_device_path: Option<&DevicePath>,
+        recursive: bool,
+    ) -> Result {
+        // The images are passed as a list terminated by a null handle.
+        let driver_images = [driver_image, None];
+        let driver_images = if driver_image.is_some() {
+            driver_images.as_ptr()
+        } else {
+            ptr::null()
+        };
+        let remaining_device_path = remaining_device_path
+            .map(|path| path as *const DevicePath)
+            .unwrap_or(ptr::null());
+        unsafe {
+            (self.connect_controller)(controller, driver_images, remaining_device_path, recursive)
+        }
+        .into()
+    }
+
+    /// Disconnects drivers from a controller.
+    ///
+    /// If `driver_image` is `None`, all the drivers managing the controller
+    /// are stopped, otherwise only the driver with this image handle. If
+    /// `child` is `None`, all the child controllers are destroyed, otherwise
+    /// only this child.
+    pub fn disconnect_controller(
+        &self,
+        controller: Handle,
+        driver_image: Option<Handle>,
+        child: Option<Handle>,
+    ) -> Result {
+        (self.disconnect_controller)(controller, driver_image, child).into()
+    }
+
     /// Get the list of protocol interface [`Guids`][Guid] that are installed
     /// on a [`Handle`].
     pub fn protocols_per_handle(&self, handle: Handle) -> Result<ProtocolsPerHandle> {
@@ -593,6 +818,16 @@ impl BootServices {
         })
     }
 
+    /// Computes the CRC32 checksum of `data`, with the algorithm used for the
+    /// headers of the UEFI tables.
+    ///
+    /// `INVALID_PARAMETER` is returned if `data` is empty.
+    pub fn calculate_crc32(&self, data: &[u8]) -> Result<u32> {
+        let mut crc32 = 0;
+        unsafe { (self.calculate_crc32)(data.as_ptr().cast(), data.len(), &mut crc32) }
+            .into_with_val(|| crc32)
+    }
+
     /// Copies memory from source to destination. The buffers can overlap.
     ///
     /// # Safety
@@ -617,32 +852,57 @@ impl BootServices {
 #[cfg(feature = "exts")]
 impl BootServices {
     /// Returns all the handles implementing a certain protocol.
+    ///
+    /// The list is empty if no handle implements the protocol.
     pub fn find_handles<P: Protocol>(&self) -> Result<Vec<Handle>> {
         // Search by protocol.
         let search_type = SearchType::from_proto::<P>();
 
-        // Determine how much we need to allocate.
-        let (status1, buffer_size) = self.locate_handle(search_type, None)?.split();
-
-        // Allocate a large enough buffer.
-        let mut buffer = Vec::with_capacity(buffer_size);
-
-        unsafe {
-            buffer.set_len(buffer_size);
-        }
+        let mut buffer = Vec::new();
+        loop {
+            // Determine how much we need to allocate.
+            let buffer_size = match self.locate_handle(search_type, None) {
+                Ok(completion) => completion.log(),
+                Err(error) if error.status() == Status::NOT_FOUND => return Ok(Vec::new().into()),
+                Err(error) => return Err(error),
+            };
 
-        // Perform the search.
-        let (status2, buffer_size) = self.locate_handle(search_type, Some(&mut buffer))?.split();
+            // Allocate a large enough buffer. It stays empty until the
+            // firmware has filled it.
+            buffer.reserve(buffer_size);
 
-        // Once the vector has been filled, update its size.
-        unsafe {
-            buffer.set_len(buffer_size);
+            // Perform the search. Handles may have been installed or removed
+            // since the size was determined, in which case we try again.
+            let result = unsafe {
+                self.locate_handle_raw(search_type, buffer.as_mut_ptr(), buffer.capacity())
+            };
+            match result {
+                Ok(completion) => {
+                    let (status, buffer_size) = completion.split();
+                    // Once the vector has been filled, update its size.
+                    unsafe {
+                        buffer.set_len(buffer_size.min(buffer.capacity()));
+                    }
+                    return status.into_with_val(|| buffer);
+                }
+                Err(error) if error.status() == Status::BUFFER_TOO_SMALL => continue,
+                Err(error) if error.status() == Status::NOT_FOUND => return Ok(Vec::new().into()),
+                Err(error) => return Err(error),
+            }
         }
+    }
 
-        // Emit output, with warnings
-        status1
-            .into_with_val(|| buffer)
-            .map(|completion| completion.with_status(status2))
+    /// Iterates over the handles implementing a certain protocol.
+    ///
+    /// Unlike with `find_handles`, the protocol of each handle is only opened
+    /// when requested, so that the handles can be filtered first.
+    pub fn handles_with_protocol<P: Protocol>(&self) -> Result<HandlesWithProtocol<'_, P>> {
+        self.find_handles::<P>()
+            .map_inner(|handles| HandlesWithProtocol {
+                boot_services: self,
+                handles: handles.into_iter(),
+                _protocol: PhantomData,
+            })
     }
 
     /// Retrieves the `SimpleFileSystem` protocol associated with
@@ -659,7 +919,8 @@ impl BootServices {
             .expect("Failed to retrieve `LoadedImage` protocol from handle");
         let loaded_image = unsafe { &*loaded_image.get() };
 
-        let device_handle = loaded_image.device();
+        // Images loaded from a memory buffer have no device.
+        let device_handle = loaded_image.device().ok_or(Status::UNSUPPORTED)?;
 
         let device_path = self
             .handle_protocol::<DevicePath>(device_handle)?
@@ -672,6 +933,129 @@ impl BootServices {
 
         self.handle_protocol::<SimpleFileSystem>(device_handle)
     }
+
+    /// Opens the root directory of the file system the given image was loaded
+    /// from.
+    ///
+    /// The `LoadedImage` protocol of the image and the protocols of its device
+    /// are opened on behalf of the image, and closed again before returning,
+    /// whether the volume could be opened or not. The returned directory stays
+    /// valid until it is dropped.
+    ///
+    /// # Errors
+    /// * `uefi::Status::UNSUPPORTED` - The image was loaded from a memory buffer, and has no
+    ///   device.
+    /// * `uefi::Status::NOT_FOUND` - The device of the image has no file system.
+    /// * The errors of `SimpleFileSystem::open_volume`.
+    pub fn open_image_volume(&self, image_handle: Handle) -> Result<Directory> {
+        let params = |handle| OpenProtocolParams {
+            handle,
+            agent: image_handle,
+            controller: None,
+        };
+        let attributes = OpenProtocolAttributes::GetProtocol;
+
+        let loaded_image = self
+            .open_protocol::<LoadedImage>(params(image_handle), attributes)?
+            .log();
+        let device_handle = unsafe { &*loaded_image.get() }
+            .device()
+            .ok_or(Status::UNSUPPORTED)?;
+
+        let device_path = self
+            .open_protocol::<DevicePath>(params(device_handle), attributes)?
+            .log();
+        let fs_handle = self
+            .locate_device_path::<SimpleFileSystem>(unsafe { &mut *device_path.get() })?
+            .log();
+
+        let sfs = self
+            .open_protocol::<SimpleFileSystem>(params(fs_handle), attributes)?
+            .log();
+        unsafe { &mut *sfs.get() }.open_volume()
+    }
+
+    /// Retrieves a copy of the current memory map, with its key.
+    ///
+    /// The descriptors are copied out of the firmware's buffer, respecting
+    /// its descriptor size, into a vector of `MemoryDescriptor`s which can be
+    /// sorted and modified, for example with `merge_adjacent`. Nothing is
+    /// allocated after the memory map is retrieved, so the key stays valid
+    /// until the caller changes the memory map.
+    pub fn memory_map_snapshot(&self) -> Result<(MemoryMapKey, Vec<MemoryDescriptor>)> {
+        let descriptor_size = mem::size_of::<MemoryDescriptor>();
+        // The allocation of the buffer may add a few descriptors to the map,
+        // and the firmware's descriptors may be larger than ours.
+        let extra_size = 16 * descriptor_size;
+
+        let mut buffer = Vec::new();
+        let mut map_size = self.memory_map_size() + extra_size;
+        loop {
+            buffer.clear();
+            buffer.resize(map_size / descriptor_size + 1, MemoryDescriptor::default());
+
+            map_size = buffer.len() * descriptor_size;
+            let mut map_key = MemoryMapKey(0);
+            let mut entry_size = 0;
+            let mut entry_version = 0;
+            let status = unsafe {
+                (self.get_memory_map)(
+                    &mut map_size,
+                    buffer.as_mut_ptr(),
+                    &mut map_key,
+                    &mut entry_size,
+                    &mut entry_version,
+                )
+            };
+            if status == Status::BUFFER_TOO_SMALL {
+                map_size += extra_size;
+                continue;
+            } else if status.is_error() {
+                return Err(status.into());
+            }
+
+            // Move the descriptors next to each other. Each descriptor is
+            // read before it is overwritten, as ours are not larger.
+            assert!(entry_size >= descriptor_size);
+            let len = map_size / entry_size;
+            let base = buffer.as_mut_ptr();
+            for index in 0..len {
+                unsafe {
+                    let entry = (base as *const u8).add(index * entry_size);
+                    let descriptor = ptr::read_unaligned(entry as *const MemoryDescriptor);
+                    base.add(index).write(descriptor);
+                }
+            }
+            buffer.truncate(len);
+
+            return status.into_with_val(|| (map_key, buffer));
+        }
+    }
+
+    /// Reads a whole file from the file system the given image was loaded
+    /// from.
+    ///
+    /// The path is relative to the root of the file system, and is opened like
+    /// with `Directory::open_path`. All the files and protocols are closed
+    /// before returning, whether the file could be read or not. An empty file
+    /// gives an empty vector.
+    ///
+    /// # Errors
+    /// * `uefi::Status::NOT_FOUND` - The file, or one of the directories of its path, doesn't
+    ///   exist.
+    /// * `uefi::Status::INVALID_PARAMETER` - The path is invalid, or is the path of a directory.
+    /// * The errors of `open_image_volume`, `Directory::open_path` and
+    ///   `RegularFile::read_to_vec`, which include `DEVICE_ERROR` for I/O errors.
+    pub fn read_file(&self, image_handle: Handle, path: &str) -> Result<Vec<u8>> {
+        let mut root = self.open_image_volume(image_handle)?.log();
+        let file = root
+            .open_path(path, FileMode::Read, FileAttribute::empty())?
+            .log();
+        match file.into_type()?.log() {
+            FileType::Regular(mut file) => file.read_to_vec(),
+            FileType::Dir(_) => Err(Status::INVALID_PARAMETER.into()),
+        }
+    }
 }
 
 impl super::Table for BootServices {
@@ -725,9 +1109,9 @@ pub enum AllocateType {
     /// Allocate any possible pages.
     AnyPages,
     /// Allocate pages at any address below the given address.
-    MaxAddress(usize),
+    MaxAddress(PhysicalAddress),
     /// Allocate pages at the specified address.
-    Address(usize),
+    Address(PhysicalAddress),
 }
 
 newtype_enum! {
@@ -797,9 +1181,9 @@ pub struct MemoryDescriptor {
     /// Skip 4 bytes as UEFI declares items in structs should be naturally aligned
     padding: u32,
     /// Starting physical address.
-    pub phys_start: u64,
+    pub phys_start: PhysicalAddress,
     /// Starting virtual address.
-    pub virt_start: u64,
+    pub virt_start: VirtualAddress,
     /// Number of 4 KiB pages contained in this range.
     pub page_count: u64,
     /// The capability attributes of this memory range.
@@ -811,8 +1195,8 @@ impl Default for MemoryDescriptor {
         MemoryDescriptor {
             ty: MemoryType::RESERVED,
             padding: 0,
-            phys_start: 0,
-            virt_start: 0,
+            phys_start: PhysicalAddress::default(),
+            virt_start: VirtualAddress::default(),
             page_count: 0,
             att: MemoryAttribute::empty(),
         }
@@ -856,6 +1240,160 @@ bitflags! {
     }
 }
 
+/// Sorts the descriptors of a memory map by increasing physical address.
+pub fn sort_by_phys_start(map: &mut [MemoryDescriptor]) {
+    map.sort_unstable_by_key(|descriptor| descriptor.phys_start);
+}
+
+/// Returns the descriptor of the given type with the most pages, or the first
+/// of them if several descriptors have as many pages.
+///
+/// The map should be merged with `merge_adjacent` first, to find the largest
+/// contiguous range of memory of this type.
+///
+/// ```
+/// use uefi::table::boot::{find_largest, MemoryDescriptor, MemoryType};
+///
+/// let descriptor = |ty, page_count| {
+///     let mut descriptor = MemoryDescriptor::default();
+///     descriptor.ty = ty;
+///     descriptor.page_count = page_count;
+///     descriptor
+/// };
+/// let map = [
+///     descriptor(MemoryType::CONVENTIONAL, 16),
+///     descriptor(MemoryType::BOOT_SERVICES_DATA, 64),
+///     descriptor(MemoryType::CONVENTIONAL, 32),
+///     descriptor(MemoryType::CONVENTIONAL, 32),
+/// ];
+/// let largest = find_largest(&map, MemoryType::CONVENTIONAL).unwrap();
+/// assert!(core::ptr::eq(largest, &map[2]));
+/// assert!(find_largest(&map, MemoryType::LOADER_CODE).is_none());
+/// ```
+pub fn find_largest(map: &[MemoryDescriptor], ty: MemoryType) -> Option<&MemoryDescriptor> {
+    map.iter()
+        .filter(|descriptor| descriptor.ty == ty)
+        .fold(None, |largest, descriptor| match largest {
+            Some(largest) if largest.page_count >= descriptor.page_count => Some(largest),
+            _ => Some(descriptor),
+        })
+}
+
+/// Two descriptors of a memory map whose ranges overlap, which is reported
+/// by `merge_adjacent`.
+///
+/// Memory maps should never contain overlapping ranges, but some buggy
+/// firmware produce them.
+#[derive(Debug, Copy, Clone)]
+pub struct MemoryMapOverlap {
+    /// The descriptor with the lowest physical address.
+    pub first: MemoryDescriptor,
+    /// The descriptor starting within the range of `first`.
+    pub second: MemoryDescriptor,
+}
+
+/// Sorts a memory map by increasing physical address, and merges the
+/// descriptors of contiguous ranges.
+///
+/// Two descriptors are merged if they have the same type and the same
+/// attributes, and if the range of the first one ends where the range of the
+/// second one starts, both in physical memory and in virtual memory. Virtual
+/// addresses are ignored if both are zero, which is the case until
+/// `RuntimeServices::set_virtual_address_map` is called.
+///
+/// If two ranges overlap, the map is only sorted, and the first overlapping
+/// pair is returned.
+///
+/// ```
+/// use uefi::data_types::PhysicalAddress;
+/// use uefi::table::boot::{merge_adjacent, MemoryAttribute, MemoryDescriptor, MemoryType};
+///
+/// let descriptor = |ty, start, page_count, att| {
+///     let mut descriptor = MemoryDescriptor::default();
+///     descriptor.ty = ty;
+///     descriptor.phys_start = PhysicalAddress::new(start);
+///     descriptor.page_count = page_count;
+///     descriptor.att = att;
+///     descriptor
+/// };
+/// let wb = MemoryAttribute::WRITE_BACK;
+/// let uc = MemoryAttribute::UNCACHEABLE;
+///
+/// let mut map = vec![
+///     descriptor(MemoryType::CONVENTIONAL, 0x3000, 1, wb),
+///     descriptor(MemoryType::CONVENTIONAL, 0x0000, 2, wb),
+///     descriptor(MemoryType::CONVENTIONAL, 0x2000, 1, wb),
+///     // The attributes differ.
+///     descriptor(MemoryType::CONVENTIONAL, 0x4000, 1, uc),
+///     // The type differs.
+///     descriptor(MemoryType::LOADER_DATA, 0x5000, 1, uc),
+///     // There is a hole before this range.
+///     descriptor(MemoryType::LOADER_DATA, 0x8000, 1, uc),
+/// ];
+/// merge_adjacent(&mut map).unwrap();
+/// let ranges: Vec<_> = map
+///     .iter()
+///     .map(|d| (d.phys_start.as_u64(), d.page_count))
+///     .collect();
+/// assert_eq!(ranges, [(0, 4), (0x4000, 1), (0x5000, 1), (0x8000, 1)]);
+///
+/// // Overlapping ranges are reported, and not merged.
+/// let mut map = vec![
+///     descriptor(MemoryType::CONVENTIONAL, 0x2000, 2, wb),
+///     descriptor(MemoryType::CONVENTIONAL, 0x0000, 3, wb),
+///     descriptor(MemoryType::CONVENTIONAL, 0x4000, 1, wb),
+/// ];
+/// let overlap = merge_adjacent(&mut map).unwrap_err();
+/// assert_eq!(overlap.first.phys_start.as_u64(), 0);
+/// assert_eq!(overlap.second.phys_start.as_u64(), 0x2000);
+/// assert_eq!(map.len(), 3);
+/// assert_eq!(map[0].phys_start.as_u64(), 0);
+///
+/// // A range reaching the end of the address space overlaps any range after it.
+/// let mut map = vec![
+///     descriptor(MemoryType::RESERVED, u64::MAX - 0xfff, 1, uc),
+///     descriptor(MemoryType::RESERVED, u64::MAX - 0x1fff, 2, uc),
+/// ];
+/// assert!(merge_adjacent(&mut map).is_err());
+/// ```
+#[cfg(feature = "exts")]
+pub fn merge_adjacent(
+    map: &mut Vec<MemoryDescriptor>,
+) -> core::result::Result<(), MemoryMapOverlap> {
+    sort_by_phys_start(map);
+
+    for pair in map.windows(2) {
+        // A range without an end reaches the end of the address space.
+        let overlaps = match pair[0].phys_start.checked_add_pages(pair[0].page_count) {
+            Some(end) => end > pair[1].phys_start,
+            None => true,
+        };
+        if overlaps {
+            return Err(MemoryMapOverlap {
+                first: pair[0],
+                second: pair[1],
+            });
+        }
+    }
+
+    map.dedup_by(|next, previous| {
+        let physically_contiguous =
+            previous.phys_start.checked_add_pages(previous.page_count) == Some(next.phys_start);
+        let virtually_contiguous = (previous.virt_start.as_u64() == 0
+            && next.virt_start.as_u64() == 0)
+            || previous.virt_start.checked_add_pages(previous.page_count) == Some(next.virt_start);
+        let mergeable = previous.ty == next.ty
+            && previous.att == next.att
+            && physically_contiguous
+            && virtually_contiguous;
+        if mergeable {
+            previous.page_count += next.page_count;
+        }
+        mergeable
+    });
+    Ok(())
+}
+
 /// A unique identifier of a memory map.
 ///
 /// If the memory map changes, this value is no longer valid.
@@ -865,13 +1403,31 @@ pub struct MemoryMapKey(usize);
 
 /// An iterator of memory descriptors
 #[derive(Debug, Clone)]
-struct MemoryMapIter<'buf> {
+pub(crate) struct MemoryMapIter<'buf> {
     buffer: &'buf [u8],
     entry_size: usize,
     index: usize,
     len: usize,
 }
 
+impl<'buf> MemoryMapIter<'buf> {
+    /// Iterates over `len` descriptors stored in `buffer`, which are
+    /// `entry_size` bytes apart from each other.
+    ///
+    /// The firmware's descriptor size may be larger than
+    /// `size_of::<MemoryDescriptor>()`, so the stride must be respected.
+    pub(crate) fn new(buffer: &'buf [u8], entry_size: usize, len: usize) -> Self {
+        assert!(entry_size >= mem::size_of::<MemoryDescriptor>());
+        assert!(buffer.len() >= entry_size * len);
+        MemoryMapIter {
+            buffer,
+            entry_size,
+            index: 0,
+            len,
+        }
+    }
+}
+
 impl<'buf> Iterator for MemoryMapIter<'buf> {
     type Item = &'buf MemoryDescriptor;
 
@@ -968,6 +1524,129 @@ pub enum TimerTrigger {
     Relative(u64),
 }
 
+/// Handles used when opening a protocol with `BootServices::open_protocol`.
+#[derive(Debug, Clone, Copy)]
+pub struct OpenProtocolParams {
+    /// The handle on which the protocol is installed.
+    pub handle: Handle,
+    /// The handle of the agent opening the protocol, which is the image handle
+    /// of the caller for applications and the driver binding handle for
+    /// drivers.
+    pub agent: Handle,
+    /// The controller handle, if the protocol is opened by a driver managing
+    /// that controller.
+    pub controller: Option<Handle>,
+}
+
+/// How a protocol is opened by `BootServices::open_protocol`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum OpenProtocolAttributes {
+    /// Open the protocol like `handle_protocol` does, without preventing other
+    /// agents from using it.
+    GetProtocol = 0x02,
+    /// Used by bus drivers to record that a child controller uses the
+    /// protocol of its parent.
+    ByChildController = 0x08,
+    /// Used by drivers to gain access to the protocol. Fails if another driver
+    /// is already using it.
+    ByDriver = 0x10,
+    /// Gain exclusive access to the protocol, disconnecting any driver which
+    /// is using it.
+    Exclusive = 0x20,
+    /// Combination of `ByDriver` and `Exclusive`.
+    ByDriverExclusive = 0x30,
+}
+
+/// A protocol interface opened by `BootServices::open_protocol`.
+///
+/// The interface is closed when this is dropped. Like with `handle_protocol`,
+/// the interface is wrapped in an `UnsafeCell`, as the firmware provides no
+/// protection against aliasing mutable accesses.
+pub struct ScopedProtocol<'a, P: Protocol> {
+    interface: &'a UnsafeCell<P>,
+    params: OpenProtocolParams,
+    boot_services: &'a BootServices,
+}
+
+impl<'a, P: Protocol> ScopedProtocol<'a, P> {
+    /// Returns the handle on which the protocol was opened.
+    pub fn handle(&self) -> Handle {
+        self.params.handle
+    }
+}
+
+impl<'a, P: Protocol> core::ops::Deref for ScopedProtocol<'a, P> {
+    type Target = UnsafeCell<P>;
+
+    fn deref(&self) -> &UnsafeCell<P> {
+        self.interface
+    }
+}
+
+impl<'a, P: Protocol> Drop for ScopedProtocol<'a, P> {
+    fn drop(&mut self) {
+        // Ignore the result, we can't do anything about an error here.
+        let _ = (self.boot_services.close_protocol)(
+            self.params.handle,
+            &P::GUID,
+            self.params.agent,
+            self.params.controller,
+        );
+    }
+}
+
+/// Iterator over the handles implementing a protocol, returned by
+/// `BootServices::handles_with_protocol`.
+#[cfg(feature = "exts")]
+pub struct HandlesWithProtocol<'a, P: Protocol> {
+    boot_services: &'a BootServices,
+    handles: alloc_api::vec::IntoIter<Handle>,
+    _protocol: PhantomData<P>,
+}
+
+#[cfg(feature = "exts")]
+impl<'a, P: Protocol> Iterator for HandlesWithProtocol<'a, P> {
+    type Item = HandleWithProtocol<'a, P>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let handle = self.handles.next()?;
+        Some(HandleWithProtocol {
+            boot_services: self.boot_services,
+            handle,
+            _protocol: PhantomData,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.handles.size_hint()
+    }
+}
+
+/// A handle implementing a protocol, yielded by `HandlesWithProtocol`.
+#[cfg(feature = "exts")]
+pub struct HandleWithProtocol<'a, P: Protocol> {
+    boot_services: &'a BootServices,
+    handle: Handle,
+    _protocol: PhantomData<P>,
+}
+
+#[cfg(feature = "exts")]
+impl<'a, P: Protocol> HandleWithProtocol<'a, P> {
+    /// Returns the handle.
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    /// Opens the protocol of the handle, like `BootServices::handle_protocol`.
+    ///
+    /// This fails if the protocol was uninstalled since the handles were
+    /// listed.
+    pub fn protocol(&self) -> Result<&'a UnsafeCell<P>> {
+        self.boot_services.handle_protocol::<P>(self.handle)
+    }
+}
+
 /// Protocol interface [`Guids`][Guid] that are installed on a [`Handle`] as
 /// returned by [`BootServices::protocols_per_handle`].
 pub struct ProtocolsPerHandle<'a> {
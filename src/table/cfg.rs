@@ -7,11 +7,12 @@
 //! This module contains the actual entries of the configuration table,
 //! as well as GUIDs for many known vendor tables.
 
-#![allow(clippy::unreadable_literal)]
-
-use crate::Guid;
+use super::boot::{MemoryDescriptor, MemoryMapIter, MemoryType};
+use crate::data_types::PAGE_SIZE;
+use crate::{Guid, Result, Status};
 use bitflags::bitflags;
 use core::ffi::c_void;
+use core::{mem, slice};
 
 /// Contains a set of GUID / pointer for a vendor-specific table.
 ///
@@ -28,52 +29,22 @@ pub struct ConfigTableEntry {
 }
 
 /// Entry pointing to the old ACPI 1 RSDP.
-pub const ACPI_GUID: Guid = Guid::from_values(
-    0xeb9d2d30,
-    0x2d88,
-    0x11d3,
-    0x9a16,
-    [0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
-);
+pub const ACPI_GUID: Guid = guid!("eb9d2d30-2d88-11d3-9a16-0090273fc14d");
 
 ///Entry pointing to the ACPI 2 RSDP.
-pub const ACPI2_GUID: Guid = Guid::from_values(
-    0x8868e871,
-    0xe4f1,
-    0x11d3,
-    0xbc22,
-    [0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81],
-);
+pub const ACPI2_GUID: Guid = guid!("8868e871-e4f1-11d3-bc22-0080c73c8881");
 
 /// Entry pointing to the SMBIOS 1.0 table.
-pub const SMBIOS_GUID: Guid = Guid::from_values(
-    0xeb9d2d31,
-    0x2d88,
-    0x11d3,
-    0x9a16,
-    [0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
-);
+pub const SMBIOS_GUID: Guid = guid!("eb9d2d31-2d88-11d3-9a16-0090273fc14d");
 
 /// Entry pointing to the SMBIOS 3.0 table.
-pub const SMBIOS3_GUID: Guid = Guid::from_values(
-    0xf2fd1544,
-    0x9794,
-    0x4a2c,
-    0x992e,
-    [0xe5, 0xbb, 0xcf, 0x20, 0xe3, 0x94],
-);
+pub const SMBIOS3_GUID: Guid = guid!("f2fd1544-9794-4a2c-992e-e5bbcf20e394");
 
 /// GUID of the UEFI properties table.
 ///
 /// The properties table is used to provide additional info
 /// about the UEFI implementation.
-pub const PROPERTIES_TABLE_GUID: Guid = Guid::from_values(
-    0x880aaca3,
-    0x4adc,
-    0x4a04,
-    0x9079,
-    [0xb7, 0x47, 0x34, 0x08, 0x25, 0xe5],
-);
+pub const PROPERTIES_TABLE_GUID: Guid = guid!("880aaca3-4adc-4a04-9079-b747340825e5");
 
 /// This table contains additional information about the UEFI implementation.
 #[repr(C)]
@@ -99,68 +70,166 @@ bitflags! {
     }
 }
 
+/// GUID of the UEFI memory attributes table.
+///
+/// This table describes the memory protection attributes (read-only and
+/// execute-protect) applied to the runtime services code and data regions.
+pub const MEMORY_ATTRIBUTES_GUID: Guid = guid!("dcfa911d-26eb-469f-a220-38b7dc461220");
+
+/// Header of the UEFI memory attributes table.
+///
+/// It is immediately followed in memory by `number_of_entries` memory
+/// descriptors, each of which is `descriptor_size` bytes long. These describe
+/// the runtime services code and data regions, split according to the
+/// protections which can be applied to them (`READ_ONLY`, `EXECUTE_PROTECT`).
+#[repr(C)]
+pub struct MemoryAttributesTable {
+    /// Version of the memory attributes table.
+    ///
+    /// The only version currently supported by this crate is `1`.
+    pub version: u32,
+    /// Number of memory descriptors following the header.
+    pub number_of_entries: u32,
+    /// Size in bytes of each memory descriptor.
+    ///
+    /// This might be larger than `size_of::<MemoryDescriptor>()`.
+    pub descriptor_size: u32,
+    /// Reserved, must be zero.
+    pub reserved: u32,
+}
+
+impl MemoryAttributesTable {
+    /// Version of the table layout described by this structure.
+    pub const VERSION: u32 = 1;
+
+    /// Returns an iterator over the memory descriptors of this table.
+    ///
+    /// Tables with a version other than `MemoryAttributesTable::VERSION`
+    /// (or with descriptors smaller than a `MemoryDescriptor`) are rejected
+    /// with `Status::INCOMPATIBLE_VERSION`, since their layout cannot be
+    /// assumed to match.
+    ///
+    /// # Safety
+    ///
+    /// This structure must be the header of a memory attributes table which
+    /// was published by the firmware, e.g. found in the configuration table
+    /// with `MEMORY_ATTRIBUTES_GUID`, so that its entries follow it in memory.
+    pub unsafe fn entries(
+        &self,
+    ) -> Result<impl ExactSizeIterator<Item = &MemoryDescriptor> + Clone> {
+        if self.version != Self::VERSION
+            || (self.descriptor_size as usize) < mem::size_of::<MemoryDescriptor>()
+        {
+            return Err(Status::INCOMPATIBLE_VERSION.into());
+        }
+
+        let entry_size = self.descriptor_size as usize;
+        let len = self.number_of_entries as usize;
+        let start = (self as *const Self).add(1) as *const u8;
+        let buffer = slice::from_raw_parts(start, entry_size * len);
+
+        Ok(MemoryMapIter::new(buffer, entry_size, len).into())
+    }
+
+    /// Returns the protected runtime services code and data regions which lie
+    /// within the runtime memory ranges of the given memory map.
+    ///
+    /// The memory map should be the final one, i.e. the one which was used to
+    /// exit boot services. Each yielded descriptor has the type of the range
+    /// it belongs to, and the `READ_ONLY` / `EXECUTE_PROTECT` attributes which
+    /// should be applied to it when mapping it.
+    ///
+    /// Malformed descriptors, whose range does not fit in the physical address
+    /// space, are skipped, and never contain an entry.
+    ///
+    /// # Safety
+    ///
+    /// This function has the same requirements as `entries`.
+    pub unsafe fn runtime_ranges<'a, I>(
+        &'a self,
+        memory_map: I,
+    ) -> Result<impl Iterator<Item = &'a MemoryDescriptor> + 'a>
+    where
+        I: Iterator<Item = &'a MemoryDescriptor> + Clone + 'a,
+    {
+        let entries = self.entries()?.log();
+        Ok(entries
+            .filter(move |entry| {
+                let is_runtime = entry.ty == MemoryType::RUNTIME_SERVICES_CODE
+                    || entry.ty == MemoryType::RUNTIME_SERVICES_DATA;
+                let (start, end) = match range(entry) {
+                    Some(range) => range,
+                    None => return false,
+                };
+                is_runtime
+                    && memory_map.clone().any(|region| match range(region) {
+                        Some((region_start, region_end)) => {
+                            region.ty == entry.ty && region_start <= start && end <= region_end
+                        }
+                        None => false,
+                    })
+            })
+            .into())
+    }
+}
+
+/// Returns the start and end addresses of the memory described by `desc`, or
+/// `None` if its end overflows.
+fn range(desc: &MemoryDescriptor) -> Option<(u64, u64)> {
+    let start = desc.phys_start.as_u64();
+    let end = desc
+        .page_count
+        .checked_mul(PAGE_SIZE)
+        .and_then(|size| start.checked_add(size))?;
+    Some((start, end))
+}
+
 /// Hand-off Blocks are used to pass data from the early pre-UEFI environment to the UEFI drivers.
 ///
 /// Most OS loaders or applications should not mess with this.
-pub const HAND_OFF_BLOCK_LIST_GUID: Guid = Guid::from_values(
-    0x7739f24c,
-    0x93d7,
-    0x11d4,
-    0x9a3a,
-    [0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
-);
+pub const HAND_OFF_BLOCK_LIST_GUID: Guid = guid!("7739f24c-93d7-11d4-9a3a-0090273fc14d");
 
 /// Table used in the early boot environment to record memory ranges.
-pub const MEMORY_TYPE_INFORMATION_GUID: Guid = Guid::from_values(
-    0x4c19049f,
-    0x4137,
-    0x4dd3,
-    0x9c10,
-    [0x8b, 0x97, 0xa8, 0x3f, 0xfd, 0xfa],
-);
+pub const MEMORY_TYPE_INFORMATION_GUID: Guid = guid!("4c19049f-4137-4dd3-9c10-8b97a83ffdfa");
 
 /// Used to identify Hand-off Blocks which store
 /// status codes reported during the pre-UEFI environment.
-pub const MEMORY_STATUS_CODE_RECORD_GUID: Guid = Guid::from_values(
-    0x60cc026,
-    0x4c0d,
-    0x4dda,
-    0x8f41,
-    [0x59, 0x5f, 0xef, 0x00, 0xa5, 0x02],
-);
+pub const MEMORY_STATUS_CODE_RECORD_GUID: Guid = guid!("060cc026-4c0d-4dda-8f41-595fef00a502");
 
 /// Table which provides Driver eXecution Environment services.
-pub const DXE_SERVICES_GUID: Guid = Guid::from_values(
-    0x5ad34ba,
-    0x6f02,
-    0x4214,
-    0x952e,
-    [0x4d, 0xa0, 0x39, 0x8e, 0x2b, 0xb9],
-);
+pub const DXE_SERVICES_GUID: Guid = guid!("05ad34ba-6f02-4214-952e-4da0398e2bb9");
 
 /// LZMA-compressed filesystem.
-pub const LZMA_COMPRESS_GUID: Guid = Guid::from_values(
-    0xee4e5898,
-    0x3914,
-    0x4259,
-    0x9d6e,
-    [0xdc, 0x7b, 0xd7, 0x94, 0x03, 0xcf],
-);
+pub const LZMA_COMPRESS_GUID: Guid = guid!("ee4e5898-3914-4259-9d6e-dc7bd79403cf");
 
 /// A custom compressed filesystem used by the Tiano UEFI implementation.
-pub const TIANO_COMPRESS_GUID: Guid = Guid::from_values(
-    0xa31280ad,
-    0x481e,
-    0x41b6,
-    0x95e8,
-    [0x12, 0x7f, 0x4c, 0x98, 0x47, 0x79],
-);
+pub const TIANO_COMPRESS_GUID: Guid = guid!("a31280ad-481e-41b6-95e8-127f4c984779");
 
 /// Pointer to the debug image info table.
-pub const DEBUG_IMAGE_INFO_GUID: Guid = Guid::from_values(
-    0x49152e77,
-    0x1ada,
-    0x4764,
-    0xb7a2,
-    [0x7a, 0xfe, 0xfe, 0xd9, 0x5e, 0x8b],
-);
+pub const DEBUG_IMAGE_INFO_GUID: Guid = guid!("49152e77-1ada-4764-b7a2-7afefed95e8b");
+
+/// Returns the name of a well-known configuration table, if `guid` is known.
+///
+/// This is mostly intended for diagnostic output.
+pub fn table_name(guid: &Guid) -> Option<&'static str> {
+    const KNOWN_TABLES: &[(Guid, &str)] = &[
+        (ACPI_GUID, "ACPI"),
+        (ACPI2_GUID, "ACPI 2.0"),
+        (SMBIOS_GUID, "SMBIOS"),
+        (SMBIOS3_GUID, "SMBIOS 3.0"),
+        (PROPERTIES_TABLE_GUID, "Properties"),
+        (MEMORY_ATTRIBUTES_GUID, "Memory attributes"),
+        (HAND_OFF_BLOCK_LIST_GUID, "Hand-off blocks"),
+        (MEMORY_TYPE_INFORMATION_GUID, "Memory type information"),
+        (MEMORY_STATUS_CODE_RECORD_GUID, "Memory status code records"),
+        (DXE_SERVICES_GUID, "DXE services"),
+        (LZMA_COMPRESS_GUID, "LZMA compression"),
+        (TIANO_COMPRESS_GUID, "Tiano compression"),
+        (DEBUG_IMAGE_INFO_GUID, "Debug image info"),
+    ];
+
+    KNOWN_TABLES
+        .iter()
+        .find(|(known, _)| known == guid)
+        .map(|(_, name)| *name)
+}
@@ -1,7 +1,7 @@
 use super::Revision;
+use core::fmt;
 
 /// All standard UEFI tables begin with a common header.
-#[derive(Debug)]
 #[repr(C)]
 pub struct Header {
     /// Unique identifier for this table.
@@ -16,3 +16,14 @@ pub struct Header {
     /// Reserved field that must be set to 0.
     _reserved: u32,
 }
+
+impl fmt::Debug for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Header")
+            .field("signature", &format_args!("{:#018x}", self.signature))
+            .field("revision", &self.revision)
+            .field("size", &self.size)
+            .field("crc", &format_args!("{:#010x}", self.crc))
+            .finish()
+    }
+}
@@ -3,8 +3,14 @@
 use super::Header;
 use crate::result::Error;
 use crate::table::boot::MemoryDescriptor;
+#[cfg(feature = "exts")]
+use crate::CString16;
 use crate::{CStr16, Char16, Guid, Result, Status};
+#[cfg(feature = "exts")]
+use alloc_api::{vec, vec::Vec};
 use bitflags::bitflags;
+#[cfg(feature = "exts")]
+use core::convert::TryFrom;
 use core::fmt;
 use core::mem::MaybeUninit;
 use core::ptr;
@@ -182,6 +188,153 @@ impl RuntimeServices {
     }
 }
 
+#[cfg(feature = "exts")]
+impl RuntimeServices {
+    /// Number of times `read_variable` tries to read a variable whose size
+    /// changes while it is read.
+    const READ_ATTEMPTS: usize = 4;
+
+    /// Returns the names and vendors of all the variables.
+    ///
+    /// The variables are listed in the order given by the firmware. Variables
+    /// whose name is not valid UCS-2 are skipped. If variables are created or
+    /// deleted while they are listed, some of them may be missed.
+    pub fn variable_names(&self) -> Result<Vec<(CString16, Guid)>> {
+        let mut names = Vec::new();
+        // The listing starts from an empty name, and each call returns the
+        // variable following the one in the buffer.
+        let mut name = vec![0u16; 64];
+        let mut vendor = Guid::from_values(0, 0, 0, 0, [0; 6]);
+        loop {
+            let mut name_size = name.len() * 2;
+            let status = unsafe {
+                (self.get_next_variable_name)(&mut name_size, name.as_mut_ptr(), &mut vendor)
+            };
+            match status {
+                Status::SUCCESS => {
+                    let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+                    let chars: core::result::Result<CString16, _> =
+                        name[..len].iter().map(|&c| Char16::try_from(c)).collect();
+                    if let Ok(chars) = chars {
+                        names.push((chars, vendor));
+                    }
+                }
+                // The current name must be kept in the larger buffer.
+                Status::BUFFER_TOO_SMALL => name.resize(name_size / 2 + 1, 0),
+                Status::NOT_FOUND => return Ok(names.into()),
+                status => return Err(status.into()),
+            }
+        }
+    }
+
+    /// Returns the contents and attributes of a variable.
+    ///
+    /// If the variable grows between the time its size is queried and the
+    /// time it is read, it is read again, and `BUFFER_TOO_SMALL` is returned
+    /// after a few attempts.
+    pub fn read_variable(
+        &self,
+        name: &CStr16,
+        vendor: &Guid,
+    ) -> Result<(Vec<u8>, VariableAttributes)> {
+        for _ in 0..Self::READ_ATTEMPTS {
+            let size = self.get_variable_size(name, vendor)?.log();
+            let mut data = vec![0; size];
+            match self.get_variable(name, vendor, &mut data) {
+                Ok(completion) => {
+                    let (status, (value, attributes)) = completion.split();
+                    let len = value.len();
+                    data.truncate(len);
+                    return status.into_with_val(|| (data, attributes));
+                }
+                Err(error) if error.status() == Status::BUFFER_TOO_SMALL => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        Err(Status::BUFFER_TOO_SMALL.into())
+    }
+
+    /// Get the size (in bytes) of a variable, whose name is converted to
+    /// UCS-2.
+    ///
+    /// Works like `get_variable_size`, but returns `INVALID_PARAMETER` if
+    /// the name cannot be represented as a `CString16`.
+    pub fn get_variable_size_str(&self, name: &str, vendor: &Guid) -> Result<usize> {
+        self.get_variable_size(&Self::variable_name(name)?, vendor)
+    }
+
+    /// Get the contents and attributes of a variable, whose name is converted
+    /// to UCS-2.
+    ///
+    /// Works like `get_variable`, but returns `INVALID_PARAMETER` if the name
+    /// cannot be represented as a `CString16`.
+    pub fn get_variable_str<'a>(
+        &self,
+        name: &str,
+        vendor: &Guid,
+        buf: &'a mut [u8],
+    ) -> Result<(&'a [u8], VariableAttributes)> {
+        self.get_variable(&Self::variable_name(name)?, vendor, buf)
+    }
+
+    /// Set the value of a variable, whose name is converted to UCS-2.
+    ///
+    /// Works like `set_variable`, but returns `INVALID_PARAMETER` if the name
+    /// cannot be represented as a `CString16`.
+    pub fn set_variable_str(
+        &self,
+        name: &str,
+        vendor: &Guid,
+        attributes: VariableAttributes,
+        data: &[u8],
+    ) -> Result {
+        self.set_variable(&Self::variable_name(name)?, vendor, attributes, data)
+    }
+
+    /// Returns the contents and attributes of a variable, whose name is
+    /// converted to UCS-2.
+    ///
+    /// Works like `read_variable`, but returns `INVALID_PARAMETER` if the
+    /// name cannot be represented as a `CString16`.
+    pub fn read_variable_str(
+        &self,
+        name: &str,
+        vendor: &Guid,
+    ) -> Result<(Vec<u8>, VariableAttributes)> {
+        self.read_variable(&Self::variable_name(name)?, vendor)
+    }
+
+    /// Converts the name of a variable to UCS-2.
+    fn variable_name(name: &str) -> core::result::Result<CString16, Error> {
+        CString16::try_from(name).map_err(|_| Status::INVALID_PARAMETER.into())
+    }
+
+    /// Returns the name, vendor, attributes and size of all the variables,
+    /// for diagnostic purposes.
+    ///
+    /// Each variable is read with `read_variable`. Variables which are
+    /// deleted while they are listed are skipped.
+    pub fn variables(&self) -> Result<Vec<VariableInfo>> {
+        let mut variables = Vec::new();
+        for (name, vendor) in self.variable_names()?.log() {
+            match self.read_variable(&name, &vendor) {
+                Ok(completion) => {
+                    let (data, attributes) = completion.log();
+                    variables.push(VariableInfo {
+                        name,
+                        vendor,
+                        attributes,
+                        size: data.len(),
+                    });
+                }
+                Err(error) if error.status() == Status::NOT_FOUND => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(variables.into())
+    }
+}
+
 impl super::Table for RuntimeServices {
     const SIGNATURE: u64 = 0x5652_4553_544e_5552;
 }
@@ -365,14 +518,22 @@ bitflags! {
     }
 }
 
+/// Description of a variable, returned by `RuntimeServices::variables`
+#[cfg(feature = "exts")]
+#[derive(Debug, Clone)]
+pub struct VariableInfo {
+    /// Name of the variable.
+    pub name: CString16,
+    /// Vendor of the variable.
+    pub vendor: Guid,
+    /// Attributes of the variable.
+    pub attributes: VariableAttributes,
+    /// Size of the contents of the variable, in bytes.
+    pub size: usize,
+}
+
 /// Vendor GUID used to access global variables.
-pub const GLOBAL_VARIABLE: Guid = Guid::from_values(
-    0x8be4df61,
-    0x93ca,
-    0x11d2,
-    0xaa0d,
-    [0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c],
-);
+pub const GLOBAL_VARIABLE: Guid = guid!("8be4df61-93ca-11d2-aa0d-00e098032b8c");
 
 /// The type of system reset.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
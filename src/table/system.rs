@@ -1,5 +1,5 @@
 use core::marker::PhantomData;
-use core::slice;
+use core::{fmt, slice};
 
 use crate::proto::console::text;
 use crate::{CStr16, Char16, Handle, Result, ResultExt, Status};
@@ -84,6 +84,15 @@ impl SystemTable<Boot> {
         unsafe { &mut *self.table.stdout.cast() }
     }
 
+    /// Returns a pointer to the standard output protocol, for code keeping
+    /// it for later, such as a logger.
+    ///
+    /// Unlike a pointer converted from the reference returned by `stdout`, it
+    /// stays valid when `stdout` is called again.
+    pub fn stdout_ptr(&self) -> *mut text::Output<'static> {
+        self.table.stdout
+    }
+
     /// Returns the standard error protocol.
     pub fn stderr(&mut self) -> &mut text::Output {
         unsafe { &mut *self.table.stderr.cast() }
@@ -126,6 +135,9 @@ impl SystemTable<Boot> {
     /// for the memory map right before exiting boot services, and to allocate a
     /// bit more storage than requested by memory_map_size.
     ///
+    /// With the `alloc` feature, the global allocator is disarmed when boot
+    /// services are exited, as by `uefi::alloc::exit_boot_services`.
+    ///
     /// If `exit_boot_services` succeeds, it will return a runtime view of the
     /// system table which more accurately reflects the state of the UEFI
     /// firmware following exit from boot services, along with a high-level
@@ -157,7 +169,14 @@ impl SystemTable<Boot> {
                     // If so, fetch another memory map and try again
                     continue;
                 } else {
-                    // If not, report the outcome of the operation
+                    // If not, disarm the allocator if boot services were
+                    // exited, and report the outcome of the operation
+                    #[cfg(feature = "alloc")]
+                    {
+                        if result.is_ok() {
+                            crate::alloc::exit_boot_services();
+                        }
+                    }
                     return result.map(|comp| {
                         let st = SystemTable {
                             table: self.table,
@@ -202,6 +221,48 @@ impl SystemTable<Runtime> {
     }
 }
 
+impl<View: SystemTableView> fmt::Debug for SystemTable<View> {
+    /// Summarizes the system table, for example for inclusion in bug reports.
+    ///
+    /// This does not allocate, so it can be used very early.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SystemTable")
+            .field("header", &self.table.header)
+            .field(
+                "firmware_vendor",
+                &format_args!("{}", self.firmware_vendor()),
+            )
+            .field("firmware_revision", &self.table.fw_revision)
+            .field("stdin_handle", &self.table.stdin_handle)
+            .field("stdout_handle", &self.table.stdout_handle)
+            .field("stderr_handle", &self.table.stderr_handle)
+            .field("config_table", &ConfigTableSummary(self.config_table()))
+            .finish()
+    }
+}
+
+/// Lists the configuration table entries, naming the ones which are known.
+struct ConfigTableSummary<'a>(&'a [cfg::ConfigTableEntry]);
+
+impl fmt::Debug for ConfigTableSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} entries ", self.0.len())?;
+        f.debug_list()
+            .entries(self.0.iter().map(ConfigTableEntrySummary))
+            .finish()
+    }
+}
+
+struct ConfigTableEntrySummary<'a>(&'a cfg::ConfigTableEntry);
+
+impl fmt::Debug for ConfigTableEntrySummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entry = self.0;
+        let name = cfg::table_name(&entry.guid).unwrap_or("unknown");
+        write!(f, "{} ({}) at {:?}", entry.guid, name, entry.address)
+    }
+}
+
 /// The actual UEFI system table
 #[repr(C)]
 struct SystemTableImpl {
@@ -3,9 +3,13 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::{quote, TokenStreamExt};
+use quote::{quote, ToTokens, TokenStreamExt};
 use syn::parse::{Parse, ParseStream};
-use syn::{parse_macro_input, DeriveInput, Generics, Ident, ItemFn, ItemType, LitStr};
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, DeriveInput, FnArg, Generics, Ident, ItemFn, ItemType, LitStr, Pat,
+    PatIdent, PatType, ReturnType, Token,
+};
 
 /// Parses a type definition, extracts its identifier and generic parameters
 struct TypeDefinition {
@@ -31,66 +35,87 @@ impl Parse for TypeDefinition {
     }
 }
 
+/// Parses a GUID in canonical textual format, such as
+/// "12345678-9abc-def0-fedc-ba9876543210", into its components
+fn parse_guid(guid_lit: &LitStr) -> syn::Result<(u32, u16, u16, u16, [u8; 6])> {
+    let guid_str = guid_lit.value();
+
+    if guid_str.len() != 36 {
+        return Err(syn::Error::new(
+            guid_lit.span(),
+            format!(
+                "\"{}\" is not a canonical GUID string (expected 36 bytes, found {})",
+                guid_str,
+                guid_str.len()
+            ),
+        ));
+    }
+
+    // The GUID string is composed of a 32-bit integer, three 16-bit ones, and a 48-bit one
+    let components: Vec<&str> = guid_str.split('-').collect();
+    let expected_bits = [32, 16, 16, 16, 48];
+    if components.len() != expected_bits.len() {
+        return Err(syn::Error::new(
+            guid_lit.span(),
+            format!(
+                "\"{}\" is not a canonical GUID string (expected 5 dash-separated components)",
+                guid_str
+            ),
+        ));
+    }
+
+    let mut values = [0u64; 5];
+    for ((value, component), bits) in values.iter_mut().zip(&components).zip(&expected_bits) {
+        // `from_str_radix` accepts a leading sign, so check the digits first
+        if component.len() != bits / 4 || !component.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(syn::Error::new(
+                guid_lit.span(),
+                format!(
+                    "GUID component \"{}\" is not a {}-bit hexadecimal number",
+                    component, bits
+                ),
+            ));
+        }
+        *value = u64::from_str_radix(component, 16).unwrap();
+    }
+
+    // Convert the node ID to an array of bytes to comply with Guid::from_values expectations
+    let node_bytes = values[4].to_be_bytes();
+    let mut node = [0; 6];
+    node.copy_from_slice(&node_bytes[2..]);
+
+    Ok((
+        values[0] as u32,
+        values[1] as u16,
+        values[2] as u16,
+        values[3] as u16,
+        node,
+    ))
+}
+
 /// `unsafe_guid` attribute macro, implements the `Identify` trait for any type
 /// (mostly works like a custom derive, but also supports type aliases)
 #[proc_macro_attribute]
 pub fn unsafe_guid(args: TokenStream, input: TokenStream) -> TokenStream {
     // Parse the arguments and input using Syn
-    let guid_str = parse_macro_input!(args as LitStr).value();
+    let guid_lit = parse_macro_input!(args as LitStr);
     let mut result: proc_macro2::TokenStream = input.clone().into();
     let type_definition = parse_macro_input!(input as TypeDefinition);
 
-    // We expect a canonical GUID string, such as "12345678-9abc-def0-fedc-ba9876543210"
-    if guid_str.len() != 36 {
-        panic!(
-            "\"{}\" is not a canonical GUID string (expected 36 bytes, found {})",
-            guid_str,
-            guid_str.len()
-        );
-    }
-    let mut guid_hex_iter = guid_str.split('-');
-    let mut next_guid_int = |expected_num_bits: usize| -> u64 {
-        let guid_hex_component = guid_hex_iter.next().unwrap();
-        if guid_hex_component.len() != expected_num_bits / 4 {
-            panic!(
-                "GUID component \"{}\" is not a {}-bit hexadecimal string",
-                guid_hex_component, expected_num_bits
-            );
-        }
-        match u64::from_str_radix(guid_hex_component, 16) {
-            Ok(number) => number,
-            _ => panic!(
-                "GUID component \"{}\" is not a hexadecimal number",
-                guid_hex_component
-            ),
-        }
-    };
-
-    // The GUID string is composed of a 32-bit integer, three 16-bit ones, and a 48-bit one
-    let time_low = next_guid_int(32) as u32;
-    let time_mid = next_guid_int(16) as u16;
-    let time_high_and_version = next_guid_int(16) as u16;
-    let clock_seq_and_variant = next_guid_int(16) as u16;
-    let node_64 = next_guid_int(48);
-
-    // Convert the node ID to an array of bytes to comply with Guid::from_values expectations
-    let node = [
-        (node_64 >> 40) as u8,
-        ((node_64 >> 32) % 0x100) as u8,
-        ((node_64 >> 24) % 0x100) as u8,
-        ((node_64 >> 16) % 0x100) as u8,
-        ((node_64 >> 8) % 0x100) as u8,
-        (node_64 % 0x100) as u8,
-    ];
+    let (time_low, time_mid, time_high_and_version, clock_seq_and_variant, node) =
+        match parse_guid(&guid_lit) {
+            Ok(components) => components,
+            Err(err) => return err.to_compile_error().into(),
+        };
 
     // At this point, we know everything we need to implement Identify
     let ident = type_definition.ident.clone();
     let (impl_generics, ty_generics, where_clause) = type_definition.generics.split_for_impl();
     result.append_all(quote! {
-        unsafe impl #impl_generics crate::Identify for #ident #ty_generics #where_clause {
+        unsafe impl #impl_generics ::uefi::Identify for #ident #ty_generics #where_clause {
             #[doc(hidden)]
             #[allow(clippy::unreadable_literal)]
-            const GUID : crate::Guid = crate::Guid::from_values(
+            const GUID: ::uefi::Guid = ::uefi::Guid::from_values(
                 #time_low,
                 #time_mid,
                 #time_high_and_version,
@@ -113,7 +138,7 @@ pub fn derive_protocol(item: TokenStream) -> TokenStream {
     let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
     let result = quote! {
         // Mark this as a `Protocol` implementation
-        impl #impl_generics crate::proto::Protocol for #ident #ty_generics #where_clause {}
+        impl #impl_generics ::uefi::proto::Protocol for #ident #ty_generics #where_clause {}
 
         // Most UEFI functions expect to be called on the bootstrap processor.
         impl #impl_generics !Send for #ident #ty_generics #where_clause {}
@@ -125,23 +150,140 @@ pub fn derive_protocol(item: TokenStream) -> TokenStream {
 }
 
 /// Custom attribute for a UEFI executable entrypoint
+///
+/// The function must have the signature
+/// `fn(Handle, SystemTable<Boot>) -> Status`, and may have any name: it is
+/// exported as `efi_main`, with the `efiapi` calling convention, which is
+/// what the UEFI targets expect. Functions which are `const`, `async`,
+/// `unsafe`, `extern` or generic are rejected with an error, and the types
+/// of the arguments are checked by the compiler.
+///
+/// With the `init` argument, as in `#[entry(init)]`, the function first
+/// initializes `uefi_services`, panicking if it fails. The crate must then
+/// depend on `uefi-services`.
 #[proc_macro_attribute]
 pub fn entry(args: TokenStream, input: TokenStream) -> TokenStream {
     // This code is inspired by the approach in this embedded Rust crate:
     // https://github.com/rust-embedded/cortex-m-rt/blob/965bf1e3291571e7e3b34834864117dc020fb391/macros/src/lib.rs#L85
 
-    if !args.is_empty() {
-        panic!("This attribute accepts no arguments");
+    let args = parse_macro_input!(args with Punctuated::<Ident, Token![,]>::parse_terminated);
+    let f = parse_macro_input!(input as ItemFn);
+    match expand_entry(&args, f) {
+        Ok(result) => result.into(),
+        Err(err) => err.to_compile_error().into(),
     }
+}
 
-    let f = parse_macro_input!(input as ItemFn);
+/// Checks the arguments and the signature of an entry point, and generates
+/// the exported function
+fn expand_entry(
+    args: &Punctuated<Ident, Token![,]>,
+    f: ItemFn,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut init = false;
+    for arg in args {
+        if arg == "init" && !init {
+            init = true;
+        } else {
+            return Err(syn::Error::new(
+                arg.span(),
+                format!("unexpected argument `{}`, the only argument is `init`", arg),
+            ));
+        }
+    }
 
-    let entry_fn_ident = &f.sig.ident;
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = f;
+    let invalid = |tokens: &dyn ToTokens, what: &str| {
+        Err(syn::Error::new_spanned(
+            tokens,
+            format!(
+                "the entry point must not be {}, its signature must be `fn(Handle, SystemTable<Boot>) -> Status`",
+                what
+            ),
+        ))
+    };
+    if let Some(constness) = &sig.constness {
+        return invalid(constness, "`const`");
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return invalid(asyncness, "`async`");
+    }
+    if let Some(unsafety) = &sig.unsafety {
+        return invalid(unsafety, "`unsafe`");
+    }
+    if let Some(abi) = &sig.abi {
+        return invalid(
+            abi,
+            "`extern`, as the calling convention is chosen by `#[entry]`",
+        );
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return invalid(&sig.generics, "generic");
+    }
+    if let Some(variadic) = &sig.variadic {
+        return invalid(variadic, "variadic");
+    }
+    if sig.inputs.len() != 2 {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            format!(
+                "the entry point must take 2 arguments, an image `Handle` and a `SystemTable<Boot>`, not {}",
+                sig.inputs.len()
+            ),
+        ));
+    }
+    if let Some(FnArg::Receiver(receiver)) = sig.inputs.first() {
+        return invalid(receiver, "a method");
+    }
+    if let ReturnType::Default = sig.output {
+        return Err(syn::Error::new_spanned(
+            &sig,
+            "the entry point must return a `Status`",
+        ));
+    }
 
-    let result = quote!(
-        static _UEFI_ENTRY_POINT_TYPE_CHECK: extern "efiapi" fn(uefi::Handle, uefi::table::SystemTable<uefi::table::Boot>) -> uefi::Status = #entry_fn_ident;
-        #[no_mangle]
-        pub extern "efiapi" #f
-    );
-    result.into()
+    let init = if init {
+        let st = match sig.inputs.iter().nth(1) {
+            Some(FnArg::Typed(PatType { pat, .. })) => match &**pat {
+                Pat::Ident(PatIdent { ident, .. }) => ident,
+                pat => {
+                    return Err(syn::Error::new_spanned(
+                        pat,
+                        "with `#[entry(init)]`, the system table argument must be a variable",
+                    ))
+                }
+            },
+            _ => unreachable!(),
+        };
+        quote! {
+            ::uefi::ResultExt::expect_success(
+                ::uefi_services::init(&mut unsafe { #st.unsafe_clone() }),
+                "Failed to initialize utilities",
+            );
+        }
+    } else {
+        quote!()
+    };
+
+    let ident = &sig.ident;
+    let inputs = &sig.inputs;
+    let output = &sig.output;
+    Ok(quote! {
+        static _UEFI_ENTRY_POINT_TYPE_CHECK: extern "efiapi" fn(
+            ::uefi::Handle,
+            ::uefi::table::SystemTable<::uefi::table::Boot>,
+        ) -> ::uefi::Status = #ident;
+
+        #(#attrs)*
+        #[export_name = "efi_main"]
+        #vis extern "efiapi" fn #ident(#inputs) #output {
+            #init
+            #block
+        }
+    })
 }
@@ -12,22 +12,36 @@
 #![no_std]
 #![feature(alloc_error_handler)]
 #![feature(asm)]
+#![feature(const_panic)]
 #![feature(lang_items)]
 #![feature(panic_info_message)]
 
 #[macro_use]
 extern crate log;
+extern crate alloc;
 // Core types.
 extern crate uefi;
 
+use alloc::string::String;
+use core::fmt::{self, Write};
 use core::ptr::NonNull;
+#[cfg(feature = "qemu")]
+use core::sync::atomic::AtomicU16;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use cfg_if::cfg_if;
 
+use log::LevelFilter;
+use uefi::data_types::ucs2;
+use uefi::logger::{LogBuffer, Logger, Sink};
 use uefi::prelude::*;
-use uefi::table::boot::{EventType, Tpl};
+use uefi::proto::debug::DebugPort;
+use uefi::proto::device_path::{DevicePath, DevicePathToText};
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::table::boot::{BootServices, EventType, Tpl};
+use uefi::table::runtime::RuntimeServices;
 use uefi::table::{Boot, SystemTable};
-use uefi::{Event, Result};
+use uefi::{guid, CStr16, Event, Guid, Result};
 
 /// Reference to the system table.
 ///
@@ -36,8 +50,18 @@ use uefi::{Event, Result};
 /// UEFI's ExitBootServices entry point for more details.
 static mut SYSTEM_TABLE: Option<SystemTable<Boot>> = None;
 
+/// Reference to the runtime services, which stay available after boot
+/// services are exited.
+static mut RUNTIME_SERVICES: Option<NonNull<RuntimeServices>> = None;
+
+/// Set when boot services are exited.
+static EXITED: AtomicBool = AtomicBool::new(false);
+
 /// Global logger object
-static mut LOGGER: Option<uefi::logger::Logger> = None;
+static mut LOGGER: Option<Logger> = None;
+
+/// Information about the image, gathered by `init_with_options`.
+static mut LAUNCH_INFO: Option<LaunchInfo> = None;
 
 /// Obtains a pointer to the system table.
 ///
@@ -49,149 +73,764 @@ static mut LOGGER: Option<uefi::logger::Logger> = None;
 /// The returned pointer is only valid until boot services are exited.
 pub fn system_table() -> NonNull<SystemTable<Boot>> {
     unsafe {
-        let table_ref = SYSTEM_TABLE
-            .as_ref()
-            .expect("The system table handle is not available");
+        let table_ref = SYSTEM_TABLE.as_ref().unwrap_or_else(|| {
+            if boot_services_exited() {
+                panic!("The system table handle is not available after exiting boot services")
+            } else {
+                panic!("The system table handle is not available before `uefi_services::init`")
+            }
+        });
         NonNull::new(table_ref as *const _ as *mut _).unwrap()
     }
 }
 
+/// Returns the boot services.
+///
+/// # Panics
+///
+/// Panics if `init` wasn't called, or if boot services were exited.
+pub fn boot_services() -> &'static BootServices {
+    match try_boot_services() {
+        Some(boot_services) => boot_services,
+        None if boot_services_exited() => {
+            panic!("Boot services are not available after exiting them")
+        }
+        None => panic!("Boot services are not available before `uefi_services::init`"),
+    }
+}
+
+/// Returns the boot services, or `None` if `init` wasn't called, or if boot
+/// services were exited.
+pub fn try_boot_services() -> Option<&'static BootServices> {
+    unsafe { SYSTEM_TABLE.as_ref() }.map(|st| st.boot_services())
+}
+
+/// Returns the runtime services, which can be used after boot services are
+/// exited, until the virtual address map is set.
+///
+/// # Panics
+///
+/// Panics if `init` wasn't called.
+pub fn runtime_services() -> &'static RuntimeServices {
+    let runtime_services = unsafe { RUNTIME_SERVICES }
+        .expect("Runtime services are not available before `uefi_services::init`");
+    unsafe { &*runtime_services.as_ptr() }
+}
+
+/// Returns whether boot services were exited, as notified by the event of
+/// the library or by `notify_exit_boot_services`.
+pub fn boot_services_exited() -> bool {
+    EXITED.load(Ordering::SeqCst)
+}
+
+/// Information about how the application was launched, to identify it in
+/// logs and crash reports
+///
+/// The information which couldn't be gathered is `None`.
+#[derive(Clone, Debug)]
+pub struct LaunchInfo {
+    /// The image handle of the application.
+    pub image: Handle,
+    /// The address at which the image was loaded.
+    pub image_base: Option<usize>,
+    /// The size of the loaded image, in bytes.
+    pub image_size: Option<u64>,
+    /// The handle of the device from which the image was loaded.
+    pub device: Option<Handle>,
+    /// The device path of the image file, as text, if the firmware provides
+    /// the device path to text protocol.
+    pub device_path: Option<String>,
+}
+
+impl fmt::Display for LaunchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.image_base {
+            Some(image_base) => write!(f, "Image loaded at {:#x}", image_base)?,
+            None => f.write_str("Image loaded at an unknown address")?,
+        }
+        if let Some(ref device_path) = self.device_path {
+            write!(f, " from {}", device_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the information about the launch of the application, gathered by
+/// `init_with_config` or `init_with_options`.
+///
+/// `None` is returned if the library wasn't initialized with one of these
+/// functions, as `init` doesn't know the image handle.
+pub fn launch_info() -> Option<&'static LaunchInfo> {
+    unsafe { LAUNCH_INFO.as_ref() }
+}
+
+/// Gathers the information returned by `launch_info`, ignoring the parts
+/// which can't be found.
+fn gather_launch_info(bt: &BootServices, image: Handle) -> LaunchInfo {
+    let loaded_image = bt
+        .handle_protocol::<LoadedImage>(image)
+        .ok()
+        .map(|loaded_image| unsafe { &*loaded_image.log().get() });
+    let (image_base, image_size) = match loaded_image.map(LoadedImage::info) {
+        Some((image_base, image_size)) => (Some(image_base), Some(image_size)),
+        None => (None, None),
+    };
+    let device = loaded_image.and_then(LoadedImage::device);
+    let device_path = loaded_image
+        .and_then(|loaded_image| device_path_text(bt, device, loaded_image.file_path()?));
+    LaunchInfo {
+        image,
+        image_base,
+        image_size,
+        device,
+        device_path,
+    }
+}
+
+/// Converts the device path of an image file to text: the path of its
+/// device, if any, followed by its path on the device.
+fn device_path_text(
+    bt: &BootServices,
+    device: Option<Handle>,
+    file_path: &DevicePath,
+) -> Option<String> {
+    let to_text = bt.locate_protocol::<DevicePathToText>().ok()?.log();
+    let to_text = unsafe { &*to_text.get() };
+    let mut text = String::new();
+    if let Some(device_path) =
+        device.and_then(|device| bt.handle_protocol::<DevicePath>(device).ok())
+    {
+        let device_path = unsafe { &*device_path.log().get() };
+        if let Ok(device_text) = to_text.convert_device_path_to_text(bt, device_path, true, true) {
+            let _ = write!(text, "{}/", &*device_text.log());
+        }
+    }
+    let file_text = to_text
+        .convert_device_path_to_text(bt, file_path, true, true)
+        .ok()?
+        .log();
+    let _ = write!(text, "{}", &*file_text);
+    Some(text)
+}
+
+/// Configuration of the logger set up by `init_with_config`
+///
+/// Each destination of the messages has its own level filter, and is disabled
+/// by `LevelFilter::Off`. By default, the messages of level `Info` and above
+/// are written to the console, colored by level, and nothing is written to the
+/// other destinations.
+#[derive(Clone, Copy, Debug)]
+pub struct LoggerConfig {
+    console: LevelFilter,
+    console_colors: bool,
+    serial: LevelFilter,
+    debug_port: LevelFilter,
+    timestamps: bool,
+}
+
+impl LoggerConfig {
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        LoggerConfig {
+            console: LevelFilter::Info,
+            console_colors: true,
+            serial: LevelFilter::Off,
+            debug_port: LevelFilter::Off,
+            timestamps: false,
+        }
+    }
+
+    /// Sets the level of the messages written to the console.
+    pub fn console(mut self, level: LevelFilter) -> Self {
+        self.console = level;
+        self
+    }
+
+    /// Sets whether the messages written to the console are colored by level,
+    /// which is the default.
+    pub fn console_colors(mut self, colors: bool) -> Self {
+        self.console_colors = colors;
+        self
+    }
+
+    /// Sets the level of the messages written to the serial device, which is
+    /// located when the first message is logged.
+    pub fn serial(mut self, level: LevelFilter) -> Self {
+        self.serial = level;
+        self
+    }
+
+    /// Sets the level of the messages written to the debug port, if there is
+    /// one when the library is initialized.
+    pub fn debug_port(mut self, level: LevelFilter) -> Self {
+        self.debug_port = level;
+        self
+    }
+
+    /// Sets whether the messages are prefixed with the time elapsed since
+    /// initialization and a sequence number, as described in
+    /// `Logger::enable_timestamps`. This is disabled by default, which saves
+    /// looking for a clock.
+    pub fn timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What to do with the watchdog timer of the firmware
+///
+/// The firmware arms the watchdog for 5 minutes when it starts an
+/// application, and resets the system if boot services are not exited in
+/// that time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Watchdog {
+    /// Leave the watchdog as it is.
+    Keep,
+    /// Disable the watchdog, which is the default.
+    Disable,
+    /// Re-arm the watchdog with this timeout, in seconds.
+    Timeout(usize),
+}
+
+/// Watchdog code logged by the firmware when the watchdog set by the library
+/// expires, the lower codes being reserved for the firmware.
+const WATCHDOG_CODE: u64 = 0x1_0000;
+
+/// Default I/O port of the `isa-debug-exit` device of QEMU.
+#[cfg(feature = "qemu")]
+pub const QEMU_EXIT_PORT: u16 = 0xf4;
+
+/// Code passed to `qemu_exit` when the program succeeded.
+#[cfg(feature = "qemu")]
+pub const QEMU_EXIT_SUCCESS: u8 = 1;
+
+/// Code passed to `qemu_exit` when the program failed without panicking.
+#[cfg(feature = "qemu")]
+pub const QEMU_EXIT_FAILURE: u8 = 2;
+
+/// Code passed to `qemu_exit` by the panic handler.
+#[cfg(feature = "qemu")]
+pub const QEMU_EXIT_PANIC: u8 = 3;
+
+/// I/O port of the `isa-debug-exit` device, set by `init_with_options`.
+#[cfg(feature = "qemu")]
+static QEMU_EXIT_IO_PORT: AtomicU16 = AtomicU16::new(QEMU_EXIT_PORT);
+
+/// Exits QEMU with `code`, through its `isa-debug-exit` device.
+///
+/// The device makes QEMU exit with the status `(code << 1) | 1`, so the
+/// `QEMU_EXIT_*` codes give the statuses 3, 5 and 7, which can't be mistaken
+/// for the statuses of QEMU itself.
+///
+/// The device is only available on x86_64. If QEMU doesn't exit, because of
+/// the architecture or because the device is missing, the system is shut
+/// down through the runtime services instead, with a success status if
+/// `code` is `QEMU_EXIT_SUCCESS`, and QEMU exits with the status 0.
+#[cfg(feature = "qemu")]
+pub fn qemu_exit(code: u8) -> ! {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        let port = QEMU_EXIT_IO_PORT.load(Ordering::Relaxed);
+        asm!("out dx, eax", in("dx") port, in("eax") u32::from(code), options(nomem, nostack));
+    }
+
+    let status = if code == QEMU_EXIT_SUCCESS {
+        Status::SUCCESS
+    } else {
+        Status::ABORTED
+    };
+    shutdown(status)
+}
+
+/// Options of `init_with_options`
+#[derive(Clone, Copy, Debug)]
+pub struct InitOptions {
+    logger: LoggerConfig,
+    watchdog: Watchdog,
+    #[cfg(feature = "qemu")]
+    qemu_exit_port: u16,
+}
+
+impl InitOptions {
+    /// Creates the default options, which are those of `init`.
+    pub fn new() -> Self {
+        InitOptions {
+            logger: LoggerConfig::new(),
+            watchdog: Watchdog::Disable,
+            #[cfg(feature = "qemu")]
+            qemu_exit_port: QEMU_EXIT_PORT,
+        }
+    }
+
+    /// Sets the configuration of the logger.
+    pub fn logger(mut self, logger: LoggerConfig) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Sets what to do with the watchdog timer.
+    pub fn watchdog(mut self, watchdog: Watchdog) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    /// Sets the I/O port of the `isa-debug-exit` device used by `qemu_exit`,
+    /// `QEMU_EXIT_PORT` by default.
+    #[cfg(feature = "qemu")]
+    pub fn qemu_exit_port(mut self, port: u16) -> Self {
+        self.qemu_exit_port = port;
+        self
+    }
+}
+
+impl Default for InitOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Name of the variable setting the log level, under `LOG_LEVEL_VENDOR`.
+///
+/// The variable contains the name of a level, such as `debug`, in ASCII.
+pub const LOG_LEVEL_VARIABLE: &str = "UefiRsLogLevel";
+
+/// Vendor GUID of the `LOG_LEVEL_VARIABLE` variable.
+pub const LOG_LEVEL_VENDOR: Guid = guid!("5f3a9c21-7b4e-4d8a-9e61-2c07a8d4b913");
+
+/// Token of the load options setting the log level, as in
+/// `UEFI_RS_LOG=debug`.
+const LOG_LEVEL_OPTION: &str = "UEFI_RS_LOG=";
+
 /// Initialize the UEFI utility library.
 ///
 /// This must be called as early as possible,
 /// before trying to use logging or memory allocation capabilities.
+///
+/// The log level can be changed without rebuilding the application, with the
+/// `LOG_LEVEL_VARIABLE` variable, whose value is one of `off`, `error`,
+/// `warn`, `info`, `debug` or `trace`. An invalid value sets the level to
+/// `info`, with a warning.
+///
+/// `ALREADY_STARTED` is returned if the library is already initialized, or
+/// if another logger is set, and `UNSUPPORTED` if boot services were exited.
+/// The library is left uninitialized when the logger can't be set, and
+/// initialization can then be tried again.
+///
+/// The watchdog timer is disabled, and a failure to do so is logged as a
+/// warning.
 pub fn init(st: &mut SystemTable<Boot>) -> Result {
+    unsafe { init_impl(None, st, &InitOptions::default()) }
+}
+
+/// Initialize the UEFI utility library, with a custom configuration of the
+/// logger.
+///
+/// Like `init`, this must be called as early as possible. Besides the
+/// `LOG_LEVEL_VARIABLE` variable, the log level can be set in the load
+/// options of `image`, for example in the command line `app.efi
+/// UEFI_RS_LOG=debug`, which takes precedence over the variable. The
+/// information returned by `launch_info` is also gathered from `image`.
+pub fn init_with_config(
+    image: Handle,
+    st: &mut SystemTable<Boot>,
+    config: &LoggerConfig,
+) -> Result {
+    init_with_options(image, st, &InitOptions::new().logger(*config))
+}
+
+/// Initialize the UEFI utility library, with custom options for the logger
+/// and the watchdog timer.
+///
+/// This is `init_with_config`, with the watchdog timer also configurable.
+pub fn init_with_options(
+    image: Handle,
+    st: &mut SystemTable<Boot>,
+    options: &InitOptions,
+) -> Result {
+    unsafe { init_impl(Some(image), st, options) }
+}
+
+/// Sets the watchdog timer of the firmware, as `init` does.
+///
+/// `init` must have been called first.
+pub fn set_watchdog(watchdog: Watchdog) -> Result {
+    let timeout = match watchdog {
+        Watchdog::Keep => return Status::SUCCESS.into(),
+        Watchdog::Disable => 0,
+        Watchdog::Timeout(timeout) => timeout,
+    };
+    boot_services().set_watchdog_timer(timeout, WATCHDOG_CODE, None)
+}
+
+/// Initializes the library, reading the log level from the load options of
+/// `image` if it is given.
+unsafe fn init_impl(
+    image: Option<Handle>,
+    st: &mut SystemTable<Boot>,
+    options: &InitOptions,
+) -> Result {
+    // Avoid double initialization.
+    if SYSTEM_TABLE.is_some() {
+        return Err(Status::ALREADY_STARTED.into());
+    }
+
+    // Setup memory allocation and logging
+    uefi::alloc::init(st.boot_services());
+    if !uefi::alloc::is_armed() {
+        // Boot services were exited, there is nothing to initialize.
+        return Err(Status::UNSUPPORTED.into());
+    }
+    init_logger(st, &options.logger)?.log();
+    let boot_services = st.boot_services();
+
+    // Setup the system table singleton
+    SYSTEM_TABLE = Some(st.unsafe_clone());
+    RUNTIME_SERVICES = NonNull::new(st.runtime_services() as *const _ as *mut _);
+
+    // Apply the log level set outside of the application, if any
+    let setting = image
+        .and_then(|image| load_options_log_level(boot_services, image))
+        .or_else(|| variable_log_level(st));
+    if let Some(setting) = setting {
+        match setting.parse::<LevelFilter>() {
+            Ok(level) => set_log_level(level),
+            Err(_) => {
+                set_log_level(LevelFilter::Info);
+                warn!("Invalid log level {:?}, using info", setting);
+            }
+        }
+    }
+
+    // Identify the image in the logs and in crash reports
+    if let Some(image) = image {
+        LAUNCH_INFO = Some(gather_launch_info(boot_services, image));
+    }
+
+    #[cfg(feature = "qemu")]
+    QEMU_EXIT_IO_PORT.store(options.qemu_exit_port, Ordering::Relaxed);
+
+    // Keep long-running applications alive, unless told otherwise
+    if let Err(error) = set_watchdog(options.watchdog) {
+        warn!("Failed to set the watchdog timer: {:?}", error.status());
+    }
+
+    // Schedule these tools to be disabled on exit from UEFI boot services
+    boot_services
+        .create_event(
+            EventType::SIGNAL_EXIT_BOOT_SERVICES,
+            Tpl::NOTIFY,
+            Some(exit_boot_services),
+        )
+        .map_inner(|_| ())
+}
+
+/// Sets the level of the messages written by the logger.
+///
+/// The level applies to every destination enabled when the library was
+/// initialized. `init` must have been called first.
+pub fn set_log_level(level: LevelFilter) {
     unsafe {
-        // Avoid double initialization.
-        if SYSTEM_TABLE.is_some() {
-            return Status::SUCCESS.into();
+        if let Some(ref logger) = LOGGER {
+            logger.set_level(level);
+            log::set_max_level(logger.max_level());
         }
+    }
+}
 
-        // Setup the system table singleton
-        SYSTEM_TABLE = Some(st.unsafe_clone());
+/// Also log to a ring buffer in memory.
+///
+/// Unlike the other destinations of the messages, the buffer keeps receiving
+/// them after the boot services are exited, so that they can be read later.
+/// `init` must have been called first. `OUT_OF_RESOURCES` is returned if the
+/// logger has no room for another destination.
+pub fn add_log_buffer(buffer: &'static LogBuffer) -> Result {
+    let logger = unsafe { LOGGER.as_ref() }.expect("The logger is not initialized");
+    logger
+        .add_sink(Sink::memory(buffer))
+        .map_err(|_| Status::OUT_OF_RESOURCES)?;
+    log::set_max_level(logger.max_level());
+    Status::SUCCESS.into()
+}
 
-        // Setup logging and memory allocation
-        init_logger(st);
-        let boot_services = st.boot_services();
-        uefi::alloc::init(boot_services);
+/// Logs the statistics of the allocator, at the info level.
+#[cfg(feature = "alloc-stats")]
+pub fn log_alloc_stats() {
+    let stats = uefi::alloc::stats();
+    info!(
+        "Allocations: {} live ({} bytes), {} bytes at peak, {} in total",
+        stats.live_allocations, stats.live_bytes, stats.peak_bytes, stats.total_allocations
+    );
+}
+
+/// Returns the value of the `UEFI_RS_LOG` token of the load options of
+/// `image`, if any.
+fn load_options_log_level(bt: &BootServices, image: Handle) -> Option<String> {
+    let loaded_image = bt.handle_protocol::<LoadedImage>(image).ok()?.log();
+    let options = unsafe { &*loaded_image.get() }.load_options_as_bytes()?;
+    let options = options
+        .chunks_exact(2)
+        .map(|code| u16::from_le_bytes([code[0], code[1]]))
+        .take_while(|&code| code != 0);
+    let options = char::decode_utf16(options)
+        .collect::<core::result::Result<String, _>>()
+        .ok()?;
+    options
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix(LOG_LEVEL_OPTION))
+        .map(String::from)
+}
+
+/// Returns the value of the `LOG_LEVEL_VARIABLE` variable, if any.
+fn variable_log_level(st: &SystemTable<Boot>) -> Option<String> {
+    let mut name = [0; 32];
+    let length = ucs2::encode_str(LOG_LEVEL_VARIABLE, &mut name).ok()?.log();
+    let name = CStr16::from_u16_with_nul(&name[..length]).ok()?;
+    let mut buffer = [0; 16];
+    let value = match st
+        .runtime_services()
+        .get_variable(name, &LOG_LEVEL_VENDOR, &mut buffer)
+    {
+        Ok(completion) => completion.log().0,
+        Err(error) if error.status() == Status::NOT_FOUND => return None,
+        // The value is too long to be a level, or can't be read.
+        Err(_) => &[],
+    };
+    let value = String::from_utf8_lossy(value);
+    Some(String::from(value.trim_end_matches('\0').trim()))
+}
 
-        // Schedule these tools to be disabled on exit from UEFI boot services
-        boot_services
-            .create_event(
-                EventType::SIGNAL_EXIT_BOOT_SERVICES,
-                Tpl::NOTIFY,
-                Some(exit_boot_services),
-            )
-            .map_inner(|_| ())
+/// Also log to the serial device, which is located when the first message
+/// is logged.
+///
+/// This is useful when the console isn't captured, for example in virtual
+/// machines and on headless systems. `init` must have been called first, and
+/// the serial logger is disabled with the rest of the logger on exit from
+/// UEFI boot services.
+pub fn enable_serial_logging() {
+    let bt = boot_services();
+    unsafe {
+        if let Some(ref logger) = LOGGER {
+            logger.set_serial(uefi::logger::SerialLogger::new(bt));
+        }
     }
 }
 
 /// Set up logging
 ///
+/// `ALREADY_STARTED` is returned if another logger is set.
+///
 /// This is unsafe because you must arrange for the logger to be reset with
 /// disable() on exit from UEFI boot services.
-unsafe fn init_logger(st: &mut SystemTable<Boot>) {
-    let stdout = st.stdout();
-
+unsafe fn init_logger(st: &mut SystemTable<Boot>, config: &LoggerConfig) -> Result {
     // Construct the logger.
+    let logger = Logger::empty();
+    logger.set_boot_services(st.boot_services());
+    if config.console != LevelFilter::Off {
+        // The logger keeps its own pointer to the console, instead of one
+        // derived from the references returned by `stdout`.
+        let sink = Sink::console(st.stdout_ptr())
+            .level(config.console)
+            .colors(config.console_colors);
+        logger.add_sink(sink).ok().unwrap();
+    }
+    if config.serial != LevelFilter::Off {
+        let sink = Sink::serial(st.boot_services()).level(config.serial);
+        logger.add_sink(sink).ok().unwrap();
+    }
+    if config.debug_port != LevelFilter::Off {
+        if let Ok(debug_port) = st.boot_services().locate_protocol::<DebugPort>() {
+            let debug_port = &mut *debug_port.log().get();
+            let sink = Sink::debug_port(debug_port).level(config.debug_port);
+            logger.add_sink(sink).ok().unwrap();
+        }
+    }
+    if config.timestamps {
+        logger.enable_timestamps(st.boot_services());
+    }
     let logger = {
-        LOGGER = Some(uefi::logger::Logger::new(stdout));
+        LOGGER = Some(logger);
         LOGGER.as_ref().unwrap()
     };
 
     // Set the logger.
-    log::set_logger(logger).unwrap(); // Can only fail if already initialized.
+    if log::set_logger(logger).is_err() {
+        LOGGER = None;
+        return Err(Status::ALREADY_STARTED.into());
+    }
 
-    // Log everything.
-    log::set_max_level(log::LevelFilter::Info);
+    // Let through the messages of the most verbose sink.
+    log::set_max_level(logger.max_level());
+    Status::SUCCESS.into()
 }
 
 /// Notify the utility library that boot services are not safe to call anymore
-fn exit_boot_services(_e: Event) {
+fn exit_boot_services(_e: &Event) {
     // DEBUG: The UEFI spec does not guarantee that this printout will work, as
     //        the services used by logging might already have been shut down.
     //        But it works on current OVMF, and can be used as a handy way to
     //        check that the callback does get called.
     //
     // info!("Shutting down the UEFI utility library");
+    notify_exit_boot_services();
+}
+
+/// Notify the utility library that boot services are not safe to call anymore
+///
+/// This is done by an event when boot services are exited, and only needs to
+/// be called by applications which stop using boot services otherwise, for
+/// example before jumping to a kernel which exits them. Afterwards,
+/// `boot_services` panics, the allocator is disarmed, and only the memory
+/// sinks of the logger are written to.
+pub fn notify_exit_boot_services() {
+    EXITED.store(true, Ordering::SeqCst);
     unsafe {
         SYSTEM_TABLE = None;
-        if let Some(ref mut logger) = LOGGER {
+        // Only the memory sinks are written to from now on, including by the
+        // panic handler.
+        if let Some(ref logger) = LOGGER {
             logger.disable();
         }
     }
     uefi::alloc::exit_boot_services();
 }
 
+/// Set by the panic handler when it starts reporting a panic.
+#[cfg(not(feature = "no_panic_handler"))]
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Writes the location and message of a panic to the first serial device, or
+/// to the debug port if there is no serial device, so that it can be read
+/// even if the console and the logger don't work.
+///
+/// The protocols are located with `LocateProtocol`, which may be called up
+/// to `Tpl::NOTIFY`, and the message is formatted without allocating memory,
+/// so that it also works after an allocation failure. Nothing is written if
+/// boot services are not available, and errors of the devices are ignored.
+#[cfg(not(feature = "no_panic_handler"))]
+fn write_panic_to_devices(info: &core::panic::PanicInfo) {
+    use uefi::proto::console::serial::Serial;
+
+    let bt = match try_boot_services() {
+        Some(bt) => bt,
+        None => return,
+    };
+    let write = |writer: &mut dyn Write| {
+        let _ = match info.location() {
+            Some(location) => write!(
+                writer,
+                "Panic in {} at ({}, {}): ",
+                location.file(),
+                location.line(),
+                location.column()
+            ),
+            None => writer.write_str("Panic: "),
+        };
+        if let Some(message) = info.message() {
+            let _ = write!(writer, "{}", message);
+        }
+        let _ = writeln!(writer);
+        if let Some(launch_info) = launch_info() {
+            let _ = writeln!(writer, "{}", launch_info);
+        }
+    };
+
+    // Warnings are ignored, as logging them could fail in the same way as
+    // the panic.
+    if let Ok(serial) = bt.locate_protocol::<Serial>() {
+        write(unsafe { &mut *serial.ignore_warning().get() });
+    } else if let Ok(debug_port) = bt.locate_protocol::<DebugPort>() {
+        write(unsafe { &mut *debug_port.ignore_warning().get() });
+    }
+}
+
 #[lang = "eh_personality"]
 fn eh_personality() {}
 
+/// Logs the panic, and shuts down the system.
+///
+/// The panic is also written to the first serial device, or to the debug
+/// port, while boot services are available. Both reports include where the
+/// image was loaded, from `launch_info`, to help with symbolication. A panic in the panic handler
+/// shuts down the system without reporting it.
+///
+/// After boot services are exited, the message only reaches the memory sinks
+/// of the logger, and the system is shut down right away. With the `qemu`
+/// feature, QEMU is exited with `QEMU_EXIT_PANIC` instead.
 #[cfg(not(feature = "no_panic_handler"))]
 #[panic_handler]
 fn panic_handler(info: &core::panic::PanicInfo) -> ! {
-    if let Some(location) = info.location() {
-        error!(
-            "Panic in {} at ({}, {}):",
-            location.file(),
-            location.line(),
-            location.column()
-        );
-        if let Some(message) = info.message() {
-            error!("{}", message);
+    // A panic while reporting a panic goes straight to the shutdown, instead
+    // of recursing through the logger or the devices which failed.
+    if !PANICKING.swap(true, Ordering::SeqCst) {
+        if let Some(location) = info.location() {
+            error!(
+                "Panic in {} at ({}, {}):",
+                location.file(),
+                location.line(),
+                location.column()
+            );
+            if let Some(message) = info.message() {
+                error!("{}", message);
+            }
+        }
+        if let Some(launch_info) = launch_info() {
+            error!("{}", launch_info);
         }
+        write_panic_to_devices(info);
     }
 
-    // Give the user some time to read the message
-    if let Some(st) = unsafe { SYSTEM_TABLE.as_ref() } {
-        st.boot_services().stall(10_000_000);
-    } else {
-        let mut dummy = 0u64;
-        // FIXME: May need different counter values in debug & release builds
-        for i in 0..300_000_000 {
-            unsafe {
-                core::ptr::write_volatile(&mut dummy, i);
-            }
-        }
+    // Give the user some time to read the message. Without boot services,
+    // it was not written to the console anyway.
+    if let Some(bt) = try_boot_services() {
+        bt.stall(10_000_000);
     }
 
     cfg_if! {
-        if #[cfg(all(target_arch = "x86_64", feature = "qemu"))] {
-            // If running in QEMU, use the f4 exit port to signal the error and exit
-            use qemu_exit::QEMUExit;
-            let custom_exit_success = 3;
-            let qemu_exit_handle = qemu_exit::X86::new(0xF4, custom_exit_success);
-            qemu_exit_handle.exit_failure();
+        if #[cfg(feature = "qemu")] {
+            // If running in QEMU, tell the host that the program panicked
+            qemu_exit(QEMU_EXIT_PANIC)
         } else {
-            // If the system table is available, use UEFI's standard shutdown mechanism
-            if let Some(st) = unsafe { SYSTEM_TABLE.as_ref() } {
-                use uefi::table::runtime::ResetType;
-                st.runtime_services()
-                    .reset(ResetType::Shutdown, uefi::Status::ABORTED, None);
-            }
+            shutdown(Status::ABORTED)
+        }
+    }
+}
+
+/// Shuts down the system through the runtime services, with `status` as the
+/// reason, or halts the processor if this fails.
+///
+/// The runtime services still work after boot services are exited, as long
+/// as the virtual address map is not set.
+#[cfg(any(feature = "qemu", not(feature = "no_panic_handler")))]
+fn shutdown(status: Status) -> ! {
+    if let Some(rt) = unsafe { RUNTIME_SERVICES } {
+        use uefi::table::runtime::ResetType;
+        unsafe { rt.as_ref() }.reset(ResetType::Shutdown, status, None);
+    }
+
+    // If we don't have any shutdown mechanism handy, the best we can do is loop
+    error!("Could not shut down, please power off the system manually...");
+    halt()
+}
 
-            // If we don't have any shutdown mechanism handy, the best we can do is loop
-            error!("Could not shut down, please power off the system manually...");
-
-            cfg_if! {
-                if #[cfg(target_arch = "x86_64")] {
-                    loop {
-                        unsafe {
-                            // Try to at least keep CPU from running at 100%
-                            asm!("hlt", options(nomem, nostack));
-                        }
-                    }
-                } else if #[cfg(target_arch = "aarch64")] {
-                    loop {
-                        unsafe {
-                            // Try to at least keep CPU from running at 100%
-                            asm!("hlt 420", options(nomem, nostack));
-                        }
-                    }
-                } else {
-                    loop {
-                        // just run forever dammit how do you return never anyway
-                    }
-                }
+/// Stops the processor forever, waking it up as rarely as the architecture
+/// allows.
+#[cfg(any(feature = "qemu", not(feature = "no_panic_handler")))]
+fn halt() -> ! {
+    loop {
+        cfg_if! {
+            if #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] {
+                // Try to at least keep CPU from running at 100%
+                unsafe { asm!("hlt", options(nomem, nostack)) };
+            } else if #[cfg(any(target_arch = "arm", target_arch = "aarch64"))] {
+                unsafe { asm!("wfi", options(nomem, nostack)) };
+            } else {
+                core::hint::spin_loop();
             }
         }
     }
@@ -199,8 +838,17 @@ fn panic_handler(info: &core::panic::PanicInfo) -> ! {
 
 #[alloc_error_handler]
 fn out_of_memory(layout: ::core::alloc::Layout) -> ! {
-    panic!(
-        "Ran out of free memory while trying to allocate {:#?}",
-        layout
-    );
+    if uefi::alloc::is_armed() {
+        panic!(
+            "Ran out of free memory while trying to allocate {:#?}",
+            layout
+        );
+    } else if uefi::alloc::is_initialized() {
+        panic!("Tried to allocate {:?} after exiting boot services", layout);
+    } else {
+        panic!(
+            "Tried to allocate {:?} before initializing the allocator",
+            layout
+        );
+    }
 }
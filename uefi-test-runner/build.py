@@ -4,6 +4,8 @@
 
 import argparse
 import filecmp
+import functools
+import http.server
 import json
 import os
 from pathlib import Path
@@ -11,11 +13,24 @@ import re
 import shutil
 import subprocess as sp
 import sys
+import threading
 
 ## Configurable settings
 # Path to workspace directory (which contains the top-level `Cargo.toml`)
 WORKSPACE_DIR = Path(__file__).resolve().parents[1]
 
+# Exit statuses of QEMU for the codes of `uefi_services::qemu_exit`, which
+# are turned into `(code << 1) | 1` by the `isa-debug-exit` device
+QEMU_EXIT_STATUS = {
+    'success': 3,
+    'failure': 5,
+    'panic': 7,
+}
+
+# CRC32 of the pattern drawn by the graphics output protocol test, with the
+# colors in RGB order
+GOP_CHECKSUM = 0xa66132fa
+
 # Try changing these with command line flags, where possible
 SETTINGS = {
     # Architecture to build for
@@ -28,6 +43,13 @@ SETTINGS = {
     'config': 'debug',
     # Disables some tests which don't work in our CI setup
     'ci': False,
+    # Launch the test runner from the UEFI shell, instead of booting it
+    'shell': False,
+    # Panic after exiting boot services, expecting the panic handler to exit QEMU
+    'panic_after_exit': False,
+    # Start a child image which panics, expecting its panic handler to write
+    # the panic to the serial device and exit QEMU
+    'panic_in_child': False,
     # QEMU executable to use
     # Indexed by the `arch` setting
     'qemu_binary': {
@@ -65,6 +87,10 @@ def esp_dir():
     'Returns the directory where we will build the emulated UEFI system partition'
     return build_dir() / 'esp'
 
+def tftp_dir():
+    'Returns the directory served by the TFTP server of QEMU and the HTTP server'
+    return build_dir() / 'tftp'
+
 def run_tool(tool, *flags):
     'Runs cargo-<tool> with certain arguments.'
 
@@ -113,11 +139,37 @@ def build(*test_flags):
 
     arch = SETTINGS['arch']
     if arch == 'x86_64':
-        output_file = boot_dir / 'BootX64.efi'
+        boot_file = boot_dir / 'BootX64.efi'
     elif arch == 'aarch64':
-        output_file = boot_dir / 'BootAA64.efi'
-
-    shutil.copy2(built_file, output_file)
+        boot_file = boot_dir / 'BootAA64.efi'
+
+    startup_script = esp_dir() / 'startup.nsh'
+    if SETTINGS['shell']:
+        # Without a boot file, OVMF starts its built-in shell, which runs the
+        # startup script.
+        boot_file.unlink(missing_ok=True)
+        shutil.copy2(built_file, esp_dir() / 'uefi-test-runner.efi')
+        # The arguments are checked by the shell parameters test.
+        startup_script.write_text('fs0:\r\nuefi-test-runner.efi "two words" fs0:\\efi\r\n')
+    else:
+        startup_script.unlink(missing_ok=True)
+        shutil.copy2(built_file, boot_file)
+
+    # Create the directory tree used by the file system tests. Its content
+    # must match the files checked by the tests.
+    tree_dir = esp_dir() / 'test_tree'
+    (tree_dir / 'nested' / 'deeper').mkdir(parents=True, exist_ok=True)
+    (tree_dir / 'a.txt').write_text('a')
+    (tree_dir / 'empty.txt').write_text('')
+    (tree_dir / 'large.bin').write_bytes(bytes(i % 251 for i in range(3 << 20)))
+    (tree_dir / 'nested' / 'b.txt').write_text('bb')
+    (tree_dir / 'nested' / 'deeper' / 'c.efi').write_text('ccc')
+
+    # Create the file downloaded by the TFTP test. Its content must match the
+    # hash checked by the test.
+    tftp_dir().mkdir(parents=True, exist_ok=True)
+    test_file = bytes((i * 7 + 3) % 256 for i in range(20000))
+    (tftp_dir() / 'test.bin').write_bytes(test_file)
 
 def clippy():
     'Runs Clippy on all projects'
@@ -178,7 +230,12 @@ def run_qemu():
     'Runs the code in QEMU.'
 
     # Rebuild all the changes.
-    build('--features', 'qemu')
+    if SETTINGS['panic_after_exit']:
+        build('--features', 'panic-after-exit')
+    elif SETTINGS['panic_in_child']:
+        build('--features', 'panic-in-child')
+    else:
+        build('--features', 'qemu')
 
     ovmf_code, ovmf_vars = ovmf_files(find_ovmf())
 
@@ -212,8 +269,8 @@ def run_qemu():
         if not SETTINGS['ci']:
             # Enable acceleration if possible.
             qemu_flags.append('--enable-kvm')
-        else:
-            # Exit instead of rebooting
+        if SETTINGS['ci'] or SETTINGS['panic_after_exit'] or SETTINGS['panic_in_child']:
+            # Exit instead of rebooting, so that a triple fault is noticed
             qemu_flags.append('-no-reboot')
     elif arch == 'aarch64':
         qemu_flags.extend([
@@ -240,6 +297,19 @@ def run_qemu():
 
         # Map the QEMU monitor to a pair of named pipes
         '-qmp', f'pipe:{qemu_monitor_pipe}',
+
+        # Provide an entropy source, used by OVMF to implement the RNG protocol.
+        '-device', 'virtio-rng-pci',
+
+        # Provide a network interface, used to test the network protocols.
+        # QEMU's TFTP server is used to test the MTFTP4 protocol.
+        '-netdev', f'user,id=net0,tftp={tftp_dir()}',
+        '-device', 'virtio-net-pci,netdev=net0',
+
+        # Provide a USB controller with a tablet, used to test the USB I/O
+        # protocol.
+        '-device', 'qemu-xhci',
+        '-device', 'usb-tablet',
     ])
 
     # For now these only work on x86_64
@@ -270,6 +340,10 @@ def run_qemu():
     # This regex can be used to detect and strip ANSI escape codes when
     # analyzing the output of the test runner.
     ansi_escape = re.compile(r'(\x9B|\x1B\[)[0-?]*[ -/]*[@-~]')
+    # Regular expression to parse the timestamps of log messages
+    timestamp_regex = re.compile(r'\[ *(\d+\.\d{3}) (\d{3,}) +\w+ *\]: ')
+    # Regular expression matching a complete message of the timer logging test
+    interleave_regex = re.compile(r'^\[ INFO\]: +\S*misc\.rs@\d+: (INTERLEAVE TEST: [a-z]{26} \d+|TIMER TICK)$')
 
     # Setup named pipes as a communication channel with QEMU's monitor
     monitor_input_path = f'{qemu_monitor_pipe}.in'
@@ -277,8 +351,30 @@ def run_qemu():
     monitor_output_path = f'{qemu_monitor_pipe}.out'
     os.mkfifo(monitor_output_path)
 
+    # Serve the TFTP directory over HTTP as well, on a port which the VM can
+    # reach through the address of the host
+    handler = functools.partial(http.server.SimpleHTTPRequestHandler, directory=str(tftp_dir()))
+    http_server = http.server.ThreadingHTTPServer(('127.0.0.1', 8000), handler)
+    threading.Thread(target=http_server.serve_forever, daemon=True).start()
+
     # Start QEMU
     qemu = sp.Popen(cmd, stdin=sp.PIPE, stdout=sp.PIPE, universal_newlines=True)
+    # Whether the messages of the serial logger and log level tests were received
+    serial_logger_ok = False
+    log_level_ok = False
+    # Whether the large allocation of the allocator statistics test was logged
+    alloc_logged = False
+    # Checksum of the pattern drawn by the graphics output protocol test
+    gop_checksum = None
+    # Whether the panic handler of the child image wrote the panic to the
+    # serial device
+    child_panic_written = False
+    # Number of times the messages of the multi-sink logger test were received
+    multi_sink_counts = {'info': 0, 'debug': 0}
+    # Timestamps and sequence numbers of the messages of the timestamp test
+    timestamps = []
+    # Messages of the timer logging test mixed with other messages
+    interleaved = []
     try:
         # Connect to the QEMU monitor
         with open(monitor_input_path, mode='w') as monitor_input,                  \
@@ -301,6 +397,28 @@ def run_qemu():
                 # Print out the processed QEMU output for logging & inspection
                 print(stripped)
 
+                if stripped.endswith('SERIAL LOGGER: ok'):
+                    serial_logger_ok = True
+                if stripped.endswith('LOG LEVEL: debug'):
+                    log_level_ok = True
+                if 'Allocated Layout' in stripped and '3000' in stripped:
+                    alloc_logged = True
+                if 'GOP CHECKSUM: ' in stripped:
+                    gop_checksum = int(stripped.split('GOP CHECKSUM: ')[1], 16)
+                if stripped.startswith('Panic in ') and                            \
+                   ': Tried to allocate Layout' in stripped and                    \
+                   stripped.endswith(' after exiting boot services'):
+                    child_panic_written = True
+                timestamp = timestamp_regex.match(stripped)
+                if timestamp and 'TIMESTAMP TEST: ' in stripped:
+                    timestamps.append((float(timestamp[1]), int(timestamp[2])))
+                if 'INTERLEAVE TEST: ' in stripped or 'TIMER TICK' in stripped:
+                    if not interleave_regex.search(stripped):
+                        interleaved.append(stripped)
+                for level in multi_sink_counts:
+                    if stripped.endswith(f'MULTI SINK: {level}'):
+                        multi_sink_counts[level] += 1
+
                 # If the app requests a screenshot, take it
                 if stripped.startswith("SCREENSHOT: "):
                     reference_name = stripped[12:]
@@ -334,13 +452,58 @@ def run_qemu():
             qemu.kill()
             status = -1
 
+        http_server.shutdown()
+
         # Delete the monitor pipes
         os.remove(monitor_input_path)
         os.remove(monitor_output_path)
 
-        # Throw an exception if QEMU failed
-        if status != 0 and status != 3:
-            raise sp.CalledProcessError(cmd=cmd, returncode=status)
+        # A triple fault exits QEMU with status 0, and QEMU only exits with
+        # status 0 after a normal run on AArch64, which has no exit device.
+        if SETTINGS['panic_after_exit'] or SETTINGS['panic_in_child']:
+            expected = 'panic'
+        else:
+            expected = 'success'
+        allowed = [QEMU_EXIT_STATUS[expected]]
+        if expected == 'success' and SETTINGS['arch'] == 'aarch64':
+            allowed.append(0)
+        if status not in allowed:
+            reasons = [name for name, code in QEMU_EXIT_STATUS.items() if code == status]
+            reason = f' ({reasons[0]})' if reasons else ''
+            raise Exception(f'Expected QEMU to exit with the {expected} status {allowed[0]}, got {status}{reason}')
+
+        if SETTINGS['panic_in_child'] and not child_panic_written:
+            raise Exception('The panic of the child image was not written to the serial device')
+
+        if interleaved:
+            raise Exception(f'Interleaved log messages: {interleaved}')
+
+        if not log_level_ok:
+            raise Exception('The debug message of the log level test was not received')
+
+        if not alloc_logged:
+            raise Exception('The large allocation of the allocator statistics test was not logged')
+
+        # The serial tests are skipped on AArch64
+        if SETTINGS['arch'] == 'x86_64' and not serial_logger_ok:
+            raise Exception('The serial logger message was not received')
+
+        # The console is mirrored on the serial device, so the `Info` message
+        # written to both sinks is received twice, and the `Debug` message,
+        # filtered out of the console, once.
+        if SETTINGS['arch'] == 'x86_64' and multi_sink_counts != {'info': 2, 'debug': 1}:
+            raise Exception(f'Unexpected multi-sink logger messages: {multi_sink_counts}')
+
+        if SETTINGS['arch'] == 'x86_64' and gop_checksum != GOP_CHECKSUM:
+            received = 'none' if gop_checksum is None else f'{gop_checksum:#010x}'
+            raise Exception(f'Expected the GOP checksum {GOP_CHECKSUM:#010x}, got {received}')
+
+        if SETTINGS['arch'] == 'x86_64':
+            if len(timestamps) != 5:
+                raise Exception(f'Expected 5 timestamped messages, got {len(timestamps)}')
+            for (time, sequence), (next_time, next_sequence) in zip(timestamps, timestamps[1:]):
+                if next_time < time or next_sequence != sequence + 1:
+                    raise Exception(f'Timestamps are not increasing: {timestamps}')
 
 def main():
     'Runs the user-requested actions.'
@@ -370,6 +533,15 @@ def main():
     parser.add_argument('--ci', help='disables some tests which currently break CI',
                         action='store_true')
 
+    parser.add_argument('--shell', help='launch the tests from the UEFI shell',
+                        action='store_true')
+
+    parser.add_argument('--panic-after-exit', help='panic after exiting boot services, and check that the panic handler exits QEMU',
+                        action='store_true')
+
+    parser.add_argument('--panic-in-child', help='start a child image which panics, and check that its panic handler reports it on the serial device',
+                        action='store_true')
+
     opts = parser.parse_args()
 
     SETTINGS['arch'] = opts.target
@@ -378,6 +550,9 @@ def main():
     SETTINGS['headless'] = opts.headless
     SETTINGS['config'] = 'release' if opts.release else 'debug'
     SETTINGS['ci'] = opts.ci
+    SETTINGS['shell'] = opts.shell
+    SETTINGS['panic_after_exit'] = opts.panic_after_exit
+    SETTINGS['panic_in_child'] = opts.panic_in_child
 
     verb = opts.verb
 
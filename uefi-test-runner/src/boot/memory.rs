@@ -1,18 +1,34 @@
 use uefi::prelude::*;
-use uefi::table::boot::{AllocateType, BootServices, MemoryDescriptor, MemoryType};
+use uefi::table::boot::{
+    find_largest, merge_adjacent, AllocateType, BootServices, MemoryAttribute, MemoryDescriptor,
+    MemoryType,
+};
+use uefi::table::cfg::{MemoryAttributesTable, MEMORY_ATTRIBUTES_GUID};
 
+use uefi::proto::timestamp::{Timestamp, TimestampProperties};
+
+use crate::alloc::boxed::Box;
 use crate::alloc::vec::Vec;
-use core::mem;
+use core::{mem, ptr};
 
-pub fn test(bt: &BootServices) {
+pub fn test(st: &SystemTable<Boot>) {
+    let bt = st.boot_services();
     info!("Testing memory functions");
 
     allocate_pages(bt);
+    allocate_pages_benchmark(bt);
     vec_alloc();
+    vec_alloc_custom_type(bt);
     alloc_alignment();
+    alloc_pages_backed(bt);
+    alloc_mixed_alignments();
+    alloc_stats();
+    alloc_zeroed(bt);
+    realloc();
     memmove(bt);
 
     memory_map(bt);
+    memory_attributes_table(st);
 }
 
 fn allocate_pages(bt: &BootServices) {
@@ -24,10 +40,10 @@ fn allocate_pages(bt: &BootServices) {
         .allocate_pages(ty, mem_ty, 1)
         .expect_success("Failed to allocate a page of memory");
 
-    assert_eq!(pgs % 4096, 0, "Page pointer is not page-aligned");
+    assert!(pgs.is_page_aligned(), "Page pointer is not page-aligned");
 
     // Reinterprete the page as an array of bytes
-    let buf = unsafe { &mut *(pgs as *mut [u8; 4096]) };
+    let buf = unsafe { &mut *pgs.as_mut_ptr::<[u8; 4096]>() };
 
     // If these don't fail then we properly allocated some memory.
     buf[0] = 0xF0;
@@ -37,6 +53,58 @@ fn allocate_pages(bt: &BootServices) {
     bt.free_pages(pgs, 1).unwrap_success();
 }
 
+/// Returns the properties of a counter, and a function reading it, from the
+/// Timestamp protocol, or the time stamp counter if the protocol is not
+/// installed.
+fn timer(bt: &BootServices) -> Option<(TimestampProperties, Box<dyn Fn() -> u64 + '_>)> {
+    match bt.locate_protocol::<Timestamp>() {
+        Ok(timestamp) => {
+            let timestamp = unsafe { &*timestamp.unwrap().get() };
+            let properties = timestamp
+                .get_properties()
+                .expect_success("Failed to get timestamp properties");
+            Some((properties, Box::new(move || timestamp.get_timestamp())))
+        }
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Err(_) => {
+            let timer = uefi::proto::timestamp::TscTimer::calibrate(bt);
+            Some((
+                timer.get_properties(),
+                Box::new(move || timer.get_timestamp()),
+            ))
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        Err(_) => None,
+    }
+}
+
+// Measures the time taken to allocate and free a page.
+fn allocate_pages_benchmark(bt: &BootServices) {
+    const ITERATIONS: u32 = 100;
+
+    let (properties, get_timestamp) = match timer(bt) {
+        Some(timer) => timer,
+        None => {
+            warn!("No timer available to benchmark page allocation");
+            return;
+        }
+    };
+
+    let start = get_timestamp();
+    for _ in 0..ITERATIONS {
+        let pgs = bt
+            .allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, 1)
+            .expect_success("Failed to allocate a page of memory");
+        bt.free_pages(pgs, 1).unwrap_success();
+    }
+    let elapsed = properties.elapsed(start, get_timestamp());
+
+    info!(
+        "Allocating and freeing a page takes {:?} on average",
+        elapsed / ITERATIONS
+    );
+}
+
 // Simple test to ensure our custom allocator works with the `alloc` crate.
 fn vec_alloc() {
     info!("Allocating a vector through the `alloc` crate");
@@ -48,6 +116,29 @@ fn vec_alloc() {
     assert_eq!(values[..], [-5, 0, 4, 16, 23], "Failed to sort vector");
 }
 
+// Allocate a vector with a custom memory type, and find it in the memory map.
+fn vec_alloc_custom_type(bt: &BootServices) {
+    info!("Allocating a vector with a custom memory type");
+
+    let custom_type = MemoryType::custom(0x8000_5546);
+    let values = uefi::alloc::with_memory_type(custom_type, || vec![0x5au8; 16 * 4096]);
+    assert_eq!(uefi::alloc::memory_type(), MemoryType::LOADER_DATA);
+    assert!(values.iter().all(|&value| value == 0x5a));
+
+    let address = values.as_ptr() as u64;
+    let (_key, map) = bt
+        .memory_map_snapshot()
+        .expect_success("Failed to get a snapshot of the memory map");
+    let region = map
+        .iter()
+        .find(|desc| {
+            let start = desc.phys_start.as_u64();
+            start <= address && address < start + desc.page_count * 4096
+        })
+        .expect("The vector is not in the memory map");
+    assert_eq!(region.ty, custom_type);
+}
+
 // Simple test to ensure our custom allocator works with correct alignment.
 fn alloc_alignment() {
     info!("Allocating a structure with alignment to 0x100");
@@ -59,6 +150,242 @@ fn alloc_alignment() {
     assert_eq!(value.as_ptr() as usize % 0x100, 0, "Wrong alignment");
 }
 
+// Allocations which are page-aligned or large are made with whole pages, and
+// must be given back to the firmware when dropped.
+fn alloc_pages_backed(bt: &BootServices) {
+    info!("Allocating page-aligned and large blocks");
+
+    const LARGE_SIZE: usize = 16 * 1024 * 1024;
+
+    #[repr(align(4096))]
+    struct Page([u8; 4096]);
+
+    #[repr(align(64))]
+    struct CacheLine([u64; 8]);
+
+    let free_pages = || {
+        let (_key, map) = bt
+            .memory_map_snapshot()
+            .expect_success("Failed to get a snapshot of the memory map");
+        map.iter()
+            .filter(|desc| desc.ty == MemoryType::CONVENTIONAL)
+            .map(|desc| desc.page_count)
+            .sum::<u64>()
+    };
+
+    let before = free_pages();
+    {
+        let mut page = Box::new(Page([0; 4096]));
+        assert_eq!(&*page as *const Page as usize % 4096, 0, "Wrong alignment");
+        page.0[4095] = 0xa5;
+
+        let mut line = Box::new(CacheLine([0; 8]));
+        assert_eq!(
+            &*line as *const CacheLine as usize % 64,
+            0,
+            "Wrong alignment"
+        );
+        line.0[7] = 0x5a5a;
+
+        let mut large = vec![0u8; LARGE_SIZE];
+        large[0] = 1;
+        large[LARGE_SIZE - 1] = 2;
+        assert_eq!(large.iter().map(|&byte| byte as u64).sum::<u64>(), 3);
+
+        let during = free_pages();
+        assert!(before >= during + (LARGE_SIZE / 4096) as u64);
+        assert_eq!((page.0[4095], line.0[7]), (0xa5, 0x5a5a));
+    }
+    // The snapshots may grow the pool by a few pages, but the 4096 pages of
+    // the large vector must be back.
+    let after = free_pages();
+    assert!(
+        after + 64 >= before,
+        "Leaked {} pages",
+        before.saturating_sub(after)
+    );
+}
+
+// Small allocations are made from the pool, with room to align them only if
+// they are over-aligned, which is decided from the layout when they are freed.
+// Mixes alignments and sizes, checking that blocks are aligned, and don't
+// overlap the blocks or bookkeeping of the others.
+fn alloc_mixed_alignments() {
+    info!("Allocating blocks with mixed alignments");
+
+    use alloc::alloc::{alloc, dealloc, Layout};
+
+    const SLOTS: usize = 64;
+    const ITERATIONS: usize = 200_000;
+
+    // Alternate between aligned and unaligned blocks of the same size.
+    for &align in &[16, 64, 256, 1024] {
+        for _ in 0..100 {
+            let unaligned = vec![1u8; 48].into_boxed_slice();
+            let layout = Layout::from_size_align(48, align).unwrap();
+            let aligned = unsafe { alloc(layout) };
+            assert!(!aligned.is_null());
+            assert_eq!(aligned as usize % align, 0, "Wrong alignment");
+            drop(unaligned);
+            unsafe { dealloc(aligned, layout) };
+        }
+    }
+
+    // Randomly free and allocate blocks, with a simple xorshift generator.
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut slots: [Option<(*mut u8, Layout)>; SLOTS] = [None; SLOTS];
+    let check_and_free = |(ptr, layout): (*mut u8, Layout), value: u8| {
+        let block = unsafe { core::slice::from_raw_parts(ptr, layout.size()) };
+        assert!(
+            block.iter().all(|&byte| byte == value),
+            "Block was overwritten"
+        );
+        unsafe { dealloc(ptr, layout) };
+    };
+    for iteration in 0..ITERATIONS {
+        let random = next();
+        let index = random as usize % SLOTS;
+        if let Some(block) = slots[index].take() {
+            check_and_free(block, index as u8);
+        }
+        if iteration % 4 != 3 {
+            let size = 1 + (random >> 8) as usize % 256;
+            let align = 1 << ((random >> 20) as usize % 9);
+            let layout = Layout::from_size_align(size, align).unwrap();
+            let ptr = unsafe { alloc(layout) };
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % align, 0, "Wrong alignment");
+            unsafe { ptr::write_bytes(ptr, index as u8, size) };
+            slots[index] = Some((ptr, layout));
+        }
+    }
+    for (index, slot) in slots.iter_mut().enumerate() {
+        if let Some(block) = slot.take() {
+            check_and_free(block, index as u8);
+        }
+    }
+}
+
+// Check that the counters of the allocator return to their baseline once the
+// allocations are freed, and that the peak is the high-water mark.
+fn alloc_stats() {
+    info!("Counting allocations");
+
+    uefi::alloc::reset_peak();
+    let baseline = uefi::alloc::stats();
+    assert_eq!(baseline.peak_bytes, baseline.live_bytes);
+
+    let small = Vec::<u8>::with_capacity(1000);
+    // Large enough to be allocated with pages.
+    let large = Vec::<u8>::with_capacity(20_000);
+    let stats = uefi::alloc::stats();
+    assert_eq!(stats.live_allocations, baseline.live_allocations + 2);
+    assert_eq!(stats.live_bytes, baseline.live_bytes + 21_000);
+    assert_eq!(stats.total_allocations, baseline.total_allocations + 2);
+    drop(large);
+
+    // The allocation is logged at the debug level.
+    uefi_services::set_log_level(log::LevelFilter::Debug);
+    uefi::alloc::log_allocations_above(Some(2000));
+    let medium = Vec::<u8>::with_capacity(3000);
+    uefi::alloc::log_allocations_above(None);
+    uefi_services::set_log_level(log::LevelFilter::Info);
+    let stats = uefi::alloc::stats();
+    assert_eq!(stats.live_bytes, baseline.live_bytes + 4000);
+    assert_eq!(stats.peak_bytes, baseline.live_bytes + 21_000);
+    drop((small, medium));
+
+    let stats = uefi::alloc::stats();
+    assert_eq!(stats.live_allocations, baseline.live_allocations);
+    assert_eq!(stats.live_bytes, baseline.live_bytes);
+    assert_eq!(stats.total_allocations, baseline.total_allocations + 3);
+    uefi_services::log_alloc_stats();
+}
+
+// Zeroed allocations must be cleared, even when they reuse dirty memory.
+// Compares the time taken to get a large zeroed block by allocating it and
+// then writing zeroes to it, which is what the default `alloc_zeroed` does,
+// and with the allocator's `alloc_zeroed`.
+fn alloc_zeroed(bt: &BootServices) {
+    info!("Allocating zeroed memory");
+
+    use alloc::alloc::{alloc, alloc_zeroed, dealloc, Layout};
+    use core::slice;
+
+    const SIZE: usize = 16 * 1024 * 1024;
+
+    // Dirty a block from the pool, and one made of pages, before allocating
+    // zeroed blocks of the same size, which are likely to reuse them.
+    for &size in &[100, SIZE] {
+        let layout = Layout::from_size_align(size, 1).unwrap();
+        unsafe {
+            let dirty = alloc(layout);
+            assert!(!dirty.is_null());
+            ptr::write_bytes(dirty, 0xff, size);
+            dealloc(dirty, layout);
+
+            let zeroed = alloc_zeroed(layout);
+            assert!(!zeroed.is_null());
+            let bytes = slice::from_raw_parts(zeroed, size);
+            assert!(bytes.iter().all(|&byte| byte == 0), "Memory was not zeroed");
+            dealloc(zeroed, layout);
+        }
+    }
+
+    if let Some((properties, get_timestamp)) = timer(bt) {
+        let layout = Layout::from_size_align(SIZE, 1).unwrap();
+        let (memset_time, zeroed_time) = unsafe {
+            let start = get_timestamp();
+            let cleared = alloc(layout);
+            ptr::write_bytes(cleared, 0, SIZE);
+            let memset_time = properties.elapsed(start, get_timestamp());
+            assert_eq!(ptr::read_volatile(cleared.add(SIZE - 1)), 0);
+            dealloc(cleared, layout);
+
+            let start = get_timestamp();
+            let zeroed = alloc_zeroed(layout);
+            let zeroed_time = properties.elapsed(start, get_timestamp());
+            assert_eq!(ptr::read_volatile(zeroed.add(SIZE - 1)), 0);
+            dealloc(zeroed, layout);
+
+            (memset_time, zeroed_time)
+        };
+
+        info!(
+            "Zeroing 16 MiB takes {:?} with `alloc` and `memset`, and {:?} with `alloc_zeroed`",
+            memset_time, zeroed_time
+        );
+    }
+}
+
+// Data must be preserved when blocks are resized, whether they move or not.
+fn realloc() {
+    info!("Resizing allocations");
+
+    let pattern = |len: usize| (0..len).map(|i| (i % 251) as u8);
+
+    // From the pool to pages, and growing pages.
+    let mut values = pattern(1000).collect::<Vec<_>>();
+    for &len in &[5000, 64 * 1024, 1024 * 1024] {
+        values.reserve_exact(len - values.len());
+        values.extend(pattern(len).skip(values.len()));
+        assert!(values.iter().copied().eq(pattern(len)));
+    }
+
+    // Shrinking pages, and from pages to the pool.
+    for &len in &[100 * 1024, 3000, 10] {
+        values.truncate(len);
+        values.shrink_to_fit();
+        assert!(values.iter().copied().eq(pattern(len)));
+    }
+}
+
 // Test that the `memmove` / `set_mem` functions work.
 fn memmove(bt: &BootServices) {
     info!("Testing the `memmove` / `set_mem` functions");
@@ -97,6 +424,14 @@ fn memory_map(bt: &BootServices) {
         buffer.set_len(buf_sz);
     }
 
+    // An empty buffer is too small, and the required size is reported.
+    let error = bt
+        .memory_map(&mut buffer[..0])
+        .expect_error("Retrieved UEFI memory map into an empty buffer");
+    assert_eq!(error.status(), Status::BUFFER_TOO_SMALL);
+    let required_sz = error.data().expect("Memory map size was not reported");
+    assert!(required_sz > 0 && required_sz <= buf_sz);
+
     let (_key, desc_iter) = bt
         .memory_map(&mut buffer)
         .expect_success("Failed to retrieve UEFI memory map");
@@ -114,8 +449,82 @@ fn memory_map(bt: &BootServices) {
     #[cfg(target_arch = "x86_64")]
     {
         let phys_start = first_desc.phys_start;
-        assert_eq!(phys_start, 0, "Memory does not start at address 0");
+        assert_eq!(phys_start.as_u64(), 0, "Memory does not start at address 0");
     }
     let page_count = first_desc.page_count;
     assert!(page_count != 0, "Memory map entry has zero size");
+
+    memory_map_snapshot(bt);
+}
+
+fn memory_map_snapshot(bt: &BootServices) {
+    let (_key, raw) = bt
+        .memory_map_snapshot()
+        .expect_success("Failed to take a snapshot of the memory map");
+    assert!(!raw.is_empty(), "Memory map snapshot is empty");
+
+    let mut merged = raw.clone();
+    merge_adjacent(&mut merged).expect("The memory map has overlapping ranges");
+    assert!(merged.len() <= raw.len());
+    assert!(merged
+        .windows(2)
+        .all(|pair| pair[0].phys_start < pair[1].phys_start));
+
+    // Merging must not lose any memory.
+    let pages = |map: &[MemoryDescriptor]| map.iter().map(|d| d.page_count).sum::<u64>();
+    assert!(pages(&merged) >= pages(&raw));
+    info!(
+        "Memory map: {} descriptors, {} after merging, {} pages",
+        raw.len(),
+        merged.len(),
+        pages(&merged)
+    );
+
+    let largest =
+        find_largest(&merged, MemoryType::CONVENTIONAL).expect("There is no conventional memory");
+    info!(
+        "Largest conventional range: {:?}, {} pages",
+        largest.phys_start, largest.page_count
+    );
+}
+
+fn memory_attributes_table(st: &SystemTable<Boot>) {
+    info!("Testing the memory attributes table");
+
+    let entry = st
+        .config_table()
+        .iter()
+        .find(|entry| entry.guid == MEMORY_ATTRIBUTES_GUID);
+
+    let table = match entry {
+        Some(entry) => unsafe { &*(entry.address as *const MemoryAttributesTable) },
+        None => {
+            warn!("Memory attributes table is not published by the firmware");
+            return;
+        }
+    };
+
+    info!("Memory attributes table version: {}", table.version);
+
+    let bt = st.boot_services();
+    let buf_sz = bt.memory_map_size() + 8 * mem::size_of::<MemoryDescriptor>();
+    let mut buffer = vec![0; buf_sz];
+    let (_key, desc_iter) = bt
+        .memory_map(&mut buffer)
+        .expect_success("Failed to retrieve UEFI memory map");
+
+    let ranges = unsafe { table.runtime_ranges(desc_iter) }
+        .expect_success("Failed to parse memory attributes table");
+
+    let xp_data_regions = ranges
+        .filter(|desc| {
+            desc.ty == MemoryType::RUNTIME_SERVICES_DATA
+                && desc.att.contains(MemoryAttribute::EXECUTE_PROTECT)
+        })
+        .count();
+
+    assert!(
+        xp_data_regions > 0,
+        "No execute-protected runtime data region was listed"
+    );
 }
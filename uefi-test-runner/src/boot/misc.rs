@@ -1,25 +1,82 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
 use uefi::prelude::*;
 use uefi::table::boot::{BootServices, EventType, TimerTrigger, Tpl};
+use uefi::Event;
+use uefi_services::Watchdog;
 
 pub fn test(bt: &BootServices) {
     info!("Testing timer...");
     test_timer(bt);
+    info!("Testing logging from a timer...");
+    test_log_from_timer(bt);
     info!("Testing watchdog...");
-    test_watchdog(bt);
+    test_watchdog();
+    info!("Testing CRC32...");
+    test_crc32(bt);
 }
 
-fn test_watchdog(bt: &BootServices) {
-    // Disable the UEFI watchdog timer
-    bt.set_watchdog_timer(0, 0x10000, None)
-        .expect_success("Could not set watchdog timer");
+fn test_crc32(bt: &BootServices) {
+    // The check value of the CRC-32 used by UEFI.
+    let crc32 = bt
+        .calculate_crc32(b"123456789")
+        .expect_success("Failed to compute a CRC32");
+    assert_eq!(crc32, 0xcbf4_3926);
+    assert_eq!(
+        bt.calculate_crc32(&[]).unwrap_err().status(),
+        Status::INVALID_PARAMETER
+    );
+}
+
+// The watchdog was disabled by `uefi_services::init`. Re-arm it, and disable
+// it again, as `init` does.
+fn test_watchdog() {
+    uefi_services::set_watchdog(Watchdog::Timeout(600))
+        .expect_success("Could not re-arm the watchdog timer");
+    uefi_services::set_watchdog(Watchdog::Disable)
+        .expect_success("Could not disable the watchdog timer");
 }
 
 fn test_timer(bt: &BootServices) {
     let timer_event = unsafe { bt.create_event(EventType::TIMER, Tpl::APPLICATION, None) }
         .expect_success("Failed to create TIMER event");
-    let mut events = [timer_event];
-    bt.set_timer(timer_event, TimerTrigger::Relative(5_0 /*00 ns */))
+    let events = [timer_event];
+    bt.set_timer(&events[0], TimerTrigger::Relative(5_0 /*00 ns */))
         .expect_success("Failed to set timer");
-    bt.wait_for_event(&mut events)
+    bt.wait_for_event(&events)
         .expect_success("Wait for event failed");
+    let [timer_event] = events;
+    bt.close_event(timer_event)
+        .expect_success("Failed to close TIMER event");
+}
+
+/// Number of calls to the notification function of `test_log_from_timer`.
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+fn log_tick(_event: &Event) {
+    TICKS.fetch_add(1, Ordering::SeqCst);
+    info!("TIMER TICK");
+}
+
+// Log messages while a periodic timer logs from its notification function.
+// build.py checks that the messages are not interleaved.
+fn test_log_from_timer(bt: &BootServices) {
+    let timer_event = unsafe {
+        bt.create_event(
+            EventType::TIMER | EventType::NOTIFY_SIGNAL,
+            Tpl::NOTIFY,
+            Some(log_tick),
+        )
+    }
+    .expect_success("Failed to create TIMER event");
+    bt.set_timer(&timer_event, TimerTrigger::Periodic(10_000 /* 1 ms */))
+        .expect_success("Failed to set timer");
+    for i in 0..20 {
+        info!("INTERLEAVE TEST: abcdefghijklmnopqrstuvwxyz {}", i);
+        bt.stall(1_000);
+    }
+    bt.set_timer(&timer_event, TimerTrigger::Cancel)
+        .expect_success("Failed to cancel timer");
+    bt.close_event(timer_event)
+        .expect_success("Failed to close TIMER event");
+    assert!(TICKS.load(Ordering::SeqCst) > 0);
 }
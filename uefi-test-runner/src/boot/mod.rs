@@ -1,8 +1,9 @@
-use uefi::table::boot::BootServices;
+use uefi::prelude::*;
 
-pub fn test(bt: &BootServices) {
+pub fn test(st: &SystemTable<Boot>) {
+    let bt = st.boot_services();
     info!("Testing boot services");
-    memory::test(bt);
+    memory::test(st);
     misc::test(bt);
 }
 
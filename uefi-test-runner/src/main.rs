@@ -2,6 +2,7 @@
 #![no_main]
 #![feature(asm)]
 #![feature(abi_efiapi)]
+#![feature(const_panic)]
 
 #[macro_use]
 extern crate log;
@@ -11,10 +12,16 @@ extern crate alloc;
 // Keep this line to ensure the `mem*` functions are linked in.
 extern crate rlibc;
 
-use core::mem;
+use alloc::boxed::Box;
+use core::{mem, ptr};
+use uefi::data_types::ucs2;
+use uefi::logger::LogBuffer;
 use uefi::prelude::*;
 use uefi::proto::console::serial::Serial;
+use uefi::proto::loaded_image::LoadedImage;
 use uefi::table::boot::MemoryDescriptor;
+use uefi::table::runtime::VariableAttributes;
+use uefi::CStr16;
 
 mod boot;
 mod proto;
@@ -22,14 +29,45 @@ mod runtime;
 
 #[entry]
 fn efi_main(image: Handle, mut st: SystemTable<Boot>) -> Status {
+    // Let the debug messages through, to test the log level variable.
+    set_log_level_variable(st.runtime_services(), b"debug");
+
     // Initialize utilities (logging, memory allocation...)
-    uefi_services::init(&mut st).expect_success("Failed to initialize utilities");
+    assert!(uefi_services::try_boot_services().is_none());
+    uefi_services::init_with_options(image, &mut st, &uefi_services::InitOptions::new())
+        .expect_success("Failed to initialize utilities");
+    assert!(uefi_services::try_boot_services().is_some());
+    assert_eq!(
+        uefi_services::runtime_services() as *const _,
+        st.runtime_services() as *const _
+    );
+    check_log_level(st.runtime_services());
+    check_launch_info(image);
+
+    // The copy of the test runner started by `panic_in_child` only panics.
+    if is_panic_child(image, st.boot_services()) {
+        // Disarm the allocator, as on exit from boot services, but keep the
+        // boot services for the panic handler. Allocating then calls the
+        // allocation error handler, and build.py checks that its panic is
+        // written to the serial device.
+        uefi::alloc::exit_boot_services();
+        let boxed = Box::new([0u64; 8]);
+        unsafe { ptr::read_volatile(&boxed[0]) };
+        panic!("PANIC TEST: the allocation did not fail");
+    }
+
+    // The utilities can only be initialized once.
+    let error = uefi_services::init(&mut st).expect_error("Initialized the utilities twice");
+    assert_eq!(error.status(), Status::ALREADY_STARTED);
 
     // Reset the console before running all the other tests.
     st.stdout()
         .reset(false)
         .expect_success("Failed to reset stdout");
 
+    // Dump the environment we are running in, to help diagnose failures.
+    info!("{:?}", st);
+
     // Ensure the tests are run on a version of UEFI we support.
     check_revision(st.uefi_revision());
 
@@ -38,10 +76,9 @@ fn efi_main(image: Handle, mut st: SystemTable<Boot>) -> Status {
 
     // Try retrieving a handle to the file system the image was booted from.
     bt.get_image_file_system(image)
-        .expect("Failed to retrieve boot file system")
-        .unwrap();
+        .expect_msg("Failed to retrieve boot file system");
 
-    boot::test(bt);
+    boot::test(&st);
 
     // Test all the supported protocols.
     proto::test(image, &mut st);
@@ -52,9 +89,99 @@ fn efi_main(image: Handle, mut st: SystemTable<Boot>) -> Status {
 
     runtime::test(st.runtime_services());
 
+    #[cfg(feature = "panic-in-child")]
+    panic_in_child(image, st.boot_services());
+
     shutdown(image, st);
 }
 
+/// Sets the variable read by `uefi_services::init` to choose the log level,
+/// or deletes it if `value` is empty.
+fn set_log_level_variable(rt: &RuntimeServices, value: &[u8]) {
+    // Memory allocation is not available before initialization.
+    let mut name = [0; 32];
+    let length = ucs2::encode_str(uefi_services::LOG_LEVEL_VARIABLE, &mut name)
+        .expect_success("Failed to encode the name of the log level variable");
+    let name = CStr16::from_u16_with_nul(&name[..length]).unwrap();
+    rt.set_variable(
+        name,
+        &uefi_services::LOG_LEVEL_VENDOR,
+        VariableAttributes::BOOTSERVICE_ACCESS,
+        value,
+    )
+    .expect_success("Failed to set the log level variable");
+}
+
+/// Checks that debug messages are logged, as set with the log level variable,
+/// and goes back to the default level.
+fn check_log_level(rt: &RuntimeServices) {
+    assert_eq!(log::max_level(), log::LevelFilter::Debug);
+    // build.py checks that this message is received.
+    debug!("LOG LEVEL: debug");
+
+    set_log_level_variable(rt, b"");
+    uefi_services::set_log_level(log::LevelFilter::Info);
+    assert_eq!(log::max_level(), log::LevelFilter::Info);
+}
+
+/// Load options of the copy of the test runner started by `panic_in_child`.
+const PANIC_CHILD_OPTIONS: &str = "panic-in-child";
+
+/// Starts a copy of the test runner which panics, to test the panic handler
+/// of a child image.
+///
+/// The panic handler of the child exits QEMU, so this function only returns
+/// if the child didn't panic, in which case it exits QEMU with a failure.
+#[cfg(feature = "panic-in-child")]
+fn panic_in_child(image: Handle, bt: &BootServices) {
+    info!("Starting a child image which panics");
+    let runner = proto::security::read_runner(image, bt).expect("Failed to read the test runner");
+    let child = bt
+        .load_image_from_buffer(image, &runner)
+        .expect_success("Failed to load the test runner image");
+
+    let mut options = [0; 32];
+    let length = ucs2::encode_str(PANIC_CHILD_OPTIONS, &mut options)
+        .expect_success("Failed to encode the load options");
+    let loaded_image = bt
+        .handle_protocol::<LoadedImage>(child)
+        .expect_success("Failed to open the loaded image of the child");
+    unsafe {
+        (*loaded_image.get()).set_load_options(
+            options.as_ptr().cast(),
+            (length * mem::size_of::<u16>()) as u32,
+        );
+    }
+
+    let _ = bt.start_image(child);
+    error!("The child image did not panic");
+    uefi_services::qemu_exit(uefi_services::QEMU_EXIT_FAILURE);
+}
+
+/// Checks whether this image is the copy of the test runner started by
+/// `panic_in_child`.
+fn is_panic_child(image: Handle, bt: &BootServices) -> bool {
+    let loaded_image = bt
+        .handle_protocol::<LoadedImage>(image)
+        .expect_success("Failed to open the loaded image");
+    let mut buffer = [0; 32];
+    let options = unsafe { (*loaded_image.get()).load_options(&mut buffer) };
+    matches!(options, Ok(options) if options == PANIC_CHILD_OPTIONS)
+}
+
+/// Logs the information gathered about the image, and checks it.
+fn check_launch_info(image: Handle) {
+    let launch_info = uefi_services::launch_info().expect("No launch information");
+    info!("{}", launch_info);
+    assert_eq!(launch_info.image, image);
+    assert!(launch_info.image_base.is_some());
+    assert!(
+        matches!(launch_info.image_size, Some(size) if size > 0),
+        "Invalid image size: {:?}",
+        launch_info.image_size
+    );
+}
+
 fn check_revision(rev: uefi::table::Revision) {
     let (major, minor) = (rev.major(), rev.minor());
 
@@ -111,8 +238,6 @@ fn check_screenshot(bt: &BootServices, name: &str) {
 }
 
 fn shutdown(image: uefi::Handle, mut st: SystemTable<Boot>) -> ! {
-    use uefi::table::runtime::ResetType;
-
     // Get our text output back.
     st.stdout().reset(false).unwrap_success();
 
@@ -124,6 +249,16 @@ fn shutdown(image: uefi::Handle, mut st: SystemTable<Boot>) -> ! {
         info!("Testing complete, shutting down...");
     }
 
+    // Keep the messages logged after exiting boot services in memory.
+    let log_buffer = if cfg!(feature = "qemu") {
+        let storage = vec![0; 4096].into_boxed_slice();
+        let log_buffer: &LogBuffer = Box::leak(Box::new(LogBuffer::new(Box::leak(storage))));
+        uefi_services::add_log_buffer(log_buffer).expect_success("Failed to add a log buffer");
+        Some(log_buffer)
+    } else {
+        None
+    };
+
     // Exit boot services as a proof that it works :)
     let max_mmap_size =
         st.boot_services().memory_map_size() + 8 * mem::size_of::<MemoryDescriptor>();
@@ -132,17 +267,41 @@ fn shutdown(image: uefi::Handle, mut st: SystemTable<Boot>) -> ! {
         .exit_boot_services(image, &mut mmap_storage[..])
         .expect_success("Failed to exit boot services");
 
-    #[cfg(target_arch = "x86_64")]
-    {
-        if cfg!(feature = "qemu") {
-            use qemu_exit::QEMUExit;
-            let custom_exit_success = 3;
-            let qemu_exit_handle = qemu_exit::X86::new(0xF4, custom_exit_success);
-            qemu_exit_handle.exit_success();
-        }
+    // Logging must not use the console anymore, but still reach the buffer.
+    if let Some(log_buffer) = log_buffer {
+        info!("Logged after exiting boot services");
+        let mut contents = [0; 128];
+        let len = log_buffer.copy_to(&mut contents);
+        assert!(contents[..len].ends_with(b"Logged after exiting boot services\n"));
+
+        // The allocator is disarmed, and allocations fail instead of calling
+        // the boot services. The panic of the allocation error handler is
+        // checked by the child image of `panic_in_child`.
+        assert!(uefi::alloc::is_initialized() && !uefi::alloc::is_armed());
+        let layout = core::alloc::Layout::from_size_align(64, 8).unwrap();
+        assert!(unsafe { alloc::alloc::alloc(layout) }.is_null());
+
+        // Only the runtime services are still available.
+        assert!(uefi_services::boot_services_exited());
+        assert!(uefi_services::try_boot_services().is_none());
+        let _ = uefi_services::runtime_services();
     }
 
+    // The panic handler must reach the exit port of QEMU, without using the
+    // boot services.
+    if cfg!(feature = "panic-after-exit") {
+        panic!("Panicking after exiting boot services");
+    }
+
+    // Tell build.py that the tests passed
+    #[cfg(feature = "qemu")]
+    uefi_services::qemu_exit(uefi_services::QEMU_EXIT_SUCCESS);
+
     // Shut down the system
-    let rt = unsafe { st.runtime_services() };
-    rt.reset(ResetType::Shutdown, Status::SUCCESS, None);
+    #[cfg(not(feature = "qemu"))]
+    {
+        use uefi::table::runtime::ResetType;
+        let rt = unsafe { st.runtime_services() };
+        rt.reset(ResetType::Shutdown, Status::SUCCESS, None);
+    }
 }
@@ -0,0 +1,85 @@
+use core::convert::TryInto;
+use core::{ptr, slice};
+use uefi::prelude::*;
+use uefi::proto::acpi::{set_checksum, AcpiTable, TABLE_HEADER_SIZE};
+use uefi::table::boot::BootServices;
+use uefi::table::cfg::{ConfigTableEntry, ACPI2_GUID};
+
+pub fn test(bt: &BootServices, config_table: &[ConfigTableEntry]) {
+    info!("Running ACPI table protocol test");
+
+    let acpi = match bt.locate_protocol::<AcpiTable>() {
+        Ok(acpi) => acpi.expect("Warnings encountered while opening ACPI table protocol"),
+        Err(_) => {
+            warn!("ACPI table protocol is not supported");
+            return;
+        }
+    };
+    let acpi = unsafe { &*acpi.get() };
+
+    const OEM_TABLE_ID: &[u8; 8] = b"UEFI-RS ";
+    let mut ssdt = [0; TABLE_HEADER_SIZE];
+    ssdt[..4].copy_from_slice(b"SSDT");
+    ssdt[4..8].copy_from_slice(&(TABLE_HEADER_SIZE as u32).to_le_bytes());
+    // Revision.
+    ssdt[8] = 2;
+    ssdt[10..16].copy_from_slice(b"UEFIRS");
+    ssdt[16..24].copy_from_slice(OEM_TABLE_ID);
+    ssdt[24..28].copy_from_slice(&1u32.to_le_bytes());
+    ssdt[28..32].copy_from_slice(b"RUST");
+    ssdt[32..36].copy_from_slice(&1u32.to_le_bytes());
+
+    // Mistakes are caught before reaching the firmware.
+    let err = acpi
+        .install_acpi_table(&ssdt)
+        .expect_err("Installed a table with a wrong checksum");
+    assert_eq!(err.status(), Status::CRC_ERROR);
+    set_checksum(&mut ssdt);
+    let err = acpi
+        .install_acpi_table(&ssdt[..TABLE_HEADER_SIZE - 1])
+        .expect_err("Installed a truncated table");
+    assert_eq!(err.status(), Status::BAD_BUFFER_SIZE);
+
+    let key = acpi
+        .install_acpi_table(&ssdt)
+        .expect_success("Failed to install SSDT");
+    assert!(
+        has_table(config_table, b"SSDT", OEM_TABLE_ID),
+        "The SSDT is missing from the XSDT"
+    );
+
+    acpi.uninstall_acpi_table(key)
+        .expect_success("Failed to uninstall SSDT");
+    assert!(
+        !has_table(config_table, b"SSDT", OEM_TABLE_ID),
+        "The SSDT is still in the XSDT"
+    );
+}
+
+/// Whether the XSDT lists a table with this signature and OEM table ID.
+fn has_table(
+    config_table: &[ConfigTableEntry],
+    signature: &[u8; 4],
+    oem_table_id: &[u8; 8],
+) -> bool {
+    let rsdp = config_table
+        .iter()
+        .find(|entry| entry.guid == ACPI2_GUID)
+        .expect("No ACPI 2 RSDP")
+        .address
+        .cast::<u8>();
+    let rsdp = unsafe { slice::from_raw_parts(rsdp, 36) };
+    assert_eq!(&rsdp[..8], b"RSD PTR ");
+    let xsdt = u64::from_le_bytes(rsdp[24..32].try_into().unwrap()) as *const u8;
+
+    let header = unsafe { slice::from_raw_parts(xsdt, TABLE_HEADER_SIZE) };
+    assert_eq!(&header[..4], b"XSDT");
+    let length = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let entries = unsafe { xsdt.add(TABLE_HEADER_SIZE).cast::<u64>() };
+    (0..(length - TABLE_HEADER_SIZE) / 8).any(|i| {
+        // The entries of the XSDT are not aligned.
+        let table = unsafe { ptr::read_unaligned(entries.add(i)) } as *const u8;
+        let header = unsafe { slice::from_raw_parts(table, TABLE_HEADER_SIZE) };
+        &header[..4] == signature && &header[16..24] == oem_table_id
+    })
+}
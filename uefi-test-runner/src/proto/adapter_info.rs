@@ -0,0 +1,42 @@
+use uefi::prelude::*;
+use uefi::proto::adapter_info::{AdapterInformation, MediaState};
+use uefi::table::boot::BootServices;
+
+pub fn test(bt: &BootServices) {
+    info!("Running adapter information protocol test");
+
+    let handles = bt
+        .find_handles::<AdapterInformation>()
+        .expect_success("Failed to get adapter information handles");
+    if handles.is_empty() {
+        warn!("No adapter information protocol available");
+        return;
+    }
+
+    for handle in handles {
+        let aip = bt
+            .handle_protocol::<AdapterInformation>(handle)
+            .expect_success("Failed to open adapter information protocol");
+        let aip = unsafe { &*aip.get() };
+
+        let types = aip
+            .get_supported_types(bt)
+            .expect_success("Failed to get supported information types");
+        info!("Supported information types: {:?}", types);
+
+        // Every supported type must be retrievable, at least as raw bytes.
+        for info_type in types.as_slice() {
+            let block = aip
+                .get_information(bt, info_type)
+                .expect_success("Failed to get information block");
+            info!("Information block: {:?}", block);
+        }
+
+        if types.contains::<MediaState>() {
+            let state = aip
+                .get::<MediaState>(bt)
+                .expect_success("Failed to get media state");
+            info!("Media state: {:?}", state.media_state);
+        }
+    }
+}
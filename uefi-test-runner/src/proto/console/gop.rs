@@ -1,5 +1,8 @@
+use crate::alloc::vec::Vec;
 use uefi::prelude::*;
-use uefi::proto::console::gop::{BltOp, BltPixel, FrameBuffer, GraphicsOutput, PixelFormat};
+use uefi::proto::console::gop::{
+    BltOp, BltPixel, BltRegion, FrameBuffer, GraphicsOutput, PixelFormat,
+};
 use uefi::table::boot::BootServices;
 
 pub fn test(bt: &BootServices) {
@@ -13,6 +16,10 @@ pub fn test(bt: &BootServices) {
         draw_fb(gop);
 
         crate::check_screenshot(bt, "gop_test");
+
+        // The pattern is drawn after the screenshot, to keep the reference
+        // screenshot valid.
+        check_pattern(bt, gop);
     } else {
         // No tests can be run.
         warn!("UEFI Graphics Output Protocol is not supported");
@@ -89,3 +96,98 @@ fn draw_fb(gop: &mut GraphicsOutput) {
     fill_rectangle((50, 30), (150, 600), [250, 128, 64]);
     fill_rectangle((400, 120), (750, 450), [16, 128, 255]);
 }
+
+/// Position and size of the square drawn by `check_pattern`.
+const PATTERN_POSITION: (usize, usize) = (768, 512);
+const PATTERN_SIZE: usize = 256;
+
+/// Color of the pixel of the pattern at the given coordinates, as RGB.
+fn pattern_color(x: usize, y: usize) -> [u8; 3] {
+    [x as u8, y as u8, (x ^ y) as u8]
+}
+
+// Draw a pattern, read it back, and send its checksum to build.py, which
+// compares it to the expected checksum.
+//
+// The top half of the pattern is drawn with `blt`, and the bottom half
+// directly to the frame buffer when the pixel format allows it. Nothing may
+// be logged until the pattern is read back, as the console draws on the
+// screen.
+fn check_pattern(bt: &BootServices, gop: &mut GraphicsOutput) {
+    let (x0, y0) = PATTERN_POSITION;
+    let half = PATTERN_SIZE / 2;
+
+    let mut pixels = Vec::with_capacity(PATTERN_SIZE * PATTERN_SIZE);
+    for y in 0..PATTERN_SIZE {
+        for x in 0..PATTERN_SIZE {
+            let [red, green, blue] = pattern_color(x, y);
+            pixels.push(BltPixel::new(red, green, blue));
+        }
+    }
+
+    gop.blt(BltOp::BufferToVideo {
+        buffer: &pixels,
+        src: BltRegion::Full,
+        dest: (x0, y0),
+        dims: (PATTERN_SIZE, half),
+    })
+    .expect_success("Failed to draw the top of the pattern");
+
+    let mi = gop.current_mode_info();
+    let stride = mi.stride();
+    let bottom_drawn = match mi.pixel_format() {
+        PixelFormat::Rgb | PixelFormat::Bgr => {
+            let bgr = mi.pixel_format() == PixelFormat::Bgr;
+            let mut fb = gop.frame_buffer();
+            for y in half..PATTERN_SIZE {
+                for x in 0..PATTERN_SIZE {
+                    let mut color = pattern_color(x, y);
+                    if bgr {
+                        color.reverse();
+                    }
+                    let pixel_base = 4 * ((y0 + y) * stride + x0 + x);
+                    unsafe { fb.write_value(pixel_base, color) };
+                }
+            }
+            true
+        }
+        _ => false,
+    };
+    if !bottom_drawn {
+        gop.blt(BltOp::BufferToVideo {
+            buffer: &pixels,
+            src: BltRegion::SubRectangle {
+                coords: (0, half),
+                px_stride: PATTERN_SIZE,
+            },
+            dest: (x0, y0 + half),
+            dims: (PATTERN_SIZE, PATTERN_SIZE - half),
+        })
+        .expect_success("Failed to draw the bottom of the pattern");
+    }
+
+    let mut read_back = vec![BltPixel::new(0, 0, 0); PATTERN_SIZE * PATTERN_SIZE];
+    gop.blt(BltOp::VideoToBltBuffer {
+        buffer: &mut read_back,
+        src: (x0, y0),
+        dest: BltRegion::Full,
+        dims: (PATTERN_SIZE, PATTERN_SIZE),
+    })
+    .expect_success("Failed to read the pattern back");
+
+    // `blt` converts the pixels to its own format, whatever the format of
+    // the frame buffer. The reserved byte is ignored, and the colors are
+    // checksummed in RGB order.
+    let mut rgb = Vec::with_capacity(3 * read_back.len());
+    for pixel in &read_back {
+        rgb.extend_from_slice(&[pixel.red, pixel.green, pixel.blue]);
+    }
+    let checksum = bt
+        .calculate_crc32(&rgb)
+        .expect_success("Failed to compute the checksum of the pattern");
+    if !bottom_drawn {
+        info!("This pixel format is not supported by the frame buffer pattern");
+    }
+    // build.py checks this checksum.
+    info!("GOP CHECKSUM: {:#010x}", checksum);
+}
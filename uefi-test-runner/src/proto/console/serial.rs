@@ -1,5 +1,9 @@
+use alloc::boxed::Box;
+use log::Log;
+use uefi::logger::{LogBuffer, Logger, SerialLogger, Sink};
 use uefi::prelude::*;
 use uefi::proto::console::serial::{ControlBits, Serial};
+use uefi::proto::timestamp::Timestamp;
 use uefi::table::boot::BootServices;
 
 pub fn test(bt: &BootServices) {
@@ -10,7 +14,12 @@ pub fn test(bt: &BootServices) {
             return;
         }
 
-        let serial = serial.expect("Warnings encountered while opening serial protocol");
+        test_logger(bt);
+        test_multi_sink(bt);
+        test_timestamps(bt);
+        test_disabled_timestamps(bt);
+
+        let serial = serial.expect_success("Warnings encountered while opening serial protocol");
         let serial = unsafe { &mut *serial.get() };
 
         let old_ctrl_bits = serial
@@ -55,3 +64,103 @@ pub fn test(bt: &BootServices) {
         warn!("No serial device found");
     }
 }
+
+/// Logs a message to the serial device, which build.py checks for.
+fn test_logger(bt: &BootServices) {
+    let mut logger = unsafe { SerialLogger::new(bt) };
+    logger.log(
+        &log::Record::builder()
+            .level(log::Level::Info)
+            .args(format_args!("SERIAL LOGGER: ok"))
+            .file(Some(file!()))
+            .line(Some(line!()))
+            .build(),
+    );
+    logger.disable();
+}
+
+/// Logs messages to a logger writing the `Info` messages to the console, and
+/// the `Debug` messages to the serial device. build.py checks which messages
+/// were received.
+fn test_multi_sink(bt: &BootServices) {
+    let stdout = unsafe { uefi_services::system_table().as_ref() }.stdout_ptr();
+    let logger = Logger::empty();
+    unsafe {
+        let console = Sink::console(stdout).level(log::LevelFilter::Info);
+        logger.add_sink(console).ok().unwrap();
+        let serial = Sink::serial(bt).level(log::LevelFilter::Debug);
+        logger.add_sink(serial).ok().unwrap();
+    }
+    assert_eq!(logger.max_level(), log::LevelFilter::Debug);
+
+    for &(level, name) in &[(log::Level::Info, "info"), (log::Level::Debug, "debug")] {
+        logger.log(
+            &log::Record::builder()
+                .level(level)
+                .args(format_args!("MULTI SINK: {}", name))
+                .file(Some(file!()))
+                .line(Some(line!()))
+                .build(),
+        );
+    }
+    // Trace messages are not written to any sink.
+    assert!(!logger.enabled(&log::Metadata::builder().level(log::Level::Trace).build()));
+    logger.disable();
+}
+
+/// Logs messages with timestamps to the serial device. build.py checks that
+/// the timestamps and sequence numbers increase.
+fn test_timestamps(bt: &BootServices) {
+    let logger = Logger::empty();
+    unsafe {
+        logger.add_sink(Sink::serial(bt)).ok().unwrap();
+        logger.enable_timestamps(bt);
+    }
+    for i in 0..5 {
+        // Let some time pass between the messages.
+        bt.stall(2_000);
+        logger.log(
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .args(format_args!("TIMESTAMP TEST: {}", i))
+                .file(Some(file!()))
+                .line(Some(line!()))
+                .build(),
+        );
+    }
+    logger.disable();
+}
+
+/// Logs a message with timestamps to memory once the logger is disabled, at
+/// which point the Timestamp protocol must no longer be used.
+fn test_disabled_timestamps(bt: &BootServices) {
+    let buffer: &LogBuffer = Box::leak(Box::new(LogBuffer::new(Box::leak(Box::new([0; 256])))));
+    let logger = Logger::empty();
+    unsafe {
+        logger.add_sink(Sink::memory(buffer)).ok().unwrap();
+        logger.enable_timestamps(bt);
+    }
+    logger.disable();
+    logger.log(
+        &log::Record::builder()
+            .level(log::Level::Info)
+            .args(format_args!("Logged while disabled"))
+            .file(Some(file!()))
+            .line(Some(line!()))
+            .build(),
+    );
+
+    let mut contents = [0; 256];
+    let len = buffer.copy_to(&mut contents);
+    let message = core::str::from_utf8(&contents[..len]).expect("Invalid log buffer contents");
+    info!("Message logged while disabled: {}", message.trim_end());
+    assert!(message.ends_with("Logged while disabled\n"));
+    // The time is only left out if it is measured with the Timestamp
+    // protocol, as the time stamp counter keeps working.
+    let uses_protocol = match bt.locate_protocol::<Timestamp>() {
+        Ok(timestamp) => unsafe { &*timestamp.log().get() }.get_properties().is_ok(),
+        Err(_) => false,
+    };
+    let untimed = message.starts_with("[         000 INFO ]: ");
+    assert_eq!(untimed, uses_protocol, "Unexpected stamp: {}", message);
+}
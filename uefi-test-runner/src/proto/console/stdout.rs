@@ -1,5 +1,8 @@
+use log::Log;
+use uefi::logger::Logger;
 use uefi::prelude::*;
 use uefi::proto::console::text::{Color, Output};
+use uefi::CStr16;
 
 pub fn test(stdout: &mut Output) {
     info!("Running text output protocol test");
@@ -8,10 +11,12 @@ pub fn test(stdout: &mut Output) {
     change_text_mode(stdout);
     change_color(stdout);
     center_text(stdout);
+    log_colors(stdout);
+    unknown_glyph(stdout);
 
     // Print all modes.
     for (index, mode) in stdout.modes().enumerate() {
-        let mode = mode.expect("Warnings encountered while querying text mode");
+        let mode = mode.expect_success("Warnings encountered while querying text mode");
         info!(
             "- Text mode #{}: {} rows by {} columns",
             index,
@@ -36,7 +41,7 @@ fn change_text_mode(stdout: &mut Output) {
         .modes()
         .last()
         .unwrap()
-        .expect("Warnings encountered while querying text mode");
+        .expect_success("Warnings encountered while querying text mode");
     stdout
         .set_mode(best_mode)
         .expect_success("Failed to change text mode");
@@ -73,3 +78,42 @@ fn center_text(stdout: &mut Output) {
             _ => panic!("Failed to hide cursor"),
         });
 }
+
+// Log a message of each level, colored by level, and check that the colors of
+// the console are restored.
+fn log_colors(stdout: &mut Output) {
+    let color = stdout.color();
+    let bt = uefi_services::boot_services();
+    let logger = unsafe { Logger::with_colors(bt, stdout, true) };
+    for &level in &[
+        log::Level::Error,
+        log::Level::Warn,
+        log::Level::Info,
+        log::Level::Debug,
+        log::Level::Trace,
+    ] {
+        logger.log(
+            &log::Record::builder()
+                .level(level)
+                .args(format_args!("Colored {} message", level))
+                .file(Some(file!()))
+                .line(Some(line!()))
+                .build(),
+        );
+    }
+    assert_eq!(stdout.color(), color);
+}
+
+// Print a character which is not in the console font, which is reported as a
+// warning rather than an error.
+fn unknown_glyph(stdout: &mut Output) {
+    let snowman = [0x2603, '\r' as u16, '\n' as u16, 0];
+    let string = CStr16::from_u16_with_nul(&snowman).unwrap();
+    let completion = stdout
+        .output_string(string)
+        .expect("Failed to print unknown glyph");
+    match completion.status() {
+        Status::SUCCESS | Status::WARN_UNKNOWN_GLYPH => completion.ignore_warning(),
+        other => panic!("Unexpected status while printing unknown glyph: {}", other),
+    }
+}
@@ -1,4 +1,8 @@
-use uefi::proto::debug::DebugSupport;
+use log::Log;
+use uefi::logger::Logger;
+use uefi::prelude::*;
+use uefi::proto::console::text::Output;
+use uefi::proto::debug::{DebugPort, DebugSupport, SystemContext};
 use uefi::table::boot::BootServices;
 
 pub fn test(bt: &BootServices) {
@@ -9,7 +13,112 @@ pub fn test(bt: &BootServices) {
         let debug_support = unsafe { &mut *debug_support.get() };
 
         info!("- Architecture: {:?}", debug_support.arch());
+        test_callbacks(debug_support);
     } else {
         warn!("Debug protocol is not supported");
     }
+
+    test_debug_port(bt);
+}
+
+fn test_callbacks(debug_support: &mut DebugSupport) {
+    let max_processor_index = debug_support
+        .get_maximum_processor_index()
+        .expect_success("Failed to get the maximum processor index");
+    info!("- Maximum processor index: {}", max_processor_index);
+
+    extern "efiapi" fn periodic_callback(_context: SystemContext) {}
+
+    unsafe { debug_support.register_periodic_callback(0, periodic_callback) }
+        .expect_success("Failed to register the periodic callback");
+    debug_support
+        .unregister_periodic_callback(0)
+        .expect_success("Failed to unregister the periodic callback");
+
+    let status = unsafe {
+        debug_support.register_periodic_callback(max_processor_index + 1, periodic_callback)
+    };
+    assert_eq!(status.unwrap_err().status(), Status::INVALID_PARAMETER);
+
+    #[cfg(target_arch = "x86_64")]
+    test_breakpoint(debug_support);
+}
+
+#[cfg(target_arch = "x86_64")]
+fn test_breakpoint(debug_support: &mut DebugSupport) {
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use uefi::proto::debug::{ExceptionType, ExceptionTypeX64};
+
+    static BREAKPOINT_HIT: AtomicBool = AtomicBool::new(false);
+
+    extern "efiapi" fn breakpoint_callback(
+        exception_type: ExceptionType,
+        mut context: SystemContext,
+    ) {
+        assert_eq!(
+            ExceptionTypeX64::from(exception_type),
+            ExceptionTypeX64::BREAKPOINT
+        );
+        // `int3` is a trap, so the saved instruction pointer is already the
+        // one of the next instruction, and execution resumes after it.
+        let context = unsafe { context.x64() };
+        if context.rip != 0 {
+            BREAKPOINT_HIT.store(true, Ordering::SeqCst);
+        }
+    }
+
+    unsafe {
+        debug_support.register_exception_callback(
+            0,
+            breakpoint_callback,
+            ExceptionTypeX64::BREAKPOINT.into(),
+        )
+    }
+    .expect_success("Failed to register the breakpoint callback");
+
+    unsafe { asm!("int3") };
+
+    debug_support
+        .unregister_exception_callback(0, ExceptionTypeX64::BREAKPOINT.into())
+        .expect_success("Failed to unregister the breakpoint callback");
+    assert!(BREAKPOINT_HIT.load(Ordering::SeqCst));
+}
+
+fn test_debug_port(bt: &BootServices) {
+    info!("Running debug port protocol test");
+    let debug_port = match bt.locate_protocol::<DebugPort>() {
+        Ok(debug_port) => {
+            debug_port.expect("Warnings encountered while opening debug port protocol")
+        }
+        Err(_) => {
+            warn!("Debug port protocol is not supported");
+            return;
+        }
+    };
+    let debug_port = unsafe { &mut *debug_port.get() };
+
+    debug_port
+        .write_all(10_000, b"uefi-rs debug port test\r\n")
+        .expect_success("Failed to write to the debug port");
+    let pending = debug_port
+        .poll()
+        .expect_success("Failed to poll the debug port");
+    info!("- Input pending: {}", pending);
+
+    // Log a record through a logger which also writes to the debug port.
+    let output = bt
+        .locate_protocol::<Output>()
+        .expect_success("Failed to open text output protocol");
+    let output = unsafe { &mut *output.get() };
+    let logger = unsafe { Logger::new(bt, output) };
+    unsafe { logger.set_debug_port(debug_port) };
+    logger.log(
+        &log::Record::builder()
+            .level(log::Level::Info)
+            .args(format_args!("Logged to the debug port"))
+            .file(Some(file!()))
+            .line(Some(line!()))
+            .build(),
+    );
+    logger.disable();
 }
@@ -0,0 +1,54 @@
+use uefi::prelude::*;
+use uefi::proto::decompress::Decompress;
+use uefi::table::boot::BootServices;
+
+pub fn test(bt: &BootServices) {
+    info!("Running decompress protocol test");
+
+    let decompress = match bt.locate_protocol::<Decompress>() {
+        Ok(decompress) => {
+            decompress.expect("Warnings encountered while opening decompress protocol")
+        }
+        Err(_) => {
+            warn!("Decompress protocol is not supported");
+            return;
+        }
+    };
+    let decompress = unsafe { &*decompress.get() };
+
+    // Compressed with the EFI compression algorithm, with a block for each
+    // literal and for the final match.
+    let compressed = include_bytes!("compressed.bin");
+    let original = b"uefi-rs uefi-rs uefi-rs uefi-rs ";
+
+    let (destination_size, scratch_size) = decompress
+        .get_info(compressed)
+        .expect_success("Failed to get decompression info");
+    assert_eq!(destination_size, original.len());
+
+    let mut destination = [0; 64];
+    let mut scratch = crate::alloc::vec![0; scratch_size];
+    let err = decompress
+        .decompress(
+            compressed,
+            &mut destination[..destination_size - 1],
+            &mut scratch,
+        )
+        .expect_err("Decompressed data into a too small buffer");
+    assert_eq!(err.status(), Status::BUFFER_TOO_SMALL);
+    let decompressed = decompress
+        .decompress(compressed, &mut destination, &mut scratch)
+        .expect_success("Failed to decompress data");
+    assert_eq!(decompressed, original);
+
+    let decompressed = decompress
+        .decompress_to_vec(compressed)
+        .expect_success("Failed to decompress data");
+    assert_eq!(decompressed, original);
+
+    // The compressed size in the header is larger than the data.
+    let err = decompress
+        .get_info(&compressed[..compressed.len() - 1])
+        .expect_err("Accepted truncated data");
+    assert_eq!(err.status(), Status::INVALID_PARAMETER);
+}
@@ -0,0 +1,198 @@
+use core::ffi::c_void;
+use uefi::prelude::*;
+use uefi::proto::device_path::DevicePath;
+use uefi::proto::driver::binding::{
+    install_driver_binding, uninstall_driver_binding, DriverBinding, DriverBindingBuilder,
+    DriverBindingInterface,
+};
+use uefi::proto::driver::component_name::{
+    install_component_name, uninstall_component_name, ComponentName2,
+};
+use uefi::proto::Protocol;
+use uefi::table::boot::{BootServices, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol};
+use uefi::{unsafe_guid, CStr8, Identify};
+
+/// Protocol of the toy controller managed by the test driver.
+#[repr(C)]
+#[unsafe_guid("4cfa6c69-2482-4824-b40f-e743d2a1ebb2")]
+struct ToyController {
+    id: u32,
+}
+
+impl Protocol for ToyController {}
+
+/// Protocol installed on the child controller created by the test driver.
+#[repr(C)]
+#[unsafe_guid("412845ce-4260-4f84-97b4-06203c38e468")]
+struct ToyChild {
+    id: u32,
+}
+
+impl Protocol for ToyChild {}
+
+static DRIVER_NAMES: [(&str, &str); 2] = [
+    ("en", "uefi-rs test driver"),
+    ("fr", "Pilote de test uefi-rs"),
+];
+
+static mut CONTROLLER: ToyController = ToyController { id: 1 };
+static mut CHILD: ToyChild = ToyChild { id: 2 };
+static mut BINDING: Option<DriverBindingInterface> = None;
+
+/// State of the test driver while it is started.
+static mut OPENED_CONTROLLER: Option<ScopedProtocol<ToyController>> = None;
+static mut CHILD_HANDLE: Option<Handle> = None;
+
+pub fn test(image: Handle, bt: &BootServices) {
+    info!("Running driver binding test");
+
+    let controller = unsafe {
+        bt.install_protocol_interface(
+            None,
+            &ToyController::GUID,
+            &mut CONTROLLER as *mut ToyController as *mut c_void,
+        )
+    }
+    .expect_success("Failed to install the toy controller");
+
+    let binding = unsafe {
+        BINDING.get_or_insert_with(|| DriverBindingBuilder::new(supported, start, stop).build())
+    };
+    install_driver_binding(bt, image, binding).expect_success("Failed to install driver binding");
+    install_component_name(bt, image, &DRIVER_NAMES)
+        .expect_success("Failed to install component name");
+    test_component_names(image, bt);
+
+    bt.connect_controller(controller, Some(image), None, false)
+        .expect_success("Failed to connect the test driver");
+    let child = unsafe { CHILD_HANDLE }.expect("The test driver did not create a child");
+    let child_protocol = bt
+        .handle_protocol::<ToyChild>(child)
+        .expect_success("Failed to open the child protocol");
+    assert_eq!(unsafe { (*child_protocol.get()).id }, 2);
+
+    bt.disconnect_controller(controller, Some(image), None)
+        .expect_success("Failed to disconnect the test driver");
+    assert!(unsafe { CHILD_HANDLE.is_none() });
+    assert!(bt.handle_protocol::<ToyChild>(child).is_err());
+
+    uninstall_component_name(bt, image).expect_success("Failed to uninstall component name");
+    uninstall_driver_binding(bt, image).expect_success("Failed to uninstall driver binding");
+    unsafe {
+        bt.uninstall_protocol_interface(
+            controller,
+            &ToyController::GUID,
+            &mut CONTROLLER as *mut ToyController as *mut c_void,
+        )
+    }
+    .expect_success("Failed to uninstall the toy controller");
+}
+
+/// Prints the English names of the drivers, and checks the names of the
+/// test driver.
+fn test_component_names(image: Handle, bt: &BootServices) {
+    let handles = bt
+        .find_handles::<ComponentName2>()
+        .expect_success("Failed to find drivers with component names");
+    for handle in handles {
+        let component_name = bt
+            .handle_protocol::<ComponentName2>(handle)
+            .expect_success("Failed to open component name protocol");
+        let component_name = unsafe { &*component_name.get() };
+        match component_name.best_driver_name(&["en-US", "en"]) {
+            Ok(name) => info!("Driver: {}", name.log()),
+            Err(error) => warn!("Driver without a name: {:?}", error.status()),
+        }
+    }
+
+    let component_name = bt
+        .handle_protocol::<ComponentName2>(image)
+        .expect_success("Failed to open the component name of the test driver");
+    let component_name = unsafe { &*component_name.get() };
+    assert!(component_name.supported_languages().iter().eq(["en", "fr"]));
+    let name = component_name
+        .best_driver_name(&["fr-FR"])
+        .expect_success("Failed to get the name of the test driver");
+    assert_eq!(name, "Pilote de test uefi-rs");
+    // Unsupported languages fall back to the first supported language.
+    let name = component_name
+        .best_driver_name(&["de-DE"])
+        .expect_success("Failed to get the name of the test driver");
+    assert_eq!(name, "uefi-rs test driver");
+    let language = CStr8::from_bytes_with_nul(b"de\0").unwrap();
+    assert_eq!(
+        component_name.driver_name(language).unwrap_err().status(),
+        Status::UNSUPPORTED
+    );
+}
+
+fn supported(
+    binding: &DriverBinding,
+    controller: Handle,
+    _remaining_device_path: Option<&DevicePath>,
+) -> uefi::Result {
+    // Opening the protocol by driver fails if it is not installed, or if the
+    // controller is already managed. The protocol is closed when dropped.
+    uefi_services::boot_services()
+        .open_protocol::<ToyController>(
+            OpenProtocolParams {
+                handle: controller,
+                agent: binding.driver_binding_handle(),
+                controller: Some(controller),
+            },
+            OpenProtocolAttributes::ByDriver,
+        )
+        .map_inner(|_| ())
+}
+
+fn start(
+    binding: &DriverBinding,
+    controller: Handle,
+    _remaining_device_path: Option<&DevicePath>,
+) -> uefi::Result {
+    let bt = uefi_services::boot_services();
+    let opened = bt
+        .open_protocol::<ToyController>(
+            OpenProtocolParams {
+                handle: controller,
+                agent: binding.driver_binding_handle(),
+                controller: Some(controller),
+            },
+            OpenProtocolAttributes::ByDriver,
+        )?
+        .log();
+    assert_eq!(unsafe { (*opened.get()).id }, 1);
+
+    let child = unsafe {
+        bt.install_protocol_interface(
+            None,
+            &ToyChild::GUID,
+            &mut CHILD as *mut ToyChild as *mut c_void,
+        )
+    }?
+    .log();
+
+    unsafe {
+        OPENED_CONTROLLER = Some(opened);
+        CHILD_HANDLE = Some(child);
+    }
+    Status::SUCCESS.into()
+}
+
+fn stop(_binding: &DriverBinding, _controller: Handle, _children: &[Handle]) -> uefi::Result {
+    // The child doesn't open the protocol of the controller, so the firmware
+    // doesn't know about it, and the driver is stopped with no children.
+    unsafe {
+        if let Some(child) = CHILD_HANDLE.take() {
+            uefi_services::boot_services()
+                .uninstall_protocol_interface(
+                    child,
+                    &ToyChild::GUID,
+                    &mut CHILD as *mut ToyChild as *mut c_void,
+                )?
+                .log();
+        }
+        OPENED_CONTROLLER = None;
+    }
+    Status::SUCCESS.into()
+}
@@ -0,0 +1,41 @@
+use uefi::prelude::*;
+use uefi::proto::firmware_management::FirmwareManagement;
+use uefi::table::boot::BootServices;
+
+pub fn test(bt: &BootServices) {
+    info!("Running firmware management protocol test");
+
+    let handles = bt
+        .find_handles::<FirmwareManagement>()
+        .expect_success("Failed to get firmware management handles");
+    if handles.is_empty() {
+        warn!("Firmware management protocol is not supported");
+        return;
+    }
+
+    for handle in handles {
+        let fmp = bt
+            .handle_protocol::<FirmwareManagement>(handle)
+            .expect_success("Failed to open firmware management protocol");
+        let fmp = unsafe { &*fmp.get() };
+
+        let mut buffer = [0; 4096];
+        let info = fmp
+            .get_image_info(bt, &mut buffer)
+            .expect_success("Failed to get image info");
+        info!(
+            "Firmware package version {:#x} ({:?}), with {} images",
+            info.package_version,
+            info.package_version_name,
+            info.descriptors.len()
+        );
+        for image in info.descriptors.iter() {
+            info!("Firmware image: {:?}", image);
+        }
+
+        match fmp.get_package_info(bt) {
+            Ok(package) => info!("Firmware package: {:?}", package.unwrap()),
+            Err(err) => info!("No firmware package info: {:?}", err.status()),
+        }
+    }
+}
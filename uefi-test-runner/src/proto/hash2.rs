@@ -0,0 +1,58 @@
+use uefi::prelude::*;
+use uefi::proto::hash2::{self, Hash2ServiceBinding};
+use uefi::table::boot::BootServices;
+
+/// SHA-256 digest of "abc", from FIPS 180-2.
+const ABC_SHA256: [u8; 32] = [
+    0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23,
+    0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00, 0x15, 0xad,
+];
+
+pub fn test(image: Handle, bt: &BootServices) {
+    info!("Running Hash2 protocol test");
+
+    if let Ok(binding) = bt.locate_protocol::<Hash2ServiceBinding>() {
+        let binding = binding.expect("Warnings encountered while opening Hash2 service binding");
+        let binding = unsafe { &mut *binding.get() };
+
+        let child = binding
+            .create_child()
+            .expect_success("Failed to create Hash2 child");
+        binding
+            .destroy_child(child)
+            .expect_success("Failed to destroy Hash2 child");
+
+        let child = binding
+            .create_scoped_child()
+            .expect_success("Failed to create Hash2 child");
+        let hash2 = child
+            .open(bt, image)
+            .expect_success("Failed to open Hash2 protocol on child");
+        let hash2 = unsafe { &mut *hash2.get() };
+
+        let size = hash2
+            .get_hash_size(&hash2::ALGORITHM_SHA256)
+            .expect_success("Failed to get SHA-256 digest size");
+        assert_eq!(size, 32);
+
+        let digest = hash2
+            .hash(&hash2::ALGORITHM_SHA256, b"abc")
+            .expect_success("Failed to hash message");
+        assert_eq!(digest.as_bytes(), &ABC_SHA256[..]);
+
+        hash2
+            .hash_init(&hash2::ALGORITHM_SHA256)
+            .expect_success("Failed to start streaming hash");
+        for part in &[&b"a"[..], b"bc"] {
+            hash2
+                .hash_update(part)
+                .expect_success("Failed to update streaming hash");
+        }
+        let streamed = hash2
+            .hash_final(&hash2::ALGORITHM_SHA256)
+            .expect_success("Failed to finish streaming hash");
+        assert_eq!(streamed, digest);
+    } else {
+        info!("Hash2 protocol is not supported");
+    }
+}
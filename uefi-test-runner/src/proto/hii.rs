@@ -0,0 +1,226 @@
+use core::convert::TryFrom;
+use uefi::prelude::*;
+use uefi::proto::console::gop::{BltOp, BltPixel, BltRegion, GraphicsOutput};
+use uefi::proto::hii::database::HiiDatabase;
+use uefi::proto::hii::font::{FontDisplayInfo, HiiFont, OutFlags};
+use uefi::proto::hii::string::HiiString;
+use uefi::proto::hii::{Package, PackageList, PackageType};
+use uefi::table::boot::BootServices;
+use uefi::{guid, CStr8, CString16, Guid};
+
+pub fn test(bt: &BootServices) {
+    info!("Running HII protocols test");
+
+    let database = match bt.locate_protocol::<HiiDatabase>() {
+        Ok(database) => database.expect("Warnings encountered while opening HII database"),
+        Err(_) => {
+            warn!("HII database protocol is not supported");
+            return;
+        }
+    };
+    let database = unsafe { &*database.get() };
+
+    test_database(database);
+
+    if let Ok(string) = bt.locate_protocol::<HiiString>() {
+        let string = string.expect("Warnings encountered while opening HII string protocol");
+        test_string(database, unsafe { &*string.get() });
+    } else {
+        warn!("HII string protocol is not supported");
+    }
+
+    if let Ok(font) = bt.locate_protocol::<HiiFont>() {
+        let font = font.expect("Warnings encountered while opening HII font protocol");
+        test_font(bt, unsafe { &*font.get() });
+    } else {
+        warn!("HII font protocol is not supported");
+    }
+}
+
+fn test_database(database: &HiiDatabase) {
+    let handles = database
+        .list_package_lists_to_vec(PackageType::ALL, None)
+        .expect_success("Failed to list package lists");
+    info!("The HII database has {} package lists", handles.len());
+    let first = *handles.first().expect("The HII database is empty");
+
+    let exported = database
+        .export_package_lists_to_vec(Some(first))
+        .expect_success("Failed to export package list");
+    let (list, rest) = PackageList::parse(&exported).expect("Invalid package list");
+    assert!(rest.is_empty());
+    info!(
+        "First package list: {:?}, with packages of types {:?}",
+        list,
+        list.packages()
+            .map(|package| package.package_type)
+            .collect::<crate::alloc::vec::Vec<_>>()
+    );
+
+    // Publish a package list with a GUID package, whose data starts with
+    // the GUID of its format.
+    const TEST_GUID: Guid = guid!("2b8b4bfd-b9f8-4e33-8a66-1f3ac2ab1c8c");
+    let mut data = [0x5a; 24];
+    data[..16].copy_from_slice(&TEST_GUID.to_bytes());
+    let packages = [Package {
+        package_type: PackageType::TYPE_GUID,
+        data: &data,
+    }];
+    let mut buffer = [0; 64];
+    let list = PackageList::build(&mut buffer, TEST_GUID, &packages)
+        .expect_success("Failed to build package list");
+    let handle = database
+        .new_package_list(list, None)
+        .expect_success("Failed to register package list");
+
+    let guid_handles = database
+        .list_package_lists_to_vec(PackageType::TYPE_GUID, Some(&TEST_GUID))
+        .expect_success("Failed to list GUID package lists");
+    assert_eq!(guid_handles, [handle]);
+    let exported = database
+        .export_package_lists_to_vec(Some(handle))
+        .expect_success("Failed to export package list");
+    assert_eq!(exported, list.as_bytes());
+    assert_eq!(
+        database
+            .get_package_list_handle(handle)
+            .expect_success("Failed to get driver handle"),
+        None
+    );
+
+    database
+        .remove_package_list(handle)
+        .expect_success("Failed to remove package list");
+    assert!(database
+        .list_package_lists_to_vec(PackageType::TYPE_GUID, Some(&TEST_GUID))
+        .expect_success("Failed to list GUID package lists")
+        .is_empty());
+
+    let mut layout = [0; 1024];
+    match database.get_keyboard_layout(None, &mut layout) {
+        Ok(layout) => info!("Keyboard layout: {:?}", layout.unwrap()),
+        Err(err) => info!("No keyboard layout: {:?}", err.status()),
+    }
+}
+
+/// Copies a language tag into `buffer`, with a null terminator.
+fn language_tag<'buf>(tag: &str, buffer: &'buf mut [u8]) -> &'buf CStr8 {
+    buffer[..tag.len()].copy_from_slice(tag.as_bytes());
+    buffer[tag.len()] = 0;
+    CStr8::from_bytes_with_nul(&buffer[..=tag.len()]).expect("Invalid language tag")
+}
+
+fn test_string(database: &HiiDatabase, string: &HiiString) {
+    let handles = database
+        .list_package_lists_to_vec(PackageType::STRINGS, None)
+        .expect_success("Failed to list string package lists");
+    let first = *handles.first().expect("No package list has strings");
+
+    let mut buffer = [0; 256];
+    let languages = string
+        .get_languages(first, &mut buffer)
+        .expect_success("Failed to get languages");
+    info!(
+        "Languages of the first string package list: {:?}",
+        languages
+    );
+    let language = languages.iter().next().expect("No languages");
+    assert_eq!(languages.best_match(&["xx-XX", language]), Some(language));
+
+    let mut tag = [0; 32];
+    let language = language_tag(language, &mut tag);
+    let mut text = [0; 256];
+    // String identifiers start at 1.
+    let text = string
+        .get_string(language, first, 1, &mut text)
+        .expect_success("Failed to get string");
+    info!("First string: {}", text);
+
+    // Strings can be added to our own package lists.
+    const TEST_GUID: Guid = guid!("5e7fa5c6-2a54-4f3c-9d25-1c7b6c3f1e0a");
+    let mut buffer = [0; 64];
+    let list = PackageList::build(&mut buffer, TEST_GUID, &[])
+        .expect_success("Failed to build package list");
+    let handle = database
+        .new_package_list(list, None)
+        .expect_success("Failed to register package list");
+
+    let language = language_tag("en-US", &mut tag);
+    let hello = CString16::try_from("Hello").unwrap();
+    let id = string
+        .new_string(handle, language, None, &hello)
+        .expect_success("Failed to add string");
+    let world = CString16::try_from("World").unwrap();
+    string
+        .set_string(handle, id, language, &world)
+        .expect_success("Failed to set string");
+    let mut text = [0; 16];
+    let read = string
+        .get_string(language, handle, id, &mut text)
+        .expect_success("Failed to get string");
+    assert_eq!(read.to_u16_slice(), world.to_u16_slice());
+
+    let mut small = [0; 2];
+    let err = string
+        .get_string(language, handle, id, &mut small)
+        .expect_err("Read a string into a too small buffer");
+    assert_eq!(err.status(), Status::BUFFER_TOO_SMALL);
+    assert_eq!(*err.data(), Some(6));
+
+    let mut buffer = [0; 64];
+    let languages = string
+        .get_languages(handle, &mut buffer)
+        .expect_success("Failed to get languages");
+    assert_eq!(languages.as_str(), "en-US");
+
+    database
+        .remove_package_list(handle)
+        .expect_success("Failed to remove package list");
+}
+
+fn test_font(bt: &BootServices, font: &HiiFont) {
+    const WIDTH: usize = 200;
+    const HEIGHT: usize = 40;
+    let background = BltPixel::new(0, 0, 0x80);
+    let info = FontDisplayInfo::new()
+        .foreground(BltPixel::new(0xff, 0xff, 0))
+        .background(background);
+
+    let text = CString16::try_from("uefi-rs").unwrap();
+    let mut pixels = crate::alloc::vec![background; WIDTH * HEIGHT];
+    font.string_to_image(
+        OutFlags::CLIP,
+        &text,
+        Some(&info),
+        &mut pixels,
+        WIDTH,
+        (4, 4),
+    )
+    .expect_success("Failed to render string");
+    assert!(
+        pixels.iter().any(|pixel| pixel.red != background.red),
+        "Nothing was rendered"
+    );
+
+    let err = font
+        .string_to_image(OutFlags::CLIP, &text, None, &mut pixels, WIDTH, (WIDTH, 0))
+        .expect_err("Rendered a string outside of the image");
+    assert_eq!(err.status(), Status::INVALID_PARAMETER);
+
+    let glyph = font
+        .get_glyph(bt, uefi::Char16::try_from('u').unwrap(), Some(&info))
+        .expect_success("Failed to get glyph");
+    info!("Glyph of 'u': {:?}", glyph);
+    assert_eq!(glyph.pixels().len(), glyph.width() * glyph.height());
+
+    if let Ok(gop) = bt.locate_protocol::<GraphicsOutput>() {
+        let gop = unsafe { &mut *gop.expect("Warnings encountered while opening GOP").get() };
+        gop.blt(BltOp::BufferToVideo {
+            buffer: &pixels,
+            src: BltRegion::Full,
+            dest: (0, 0),
+            dims: (WIDTH, HEIGHT),
+        })
+        .expect_success("Failed to blit rendered string");
+    }
+}
@@ -0,0 +1,157 @@
+use crate::alloc::string::String;
+use crate::alloc::vec::Vec;
+use uefi::prelude::*;
+use uefi::proto::media::file::{
+    Directory, File, FileAttribute, FileMode, FileType, RegularFile, WalkDir,
+};
+
+/// Tests the file system helpers on the directory tree created by `build.py`
+/// at the root of the boot volume.
+pub fn test(image: Handle, bt: &BootServices, root: &mut Directory) {
+    info!("Testing file paths");
+
+    if root
+        .open("test_tree", FileMode::Read, FileAttribute::empty())
+        .is_err()
+    {
+        warn!("The test directory tree is not on the boot volume");
+        return;
+    }
+
+    test_open_path(root);
+    test_walk(root);
+    test_read_file(image, bt);
+}
+
+fn open_path(root: &mut Directory, path: &str) -> uefi::Result<FileType> {
+    root.open_path(path, FileMode::Read, FileAttribute::empty())?
+        .log()
+        .into_type()
+}
+
+fn read_contents(mut file: RegularFile) -> Vec<u8> {
+    let mut buffer = [0; 16];
+    let size = file
+        .read(&mut buffer)
+        .expect_success("Failed to read the test file");
+    buffer[..size].to_vec()
+}
+
+fn test_open_path(root: &mut Directory) {
+    for path in &[
+        "test_tree\\nested\\b.txt",
+        "test_tree/nested/b.txt",
+        "\\test_tree//nested/./deeper/../b.txt",
+        "test_tree\\NESTED\\B.TXT",
+    ] {
+        match open_path(root, path).expect_success("Failed to open the test file") {
+            FileType::Regular(file) => assert_eq!(read_contents(file), b"bb"),
+            FileType::Dir(_) => panic!("Opened a directory instead of {}", path),
+        }
+    }
+
+    match open_path(root, "test_tree/nested/deeper/").expect_success("Failed to open directory") {
+        FileType::Dir(_) => {}
+        FileType::Regular(_) => panic!("Opened a file instead of a directory"),
+    }
+
+    for (path, status) in &[
+        ("", Status::INVALID_PARAMETER),
+        ("./", Status::INVALID_PARAMETER),
+        ("test_tree/../..", Status::INVALID_PARAMETER),
+        ("test_tree/missing/b.txt", Status::NOT_FOUND),
+        ("test_tree/a.txt/b.txt", Status::NOT_FOUND),
+    ] {
+        match open_path(root, path) {
+            Ok(_) => panic!("Opened the invalid path {:?}", path),
+            Err(error) => assert_eq!(error.status(), *status, "Wrong error for {:?}", path),
+        }
+    }
+}
+
+/// Returns the lowercase paths, the depths and the sizes of the entries of the
+/// test tree found by a walk, in order.
+fn walk_test_tree(walk: WalkDir) -> Vec<(String, usize, u64)> {
+    let mut entries: Vec<_> = walk
+        .map(|entry| entry.expect_success("Failed to walk the boot volume"))
+        .filter(|entry| entry.path().to_ascii_lowercase().starts_with("test_tree"))
+        .map(|entry| {
+            let size = if entry.is_dir() {
+                0
+            } else {
+                entry.info().file_size()
+            };
+            (entry.path().to_ascii_lowercase(), entry.depth(), size)
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+fn test_walk(root: &mut Directory) {
+    let entries = walk_test_tree(WalkDir::new(root));
+    info!("Test tree: {:?}", entries);
+    let expected = [
+        ("test_tree", 1, 0),
+        ("test_tree\\a.txt", 2, 1),
+        ("test_tree\\empty.txt", 2, 0),
+        ("test_tree\\large.bin", 2, 3 << 20),
+        ("test_tree\\nested", 2, 0),
+        ("test_tree\\nested\\b.txt", 3, 2),
+        ("test_tree\\nested\\deeper", 3, 0),
+        ("test_tree\\nested\\deeper\\c.efi", 4, 3),
+    ];
+    assert!(entries
+        .iter()
+        .map(|(path, depth, size)| (path.as_str(), *depth, *size))
+        .eq(expected.iter().copied()));
+
+    // The subdirectories at the maximum depth are not read.
+    let entries = walk_test_tree(WalkDir::new(root).max_depth(3));
+    assert!(entries
+        .iter()
+        .map(|(path, depth, size)| (path.as_str(), *depth, *size))
+        .eq(expected[..7].iter().copied()));
+
+    // The walk finds the files in nested directories, such as the images.
+    let images = WalkDir::new(root)
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.unwrap())
+        .filter(|entry| !entry.is_dir() && entry.path().to_ascii_lowercase().ends_with(".efi"))
+        .count();
+    assert!(images >= 2, "The walk did not find the images");
+}
+
+fn test_read_file(image: Handle, bt: &BootServices) {
+    let data = bt
+        .read_file(image, "test_tree\\nested\\b.txt")
+        .expect_success("Failed to read the test file");
+    assert_eq!(data, b"bb");
+
+    let data = bt
+        .read_file(image, "test_tree/empty.txt")
+        .expect_success("Failed to read the empty file");
+    assert!(data.is_empty());
+
+    // The large file is read in several chunks.
+    let data = bt
+        .read_file(image, "test_tree/large.bin")
+        .expect_success("Failed to read the large file");
+    assert_eq!(data.len(), 3 << 20);
+    assert!(data
+        .iter()
+        .enumerate()
+        .all(|(i, &byte)| byte == (i % 251) as u8));
+
+    for (path, status) in &[
+        ("test_tree/missing.txt", Status::NOT_FOUND),
+        ("test_tree/missing/b.txt", Status::NOT_FOUND),
+        ("test_tree/a.txt/b.txt", Status::NOT_FOUND),
+        ("test_tree/nested", Status::INVALID_PARAMETER),
+    ] {
+        match bt.read_file(image, path) {
+            Ok(_) => panic!("Read the invalid path {:?}", path),
+            Err(error) => assert_eq!(error.status(), *status, "Wrong error for {:?}", path),
+        }
+    }
+}
@@ -1,47 +1,47 @@
+use crate::alloc::string::String;
+use crate::alloc::vec::Vec;
+use core::mem;
+use uefi::data_types::Align;
 use uefi::prelude::*;
+use uefi::proto::media::file::{
+    Directory, File, FileAttribute, FileInfo, FileMode, FileSystemInfo, FileType,
+};
 use uefi::proto::media::fs::SimpleFileSystem;
 use uefi::proto::media::partition::PartitionInfo;
 
-pub fn test(bt: &BootServices) {
+pub fn test(image: Handle, bt: &BootServices) {
     info!("Testing Media Access protocols");
 
-    if let Ok(sfs) = bt.locate_protocol::<SimpleFileSystem>() {
-        let sfs = sfs.expect("Cannot open `SimpleFileSystem` protocol");
-        let sfs = unsafe { &mut *sfs.get() };
-        let mut directory = sfs.open_volume().unwrap().unwrap();
-        let mut buffer = vec![0; 128];
-        loop {
-            let file_info = match directory.read_entry(&mut buffer) {
-                Ok(completion) => {
-                    if let Some(info) = completion.unwrap() {
-                        info
-                    } else {
-                        // We've reached the end of the directory
-                        break;
-                    }
-                }
-                Err(error) => {
-                    // Buffer is not big enough, allocate a bigger one and try again.
-                    let min_size = error.data().unwrap();
-                    buffer.resize(min_size, 0);
-                    continue;
-                }
-            };
-            info!("Root directory entry: {:?}", file_info);
-        }
-        directory.reset_entry_readout().unwrap().unwrap();
+    // Every volume is listed, but the file tests only run on the boot volume.
+    let volumes = bt
+        .handles_with_protocol::<SimpleFileSystem>()
+        .expect_msg("Failed to get handles for `SimpleFileSystem` protocol");
+    let mut found_volume = false;
+    for volume in volumes {
+        found_volume = true;
+        info!("Testing the volume of handle {:?}", volume.handle());
+        let fs = volume.protocol().expect_msg("Failed to get file system");
+        let fs = unsafe { &mut *fs.get() };
+        let mut directory = fs.open_volume().expect_msg("Failed to open the volume");
+        test_root_directory(&mut directory);
+        test_boxed_info(&mut directory);
+    }
+
+    if found_volume {
+        let mut directory = bt
+            .open_image_volume(image)
+            .expect_success("Failed to open the boot volume");
+        file::test(image, bt, &mut directory);
     } else {
         warn!("`SimpleFileSystem` protocol is not available");
     }
 
     let handles = bt
-        .find_handles::<PartitionInfo>()
-        .expect_success("Failed to get handles for `PartitionInfo` protocol");
+        .handles_with_protocol::<PartitionInfo>()
+        .expect_msg("Failed to get handles for `PartitionInfo` protocol");
 
     for handle in handles {
-        let pi = bt
-            .handle_protocol::<PartitionInfo>(handle)
-            .expect_success("Failed to get partition info");
+        let pi = handle.protocol().expect_msg("Failed to get partition info");
         let pi = unsafe { &*pi.get() };
 
         if let Some(mbr) = pi.mbr_partition_record() {
@@ -53,3 +53,121 @@ pub fn test(bt: &BootServices) {
         }
     }
 }
+
+mod file;
+
+fn test_root_directory(directory: &mut Directory) {
+    let mut buffer = vec![0; 128];
+    loop {
+        let file_info = match directory.read_entry(&mut buffer) {
+            Ok(completion) => {
+                if let Some(info) = completion.unwrap() {
+                    info
+                } else {
+                    // We've reached the end of the directory
+                    break;
+                }
+            }
+            Err(error) => {
+                // Buffer is not big enough, allocate a bigger one and try again.
+                let min_size = error.data().unwrap();
+                buffer.resize(min_size, 0);
+                continue;
+            }
+        };
+        info!("Root directory entry: {:?}", file_info);
+    }
+    directory.reset_entry_readout().unwrap().unwrap();
+}
+
+/// Checks that boxed infos are correctly aligned, and fill exactly their
+/// allocation, which is freed with the layout of the box when it is dropped.
+fn check_boxed_info<Info: Align + ?Sized>(info: &Info) {
+    assert_eq!(
+        (info as *const Info as *const u8 as usize) % Info::alignment(),
+        0
+    );
+    assert_eq!(mem::size_of_val(info) % Info::alignment(), 0);
+}
+
+fn test_boxed_info(root: &mut Directory) {
+    // The root directory has an empty name.
+    let info = root
+        .get_boxed_info::<FileInfo>()
+        .expect_msg("Failed to get info of the root directory");
+    check_boxed_info(&*info);
+    assert!(info.attribute().contains(FileAttribute::DIRECTORY));
+    assert_eq!(info.file_name().to_u16_slice().len(), 0);
+
+    let info = root
+        .get_boxed_info::<FileSystemInfo>()
+        .expect_msg("Failed to get info of the file system");
+    check_boxed_info(&*info);
+    info!("File system: {:?}", info);
+
+    // The boxed entries must match the entries read into a buffer.
+    let mut buffer = vec![0; 1024];
+    let mut names = Vec::new();
+    while let Some(info) = root
+        .read_entry(&mut buffer)
+        .expect_msg("Failed to read directory entry")
+    {
+        names.push((info.file_name().to_u16_slice().to_vec(), info.file_size()));
+    }
+    root.reset_entry_readout().unwrap().unwrap();
+    let mut boxed_names = Vec::new();
+    while let Some(info) = root
+        .read_boxed_entry()
+        .expect_success("Failed to read boxed directory entry")
+    {
+        check_boxed_info(&*info);
+        boxed_names.push((info.file_name().to_u16_slice().to_vec(), info.file_size()));
+    }
+    assert_eq!(names, boxed_names);
+    // Reading past the end of the directory still returns no entry.
+    assert!(root.read_boxed_entry().unwrap().unwrap().is_none());
+    root.reset_entry_readout().unwrap().unwrap();
+
+    // A long file name needs an info much larger than the first guess of
+    // most callers.
+    let mut long_name = String::new();
+    while long_name.len() < 200 {
+        long_name.push_str("long-file-name-");
+    }
+    long_name.push_str("end.txt");
+    let file = match root.open(
+        &long_name,
+        FileMode::CreateReadWrite,
+        FileAttribute::empty(),
+    ) {
+        Ok(file) => file.unwrap(),
+        Err(error) => {
+            warn!("Cannot create a file on the volume: {:?}", error.status());
+            return;
+        }
+    };
+    let mut file = match file.into_type().unwrap().unwrap() {
+        FileType::Regular(file) => file,
+        FileType::Dir(_) => panic!("Created a directory instead of a file"),
+    };
+    let info = file
+        .get_boxed_info::<FileInfo>()
+        .expect_success("Failed to get info of a file with a long name");
+    check_boxed_info(&*info);
+    assert_eq!(info.file_name(), long_name.as_str());
+    assert_eq!(info.file_size(), 0);
+
+    let mut found = false;
+    while let Some(info) = root
+        .read_boxed_entry()
+        .expect_success("Failed to read boxed directory entry")
+    {
+        check_boxed_info(&*info);
+        found |= info.file_name() == long_name.as_str();
+    }
+    assert!(found, "The file with a long name is not in the directory");
+    root.reset_entry_readout().unwrap().unwrap();
+
+    file.delete()
+        .expect_success("Failed to delete the file with a long name");
+}
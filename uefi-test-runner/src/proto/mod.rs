@@ -12,9 +12,27 @@ pub fn test(image: Handle, st: &mut SystemTable<Boot>) {
     find_protocol(bt);
     test_protocols_per_handle(image, bt);
 
+    acpi::test(bt, st.config_table());
+    adapter_info::test(bt);
     debug::test(bt);
-    media::test(bt);
+    decompress::test(bt);
+    driver::test(image, bt);
+    firmware_management::test(bt);
+    hash2::test(image, bt);
+    hii::test(bt);
+    media::test(image, bt);
+    network::test(image, bt);
+    pci::test(bt);
     pi::test(bt);
+    pkcs7::test(bt);
+    rng::test(bt);
+    security::test(image, bt);
+    shell::test(image, bt);
+    tcg::test(bt);
+    timestamp::test(bt);
+    unicode_collation::test(image, bt);
+    usb::test(bt);
+    variable_policy::test(bt, st.runtime_services());
 
     #[cfg(any(
         target_arch = "i386",
@@ -52,10 +70,23 @@ fn test_protocols_per_handle(image: Handle, bt: &BootServices) {
         .any(|guid| **guid == LoadedImage::GUID));
 }
 
+mod acpi;
+mod adapter_info;
 mod console;
 mod debug;
+mod decompress;
+mod driver;
+mod firmware_management;
+mod hash2;
+mod hii;
 mod media;
+mod network;
+mod pci;
 mod pi;
+mod pkcs7;
+mod rng;
+pub mod security;
+mod shell;
 #[cfg(any(
     target_arch = "i386",
     target_arch = "x86_64",
@@ -63,3 +94,8 @@ mod pi;
     target_arch = "aarch64"
 ))]
 mod shim;
+mod tcg;
+mod timestamp;
+mod unicode_collation;
+mod usb;
+mod variable_policy;
@@ -0,0 +1,970 @@
+use core::cell::UnsafeCell;
+use uefi::data_types::{ucs2, IpAddress, Ipv4Address, Ipv6Address, MacAddress};
+use uefi::prelude::*;
+use uefi::proto::network::arp::{self, Arp};
+use uefi::proto::network::dhcp4::{self, Dhcp4, DhcpOption, MessageType, OptionCode, State};
+use uefi::proto::network::dns4::{self, Dns4, RecordClass, RecordType};
+use uefi::proto::network::http::{self, Http};
+use uefi::proto::network::ip4config2::{Ip4Config2, Policy};
+use uefi::proto::network::ip6config::Ip6Config;
+use uefi::proto::network::mnp::{
+    CompletionToken, ConfigData, Fragment, ManagedNetwork, TransmitData,
+};
+use uefi::proto::network::mtftp4::{self, Mtftp4, TransferParams};
+use uefi::proto::network::pxe::BaseCode;
+use uefi::proto::network::snp::{NetworkState, NetworkStatistics, ReceiveFlags, SimpleNetwork};
+use uefi::proto::network::tcp4::{self, AccessPoint, ConnectionState, Tcp4};
+use uefi::proto::network::tls::{TlsConfigDataType, TlsServiceBinding};
+use uefi::proto::network::udp4::{self, Udp4};
+use uefi::proto::network::udp6::{self, Udp6};
+use uefi::proto::network::vlan::{VlanConfig, VlanEntry};
+use uefi::proto::service_binding::{ChildProtocol, ServiceBinding};
+use uefi::table::boot::{BootServices, EventType, Tpl};
+use uefi::{CStr16, CStr8};
+
+pub fn test(image: Handle, bt: &BootServices) {
+    info!("Testing network protocols");
+
+    if let Ok(snp) = bt.locate_protocol::<SimpleNetwork>() {
+        let snp = snp.expect("Warnings encountered while opening SNP");
+        let snp = unsafe { &mut *snp.get() };
+        test_snp(snp);
+    } else {
+        warn!("No network interface found");
+    }
+
+    let gateway_mac = test_mnp(image, bt);
+    // The DHCP4 test must run before any other protocol configures the
+    // interface through DHCP, since only one DHCP client can be active.
+    test_dhcp4(image, bt);
+    test_pxe(bt);
+    test_ip4config2(bt);
+    test_arp(image, bt, gateway_mac);
+    if let Some(address) = test_ip6config(bt) {
+        test_udp6(image, bt, address);
+    }
+    test_dns4(image, bt);
+    test_mtftp4(image, bt);
+    test_http(image, bt);
+    test_tls(image, bt);
+    test_tcp4(image, bt);
+    test_udp4(image, bt);
+    // Changing the VLANs restarts the network stack, so this test runs last.
+    test_vlan(bt);
+}
+
+/// Opens the service binding of the first network interface which supports
+/// protocol `P`.
+fn open_service_binding<P: ChildProtocol>(
+    bt: &BootServices,
+) -> Option<&UnsafeCell<ServiceBinding<P>>> {
+    let handles = bt
+        .find_handles::<ServiceBinding<P>>()
+        .expect_success("Failed to get handles for service binding");
+    let binding = bt
+        .handle_protocol::<ServiceBinding<P>>(*handles.first()?)
+        .expect_success("Failed to open service binding");
+    Some(binding)
+}
+
+fn test_snp(snp: &mut SimpleNetwork) {
+    info!("Running SNP test");
+
+    // The interface may already have been brought up by the network stack.
+    if snp.mode().state() == NetworkState::STOPPED {
+        snp.start()
+            .expect_success("Failed to start network interface");
+    }
+    if snp.mode().state() == NetworkState::STARTED {
+        snp.initialize(0, 0)
+            .expect_success("Failed to initialize network interface");
+    }
+    assert_eq!(snp.mode().state(), NetworkState::INITIALIZED);
+
+    let mode = snp.mode();
+    let addr_size = mode.hw_address_size() as usize;
+    info!(
+        "Network interface {} has an MTU of {} bytes",
+        mode.current_address().display(addr_size),
+        mode.max_packet_size()
+    );
+
+    let filters = ReceiveFlags::UNICAST | ReceiveFlags::BROADCAST;
+    snp.receive_filters(
+        filters & snp.mode().receive_filter_mask(),
+        ReceiveFlags::empty(),
+    )
+    .expect_success("Failed to set receive filters");
+
+    test_mcast(snp);
+
+    let stats_before = statistics(snp);
+
+    // Broadcast a frame with the EtherType reserved for local experiments.
+    let header_size = snp.mode().media_header_size() as usize;
+    let mut frame = [0; 64];
+    frame[header_size..header_size + 12].copy_from_slice(b"uefi-rs test");
+    let broadcast = *snp.mode().broadcast_address();
+    snp.transmit(
+        header_size,
+        &mut frame,
+        None,
+        Some(&broadcast),
+        Some(0x88b5),
+    )
+    .expect_success("Failed to transmit frame");
+
+    // Nothing is expected to be received, but the call must not fail.
+    let mut buffer = [0; 1536];
+    let received = snp
+        .receive(&mut buffer)
+        .expect_success("Failed to receive frame");
+    info!("Received frame: {:?}", received);
+
+    if let Some(stats) = stats_before {
+        let stats_after = statistics(snp).expect("Statistics are no longer supported");
+        if let (Some(before), Some(after)) = (stats.tx_good_frames(), stats_after.tx_good_frames())
+        {
+            assert!(after > before, "Transmitted frame was not counted");
+        }
+    }
+}
+
+/// Reads the statistics of the interface, if it supports them.
+fn statistics(snp: &mut SimpleNetwork) -> Option<NetworkStatistics> {
+    match snp.statistics() {
+        Ok(stats) => {
+            let stats = stats.expect("Warnings encountered while reading statistics");
+            info!("Network statistics: {:?}", stats);
+            Some(stats)
+        }
+        Err(err) if err.status() == Status::UNSUPPORTED => {
+            info!("Network statistics are not supported");
+            None
+        }
+        Err(err) => panic!("Failed to read network statistics: {:?}", err),
+    }
+}
+
+fn test_mcast(snp: &mut SimpleNetwork) {
+    // mDNS uses 224.0.0.251, which maps to 01:00:5e:00:00:fb.
+    let ip = IpAddress::from(Ipv4Address([224, 0, 0, 251]));
+    let mac = snp
+        .mcast_ip_to_mac(false, &ip)
+        .expect_success("Failed to convert multicast IP address");
+    assert_eq!(mac, MacAddress::from([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]));
+
+    if !snp
+        .mode()
+        .receive_filter_mask()
+        .contains(ReceiveFlags::MULTICAST)
+    {
+        info!("Multicast filtering is not supported");
+        return;
+    }
+    snp.set_mcast_filter(&[mac])
+        .expect_success("Failed to set multicast filter");
+    assert_eq!(snp.mode().mcast_filter(), &[mac]);
+    snp.reset_mcast_filter()
+        .expect_success("Failed to reset multicast filter");
+}
+
+/// Returns the hardware address of the gateway, if it replied to the ARP
+/// request.
+fn test_mnp(image: Handle, bt: &BootServices) -> Option<[u8; 6]> {
+    info!("Running MNP test");
+
+    let binding = if let Some(binding) = open_service_binding::<ManagedNetwork>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No MNP service binding found");
+        return None;
+    };
+
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create MNP child");
+    let mnp = child
+        .open(bt, image)
+        .expect_success("Failed to open MNP on child");
+    let mnp = unsafe { &mut *mnp.get() };
+
+    const ETHERTYPE_ARP: u16 = 0x0806;
+    let config = ConfigData {
+        protocol_type_filter: ETHERTYPE_ARP,
+        enable_unicast_receive: true,
+        enable_broadcast_receive: true,
+        ..ConfigData::default()
+    };
+    mnp.configure(Some(&config))
+        .expect_success("Failed to configure MNP");
+    let (_, mode) = mnp
+        .get_mode_data()
+        .expect_success("Failed to get MNP mode data");
+    let own_addr = *mode.current_address();
+    let broadcast = *mode.broadcast_address();
+
+    let event = unsafe { bt.create_event(EventType::empty(), Tpl::CALLBACK, None) }
+        .expect_success("Failed to create MNP event");
+
+    // Ask the QEMU user-mode gateway for its address. The request and its
+    // header are sent as separate fragments, and the reply is sent back to
+    // our own address.
+    let mut request = [0; 28];
+    request[..8].copy_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x01]);
+    request[8..14].copy_from_slice(&own_addr.as_bytes()[..6]);
+    request[14..18].copy_from_slice(&[10, 0, 2, 15]);
+    request[24..28].copy_from_slice(&[10, 0, 2, 2]);
+    let fragments = [
+        Fragment::new(&request[..8]).unwrap(),
+        Fragment::new(&request[8..]).unwrap(),
+    ];
+    let data = TransmitData::new(&broadcast, None, ETHERTYPE_ARP, &fragments).unwrap();
+    mnp.transmit(&event, &data)
+        .expect_success("Failed to transmit ARP request");
+
+    let mut token = unsafe { CompletionToken::new(&event) };
+    let mut gateway_mac = None;
+    for _ in 0..100 {
+        let packet = mnp
+            .receive(&mut token, 1000)
+            .expect_success("Failed to receive packet");
+        if let Some(packet) = packet {
+            let is_reply = packet.data().get(6..8) == Some(&[0x00, 0x02]);
+            if is_reply && packet.dest_addr() == &own_addr.as_bytes()[..6] {
+                let mut mac = [0; 6];
+                mac.copy_from_slice(&packet.src_addr()[..6]);
+                gateway_mac = Some(mac);
+            }
+            packet
+                .recycle(bt)
+                .expect_success("Failed to recycle packet");
+            if gateway_mac.is_some() {
+                break;
+            }
+        }
+        bt.stall(10_000);
+    }
+    if gateway_mac.is_none() {
+        warn!("No ARP reply received from the gateway");
+    }
+
+    bt.close_event(event)
+        .expect_success("Failed to close MNP event");
+    mnp.configure(None).expect_success("Failed to reset MNP");
+    gateway_mac
+}
+
+fn test_dhcp4(image: Handle, bt: &BootServices) {
+    info!("Running DHCP4 test");
+
+    let binding = if let Some(binding) = open_service_binding::<Dhcp4>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No DHCP4 service binding found");
+        return;
+    };
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create DHCP4 child");
+    let dhcp = child
+        .open(bt, image)
+        .expect_success("Failed to open DHCP4 on child");
+    let dhcp = unsafe { &mut *dhcp.get() };
+
+    let mut request_list = [0; 8];
+    let request_list = DhcpOption::parameter_request_list(
+        &mut request_list,
+        &[
+            OptionCode::SUBNET_MASK,
+            OptionCode::ROUTER,
+            OptionCode::DNS_SERVER,
+        ],
+    )
+    .expect_success("Failed to build parameter request list");
+    let config = dhcp4::ConfigData {
+        options: &[request_list],
+        ..dhcp4::ConfigData::default()
+    };
+    match dhcp.configure(Some(&config)) {
+        Ok(completion) => completion.expect("Warnings encountered while configuring DHCP4"),
+        Err(err) if err.status() == Status::ACCESS_DENIED => {
+            warn!("Another DHCP4 client is already configured");
+            return;
+        }
+        Err(err) => panic!("Failed to configure DHCP4: {:?}", err.status()),
+    }
+    let mode = dhcp
+        .get_mode_data()
+        .expect_success("Failed to get DHCP4 mode data");
+    assert_eq!(mode.state, State::INIT);
+
+    // QEMU's user-mode network stack always leases the same address.
+    dhcp.start(None).expect_success("Failed to acquire a lease");
+    let mode = dhcp
+        .get_mode_data()
+        .expect_success("Failed to get DHCP4 mode data");
+    assert_eq!(mode.state, State::BOUND);
+    assert_eq!(mode.client_address, Ipv4Address([10, 0, 2, 15]));
+    assert_eq!(mode.subnet_mask, Ipv4Address([255, 255, 255, 0]));
+
+    let reply = mode.reply_packet.expect("No DHCP reply packet");
+    assert_eq!(reply.message_type(), Some(MessageType::ACK));
+    assert_eq!(reply.your_address(), mode.client_address);
+
+    // The options parsed by the firmware must match those parsed by the
+    // host-side iterator.
+    let mut options = [None; dhcp4::MAX_OPTIONS];
+    let count = dhcp
+        .parse(reply, &mut options)
+        .expect_success("Failed to parse DHCP reply");
+    assert_eq!(count, reply.options().count());
+    for (parsed, option) in options.iter().zip(reply.options()) {
+        assert_eq!(parsed.unwrap().as_bytes(), option.as_bytes());
+    }
+
+    let packet = dhcp
+        .build(bt, reply, &[OptionCode::DNS_SERVER], &[])
+        .expect_success("Failed to build DHCP packet");
+    assert!(packet.option(OptionCode::DNS_SERVER).is_none());
+    assert_eq!(packet.message_type(), Some(MessageType::ACK));
+    drop(packet);
+
+    dhcp.release().expect_success("Failed to release the lease");
+    let mode = dhcp
+        .get_mode_data()
+        .expect_success("Failed to get DHCP4 mode data");
+    assert_eq!(mode.state, State::INIT);
+    dhcp.stop().expect_success("Failed to stop DHCP4");
+    dhcp.configure(None).expect_success("Failed to reset DHCP4");
+}
+
+fn test_pxe(bt: &BootServices) {
+    info!("Running PXE base code test");
+
+    let pxe = if let Ok(pxe) = bt.locate_protocol::<BaseCode>() {
+        pxe.expect("Warnings encountered while opening PXE base code")
+    } else {
+        warn!("No PXE base code found");
+        return;
+    };
+    let pxe = unsafe { &mut *pxe.get() };
+
+    let started_here = match pxe.start(false) {
+        Ok(completion) => {
+            completion.expect("Warnings encountered while starting PXE base code");
+            true
+        }
+        Err(err) if err.status() == Status::ALREADY_STARTED => false,
+        Err(err) => panic!("Failed to start PXE base code: {:?}", err.status()),
+    };
+    let mode = pxe.mode();
+    assert!(mode.started());
+    assert!(!mode.using_ipv6());
+
+    // The DHCP server of QEMU does not send the PXE options, which some
+    // implementations require.
+    match pxe.dhcp(false) {
+        Ok(completion) => {
+            completion.expect("Warnings encountered during PXE DHCP");
+            let mode = pxe.mode();
+            assert_eq!(mode.station_ip().as_ipv4(), Ipv4Address([10, 0, 2, 15]));
+            let discover = mode.dhcp_discover().expect("No DHCP discover cached");
+            assert_eq!(discover.message_type(), Some(MessageType::DISCOVER));
+            let ack = mode.dhcp_ack().expect("No DHCP acknowledgement cached");
+            assert_eq!(ack.message_type(), Some(MessageType::ACK));
+            assert_eq!(ack.your_address(), Ipv4Address([10, 0, 2, 15]));
+        }
+        Err(err) => warn!("PXE DHCP failed: {:?}", err.status()),
+    }
+
+    if started_here {
+        pxe.stop().expect_success("Failed to stop PXE base code");
+    }
+}
+
+fn test_dns4(image: Handle, bt: &BootServices) {
+    info!("Running DNS4 test");
+
+    let binding = if let Some(binding) = open_service_binding::<Dns4>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No DNS4 service binding found");
+        return;
+    };
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create DNS4 child");
+    let dns = child
+        .open(bt, image)
+        .expect_success("Failed to open DNS4 on child");
+    let dns = unsafe { &mut *dns.get() };
+
+    // Query the resolver of QEMU's user-mode network stack.
+    let servers = [Ipv4Address([10, 0, 2, 3])];
+    let config = dns4::ConfigData {
+        dns_servers: &servers,
+        ..dns4::ConfigData::default()
+    };
+    dns.configure(Some(&config))
+        .expect_success("Failed to configure DNS4");
+    let mode = dns
+        .get_mode_data(bt)
+        .expect_success("Failed to get DNS4 mode data");
+    assert_eq!(mode.config().dns_servers, servers);
+    assert!(mode.config().enable_dns_cache);
+    drop(mode);
+
+    // Entries added to the cache are resolved without querying the server.
+    let mut buffer = [0; 16];
+    let len =
+        ucs2::encode_str("uefi-rs.test", &mut buffer).expect_success("Failed to encode host name");
+    let host_name = CStr16::from_u16_with_nul(&buffer[..len]).expect("Invalid host name");
+    let cached = Ipv4Address([10, 0, 2, 99]);
+    dns.add_cache_entry(host_name, cached, 60, false)
+        .expect_success("Failed to add DNS cache entry");
+    let mode = dns
+        .get_mode_data(bt)
+        .expect_success("Failed to get DNS4 mode data");
+    assert!(mode.cache().any(
+        |entry| entry.host_name.to_u16_slice() == host_name.to_u16_slice()
+            && entry.address == cached
+    ));
+    drop(mode);
+    let addresses = dns
+        .host_name_to_ip_blocking(bt, host_name)
+        .expect_success("Failed to resolve cached host name");
+    assert_eq!(addresses.as_slice(), [cached]);
+    drop(addresses);
+    dns.remove_cache_entry(host_name, cached)
+        .expect_success("Failed to remove DNS cache entry");
+
+    // The host may not have access to a DNS server.
+    match dns.resolve(bt, "example.com") {
+        Ok(completion) => {
+            let addresses = completion.expect("Warnings encountered while resolving");
+            info!("example.com resolved to {:?}", addresses);
+            assert!(!addresses.is_empty());
+
+            let name = CStr8::from_bytes_with_nul(b"example.com\0").unwrap();
+            let records = dns
+                .general_lookup_blocking(bt, name, RecordType::A, RecordClass::IN)
+                .expect_success("Failed to look up A records");
+            assert!(records
+                .iter()
+                .any(|record| record.record_type == RecordType::A && record.data.len() == 4));
+        }
+        Err(err) => warn!("Failed to resolve example.com: {:?}", err.status()),
+    }
+
+    dns.configure(None).expect_success("Failed to reset DNS4");
+}
+
+fn test_mtftp4(image: Handle, bt: &BootServices) {
+    info!("Running MTFTP4 test");
+
+    let binding = if let Some(binding) = open_service_binding::<Mtftp4>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No MTFTP4 service binding found");
+        return;
+    };
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create MTFTP4 child");
+    let tftp = child
+        .open(bt, image)
+        .expect_success("Failed to open MTFTP4 on child");
+    let tftp = unsafe { &mut *tftp.get() };
+
+    // The build script serves a test file with QEMU's built-in TFTP server,
+    // which listens on the address of the host.
+    let config = mtftp4::ConfigData::new(Ipv4Address([10, 0, 2, 2]));
+    tftp.configure(Some(&config))
+        .expect_success("Failed to configure MTFTP4");
+    let mode = tftp
+        .get_mode_data()
+        .expect_success("Failed to get MTFTP4 mode data");
+    assert_eq!(mode.config(), &config);
+
+    let filename = CStr8::from_bytes_with_nul(b"test.bin\0").unwrap();
+    let params = TransferParams::default();
+    let data = tftp
+        .read_file_to_vec(bt, filename, &params)
+        .expect_success("Failed to download test file");
+    assert_eq!(data.len(), 20000);
+    assert_eq!(fnv1a(&data), 0x7fc1_c585);
+
+    let mut len = 0;
+    tftp.read_file_with(filename, &params, |packet| {
+        if let Some((_, payload)) = packet.data() {
+            len += payload.len();
+        }
+        Status::SUCCESS
+    })
+    .expect_success("Failed to download test file with a callback");
+    assert_eq!(len, data.len());
+
+    let mut buffer = [0; 512];
+    let err = tftp
+        .read_file(filename, &params, &mut buffer)
+        .expect_err("Downloaded a file into a buffer which is too small");
+    assert_eq!(err.status(), Status::BUFFER_TOO_SMALL);
+
+    tftp.configure(None)
+        .expect_success("Failed to reset MTFTP4");
+}
+
+fn test_http(image: Handle, bt: &BootServices) {
+    info!("Running HTTP test");
+
+    let binding = if let Some(binding) = open_service_binding::<Http>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No HTTP service binding found");
+        return;
+    };
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create HTTP child");
+    let client = child
+        .open(bt, image)
+        .expect_success("Failed to open HTTP on child");
+    let client = unsafe { &mut *client.get() };
+
+    let config = http::ConfigData::default();
+    client
+        .configure(Some(&config))
+        .expect_success("Failed to configure HTTP");
+    let mode = client
+        .get_mode_data()
+        .expect_success("Failed to get HTTP mode data");
+    assert_eq!(mode, config);
+
+    // The build script serves the TFTP test file over HTTP as well, on a
+    // port of the host.
+    match client.get(bt, "http://10.0.2.2:8000/test.bin") {
+        Ok(data) => {
+            let data = data.log();
+            assert_eq!(data.len(), 20000);
+            assert_eq!(fnv1a(&data), 0x7fc1_c585);
+        }
+        Err(err) => warn!("Failed to download test file over HTTP: {:?}", err.status()),
+    }
+
+    client
+        .configure(None)
+        .expect_success("Failed to reset HTTP");
+}
+
+/// Computes the 32-bit FNV-1a hash of some data.
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn test_tls(image: Handle, bt: &BootServices) {
+    info!("Running TLS Configuration test");
+
+    let binding = if let Ok(binding) = bt.locate_protocol::<TlsServiceBinding>() {
+        binding.expect("Warnings encountered while opening TLS service binding")
+    } else {
+        warn!("TLS is not supported");
+        return;
+    };
+    let binding = unsafe { &mut *binding.get() };
+
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create TLS child");
+    let config = child
+        .open(bt, image)
+        .expect_success("Failed to open TLS Configuration on child");
+    let config = unsafe { &mut *config.get() };
+
+    // A self-signed certificate, used both as the CA and as our own.
+    let certificate = include_bytes!("test_ca.der");
+    config
+        .set_data(TlsConfigDataType::CA_CERTIFICATE, certificate)
+        .expect_success("Failed to set CA certificate");
+    config
+        .set_data(TlsConfigDataType::HOST_PUBLIC_CERT, certificate)
+        .expect_success("Failed to set host certificate");
+
+    let size = config
+        .get_data(TlsConfigDataType::HOST_PUBLIC_CERT, &mut [])
+        .expect_err("Host certificate fits in an empty buffer");
+    assert_eq!(size.status(), Status::BUFFER_TOO_SMALL);
+    assert_eq!(*size.data(), Some(certificate.len()));
+    let read = config
+        .get_data_to_vec(TlsConfigDataType::HOST_PUBLIC_CERT)
+        .expect_success("Failed to read host certificate");
+    assert_eq!(read, &certificate[..]);
+
+    match config.get_data_to_vec(TlsConfigDataType::CA_CERTIFICATE) {
+        Ok(read) => assert_eq!(read.log(), &certificate[..]),
+        Err(err) if err.status() == Status::UNSUPPORTED => {
+            info!("CA certificates cannot be read back")
+        }
+        Err(err) => panic!("Failed to read CA certificate: {:?}", err.status()),
+    }
+}
+
+fn test_tcp4(image: Handle, bt: &BootServices) {
+    info!("Running TCP4 test");
+
+    let binding = if let Some(binding) = open_service_binding::<Tcp4>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No TCP4 service binding found");
+        return;
+    };
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create TCP4 child");
+    let tcp = child
+        .open(bt, image)
+        .expect_success("Failed to open TCP4 on child");
+    let tcp = unsafe { &mut *tcp.get() };
+
+    // Connect to the DNS port of the QEMU user-mode resolver, which forwards
+    // the connection to the DNS server of the host.
+    let config = tcp4::ConfigData::new(AccessPoint {
+        station_address: Ipv4Address([10, 0, 2, 15]),
+        subnet_mask: Ipv4Address([255, 255, 255, 0]),
+        remote_address: Ipv4Address([10, 0, 2, 3]),
+        remote_port: 53,
+        active: true,
+        ..AccessPoint::default()
+    });
+    tcp.configure(Some(&config))
+        .expect_success("Failed to configure TCP4");
+    let (state, mode_config) = tcp
+        .get_mode_data()
+        .expect_success("Failed to get TCP4 mode data");
+    assert_eq!(state, ConnectionState::CLOSED);
+    assert_eq!(mode_config.access_point, config.access_point);
+    assert!(mode_config.options.is_some());
+
+    // The host may not have a DNS server reachable over TCP.
+    match tcp.connect_blocking(bt) {
+        Ok(completion) => {
+            completion.expect("Warnings encountered while connecting");
+            let (state, _) = tcp
+                .get_mode_data()
+                .expect_success("Failed to get TCP4 mode data");
+            assert_eq!(state, ConnectionState::ESTABLISHED);
+            tcp.close_blocking(bt, false)
+                .expect_success("Failed to close TCP4 connection");
+        }
+        Err(err) => warn!("Failed to connect to the DNS server: {:?}", err.status()),
+    }
+
+    tcp.configure(None).expect_success("Failed to reset TCP4");
+}
+
+fn test_udp4(image: Handle, bt: &BootServices) {
+    info!("Running UDP4 test");
+
+    let binding = if let Some(binding) = open_service_binding::<Udp4>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No UDP4 service binding found");
+        return;
+    };
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create UDP4 child");
+    let udp = child
+        .open(bt, image)
+        .expect_success("Failed to open UDP4 on child");
+    let udp = unsafe { &mut *udp.get() };
+
+    let config = udp4::ConfigData {
+        use_default_address: false,
+        station_address: Ipv4Address([10, 0, 2, 15]),
+        subnet_mask: Ipv4Address([255, 255, 255, 0]),
+        receive_timeout: 2_000_000,
+        ..udp4::ConfigData::default()
+    };
+    udp.configure(Some(&config))
+        .expect_success("Failed to configure UDP4");
+    let mode_config = udp
+        .get_mode_data()
+        .expect_success("Failed to get UDP4 mode data");
+    assert_eq!(mode_config.station_address, config.station_address);
+
+    // Ask the QEMU user-mode resolver for the address of example.com.
+    let resolver = Ipv4Address([10, 0, 2, 3]);
+    let mut query = [0; 29];
+    query[..12].copy_from_slice(&[0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    query[12..25].copy_from_slice(b"\x07example\x03com\x00");
+    query[25..].copy_from_slice(&[0, 1, 0, 1]);
+    udp.send_to(bt, &query, resolver, 53)
+        .expect_success("Failed to send DNS query");
+
+    // The host may not have a working DNS server.
+    let mut reply = [0; 512];
+    match udp.recv_from(bt, &mut reply) {
+        Ok(completion) => {
+            let (len, session) = completion.expect("Warnings encountered while receiving");
+            assert_eq!(session.source_address, resolver);
+            assert_eq!(session.source_port, 53);
+            assert!(len >= 12, "DNS reply is too short");
+            assert_eq!(&reply[..2], &[0x12, 0x34], "DNS reply has the wrong ID");
+            assert_ne!(reply[2] & 0x80, 0, "DNS reply is not a response");
+        }
+        Err(err) => warn!("No reply from the DNS server: {:?}", err.status()),
+    }
+
+    udp.configure(None).expect_success("Failed to reset UDP4");
+}
+
+fn test_ip4config2(bt: &BootServices) {
+    info!("Running IP4 Config2 test");
+
+    let config = if let Ok(config) = bt.locate_protocol::<Ip4Config2>() {
+        config.expect("Warnings encountered while opening IP4 Config2")
+    } else {
+        warn!("IP4 Config2 protocol is not supported");
+        return;
+    };
+    let config = unsafe { &mut *config.get() };
+
+    config
+        .set_policy(Policy::DHCP)
+        .expect_success("Failed to enable DHCP");
+    assert_eq!(
+        config.policy().expect_success("Failed to get policy"),
+        Policy::DHCP
+    );
+
+    // QEMU's user-mode DHCP server always hands out the same address.
+    let address = config
+        .wait_for_address(bt, 10_000_000)
+        .expect_success("Failed to obtain an address through DHCP");
+    assert_eq!(address, Ipv4Address([10, 0, 2, 15]));
+
+    #[repr(align(8))]
+    struct Buffer([u8; 512]);
+    let mut buffer = Buffer([0; 512]);
+    let info = config
+        .interface_info(&mut buffer.0)
+        .expect_success("Failed to get interface info");
+    assert_eq!(info.station_address(), address);
+    assert_eq!(info.subnet_mask(), Ipv4Address([255, 255, 255, 0]));
+    info!(
+        "Interface {} has {} routes",
+        info.name().expect("Interface name is not terminated"),
+        info.route_table().len()
+    );
+
+    let mut dns_servers = [Ipv4Address::UNSPECIFIED; 4];
+    let dns_servers = config
+        .dns_servers(&mut dns_servers)
+        .expect_success("Failed to get DNS servers");
+    assert!(dns_servers.contains(&Ipv4Address([10, 0, 2, 3])));
+}
+
+fn test_arp(image: Handle, bt: &BootServices, gateway_mac: Option<[u8; 6]>) {
+    info!("Running ARP test");
+
+    let binding = if let Some(binding) = open_service_binding::<Arp>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No ARP service binding found");
+        return;
+    };
+
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create ARP child");
+    let arp = child
+        .open(bt, image)
+        .expect_success("Failed to open ARP on child");
+    let arp = unsafe { &mut *arp.get() };
+
+    let station_address = Ipv4Address([10, 0, 2, 15]);
+    arp.configure(Some(&arp::ConfigData::ipv4(&station_address)))
+        .expect_success("Failed to configure ARP");
+
+    let gateway = Ipv4Address([10, 0, 2, 2]);
+    match arp.resolve(bt, &gateway.0) {
+        Ok(mac) => {
+            let (_, mac) = mac.split();
+            let mac = &mac.as_bytes()[..6];
+            info!("Gateway has hardware address {:02x?}", mac);
+            if let Some(expected) = gateway_mac {
+                assert_eq!(mac, expected, "ARP and MNP disagree on the gateway address");
+            }
+
+            let entries = arp
+                .find(bt, Some(arp::Address::Protocol(&gateway.0)), false)
+                .expect_success("Failed to find the gateway in the ARP cache");
+            let entry = entries.iter().next().expect("ARP cache entry is missing");
+            assert_eq!(entry.protocol_type, arp::ETHER_TYPE_IPV4);
+            assert_eq!(entry.protocol_address, &gateway.0);
+            assert_eq!(&entry.hw_address[..6], mac);
+            assert!(!entry.deny);
+        }
+        Err(err) => warn!("Failed to resolve the gateway: {:?}", err.status()),
+    }
+
+    // Static entries survive a flush, but not a deletion.
+    let peer = Ipv4Address([10, 0, 2, 42]);
+    let peer_mac = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+    arp.add(&peer.0, &peer_mac, 0, true)
+        .expect_success("Failed to add a static ARP entry");
+    arp.flush().expect_success("Failed to flush the ARP cache");
+    let entries = arp
+        .find(bt, Some(arp::Address::Protocol(&peer.0)), false)
+        .expect_success("Static ARP entry was flushed");
+    let entry = entries.iter().next().expect("Static ARP entry is missing");
+    assert!(entry.is_static);
+    assert_eq!(&entry.hw_address[..6], &peer_mac);
+    drop(entries);
+    arp.delete(Some(arp::Address::Protocol(&peer.0)))
+        .expect_success("Failed to delete the static ARP entry");
+    assert_eq!(
+        arp.find(bt, Some(arp::Address::Protocol(&peer.0)), false)
+            .map(|_| ())
+            .unwrap_err()
+            .status(),
+        Status::NOT_FOUND
+    );
+
+    arp.configure(None).expect_success("Failed to reset ARP");
+}
+
+/// Returns the link-local address of the interface, if it has one.
+fn test_ip6config(bt: &BootServices) -> Option<Ipv6Address> {
+    info!("Running IP6 Config test");
+
+    let config = if let Ok(config) = bt.locate_protocol::<Ip6Config>() {
+        config.expect("Warnings encountered while opening IP6 Config")
+    } else {
+        warn!("IP6 Config protocol is not supported");
+        return None;
+    };
+    let config = unsafe { &mut *config.get() };
+
+    let transmits = config
+        .dup_addr_detect_transmits()
+        .expect_success("Failed to get the duplicate address detection transmits");
+    info!(
+        "Duplicate address detection sends {} solicitations",
+        transmits.dup_addr_detect_transmits
+    );
+
+    // The link-local address is assigned once the duplicate address
+    // detection succeeds.
+    let address = match config.wait_for_address(bt, 10_000_000) {
+        Ok(address) => address.log(),
+        Err(err) => {
+            warn!("No IPv6 address was assigned: {:?}", err.status());
+            return None;
+        }
+    };
+    assert_eq!(
+        (address.0[0], address.0[1] & 0xc0),
+        (0xfe, 0x80),
+        "The first IPv6 address is not link-local"
+    );
+
+    #[repr(align(8))]
+    struct Buffer([u8; 1024]);
+    let mut buffer = Buffer([0; 1024]);
+    let info = config
+        .interface_info(&mut buffer.0)
+        .expect_success("Failed to get interface info");
+    assert!(info
+        .addresses()
+        .iter()
+        .any(|info| info.address == address && info.prefix_length == 64));
+    assert_eq!(info.hw_address().len(), 6);
+
+    Some(address)
+}
+
+fn test_udp6(image: Handle, bt: &BootServices, link_local: Ipv6Address) {
+    info!("Running UDP6 test");
+
+    let binding = if let Some(binding) = open_service_binding::<Udp6>(bt) {
+        unsafe { &mut *binding.get() }
+    } else {
+        warn!("No UDP6 service binding found");
+        return;
+    };
+    let child = binding
+        .create_scoped_child()
+        .expect_success("Failed to create UDP6 child");
+    let udp = child
+        .open(bt, image)
+        .expect_success("Failed to open UDP6 on child");
+    let udp = unsafe { &mut *udp.get() };
+
+    let config = udp6::ConfigData {
+        station_address: link_local,
+        ..udp6::ConfigData::default()
+    };
+    udp.configure(Some(&config))
+        .expect_success("Failed to configure UDP6");
+    let mode_config = udp
+        .get_mode_data()
+        .expect_success("Failed to get UDP6 mode data");
+    assert_eq!(mode_config.station_address, link_local);
+
+    // Send a datagram to the discard port of all the nodes of the link.
+    let all_nodes = Ipv6Address([0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    udp.send_to(bt, b"uefi-rs", all_nodes, 9)
+        .expect_success("Failed to send UDP6 datagram");
+
+    udp.configure(None).expect_success("Failed to reset UDP6");
+}
+
+fn test_vlan(bt: &BootServices) {
+    info!("Running VLAN Config test");
+
+    let config = if let Ok(config) = bt.locate_protocol::<VlanConfig>() {
+        config.expect("Warnings encountered while opening VLAN Config")
+    } else {
+        warn!("VLAN Config protocol is not supported");
+        return;
+    };
+    let config = unsafe { &mut *config.get() };
+
+    let original = config.find(bt, None).expect_success("Failed to find VLANs");
+    assert!(
+        original.iter().all(|entry| entry.vlan_id != 100),
+        "VLAN 100 is already configured"
+    );
+
+    assert_eq!(
+        config.set(4095, 0).unwrap_err().status(),
+        Status::INVALID_PARAMETER
+    );
+    assert_eq!(
+        config.set(100, 8).unwrap_err().status(),
+        Status::INVALID_PARAMETER
+    );
+
+    config.set(100, 3).expect_success("Failed to set VLAN");
+    let entries = config
+        .find(bt, Some(100))
+        .expect_success("Failed to find VLAN");
+    assert_eq!(
+        entries,
+        [VlanEntry {
+            vlan_id: 100,
+            priority: 3
+        }]
+    );
+
+    config.remove(100).expect_success("Failed to remove VLAN");
+    assert_eq!(config.remove(100).unwrap_err().status(), Status::NOT_FOUND);
+    assert_eq!(
+        config.find(bt, None).expect_success("Failed to find VLANs"),
+        original
+    );
+}
@@ -0,0 +1,176 @@
+use uefi::prelude::*;
+use uefi::proto::pci::io::{ConfigOffset, PciIo};
+use uefi::proto::pci::root_bridge::{PciAddress, PciRootBridgeIo, ResourceKind};
+use uefi::proto::pci::{Attributes, DmaOperation};
+use uefi::table::boot::{BootServices, MemoryType};
+
+/// Vendor and device identifiers of the host bridges emulated by QEMU: the
+/// Q35 and i440FX chipsets, and the generic PCIe host bridge of `virt`.
+const HOST_BRIDGES: [(u16, u16); 3] = [(0x8086, 0x29c0), (0x8086, 0x1237), (0x1b36, 0x0008)];
+
+pub fn test(bt: &BootServices) {
+    test_pci_io(bt);
+    test_root_bridge(bt);
+}
+
+fn test_pci_io(bt: &BootServices) {
+    info!("Running PCI I/O protocol test");
+
+    let handles = bt
+        .find_handles::<PciIo>()
+        .expect_success("Failed to get PCI I/O handles");
+    if handles.is_empty() {
+        warn!("No PCI devices found");
+        return;
+    }
+
+    let mut found_host_bridge = false;
+    let mut tested_dma = false;
+    for handle in handles {
+        let pci = bt
+            .handle_protocol::<PciIo>(handle)
+            .expect_success("Failed to open PCI I/O protocol");
+        let pci = unsafe { &mut *pci.get() };
+
+        let location = pci.get_location().expect_success("Failed to get location");
+        let vendor_id: u16 = pci
+            .pci_read(ConfigOffset::VENDOR_ID)
+            .expect_success("Failed to read vendor ID");
+        let device_id: u16 = pci
+            .pci_read(ConfigOffset::DEVICE_ID)
+            .expect_success("Failed to read device ID");
+        let class: u8 = pci
+            .pci_read(ConfigOffset::CLASS)
+            .expect_success("Failed to read class code");
+        info!(
+            "PCI function {}: {:04x}:{:04x}, class {:02x}",
+            location, vendor_id, device_id, class
+        );
+        assert_ne!(vendor_id, 0xffff, "Function at {} does not exist", location);
+
+        // BAR indices are checked before calling the firmware.
+        assert_eq!(
+            pci.mem_read::<u32>(6, 0).unwrap_err().status(),
+            Status::INVALID_PARAMETER
+        );
+
+        let supported = pci
+            .supported_attributes()
+            .expect_success("Failed to get supported attributes");
+        let enabled = pci.attributes().expect_success("Failed to get attributes");
+        info!("Attributes: {:?} (supported: {:?})", enabled, supported);
+
+        if HOST_BRIDGES.contains(&(vendor_id, device_id)) {
+            found_host_bridge = true;
+        } else if !tested_dma && supported.contains(Attributes::BUS_MASTER) {
+            test_dma(pci);
+            tested_dma = true;
+        }
+    }
+    assert!(found_host_bridge, "QEMU host bridge not found");
+    if !tested_dma {
+        warn!("No PCI device supports DMA");
+    }
+}
+
+fn test_dma(pci: &PciIo) {
+    info!("Testing PCI DMA mappings");
+
+    assert_eq!(
+        pci.allocate_buffer(MemoryType::LOADER_DATA, 1, Attributes::empty())
+            .unwrap_err()
+            .status(),
+        Status::INVALID_PARAMETER
+    );
+
+    let mut buffer = pci
+        .allocate_buffer(MemoryType::BOOT_SERVICES_DATA, 1, Attributes::empty())
+        .expect_success("Failed to allocate common buffer");
+    assert_eq!(buffer.len(), 4096);
+    assert!(buffer.as_slice().iter().all(|&byte| byte == 0));
+    buffer.as_mut_slice()[..7].copy_from_slice(b"uefi-rs");
+
+    let mapping = pci
+        .map_common_buffer(&mut buffer)
+        .expect_success("Failed to map common buffer");
+    assert_eq!(mapping.operation(), DmaOperation::BUS_MASTER_COMMON_BUFFER);
+    assert_eq!(mapping.len(), 4096);
+    info!(
+        "Common buffer is at device address {:?}",
+        mapping.device_address()
+    );
+    mapping
+        .unmap()
+        .expect_success("Failed to unmap common buffer");
+    assert_eq!(&buffer.as_slice()[..7], b"uefi-rs");
+
+    let data = [0x55; 512];
+    let mapping = pci.map_read(&data).expect_success("Failed to map buffer");
+    assert_eq!(mapping.operation(), DmaOperation::BUS_MASTER_READ);
+    assert!(!mapping.is_empty() && mapping.len() <= data.len());
+    drop(mapping);
+
+    pci.flush().expect_success("Failed to flush posted writes");
+}
+
+fn test_root_bridge(bt: &BootServices) {
+    info!("Running PCI Root Bridge I/O protocol test");
+
+    let root_bridge = if let Ok(root_bridge) = bt.locate_protocol::<PciRootBridgeIo>() {
+        root_bridge.expect("Warnings encountered while opening PCI Root Bridge I/O protocol")
+    } else {
+        warn!("PCI Root Bridge I/O protocol is not supported");
+        return;
+    };
+    let root_bridge = unsafe { &*root_bridge.get() };
+
+    let resources = root_bridge
+        .configuration()
+        .expect_success("Failed to get root bridge resources");
+    for space in resources.address_spaces() {
+        info!(
+            "Root bridge decodes {:?} {:#x}..{:#x}",
+            space.kind,
+            space.min,
+            space.end()
+        );
+    }
+    assert!(resources
+        .address_spaces()
+        .any(|space| space.kind == ResourceKind::BUS && space.min == 0));
+
+    // Scan bus 0, including the functions of multi-function devices.
+    let mut found_host_bridge = false;
+    for device in 0..32 {
+        for function in 0..8 {
+            let read_u16 = |register| -> u16 {
+                root_bridge
+                    .pci_read(PciAddress::new(0, device, function, register))
+                    .expect_success("Failed to read configuration space")
+            };
+            let vendor_id = read_u16(ConfigOffset::VENDOR_ID);
+            if vendor_id == 0xffff {
+                if function == 0 {
+                    break;
+                }
+                continue;
+            }
+            let device_id = read_u16(ConfigOffset::DEVICE_ID);
+            info!(
+                "Found {:04x}:{:04x} at 00:{:02x}.{:x}",
+                vendor_id, device_id, device, function
+            );
+            if HOST_BRIDGES.contains(&(vendor_id, device_id)) {
+                found_host_bridge = true;
+            }
+
+            let header_type: u8 = root_bridge
+                .pci_read(PciAddress::new(0, device, 0, ConfigOffset::HEADER_TYPE))
+                .expect_success("Failed to read header type");
+            if function == 0 && header_type & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+    assert!(found_host_bridge, "QEMU host bridge not found on bus 0");
+}
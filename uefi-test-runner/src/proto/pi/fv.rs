@@ -0,0 +1,91 @@
+use uefi::prelude::*;
+use uefi::proto::pi::fv::{FileType, FirmwareVolume, SectionType};
+use uefi::table::boot::BootServices;
+use uefi::{guid, Guid};
+
+/// The DXE core of EDK2, which OVMF keeps in its DXE firmware volume.
+const DXE_CORE_GUID: Guid = guid!("d6a2cb7f-6a18-4e2f-b43b-9920a733700a");
+
+pub fn test(bt: &BootServices) {
+    info!("Running firmware volume protocol test");
+
+    let handles = bt
+        .find_handles::<FirmwareVolume>()
+        .expect_success("Failed to get firmware volume handles");
+    if handles.is_empty() {
+        warn!("Firmware volume protocol is not supported");
+        return;
+    }
+
+    let mut found = false;
+    for handle in handles {
+        let fv = bt
+            .handle_protocol::<FirmwareVolume>(handle)
+            .expect_success("Failed to open firmware volume");
+        let fv = unsafe { &*fv.get() };
+        let attributes = fv
+            .get_volume_attributes()
+            .expect_success("Failed to get volume attributes");
+
+        let files = fv
+            .files(FileType::ALL)
+            .expect_success("Failed to list files");
+        let mut count = 0;
+        for file in files {
+            let file = file.expect_success("Failed to get next file");
+            count += 1;
+            if file.file_type == FileType::DXE_CORE {
+                assert_eq!(file.name, DXE_CORE_GUID);
+                found = true;
+            }
+        }
+        info!(
+            "Firmware volume with attributes {:?} has {} files",
+            attributes, count
+        );
+
+        // Filtering by type only returns files of that type.
+        let drivers = fv
+            .files(FileType::DRIVER)
+            .expect_success("Failed to list drivers");
+        for driver in drivers {
+            let driver = driver.expect_success("Failed to get next driver");
+            assert_eq!(driver.file_type, FileType::DRIVER);
+        }
+
+        if let Ok(info) = fv.get_file_info(&DXE_CORE_GUID) {
+            let info = info.unwrap();
+            assert_eq!(info.file_type, FileType::DXE_CORE);
+
+            let file = fv
+                .read_file(bt, &DXE_CORE_GUID)
+                .expect_success("Failed to read the DXE core");
+            assert_eq!(file.data.as_bytes().len(), info.size);
+
+            let section = fv
+                .read_section(bt, &DXE_CORE_GUID, SectionType::USER_INTERFACE, 0)
+                .expect_success("Failed to read the name of the DXE core");
+            let name: crate::alloc::string::String = char::decode_utf16(
+                section
+                    .data
+                    .as_bytes()
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                    .take_while(|&c| c != 0),
+            )
+            .map(|c| c.expect("Invalid name"))
+            .collect();
+            assert_eq!(name, "DxeCore");
+            info!(
+                "Read the DXE core, with authentication status {:?}",
+                section.authentication_status
+            );
+
+            let err = fv
+                .read_section(bt, &DXE_CORE_GUID, SectionType::USER_INTERFACE, 1)
+                .expect_err("Read a missing section");
+            assert_eq!(err.status(), Status::NOT_FOUND);
+        }
+    }
+    assert!(found, "The DXE core was not found");
+}
@@ -3,7 +3,9 @@ use uefi::prelude::*;
 pub fn test(bt: &BootServices) {
     info!("Testing Platform Initialization protocols");
 
+    fv::test(bt);
     mp::test(bt);
 }
 
+mod fv;
 mod mp;
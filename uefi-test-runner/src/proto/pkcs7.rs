@@ -0,0 +1,33 @@
+use uefi::prelude::*;
+use uefi::proto::pkcs7::{Pkcs7Verify, SignatureData, SignatureList, CERT_SHA256_GUID};
+use uefi::table::boot::BootServices;
+use uefi::Guid;
+
+pub fn test(bt: &BootServices) {
+    info!("Running PKCS7 verify protocol test");
+
+    if let Ok(pkcs7) = bt.locate_protocol::<Pkcs7Verify>() {
+        let pkcs7 = pkcs7.expect("Warnings encountered while opening PKCS7 verify protocol");
+        let pkcs7 = unsafe { &*pkcs7.get() };
+
+        let owner = Guid::from_values(0, 0, 0, 0, [0; 6]);
+        let mut buffer = [0; 128];
+        let allowed = SignatureList::build(
+            &mut buffer,
+            CERT_SHA256_GUID,
+            &[SignatureData {
+                owner,
+                data: &[0; 32],
+            }],
+        )
+        .expect_success("Failed to build signature list");
+
+        // Garbage is not a valid PKCS#7 signed data blob.
+        let mut content = [0; 16];
+        pkcs7
+            .verify_buffer(&[0; 64], None, &[allowed], &[], &[], &mut content)
+            .expect_err("PKCS7 verify protocol accepted an invalid signature");
+    } else {
+        info!("PKCS7 verify protocol is not supported");
+    }
+}
@@ -0,0 +1,68 @@
+use crate::alloc::vec::Vec;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use uefi::prelude::*;
+use uefi::proto::rng::{self, Rng, RngAdapter};
+use uefi::table::boot::BootServices;
+use uefi::Guid;
+
+pub fn test(bt: &BootServices) {
+    info!("Running RNG protocol test");
+
+    if let Ok(rng) = bt.locate_protocol::<Rng>() {
+        let rng = rng.expect("Warnings encountered while opening RNG protocol");
+        let rng = unsafe { &mut *rng.get() };
+
+        let mut algorithms = [Guid::from_values(0, 0, 0, 0, [0; 6]); 8];
+        let algorithms = rng
+            .get_info(&mut algorithms)
+            .expect_success("Failed to get RNG algorithms");
+        info!("Supported RNG algorithms: {:?}", algorithms);
+
+        let mut first = [0u8; 32];
+        let mut second = [0u8; 32];
+        rng.get_rng(None, &mut first)
+            .expect_success("Failed to get random bytes");
+        rng.get_rng(None, &mut second)
+            .expect_success("Failed to get random bytes");
+        // The odds of two identical 256-bit random numbers are negligible.
+        assert_ne!(first, second, "RNG returned the same bytes twice");
+
+        let status = rng
+            .get_rng(None, &mut [])
+            .expect_error("Requesting zero random bytes should fail")
+            .status();
+        assert_eq!(status, Status::INVALID_PARAMETER);
+
+        // X9.31 is deprecated and not implemented by OVMF.
+        if !algorithms.contains(&rng::ALGORITHM_X9_31_3DES) {
+            let status = rng
+                .get_rng(Some(&rng::ALGORITHM_X9_31_3DES), &mut first)
+                .expect_error("Unsupported RNG algorithm was accepted")
+                .status();
+            assert_eq!(status, Status::UNSUPPORTED);
+        }
+
+        test_rand(rng);
+    } else {
+        warn!("No RNG protocol found");
+    }
+}
+
+// Seed a generator from the rand crate with the firmware's RNG.
+fn test_rand(rng: &mut Rng) {
+    let mut small_rng =
+        SmallRng::from_rng(RngAdapter::new(rng)).expect("Failed to seed RNG from firmware");
+
+    let original = (0..64).collect::<Vec<u32>>();
+    let mut shuffled = original.clone();
+    shuffled.shuffle(&mut small_rng);
+
+    // Shuffling must only move elements around. There are 64! permutations,
+    // so getting back the original order is astronomically unlikely.
+    let mut sorted = shuffled.clone();
+    sorted.sort_unstable();
+    assert_eq!(sorted, original);
+    assert_ne!(shuffled, original, "Shuffling did not change the order");
+}
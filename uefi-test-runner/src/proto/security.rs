@@ -0,0 +1,68 @@
+use crate::alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use uefi::prelude::*;
+use uefi::proto::device_path::DevicePath;
+use uefi::proto::security::Security2;
+use uefi::table::boot::BootServices;
+
+#[cfg(target_arch = "x86_64")]
+const RUNNER_PATH: &str = "EFI\\Boot\\BootX64.efi";
+#[cfg(target_arch = "aarch64")]
+const RUNNER_PATH: &str = "EFI\\Boot\\BootAA64.efi";
+
+/// Size of the last file seen by the authentication hook.
+static OBSERVED_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn logging_policy(_: Option<&DevicePath>, file: Option<&[u8]>, boot_policy: bool) -> Status {
+    let size = file.map_or(0, |file| file.len());
+    info!(
+        "Authenticating a {} byte image (boot policy: {})",
+        size, boot_policy
+    );
+    OBSERVED_SIZE.store(size, Ordering::Relaxed);
+    Status::SUCCESS
+}
+
+pub fn test(image: Handle, bt: &BootServices) {
+    info!("Running Security2 protocol test");
+
+    let security = if let Ok(security) = bt.locate_protocol::<Security2>() {
+        security.expect("Warnings encountered while opening Security2 protocol")
+    } else {
+        warn!("No Security2 protocol found");
+        return;
+    };
+    let security = unsafe { &mut *security.get() };
+
+    let runner = if let Some(runner) = read_runner(image, bt) {
+        runner
+    } else {
+        warn!("Could not read the test runner image, skipping test");
+        return;
+    };
+
+    let hook = unsafe { security.hook(logging_policy) }.expect_success("Failed to install hook");
+    let loaded = bt.load_image_from_buffer(image, &runner);
+    hook.unhook();
+
+    let handle = loaded.expect_success("Failed to load the test runner image");
+    // Images loaded from memory have no boot volume.
+    match bt.open_image_volume(handle) {
+        Ok(_) => panic!("Opened the boot volume of an image loaded from memory"),
+        Err(error) => assert_eq!(error.status(), Status::UNSUPPORTED),
+    }
+    bt.unload_image(handle)
+        .expect_success("Failed to unload the test runner image");
+    assert_eq!(
+        OBSERVED_SIZE.load(Ordering::Relaxed),
+        runner.len(),
+        "The hook did not observe the image load"
+    );
+}
+
+/// Reads the test runner image from the boot volume.
+pub fn read_runner(image: Handle, bt: &BootServices) -> Option<Vec<u8>> {
+    bt.read_file(image, RUNNER_PATH)
+        .ok()
+        .map(|runner| runner.unwrap())
+}
@@ -0,0 +1,107 @@
+use crate::alloc::string::String;
+use core::convert::TryFrom;
+use uefi::prelude::*;
+use uefi::proto::media::file::FileMode;
+use uefi::proto::shell::params::{self, ShellParameters};
+use uefi::proto::shell::Shell;
+use uefi::table::boot::BootServices;
+use uefi::CString16;
+
+pub fn test(image: Handle, bt: &BootServices) {
+    info!("Running shell protocol test");
+
+    let args = params::args(bt, image).expect_success("Failed to get arguments");
+    info!("Arguments: {:?}", args);
+
+    let shell = match Shell::find(bt) {
+        Some(shell) => shell,
+        None => {
+            // The runner is only launched from the shell by `build.py run --shell`.
+            warn!("Not launched from the shell");
+            return;
+        }
+    };
+    let (major, minor) = shell.version();
+    info!("Shell version {}.{}", major, minor);
+    assert!(shell.is_root_shell());
+    assert!(
+        shell.batch_is_active(),
+        "The runner should be run by startup.nsh"
+    );
+    test_parameters(image, bt, shell, &args);
+
+    let name = CString16::try_from("uefi_rs_test").unwrap();
+    let value = CString16::try_from("shell value").unwrap();
+    shell
+        .set_env(&name, &value, true)
+        .expect_success("Failed to set environment variable");
+    let read = shell
+        .get_env(&name)
+        .expect("Environment variable was not set");
+    assert_eq!(read.to_u16_slice(), value.to_u16_slice());
+    assert!(shell
+        .env_names()
+        .any(|var| var.to_u16_slice() == name.to_u16_slice()));
+
+    // Commands run in the same shell, and see its variables.
+    let command = CString16::try_from("set -v uefi_rs_test executed").unwrap();
+    let status = shell
+        .execute(image, &command)
+        .expect_success("Failed to run command");
+    assert_eq!(status, Status::SUCCESS);
+    let read = shell
+        .get_env(&name)
+        .expect("Environment variable was removed");
+    assert_eq!(
+        read.to_u16_slice(),
+        CString16::try_from("executed").unwrap().to_u16_slice()
+    );
+    let empty = CString16::try_from("").unwrap();
+    shell
+        .set_env(&name, &empty, true)
+        .expect_success("Failed to delete environment variable");
+    assert!(shell.get_env(&name).is_none());
+
+    let cur_dir = shell
+        .get_cur_dir(None)
+        .expect("The shell has no current directory");
+    info!("Current directory: {}", cur_dir);
+    let fs0 = CString16::try_from("fs0:").unwrap();
+    let path = shell
+        .get_device_path_from_map(&fs0)
+        .expect("fs0: is not mapped");
+    let maps = shell
+        .get_map_from_device_path(path)
+        .expect("fs0: has no mappings");
+    info!("Mappings of fs0: {}", maps);
+
+    // The script which launched the runner is at the root of the ESP.
+    let path = CString16::try_from("fs0:\\startup.nsh").unwrap();
+    let mut file = shell
+        .open_file_by_name(&path, FileMode::Read)
+        .expect_success("Failed to open startup.nsh");
+    let size = file.get_size().expect_success("Failed to get file size");
+    let mut buffer = [0; 256];
+    let len = file
+        .read(&mut buffer)
+        .expect_success("Failed to read startup.nsh");
+    assert_eq!(len as u64, size);
+    let script = core::str::from_utf8(&buffer[..len]).expect("Script is not ASCII");
+    assert!(script.contains("uefi-test-runner.efi"));
+}
+
+fn test_parameters(image: Handle, bt: &BootServices, shell: &Shell, args: &[String]) {
+    // The arguments are passed by `startup.nsh`.
+    assert_eq!(args[1..], ["two words", "fs0:\\efi"]);
+
+    let params = bt
+        .handle_protocol::<ShellParameters>(image)
+        .expect_success("Failed to open shell parameters protocol");
+    let params = unsafe { &*params.get() };
+    assert_eq!(params.args().len(), args.len());
+
+    let mut stdout = params.stdout(shell).expect("No standard output");
+    stdout
+        .write(b"Written to the shell's standard output\r\n")
+        .expect_success("Failed to write to standard output");
+}
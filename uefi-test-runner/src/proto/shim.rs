@@ -14,6 +14,9 @@ pub fn test(bt: &BootServices) {
         shim_lock
             .verify(&buffer)
             .expect_err("shim failed to reject an invalid application");
+        shim_lock
+            .context(&buffer)
+            .expect_err("shim parsed the headers of an invalid application");
     } else {
         info!("Shim lock protocol is not supported");
     }
@@ -0,0 +1,126 @@
+use uefi::prelude::*;
+use uefi::proto::tcg::v2::{EventLogFormat, HashLogExtendEventFlags, PcrEvent, Tcg};
+use uefi::proto::tcg::{v1, EventType, PcrIndex};
+use uefi::table::boot::BootServices;
+
+pub fn test(bt: &BootServices) {
+    test_tcg2(bt);
+    test_tcg1(bt);
+}
+
+fn test_tcg2(bt: &BootServices) {
+    info!("Running TCG2 protocol test");
+
+    if let Ok(tcg) = bt.locate_protocol::<Tcg>() {
+        let tcg = tcg.expect("Warnings encountered while opening TCG2 protocol");
+        let tcg = unsafe { &mut *tcg.get() };
+
+        let capability = tcg
+            .get_capability()
+            .expect_success("Failed to get TCG2 capabilities");
+        info!("TCG2 capabilities: {:?}", capability);
+
+        if !capability.tpm_present {
+            let status = tcg
+                .submit_command(&[], &mut [])
+                .expect_error("Command was submitted without a TPM")
+                .status();
+            assert_eq!(status, Status::NOT_FOUND);
+            return;
+        }
+
+        // TPM2_GetRandom, requesting 8 bytes.
+        let command = [
+            0x80, 0x01, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x01, 0x7b, 0x00, 0x08,
+        ];
+        let mut response = [0; 64];
+        tcg.submit_command(&command, &mut response)
+            .expect_success("Failed to submit TPM command");
+
+        let tag = u16::from_be_bytes([response[0], response[1]]);
+        let rc = u32::from_be_bytes([response[6], response[7], response[8], response[9]]);
+        assert_eq!(tag, 0x8001, "Unexpected TPM response tag");
+        assert_eq!(rc, 0, "TPM2_GetRandom failed");
+
+        test_measurement(tcg);
+    } else {
+        info!("No TCG2 protocol found, skipping test");
+    }
+}
+
+fn test_measurement(tcg: &mut Tcg) {
+    let data = b"uefi-rs test measurement";
+    let mut buffer = [0; 64];
+    let event = PcrEvent::build(&mut buffer, PcrIndex::new(8).unwrap(), EventType::IPL, data)
+        .expect_success("Failed to build TCG2 event");
+    tcg.hash_log_extend_event(HashLogExtendEventFlags::empty(), data, event)
+        .expect_success("Failed to extend PCR 8");
+
+    let log = tcg
+        .get_event_log(EventLogFormat::TCG_2)
+        .expect_success("Failed to get TCG2 event log");
+    info!("TCG2 event log: {:?}", log);
+
+    let location = log.location.expect("Event log is missing");
+    let last_entry = log
+        .last_entry
+        .expect("Event log is empty after a measurement");
+    assert!(
+        last_entry >= location,
+        "Last event is before the start of the log"
+    );
+}
+
+fn test_tcg1(bt: &BootServices) {
+    info!("Running TCG protocol test");
+
+    let tcg = if let Ok(tcg) = bt.locate_protocol::<v1::Tcg>() {
+        tcg.expect("Warnings encountered while opening TCG protocol")
+    } else {
+        info!("No TCG protocol found, skipping test");
+        return;
+    };
+    let tcg = unsafe { &mut *tcg.get() };
+
+    let state = tcg
+        .status_check()
+        .expect_success("Failed to check the TCG status");
+    info!("TCG status: {:?}", state);
+    if !state.is_usable() {
+        if state.tpm_deactivated {
+            warn!("TPM 1.2 is deactivated, skipping test");
+        }
+        return;
+    }
+
+    // SHA-1 of "abc", from FIPS 180-2.
+    let digest = tcg.hash_all(b"abc").expect_success("Failed to hash data");
+    assert_eq!(
+        digest,
+        [
+            0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50,
+            0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d,
+        ]
+    );
+
+    let data = b"uefi-rs test measurement";
+    let mut buffer = [0; 64];
+    let event = v1::PcrEvent::build(
+        &mut buffer,
+        PcrIndex::new(8).unwrap(),
+        EventType::IPL,
+        [0; 20],
+        data,
+    )
+    .expect_success("Failed to build TCG event");
+    let (_, last_entry) = tcg
+        .hash_log_extend_event(data, event)
+        .expect_success("Failed to extend PCR 8");
+    assert_ne!(event.digest(), [0; 20], "Event digest was not updated");
+
+    let location = state.event_log_location.expect("Event log is missing");
+    assert!(
+        last_entry >= location,
+        "Last event is before the start of the log"
+    );
+}
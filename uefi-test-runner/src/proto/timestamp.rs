@@ -0,0 +1,32 @@
+use uefi::prelude::*;
+use uefi::proto::timestamp::Timestamp;
+use uefi::table::boot::BootServices;
+
+pub fn test(bt: &BootServices) {
+    info!("Running timestamp protocol test");
+
+    let timestamp = match bt.locate_protocol::<Timestamp>() {
+        Ok(timestamp) => timestamp.expect("Warnings encountered while opening timestamp protocol"),
+        Err(_) => {
+            warn!("Timestamp protocol is not supported");
+            return;
+        }
+    };
+    let timestamp = unsafe { &*timestamp.get() };
+
+    let properties = timestamp
+        .get_properties()
+        .expect_success("Failed to get timestamp properties");
+    info!(
+        "Timestamp frequency: {} Hz, end value: {:#x}",
+        properties.frequency, properties.end_value
+    );
+    assert_ne!(properties.frequency, 0);
+
+    let start = timestamp.get_timestamp();
+    bt.stall(1_000);
+    let end = timestamp.get_timestamp();
+    let elapsed = properties.elapsed(start, end);
+    info!("Stalling for 1 ms took {:?}", elapsed);
+    assert!(elapsed.as_micros() >= 1_000);
+}
@@ -0,0 +1,90 @@
+use crate::alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use uefi::prelude::*;
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::proto::unicode_collation::{self, UnicodeCollation};
+use uefi::table::boot::BootServices;
+use uefi::CString16;
+
+pub fn test(image: Handle, bt: &BootServices) {
+    info!("Running Unicode collation protocol test");
+
+    let collation = match bt.locate_protocol::<UnicodeCollation>() {
+        Ok(collation) => collation.expect("Warnings encountered while opening Unicode collation"),
+        Err(_) => {
+            warn!("Unicode collation protocol is not supported");
+            return;
+        }
+    };
+    let collation = unsafe { &*collation.get() };
+    info!("Supported languages: {}", collation.supported_languages());
+
+    let lower = CString16::try_from("startup.nsh").unwrap();
+    let upper = CString16::try_from("STARTUP.NSH").unwrap();
+    let other = CString16::try_from("boot.efi").unwrap();
+    assert_eq!(collation.stri_coll(&lower, &upper), Ordering::Equal);
+    assert_eq!(collation.stri_coll(&lower, &other), Ordering::Greater);
+
+    let mut buffer = [0; 16];
+    let converted = collation
+        .str_upr(&lower, &mut buffer)
+        .expect_success("Failed to convert to upper case");
+    assert_eq!(converted, &*upper);
+    let converted = collation
+        .str_lwr(&upper, &mut buffer)
+        .expect_success("Failed to convert to lower case");
+    assert_eq!(converted, &*lower);
+    let err = collation
+        .str_lwr(&upper, &mut buffer[..4])
+        .expect_err("Converted a string into a too small buffer");
+    assert_eq!(*err.data(), Some(12));
+
+    // Periods are removed when converting to FAT names.
+    let mut fat = [b' '; 11];
+    assert!(!collation.str_to_fat(&upper, &mut fat));
+    assert_eq!(&fat, b"STARTUPNSH ");
+    let name = collation
+        .fat_to_str(&fat[..10], &mut buffer)
+        .expect_success("Failed to convert FAT name");
+    assert_eq!(name, "STARTUPNSH");
+
+    // The firmware and the Rust implementation must agree on the files of
+    // the boot volume.
+    let names = root_directory_names(image, bt);
+    for pattern in &["*", "*.EFI", "[a-m]*", "?f?", "[e][f]i", "*[*]*"] {
+        let pattern = CString16::try_from(*pattern).unwrap();
+        let matched: Vec<_> = names
+            .iter()
+            .filter(|name| collation.metai_match(name, &pattern))
+            .collect();
+        let expected: Vec<_> = names
+            .iter()
+            .filter(|name| unicode_collation::metai_match(name, &pattern))
+            .collect();
+        info!("Files matching {}: {:?}", pattern, matched);
+        assert_eq!(matched, expected);
+        assert!(names
+            .iter()
+            .all(|name| unicode_collation::matches(bt, name, &pattern)
+                == collation.metai_match(name, &pattern)));
+    }
+}
+
+fn root_directory_names(image: Handle, bt: &BootServices) -> Vec<CString16> {
+    let mut names = Vec::new();
+    if bt.locate_protocol::<SimpleFileSystem>().is_err() {
+        return names;
+    }
+    let mut directory = bt
+        .open_image_volume(image)
+        .expect_success("Failed to open the boot volume");
+    let mut buffer = [0; 256];
+    while let Some(info) = directory
+        .read_entry(&mut buffer)
+        .expect_success("Failed to read directory entry")
+    {
+        names.push(CString16::from(info.file_name()));
+    }
+    names
+}
@@ -0,0 +1,135 @@
+use crate::alloc::string::ToString;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use uefi::prelude::*;
+use uefi::proto::usb::io::{DataStage, DescriptorType, DeviceRequest, TransferType, UsbIo};
+use uefi::table::boot::BootServices;
+
+/// Vendor and product identifiers of QEMU's USB tablet.
+const QEMU_TABLET: (u16, u16) = (0x0627, 0x0001);
+
+pub fn test(bt: &BootServices) {
+    info!("Running USB I/O protocol test");
+
+    let handles = bt
+        .find_handles::<UsbIo>()
+        .expect_success("Failed to get USB I/O handles");
+    if handles.is_empty() {
+        warn!("No USB devices found");
+        return;
+    }
+
+    let mut found_tablet = false;
+    for handle in handles {
+        let usb = bt
+            .handle_protocol::<UsbIo>(handle)
+            .expect_success("Failed to open USB I/O protocol");
+        let usb = unsafe { &mut *usb.get() };
+
+        let device = usb
+            .get_device_descriptor()
+            .expect_success("Failed to get device descriptor");
+        let interface = usb
+            .get_interface_descriptor()
+            .expect_success("Failed to get interface descriptor");
+        info!(
+            "USB device {:04x}:{:04x}, interface {} of class {:02x}",
+            device.id_vendor,
+            device.id_product,
+            interface.interface_number,
+            interface.interface_class
+        );
+        if (device.id_vendor, device.id_product) != QEMU_TABLET {
+            continue;
+        }
+        found_tablet = true;
+
+        // The descriptor read through a control transfer must match the one
+        // cached by the firmware.
+        let mut raw = [0; 18];
+        usb.control_transfer(
+            &DeviceRequest::get_descriptor(DescriptorType::DEVICE, 0, 0),
+            DataStage::In(&mut raw),
+            1000,
+        )
+        .expect_success("Failed to read device descriptor");
+        assert_eq!(raw[0], 18);
+        assert_eq!(raw[1], DescriptorType::DEVICE.0);
+        assert_eq!(u16::from_le_bytes([raw[8], raw[9]]), device.id_vendor);
+        assert_eq!(u16::from_le_bytes([raw[10], raw[11]]), device.id_product);
+
+        let config = usb
+            .get_config_descriptor()
+            .expect_success("Failed to get configuration descriptor");
+        assert!(config.num_interfaces >= 1);
+        assert_eq!(interface.num_endpoints, 1);
+        let endpoint = usb
+            .get_endpoint_descriptor(0)
+            .expect_success("Failed to get endpoint descriptor");
+        assert_eq!(endpoint.endpoint_address & 0x80, 0x80, "Endpoint is not IN");
+        assert_eq!(endpoint.transfer_type(), TransferType::INTERRUPT);
+        assert!(usb.get_endpoint_descriptor(1).is_err());
+        test_interrupt_transfers(
+            bt,
+            usb,
+            endpoint.endpoint_address,
+            endpoint.max_packet_size.into(),
+        );
+
+        let languages = usb
+            .get_supported_languages()
+            .expect_success("Failed to get supported languages");
+        let lang_id = *languages.first().expect("Tablet has no languages");
+        let product = usb
+            .get_string_descriptor(bt, lang_id, device.str_product)
+            .expect_success("Failed to get product string");
+        info!("Product: {}", product);
+        assert_eq!(product.to_string(), "QEMU USB Tablet");
+        let manufacturer = usb
+            .get_string_descriptor(bt, lang_id, device.str_manufacturer)
+            .expect_success("Failed to get manufacturer string");
+        assert_eq!(manufacturer.to_string(), "QEMU");
+    }
+    assert!(found_tablet, "QEMU USB tablet not found");
+}
+
+fn test_interrupt_transfers(bt: &BootServices, usb: &mut UsbIo, endpoint: u8, packet_size: usize) {
+    // Transfers must match the direction and type of an endpoint.
+    let mut buffer = [0; 64];
+    let packet = &mut buffer[..packet_size.min(64)];
+    let err = usb
+        .sync_interrupt_transfer(endpoint & 0x7f, DataStage::Out(packet), 10)
+        .expect_err("Transfer in the wrong direction succeeded");
+    assert_eq!(err.status(), Status::INVALID_PARAMETER);
+    let err = usb
+        .bulk_transfer(endpoint, DataStage::In(packet), 10)
+        .expect_err("Bulk transfer on an interrupt endpoint succeeded");
+    assert_eq!(err.status(), Status::INVALID_PARAMETER);
+
+    // The tablet only reports when the pointer moves, so the transfer
+    // usually times out.
+    match usb.sync_interrupt_transfer(endpoint, DataStage::In(packet), 10) {
+        Ok(len) => info!("Received {} bytes from the tablet", len.unwrap()),
+        Err(err) => info!(
+            "Interrupt transfer failed: {:?} ({:?})",
+            err.status(),
+            err.data()
+        ),
+    }
+
+    static REPORTS: AtomicUsize = AtomicUsize::new(0);
+    let transfer = usb
+        .async_interrupt_transfer(endpoint, 10, packet_size, |data, _status| {
+            if !data.is_empty() {
+                REPORTS.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .expect_success("Failed to start asynchronous interrupt transfer");
+    bt.stall(200_000);
+    transfer
+        .cancel()
+        .expect_success("Failed to cancel asynchronous interrupt transfer");
+    info!(
+        "Received {} reports while polling the tablet",
+        REPORTS.load(Ordering::Relaxed)
+    );
+}
@@ -0,0 +1,57 @@
+use core::convert::TryFrom;
+use uefi::prelude::*;
+use uefi::proto::variable_policy::{LockPolicy, PolicyBuilder, VariablePolicy};
+use uefi::table::boot::BootServices;
+use uefi::table::runtime::{RuntimeServices, VariableAttributes};
+use uefi::{guid, CString16};
+
+pub fn test(bt: &BootServices, rt: &RuntimeServices) {
+    info!("Running variable policy protocol test");
+
+    let policy = if let Ok(policy) = bt.locate_protocol::<VariablePolicy>() {
+        policy.expect("Warnings encountered while opening variable policy protocol")
+    } else {
+        info!("Variable policy protocol is not supported");
+        return;
+    };
+    let policy = unsafe { &mut *policy.get() };
+
+    let enabled = policy
+        .is_variable_policy_enabled()
+        .expect_success("Failed to get variable policy state");
+    if !enabled {
+        warn!("Variable policies are disabled, skipping test");
+        return;
+    }
+
+    // Arbitrary GUID generated for this test.
+    let vendor = guid!("5e4a4f2c-7d2b-4b37-9a43-1d3f0e6c8b21");
+    let name = CString16::try_from("UefiRsPolicyTestVar").unwrap();
+    let attributes = VariableAttributes::BOOTSERVICE_ACCESS;
+
+    let mut buffer = [0; 128];
+    let entry = PolicyBuilder::new(vendor)
+        .name(&name)
+        .lock_policy(LockPolicy::LockOnCreate)
+        .build(&mut buffer)
+        .expect_success("Failed to build variable policy");
+    policy
+        .register_variable_policy(entry)
+        .expect_success("Failed to register variable policy");
+
+    let mut dump = [0; 4096];
+    let registered = policy
+        .dump_variable_policy(&mut dump)
+        .expect_success("Failed to dump variable policies")
+        .any(|entry| entry.namespace() == vendor && matches!(entry.name(), Some(n) if n == *name));
+    assert!(registered, "Registered policy is missing from the dump");
+
+    // The variable can be created once, and is then locked.
+    rt.set_variable(&name, &vendor, attributes, b"first")
+        .expect_success("Failed to create the test variable");
+    let status = rt
+        .set_variable(&name, &vendor, attributes, b"second")
+        .expect_err("Locked variable was modified")
+        .status();
+    assert_eq!(status, Status::WRITE_PROTECTED);
+}
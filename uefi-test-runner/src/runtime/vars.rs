@@ -1,59 +1,81 @@
-use alloc::vec::Vec;
+use core::convert::TryFrom;
 use log::info;
 use uefi::prelude::*;
-use uefi::table::runtime::VariableAttributes;
-use uefi::{CStr16, Guid};
-
-struct CString16(Vec<u16>);
-
-impl CString16 {
-    fn from_str(input: &str) -> CString16 {
-        let mut v: Vec<u16> = input.encode_utf16().collect();
-        v.push(0);
-        CString16(v)
-    }
-
-    fn as_cstr16(&self) -> &CStr16 {
-        match CStr16::from_u16_with_nul(&self.0) {
-            Ok(s) => s,
-            Err(_) => panic!("invalid string"),
-        }
-    }
-}
+use uefi::table::runtime::{VariableAttributes, GLOBAL_VARIABLE};
+use uefi::{guid, CString16};
 
 fn test_variables(rt: &RuntimeServices) {
-    let name = CString16::from_str("UefiRsTestVar");
+    let name = CString16::try_from("UefiRsTestVar").unwrap();
     let test_value = b"TestValue";
     let test_attrs = VariableAttributes::BOOTSERVICE_ACCESS | VariableAttributes::RUNTIME_ACCESS;
 
     // Arbitrary GUID generated for this test.
-    let vendor = Guid::from_values(
-        0x9baf21cf,
-        0xe187,
-        0x497e,
-        0xae77,
-        [0x5b, 0xd8, 0xb0, 0xe0, 0x97, 0x03],
-    );
+    let vendor = guid!("9baf21cf-e187-497e-ae77-5bd8b0e09703");
 
     info!("Testing set_variable");
-    rt.set_variable(name.as_cstr16(), &vendor, test_attrs, test_value)
+    rt.set_variable(&name, &vendor, test_attrs, test_value)
         .expect_success("failed to set variable");
 
     info!("Testing get_variable_size");
     let size = rt
-        .get_variable_size(name.as_cstr16(), &vendor)
+        .get_variable_size(&name, &vendor)
         .expect_success("failed to get variable size");
     assert_eq!(size, test_value.len());
 
     info!("Testing get_variable");
     let mut buf = [0u8; 9];
     let (data, attrs) = rt
-        .get_variable(name.as_cstr16(), &vendor, &mut buf)
+        .get_variable(&name, &vendor, &mut buf)
         .expect_success("failed to get variable");
     assert_eq!(data, test_value);
     assert_eq!(attrs, test_attrs);
+
+    info!("Testing read_variable");
+    let (data, attrs) = rt
+        .read_variable(&name, &vendor)
+        .expect_success("failed to read variable");
+    assert_eq!(data, test_value);
+    assert_eq!(attrs, test_attrs);
+
+    info!("Testing read_variable_str");
+    let (data, attrs) = rt
+        .read_variable_str("UefiRsTestVar", &vendor)
+        .expect_success("failed to read variable");
+    assert_eq!(data, test_value);
+    assert_eq!(attrs, test_attrs);
+    let error = rt
+        .read_variable_str("UefiRsTestVar\u{1f600}", &vendor)
+        .expect_error("read a variable whose name is not UCS-2");
+    assert_eq!(error.status(), Status::INVALID_PARAMETER);
+
+    info!("Testing variable_names");
+    let names = rt
+        .variable_names()
+        .expect_success("failed to list variables");
+    assert!(names.iter().any(|(n, v)| *n == name && *v == vendor));
+}
+
+fn test_variable_dump(rt: &RuntimeServices) {
+    info!("Dumping variables");
+    let variables = rt.variables().expect_success("failed to dump variables");
+    for variable in &variables {
+        info!(
+            "{} {}: {:?}, {} bytes",
+            variable.vendor, variable.name, variable.attributes, variable.size
+        );
+    }
+
+    let boot_order = variables
+        .iter()
+        .find(|variable| variable.name == "BootOrder" && variable.vendor == GLOBAL_VARIABLE)
+        .expect("BootOrder variable is missing");
+    assert!(boot_order
+        .attributes
+        .contains(VariableAttributes::NON_VOLATILE));
+    assert!(boot_order.size > 0 && boot_order.size % 2 == 0);
 }
 
 pub fn test(rt: &RuntimeServices) {
     test_variables(rt);
+    test_variable_dump(rt);
 }